@@ -64,6 +64,7 @@ pub fn new_partial(config: &Configuration) -> Result<sc_service::PartialComponen
 		&task_manager.spawn_handle(),
 		config.prometheus_registry(),
 		sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone()),
+		None,
 	)?;
 
 	Ok(sc_service::PartialComponents {
@@ -100,7 +101,7 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 	if config.offchain_worker.enabled {
 		sc_service::build_offchain_workers(
 			&config, backend.clone(), task_manager.spawn_handle(), client.clone(), network.clone(),
-		);
+		)?;
 	}
 
 	let role = config.role.clone();
@@ -159,6 +160,8 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 			force_authoring,
 			keystore.clone(),
 			can_author_with,
+			None,
+			prometheus_registry.as_ref(),
 		)?;
 
 		// the AURA authoring task is considered essential, i.e. if it
@@ -180,6 +183,7 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 		justification_period: 512,
 		name: Some(name),
 		observer_enabled: false,
+		gossip_limits: Default::default(),
 		keystore,
 		is_authority: role.is_network_authority(),
 	};
@@ -251,6 +255,7 @@ pub fn new_light(config: Configuration) -> Result<TaskManager, ServiceError> {
 		&task_manager.spawn_handle(),
 		config.prometheus_registry(),
 		sp_consensus::NeverCanAuthor,
+		None,
 	)?;
 
 	let finality_proof_provider =
@@ -272,7 +277,7 @@ pub fn new_light(config: Configuration) -> Result<TaskManager, ServiceError> {
 	if config.offchain_worker.enabled {
 		sc_service::build_offchain_workers(
 			&config, backend.clone(), task_manager.spawn_handle(), client.clone(), network.clone(),
-		);
+		)?;
 	}
 
 	sc_service::spawn_tasks(sc_service::SpawnTasksParams {
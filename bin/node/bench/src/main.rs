@@ -23,6 +23,7 @@ mod import;
 mod generator;
 mod simple_trie;
 mod state_sizes;
+mod sync;
 mod tempdb;
 mod trie;
 mod txpool;
@@ -39,6 +40,7 @@ use crate::{
 	trie::{TrieReadBenchmarkDescription, TrieWriteBenchmarkDescription, DatabaseSize},
 	construct::ConstructionBenchmarkDescription,
 	txpool::PoolBenchmarkDescription,
+	sync::MajorSyncBenchmarkDescription,
 };
 
 #[derive(Debug, StructOpt)]
@@ -151,6 +153,8 @@ fn main() {
 			database_type: BenchDataBaseType::RocksDb,
 		},
 		PoolBenchmarkDescription { database_type: BenchDataBaseType::RocksDb },
+		sources in [1, 4].iter() =>
+			MajorSyncBenchmarkDescription { sources: *sources, blocks: 2048 },
 	);
 
 	if opt.list {
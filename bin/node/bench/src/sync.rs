@@ -0,0 +1,98 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Major sync benchmark.
+//!
+//! The goal of this benchmark is to figure out the time needed by a fresh node to
+//! download and import a chain from one or several peers. All nodes are connected
+//! through an in-memory transport, so the result measures the sync logic and block
+//! import rather than the network.
+
+use std::borrow::Cow;
+
+use sc_network_test::{TestNet, TestNetFactory};
+
+use crate::core::{self, Path, Mode};
+
+pub struct MajorSyncBenchmarkDescription {
+	/// Number of peers that serve the chain.
+	pub sources: usize,
+	/// Length of the chain to sync.
+	pub blocks: usize,
+}
+
+pub struct MajorSyncBenchmark {
+	net: TestNet,
+	blocks: usize,
+}
+
+impl core::BenchmarkDescription for MajorSyncBenchmarkDescription {
+	fn path(&self) -> Path {
+		let mut path = Path::new(&["node", "sync"]);
+		path.push(&format!("{}", self.sources));
+		path.push(&format!("{}", self.blocks));
+		path
+	}
+
+	fn setup(self: Box<Self>) -> Box<dyn core::Benchmark> {
+		let mut net = TestNet::new(self.sources);
+		net.peer(0).push_blocks(self.blocks, false);
+		net.block_until_sync();
+
+		Box::new(MajorSyncBenchmark {
+			net,
+			blocks: self.blocks,
+		})
+	}
+
+	fn name(&self) -> Cow<'static, str> {
+		format!(
+			"Major sync of {} blocks from {} peer{}",
+			self.blocks,
+			self.sources,
+			if self.sources == 1 { "" } else { "s" },
+		).into()
+	}
+}
+
+impl core::Benchmark for MajorSyncBenchmark {
+	fn run(&mut self, mode: Mode) -> std::time::Duration {
+		if mode == Mode::Profile {
+			std::thread::park_timeout(std::time::Duration::from_secs(3));
+		}
+
+		let start = std::time::Instant::now();
+		self.net.add_full_peer();
+		self.net.block_until_sync();
+		let elapsed = start.elapsed();
+
+		let syncing = self.net.peers().len() - 1;
+		assert_eq!(
+			self.net.peer(syncing).client().info().best_number as usize,
+			self.blocks,
+			"Syncing peer should have imported the whole chain",
+		);
+		// Drop the synced node so that every run starts from the same set of peers.
+		self.net.mut_peers(|peers| { peers.pop(); });
+
+		if mode == Mode::Profile {
+			std::thread::park_timeout(std::time::Duration::from_secs(1));
+		}
+		elapsed
+	}
+}
@@ -56,6 +56,9 @@ pub struct Extensions {
 	pub fork_blocks: sc_client_api::ForkBlocks<Block>,
 	/// Known bad block hashes.
 	pub bad_blocks: sc_client_api::BadBlocks<Block>,
+	/// Limits enforced on the runtime code.
+	#[serde(default)]
+	pub execution_limits: sc_service::ExecutionLimits,
 }
 
 /// Specialized `ChainSpec`.
@@ -34,6 +34,7 @@ use std::sync::Arc;
 
 use jsonrpc_pubsub::manager::SubscriptionManager;
 use node_primitives::{Block, BlockNumber, AccountId, Index, Balance, Hash};
+use sc_authority_discovery_rpc::AuthorityDiscoveryRpcHandler;
 use sc_consensus_babe::{Config, Epoch};
 use sc_consensus_babe_rpc::BabeRpcHandler;
 use sc_consensus_epochs::SharedEpochChanges;
@@ -96,6 +97,8 @@ pub struct FullDeps<C, P, SC> {
 	pub babe: BabeDeps,
 	/// GRANDPA specific dependencies.
 	pub grandpa: GrandpaDeps,
+	/// Authority discovery service, if the node runs authority discovery.
+	pub authority_discovery: Option<sc_authority_discovery::Service>,
 }
 
 /// A IO handler that uses all Full RPC extensions.
@@ -128,6 +131,7 @@ pub fn create_full<C, P, SC>(
 		deny_unsafe,
 		babe,
 		grandpa,
+		authority_discovery,
 	} = deps;
 
 	let BabeDeps {
@@ -176,6 +180,13 @@ pub fn create_full<C, P, SC>(
 			)
 		)
 	);
+	if let Some(authority_discovery) = authority_discovery {
+		io.extend_with(
+			sc_authority_discovery_rpc::AuthorityDiscoveryApi::to_delegate(
+				AuthorityDiscoveryRpcHandler::new(authority_discovery),
+			)
+		);
+	}
 
 	io
 }
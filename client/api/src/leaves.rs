@@ -55,6 +55,11 @@ impl<H, N: Ord> FinalizationDisplaced<H, N> {
 		// one transaction, then there will be no overlap in the keys.
 		self.leaves.append(&mut other.leaves);
 	}
+
+	/// Iterate over all displaced leaves.
+	pub fn leaves(&self) -> impl IntoIterator<Item=&H> {
+		self.leaves.values().flatten()
+	}
 }
 
 /// list of leaf hashes ordered by number (descending).
@@ -198,6 +203,25 @@ impl<H, N> LeafSet<H, N> where
 		self.storage.iter().flat_map(|(_, hashes)| hashes.iter()).cloned().collect()
 	}
 
+	/// returns all leaves along with their block number,
+	/// ordered by their block number descending.
+	pub fn items(&self) -> Vec<(H, N)> {
+		self.storage.iter()
+			.flat_map(|(number, hashes)| hashes.iter().map(move |h| (h.clone(), number.0.clone())))
+			.collect()
+	}
+
+	/// Remove a single leaf, e.g. because its block is missing from the database.
+	///
+	/// Returns `false` if the leaf wasn't in the set.
+	pub fn remove(&mut self, hash: H, number: N) -> bool {
+		let removed = self.remove_leaf(&Reverse(number), &hash);
+		if removed {
+			self.pending_removed.push(hash);
+		}
+		removed
+	}
+
 	/// Number of known leaves
 	pub fn count(&self) -> usize {
 		self.storage.len()
@@ -299,6 +323,22 @@ mod tests {
 		assert!(set.contains(2, 2_2));
 	}
 
+	#[test]
+	fn items_and_remove() {
+		let mut set = LeafSet::new();
+		set.import(0u32, 0u32, 0u32);
+		set.import(1_1, 1, 0);
+		set.import(1_2, 1, 0);
+		set.import(2_1, 2, 1_1);
+
+		assert_eq!(set.items(), vec![(2_1, 2), (1_2, 1)]);
+
+		assert!(set.remove(1_2, 1));
+		assert!(!set.remove(1_2, 1));
+		assert!(!set.remove(2_1, 3));
+		assert_eq!(set.items(), vec![(2_1, 2)]);
+	}
+
 	#[test]
 	fn flush_to_disk() {
 		const PREFIX: &[u8] = b"abcdefg";
@@ -0,0 +1,139 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! RPC api for authority discovery.
+
+use futures::{future::BoxFuture, FutureExt as _, TryFutureExt as _};
+use jsonrpc_core::{Error as RpcError, futures::future as rpc_future};
+use jsonrpc_derive::rpc;
+use sc_authority_discovery::Service;
+use sc_network::Multiaddr;
+use sp_authority_discovery::AuthorityId;
+
+type FutureResult<T> = Box<dyn rpc_future::Future<Item = T, Error = RpcError> + Send>;
+
+/// Provides rpc methods for interacting with authority discovery.
+#[rpc]
+pub trait AuthorityDiscoveryApi {
+	/// Returns the addresses of the given authority, as resolved from the DHT by
+	/// the local authority discovery worker, or `None` if no addresses are known.
+	#[rpc(name = "authorityDiscovery_addresses")]
+	fn addresses(&self, authority_id: AuthorityId) -> FutureResult<Option<Vec<String>>>;
+}
+
+/// Lookup of the addresses of an authority, implemented by the authority discovery
+/// [`Service`].
+pub trait AuthorityAddresses: Send + Sync + 'static {
+	/// Returns the known addresses of the given authority, if any.
+	fn addresses(&self, authority_id: AuthorityId) -> BoxFuture<'static, Option<Vec<Multiaddr>>>;
+}
+
+impl AuthorityAddresses for Service {
+	fn addresses(&self, authority_id: AuthorityId) -> BoxFuture<'static, Option<Vec<Multiaddr>>> {
+		let mut service = self.clone();
+		async move { service.get_addresses_by_authority_id(authority_id).await }.boxed()
+	}
+}
+
+/// Implements the [`AuthorityDiscoveryApi`] RPC trait for interacting with
+/// authority discovery.
+pub struct AuthorityDiscoveryRpcHandler<S = Service> {
+	service: S,
+}
+
+impl<S> AuthorityDiscoveryRpcHandler<S> {
+	/// Creates a new instance of the authority discovery RPC handler.
+	pub fn new(service: S) -> Self {
+		Self { service }
+	}
+}
+
+impl<S: AuthorityAddresses> AuthorityDiscoveryApi for AuthorityDiscoveryRpcHandler<S> {
+	fn addresses(&self, authority_id: AuthorityId) -> FutureResult<Option<Vec<String>>> {
+		let future = self.service.addresses(authority_id).map(|addresses| {
+			Ok::<_, RpcError>(addresses.map(|addresses| {
+				addresses.into_iter().map(|address| address.to_string()).collect()
+			}))
+		});
+
+		Box::new(future.boxed().compat())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use jsonrpc_core::IoHandler;
+	use sp_core::crypto::Ss58Codec;
+	use sp_keyring::Sr25519Keyring;
+	use std::collections::HashMap;
+
+	struct TestAddresses(HashMap<AuthorityId, Vec<Multiaddr>>);
+
+	impl AuthorityAddresses for TestAddresses {
+		fn addresses(&self, authority_id: AuthorityId) -> BoxFuture<'static, Option<Vec<Multiaddr>>> {
+			futures::future::ready(self.0.get(&authority_id).cloned()).boxed()
+		}
+	}
+
+	fn authority_id(keyring: Sr25519Keyring) -> AuthorityId {
+		keyring.public().into()
+	}
+
+	fn setup_io_handler() -> IoHandler {
+		let address: Multiaddr = "/ip4/127.0.0.1/tcp/30333".parse().unwrap();
+		let addresses = vec![(authority_id(Sr25519Keyring::Alice), vec![address])]
+			.into_iter()
+			.collect();
+
+		let handler = AuthorityDiscoveryRpcHandler::new(TestAddresses(addresses));
+		let mut io = IoHandler::new();
+		io.extend_with(AuthorityDiscoveryApi::to_delegate(handler));
+		io
+	}
+
+	fn addresses_request(keyring: Sr25519Keyring) -> String {
+		format!(
+			r#"{{"jsonrpc":"2.0","method":"authorityDiscovery_addresses","params":["{}"],"id":1}}"#,
+			authority_id(keyring).to_ss58check(),
+		)
+	}
+
+	#[test]
+	fn returns_addresses_of_known_authority() {
+		let io = setup_io_handler();
+
+		let response = r#"{"jsonrpc":"2.0","result":["/ip4/127.0.0.1/tcp/30333"],"id":1}"#;
+		assert_eq!(
+			Some(response.into()),
+			io.handle_request_sync(&addresses_request(Sr25519Keyring::Alice)),
+		);
+	}
+
+	#[test]
+	fn returns_null_for_unknown_authority() {
+		let io = setup_io_handler();
+
+		let response = r#"{"jsonrpc":"2.0","result":null,"id":1}"#;
+		assert_eq!(
+			Some(response.into()),
+			io.handle_request_sync(&addresses_request(Sr25519Keyring::Bob)),
+		);
+	}
+}
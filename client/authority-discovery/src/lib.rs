@@ -24,7 +24,7 @@
 //!
 //! See [`Worker`] and [`Service`] for more documentation.
 
-pub use crate::{service::Service, worker::{NetworkProvider, Worker, Role}};
+pub use crate::{service::Service, worker::{NetworkProvider, Worker, WorkerConfig, Role}};
 
 use std::pin::Pin;
 use std::sync::Arc;
@@ -45,6 +45,8 @@ mod tests;
 mod worker;
 
 /// Create a new authority discovery [`Worker`] and [`Service`].
+///
+/// See [`new_worker_and_service_with_config`] to configure the [`Worker`] beyond its sentry nodes.
 pub fn new_worker_and_service<Client, Network, Block>(
 	client: Arc<Client>,
 	network: Arc<Network>,
@@ -53,6 +55,31 @@ pub fn new_worker_and_service<Client, Network, Block>(
 	role: Role,
 	prometheus_registry: Option<prometheus_endpoint::Registry>,
 ) -> (Worker<Client, Network, Block>, Service)
+where
+	Block: BlockT + Unpin + 'static,
+	Network: NetworkProvider,
+	Client: ProvideRuntimeApi<Block> + Send + Sync + 'static + HeaderBackend<Block>,
+	<Client as ProvideRuntimeApi<Block>>::Api: AuthorityDiscoveryApi<Block, Error = sp_blockchain::Error>,
+{
+	new_worker_and_service_with_config(
+		WorkerConfig { sentry_nodes, ..Default::default() },
+		client,
+		network,
+		dht_event_rx,
+		role,
+		prometheus_registry,
+	)
+}
+
+/// Same as [`new_worker_and_service`] but with a given [`WorkerConfig`].
+pub fn new_worker_and_service_with_config<Client, Network, Block>(
+	config: WorkerConfig,
+	client: Arc<Client>,
+	network: Arc<Network>,
+	dht_event_rx: Pin<Box<dyn Stream<Item = DhtEvent> + Send>>,
+	role: Role,
+	prometheus_registry: Option<prometheus_endpoint::Registry>,
+) -> (Worker<Client, Network, Block>, Service)
 where
 	Block: BlockT + Unpin + 'static,
 	Network: NetworkProvider,
@@ -62,7 +89,7 @@ where
 	let (to_worker, from_service) = mpsc::channel(0);
 
 	let worker = Worker::new(
-		from_service, client, network, sentry_nodes, dht_event_rx, role, prometheus_registry,
+		from_service, client, network, config, dht_event_rx, role, prometheus_registry,
 	);
 	let service = Service::new(to_worker);
 
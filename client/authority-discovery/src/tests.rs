@@ -49,12 +49,13 @@ fn get_addresses_and_authority_id() {
 
 	let test_api = Arc::new(TestApi {
 		authorities: vec![],
+		next_authorities: vec![],
 	});
 
 	let (mut worker, mut service) = new_worker_and_service(
 		test_api,
 		network.clone(),
-		vec![],
+		Default::default(),
 		dht_event_rx.boxed(),
 		Role::Authority(key_store),
 		None,
@@ -31,8 +31,10 @@ use futures_timer::Delay;
 use addr_cache::AddrCache;
 use codec::Decode;
 use libp2p::core::multiaddr;
-use log::{debug, error, log_enabled};
-use prometheus_endpoint::{Counter, CounterVec, Gauge, Opts, U64, register};
+use log::{debug, error, log_enabled, warn};
+use prometheus_endpoint::{
+	Counter, CounterVec, Gauge, Histogram, HistogramOpts, Opts, U64, register,
+};
 use prost::Message;
 use sc_client_api::blockchain::HeaderBackend;
 use sc_network::{
@@ -44,7 +46,7 @@ use sc_network::{
 	PeerId,
 };
 use sp_authority_discovery::{AuthorityDiscoveryApi, AuthorityId, AuthoritySignature, AuthorityPair};
-use sp_core::crypto::{key_types, Pair};
+use sp_core::crypto::{key_types, KeyTypeId, Pair};
 use sp_core::traits::BareCryptoStorePtr;
 use sp_runtime::{traits::Block as BlockT, generic::BlockId};
 use sp_api::ProvideRuntimeApi;
@@ -66,6 +68,44 @@ const LIBP2P_KADEMLIA_BOOTSTRAP_TIME: Duration = Duration::from_secs(30);
 /// discovery module.
 const AUTHORITIES_PRIORITY_GROUP_NAME: &'static str = "authorities";
 
+/// Interval on which to query for addresses of other authorities.
+const QUERY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Age after which a cached record is considered stale, i.e. it has not been refreshed by the last
+/// three queries.
+const STALE_RECORD_AGE: Duration = Duration::from_secs(3 * 10 * 60);
+
+/// Configuration of a [`Worker`].
+#[derive(Clone, Debug)]
+pub struct WorkerConfig {
+	/// Key type of the session keys addresses are signed with and resolved for.
+	///
+	/// The runtime is expected to expose sr25519 public keys of this key type through the
+	/// [`AuthorityDiscoveryApi`].
+	pub key_type: KeyTypeId,
+	/// Public addresses of the sentry nodes guarding this node.
+	///
+	/// Only applicable to [`Role::Authority`]. If not empty, the addresses of the sentry nodes are
+	/// published instead of the node's own external addresses.
+	pub sentry_nodes: Vec<MultiaddrWithPeerId>,
+	/// Whether to publish and resolve addresses for the authority set of the next session in
+	/// addition to the current one.
+	///
+	/// Requires version 2 of the [`AuthorityDiscoveryApi`], only the current authority set is used
+	/// with older runtimes.
+	pub next_session_authorities: bool,
+}
+
+impl Default for WorkerConfig {
+	fn default() -> Self {
+		WorkerConfig {
+			key_type: key_types::AUTHORITY_DISCOVERY,
+			sentry_nodes: Vec::new(),
+			next_session_authorities: true,
+		}
+	}
+}
+
 /// Role an authority discovery module can run as.
 pub enum Role {
 	/// Actual authority as well as a reference to its key store.
@@ -95,7 +135,7 @@ pub enum Role {
 ///
 /// 2. **Discovers other authorities**
 ///
-///    1. Retrieves the current set of authorities.
+///    1. Retrieves the current and, if enabled, the next set of authorities.
 ///
 ///    2. Starts DHT queries for the ids of the authorities.
 ///
@@ -145,6 +185,10 @@ where
 
 	role: Role,
 
+	key_type: KeyTypeId,
+
+	next_session_authorities: bool,
+
 	phantom: PhantomData<Block>,
 }
 
@@ -159,13 +203,13 @@ where
 {
 	/// Return a new [`Worker`].
 	///
-	/// Note: When specifying `sentry_nodes` this module will not advertise the public addresses of
-	/// the node itself but only the public addresses of its sentry nodes.
+	/// Note: When specifying [`WorkerConfig::sentry_nodes`] this module will not advertise the
+	/// public addresses of the node itself but only the public addresses of its sentry nodes.
 	pub(crate) fn new(
 		from_service: mpsc::Receiver<ServicetoWorkerMsg>,
 		client: Arc<Client>,
 		network: Arc<Network>,
-		sentry_nodes: Vec<MultiaddrWithPeerId>,
+		config: WorkerConfig,
 		dht_event_rx: Pin<Box<dyn Stream<Item = DhtEvent> + Send>>,
 		role: Role,
 		prometheus_registry: Option<prometheus_endpoint::Registry>,
@@ -183,8 +227,7 @@ where
 		// interval on which to query for external addresses of other authorities is a trade off
 		// between efficiency and performance.
 		let query_interval_start = Instant::now() + LIBP2P_KADEMLIA_BOOTSTRAP_TIME;
-		let query_interval_duration = Duration::from_secs(10 * 60);
-		let query_interval = interval_at(query_interval_start, query_interval_duration);
+		let query_interval = interval_at(query_interval_start, QUERY_INTERVAL);
 
 		// Querying 500 [`AuthorityId`]s takes ~1m on the Kusama DHT (10th of August 2020) when
 		// comparing `authority_discovery_authority_addresses_requested_total` and
@@ -192,13 +235,28 @@ where
 		// group on the same interval as the [`query_interval`] above, just delayed by 2 minutes.
 		let priority_group_set_interval = interval_at(
 			query_interval_start + Duration::from_secs(2 * 60),
-			query_interval_duration,
+			QUERY_INTERVAL,
 		);
 
-		let sentry_nodes = if !sentry_nodes.is_empty() {
-			Some(sentry_nodes.into_iter().map(|ma| ma.concat()).collect::<Vec<_>>())
-		} else {
-			None
+		let WorkerConfig { key_type, sentry_nodes, next_session_authorities } = config;
+
+		let sentry_nodes = match &role {
+			Role::Authority(_) if !sentry_nodes.is_empty() => {
+				Some(sentry_nodes.into_iter().map(|ma| ma.concat()).collect::<Vec<_>>())
+			},
+			Role::Authority(_) => None,
+			Role::Sentry => {
+				// Sentry nodes don't publish any addresses, thus there is nothing to replace
+				// their own addresses with.
+				if !sentry_nodes.is_empty() {
+					warn!(
+						target: LOG_TARGET,
+						"Ignoring {} sentry node addresses configured for a sentry node.",
+						sentry_nodes.len(),
+					);
+				}
+				None
+			},
 		};
 
 		let addr_cache = AddrCache::new();
@@ -227,6 +285,8 @@ where
 			priority_group_set_interval,
 			addr_cache,
 			role,
+			key_type,
+			next_session_authorities,
 			metrics,
 			phantom: PhantomData,
 		}
@@ -271,11 +331,13 @@ where
 		let keys = Worker::get_own_public_keys_within_authority_set(
 			&key_store,
 			&self.client,
+			self.key_type,
+			self.next_session_authorities,
 		)?.into_iter().map(Into::into).collect::<Vec<_>>();
 
 		let signatures = key_store.read()
 			.sign_with_all(
-				key_types::AUTHORITY_DISCOVERY,
+				self.key_type,
 				keys.clone(),
 				serialized_addresses.as_slice(),
 			)
@@ -305,18 +367,14 @@ where
 	}
 
 	fn request_addresses_of_others(&mut self) -> Result<()> {
-		let id = BlockId::hash(self.client.info().best_hash);
+		let authorities = Worker::authorities(&self.client, self.next_session_authorities)?;
 
-		let authorities = self
-			.client
-			.runtime_api()
-			.authorities(&id)
-			.map_err(Error::CallingRuntime)?;
+		self.update_record_freshness_metrics();
 
 		let local_keys = match &self.role {
 			Role::Authority(key_store) => {
 				key_store.read()
-					.sr25519_public_keys(key_types::AUTHORITY_DISCOVERY)
+					.sr25519_public_keys(self.key_type)
 					.into_iter()
 					.collect::<HashSet<_>>()
 			},
@@ -425,11 +483,10 @@ where
 		})?.ok_or(Error::ReceivingDhtValueFoundEventWithNoRecords)?;
 
 		let authorities = {
-			let block_id = BlockId::hash(self.client.info().best_hash);
 			// From the Dht we only get the hashed authority id. In order to retrieve the actual
 			// authority id and to ensure it is actually an authority, we match the hash against the
 			// hash of the authority id of all other authorities.
-			let authorities = self.client.runtime_api().authorities(&block_id)?;
+			let authorities = Worker::authorities(&self.client, self.next_session_authorities)?;
 			self.addr_cache.retain_ids(&authorities);
 			authorities
 				.into_iter()
@@ -437,7 +494,7 @@ where
 				.collect::<HashMap<_, _>>()
 		};
 
-		// Check if the event origins from an authority in the current authority set.
+		// Check if the event origins from an authority in the current or next authority set.
 		let authority_id: &AuthorityId = authorities
 			.get(&remote_key)
 			.ok_or(Error::MatchingHashedAuthorityIdWithAuthorityId)?;
@@ -492,36 +549,86 @@ where
 			.collect();
 
 		if !remote_addresses.is_empty() {
-			self.addr_cache.insert(authority_id.clone(), remote_addresses);
+			let previous_record_age = self.addr_cache.insert(authority_id.clone(), remote_addresses);
 			if let Some(metrics) = &self.metrics {
 				metrics.known_authorities_count.set(
 					self.addr_cache.num_ids().try_into().unwrap_or(std::u64::MAX)
 				);
+				if let Some(age) = previous_record_age {
+					metrics.record_refresh_interval.observe(age.as_secs_f64());
+				}
 			}
 		}
 
 		Ok(())
 	}
 
-	/// Retrieve our public keys within the current authority set.
+	/// Retrieve the authorities to publish and resolve addresses for, i.e. the current authority
+	/// set and, if enabled and supported by the runtime, the authority set of the next session.
+	fn authorities(client: &Client, next_session_authorities: bool) -> Result<Vec<AuthorityId>> {
+		let id = BlockId::hash(client.info().best_hash);
+		let runtime_api = client.runtime_api();
+
+		let mut authorities = runtime_api
+			.authorities(&id)
+			.map_err(Error::CallingRuntime)?;
+
+		if !next_session_authorities {
+			return Ok(authorities);
+		}
+
+		let has_next_authorities = runtime_api
+			.has_api_with::<dyn AuthorityDiscoveryApi<Block, Error = sp_blockchain::Error>, _>(
+				&id,
+				|version| version >= 2,
+			)
+			.map_err(Error::CallingRuntime)?;
+
+		if has_next_authorities {
+			let next_authorities = runtime_api
+				.next_authorities(&id)
+				.map_err(Error::CallingRuntime)?;
+
+			for authority in next_authorities {
+				if !authorities.contains(&authority) {
+					authorities.push(authority);
+				}
+			}
+		}
+
+		Ok(authorities)
+	}
+
+	/// Update the metrics tracking how fresh the cached DHT records are.
+	fn update_record_freshness_metrics(&self) {
+		if let Some(metrics) = &self.metrics {
+			metrics.oldest_record_age.set(
+				self.addr_cache.oldest_record_age().map(|age| age.as_secs()).unwrap_or(0)
+			);
+			metrics.stale_records_count.set(
+				self.addr_cache.num_stale_ids(STALE_RECORD_AGE).try_into().unwrap_or(std::u64::MAX)
+			);
+		}
+	}
+
+	/// Retrieve our public keys within the current (and if enabled the next) authority set.
 	//
 	// A node might have multiple authority discovery keys within its keystore, e.g. an old one and
 	// one for the upcoming session. In addition it could be participating in the current authority
 	// set with two keys. The function does not return all of the local authority discovery public
-	// keys, but only the ones intersecting with the current authority set.
+	// keys, but only the ones intersecting with the current or next authority set.
 	fn get_own_public_keys_within_authority_set(
 		key_store: &BareCryptoStorePtr,
 		client: &Client,
+		key_type: KeyTypeId,
+		next_session_authorities: bool,
 	) -> Result<HashSet<AuthorityId>> {
 		let local_pub_keys = key_store.read()
-			.sr25519_public_keys(key_types::AUTHORITY_DISCOVERY)
+			.sr25519_public_keys(key_type)
 			.into_iter()
 			.collect::<HashSet<_>>();
 
-		let id = BlockId::hash(client.info().best_hash);
-		let current_authorities = client.runtime_api()
-			.authorities(&id)
-			.map_err(Error::CallingRuntime)?
+		let current_authorities = Worker::authorities(client, next_session_authorities)?
 			.into_iter()
 			.map(std::convert::Into::into)
 			.collect::<HashSet<_>>();
@@ -707,6 +814,9 @@ pub(crate) struct Metrics {
 	handle_value_found_event_failure: Counter<U64>,
 	known_authorities_count: Gauge<U64>,
 	priority_group_size: Gauge<U64>,
+	record_refresh_interval: Histogram,
+	oldest_record_age: Gauge<U64>,
+	stale_records_count: Gauge<U64>,
 }
 
 impl Metrics {
@@ -766,6 +876,31 @@ impl Metrics {
 				)?,
 				registry,
 			)?,
+			record_refresh_interval: register(
+				Histogram::with_opts(
+					HistogramOpts::new(
+						"authority_discovery_record_refresh_interval_seconds",
+						"Time between two updates of the cached addresses of a single authority."
+					).buckets(vec![60.0, 300.0, 600.0, 1200.0, 1800.0, 3600.0, 3.0 * 3600.0, 12.0 * 3600.0]),
+				)?,
+				registry,
+			)?,
+			oldest_record_age: register(
+				Gauge::new(
+					"authority_discovery_oldest_record_age_seconds",
+					"Time since the least recently updated cached record of an authority was \
+					 received."
+				)?,
+				registry,
+			)?,
+			stale_records_count: register(
+				Gauge::new(
+					"authority_discovery_stale_records_count",
+					"Number of cached authority records that have not been refreshed by the last \
+					 three queries."
+				)?,
+				registry,
+			)?,
 		})
 	}
 }
@@ -17,6 +17,7 @@
 use libp2p::core::multiaddr::{Multiaddr, Protocol};
 use rand::seq::SliceRandom;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 use sp_authority_discovery::AuthorityId;
 use sc_network::PeerId;
@@ -30,6 +31,8 @@ const MAX_NUM_AUTHORITY_CONN: usize = 10;
 pub(super) struct AddrCache {
 	authority_id_to_addresses: HashMap<AuthorityId, Vec<Multiaddr>>,
 	peer_id_to_authority_id: HashMap<PeerId, AuthorityId>,
+	/// Time the addresses of each [`AuthorityId`] were last inserted.
+	authority_id_to_last_update: HashMap<AuthorityId, Instant>,
 }
 
 impl AddrCache {
@@ -37,14 +40,21 @@ impl AddrCache {
 		AddrCache {
 			authority_id_to_addresses: HashMap::new(),
 			peer_id_to_authority_id: HashMap::new(),
+			authority_id_to_last_update: HashMap::new(),
 		}
 	}
 
 	/// Inserts the given [`AuthorityId`] and [`Vec<Multiaddr>`] pair for future lookups by
 	/// [`AuthorityId`] or [`PeerId`].
-	pub fn insert(&mut self, authority_id: AuthorityId, mut addresses: Vec<Multiaddr>) {
+	///
+	/// Returns the age of the replaced entry, if any.
+	pub fn insert(
+		&mut self,
+		authority_id: AuthorityId,
+		mut addresses: Vec<Multiaddr>,
+	) -> Option<Duration> {
 		if addresses.is_empty() {
-			return;
+			return None;
 		}
 
 		// Insert into `self.peer_id_to_authority_id`.
@@ -57,7 +67,10 @@ impl AddrCache {
 
 		// Insert into `self.authority_id_to_addresses`.
 		addresses.sort_unstable_by(|a, b| a.as_ref().cmp(b.as_ref()));
-		self.authority_id_to_addresses.insert(authority_id, addresses);
+		self.authority_id_to_addresses.insert(authority_id.clone(), addresses);
+
+		self.authority_id_to_last_update.insert(authority_id, Instant::now())
+			.map(|last_update| last_update.elapsed())
 	}
 
 	/// Returns the number of authority IDs in the cache.
@@ -70,6 +83,20 @@ impl AddrCache {
 		self.authority_id_to_addresses.get(&authority_id)
 	}
 
+	/// Returns the time since the least recently updated entry was inserted.
+	pub fn oldest_record_age(&self) -> Option<Duration> {
+		self.authority_id_to_last_update.values()
+			.min()
+			.map(|last_update| last_update.elapsed())
+	}
+
+	/// Returns the number of authority IDs whose addresses have not been updated within `max_age`.
+	pub fn num_stale_ids(&self, max_age: Duration) -> usize {
+		self.authority_id_to_last_update.values()
+			.filter(|last_update| last_update.elapsed() > max_age)
+			.count()
+	}
+
 	/// Returns the [`AuthorityId`] for the given [`PeerId`].
 	pub fn get_authority_id_by_peer_id(&self, peer_id: &PeerId) -> Option<&AuthorityId> {
 		self.peer_id_to_authority_id.get(peer_id)
@@ -112,6 +139,7 @@ impl AddrCache {
 		for authority_id_to_remove in authority_ids_to_remove {
 			// Remove other entries from `self.authority_id_to_addresses`.
 			let addresses = self.authority_id_to_addresses.remove(&authority_id_to_remove);
+			self.authority_id_to_last_update.remove(&authority_id_to_remove);
 
 			// Remove other entries from `self.peer_id_to_authority_id`.
 			let peer_ids = addresses.iter()
@@ -230,4 +258,30 @@ mod tests {
 			.max_tests(10)
 			.quickcheck(property as fn(_, _, _) -> TestResult)
 	}
+
+	#[test]
+	fn tracks_record_freshness() {
+		let authority = AuthorityPair::from_seed_slice(&[1; 32]).unwrap().public();
+		let address = "/ip6/2001:db8:0:0:0:0:0:2/tcp/30333".parse::<Multiaddr>()
+			.unwrap()
+			.with(Protocol::P2p(PeerId::random().into()));
+
+		let mut cache = AddrCache::new();
+		assert_eq!(None, cache.oldest_record_age());
+
+		assert_eq!(None, cache.insert(authority.clone(), vec![address.clone()]));
+		assert!(cache.oldest_record_age().is_some());
+		assert_eq!(0, cache.num_stale_ids(Duration::from_secs(60)));
+
+		std::thread::sleep(Duration::from_millis(10));
+		assert_eq!(1, cache.num_stale_ids(Duration::from_millis(1)));
+
+		assert!(
+			cache.insert(authority.clone(), vec![address]).is_some(),
+			"Expect replacing an entry to return the age of the replaced entry.",
+		);
+
+		cache.retain_ids(&vec![]);
+		assert_eq!(None, cache.oldest_record_age());
+	}
 }
@@ -98,6 +98,7 @@ fn interval_at_with_initial_delay() {
 #[derive(Clone)]
 pub(crate) struct TestApi {
 	pub(crate) authorities: Vec<AuthorityId>,
+	pub(crate) next_authorities: Vec<AuthorityId>,
 }
 
 impl ProvideRuntimeApi<Block> for TestApi {
@@ -106,6 +107,7 @@ impl ProvideRuntimeApi<Block> for TestApi {
 	fn runtime_api<'a>(&'a self) -> ApiRef<'a, Self::Api> {
 		RuntimeApi {
 			authorities: self.authorities.clone(),
+			next_authorities: self.next_authorities.clone(),
 		}.into()
 	}
 }
@@ -154,6 +156,7 @@ impl<Block: BlockT> HeaderBackend<Block> for TestApi {
 
 pub(crate) struct RuntimeApi {
 	authorities: Vec<AuthorityId>,
+	next_authorities: Vec<AuthorityId>,
 }
 
 sp_api::mock_impl_runtime_apis! {
@@ -163,6 +166,10 @@ sp_api::mock_impl_runtime_apis! {
 		fn authorities(&self) -> Vec<AuthorityId> {
 			self.authorities.clone()
 		}
+
+		fn next_authorities(&self) -> Vec<AuthorityId> {
+			self.next_authorities.clone()
+		}
 	}
 }
 
@@ -223,6 +230,7 @@ fn new_registers_metrics() {
 	let key_store = KeyStore::new();
 	let test_api = Arc::new(TestApi {
 		authorities: vec![],
+		next_authorities: vec![],
 	});
 
 	let registry = prometheus_endpoint::Registry::new();
@@ -232,7 +240,7 @@ fn new_registers_metrics() {
 		from_service,
 		test_api,
 		network.clone(),
-		vec![],
+		Default::default(),
 		dht_event_rx.boxed(),
 		Role::Authority(key_store),
 		Some(registry.clone()),
@@ -252,6 +260,7 @@ fn request_addresses_of_others_triggers_dht_get_query() {
 
 	let test_api = Arc::new(TestApi {
 		authorities: vec![authority_1_key_pair.public(), authority_2_key_pair.public()],
+		next_authorities: vec![],
 	});
 
 	let network: Arc<TestNetwork> = Arc::new(Default::default());
@@ -263,7 +272,7 @@ fn request_addresses_of_others_triggers_dht_get_query() {
 		from_service,
 		test_api,
 		network.clone(),
-		vec![],
+		Default::default(),
 		dht_event_rx.boxed(),
 		Role::Authority(key_store),
 		None,
@@ -275,6 +284,78 @@ fn request_addresses_of_others_triggers_dht_get_query() {
 	assert_eq!(network.get_value_call.lock().unwrap().len(), 2);
 }
 
+#[test]
+fn request_addresses_of_next_authorities() {
+	let authority_1_key_pair = AuthorityPair::from_seed_slice(&[1; 32]).unwrap();
+	let authority_2_key_pair = AuthorityPair::from_seed_slice(&[2; 32]).unwrap();
+
+	let test_api = Arc::new(TestApi {
+		authorities: vec![authority_1_key_pair.public()],
+		// Authority 1 stays in the next authority set, thus is only queried once.
+		next_authorities: vec![authority_1_key_pair.public(), authority_2_key_pair.public()],
+	});
+
+	let request_count = |next_session_authorities| {
+		let (_dht_event_tx, dht_event_rx) = channel(1000);
+		let network: Arc<TestNetwork> = Arc::new(Default::default());
+
+		let (_to_worker, from_service) = mpsc::channel(0);
+		let mut worker = Worker::new(
+			from_service,
+			test_api.clone(),
+			network.clone(),
+			WorkerConfig { next_session_authorities, ..Default::default() },
+			dht_event_rx.boxed(),
+			Role::Authority(KeyStore::new()),
+			None,
+		);
+
+		worker.request_addresses_of_others().unwrap();
+
+		let count = network.get_value_call.lock().unwrap().len();
+		count
+	};
+
+	assert_eq!(request_count(true), 2);
+	assert_eq!(request_count(false), 1);
+}
+
+#[test]
+fn publish_addresses_with_next_session_key_of_custom_key_type() {
+	let key_type = KeyTypeId(*b"test");
+
+	let (_dht_event_tx, dht_event_rx) = channel(1000);
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+
+	let key_store = KeyStore::new();
+	let public = key_store
+		.write()
+		.sr25519_generate_new(key_type, None)
+		.unwrap();
+	let test_api = Arc::new(TestApi {
+		authorities: vec![],
+		// The node only becomes an authority in the next session.
+		next_authorities: vec![public.into()],
+	});
+
+	let (_to_worker, from_service) = mpsc::channel(0);
+	let mut worker = Worker::new(
+		from_service,
+		test_api,
+		network.clone(),
+		WorkerConfig { key_type, ..Default::default() },
+		dht_event_rx.boxed(),
+		Role::Authority(key_store),
+		None,
+	);
+
+	worker.publish_ext_addresses().unwrap();
+
+	let put_value_call = network.put_value_call.lock().unwrap();
+	assert_eq!(put_value_call.len(), 1);
+	assert_eq!(put_value_call[0].0, hash_authority_id(&public.to_raw_vec()));
+}
+
 #[test]
 fn publish_discover_cycle() {
 	let _ = ::env_logger::try_init();
@@ -300,6 +381,7 @@ fn publish_discover_cycle() {
 		.unwrap();
 	let test_api = Arc::new(TestApi {
 		authorities: vec![node_a_public.into()],
+		next_authorities: vec![],
 	});
 
 	let (_to_worker, from_service) = mpsc::channel(0);
@@ -307,7 +389,7 @@ fn publish_discover_cycle() {
 		from_service,
 		test_api,
 		network.clone(),
-		vec![],
+		Default::default(),
 		dht_event_rx.boxed(),
 		Role::Authority(key_store),
 		None,
@@ -329,6 +411,7 @@ fn publish_discover_cycle() {
 	let test_api = Arc::new(TestApi {
 		// Make sure node B identifies node A as an authority.
 		authorities: vec![node_a_public.into()],
+		next_authorities: vec![],
 	});
 	let network: Arc<TestNetwork> = Arc::new(Default::default());
 	let key_store = KeyStore::new();
@@ -338,7 +421,7 @@ fn publish_discover_cycle() {
 		from_service,
 		test_api,
 		network.clone(),
-		vec![],
+		Default::default(),
 		dht_event_rx.boxed(),
 		Role::Authority(key_store),
 		None,
@@ -377,6 +460,7 @@ fn terminate_when_event_stream_terminates() {
 	let key_store = KeyStore::new();
 	let test_api = Arc::new(TestApi {
 		authorities: vec![],
+		next_authorities: vec![],
 	});
 
 	let (_to_worker, from_service) = mpsc::channel(0);
@@ -384,7 +468,7 @@ fn terminate_when_event_stream_terminates() {
 		from_service,
 		test_api,
 		network.clone(),
-		vec![],
+		Default::default(),
 		dht_event_rx.boxed(),
 		Role::Authority(key_store),
 		None,
@@ -412,6 +496,7 @@ fn continue_operating_when_service_channel_is_dropped() {
 	let key_store = KeyStore::new();
 	let test_api = Arc::new(TestApi {
 		authorities: vec![],
+		next_authorities: vec![],
 	});
 
 	let (to_worker, from_service) = mpsc::channel(0);
@@ -419,7 +504,7 @@ fn continue_operating_when_service_channel_is_dropped() {
 		from_service,
 		test_api,
 		network.clone(),
-		vec![],
+		Default::default(),
 		dht_event_rx.boxed(),
 		Role::Authority(key_store),
 		None,
@@ -453,6 +538,7 @@ fn dont_stop_polling_when_error_is_returned() {
 	let key_store = KeyStore::new();
 	let test_api = Arc::new(TestApi {
 		authorities: vec![],
+		next_authorities: vec![],
 	});
 	let mut pool = LocalPool::new();
 
@@ -461,7 +547,7 @@ fn dont_stop_polling_when_error_is_returned() {
 		from_service,
 		test_api,
 		network.clone(),
-		vec![],
+		Default::default(),
 		dht_event_rx.boxed(),
 		Role::Authority(key_store),
 		None,
@@ -581,6 +667,7 @@ fn never_add_own_address_to_priority_group() {
 	let sentry_test_api = Arc::new(TestApi {
 		// Make sure the sentry node identifies its validator as an authority.
 		authorities: vec![validator_public.into()],
+		next_authorities: vec![],
 	});
 
 	let (_to_worker, from_service) = mpsc::channel(0);
@@ -588,7 +675,7 @@ fn never_add_own_address_to_priority_group() {
 		from_service,
 		sentry_test_api,
 		sentry_network.clone(),
-		vec![],
+		Default::default(),
 		dht_event_rx.boxed(),
 		Role::Sentry,
 		None,
@@ -668,6 +755,7 @@ fn do_not_cache_addresses_without_peer_id() {
 	let local_test_api = Arc::new(TestApi {
 		// Make sure the sentry node identifies its validator as an authority.
 		authorities: vec![remote_public.into()],
+		next_authorities: vec![],
 	});
 	let local_network: Arc<TestNetwork> = Arc::new(Default::default());
 	let local_key_store = KeyStore::new();
@@ -677,7 +765,7 @@ fn do_not_cache_addresses_without_peer_id() {
 		from_service,
 		local_test_api,
 		local_network.clone(),
-		vec![],
+		Default::default(),
 		dht_event_rx.boxed(),
 		Role::Authority(local_key_store),
 		None,
@@ -0,0 +1,82 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error;
+use crate::params::{DatabaseParams, PruningParams, SharedParams};
+use crate::CliConfiguration;
+use sc_service::{Configuration, chain_ops::check_database};
+use sp_runtime::traits::Block as BlockT;
+use structopt::StructOpt;
+
+/// The `check-db` command used to check the integrity of the database.
+///
+/// Walks the canonical chain, checking that the state root of every block that isn't pruned can
+/// be found, and checks the leaves set and the meta keys. Only the root node of each state is
+/// checked. The node must not be running.
+#[derive(Debug, StructOpt)]
+pub struct CheckDbCmd {
+	/// Fix the leaves set and revert the blocks above the last consistent block.
+	///
+	/// Finalized blocks are never reverted.
+	#[structopt(long)]
+	pub repair: bool,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+impl CheckDbCmd {
+	/// Run the check-db command
+	pub fn run<B: BlockT>(&self, config: &Configuration) -> error::Result<()> {
+		let report = check_database::<B>(config, self.repair)?;
+
+		if report.is_consistent() {
+			Ok(())
+		} else if self.repair {
+			Err(error::Error::Other("The database could not be repaired".into()))
+		} else {
+			Err(error::Error::Other(format!(
+				"Found {} problems in the database, run with `--repair` to fix them",
+				report.problems.len(),
+			)))
+		}
+	}
+}
+
+impl CliConfiguration for CheckDbCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
@@ -0,0 +1,118 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::arg_enums::Database;
+use crate::error;
+use crate::params::{DatabaseParams, PruningParams, SharedParams};
+use crate::CliConfiguration;
+use sc_service::{Configuration, DatabaseConfig, chain_ops::migrate_database};
+use sp_runtime::traits::Block as BlockT;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// The `db-migrate` command used to copy the database to another backend.
+///
+/// Headers, bodies, state, aux and offchain data are copied column by column, and the best and
+/// finalized blocks of the new database are checked against the original one. The node must
+/// not be running, and the original database is left untouched.
+///
+/// Only RocksDb databases can be migrated, as ParityDb and SubDb can't be iterated over: moving
+/// from RocksDb to another backend is one-way.
+#[derive(Debug, StructOpt)]
+pub struct DbMigrateCmd {
+	/// Database backend to migrate to.
+	#[structopt(
+		long = "to",
+		value_name = "DB",
+		possible_values = &Database::variants(),
+		case_insensitive = true,
+	)]
+	pub target: Database,
+
+	/// Path of the new database.
+	///
+	/// Defaults to the path the node uses for the target backend.
+	#[structopt(long, value_name = "PATH", parse(from_os_str))]
+	pub target_path: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+impl DbMigrateCmd {
+	/// Run the db-migrate command
+	pub fn run<B: BlockT>(&self, config: &Configuration) -> error::Result<()> {
+		if !config.database.supports_iteration() {
+			return Err(error::Error::Input(format!(
+				"Migrating from a {} database is not supported",
+				config.database,
+			)));
+		}
+		let target_path = match (&self.target_path, config.database.path()) {
+			(Some(path), _) => path.clone(),
+			(None, Some(source_path)) => source_path.with_file_name(match self.target {
+				Database::RocksDb => "db",
+				Database::SubDb => "subdb",
+				Database::ParityDb => "paritydb",
+			}),
+			(None, None) => return Err(error::Error::Input(
+				"The path of the new database must be given with `--target-path`".into()
+			)),
+		};
+		if target_path.exists() {
+			return Err(error::Error::Input(format!(
+				"{} already exists, refusing to overwrite it",
+				target_path.display(),
+			)));
+		}
+
+		let target = match self.target {
+			Database::RocksDb => DatabaseConfig::RocksDb {
+				path: target_path,
+				cache_size: self.database_params.database_cache_size().unwrap_or(128),
+			},
+			Database::SubDb => DatabaseConfig::SubDb { path: target_path },
+			Database::ParityDb => DatabaseConfig::ParityDb { path: target_path },
+		};
+		migrate_database::<B>(config, target)?;
+		Ok(())
+	}
+}
+
+impl CliConfiguration for DbMigrateCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
@@ -17,6 +17,8 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 mod build_spec_cmd;
 mod check_block_cmd;
+mod check_db_cmd;
+mod db_migrate_cmd;
 mod export_blocks_cmd;
 mod export_state_cmd;
 mod import_blocks_cmd;
@@ -40,6 +42,8 @@ use structopt::StructOpt;
 pub use self::{
 	build_spec_cmd::BuildSpecCmd,
 	check_block_cmd::CheckBlockCmd,
+	check_db_cmd::CheckDbCmd,
+	db_migrate_cmd::DbMigrateCmd,
 	export_blocks_cmd::ExportBlocksCmd,
 	export_state_cmd::ExportStateCmd,
 	import_blocks_cmd::ImportBlocksCmd,
@@ -84,6 +88,12 @@ pub enum Subcommand {
 
 	/// Remove the whole chain data.
 	PurgeChain(PurgeChainCmd),
+
+	/// Check the integrity of the database.
+	CheckDb(CheckDbCmd),
+
+	/// Copy the database to another database backend.
+	DbMigrate(DbMigrateCmd),
 }
 
 /// Macro that helps implement CliConfiguration on an enum of subcommand automatically
@@ -282,6 +292,19 @@ macro_rules! substrate_cli_subcommands {
 				}
 			}
 
+			fn blocks_pruning(&self) -> $crate::Result<::sc_service::config::BlocksPruning> {
+				match self {
+					$($enum::$variant(cmd) => cmd.blocks_pruning()),*
+				}
+			}
+
+			fn offchain_index_pruning(&self)
+			-> $crate::Result<::sc_service::config::OffchainIndexPruning> {
+				match self {
+					$($enum::$variant(cmd) => cmd.offchain_index_pruning()),*
+				}
+			}
+
 			fn chain_id(&self, is_dev: bool) -> $crate::Result<String> {
 				match self {
 					$($enum::$variant(cmd) => cmd.chain_id(is_dev)),*
@@ -454,5 +477,7 @@ substrate_cli_subcommands!(
 		ImportBlocks,
 		CheckBlock,
 		Revert,
-		PurgeChain
+		PurgeChain,
+		CheckDb,
+		DbMigrate
 );
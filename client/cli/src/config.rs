@@ -27,9 +27,10 @@ use crate::{
 use names::{Generator, Name};
 use sc_client_api::execution_extensions::ExecutionStrategies;
 use sc_service::config::{
-	BasePath, Configuration, DatabaseConfig, ExtTransport, KeystoreConfig, NetworkConfiguration,
-	NodeKeyConfig, OffchainWorkerConfig, PrometheusConfig, PruningMode, Role, RpcMethods,
-	TaskExecutor, TelemetryEndpoints, TransactionPoolOptions, WasmExecutionMethod,
+	BasePath, BlocksPruning, Configuration, DatabaseConfig, ExtTransport, KeystoreConfig,
+	NetworkConfiguration, NodeKeyConfig, OffchainWorkerConfig, PrometheusConfig, PruningMode, Role,
+	RpcMethods, TaskExecutor, TelemetryEndpoints, TransactionPoolOptions, WasmExecutionMethod,
+	NativeWasmCheckConfig, OffchainIndexPruning,
 };
 use sc_service::{ChainSpec, TracingReceiver};
 use std::net::SocketAddr;
@@ -41,6 +42,12 @@ pub(crate) const NODE_NAME_MAX_LENGTH: usize = 64;
 /// default sub directory to store network config
 pub(crate) const DEFAULT_NETWORK_CONFIG_PATH: &'static str = "network";
 
+/// The directory of compiled runtimes, relative to the chain directory.
+pub(crate) const DEFAULT_WASM_RUNTIME_CACHE_PATH: &'static str = "wasmtime-cache";
+
+/// The directory of native and wasm runtime mismatch reports, relative to the chain directory.
+pub(crate) const DEFAULT_NATIVE_WASM_MISMATCHES_PATH: &'static str = "native-wasm-mismatches";
+
 /// Default configuration values used by Substrate
 ///
 /// These values will be used by [`CliConfiguritation`] to set
@@ -253,6 +260,26 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 			.unwrap_or_else(|| Ok(Default::default()))
 	}
 
+	/// Get the block body pruning mode.
+	///
+	/// By default this is retrieved from `PruningParams` if it is available. Otherwise its
+	/// `BlocksPruning::default()`.
+	fn blocks_pruning(&self) -> Result<BlocksPruning> {
+		self.pruning_params()
+			.map(|x| x.blocks_pruning())
+			.unwrap_or_else(|| Ok(Default::default()))
+	}
+
+	/// Get the pruning mode of the data written through offchain indexing.
+	///
+	/// By default this is retrieved from `PruningParams` if it is available. Otherwise its
+	/// `OffchainIndexPruning::default()`.
+	fn offchain_index_pruning(&self) -> Result<OffchainIndexPruning> {
+		self.pruning_params()
+			.map(|x| x.offchain_index_pruning())
+			.unwrap_or_else(|| Ok(Default::default()))
+	}
+
 	/// Get the chain ID (string).
 	///
 	/// By default this is retrieved from `SharedParams`.
@@ -277,6 +304,49 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 			.unwrap_or_default())
 	}
 
+	/// Get the directory of the on-disk cache of compiled runtimes.
+	///
+	/// By default this is the `wasmtime-cache` directory in `config_dir`, unless it is disabled
+	/// in `ImportParams`.
+	fn wasm_runtime_cache_path(&self, config_dir: &PathBuf) -> Result<Option<PathBuf>> {
+		Ok(match self.import_params() {
+			Some(params) if params.no_wasm_runtime_cache => None,
+			_ => Some(config_dir.join(DEFAULT_WASM_RUNTIME_CACHE_PATH)),
+		})
+	}
+
+	/// Returns `true` if the host functions called by the runtime should be measured.
+	///
+	/// By default this is retrieved from `ImportParams` if it is available. Otherwise its
+	/// `false`.
+	fn instrument_host_functions(&self) -> Result<bool> {
+		Ok(self.import_params()
+			.map(|x| x.instrument_host_functions)
+			.unwrap_or_default())
+	}
+
+	/// Get the comparison of the native and wasm runtimes.
+	///
+	/// By default this is retrieved from `ImportParams` if it is available. Reports are written
+	/// to the `native-wasm-mismatches` directory in `config_dir`.
+	fn native_wasm_check(&self, config_dir: &PathBuf) -> Result<Option<NativeWasmCheckConfig>> {
+		let fraction = match self.import_params().and_then(|x| x.native_wasm_check) {
+			Some(fraction) => fraction,
+			None => return Ok(None),
+		};
+		if !(0.0..=1.0).contains(&fraction) {
+			return Err(format!(
+				"Invalid native wasm check fraction {}: must be between 0 and 1",
+				fraction,
+			).into());
+		}
+
+		Ok(Some(NativeWasmCheckConfig {
+			fraction,
+			path: config_dir.join(DEFAULT_NATIVE_WASM_MISMATCHES_PATH),
+		}))
+	}
+
 	/// Get the execution strategies.
 	///
 	/// By default this is retrieved from `ImportParams` if it is available. Otherwise its
@@ -490,7 +560,12 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 			state_cache_size: self.state_cache_size()?,
 			state_cache_child_ratio: self.state_cache_child_ratio()?,
 			pruning: self.pruning(unsafe_pruning, &role)?,
+			blocks_pruning: self.blocks_pruning()?,
+			offchain_index_pruning: self.offchain_index_pruning()?,
 			wasm_method: self.wasm_method()?,
+			wasm_runtime_cache_path: self.wasm_runtime_cache_path(&config_dir)?,
+			instrument_host_functions: self.instrument_host_functions()?,
+			native_wasm_check: self.native_wasm_check(&config_dir)?,
 			execution_strategies: self.execution_strategies(is_dev, is_validator)?,
 			rpc_http: self.rpc_http(DCV::rpc_http_listen_port())?,
 			rpc_ws: self.rpc_ws(DCV::rpc_ws_listen_port())?,
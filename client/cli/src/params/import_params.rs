@@ -55,6 +55,27 @@ pub struct ImportParams {
 	)]
 	pub wasm_method: WasmExecutionMethod,
 
+	/// Don't store compiled runtimes on disk.
+	///
+	/// By default, runtimes compiled with the `Compiled` execution method are stored in the
+	/// chain directory so that they are not compiled again when the node restarts.
+	#[structopt(long = "no-wasm-runtime-cache")]
+	pub no_wasm_runtime_cache: bool,
+
+	/// Measure the calls made by the runtime to host functions.
+	///
+	/// The number of calls and the time spent in each host function are exported to Prometheus,
+	/// and as spans of the `host_functions` tracing target. This slows down block import.
+	#[structopt(long = "instrument-host-functions")]
+	pub instrument_host_functions: bool,
+
+	/// Execute a fraction of the imported blocks with both the native and the wasm runtime.
+	///
+	/// The fraction is between 0 and 1. Blocks on which the runtimes disagree are reported in
+	/// the `native-wasm-mismatches` directory of the chain, along with the storage diff.
+	#[structopt(long = "native-wasm-check", value_name = "FRACTION")]
+	pub native_wasm_check: Option<f64>,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub execution_strategies: ExecutionStrategiesParams,
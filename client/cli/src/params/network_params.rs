@@ -18,7 +18,10 @@
 
 use crate::params::node_key_params::NodeKeyParams;
 use sc_network::{
-	config::{NetworkConfiguration, NodeKeyConfig, NonReservedPeerMode, TransportConfig},
+	config::{
+		NetworkConfiguration, NodeKeyConfig, NonReservedPeerMode, TransportConfig,
+		WebSocketTlsClientConfig, WebSocketTlsConfig, WebSocketTlsServerConfig,
+	},
 	multiaddr::Protocol,
 };
 use sc_service::{ChainSpec, config::{Multiaddr, MultiaddrWithPeerId}};
@@ -88,6 +91,34 @@ pub struct NetworkParams {
 	)]
 	pub max_parallel_downloads: u32,
 
+	/// Maximum number of block requests a single peer can send every 10 seconds.
+	///
+	/// Further requests are refused. Peers syncing from this node send many requests.
+	#[structopt(
+		long = "max-block-requests-per-peer",
+		value_name = "COUNT",
+		default_value = "1024"
+	)]
+	pub max_block_requests_per_peer: u32,
+
+	/// Maximum number of MiB of block data sent to a single peer every 10 seconds.
+	#[structopt(
+		long = "max-block-response-mib-per-peer",
+		value_name = "MIB",
+		default_value = "1024"
+	)]
+	pub max_block_response_mib_per_peer: u64,
+
+	/// Maximum number of light client requests sent to a single peer at the same time.
+	///
+	/// Only used by light clients. Increase to fetch data faster from fewer peers.
+	#[structopt(
+		long = "max-light-requests-per-peer",
+		value_name = "COUNT",
+		default_value = "1"
+	)]
+	pub max_light_requests_per_peer: u32,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub node_key_params: NodeKeyParams,
@@ -102,6 +133,24 @@ pub struct NetworkParams {
 	/// By default this option is true for `--dev` and false otherwise.
 	#[structopt(long)]
 	pub discover_local: bool,
+
+	/// Certificate chain used to secure the WebSocket transport with TLS, in PEM or DER format.
+	///
+	/// Required in order to listen on `/wss` addresses, for example
+	/// `--listen-addr /ip4/0.0.0.0/tcp/443/wss`. Must be passed together with `--ws-tls-key`.
+	#[structopt(long, value_name = "PATH", requires = "ws-tls-key")]
+	pub ws_tls_cert: Option<PathBuf>,
+
+	/// Private key of the certificate passed with `--ws-tls-cert`, in PEM or DER format.
+	#[structopt(long, value_name = "PATH", requires = "ws-tls-cert")]
+	pub ws_tls_key: Option<PathBuf>,
+
+	/// Additional certificate to trust when dialing `/wss` addresses, in PEM or DER format.
+	///
+	/// Useful for nodes using self-signed certificates. Can be passed multiple times, and doesn't
+	/// require `--ws-tls-cert`.
+	#[structopt(long, value_name = "PATH")]
+	pub ws_tls_trust: Vec<PathBuf>,
 }
 
 impl NetworkParams {
@@ -133,6 +182,24 @@ impl NetworkParams {
 
 		let public_addresses = self.public_addr.clone();
 
+		let websocket_tls_server = match (&self.ws_tls_cert, &self.ws_tls_key) {
+			(Some(certificate_chain), Some(private_key)) => Some(WebSocketTlsServerConfig {
+				certificate_chain: certificate_chain.clone(),
+				private_key: private_key.clone(),
+			}),
+			_ => None,
+		};
+		let websocket_tls = if websocket_tls_server.is_some() || !self.ws_tls_trust.is_empty() {
+			Some(WebSocketTlsConfig {
+				server: websocket_tls_server,
+				client: WebSocketTlsClientConfig {
+					trusted_certificates: self.ws_tls_trust.clone(),
+				},
+			})
+		} else {
+			None
+		};
+
 		let mut boot_nodes = chain_spec.boot_nodes().to_vec();
 		boot_nodes.extend(self.bootnodes.clone());
 
@@ -148,6 +215,7 @@ impl NetworkParams {
 			listen_addresses,
 			public_addresses,
 			notifications_protocols: Vec::new(),
+			request_response_protocols: Vec::new(),
 			node_key,
 			node_name: node_name.to_string(),
 			client_version: client_id.to_string(),
@@ -158,8 +226,12 @@ impl NetworkParams {
 				allow_private_ipv4: !self.no_private_ipv4,
 				wasm_external_transport: None,
 				use_yamux_flow_control: !self.no_yamux_flow_control,
+				websocket_tls,
 			},
 			max_parallel_downloads: self.max_parallel_downloads,
+			max_block_requests_per_peer: self.max_block_requests_per_peer,
+			max_block_response_bytes_per_peer: self.max_block_response_mib_per_peer.saturating_mul(1024 * 1024),
+			max_light_requests_per_peer: self.max_light_requests_per_peer,
 			allow_non_globals_in_dht: self.discover_local || is_dev,
 		}
 	}
@@ -23,8 +23,9 @@
 //! targeted at handling input parameter parsing providing
 //! a reasonable abstraction.
 
+use std::{path::PathBuf, time::Duration};
 use structopt::StructOpt;
-use sc_service::config::OffchainWorkerConfig;
+use sc_service::config::{OffchainHttpConfig, OffchainWorkerConfig};
 use sc_network::config::Role;
 
 use crate::error;
@@ -55,6 +56,35 @@ pub struct OffchainWorkerParams {
 		value_name = "ENABLE_OFFCHAIN_INDEXING"
 	)]
 	pub indexing_enabled: bool,
+
+	/// HTTP proxy that offchain worker requests are tunneled through.
+	///
+	/// Proxies requiring credentials are not supported.
+	#[structopt(long = "offchain-http-proxy", value_name = "URI")]
+	pub http_proxy: Option<String>,
+
+	/// PEM file of a certificate authority to trust for offchain worker requests.
+	///
+	/// Can be passed multiple times. The certificate authorities of the system are always trusted.
+	#[structopt(long = "offchain-http-ca-cert", value_name = "PATH", number_of_values = 1)]
+	pub http_ca_certificates: Vec<PathBuf>,
+
+	/// Maximum number of HTTP requests an offchain worker can start per block.
+	#[structopt(long = "offchain-http-max-requests", value_name = "COUNT")]
+	pub http_max_requests: Option<u32>,
+
+	/// Maximum size of the body of an HTTP response to an offchain worker, in bytes.
+	#[structopt(long = "offchain-http-max-response-size", value_name = "BYTES")]
+	pub http_max_response_size: Option<u64>,
+
+	/// Timeout for establishing the connections of offchain worker HTTP requests, in seconds.
+	#[structopt(long = "offchain-http-connect-timeout", value_name = "SECS")]
+	pub http_connect_timeout: Option<u64>,
+
+	/// Timeout for receiving the response to an offchain worker HTTP request, body included,
+	/// in seconds.
+	#[structopt(long = "offchain-http-response-timeout", value_name = "SECS")]
+	pub http_response_timeout: Option<u64>,
 }
 
 impl OffchainWorkerParams {
@@ -73,6 +103,18 @@ impl OffchainWorkerParams {
 
 		let indexing_enabled = enabled && self.indexing_enabled;
 
-		Ok(OffchainWorkerConfig { enabled, indexing_enabled })
+		let http = OffchainHttpConfig {
+			proxy: self.http_proxy.clone(),
+			ca_certificates: self.http_ca_certificates.clone(),
+			max_requests: self.http_max_requests,
+			max_response_size: self.http_max_response_size,
+			connect_timeout: self.http_connect_timeout.map(Duration::from_secs),
+			response_timeout: self.http_response_timeout.map(Duration::from_secs),
+		};
+		http.check().map_err(|err| error::Error::Input(
+			format!("Invalid offchain worker HTTP configuration: {}", err)
+		))?;
+
+		Ok(OffchainWorkerConfig { enabled, indexing_enabled, http })
 	}
 }
@@ -17,7 +17,7 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::error;
-use sc_service::{PruningMode, Role};
+use sc_service::{BlocksPruning, OffchainIndexPruning, PruningMode, Role};
 use structopt::StructOpt;
 
 /// Parameters to define the pruning mode
@@ -30,6 +30,33 @@ pub struct PruningParams {
 	/// 256 blocks.
 	#[structopt(long = "pruning", value_name = "PRUNING_MODE")]
 	pub pruning: Option<String>,
+
+	/// Keep the full state history of storage keys starting with this hex encoded prefix,
+	/// when state pruning is enabled.
+	///
+	/// Can be given multiple times. Only the main trie can be archived, and only with RocksDB.
+	#[structopt(long = "pruning-archive-prefix", value_name = "HEX", number_of_values = 1)]
+	pub pruning_archive_prefixes: Vec<String>,
+
+	/// Specify the block body pruning mode, a positive number of finalized blocks to keep
+	/// the body of or 'archive'.
+	///
+	/// The bodies of blocks on forks are removed once the forks fall behind the finalized block.
+	/// Headers are always kept. Default is to keep the bodies of all blocks.
+	#[structopt(long = "blocks-pruning", value_name = "PRUNING_MODE")]
+	pub blocks_pruning: Option<String>,
+
+	/// Also remove the justifications of blocks whose body is pruned.
+	#[structopt(long = "prune-justifications", requires = "blocks-pruning")]
+	pub prune_justifications: bool,
+
+	/// Specify the pruning mode of the data written to the offchain storage through
+	/// offchain indexing, a positive number of finalized blocks to keep the data of or 'archive'.
+	///
+	/// The data written by blocks that leave the best chain is always discarded.
+	/// Default is to keep the data of all finalized blocks.
+	#[structopt(long = "offchain-index-pruning", value_name = "PRUNING_MODE")]
+	pub offchain_index_pruning: Option<String>,
 }
 
 impl PruningParams {
@@ -39,7 +66,7 @@ impl PruningParams {
 		// `ArchiveAll`), otherwise we keep state for the last 256 blocks. if the
 		// node is an authority and pruning is enabled explicitly, then we error
 		// unless `unsafe_pruning` is set.
-		Ok(match &self.pruning {
+		let mode = match &self.pruning {
 			Some(ref s) if s == "archive" => PruningMode::ArchiveAll,
 			None if role.is_network_authority() => PruningMode::ArchiveAll,
 			None => PruningMode::default(),
@@ -56,6 +83,42 @@ impl PruningParams {
 					error::Error::Input("Invalid pruning mode specified".to_string())
 				})?)
 			}
+		};
+		Ok(mode.with_archive_prefixes(self.archive_prefixes()?))
+	}
+
+	fn archive_prefixes(&self) -> error::Result<Vec<Vec<u8>>> {
+		self.pruning_archive_prefixes.iter().map(|prefix| {
+			hex::decode(prefix.trim_start_matches("0x")).map_err(|_| error::Error::Input(
+				format!("Invalid pruning archive prefix: {}", prefix)
+			))
+		}).collect()
+	}
+
+	/// Get the block body pruning value from the parameters
+	pub fn blocks_pruning(&self) -> error::Result<BlocksPruning> {
+		Ok(match &self.blocks_pruning {
+			Some(ref s) if s == "archive" => BlocksPruning::KeepAll,
+			None => BlocksPruning::KeepAll,
+			Some(s) => BlocksPruning::KeepFinalized {
+				blocks: s.parse::<u32>().ok().filter(|blocks| *blocks > 0).ok_or_else(|| {
+					error::Error::Input("Invalid blocks pruning mode specified".to_string())
+				})?,
+				justifications: self.prune_justifications,
+			},
+		})
+	}
+
+	/// Get the offchain indexing data pruning value from the parameters
+	pub fn offchain_index_pruning(&self) -> error::Result<OffchainIndexPruning> {
+		Ok(match &self.offchain_index_pruning {
+			Some(ref s) if s == "archive" => OffchainIndexPruning::KeepAll,
+			None => OffchainIndexPruning::KeepAll,
+			Some(s) => OffchainIndexPruning::KeepFinalized {
+				blocks: s.parse::<u32>().ok().filter(|blocks| *blocks > 0).ok_or_else(|| {
+					error::Error::Input("Invalid offchain index pruning mode specified".to_string())
+				})?,
+			},
 		})
 	}
 }
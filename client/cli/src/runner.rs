@@ -212,6 +212,8 @@ impl<C: SubstrateCli> Runner<C> {
 				run_until_exit(self.tokio_runtime, cmd.run(client, backend), task_manager)
 			},
 			Subcommand::PurgeChain(cmd) => cmd.run(db_config),
+			Subcommand::CheckDb(cmd) => cmd.run::<B>(&self.config),
+			Subcommand::DbMigrate(cmd) => cmd.run::<B>(&self.config),
 			Subcommand::ExportState(cmd) => {
 				let (client, _, _, task_manager) = builder(self.config)?;
 				run_until_exit(self.tokio_runtime, cmd.run(client, chain_spec), task_manager)
@@ -38,7 +38,9 @@ use std::{
 use futures::prelude::*;
 use parking_lot::Mutex;
 use log::{debug, info, trace};
-use prometheus_endpoint::Registry;
+use prometheus_endpoint::{
+	register, CounterVec, GaugeVec, Opts, PrometheusError, Registry, U64,
+};
 
 use codec::{Encode, Decode, Codec};
 
@@ -55,7 +57,7 @@ use sp_blockchain::{
 	ProvideCache, HeaderBackend,
 };
 use sp_block_builder::BlockBuilder as BlockBuilderApi;
-use sp_core::crypto::Public;
+use sp_core::{crypto::Public, hexdisplay::HexDisplay};
 use sp_application_crypto::{AppKey, AppPublic};
 use sp_runtime::{
 	generic::{BlockId, OpaqueDigestItemId},
@@ -121,6 +123,83 @@ fn slot_author<P: Pair>(slot_num: u64, authorities: &[AuthorityId<P>]) -> Option
 	Some(current_author)
 }
 
+/// Get the fallback author for the given slot, built on top of a block authored
+/// at `parent_slot`. Once at least `max_skipped_slots` consecutive slots have
+/// been left empty, the authority following the expected slot author in the
+/// authority list is allowed to claim the slot as well, moving on to the next
+/// authority (round-robin) every `max_skipped_slots` slots. The expected slot
+/// author itself is never selected.
+///
+/// Returns `None` if no fallback author should be selected.
+fn fallback_slot_author<P: Pair>(
+	slot_num: u64,
+	parent_slot: u64,
+	authorities: &[AuthorityId<P>],
+	max_skipped_slots: Option<u64>,
+) -> Option<&AuthorityId<P>> {
+	let max_skipped_slots = max_skipped_slots?.max(1);
+	let skipped_slots = slot_num.saturating_sub(parent_slot + 1);
+
+	let offset = skipped_slots / max_skipped_slots;
+	let others = (authorities.len() as u64).checked_sub(1)?;
+	if offset == 0 || others == 0 {
+		return None;
+	}
+
+	slot_author::<P>(slot_num.wrapping_add(1 + (offset - 1) % others), authorities)
+}
+
+/// Formats an authority for use as a metrics label.
+fn authority_label<A: AsRef<[u8]>>(authority: &A) -> String {
+	format!("0x{}", HexDisplay::from(&authority.as_ref()))
+}
+
+/// Prometheus metrics for the Aura slot worker.
+struct Metrics {
+	slots_claimed: CounterVec<U64>,
+	slots_missed: CounterVec<U64>,
+	skipped_slots: GaugeVec<U64>,
+}
+
+impl Metrics {
+	fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			slots_claimed: register(
+				CounterVec::new(
+					Opts::new(
+						"aura_slots_claimed_total",
+						"Number of slots claimed by the local authorities, either as \
+						 expected slot author or as fallback author."
+					),
+					&["authority", "kind"],
+				)?,
+				registry,
+			)?,
+			slots_missed: register(
+				CounterVec::new(
+					Opts::new(
+						"aura_slots_missed_total",
+						"Number of slots without a block on the best chain, by expected slot author."
+					),
+					&["authority"],
+				)?,
+				registry,
+			)?,
+			skipped_slots: register(
+				GaugeVec::new(
+					Opts::new(
+						"aura_skipped_slots",
+						"Number of consecutive slots without a block since the best block, \
+						 by expected author of the current slot."
+					),
+					&["authority"],
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 struct AuraSlotCompatible;
 
@@ -138,6 +217,10 @@ impl SlotCompatible for AuraSlotCompatible {
 }
 
 /// Start the aura worker. The returned future should be run in a futures executor.
+///
+/// If `max_skipped_slots` is set, a fallback author may claim a slot once that
+/// many consecutive slots have been left empty. All nodes on the network must
+/// use the same value, which must also be passed to the `import_queue`.
 pub fn start_aura<B, C, SC, E, I, P, SO, CAW, Error>(
 	slot_duration: SlotDuration,
 	client: Arc<C>,
@@ -149,6 +232,8 @@ pub fn start_aura<B, C, SC, E, I, P, SO, CAW, Error>(
 	force_authoring: bool,
 	keystore: KeyStorePtr,
 	can_author_with: CAW,
+	max_skipped_slots: Option<u64>,
+	prometheus_registry: Option<&Registry>,
 ) -> Result<impl Future<Output = ()>, sp_consensus::Error> where
 	B: BlockT,
 	C: ProvideRuntimeApi<B> + BlockOf + ProvideCache<B> + AuxStore + Send + Sync,
@@ -164,6 +249,15 @@ pub fn start_aura<B, C, SC, E, I, P, SO, CAW, Error>(
 	SO: SyncOracle + Send + Sync + Clone,
 	CAW: CanAuthorWith<B> + Send,
 {
+	let metrics = match prometheus_registry.map(Metrics::register) {
+		Some(Ok(metrics)) => Some(metrics),
+		Some(Err(e)) => {
+			debug!(target: "aura", "Failed to register metrics: {:?}", e);
+			None
+		},
+		None => None,
+	};
+
 	let worker = AuraWorker {
 		client,
 		block_import: Arc::new(Mutex::new(block_import)),
@@ -171,6 +265,9 @@ pub fn start_aura<B, C, SC, E, I, P, SO, CAW, Error>(
 		keystore,
 		sync_oracle: sync_oracle.clone(),
 		force_authoring,
+		max_skipped_slots,
+		metrics,
+		last_accounted_slot: Mutex::new(None),
 		_key_type: PhantomData::<P>,
 	};
 	register_aura_inherent_data_provider(
@@ -195,6 +292,10 @@ struct AuraWorker<C, E, I, P, SO> {
 	keystore: KeyStorePtr,
 	sync_oracle: SO,
 	force_authoring: bool,
+	max_skipped_slots: Option<u64>,
+	metrics: Option<Metrics>,
+	/// The last slot accounted for in the missed slots metrics.
+	last_accounted_slot: Mutex<Option<u64>>,
 	_key_type: PhantomData<P>,
 }
 
@@ -242,17 +343,90 @@ impl<B, C, E, I, P, Error, SO> sc_consensus_slots::SimpleSlotWorker<B> for AuraW
 
 	fn claim_slot(
 		&self,
-		_header: &B::Header,
+		header: &B::Header,
 		slot_number: u64,
 		epoch_data: &Self::EpochData,
 	) -> Option<Self::Claim> {
-		let expected_author = slot_author::<P>(slot_number, epoch_data);
-		expected_author.and_then(|p| {
-			self.keystore.read()
-				.key_pair_by_type::<P>(&p, sp_application_crypto::key_types::AURA).ok()
-		}).and_then(|p| {
-			Some(p.public())
-		})
+		let keystore = self.keystore.read();
+		let claim = |author: &AuthorityId<P>| {
+			keystore.key_pair_by_type::<P>(author, sp_application_crypto::key_types::AURA)
+				.ok()
+				.map(|p| p.public())
+		};
+
+		let (claim, kind) = match slot_author::<P>(slot_number, epoch_data).and_then(claim) {
+			Some(claim) => (claim, "primary"),
+			None => {
+				// the genesis block has no slot, so the number of skipped slots
+				// can't be known when building on top of it.
+				if header.number().is_zero() {
+					return None;
+				}
+
+				let parent_slot = find_pre_digest::<B, P>(header).ok()?;
+				let claim = fallback_slot_author::<P>(
+					slot_number,
+					parent_slot,
+					epoch_data,
+					self.max_skipped_slots,
+				).and_then(claim)?;
+
+				debug!(target: "aura",
+					"Claiming slot {} as fallback author, {} slots skipped since parent.",
+					slot_number,
+					slot_number.saturating_sub(parent_slot + 1),
+				);
+
+				(claim, "fallback")
+			},
+		};
+
+		if let Some(metrics) = &self.metrics {
+			metrics.slots_claimed.with_label_values(&[&authority_label(&claim), kind]).inc();
+		}
+
+		Some(claim)
+	}
+
+	fn notify_slot(
+		&self,
+		header: &B::Header,
+		slot_number: u64,
+		epoch_data: &Self::EpochData,
+	) {
+		let metrics = match &self.metrics {
+			Some(metrics) => metrics,
+			None => return,
+		};
+
+		let parent_slot = match find_pre_digest::<B, P>(header) {
+			Ok(parent_slot) => parent_slot,
+			Err(_) => return,
+		};
+
+		// only the expected author of the current slot is reported.
+		metrics.skipped_slots.reset();
+		if let Some(author) = slot_author::<P>(slot_number, epoch_data) {
+			metrics.skipped_slots
+				.with_label_values(&[&authority_label(author)])
+				.set(slot_number.saturating_sub(parent_slot + 1));
+		}
+
+		// only account for slots we have been notified of, so that an old chain
+		// head (e.g. genesis) doesn't count every slot since as missed.
+		let mut last_accounted_slot = self.last_accounted_slot.lock();
+		if let Some(last_accounted_slot) = *last_accounted_slot {
+			let first_slot = (parent_slot + 1).max(last_accounted_slot + 1);
+			for slot in first_slot..slot_number {
+				if let Some(author) = slot_author::<P>(slot, epoch_data) {
+					metrics.slots_missed.with_label_values(&[&authority_label(author)]).inc();
+				}
+			}
+		}
+
+		*last_accounted_slot = Some(
+			last_accounted_slot.unwrap_or_default().max(slot_number.saturating_sub(1))
+		);
 	}
 
 	fn pre_digest_data(
@@ -438,6 +612,8 @@ fn check_header<C, B: BlockT, P: Pair>(
 	mut header: B::Header,
 	hash: B::Hash,
 	authorities: &[AuthorityId<P>],
+	parent_slot: Option<u64>,
+	max_skipped_slots: Option<u64>,
 ) -> Result<CheckedHeader<B::Header, (u64, DigestItemFor<B>)>, Error<B>> where
 	DigestItemFor<B>: CompatibleDigestItem<P>,
 	P::Signature: Decode,
@@ -468,13 +644,28 @@ fn check_header<C, B: BlockT, P: Pair>(
 
 		let pre_hash = header.hash();
 
-		if P::verify(&sig, pre_hash.as_ref(), expected_author) {
+		// if the expected author didn't sign the block, it might have been
+		// authored by the fallback author.
+		let author = if P::verify(&sig, pre_hash.as_ref(), expected_author) {
+			Some(expected_author)
+		} else {
+			parent_slot
+				.and_then(|parent_slot| fallback_slot_author::<P>(
+					slot_num,
+					parent_slot,
+					authorities,
+					max_skipped_slots,
+				))
+				.filter(|fallback_author| P::verify(&sig, pre_hash.as_ref(), fallback_author))
+		};
+
+		if let Some(author) = author {
 			if let Some(equivocation_proof) = check_equivocation(
 				client,
 				slot_now,
 				slot_num,
 				&header,
-				expected_author,
+				author,
 			).map_err(Error::Client)? {
 				info!(
 					"Slot author is equivocating at slot {} with headers {:?} and {:?}",
@@ -497,6 +688,7 @@ pub struct AuraVerifier<C, P, CAW> {
 	phantom: PhantomData<P>,
 	inherent_data_providers: sp_inherents::InherentDataProviders,
 	can_author_with: CAW,
+	max_skipped_slots: Option<u64>,
 }
 
 impl<C, P, CAW> AuraVerifier<C, P, CAW> where
@@ -572,6 +764,7 @@ impl<B: BlockT, C, P, CAW> Verifier<B> for AuraVerifier<C, P, CAW> where
 		Sync +
 		sc_client_api::backend::AuxStore +
 		ProvideCache<B> +
+		HeaderBackend<B> +
 		BlockOf,
 	C::Api: BlockBuilderApi<B> + AuraApi<B, AuthorityId<P>> + ApiExt<B, Error = sp_blockchain::Error>,
 	DigestItemFor<B>: CompatibleDigestItem<P>,
@@ -597,6 +790,23 @@ impl<B: BlockT, C, P, CAW> Verifier<B> for AuraVerifier<C, P, CAW> where
 		let authorities = authorities(self.client.as_ref(), &BlockId::Hash(parent_hash))
 			.map_err(|e| format!("Could not fetch authorities at {:?}: {:?}", parent_hash, e))?;
 
+		// the parent slot is only needed to check fallback authors, which are
+		// never selected on top of genesis since it has no slot.
+		let parent_slot = match self.max_skipped_slots {
+			Some(_) => {
+				let parent_header = self.client.header(BlockId::Hash(parent_hash))
+					.map_err(|e| format!("Could not fetch parent header {:?}: {:?}", parent_hash, e))?
+					.ok_or_else(|| String::from(Error::<B>::ParentUnavailable(parent_hash, hash)))?;
+
+				if parent_header.number().is_zero() {
+					None
+				} else {
+					Some(find_pre_digest::<B, P>(&parent_header).map_err(|e| e.to_string())?)
+				}
+			},
+			None => None,
+		};
+
 		// we add one to allow for some small drift.
 		// FIXME #1019 in the future, alter this queue to allow deferring of
 		// headers
@@ -606,6 +816,8 @@ impl<B: BlockT, C, P, CAW> Verifier<B> for AuraVerifier<C, P, CAW> where
 			header,
 			hash,
 			&authorities[..],
+			parent_slot,
+			self.max_skipped_slots,
 		).map_err(|e| e.to_string())?;
 		match checked_header {
 			CheckedHeader::Checked(pre_header, (slot_num, seal)) => {
@@ -836,6 +1048,7 @@ pub fn import_queue<B, I, C, P, S, CAW>(
 	spawner: &S,
 	registry: Option<&Registry>,
 	can_author_with: CAW,
+	max_skipped_slots: Option<u64>,
 ) -> Result<DefaultImportQueue<B, C>, sp_consensus::Error> where
 	B: BlockT,
 	C::Api: BlockBuilderApi<B> + AuraApi<B, AuthorityId<P>> + ApiExt<B, Error = sp_blockchain::Error>,
@@ -856,6 +1069,7 @@ pub fn import_queue<B, I, C, P, S, CAW>(
 		inherent_data_providers,
 		phantom: PhantomData,
 		can_author_with,
+		max_skipped_slots,
 	};
 
 	Ok(BasicQueue::new(
@@ -969,6 +1183,7 @@ mod tests {
 						inherent_data_providers,
 						phantom: Default::default(),
 						can_author_with: AlwaysCanAuthor,
+						max_skipped_slots: None,
 					}
 				},
 				PeersClient::Light(_, _) => unreachable!("No (yet) tests for light client + Aura"),
@@ -1042,6 +1257,8 @@ mod tests {
 				false,
 				keystore,
 				sp_consensus::AlwaysCanAuthor,
+				None,
+				None,
 			).expect("Starts aura"));
 		}
 
@@ -1100,6 +1317,9 @@ mod tests {
 			keystore,
 			sync_oracle: DummyOracle.clone(),
 			force_authoring: false,
+			max_skipped_slots: None,
+			metrics: None,
+			last_accounted_slot: Mutex::new(None),
 			_key_type: PhantomData::<AuthorityPair>,
 		};
 
@@ -1119,4 +1339,166 @@ mod tests {
 		assert!(worker.claim_slot(&head, 6, &authorities).is_none());
 		assert!(worker.claim_slot(&head, 7, &authorities).is_some());
 	}
+
+	fn header_at_slot(number: u64, slot_number: u64) -> Header {
+		Header::new(
+			number,
+			H256::from_low_u64_be(0),
+			H256::from_low_u64_be(0),
+			Default::default(),
+			sp_runtime::generic::Digest {
+				logs: vec![
+					<DigestItemFor<TestBlock> as CompatibleDigestItem<AuthorityPair>>::aura_pre_digest(
+						slot_number,
+					),
+				],
+			},
+		)
+	}
+
+	fn worker_with_key(
+		max_skipped_slots: Option<u64>,
+		registry: Option<&Registry>,
+	) -> (
+		AuraWorker<PeersFullClient, DummyFactory, PeersFullClient, AuthorityPair, DummyOracle>,
+		Vec<sp_consensus_aura::sr25519::AuthorityId>,
+		tempfile::TempDir,
+	) {
+		let mut net = AuraTestNet::new(1);
+
+		let keystore_path = tempfile::tempdir().expect("Creates keystore path");
+		let keystore = sc_keystore::Store::open(keystore_path.path(), None).expect("Creates keystore.");
+		let my_key = keystore.write()
+			.generate_by_type::<AuthorityPair>(AuthorityPair::ID)
+			.expect("Key should be created");
+
+		let authorities = vec![
+			Keyring::Alice.public().into(),
+			Keyring::Bob.public().into(),
+			Keyring::Charlie.public().into(),
+			my_key.public(),
+		];
+
+		let client = net.peer(0).client().as_full().expect("full clients are created").clone();
+		let environ = DummyFactory(client.clone());
+
+		let worker = AuraWorker {
+			client: client.clone(),
+			block_import: Arc::new(Mutex::new(client)),
+			env: environ,
+			keystore,
+			sync_oracle: DummyOracle.clone(),
+			force_authoring: false,
+			max_skipped_slots,
+			metrics: registry.map(|registry| Metrics::register(registry).unwrap()),
+			last_accounted_slot: Mutex::new(None),
+			_key_type: PhantomData::<AuthorityPair>,
+		};
+
+		(worker, authorities, keystore_path)
+	}
+
+	#[test]
+	fn fallback_slot_author_is_round_robin() {
+		let authorities: Vec<sp_consensus_aura::sr25519::AuthorityId> = vec![
+			Keyring::Alice.public().into(),
+			Keyring::Bob.public().into(),
+			Keyring::Charlie.public().into(),
+		];
+
+		// disabled without a policy.
+		assert!(fallback_slot_author::<AuthorityPair>(10, 0, &authorities, None).is_none());
+
+		// no fallback until enough slots have been skipped.
+		assert!(fallback_slot_author::<AuthorityPair>(1, 0, &authorities, Some(2)).is_none());
+		assert!(fallback_slot_author::<AuthorityPair>(2, 0, &authorities, Some(2)).is_none());
+
+		// the expected author of slot 3 is Alice, 2 slots skipped selects Bob.
+		assert_eq!(
+			fallback_slot_author::<AuthorityPair>(3, 0, &authorities, Some(2)),
+			Some(&authorities[1]),
+		);
+
+		// the expected author of slot 5 is Charlie, 4 slots skipped selects Bob.
+		assert_eq!(
+			fallback_slot_author::<AuthorityPair>(5, 0, &authorities, Some(2)),
+			Some(&authorities[1]),
+		);
+
+		// the expected author of slot 7 is Bob, 6 slots skipped skips him and selects Charlie.
+		assert_eq!(
+			fallback_slot_author::<AuthorityPair>(7, 0, &authorities, Some(2)),
+			Some(&authorities[2]),
+		);
+
+		// there is no fallback for a single authority.
+		assert!(fallback_slot_author::<AuthorityPair>(7, 0, &authorities[..1], Some(2)).is_none());
+	}
+
+	#[test]
+	fn current_node_authority_should_claim_fallback_slot() {
+		let (worker, authorities, _keystore_path) = worker_with_key(Some(2), None);
+
+		// our key is the expected author of slots 3 and 7.
+		let head = header_at_slot(1, 0);
+		assert!(worker.claim_slot(&head, 1, &authorities).is_none());
+		assert!(worker.claim_slot(&head, 2, &authorities).is_none());
+		assert!(worker.claim_slot(&head, 3, &authorities).is_some());
+
+		// the expected author of slot 4 is Alice, 3 skipped slots selects Bob.
+		assert!(worker.claim_slot(&head, 4, &authorities).is_none());
+
+		// the expected author of slot 5 is Bob, 4 skipped slots selects us.
+		assert!(worker.claim_slot(&head, 5, &authorities).is_some());
+
+		// the expected author of slot 6 is Charlie, 5 skipped slots selects Alice.
+		assert!(worker.claim_slot(&head, 6, &authorities).is_none());
+
+		// there is no fallback on top of genesis, which has no slot.
+		let genesis = header_at_slot(0, 0);
+		assert!(worker.claim_slot(&genesis, 5, &authorities).is_none());
+		assert!(worker.claim_slot(&genesis, 7, &authorities).is_some());
+
+		// without a policy we only claim our own slots.
+		let (worker, authorities, _keystore_path) = worker_with_key(None, None);
+		assert!(worker.claim_slot(&head, 5, &authorities).is_none());
+		assert!(worker.claim_slot(&head, 7, &authorities).is_some());
+	}
+
+	#[test]
+	fn slot_metrics_are_reported() {
+		let registry = Registry::new();
+		let (worker, authorities, _keystore_path) = worker_with_key(None, Some(&registry));
+		let metrics = worker.metrics.as_ref().unwrap();
+
+		let head = header_at_slot(1, 10);
+
+		let skipped_slots = |author: usize| {
+			metrics.skipped_slots.with_label_values(&[&authority_label(&authorities[author])]).get()
+		};
+
+		// the first notification only sets the accounting starting point.
+		worker.notify_slot(&head, 12, &authorities);
+		assert_eq!(skipped_slots(0), 1);
+		assert_eq!(metrics.slots_missed.with_label_values(&[&authority_label(&authorities[3])]).get(), 0);
+
+		// slots 12, 13 and 14 are empty, the expected author of slot 15 is us.
+		worker.notify_slot(&head, 15, &authorities);
+		assert_eq!(skipped_slots(3), 4);
+		assert_eq!(skipped_slots(0), 0);
+		for (slot, author) in &[(12, 0), (13, 1), (14, 2)] {
+			assert_eq!(
+				metrics.slots_missed.with_label_values(&[&authority_label(&authorities[*author])]).get(),
+				1,
+				"slot {} should be accounted as missed",
+				slot,
+			);
+		}
+
+		assert!(worker.claim_slot(&head, 15, &authorities).is_some());
+		assert_eq!(
+			metrics.slots_claimed.with_label_values(&[&authority_label(&authorities[3]), "primary"]).get(),
+			1,
+		);
+	}
 }
@@ -27,12 +27,14 @@ use jsonrpc_core::{
 use jsonrpc_derive::rpc;
 use sc_consensus_epochs::{descendent_query, Epoch as EpochT, SharedEpochChanges};
 use sp_consensus_babe::{
+	AllowedSlots,
 	AuthorityId,
 	BabeApi as BabeRuntimeApi,
-	digests::PreDigest,
+	digests::{CompatibleDigestItem, PreDigest},
 };
 use serde::{Deserialize, Serialize};
 use sp_core::{
+	Bytes,
 	crypto::Public,
 	traits::BareCryptoStore,
 };
@@ -40,7 +42,7 @@ use sp_application_crypto::AppKey;
 use sc_keystore::KeyStorePtr;
 use sc_rpc_api::DenyUnsafe;
 use sp_api::{ProvideRuntimeApi, BlockId};
-use sp_runtime::traits::{Block as BlockT, Header as _};
+use sp_runtime::traits::{Block as BlockT, Header as _, Zero};
 use sp_consensus::{SelectChain, Error as ConsensusError};
 use sp_blockchain::{HeaderBackend, HeaderMetadata, Error as BlockChainError};
 use std::{collections::HashMap, sync::Arc};
@@ -49,11 +51,32 @@ type FutureResult<T> = Box<dyn rpc_future::Future<Item = T, Error = RpcError> +
 
 /// Provides rpc methods for interacting with Babe.
 #[rpc]
-pub trait BabeApi {
+pub trait BabeApi<Hash> {
 	/// Returns data about which slots (primary or secondary) can be claimed in the current epoch
 	/// with the keys in the keystore.
 	#[rpc(name = "babe_epochAuthorship")]
 	fn epoch_authorship(&self) -> FutureResult<HashMap<AuthorityId, EpochAuthorship>>;
+
+	/// Returns the descriptors of the current and next epoch at the given block
+	/// (or the best block if none is given), as tracked by the epoch changes tree.
+	#[rpc(name = "babe_epochs")]
+	fn epochs(&self, at: Option<Hash>) -> FutureResult<Epochs>;
+
+	/// Returns the slots each authority can claim in the epoch following the one
+	/// at the given block. Secondary slots are known for every authority,
+	/// primary slots can only be computed for keys in the local keystore.
+	#[rpc(name = "babe_nextEpochSlotClaims")]
+	fn next_epoch_slot_claims(
+		&self,
+		at: Option<Hash>,
+	) -> FutureResult<HashMap<AuthorityId, EpochAuthorship>>;
+
+	/// Returns an estimate of the slots missed by each authority in the epoch
+	/// at the given block, computed from the imported chain up to that block.
+	/// This walks back the chain to the start of the epoch, and is therefore
+	/// an unsafe call.
+	#[rpc(name = "babe_missedSlots")]
+	fn missed_slots(&self, at: Option<Hash>) -> FutureResult<MissedSlots>;
 }
 
 /// Implements the BabeRpc trait for interacting with Babe.
@@ -93,7 +116,23 @@ impl<B: BlockT, C, SC> BabeRpcHandler<B, C, SC> {
 	}
 }
 
-impl<B, C, SC> BabeApi for BabeRpcHandler<B, C, SC>
+impl<B: BlockT, C, SC> BabeRpcHandler<B, C, SC>
+	where
+		C: HeaderBackend<B> + 'static,
+		SC: SelectChain<B>,
+{
+	/// Returns the header of the given block, or of the best block if none is given.
+	fn header_at(&self, at: Option<B::Hash>) -> Result<B::Header, Error> {
+		match at {
+			Some(hash) => self.client.header(BlockId::Hash(hash))
+				.map_err(|e| Error::StringError(format!("{:?}", e)))?
+				.ok_or_else(|| Error::StringError(format!("Unknown block {:?}", hash))),
+			None => self.select_chain.best_chain().map_err(Error::Consensus),
+		}
+	}
+}
+
+impl<B, C, SC> BabeApi<B::Hash> for BabeRpcHandler<B, C, SC>
 	where
 		B: BlockT,
 		C: ProvideRuntimeApi<B> + HeaderBackend<B> + HeaderMetadata<B, Error=BlockChainError> + 'static,
@@ -125,7 +164,7 @@ impl<B, C, SC> BabeApi for BabeRpcHandler<B, C, SC>
 				.map_err(|err| {
 					Error::StringError(format!("{:?}", err))
 				})?;
-			let epoch = epoch_data(&shared_epoch, &client, &babe_config, epoch_start, &select_chain)?;
+			let epoch = epoch_data(&shared_epoch, &client, &babe_config, epoch_start, &header)?;
 			let (epoch_start, epoch_end) = (epoch.start_slot(), epoch.end_slot());
 
 			let mut claims: HashMap<AuthorityId, EpochAuthorship> = HashMap::new();
@@ -167,6 +206,161 @@ impl<B, C, SC> BabeApi for BabeRpcHandler<B, C, SC>
 
 		Box::new(future.compat())
 	}
+
+	fn epochs(&self, at: Option<B::Hash>) -> FutureResult<Epochs> {
+		let result = self.header_at(at).and_then(|header| {
+			let (current, next) = current_and_next_epoch(
+				&self.shared_epoch_changes,
+				&self.client,
+				&self.babe_config,
+				&header,
+			)?;
+
+			Ok(Epochs {
+				current: EpochDescriptor::from(&current),
+				next: next.as_ref().map(EpochDescriptor::from),
+			})
+		});
+
+		Box::new(rpc_future::result(result.map_err(Into::into)))
+	}
+
+	fn next_epoch_slot_claims(
+		&self,
+		at: Option<B::Hash>,
+	) -> FutureResult<HashMap<AuthorityId, EpochAuthorship>> {
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return Box::new(rpc_future::err(err.into()));
+		}
+
+		let result = self.header_at(at).and_then(|header| {
+			let (_, next) = current_and_next_epoch(
+				&self.shared_epoch_changes,
+				&self.client,
+				&self.babe_config,
+				&header,
+			)?;
+			let epoch = next.ok_or_else(|| Error::StringError(
+				"The next epoch is not yet known at the given block".into()
+			))?;
+
+			let keys = {
+				let ks = self.keystore.read();
+				epoch.authorities.iter()
+					.enumerate()
+					.filter(|(_, a)| ks.has_keys(&[(a.0.to_raw_vec(), AuthorityId::ID)]))
+					.map(|(i, a)| (a.0.clone(), i))
+					.collect::<Vec<_>>()
+			};
+
+			let mut claims: HashMap<AuthorityId, EpochAuthorship> = epoch.authorities.iter()
+				.map(|(id, _)| (id.clone(), EpochAuthorship::default()))
+				.collect();
+
+			let secondary_slots_allowed =
+				epoch.config.allowed_slots.is_secondary_plain_slots_allowed() ||
+				epoch.config.allowed_slots.is_secondary_vrf_slots_allowed();
+
+			for slot_number in epoch.start_slot()..epoch.end_slot() {
+				let primary = authorship::claim_slot_using_keys(
+					slot_number,
+					&epoch,
+					&self.keystore,
+					&keys,
+				).filter(|(claim, _)| matches!(claim, PreDigest::Primary { .. }));
+
+				if let Some((_, key)) = primary {
+					claims.entry(key).or_default().primary.push(slot_number);
+				} else if secondary_slots_allowed {
+					let author = authorship::secondary_slot_author(
+						slot_number,
+						&epoch.authorities,
+						epoch.randomness,
+					);
+
+					if let Some(author) = author {
+						let authorship = claims.entry(author.clone()).or_default();
+						if epoch.config.allowed_slots.is_secondary_vrf_slots_allowed() {
+							authorship.secondary_vrf.push(slot_number);
+						} else {
+							authorship.secondary.push(slot_number);
+						}
+					}
+				}
+			}
+
+			Ok(claims)
+		});
+
+		Box::new(rpc_future::result(result.map_err(Into::into)))
+	}
+
+	fn missed_slots(&self, at: Option<B::Hash>) -> FutureResult<MissedSlots> {
+		// walking back the chain to the start of the epoch is expensive.
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return Box::new(rpc_future::err(err.into()));
+		}
+
+		let result = self.header_at(at).and_then(|header| {
+			let (epoch, _) = current_and_next_epoch(
+				&self.shared_epoch_changes,
+				&self.client,
+				&self.babe_config,
+				&header,
+			)?;
+
+			let secondary_slots_allowed =
+				epoch.config.allowed_slots.is_secondary_plain_slots_allowed() ||
+				epoch.config.allowed_slots.is_secondary_vrf_slots_allowed();
+
+			let mut missed_slots = MissedSlots {
+				epoch_index: epoch.epoch_index,
+				empty_slots: 0,
+				missed: epoch.authorities.iter().map(|(id, _)| (id.clone(), 0)).collect(),
+			};
+
+			// walk back the chain until the start of the epoch, accounting every
+			// slot between two consecutive blocks as empty.
+			let mut current = header;
+			let mut current_slot = slot_number::<B>(&current)?;
+			while !current.number().is_zero() && current_slot > epoch.start_slot() {
+				let parent = self.client.header(BlockId::Hash(*current.parent_hash()))
+					.map_err(|e| Error::StringError(format!("{:?}", e)))?
+					.ok_or_else(|| Error::StringError(
+						format!("Missing header {:?}", current.parent_hash())
+					))?;
+				let parent_slot = slot_number::<B>(&parent)?
+					.max(epoch.start_slot().saturating_sub(1));
+
+				for slot in parent_slot + 1..current_slot {
+					missed_slots.empty_slots += 1;
+
+					// with secondary slots enabled every slot has an expected
+					// author, otherwise we can't attribute the empty slot.
+					if !secondary_slots_allowed {
+						continue;
+					}
+
+					let author = authorship::secondary_slot_author(
+						slot,
+						&epoch.authorities,
+						epoch.randomness,
+					);
+
+					if let Some(author) = author {
+						*missed_slots.missed.entry(author.clone()).or_default() += 1;
+					}
+				}
+
+				current = parent;
+				current_slot = slot_number::<B>(&current)?;
+			}
+
+			Ok(missed_slots)
+		});
+
+		Box::new(rpc_future::result(result.map_err(Into::into)))
+	}
 }
 
 /// Holds information about the `slot_number`'s that can be claimed by a given key.
@@ -180,6 +374,108 @@ pub struct EpochAuthorship {
 	secondary_vrf: Vec<u64>,
 }
 
+/// The current and next epoch at a given block.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Epochs {
+	/// The epoch the block belongs to.
+	current: EpochDescriptor,
+	/// The epoch following the current one, if already announced.
+	next: Option<EpochDescriptor>,
+}
+
+/// Types of slots allowed in an epoch.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EpochAllowedSlots {
+	/// Only primary slots are allowed.
+	PrimarySlots,
+	/// Primary and secondary plain slots are allowed.
+	PrimaryAndSecondaryPlainSlots,
+	/// Primary and secondary VRF slots are allowed.
+	PrimaryAndSecondaryVrfSlots,
+}
+
+/// An authority of an epoch, alongside its primary slot VRF threshold.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpochAuthority {
+	/// The authority id.
+	id: AuthorityId,
+	/// The authority weight.
+	weight: u64,
+	/// The VRF output threshold (as a hex-encoded 128-bit number) under which the
+	/// authority can claim a primary slot.
+	primary_threshold: String,
+}
+
+/// Describes a BABE epoch.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpochDescriptor {
+	/// The epoch index.
+	epoch_index: u64,
+	/// The starting slot of the epoch.
+	start_slot: u64,
+	/// The duration of this epoch in slots.
+	duration: u64,
+	/// The authorities of the epoch.
+	authorities: Vec<EpochAuthority>,
+	/// Randomness for this epoch.
+	randomness: Bytes,
+	/// The `c` constant used in the primary threshold calculation.
+	c: (u64, u64),
+	/// Types of slots allowed in this epoch.
+	allowed_slots: EpochAllowedSlots,
+}
+
+impl From<&Epoch> for EpochDescriptor {
+	fn from(epoch: &Epoch) -> Self {
+		let authorities = epoch.authorities.iter()
+			.enumerate()
+			.map(|(i, (id, weight))| EpochAuthority {
+				id: id.clone(),
+				weight: *weight,
+				primary_threshold: format!(
+					"{:#034x}",
+					authorship::calculate_primary_threshold(epoch.config.c, &epoch.authorities, i),
+				),
+			})
+			.collect();
+
+		EpochDescriptor {
+			epoch_index: epoch.epoch_index,
+			start_slot: epoch.start_slot,
+			duration: epoch.duration,
+			authorities,
+			randomness: Bytes(epoch.randomness.to_vec()),
+			c: epoch.config.c,
+			allowed_slots: match epoch.config.allowed_slots {
+				AllowedSlots::PrimarySlots =>
+					EpochAllowedSlots::PrimarySlots,
+				AllowedSlots::PrimaryAndSecondaryPlainSlots =>
+					EpochAllowedSlots::PrimaryAndSecondaryPlainSlots,
+				AllowedSlots::PrimaryAndSecondaryVRFSlots =>
+					EpochAllowedSlots::PrimaryAndSecondaryVrfSlots,
+			},
+		}
+	}
+}
+
+/// Estimate of the slots missed in an epoch. Empty slots are attributed to
+/// their expected secondary slot author, which is only possible if the epoch
+/// allows secondary slots.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissedSlots {
+	/// The index of the epoch.
+	epoch_index: u64,
+	/// Total number of slots without a block in the epoch so far.
+	empty_slots: u64,
+	/// Number of empty slots attributed to each authority.
+	missed: HashMap<AuthorityId, u64>,
+}
+
 /// Errors encountered by the RPC
 #[derive(Debug, derive_more::Display, derive_more::From)]
 pub enum Error {
@@ -199,20 +495,18 @@ impl From<Error> for jsonrpc_core::Error {
 	}
 }
 
-/// fetches the epoch data for a given slot_number.
-fn epoch_data<B, C, SC>(
+/// fetches the epoch data for a given slot_number, for a child of the given parent.
+fn epoch_data<B, C>(
 	epoch_changes: &SharedEpochChanges<B, Epoch>,
 	client: &Arc<C>,
 	babe_config: &Config,
 	slot_number: u64,
-	select_chain: &SC,
+	parent: &B::Header,
 ) -> Result<Epoch, Error>
 	where
 		B: BlockT,
 		C: HeaderBackend<B> + HeaderMetadata<B, Error=BlockChainError> + 'static,
-		SC: SelectChain<B>,
 {
-	let parent = select_chain.best_chain()?;
 	epoch_changes.lock().epoch_data_for_child_of(
 		descendent_query(&**client),
 		&parent.hash(),
@@ -224,6 +518,47 @@ fn epoch_data<B, C, SC>(
 		.ok_or(Error::Consensus(ConsensusError::InvalidAuthoritiesSet))
 }
 
+/// extracts the slot number from the BABE pre-digest of the given header.
+fn slot_number<B: BlockT>(header: &B::Header) -> Result<u64, Error> {
+	// genesis block doesn't contain a pre digest.
+	if header.number().is_zero() {
+		return Ok(0);
+	}
+
+	header.digest().logs().iter()
+		.find_map(|log| log.as_babe_pre_digest())
+		.map(|pre_digest| pre_digest.slot_number())
+		.ok_or_else(|| Error::StringError(
+			format!("No BABE pre-runtime digest found in {:?}", header.hash())
+		))
+}
+
+/// fetches the epoch the given block belongs to, as well as the next epoch if
+/// it was already announced.
+fn current_and_next_epoch<B, C>(
+	epoch_changes: &SharedEpochChanges<B, Epoch>,
+	client: &Arc<C>,
+	babe_config: &Config,
+	header: &B::Header,
+) -> Result<(Epoch, Option<Epoch>), Error>
+	where
+		B: BlockT,
+		C: HeaderBackend<B> + HeaderMetadata<B, Error=BlockChainError> + 'static,
+{
+	let current = epoch_data(epoch_changes, client, babe_config, slot_number::<B>(header)?, header)?;
+
+	// the next epoch is only announced in the first block of the current epoch.
+	if header.number().is_zero() {
+		return Ok((current, None));
+	}
+
+	let next = epoch_data(epoch_changes, client, babe_config, current.end_slot(), header)
+		.ok()
+		.filter(|next| next.epoch_index == current.epoch_index + 1);
+
+	Ok((current, next))
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -239,8 +574,18 @@ mod tests {
 	use sp_keyring::Ed25519Keyring;
 	use sc_keystore::Store;
 
-	use std::sync::Arc;
-	use sc_consensus_babe::{Config, block_import, AuthorityPair};
+	use std::{any::Any, borrow::Cow, sync::Arc};
+	use codec::Encode;
+	use sc_block_builder::BlockBuilderProvider;
+	use sc_consensus_babe::{
+		Config, block_import, AuthorityPair, BabeIntermediate, INTERMEDIATE_KEY,
+	};
+	use sp_consensus::{BlockImport, BlockImportParams, BlockOrigin, ForkChoiceStrategy};
+	use sp_consensus_babe::{
+		BABE_ENGINE_ID, ConsensusLog,
+		digests::{NextEpochDescriptor, SecondaryPlainPreDigest},
+	};
+	use sp_runtime::{DigestItem, generic::Digest};
 	use jsonrpc_core::IoHandler;
 
 	/// creates keystore backed by a temp file
@@ -291,6 +636,191 @@ mod tests {
 		assert_eq!(Some(response.into()), io.handle_request_sync(request));
 	}
 
+	#[test]
+	fn epochs_works_at_genesis() {
+		let handler = test_babe_rpc_handler(DenyUnsafe::No);
+		let mut io = IoHandler::new();
+
+		io.extend_with(BabeApi::to_delegate(handler));
+		let request = r#"{"jsonrpc":"2.0","method":"babe_epochs","params": [],"id":1}"#;
+
+		let response = io.handle_request_sync(request).unwrap();
+		let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+		let result = &response["result"];
+
+		assert_eq!(result["current"]["epochIndex"], 0);
+		assert_eq!(result["current"]["startSlot"], 0);
+		assert_eq!(result["current"]["authorities"].as_array().unwrap().len(), 3);
+		assert!(result["next"].is_null());
+	}
+
+	#[test]
+	fn missed_slots_works_at_genesis() {
+		let handler = test_babe_rpc_handler(DenyUnsafe::No);
+		let mut io = IoHandler::new();
+
+		io.extend_with(BabeApi::to_delegate(handler));
+		let request = r#"{"jsonrpc":"2.0","method":"babe_missedSlots","params": [],"id":1}"#;
+
+		let response = io.handle_request_sync(request).unwrap();
+		let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+		let result = &response["result"];
+
+		assert_eq!(result["epochIndex"], 0);
+		assert_eq!(result["emptySlots"], 0);
+		assert!(result["missed"].as_object().unwrap().values().all(|missed| missed == 0));
+	}
+
+	/// builds a child of `parent` authored at the given slot and imports it
+	/// through the BABE block import.
+	fn import_block_at_slot<I: BlockImport<Block, Error = ConsensusError>>(
+		client: &Arc<TestClient>,
+		block_import: &mut I,
+		epoch_changes: &SharedEpochChanges<Block, Epoch>,
+		config: &Config,
+		parent: &<Block as BlockT>::Header,
+		slot_number: u64,
+	) -> <Block as BlockT>::Header {
+		let pre_digest = Digest {
+			logs: vec![
+				DigestItem::babe_pre_digest(PreDigest::SecondaryPlain(SecondaryPlainPreDigest {
+					authority_index: 0,
+					slot_number,
+				})),
+			],
+		};
+
+		let mut block = client.new_block_at(&BlockId::Hash(parent.hash()), pre_digest, false)
+			.unwrap()
+			.build()
+			.unwrap()
+			.block;
+
+		let epoch_descriptor = epoch_changes.lock().epoch_descriptor_for_child_of(
+			descendent_query(&**client),
+			&parent.hash(),
+			*parent.number(),
+			slot_number,
+		).unwrap().unwrap();
+
+		// the test runtime doesn't announce the next epoch, reuse the genesis one.
+		if parent.number().is_zero() {
+			let epoch = Epoch::genesis(config, slot_number);
+			let digest = ConsensusLog::NextEpochData(NextEpochDescriptor {
+				authorities: epoch.authorities,
+				randomness: epoch.randomness,
+			}).encode();
+			block.header.digest_mut().push(DigestItem::Consensus(BABE_ENGINE_ID, digest));
+		}
+
+		let mut import = BlockImportParams::new(BlockOrigin::Own, block.header.clone());
+		import.body = Some(block.extrinsics);
+		import.intermediates.insert(
+			Cow::from(INTERMEDIATE_KEY),
+			Box::new(BabeIntermediate::<Block> { epoch_descriptor }) as Box<dyn Any>,
+		);
+		import.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+		block_import.import_block(import, Default::default()).unwrap();
+
+		block.header
+	}
+
+	#[test]
+	fn missed_slots_are_attributed_to_secondary_authors() {
+		let (client, longest_chain) = TestClientBuilder::new().build_with_longest_chain();
+		let client = Arc::new(client);
+		let config = Config::get_or_compute(&*client).expect("config available");
+		let (mut block_import, link) = block_import(
+			config.clone(),
+			client.clone(),
+			client.clone(),
+		).expect("can initialize block-import");
+		let epoch_changes = link.epoch_changes().clone();
+
+		// blocks at slots 1, 3 and 6 of the genesis epoch, slots 2, 4 and 5 are
+		// left empty.
+		let mut head = client.header(BlockId::Number(0)).unwrap().unwrap();
+		for slot_number in &[1, 3, 6] {
+			head = import_block_at_slot(
+				&client,
+				&mut block_import,
+				&epoch_changes,
+				&config,
+				&head,
+				*slot_number,
+			);
+		}
+
+		let epoch = Epoch::genesis(&config, 1);
+		let mut expected: HashMap<AuthorityId, u64> = epoch.authorities.iter()
+			.map(|(id, _)| (id.clone(), 0))
+			.collect();
+		for slot_number in &[2, 4, 5] {
+			let author = authorship::secondary_slot_author(
+				*slot_number,
+				&epoch.authorities,
+				epoch.randomness,
+			).unwrap();
+			*expected.get_mut(author).unwrap() += 1;
+		}
+
+		let handler = BabeRpcHandler::new(
+			client.clone(),
+			epoch_changes,
+			create_temp_keystore::<AuthorityPair>(Ed25519Keyring::Alice).0,
+			config,
+			longest_chain,
+			DenyUnsafe::No,
+		);
+		let mut io = IoHandler::new();
+		io.extend_with(BabeApi::to_delegate(handler));
+
+		let request = format!(
+			r#"{{"jsonrpc":"2.0","method":"babe_missedSlots","params":["{:?}"],"id":1}}"#,
+			head.hash(),
+		);
+		let response = io.handle_request_sync(&request).unwrap();
+		let mut response: serde_json::Value = serde_json::from_str(&response).unwrap();
+		let result = response["result"].take();
+
+		assert_eq!(result["epochIndex"], 0);
+		assert_eq!(result["emptySlots"], 3);
+		let missed: HashMap<AuthorityId, u64> = serde_json::from_value(result["missed"].clone())
+			.unwrap();
+		assert_eq!(missed.len(), 3);
+		assert_eq!(missed, expected);
+	}
+
+	#[test]
+	fn missed_slots_is_unsafe() {
+		let handler = test_babe_rpc_handler(DenyUnsafe::Yes);
+		let mut io = IoHandler::new();
+
+		io.extend_with(BabeApi::to_delegate(handler));
+		let request = r#"{"jsonrpc":"2.0","method":"babe_missedSlots","params": [],"id":1}"#;
+
+		let response = io.handle_request_sync(request).unwrap();
+		let mut response: serde_json::Value = serde_json::from_str(&response).unwrap();
+		let error: RpcError = serde_json::from_value(response["error"].take()).unwrap();
+
+		assert_eq!(error, RpcError::method_not_found())
+	}
+
+	#[test]
+	fn next_epoch_slot_claims_is_unsafe() {
+		let handler = test_babe_rpc_handler(DenyUnsafe::Yes);
+		let mut io = IoHandler::new();
+
+		io.extend_with(BabeApi::to_delegate(handler));
+		let request = r#"{"jsonrpc":"2.0","method":"babe_nextEpochSlotClaims","params": [],"id":1}"#;
+
+		let response = io.handle_request_sync(request).unwrap();
+		let mut response: serde_json::Value = serde_json::from_str(&response).unwrap();
+		let error: RpcError = serde_json::from_value(response["error"].take()).unwrap();
+
+		assert_eq!(error, RpcError::method_not_found())
+	}
+
 	#[test]
 	fn epoch_authorship_is_unsafe() {
 		let handler = test_babe_rpc_handler(DenyUnsafe::Yes);
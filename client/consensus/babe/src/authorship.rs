@@ -39,7 +39,7 @@ use super::Epoch;
 
 /// Calculates the primary selection threshold for a given authority, taking
 /// into account `c` (`1 - c` represents the probability of a slot being empty).
-pub(super) fn calculate_primary_threshold(
+pub fn calculate_primary_threshold(
 	c: (u64, u64),
 	authorities: &[(AuthorityId, BabeAuthorityWeight)],
 	authority_index: usize,
@@ -103,7 +103,7 @@ pub(super) fn check_primary_threshold(inout: &VRFInOut, threshold: u128) -> bool
 /// Get the expected secondary author for the given slot and with given
 /// authorities. This should always assign the slot to some authority unless the
 /// authorities list is empty.
-pub(super) fn secondary_slot_author(
+pub fn secondary_slot_author(
 	slot_number: u64,
 	authorities: &[(AuthorityId, BabeAuthorityWeight)],
 	randomness: [u8; 32],
@@ -0,0 +1,210 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Sassafras slot claiming and ticket generation.
+
+use sp_application_crypto::AppKey;
+use sp_consensus_sassafras::{
+	AuthorityId, Epoch, SassafrasConfiguration, SlotNumber, Ticket, TicketEnvelope, TicketId,
+	SASSAFRAS_TICKET_VRF_PREFIX, compute_ticket_threshold, make_slot_transcript_data,
+	make_ticket_transcript, make_ticket_transcript_data,
+	digests::PreDigest,
+};
+use sp_consensus_vrf::schnorrkel::{VRFOutput, VRFProof};
+use sp_core::{crypto::Public, traits::BareCryptoStore};
+use schnorrkel::keys::PublicKey;
+use sc_keystore::KeyStorePtr;
+
+/// Tries to claim the given slot number. The slot belongs to the owner of
+/// `ticket` if there is a ticket assigned to the slot, and to the round-robin
+/// fallback author of the epoch otherwise. Returns `None` if we don't own the
+/// key of the slot author.
+pub fn claim_slot(
+	slot_number: SlotNumber,
+	epoch: &Epoch,
+	ticket: Option<Ticket>,
+	keystore: &KeyStorePtr,
+) -> Option<(PreDigest, AuthorityId)> {
+	let (authority_index, ticket_attempt) = match ticket {
+		Some(ticket) => (ticket.authority_index, Some(ticket.attempt)),
+		None => (epoch.fallback_author(slot_number)?, None),
+	};
+
+	let authority_id = epoch.authorities.get(authority_index as usize)?;
+
+	let transcript_data = make_slot_transcript_data(
+		&epoch.randomness,
+		slot_number,
+		epoch.epoch_index,
+	);
+	let signature = keystore.read().sr25519_vrf_sign(
+		AuthorityId::ID,
+		authority_id.as_ref(),
+		transcript_data,
+	).ok()?;
+
+	let pre_digest = PreDigest {
+		authority_index,
+		slot_number,
+		ticket_attempt,
+		vrf_output: VRFOutput(signature.output),
+		vrf_proof: VRFProof(signature.proof),
+	};
+
+	Some((pre_digest, authority_id.clone()))
+}
+
+/// Generates the winning tickets of all our authority keys for the given
+/// epoch, i.e. the tickets whose identifier is below the ticket threshold.
+pub fn generate_tickets(
+	epoch: &Epoch,
+	config: &SassafrasConfiguration,
+	keystore: &KeyStorePtr,
+) -> Vec<TicketEnvelope> {
+	let threshold = compute_ticket_threshold(
+		config.redundancy_factor,
+		epoch.duration,
+		config.max_attempts,
+		epoch.authorities.len(),
+	);
+
+	let mut tickets = Vec::new();
+	for (authority_index, authority_id) in epoch.authorities.iter().enumerate() {
+		if !keystore.read().has_keys(&[(authority_id.to_raw_vec(), AuthorityId::ID)]) {
+			continue;
+		}
+
+		let public = match PublicKey::from_bytes(&authority_id.to_raw_vec()) {
+			Ok(public) => public,
+			Err(_) => continue,
+		};
+
+		for attempt in 0..config.max_attempts {
+			let transcript_data = make_ticket_transcript_data(
+				&epoch.randomness,
+				attempt,
+				epoch.epoch_index,
+			);
+			let signature = match keystore.read().sr25519_vrf_sign(
+				AuthorityId::ID,
+				authority_id.as_ref(),
+				transcript_data,
+			) {
+				Ok(signature) => signature,
+				Err(_) => break,
+			};
+
+			let transcript = make_ticket_transcript(&epoch.randomness, attempt, epoch.epoch_index);
+			let inout = match signature.output.attach_input_hash(&public, transcript) {
+				Ok(inout) => inout,
+				Err(_) => continue,
+			};
+
+			let id = TicketId::from_le_bytes(inout.make_bytes::<[u8; 16]>(SASSAFRAS_TICKET_VRF_PREFIX));
+			if id < threshold {
+				tickets.push(TicketEnvelope {
+					authority_index: authority_index as u32,
+					attempt,
+					vrf_output: VRFOutput(signature.output),
+					vrf_proof: VRFProof(signature.proof),
+				});
+			}
+		}
+	}
+
+	tickets
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_consensus_sassafras::verify_ticket;
+	use sp_core::crypto::Pair;
+	use sp_keyring::sr25519::Keyring;
+
+	fn epoch(authorities: Vec<AuthorityId>) -> Epoch {
+		Epoch {
+			epoch_index: 1,
+			start_slot: 6,
+			duration: 6,
+			authorities,
+			randomness: [7; 32],
+		}
+	}
+
+	#[test]
+	fn claims_ticket_and_fallback_slots() {
+		let keystore = sc_keystore::Store::new_in_memory();
+		let local: AuthorityId = keystore.write()
+			.generate_by_type::<sp_consensus_sassafras::AuthorityPair>(AuthorityId::ID)
+			.expect("Key should be created")
+			.public();
+
+		let epoch = epoch(vec![Keyring::Alice.public().into(), local.clone()]);
+
+		// fallback slots are assigned round-robin.
+		assert!(claim_slot(6, &epoch, None, &keystore).is_none());
+		let (pre_digest, author) = claim_slot(7, &epoch, None, &keystore).unwrap();
+		assert_eq!(author, local);
+		assert_eq!(pre_digest.authority_index, 1);
+		assert_eq!(pre_digest.ticket_attempt, None);
+
+		// a ticket takes precedence over the fallback author.
+		let ticket = Ticket { id: 0, authority_index: 1, attempt: 3 };
+		let (pre_digest, _) = claim_slot(6, &epoch, Some(ticket), &keystore).unwrap();
+		assert_eq!(pre_digest.ticket_attempt, Some(3));
+
+		let ticket = Ticket { id: 0, authority_index: 0, attempt: 0 };
+		assert!(claim_slot(7, &epoch, Some(ticket), &keystore).is_none());
+	}
+
+	#[test]
+	fn generated_tickets_are_valid() {
+		let keystore = sc_keystore::Store::new_in_memory();
+		let local: AuthorityId = keystore.write()
+			.generate_by_type::<sp_consensus_sassafras::AuthorityPair>(AuthorityId::ID)
+			.expect("Key should be created")
+			.public();
+
+		let epoch = epoch(vec![Keyring::Alice.public().into(), local.clone()]);
+		let config = SassafrasConfiguration {
+			slot_duration: 1000,
+			epoch_duration: 6,
+			max_attempts: 8,
+			redundancy_factor: 2,
+		};
+
+		let tickets = generate_tickets(&epoch, &config, &keystore);
+		let threshold = compute_ticket_threshold(2, 6, 8, 2);
+
+		// each attempt wins with probability 3/4.
+		assert!(!tickets.is_empty());
+		for ticket in tickets {
+			assert_eq!(ticket.authority_index, 1);
+			assert!(ticket.attempt < 8);
+
+			let id = verify_ticket(&local, &epoch.randomness, epoch.epoch_index, &ticket)
+				.expect("generated tickets have a valid proof");
+			assert!(id < threshold);
+		}
+
+		// no tickets are generated without attempts.
+		let config = SassafrasConfiguration { max_attempts: 0, ..config };
+		assert!(generate_tickets(&epoch, &config, &keystore).is_empty());
+	}
+}
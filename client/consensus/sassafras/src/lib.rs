@@ -0,0 +1,863 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! # Sassafras
+//!
+//! Experimental constant-time block production engine. During every epoch,
+//! the authorities generate VRF tickets for the next epoch and submit the
+//! winning ones on-chain. At the start of the next epoch the runtime turns
+//! the winning tickets into a slot schedule, and every slot is claimed by the
+//! owner of the ticket assigned to it. Slots without a ticket are claimed by
+//! a round-robin fallback author, so that there is exactly one author per slot.
+//!
+//! The epoch data (authorities, randomness and slot schedule) is read from
+//! the runtime state of the parent block, see `SassafrasApi`.
+//!
+//! WARNING: this engine does NOT provide slot owner anonymity. Tickets are
+//! meant to be generated with a ring-VRF, which keeps slot owners anonymous
+//! until they claim their slot. Ring-VRFs are not available yet, so tickets
+//! carry the index of the authority that generated them and the whole slot
+//! schedule of an epoch is public as soon as the tickets are on-chain.
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+
+use std::{sync::Arc, time::Duration, thread, pin::Pin, convert::TryInto};
+
+use futures::prelude::*;
+use parking_lot::Mutex;
+use log::{debug, info, trace, warn};
+use schnorrkel::PublicKey;
+
+use sp_consensus::{
+	self, BlockImport, Environment, Proposer, CanAuthorWith, ForkChoiceStrategy, BlockImportParams,
+	BlockOrigin, Error as ConsensusError, SelectChain, SlotData,
+};
+use sp_consensus::import_queue::{
+	Verifier, BasicQueue, DefaultImportQueue, BoxJustificationImport, BoxFinalityProofImport,
+};
+use sc_client_api::{backend::AuxStore, BlockOf};
+use sp_blockchain::{Result as ClientResult, well_known_cache_keys::Id as CacheKeyId, HeaderBackend};
+use sp_block_builder::BlockBuilder as BlockBuilderApi;
+use sp_core::{Pair, crypto::Public};
+use sp_application_crypto::AppKey;
+use sp_runtime::{generic::BlockId, Justification};
+use sp_runtime::traits::{Block as BlockT, Header, DigestItemFor, Zero};
+use sp_api::{ApiExt, ProvideRuntimeApi};
+use sp_inherents::{InherentDataProviders, InherentData};
+use sp_timestamp::{
+	TimestampInherentData, InherentType as TimestampInherent, InherentError as TIError
+};
+use sc_telemetry::{telemetry, CONSENSUS_TRACE, CONSENSUS_DEBUG, CONSENSUS_INFO};
+use sc_consensus_slots::{
+	CheckedHeader, SlotWorker, SlotInfo, SlotCompatible, StorageChanges, check_equivocation,
+};
+use sc_keystore::KeyStorePtr;
+
+pub use sp_consensus_sassafras::{
+	SassafrasApi, SassafrasConfiguration, Epoch, Ticket, TicketEnvelope, AuthorityId,
+	AuthorityPair, AuthoritySignature, SlotNumber, SASSAFRAS_ENGINE_ID, make_slot_transcript,
+	digests::{CompatibleDigestItem, PreDigest},
+	inherents::{SassafrasInherentData, InherentDataProvider, INHERENT_IDENTIFIER},
+};
+pub use sp_consensus::SyncOracle;
+
+pub mod authorship;
+#[cfg(test)]
+mod tests;
+
+/// The Sassafras configuration, read from the runtime at genesis.
+pub type Config = sc_consensus_slots::SlotDuration<SassafrasConfiguration>;
+
+/// Either fetch the Sassafras configuration from disk or compute it from the
+/// genesis state.
+pub fn configuration<B, C>(client: &C) -> ClientResult<Config> where
+	B: BlockT,
+	C: AuxStore + ProvideRuntimeApi<B>,
+	C::Api: SassafrasApi<B, Error = sp_blockchain::Error>,
+{
+	trace!(target: "sassafras", "Getting configuration");
+	Config::get_or_compute(client, |a, b| a.configuration(b))
+}
+
+#[derive(derive_more::Display, Debug)]
+enum Error<B: BlockT> {
+	#[display(fmt = "Multiple Sassafras pre-runtime headers")]
+	MultiplePreRuntimeDigests,
+	#[display(fmt = "No Sassafras pre-runtime digest found")]
+	NoPreRuntimeDigest,
+	#[display(fmt = "Header {:?} is unsealed", _0)]
+	HeaderUnsealed(B::Hash),
+	#[display(fmt = "Header {:?} has a bad seal", _0)]
+	HeaderBadSeal(B::Hash),
+	#[display(fmt = "Slot {} was claimed by an unexpected author", _0)]
+	UnexpectedAuthor(SlotNumber),
+	#[display(fmt = "Bad signature on {:?}", _0)]
+	BadSignature(B::Hash),
+	#[display(fmt = "VRF verification failed: {:?}", _0)]
+	VRFVerificationFailed(schnorrkel::SignatureError),
+	#[display(fmt = "Rejecting block too far in future")]
+	TooFarInFuture,
+	#[display(fmt = "Slot number must increase: parent slot: {}, this slot: {}", _0, _1)]
+	SlotNumberMustIncrease(SlotNumber, SlotNumber),
+	#[display(fmt = "Parent ({}) of {} unavailable. Cannot import", _0, _1)]
+	ParentUnavailable(B::Hash, B::Hash),
+	Client(sp_blockchain::Error),
+	DataProvider(String),
+	Runtime(String),
+}
+
+impl<B: BlockT> std::convert::From<Error<B>> for String {
+	fn from(error: Error<B>) -> String {
+		error.to_string()
+	}
+}
+
+fn sassafras_err<B: BlockT>(error: Error<B>) -> Error<B> {
+	debug!(target: "sassafras", "{}", error);
+	error
+}
+
+/// Extract the Sassafras pre digest from the given header. Pre-runtime digests
+/// are mandatory, the function will return `Err` if none is found.
+pub fn find_pre_digest<B: BlockT>(header: &B::Header) -> Result<PreDigest, String> {
+	let mut pre_digest: Option<_> = None;
+	for log in header.digest().logs() {
+		trace!(target: "sassafras", "Checking log {:?}, looking for pre runtime digest", log);
+		match (log.as_sassafras_pre_digest(), pre_digest.is_some()) {
+			(Some(_), true) => return Err(Error::<B>::MultiplePreRuntimeDigests.into()),
+			(None, _) => trace!(target: "sassafras", "Ignoring digest not meant for us"),
+			(s, false) => pre_digest = s,
+		}
+	}
+	pre_digest.ok_or_else(|| Error::<B>::NoPreRuntimeDigest.into())
+}
+
+/// Returns the epoch the given slot belongs to, for a block built on top of
+/// `parent`. The slot belongs to the next epoch of the parent once the
+/// current epoch of the parent has ended. Epochs without any block are
+/// skipped: the epoch index and start slot are derived from the slot, and
+/// the slot is claimed with the authorities and randomness of the next epoch
+/// of the parent.
+pub fn epoch_for_slot<B, C>(
+	client: &C,
+	parent: &B::Header,
+	slot_number: SlotNumber,
+) -> ClientResult<Epoch> where
+	B: BlockT,
+	C: ProvideRuntimeApi<B>,
+	C::Api: SassafrasApi<B, Error = sp_blockchain::Error>,
+{
+	let at = BlockId::Hash(parent.hash());
+	let epoch = client.runtime_api().current_epoch(&at)?;
+
+	// the first epoch actually starts at the slot of block #1.
+	if parent.number().is_zero() {
+		return Ok(Epoch { start_slot: slot_number, ..epoch });
+	}
+
+	if slot_number < epoch.end_slot() {
+		return Ok(epoch);
+	}
+
+	let next_epoch = client.runtime_api().next_epoch(&at)?;
+	let skipped_epochs = slot_number.saturating_sub(next_epoch.start_slot)
+		.checked_div(next_epoch.duration)
+		.unwrap_or_default();
+
+	Ok(Epoch {
+		epoch_index: next_epoch.epoch_index.saturating_add(skipped_epochs),
+		start_slot: next_epoch.start_slot
+			.saturating_add(skipped_epochs.saturating_mul(next_epoch.duration)),
+		..next_epoch
+	})
+}
+
+/// Parameters for Sassafras.
+pub struct SassafrasParams<C, E, I, SO, SC, CAW> {
+	/// The keystore that manages the keys of the node.
+	pub keystore: KeyStorePtr,
+
+	/// The client to use
+	pub client: Arc<C>,
+
+	/// The SelectChain Strategy
+	pub select_chain: SC,
+
+	/// The environment we are producing blocks for.
+	pub env: E,
+
+	/// The underlying block-import object to supply our produced blocks to.
+	pub block_import: I,
+
+	/// A sync oracle
+	pub sync_oracle: SO,
+
+	/// Providers for inherent data.
+	pub inherent_data_providers: InherentDataProviders,
+
+	/// Force authoring of blocks even if we are offline
+	pub force_authoring: bool,
+
+	/// The Sassafras configuration.
+	pub config: Config,
+
+	/// Checks if the current native implementation can author with a runtime at a given block.
+	pub can_author_with: CAW,
+}
+
+/// Start the Sassafras worker. The returned future should be run in a futures executor.
+pub fn start_sassafras<B, C, SC, E, I, SO, CAW, Error>(SassafrasParams {
+	keystore,
+	client,
+	select_chain,
+	env,
+	block_import,
+	sync_oracle,
+	inherent_data_providers,
+	force_authoring,
+	config,
+	can_author_with,
+}: SassafrasParams<C, E, I, SO, SC, CAW>) -> Result<
+	impl futures::Future<Output=()>,
+	sp_consensus::Error,
+> where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + BlockOf + Send + Sync + 'static,
+	C::Api: SassafrasApi<B, Error = sp_blockchain::Error>,
+	SC: SelectChain<B> + 'static,
+	E: Environment<B, Error = Error> + Send + Sync + 'static,
+	E::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
+	I: BlockImport<B, Error = ConsensusError, Transaction = sp_api::TransactionFor<C, B>> + Send
+		+ Sync + 'static,
+	Error: std::error::Error + Send + From<ConsensusError> + From<I::Error> + 'static,
+	SO: SyncOracle + Send + Sync + Clone + 'static,
+	CAW: CanAuthorWith<B> + Send + 'static,
+{
+	warn!(
+		target: "sassafras",
+		"⚠️  Sassafras is experimental and slot owners are NOT anonymous, \
+		the slot schedule is public one epoch in advance.",
+	);
+
+	let worker = SassafrasWorker {
+		client,
+		block_import: Arc::new(Mutex::new(block_import)),
+		env,
+		sync_oracle: sync_oracle.clone(),
+		force_authoring,
+		keystore,
+		config: config.clone(),
+		last_ticketed_epoch: Mutex::new(None),
+	};
+
+	register_sassafras_inherent_data_provider(&inherent_data_providers, config.slot_duration())?;
+
+	Ok(sc_consensus_slots::start_slot_worker::<_, _, _, _, _, SassafrasSlotCompatible, _>(
+		config,
+		select_chain,
+		worker,
+		sync_oracle,
+		inherent_data_providers,
+		SassafrasSlotCompatible,
+		can_author_with,
+	))
+}
+
+struct SassafrasWorker<C, E, I, SO> {
+	client: Arc<C>,
+	block_import: Arc<Mutex<I>>,
+	env: E,
+	sync_oracle: SO,
+	force_authoring: bool,
+	keystore: KeyStorePtr,
+	config: Config,
+	/// The last epoch we have submitted tickets for.
+	last_ticketed_epoch: Mutex<Option<u64>>,
+}
+
+impl<B, C, E, I, Error, SO> sc_consensus_slots::SimpleSlotWorker<B> for SassafrasWorker<C, E, I, SO> where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + BlockOf,
+	C::Api: SassafrasApi<B, Error = sp_blockchain::Error>,
+	E: Environment<B, Error = Error>,
+	E::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
+	I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync + 'static,
+	SO: SyncOracle + Send + Clone,
+	Error: std::error::Error + Send + From<ConsensusError> + From<I::Error> + 'static,
+{
+	type EpochData = Epoch;
+	type Claim = (PreDigest, AuthorityId);
+	type SyncOracle = SO;
+	type CreateProposer = Pin<Box<
+		dyn Future<Output = Result<E::Proposer, sp_consensus::Error>> + Send + 'static
+	>>;
+	type Proposer = E::Proposer;
+	type BlockImport = I;
+
+	fn logging_target(&self) -> &'static str {
+		"sassafras"
+	}
+
+	fn block_import(&self) -> Arc<Mutex<Self::BlockImport>> {
+		self.block_import.clone()
+	}
+
+	fn epoch_data(
+		&self,
+		parent: &B::Header,
+		slot_number: u64,
+	) -> Result<Self::EpochData, ConsensusError> {
+		epoch_for_slot(&*self.client, parent, slot_number)
+			.map_err(|e| ConsensusError::ChainLookup(format!("{:?}", e)))
+	}
+
+	fn authorities_len(&self, epoch_data: &Self::EpochData) -> Option<usize> {
+		Some(epoch_data.authorities.len())
+	}
+
+	fn claim_slot(
+		&self,
+		parent_header: &B::Header,
+		slot_number: SlotNumber,
+		epoch_data: &Self::EpochData,
+	) -> Option<Self::Claim> {
+		debug!(target: "sassafras", "Attempting to claim slot {}", slot_number);
+
+		let ticket = match self.client.runtime_api()
+			.slot_ticket(&BlockId::Hash(parent_header.hash()), slot_number)
+		{
+			Ok(ticket) => ticket,
+			Err(e) => {
+				warn!(target: "sassafras", "Unable to fetch the ticket of slot {}: {:?}", slot_number, e);
+				return None;
+			},
+		};
+
+		let s = authorship::claim_slot(slot_number, epoch_data, ticket, &self.keystore);
+
+		if s.is_some() {
+			debug!(
+				target: "sassafras",
+				"Claimed slot {} ({})",
+				slot_number,
+				if ticket.is_some() { "ticket" } else { "fallback" },
+			);
+		}
+
+		s
+	}
+
+	fn notify_slot(
+		&self,
+		parent_header: &B::Header,
+		_slot_number: SlotNumber,
+		epoch_data: &Self::EpochData,
+	) {
+		// tickets are generated once per epoch, for the epoch following the
+		// one of the current slot.
+		let epoch_index = epoch_data.epoch_index + 1;
+		let mut last_ticketed_epoch = self.last_ticketed_epoch.lock();
+		if last_ticketed_epoch.map_or(false, |last| last >= epoch_index) {
+			return;
+		}
+
+		// tickets are only accepted for the next epoch of the chain head, if the
+		// slot already belongs to a later epoch wait for the chain to catch up.
+		let at = BlockId::Hash(parent_header.hash());
+		let next_epoch = match self.client.runtime_api().next_epoch(&at) {
+			Ok(next_epoch) if next_epoch.epoch_index == epoch_index => next_epoch,
+			Ok(_) => return,
+			Err(e) => {
+				warn!(target: "sassafras", "Unable to fetch the next epoch: {:?}", e);
+				return;
+			},
+		};
+
+		let tickets = authorship::generate_tickets(&next_epoch, &self.config, &self.keystore);
+		if tickets.is_empty() {
+			// the tickets only depend on the epoch and our keys, there is no point
+			// in trying again at the next slot.
+			*last_ticketed_epoch = Some(epoch_index);
+			return;
+		}
+
+		// the epoch is only marked as ticketed once the tickets made it into the
+		// transaction pool, so that a failed submission is retried at the next slot.
+		let tickets_len = tickets.len();
+		match self.client.runtime_api().submit_tickets_unsigned_extrinsic(&at, tickets) {
+			Ok(true) => {
+				*last_ticketed_epoch = Some(epoch_index);
+				info!(
+					target: "sassafras",
+					"🎟  Submitted {} tickets for epoch {}",
+					tickets_len,
+					epoch_index,
+				)
+			},
+			Ok(false) => debug!(
+				target: "sassafras",
+				"Runtime refused to submit tickets for epoch {}",
+				epoch_index,
+			),
+			Err(e) => warn!(
+				target: "sassafras",
+				"Unable to submit tickets for epoch {}: {:?}",
+				epoch_index,
+				e,
+			),
+		}
+	}
+
+	fn pre_digest_data(
+		&self,
+		_slot_number: u64,
+		claim: &Self::Claim,
+	) -> Vec<sp_runtime::DigestItem<B::Hash>> {
+		vec![
+			<DigestItemFor<B> as CompatibleDigestItem>::sassafras_pre_digest(claim.0.clone()),
+		]
+	}
+
+	fn block_import_params(&self) -> Box<dyn Fn(
+		B::Header,
+		&B::Hash,
+		Vec<B::Extrinsic>,
+		StorageChanges<I::Transaction, B>,
+		Self::Claim,
+		Self::EpochData,
+	) -> Result<
+		sp_consensus::BlockImportParams<B, I::Transaction>,
+		sp_consensus::Error> + Send + 'static>
+	{
+		let keystore = self.keystore.clone();
+		Box::new(move |header, header_hash, body, storage_changes, (_, public), _epoch| {
+			// sign the pre-sealed hash of the block and then
+			// add it to a digest item.
+			let public_type_pair = public.clone().into();
+			let public = public.to_raw_vec();
+			let signature = keystore.read()
+				.sign_with(
+					<AuthorityId as AppKey>::ID,
+					&public_type_pair,
+					header_hash.as_ref()
+				)
+				.map_err(|e| sp_consensus::Error::CannotSign(
+					public.clone(), e.to_string(),
+				))?;
+			let signature: AuthoritySignature = signature.clone().try_into()
+				.map_err(|_| sp_consensus::Error::InvalidSignature(
+					signature, public
+				))?;
+			let digest_item = <DigestItemFor<B> as CompatibleDigestItem>::sassafras_seal(signature);
+
+			let mut import_block = BlockImportParams::new(BlockOrigin::Own, header);
+			import_block.post_digests.push(digest_item);
+			import_block.body = Some(body);
+			import_block.storage_changes = Some(storage_changes);
+			import_block.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+
+			Ok(import_block)
+		})
+	}
+
+	fn force_authoring(&self) -> bool {
+		self.force_authoring
+	}
+
+	fn sync_oracle(&mut self) -> &mut Self::SyncOracle {
+		&mut self.sync_oracle
+	}
+
+	fn proposer(&mut self, block: &B::Header) -> Self::CreateProposer {
+		Box::pin(self.env.init(block).map_err(|e| {
+			sp_consensus::Error::ClientImport(format!("{:?}", e))
+		}))
+	}
+
+	fn proposing_remaining_duration(
+		&self,
+		parent_head: &B::Header,
+		slot_info: &SlotInfo,
+	) -> Option<Duration> {
+		let slot_remaining = self.slot_remaining_duration(slot_info);
+
+		let parent_slot = match find_pre_digest::<B>(parent_head) {
+			Err(_) => return Some(slot_remaining),
+			Ok(d) => d.slot_number,
+		};
+
+		if let Some(slot_lenience) =
+			sc_consensus_slots::slot_lenience_exponential(parent_slot, slot_info)
+		{
+			debug!(target: "sassafras",
+				"No block for {} slots. Applying exponential lenience of {}s",
+				slot_info.number.saturating_sub(parent_slot + 1),
+				slot_lenience.as_secs(),
+			);
+
+			Some(slot_remaining + slot_lenience)
+		} else {
+			Some(slot_remaining)
+		}
+	}
+}
+
+impl<B, C, E, I, Error, SO> SlotWorker<B> for SassafrasWorker<C, E, I, SO> where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + BlockOf + Send + Sync,
+	C::Api: SassafrasApi<B, Error = sp_blockchain::Error>,
+	E: Environment<B, Error = Error> + Send + Sync,
+	E::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
+	I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync + 'static,
+	SO: SyncOracle + Send + Sync + Clone,
+	Error: std::error::Error + Send + From<ConsensusError> + From<I::Error> + 'static,
+{
+	type OnSlot = Pin<Box<dyn Future<Output = Result<(), ConsensusError>> + Send>>;
+
+	fn on_slot(&mut self, chain_head: B::Header, slot_info: SlotInfo) -> Self::OnSlot {
+		<Self as sc_consensus_slots::SimpleSlotWorker<B>>::on_slot(self, chain_head, slot_info)
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct SassafrasSlotCompatible;
+
+impl SlotCompatible for SassafrasSlotCompatible {
+	fn extract_timestamp_and_slot(
+		&self,
+		data: &InherentData,
+	) -> Result<(TimestampInherent, u64, std::time::Duration), sp_consensus::Error> {
+		data.timestamp_inherent_data()
+			.and_then(|t| data.sassafras_inherent_data().map(|a| (t, a)))
+			.map_err(Into::into)
+			.map_err(sp_consensus::Error::InherentData)
+			.map(|(x, y)| (x, y, Default::default()))
+	}
+}
+
+/// Check a header has been signed by the author expected at its slot. If the
+/// slot is too far in the future, an error will be returned. If successful,
+/// returns the pre-header and the digest item containing the seal.
+///
+/// The slot author is the owner of `ticket` if there is a ticket assigned to
+/// the slot, and the round-robin fallback author of `epoch` otherwise.
+fn check_header<C, B: BlockT>(
+	client: &C,
+	slot_now: SlotNumber,
+	mut header: B::Header,
+	hash: B::Hash,
+	pre_digest: &PreDigest,
+	epoch: &Epoch,
+	ticket: Option<Ticket>,
+) -> Result<CheckedHeader<B::Header, DigestItemFor<B>>, Error<B>> where
+	C: AuxStore,
+{
+	let seal = match header.digest_mut().pop() {
+		Some(x) => x,
+		None => return Err(sassafras_err(Error::HeaderUnsealed(hash))),
+	};
+
+	let sig = seal.as_sassafras_seal().ok_or_else(|| {
+		sassafras_err(Error::HeaderBadSeal(hash))
+	})?;
+
+	let slot_number = pre_digest.slot_number;
+	if slot_number > slot_now {
+		header.digest_mut().push(seal);
+		return Ok(CheckedHeader::Deferred(header, slot_number));
+	}
+
+	let expected = match ticket {
+		Some(ticket) => Some((ticket.authority_index, Some(ticket.attempt))),
+		None => epoch.fallback_author(slot_number).map(|index| (index, None)),
+	};
+
+	if expected != Some((pre_digest.authority_index, pre_digest.ticket_attempt)) {
+		return Err(sassafras_err(Error::UnexpectedAuthor(slot_number)));
+	}
+
+	let author = epoch.authorities.get(pre_digest.authority_index as usize)
+		.ok_or_else(|| sassafras_err(Error::UnexpectedAuthor(slot_number)))?;
+
+	let pre_hash = header.hash();
+	if !AuthorityPair::verify(&sig, pre_hash.as_ref(), author) {
+		return Err(sassafras_err(Error::BadSignature(hash)));
+	}
+
+	let transcript = make_slot_transcript(&epoch.randomness, slot_number, epoch.epoch_index);
+	PublicKey::from_bytes(author.as_slice())
+		.and_then(|p| p.vrf_verify(transcript, &pre_digest.vrf_output, &pre_digest.vrf_proof))
+		.map_err(|e| sassafras_err(Error::VRFVerificationFailed(e)))?;
+
+	if let Some(equivocation_proof) = check_equivocation(
+		client,
+		slot_now,
+		slot_number,
+		&header,
+		author,
+	).map_err(Error::Client)? {
+		info!(
+			target: "sassafras",
+			"Slot author {:?} is equivocating at slot {} with headers {:?} and {:?}",
+			author,
+			slot_number,
+			equivocation_proof.first_header.hash(),
+			equivocation_proof.second_header.hash(),
+		);
+	}
+
+	Ok(CheckedHeader::Checked(header, seal))
+}
+
+/// A verifier for Sassafras blocks.
+pub struct SassafrasVerifier<C, CAW> {
+	client: Arc<C>,
+	inherent_data_providers: InherentDataProviders,
+	can_author_with: CAW,
+}
+
+impl<C, CAW> SassafrasVerifier<C, CAW> {
+	fn check_inherents<B: BlockT>(
+		&self,
+		block: B,
+		block_id: BlockId<B>,
+		inherent_data: InherentData,
+		timestamp_now: u64,
+	) -> Result<(), Error<B>> where
+		C: ProvideRuntimeApi<B>, C::Api: BlockBuilderApi<B, Error = sp_blockchain::Error>,
+		CAW: CanAuthorWith<B>,
+	{
+		const MAX_TIMESTAMP_DRIFT_SECS: u64 = 60;
+
+		if let Err(e) = self.can_author_with.can_author_with(&block_id) {
+			debug!(
+				target: "sassafras",
+				"Skipping `check_inherents` as authoring version is not compatible: {}",
+				e,
+			);
+
+			return Ok(())
+		}
+
+		let inherent_res = self.client.runtime_api().check_inherents(
+			&block_id,
+			block,
+			inherent_data,
+		).map_err(Error::Client)?;
+
+		if !inherent_res.ok() {
+			inherent_res
+				.into_errors()
+				.try_for_each(|(i, e)| match TIError::try_from(&i, &e) {
+					Some(TIError::ValidAtTimestamp(timestamp)) => {
+						// halt import until timestamp is valid.
+						// reject when too far ahead.
+						if timestamp > timestamp_now + MAX_TIMESTAMP_DRIFT_SECS {
+							return Err(Error::TooFarInFuture);
+						}
+
+						let diff = timestamp.saturating_sub(timestamp_now);
+						info!(
+							target: "sassafras",
+							"halting for block {} seconds in the future",
+							diff
+						);
+						telemetry!(CONSENSUS_INFO; "sassafras.halting_for_future_block";
+							"diff" => ?diff
+						);
+						thread::sleep(Duration::from_secs(diff));
+						Ok(())
+					},
+					Some(TIError::Other(e)) => Err(Error::Runtime(e.into())),
+					None => Err(Error::DataProvider(
+						self.inherent_data_providers.error_to_string(&i, &e)
+					)),
+				})
+		} else {
+			Ok(())
+		}
+	}
+}
+
+impl<B, C, CAW> Verifier<B> for SassafrasVerifier<C, CAW> where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + AuxStore + Send + Sync,
+	C::Api: BlockBuilderApi<B, Error = sp_blockchain::Error>
+		+ SassafrasApi<B, Error = sp_blockchain::Error>,
+	CAW: CanAuthorWith<B> + Send + Sync,
+{
+	fn verify(
+		&mut self,
+		origin: BlockOrigin,
+		header: B::Header,
+		justification: Option<Justification>,
+		mut body: Option<Vec<B::Extrinsic>>,
+	) -> Result<(BlockImportParams<B, ()>, Option<Vec<(CacheKeyId, Vec<u8>)>>), String> {
+		trace!(
+			target: "sassafras",
+			"Verifying origin: {:?} header: {:?} justification: {:?} body: {:?}",
+			origin,
+			header,
+			justification,
+			body,
+		);
+
+		let mut inherent_data = self
+			.inherent_data_providers
+			.create_inherent_data()
+			.map_err(|e| e.into_string())?;
+		let (timestamp_now, slot_now, _) = SassafrasSlotCompatible.extract_timestamp_and_slot(&inherent_data)
+			.map_err(|e| format!("Could not extract timestamp and slot: {:?}", e))?;
+
+		let hash = header.hash();
+		let parent_hash = *header.parent_hash();
+		let parent_id = BlockId::Hash(parent_hash);
+
+		let parent_header = self.client.header(parent_id)
+			.map_err(|e| format!("Could not fetch parent header {:?}: {:?}", parent_hash, e))?
+			.ok_or_else(|| String::from(Error::<B>::ParentUnavailable(parent_hash, hash)))?;
+
+		let pre_digest = find_pre_digest::<B>(&header)?;
+
+		// make sure that slot number is strictly increasing
+		if !parent_header.number().is_zero() {
+			let parent_slot = find_pre_digest::<B>(&parent_header)?.slot_number;
+			if pre_digest.slot_number <= parent_slot {
+				return Err(sassafras_err(
+					Error::<B>::SlotNumberMustIncrease(parent_slot, pre_digest.slot_number)
+				).into());
+			}
+		}
+
+		let epoch = epoch_for_slot(&*self.client, &parent_header, pre_digest.slot_number)
+			.map_err(|e| format!("Could not fetch epoch at {:?}: {:?}", parent_hash, e))?;
+		let ticket = self.client.runtime_api()
+			.slot_ticket(&parent_id, pre_digest.slot_number)
+			.map_err(|e| format!("Could not fetch slot ticket at {:?}: {:?}", parent_hash, e))?;
+
+		// we add one to allow for some small drift.
+		// FIXME #1019 in the future, alter this queue to allow deferring of
+		// headers
+		let checked_header = check_header::<C, B>(
+			&self.client,
+			slot_now + 1,
+			header,
+			hash,
+			&pre_digest,
+			&epoch,
+			ticket,
+		).map_err(|e| e.to_string())?;
+
+		match checked_header {
+			CheckedHeader::Checked(pre_header, seal) => {
+				// if the body is passed through, we need to use the runtime
+				// to check that the internally-set timestamp in the inherents
+				// actually matches the slot set in the seal.
+				if let Some(inner_body) = body.take() {
+					inherent_data.sassafras_replace_inherent_data(pre_digest.slot_number);
+					let block = B::new(pre_header.clone(), inner_body);
+
+					self.check_inherents(
+						block.clone(),
+						parent_id,
+						inherent_data,
+						timestamp_now,
+					).map_err(|e| e.to_string())?;
+
+					let (_, inner_body) = block.deconstruct();
+					body = Some(inner_body);
+				}
+
+				trace!(target: "sassafras", "Checked {:?}; importing.", pre_header);
+				telemetry!(CONSENSUS_TRACE; "sassafras.checked_and_importing"; "pre_header" => ?pre_header);
+
+				let mut import_block = BlockImportParams::new(origin, pre_header);
+				import_block.post_digests.push(seal);
+				import_block.body = body;
+				import_block.justification = justification;
+				import_block.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+				import_block.post_hash = Some(hash);
+
+				Ok((import_block, None))
+			}
+			CheckedHeader::Deferred(a, b) => {
+				debug!(target: "sassafras", "Checking {:?} failed; {:?}, {:?}.", hash, a, b);
+				telemetry!(CONSENSUS_DEBUG; "sassafras.header_too_far_in_future";
+					"hash" => ?hash, "a" => ?a, "b" => ?b
+				);
+				Err(Error::<B>::TooFarInFuture.into())
+			}
+		}
+	}
+}
+
+/// Register the Sassafras inherent data provider, if not registered already.
+fn register_sassafras_inherent_data_provider(
+	inherent_data_providers: &InherentDataProviders,
+	slot_duration: u64,
+) -> Result<(), sp_consensus::Error> {
+	debug!(target: "sassafras", "Registering");
+	if !inherent_data_providers.has_provider(&INHERENT_IDENTIFIER) {
+		inherent_data_providers
+			.register_provider(InherentDataProvider::new(slot_duration))
+			.map_err(Into::into)
+			.map_err(sp_consensus::Error::InherentData)
+	} else {
+		Ok(())
+	}
+}
+
+/// Start an import queue for the Sassafras consensus algorithm.
+pub fn import_queue<B, I, C, S, CAW>(
+	config: Config,
+	block_import: I,
+	justification_import: Option<BoxJustificationImport<B>>,
+	finality_proof_import: Option<BoxFinalityProofImport<B>>,
+	client: Arc<C>,
+	inherent_data_providers: InherentDataProviders,
+	spawner: &S,
+	registry: Option<&prometheus_endpoint::Registry>,
+	can_author_with: CAW,
+) -> ClientResult<DefaultImportQueue<B, C>> where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + AuxStore + Send + Sync + 'static,
+	C::Api: BlockBuilderApi<B, Error = sp_blockchain::Error>
+		+ SassafrasApi<B, Error = sp_blockchain::Error>
+		+ ApiExt<B, Error = sp_blockchain::Error>,
+	I: BlockImport<B, Error = ConsensusError, Transaction = sp_api::TransactionFor<C, B>>
+		+ Send + Sync + 'static,
+	S: sp_core::traits::SpawnNamed,
+	CAW: CanAuthorWith<B> + Send + Sync + 'static,
+{
+	register_sassafras_inherent_data_provider(&inherent_data_providers, config.slot_duration())?;
+
+	let verifier = SassafrasVerifier {
+		client,
+		inherent_data_providers,
+		can_author_with,
+	};
+
+	Ok(BasicQueue::new(
+		verifier,
+		Box::new(block_import),
+		justification_import,
+		finality_proof_import,
+		spawner,
+		registry,
+	))
+}
@@ -0,0 +1,507 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Sassafras testsuite
+
+use super::*;
+use sp_consensus::{NoNetwork as DummyOracle, Proposal, RecordProof, AlwaysCanAuthor};
+use sc_network_test::{Block as TestBlock, *};
+use sp_runtime::traits::DigestFor;
+use sc_network::config::ProtocolConfig;
+use sp_keyring::sr25519::Keyring;
+use sc_client_api::BlockchainEvents;
+use sc_block_builder::BlockBuilderProvider;
+use sc_consensus_slots::SimpleSlotWorker;
+use sp_api::ApiRef;
+use sp_consensus::{BlockCheckParams, ImportResult};
+use std::{collections::HashMap, task::Poll};
+
+type Error = sp_blockchain::Error;
+
+type TestClient = substrate_test_runtime_client::client::Client<
+	substrate_test_runtime_client::Backend,
+	substrate_test_runtime_client::Executor,
+	TestBlock,
+	substrate_test_runtime_client::runtime::RuntimeApi
+>;
+
+struct DummyFactory(Arc<TestClient>);
+struct DummyProposer(u64, Arc<TestClient>);
+
+impl Environment<TestBlock> for DummyFactory {
+	type Proposer = DummyProposer;
+	type CreateProposer = future::Ready<Result<DummyProposer, Error>>;
+	type Error = Error;
+
+	fn init(&mut self, parent_header: &<TestBlock as BlockT>::Header)
+		-> Self::CreateProposer
+	{
+		future::ready(Ok(DummyProposer(parent_header.number + 1, self.0.clone())))
+	}
+}
+
+impl Proposer<TestBlock> for DummyProposer {
+	type Error = Error;
+	type Transaction = sc_client_api::TransactionFor<
+		substrate_test_runtime_client::Backend,
+		TestBlock
+	>;
+	type Proposal = future::Ready<Result<Proposal<TestBlock, Self::Transaction>, Error>>;
+
+	fn propose(
+		self,
+		_: InherentData,
+		digests: DigestFor<TestBlock>,
+		_: Duration,
+		_: RecordProof,
+	) -> Self::Proposal {
+		let r = self.1.new_block(digests).unwrap().build().map_err(|e| e.into());
+
+		future::ready(r.map(|b| Proposal {
+			block: b.block,
+			proof: b.proof,
+			storage_changes: b.storage_changes,
+		}))
+	}
+}
+
+const SLOT_DURATION: u64 = 1000;
+
+pub struct SassafrasTestNet {
+	peers: Vec<Peer<()>>,
+}
+
+impl TestNetFactory for SassafrasTestNet {
+	type Verifier = SassafrasVerifier<PeersFullClient, AlwaysCanAuthor>;
+	type PeerData = ();
+
+	/// Create new test network with peers and given config.
+	fn from_config(_config: &ProtocolConfig) -> Self {
+		SassafrasTestNet {
+			peers: Vec::new(),
+		}
+	}
+
+	fn make_verifier(&self, client: PeersClient, _cfg: &ProtocolConfig, _peer_data: &())
+		-> Self::Verifier
+	{
+		match client {
+			PeersClient::Full(client, _) => {
+				let config = configuration(&*client).expect("configuration available");
+				let inherent_data_providers = InherentDataProviders::new();
+				register_sassafras_inherent_data_provider(
+					&inherent_data_providers,
+					config.slot_duration(),
+				).expect("Registers sassafras inherent data provider");
+
+				assert_eq!(config.slot_duration(), SLOT_DURATION);
+				SassafrasVerifier {
+					client,
+					inherent_data_providers,
+					can_author_with: AlwaysCanAuthor,
+				}
+			},
+			PeersClient::Light(_, _) => unreachable!("No (yet) tests for light client + Sassafras"),
+		}
+	}
+
+	fn peer(&mut self, i: usize) -> &mut Peer<Self::PeerData> {
+		&mut self.peers[i]
+	}
+
+	fn peers(&self) -> &Vec<Peer<Self::PeerData>> {
+		&self.peers
+	}
+
+	fn mut_peers<F: FnOnce(&mut Vec<Peer<Self::PeerData>>)>(&mut self, closure: F) {
+		closure(&mut self.peers);
+	}
+}
+
+#[test]
+fn authoring_blocks() {
+	let _ = env_logger::try_init();
+	let net = SassafrasTestNet::new(3);
+
+	let peers = &[
+		(0, Keyring::Alice),
+		(1, Keyring::Bob),
+		(2, Keyring::Charlie),
+	];
+
+	let net = Arc::new(Mutex::new(net));
+	let mut import_notifications = Vec::new();
+	let mut sassafras_futures = Vec::new();
+	// the test runtime assigns tickets to odd slots, see `sassafras_slot_ticket`.
+	let ticket_claims = Arc::new(Mutex::new(0));
+
+	let mut keystore_paths = Vec::new();
+	for (peer_id, key) in peers {
+		let mut net = net.lock();
+		let peer = net.peer(*peer_id);
+		let client = peer.client().as_full().expect("full clients are created").clone();
+		let select_chain = peer.select_chain().expect("full client has a select chain");
+		let keystore_path = tempfile::tempdir().expect("Creates keystore path");
+		let keystore = sc_keystore::Store::open(keystore_path.path(), None).expect("Creates keystore.");
+
+		keystore.write().insert_ephemeral_from_seed::<AuthorityPair>(&key.to_seed())
+			.expect("Creates authority key");
+		keystore_paths.push(keystore_path);
+
+		let environ = DummyFactory(client.clone());
+		let ticket_claims = ticket_claims.clone();
+		import_notifications.push(
+			client.import_notification_stream()
+				.take_while(|n| future::ready(!(n.origin != BlockOrigin::Own && n.header.number() < &5)))
+				.for_each(move |n| {
+					let pre_digest = find_pre_digest::<TestBlock>(&n.header)
+						.expect("imported blocks have a pre-digest");
+					if pre_digest.ticket_attempt.is_some() {
+						assert_eq!(pre_digest.slot_number % 2, 1);
+						*ticket_claims.lock() += 1;
+					}
+					future::ready(())
+				})
+		);
+
+		let config = configuration(&*client).expect("configuration available");
+
+		sassafras_futures.push(start_sassafras(SassafrasParams {
+			keystore,
+			client: client.clone(),
+			select_chain,
+			env: environ,
+			block_import: client,
+			sync_oracle: DummyOracle,
+			inherent_data_providers: InherentDataProviders::new(),
+			force_authoring: false,
+			config,
+			can_author_with: AlwaysCanAuthor,
+		}).expect("Starts sassafras"));
+	}
+
+	futures::executor::block_on(future::select(
+		future::poll_fn(move |cx| {
+			net.lock().poll(cx);
+			Poll::<()>::Pending
+		}),
+		future::select(
+			future::join_all(sassafras_futures),
+			future::join_all(import_notifications)
+		)
+	));
+
+	assert!(*ticket_claims.lock() > 0, "some slots are claimed with a ticket");
+}
+
+#[test]
+fn first_epoch_starts_at_the_first_slot() {
+	let client = substrate_test_runtime_client::new();
+	let genesis = client.header(BlockId::Number(0)).unwrap().unwrap();
+
+	let epoch = epoch_for_slot(&client, &genesis, 42).unwrap();
+	assert_eq!(epoch.epoch_index, 0);
+	assert_eq!(epoch.start_slot, 42);
+	assert_eq!(epoch.authorities, vec![
+		Keyring::Alice.public().into(),
+		Keyring::Bob.public().into(),
+		Keyring::Charlie.public().into(),
+	]);
+}
+
+#[test]
+fn pre_digest_is_found_in_header() {
+	let client = substrate_test_runtime_client::new();
+	let genesis = client.header(BlockId::Number(0)).unwrap().unwrap();
+	assert!(find_pre_digest::<TestBlock>(&genesis).is_err());
+
+	let keystore_path = tempfile::tempdir().expect("Creates keystore path");
+	let keystore = sc_keystore::Store::open(keystore_path.path(), None).expect("Creates keystore.");
+	keystore.write().insert_ephemeral_from_seed::<AuthorityPair>(&Keyring::Alice.to_seed())
+		.expect("Creates authority key");
+
+	let epoch = epoch_for_slot(&client, &genesis, 3).unwrap();
+	let (pre_digest, author) = authorship::claim_slot(3, &epoch, None, &keystore)
+		.expect("Alice is the fallback author of slot 3");
+	assert_eq!(author, Keyring::Alice.public().into());
+
+	let mut header = genesis.clone();
+	header.digest_mut().push(
+		<DigestItemFor<TestBlock> as CompatibleDigestItem>::sassafras_pre_digest(pre_digest),
+	);
+
+	let found = find_pre_digest::<TestBlock>(&header).unwrap();
+	assert_eq!(found.slot_number, 3);
+	assert_eq!(found.authority_index, 0);
+	assert_eq!(found.ticket_attempt, None);
+}
+
+/// Runtime API mock with fixed epochs, which assigns `ticket` to every slot
+/// and records the submitted tickets.
+#[derive(Clone)]
+struct TestApi {
+	current_epoch: Epoch,
+	next_epoch: Epoch,
+	ticket: Arc<Mutex<Option<Ticket>>>,
+	submitted: Arc<Mutex<Vec<TicketEnvelope>>>,
+	accept_tickets: Arc<Mutex<bool>>,
+}
+
+impl TestApi {
+	fn new() -> Self {
+		TestApi {
+			current_epoch: Epoch {
+				epoch_index: 0,
+				start_slot: 100,
+				duration: 6,
+				authorities: vec![Keyring::Alice.public().into()],
+				randomness: [1; 32],
+			},
+			next_epoch: Epoch {
+				epoch_index: 1,
+				start_slot: 106,
+				duration: 6,
+				authorities: vec![Keyring::Alice.public().into(), Keyring::Bob.public().into()],
+				randomness: [2; 32],
+			},
+			ticket: Default::default(),
+			submitted: Default::default(),
+			accept_tickets: Arc::new(Mutex::new(true)),
+		}
+	}
+}
+
+struct TestRuntimeApi(TestApi);
+
+impl ProvideRuntimeApi<TestBlock> for TestApi {
+	type Api = TestRuntimeApi;
+
+	fn runtime_api<'a>(&'a self) -> ApiRef<'a, Self::Api> {
+		TestRuntimeApi(self.clone()).into()
+	}
+}
+
+impl BlockOf for TestApi {
+	type Type = TestBlock;
+}
+
+impl AuxStore for TestApi {
+	fn insert_aux<
+		'a,
+		'b: 'a,
+		'c: 'a,
+		I: IntoIterator<Item=&'a(&'c [u8], &'c [u8])>,
+		D: IntoIterator<Item=&'a &'b [u8]>,
+	>(&self, _insert: I, _delete: D) -> ClientResult<()> {
+		Ok(())
+	}
+
+	fn get_aux(&self, _key: &[u8]) -> ClientResult<Option<Vec<u8>>> {
+		Ok(None)
+	}
+}
+
+sp_api::mock_impl_runtime_apis! {
+	impl SassafrasApi<TestBlock> for TestRuntimeApi {
+		type Error = sp_blockchain::Error;
+
+		fn configuration(&self) -> SassafrasConfiguration {
+			SassafrasConfiguration {
+				slot_duration: SLOT_DURATION,
+				epoch_duration: 6,
+				max_attempts: 8,
+				redundancy_factor: 2,
+			}
+		}
+
+		fn current_epoch(&self) -> Epoch {
+			self.0.current_epoch.clone()
+		}
+
+		fn next_epoch(&self) -> Epoch {
+			self.0.next_epoch.clone()
+		}
+
+		fn slot_ticket(&self, _slot_number: SlotNumber) -> Option<Ticket> {
+			*self.0.ticket.lock()
+		}
+
+		fn submit_tickets_unsigned_extrinsic(&self, tickets: Vec<TicketEnvelope>) -> bool {
+			if !*self.0.accept_tickets.lock() {
+				return false;
+			}
+
+			self.0.submitted.lock().extend(tickets);
+			true
+		}
+	}
+}
+
+type TestApiTransaction = sp_api::TransactionFor<TestApi, TestBlock>;
+
+/// Environment of a worker which never authors blocks.
+struct NoEnvironment;
+struct NoProposer;
+
+impl Environment<TestBlock> for NoEnvironment {
+	type Proposer = NoProposer;
+	type CreateProposer = future::Pending<Result<NoProposer, Error>>;
+	type Error = Error;
+
+	fn init(&mut self, _: &<TestBlock as BlockT>::Header) -> Self::CreateProposer {
+		future::pending()
+	}
+}
+
+impl Proposer<TestBlock> for NoProposer {
+	type Error = Error;
+	type Transaction = TestApiTransaction;
+	type Proposal = future::Pending<Result<Proposal<TestBlock, Self::Transaction>, Error>>;
+
+	fn propose(
+		self,
+		_: InherentData,
+		_: DigestFor<TestBlock>,
+		_: Duration,
+		_: RecordProof,
+	) -> Self::Proposal {
+		future::pending()
+	}
+}
+
+struct NoBlockImport;
+
+impl BlockImport<TestBlock> for NoBlockImport {
+	type Error = ConsensusError;
+	type Transaction = TestApiTransaction;
+
+	fn check_block(
+		&mut self,
+		_: BlockCheckParams<TestBlock>,
+	) -> Result<ImportResult, Self::Error> {
+		unreachable!("the worker never authors blocks")
+	}
+
+	fn import_block(
+		&mut self,
+		_: BlockImportParams<TestBlock, Self::Transaction>,
+		_: HashMap<CacheKeyId, Vec<u8>>,
+	) -> Result<ImportResult, Self::Error> {
+		unreachable!("the worker never authors blocks")
+	}
+}
+
+fn header_at(number: u64) -> <TestBlock as BlockT>::Header {
+	<<TestBlock as BlockT>::Header as Header>::new(
+		number,
+		Default::default(),
+		Default::default(),
+		Default::default(),
+		Default::default(),
+	)
+}
+
+#[test]
+fn epochs_without_blocks_are_skipped() {
+	let api = TestApi::new();
+	let parent = header_at(1);
+
+	let epoch = epoch_for_slot(&api, &parent, 105).unwrap();
+	assert_eq!(epoch, api.current_epoch);
+
+	let epoch = epoch_for_slot(&api, &parent, 111).unwrap();
+	assert_eq!(epoch, api.next_epoch);
+
+	// no block was authored during epochs 1 and 2.
+	let epoch = epoch_for_slot(&api, &parent, 119).unwrap();
+	assert_eq!(epoch.epoch_index, 3);
+	assert_eq!(epoch.start_slot, 118);
+	assert_eq!(epoch.authorities, api.next_epoch.authorities);
+	assert_eq!(epoch.randomness, api.next_epoch.randomness);
+}
+
+#[test]
+fn tickets_are_submitted_and_claimed() {
+	let keystore_path = tempfile::tempdir().expect("Creates keystore path");
+	let keystore = sc_keystore::Store::open(keystore_path.path(), None).expect("Creates keystore.");
+	keystore.write().insert_ephemeral_from_seed::<AuthorityPair>(&Keyring::Alice.to_seed())
+		.expect("Creates authority key");
+
+	let api = TestApi::new();
+	let worker = SassafrasWorker {
+		client: Arc::new(api.clone()),
+		block_import: Arc::new(Mutex::new(NoBlockImport)),
+		env: NoEnvironment,
+		sync_oracle: DummyOracle,
+		force_authoring: false,
+		keystore,
+		config: configuration(&api).expect("configuration available"),
+		last_ticketed_epoch: Mutex::new(None),
+	};
+	let parent = header_at(1);
+	let notify_slot = |slot_number| {
+		let epoch = worker.epoch_data(&parent, slot_number).unwrap();
+		worker.notify_slot(&parent, slot_number, &epoch);
+	};
+
+	// tickets can't be submitted for the epoch after next of the chain head.
+	notify_slot(106);
+	assert!(api.submitted.lock().is_empty());
+
+	// tickets refused by the runtime are submitted again at the next slot.
+	*api.accept_tickets.lock() = false;
+	notify_slot(100);
+	assert!(api.submitted.lock().is_empty());
+	assert_eq!(*worker.last_ticketed_epoch.lock(), None);
+	*api.accept_tickets.lock() = true;
+
+	// tickets are submitted for the next epoch, once.
+	notify_slot(101);
+	let submitted = api.submitted.lock().clone();
+	assert!(!submitted.is_empty());
+	for ticket in &submitted {
+		assert_eq!(ticket.authority_index, 0);
+		assert!(sp_consensus_sassafras::verify_ticket(
+			&Keyring::Alice.public().into(),
+			&api.next_epoch.randomness,
+			api.next_epoch.epoch_index,
+			ticket,
+		).is_some());
+	}
+
+	notify_slot(102);
+	assert_eq!(api.submitted.lock().len(), submitted.len());
+
+	// a slot of the next epoch is claimed with the ticket assigned to it.
+	*api.ticket.lock() = Some(Ticket { id: 0, authority_index: 0, attempt: submitted[0].attempt });
+	let epoch = worker.epoch_data(&parent, 107).unwrap();
+	let (pre_digest, author) = worker.claim_slot(&parent, 107, &epoch).expect("Alice owns the ticket");
+	assert_eq!(author, Keyring::Alice.public().into());
+	assert_eq!(pre_digest.ticket_attempt, Some(submitted[0].attempt));
+
+	let transcript = make_slot_transcript(&epoch.randomness, 107, epoch.epoch_index);
+	PublicKey::from_bytes(author.as_slice())
+		.and_then(|p| p.vrf_verify(transcript, &pre_digest.vrf_output, &pre_digest.vrf_proof))
+		.expect("the VRF is signed for the slot and epoch");
+
+	// slots without a ticket are claimed by the fallback author.
+	*api.ticket.lock() = None;
+	assert!(worker.claim_slot(&parent, 107, &epoch).is_none());
+	let (pre_digest, _) = worker.claim_slot(&parent, 108, &epoch).expect("Alice is the fallback author");
+	assert_eq!(pre_digest.ticket_attempt, None);
+}
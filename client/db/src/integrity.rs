@@ -0,0 +1,421 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Database integrity checks.
+//!
+//! Walks the canonical chain and verifies that headers link up, that the state root of every
+//! block that isn't pruned can be found, and that the leaves set and the meta keys point to
+//! existing blocks. Only the root node of each state is looked up, the rest of the trie isn't
+//! checked. Problems found above the last finalized block can be repaired by reverting the
+//! blocks above the last consistent one.
+
+use std::fmt;
+use hash_db::EMPTY_PREFIX;
+use log::info;
+use sp_blockchain::{HeaderBackend, Result as ClientResult};
+use sp_database::Transaction;
+use sp_runtime::generic::BlockId;
+use sp_runtime::traits::{
+	Block as BlockT, Header as HeaderT, NumberFor, Zero, One, SaturatedConversion, Hash, HashFor,
+};
+use sp_blockchain::HeaderMetadata;
+use crate::{Backend, children, columns, utils::{self, meta_keys}};
+
+/// A problem found by [`Backend::check_integrity`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityProblem<Block: BlockT> {
+	/// There is no canonical header at this height.
+	MissingHeader(NumberFor<Block>),
+	/// The header's parent isn't the canonical block at the previous height.
+	WrongParent(NumberFor<Block>, Block::Hash),
+	/// The state of a block that isn't pruned can't be found.
+	MissingState(NumberFor<Block>, Block::Hash),
+	/// A leaf doesn't point to a known block of the right height.
+	InvalidLeaf(NumberFor<Block>, Block::Hash),
+	/// The best block isn't part of the leaves set.
+	BestNotLeaf(NumberFor<Block>, Block::Hash),
+	/// A meta key is inconsistent with the rest of the database.
+	Meta(String),
+}
+
+impl<Block: BlockT> fmt::Display for IntegrityProblem<Block> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			IntegrityProblem::MissingHeader(number) =>
+				write!(f, "Missing canonical header #{}", number),
+			IntegrityProblem::WrongParent(number, hash) =>
+				write!(f, "Parent of block #{} ({:?}) is not the canonical block below it", number, hash),
+			IntegrityProblem::MissingState(number, hash) =>
+				write!(f, "State of block #{} ({:?}) not found", number, hash),
+			IntegrityProblem::InvalidLeaf(number, hash) =>
+				write!(f, "Leaf #{} ({:?}) is not a known block", number, hash),
+			IntegrityProblem::BestNotLeaf(number, hash) =>
+				write!(f, "Best block #{} ({:?}) is not a leaf", number, hash),
+			IntegrityProblem::Meta(message) =>
+				write!(f, "Meta: {}", message),
+		}
+	}
+}
+
+/// Outcome of [`Backend::check_integrity`].
+#[derive(Debug)]
+pub struct IntegrityReport<Block: BlockT> {
+	/// Number of canonical blocks that have been checked.
+	pub checked_blocks: u64,
+	/// Number of blocks whose state root has been looked up. Only the root node of each state is
+	/// checked, not the rest of the trie.
+	pub checked_states: u64,
+	/// Problems found, including the repaired ones.
+	pub problems: Vec<IntegrityProblem<Block>>,
+	/// Whether all the problems have been repaired.
+	pub repaired: bool,
+	/// New best block, if the best block has been moved back during repair.
+	pub new_best: Option<(NumberFor<Block>, Block::Hash)>,
+}
+
+impl<Block: BlockT> IntegrityReport<Block> {
+	/// Returns true if the database is consistent, possibly after repair.
+	pub fn is_consistent(&self) -> bool {
+		self.problems.is_empty() || self.repaired
+	}
+}
+
+impl<Block: BlockT> Backend<Block> {
+	/// Check the integrity of the database.
+	///
+	/// If `repair` is set, invalid leaves are removed from the leaves set and the blocks above
+	/// the last consistent block are reverted, the same way [`Backend::revert`] does, so that they
+	/// can be imported again. Problems in the finalized chain can't be repaired.
+	///
+	/// [`Backend::revert`]: sc_client_api::backend::Backend::revert
+	pub fn check_integrity(&self, repair: bool) -> ClientResult<IntegrityReport<Block>> {
+		let info = self.blockchain.info();
+		let mut problems = Vec::new();
+
+		self.check_meta(&info, &mut problems)?;
+		let (checked_blocks, checked_states, first_bad) = self.check_canonical_chain(
+			info.best_number,
+			&mut problems,
+		)?;
+		let invalid_leaves = self.check_leaves(&info, &mut problems)?;
+
+		let mut report = IntegrityReport {
+			checked_blocks,
+			checked_states,
+			repaired: problems.is_empty(),
+			problems,
+			new_best: None,
+		};
+
+		if !repair || report.problems.is_empty() {
+			return Ok(report);
+		}
+
+		let new_best = match first_bad {
+			Some(first_bad) if first_bad <= info.finalized_number => {
+				info!(target: "db", "Inconsistent finalized block #{}, can't repair", first_bad);
+				return Ok(report);
+			},
+			Some(first_bad) => {
+				let number = first_bad - One::one();
+				let hash = self.blockchain.hash(number)?.ok_or_else(|| sp_blockchain::Error::UnknownBlock(
+					format!("Canonical block #{} not found", number)
+				))?;
+				Some((number, hash))
+			},
+			None => None,
+		};
+
+		if let Some((number, hash)) = new_best {
+			info!(target: "db", "Moving best block back to #{} ({:?})", number, hash);
+			self.revert_to(number, hash, info.best_number)?;
+		}
+
+		let mut transaction = Transaction::new();
+		let mut leaves = self.blockchain.leaves.write();
+		for (number, hash) in invalid_leaves {
+			leaves.remove(hash, number);
+		}
+		if let Some((number, hash)) = new_best {
+			leaves.revert(hash, number);
+		} else if !leaves.hashes().contains(&info.best_hash) {
+			let parent_hash = self.blockchain.header(BlockId::Hash(info.best_hash))?
+				.map(|header| *header.parent_hash())
+				.unwrap_or_default();
+			leaves.import(info.best_hash, info.best_number, parent_hash);
+		}
+		leaves.prepare_transaction(&mut transaction, columns::META, meta_keys::LEAF_PREFIX);
+		self.storage.db.commit(transaction)?;
+		drop(leaves);
+
+		// Problems in the meta keys other than the best block can't be fixed here.
+		report.repaired = report.problems.iter().all(|problem| match problem {
+			IntegrityProblem::Meta(_) => false,
+			_ => true,
+		});
+		report.new_best = new_best;
+		Ok(report)
+	}
+
+	/// Revert the canonical blocks from `best_number` down to, but excluding, block `number`.
+	///
+	/// Unlike [`Backend::revert`], this doesn't need the reverted blocks to be consistent: the
+	/// blocks whose header has been lost only have their state changes reverted. The headers,
+	/// bodies and justifications of the reverted blocks are removed as well.
+	///
+	/// [`Backend::revert`]: sc_client_api::backend::Backend::revert
+	fn revert_to(
+		&self,
+		number: NumberFor<Block>,
+		hash: Block::Hash,
+		best_number: NumberFor<Block>,
+	) -> ClientResult<()> {
+		let mut removed_number = best_number;
+		while removed_number > number {
+			let mut transaction = Transaction::new();
+			let parent_number = removed_number - One::one();
+			let removed = self.blockchain.header(BlockId::Number(removed_number))?;
+			let removed_hash = removed.as_ref().map(|header| header.hash());
+			let parent_hash = if parent_number == number {
+				hash
+			} else {
+				match &removed {
+					Some(header) => *header.parent_hash(),
+					None => self.blockchain.hash(parent_number)?.unwrap_or(hash),
+				}
+			};
+
+			utils::remove_number_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, removed_number)?;
+			if let Some(removed_hash) = removed_hash {
+				let lookup_key = utils::number_and_hash_to_lookup_key(removed_number, removed_hash)?;
+				transaction.remove(columns::HEADER, &lookup_key);
+				transaction.remove(columns::BODY, &lookup_key);
+				transaction.remove(columns::JUSTIFICATION, &lookup_key);
+				children::remove_children(&mut transaction, columns::META, meta_keys::CHILDREN_PREFIX, removed_hash);
+				self.blockchain.remove_header_metadata(removed_hash);
+			}
+
+			self.revert_best_block(
+				transaction,
+				self.storage.state_db.revert_one(),
+				removed_number,
+				removed_hash,
+				parent_hash,
+				false,
+			)?;
+			removed_number = parent_number;
+		}
+		Ok(())
+	}
+
+	fn check_meta(
+		&self,
+		info: &sp_blockchain::Info<Block>,
+		problems: &mut Vec<IntegrityProblem<Block>>,
+	) -> ClientResult<()> {
+		let mut check_canonical = |name: &str, number: NumberFor<Block>, hash: Block::Hash| -> ClientResult<()> {
+			if self.blockchain.hash(number)? != Some(hash) {
+				problems.push(IntegrityProblem::Meta(format!(
+					"{} block #{} ({:?}) is not canonical", name, number, hash,
+				)));
+			}
+			Ok(())
+		};
+
+		check_canonical("Genesis", Zero::zero(), info.genesis_hash)?;
+		check_canonical("Finalized", info.finalized_number, info.finalized_hash)?;
+
+		if info.finalized_number > info.best_number {
+			problems.push(IntegrityProblem::Meta(format!(
+				"Finalized block #{} is above best block #{}", info.finalized_number, info.best_number,
+			)));
+		}
+		Ok(())
+	}
+
+	/// Walk the canonical chain from the best block down to genesis.
+	///
+	/// Returns the number of checked blocks and states, and the lowest block with a problem.
+	fn check_canonical_chain(
+		&self,
+		best_number: NumberFor<Block>,
+		problems: &mut Vec<IntegrityProblem<Block>>,
+	) -> ClientResult<(u64, u64, Option<NumberFor<Block>>)> {
+		let empty_root = HashFor::<Block>::trie_root(Vec::new());
+		let best_canonical = self.storage.state_db.best_canonical();
+		let mut checked_blocks = 0u64;
+		let mut checked_states = 0u64;
+		let mut first_bad = None;
+		// Hash the next block expects as its parent.
+		let mut expected_hash = None;
+		let mut number = best_number;
+
+		loop {
+			checked_blocks += 1;
+			match self.blockchain.header(BlockId::Number(number))? {
+				None => {
+					problems.push(IntegrityProblem::MissingHeader(number));
+					first_bad = Some(number);
+					expected_hash = None;
+				},
+				Some(header) => {
+					let hash = header.hash();
+					if expected_hash.map_or(false, |expected| expected != hash) {
+						// The child at `number + 1` doesn't link to this block.
+						let child = number + One::one();
+						let child_hash = self.blockchain.hash(child)?.unwrap_or_default();
+						problems.push(IntegrityProblem::WrongParent(child, child_hash));
+						first_bad = Some(child);
+					}
+
+					let number_u64 = number.saturated_into::<u64>();
+					// Blocks above the last canonicalized one must be in the state-db journal.
+					let above_canonical = best_canonical.map_or(true, |c| number_u64 > c);
+					let pruned = self.storage.state_db.is_pruned(&hash, number_u64);
+					if above_canonical || !pruned {
+						checked_states += 1;
+						let root = *header.state_root();
+						let found = !pruned && (root == empty_root ||
+							sp_state_machine::Storage::get(self.storage.as_ref(), &root, EMPTY_PREFIX)
+								.map_err(sp_blockchain::Error::Backend)?
+								.is_some());
+						if !found {
+							problems.push(IntegrityProblem::MissingState(number, hash));
+							first_bad = Some(number);
+						}
+					}
+					expected_hash = Some(*header.parent_hash());
+				},
+			}
+
+			if number.is_zero() {
+				break;
+			}
+			number -= One::one();
+		}
+
+		Ok((checked_blocks, checked_states, first_bad))
+	}
+
+	/// Check the leaves set, returning the leaves that should be removed.
+	fn check_leaves(
+		&self,
+		info: &sp_blockchain::Info<Block>,
+		problems: &mut Vec<IntegrityProblem<Block>>,
+	) -> ClientResult<Vec<(NumberFor<Block>, Block::Hash)>> {
+		let leaves = self.blockchain.leaves.read().items();
+		let mut invalid = Vec::new();
+		for (hash, number) in &leaves {
+			let known = self.blockchain.header(BlockId::Hash(*hash))?
+				.map_or(false, |header| header.number() == number);
+			if !known || *number < info.finalized_number {
+				problems.push(IntegrityProblem::InvalidLeaf(*number, *hash));
+				invalid.push((*number, *hash));
+			}
+		}
+		if !leaves.iter().any(|(hash, _)| *hash == info.best_hash) {
+			problems.push(IntegrityProblem::BestNotLeaf(info.best_number, info.best_hash));
+		}
+		Ok(invalid)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use sc_client_api::backend::Backend as _;
+	use sp_blockchain::{Backend as _, HeaderBackend as _};
+	use sp_runtime::generic::BlockId;
+	use crate::tests::{Block, insert_header};
+	use crate::{Backend, columns, utils::meta_keys};
+	use super::IntegrityProblem;
+
+	#[test]
+	fn consistent_database_passes() {
+		let backend = Backend::<Block>::new_test(10, 10);
+		let mut parent = insert_header(&backend, 0, Default::default(), None, Default::default());
+		for number in 1..5 {
+			parent = insert_header(&backend, number, parent, None, Default::default());
+		}
+		backend.finalize_block(BlockId::Number(2), None).unwrap();
+
+		let report = backend.check_integrity(false).unwrap();
+		assert_eq!(report.problems, vec![]);
+		assert_eq!(report.checked_blocks, 5);
+		assert!(report.is_consistent());
+	}
+
+	#[test]
+	fn repairs_leaves_set() {
+		let backend = Backend::<Block>::new_test(10, 10);
+		let block0 = insert_header(&backend, 0, Default::default(), None, Default::default());
+		let block1 = insert_header(&backend, 1, block0, None, Default::default());
+
+		// Forget about the best block in the leaves set, and add one that doesn't exist.
+		{
+			let mut leaves = backend.blockchain.leaves.write();
+			leaves.remove(block1, 1);
+			leaves.import([1; 32].into(), 5, Default::default());
+			let mut transaction = sp_database::Transaction::new();
+			leaves.prepare_transaction(&mut transaction, columns::META, meta_keys::LEAF_PREFIX);
+			backend.storage.db.commit(transaction).unwrap();
+		}
+
+		let report = backend.check_integrity(false).unwrap();
+		assert_eq!(report.problems, vec![
+			IntegrityProblem::InvalidLeaf(5, [1; 32].into()),
+			IntegrityProblem::BestNotLeaf(1, block1),
+		]);
+		assert!(!report.is_consistent());
+
+		let report = backend.check_integrity(true).unwrap();
+		assert!(report.is_consistent());
+		assert_eq!(report.new_best, None);
+		assert_eq!(backend.check_integrity(false).unwrap().problems, vec![]);
+	}
+
+	#[test]
+	fn moves_best_block_back_to_last_consistent_one() {
+		let backend = Backend::<Block>::new_test(10, 10);
+		let mut hashes = vec![insert_header(&backend, 0, Default::default(), None, Default::default())];
+		for number in 1..5 {
+			let parent = hashes[number as usize - 1];
+			hashes.push(insert_header(&backend, number, parent, None, Default::default()));
+		}
+		backend.finalize_block(BlockId::Number(1), None).unwrap();
+
+		// Lose the canonical header of block #3, as if a commit had been interrupted.
+		let mut transaction = sp_database::Transaction::new();
+		crate::utils::remove_number_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, 3u64).unwrap();
+		backend.storage.db.commit(transaction).unwrap();
+
+		let report = backend.check_integrity(false).unwrap();
+		assert_eq!(report.problems, vec![IntegrityProblem::MissingHeader(3)]);
+
+		let report = backend.check_integrity(true).unwrap();
+		assert!(report.is_consistent());
+		assert_eq!(report.new_best, Some((2, hashes[2])));
+		assert_eq!(backend.blockchain.info().best_hash, hashes[2]);
+		assert_eq!(backend.check_integrity(false).unwrap().problems, vec![]);
+
+		// The reverted blocks are unknown, so that they can be imported again.
+		for hash in &hashes[3..] {
+			assert_eq!(backend.blockchain.header(BlockId::Hash(*hash)).unwrap(), None);
+		}
+		assert_eq!(backend.blockchain.leaves().unwrap(), vec![hashes[2]]);
+	}
+}
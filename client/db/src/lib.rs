@@ -36,6 +36,9 @@ pub mod bench;
 
 mod children;
 mod cache;
+mod integrity;
+mod migrate;
+mod offchain_index;
 mod changes_tries_storage;
 mod storage_cache;
 #[cfg(any(feature = "with-kvdb-rocksdb", test))]
@@ -48,6 +51,7 @@ mod parity_db;
 mod subdb;
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
 use std::path::{Path, PathBuf};
 use std::io;
 use std::collections::{HashMap, HashSet};
@@ -90,6 +94,8 @@ use log::{trace, debug, warn};
 // Re-export the Database trait so that one can pass an implementation of it.
 pub use sp_database::Database;
 pub use sc_state_db::PruningMode;
+pub use integrity::{IntegrityProblem, IntegrityReport};
+pub use migrate::{migrate_database, MigrationProgress, MigrationSummary};
 
 #[cfg(any(feature = "with-kvdb-rocksdb", test))]
 pub use bench::BenchmarkingState;
@@ -99,6 +105,10 @@ const MIN_BLOCKS_TO_KEEP_CHANGES_TRIES_FOR: u32 = 32768;
 /// Default value for storage cache child ratio.
 const DEFAULT_CHILD_RATIO: (usize, usize) = (1, 10);
 
+/// Maximum number of block bodies removed in a single transaction when an existing database
+/// catches up with the blocks pruning setting.
+const PRUNE_BLOCKS_BATCH: u64 = 4096;
+
 /// DB-backed patricia trie state, transaction type is an overlay of changes to commit.
 pub type DbState<B> = sp_state_machine::TrieBackend<
 	Arc<dyn sp_state_machine::Storage<HashFor<B>>>, HashFor<B>
@@ -116,6 +126,13 @@ pub struct RefTrackingState<Block: BlockT> {
 	state: DbState<Block>,
 	storage: Arc<StorageDb<Block>>,
 	parent_hash: Option<Block::Hash>,
+	pruned: Option<PrunedState<Block::Hash>>,
+}
+
+/// A state that has been discarded, except under the archived storage prefixes.
+struct PrunedState<H> {
+	block: H,
+	archive_prefixes: Vec<Vec<u8>>,
 }
 
 impl<B: BlockT> RefTrackingState<B> {
@@ -124,6 +141,52 @@ impl<B: BlockT> RefTrackingState<B> {
 			state,
 			parent_hash,
 			storage,
+			pruned: None,
+		}
+	}
+
+	/// Only allow reading the storage under `archive_prefixes` of `state`, which is the pruned
+	/// state of `block`.
+	fn new_pruned(
+		state: DbState<B>,
+		storage: Arc<StorageDb<B>>,
+		block: B::Hash,
+		archive_prefixes: Vec<Vec<u8>>,
+	) -> Self {
+		RefTrackingState {
+			state,
+			parent_hash: None,
+			storage,
+			pruned: Some(PrunedState { block, archive_prefixes }),
+		}
+	}
+
+	/// Returns true if the storage under `key` can be read.
+	fn is_readable(&self, key: &[u8]) -> bool {
+		self.pruned.as_ref().map_or(true, |pruned| {
+			pruned.archive_prefixes.iter().any(|prefix| key.starts_with(prefix))
+		})
+	}
+
+	fn discarded_error(&self) -> String {
+		let block = self.pruned.as_ref().map(|pruned| &pruned.block);
+		format!("State already discarded for {:?}", block)
+	}
+
+	/// Fails unless the storage under `key` can be read.
+	fn ensure_readable(&self, key: &[u8]) -> Result<(), String> {
+		if self.is_readable(key) {
+			Ok(())
+		} else {
+			Err(self.discarded_error())
+		}
+	}
+
+	/// Fails if only the archived storage can be read.
+	fn ensure_not_pruned(&self) -> Result<(), String> {
+		match self.pruned {
+			Some(_) => Err(self.discarded_error()),
+			None => Ok(()),
 		}
 	}
 }
@@ -148,10 +211,12 @@ impl<B: BlockT> StateBackend<HashFor<B>> for RefTrackingState<B> {
 	type TrieBackendStorage = <DbState<B> as StateBackend<HashFor<B>>>::TrieBackendStorage;
 
 	fn storage(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.ensure_readable(key)?;
 		self.state.storage(key)
 	}
 
 	fn storage_hash(&self, key: &[u8]) -> Result<Option<B::Hash>, Self::Error> {
+		self.ensure_readable(key)?;
 		self.state.storage_hash(key)
 	}
 
@@ -160,10 +225,12 @@ impl<B: BlockT> StateBackend<HashFor<B>> for RefTrackingState<B> {
 		child_info: &ChildInfo,
 		key: &[u8],
 	) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.ensure_not_pruned()?;
 		self.state.child_storage(child_info, key)
 	}
 
 	fn exists_storage(&self, key: &[u8]) -> Result<bool, Self::Error> {
+		self.ensure_readable(key)?;
 		self.state.exists_storage(key)
 	}
 
@@ -172,11 +239,16 @@ impl<B: BlockT> StateBackend<HashFor<B>> for RefTrackingState<B> {
 		child_info: &ChildInfo,
 		key: &[u8],
 	) -> Result<bool, Self::Error> {
+		self.ensure_not_pruned()?;
 		self.state.exists_child_storage(child_info, key)
 	}
 
 	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
-		self.state.next_storage_key(key)
+		self.ensure_readable(key)?;
+		match self.state.next_storage_key(key)? {
+			Some(next) if !self.is_readable(&next) => Err(self.discarded_error()),
+			next => Ok(next),
+		}
 	}
 
 	fn next_child_storage_key(
@@ -184,15 +256,24 @@ impl<B: BlockT> StateBackend<HashFor<B>> for RefTrackingState<B> {
 		child_info: &ChildInfo,
 		key: &[u8],
 	) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.ensure_not_pruned()?;
 		self.state.next_child_storage_key(child_info, key)
 	}
 
 	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F) {
-		self.state.for_keys_with_prefix(prefix, f)
+		if self.is_readable(prefix) {
+			self.state.for_keys_with_prefix(prefix, f)
+		} else {
+			warn!("{}", self.discarded_error());
+		}
 	}
 
 	fn for_key_values_with_prefix<F: FnMut(&[u8], &[u8])>(&self, prefix: &[u8], f: F) {
-		self.state.for_key_values_with_prefix(prefix, f)
+		if self.is_readable(prefix) {
+			self.state.for_key_values_with_prefix(prefix, f)
+		} else {
+			warn!("{}", self.discarded_error());
+		}
 	}
 
 	fn for_keys_in_child_storage<F: FnMut(&[u8])>(
@@ -200,7 +281,10 @@ impl<B: BlockT> StateBackend<HashFor<B>> for RefTrackingState<B> {
 		child_info: &ChildInfo,
 		f: F,
 	) {
-		self.state.for_keys_in_child_storage(child_info, f)
+		match self.ensure_not_pruned() {
+			Ok(()) => self.state.for_keys_in_child_storage(child_info, f),
+			Err(e) => warn!("{}", e),
+		}
 	}
 
 	fn for_child_keys_with_prefix<F: FnMut(&[u8])>(
@@ -209,7 +293,10 @@ impl<B: BlockT> StateBackend<HashFor<B>> for RefTrackingState<B> {
 		prefix: &[u8],
 		f: F,
 	) {
-		self.state.for_child_keys_with_prefix(child_info, prefix, f)
+		match self.ensure_not_pruned() {
+			Ok(()) => self.state.for_child_keys_with_prefix(child_info, prefix, f),
+			Err(e) => warn!("{}", e),
+		}
 	}
 
 	fn storage_root<'a>(
@@ -228,11 +315,22 @@ impl<B: BlockT> StateBackend<HashFor<B>> for RefTrackingState<B> {
 	}
 
 	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
-		self.state.pairs()
+		match self.ensure_not_pruned() {
+			Ok(()) => self.state.pairs(),
+			Err(e) => {
+				warn!("{}", e);
+				Vec::new()
+			},
+		}
 	}
 
 	fn keys(&self, prefix: &[u8]) -> Vec<Vec<u8>> {
-		self.state.keys(prefix)
+		if self.is_readable(prefix) {
+			self.state.keys(prefix)
+		} else {
+			warn!("{}", self.discarded_error());
+			Vec::new()
+		}
 	}
 
 	fn child_keys(
@@ -240,12 +338,22 @@ impl<B: BlockT> StateBackend<HashFor<B>> for RefTrackingState<B> {
 		child_info: &ChildInfo,
 		prefix: &[u8],
 	) -> Vec<Vec<u8>> {
-		self.state.child_keys(child_info, prefix)
+		match self.ensure_not_pruned() {
+			Ok(()) => self.state.child_keys(child_info, prefix),
+			Err(e) => {
+				warn!("{}", e);
+				Vec::new()
+			},
+		}
 	}
 
 	fn as_trie_backend(&mut self)
 		-> Option<&sp_state_machine::TrieBackend<Self::TrieBackendStorage, HashFor<B>>>
 	{
+		// Pruned states can't be executed on nor proven.
+		if self.pruned.is_some() {
+			return None;
+		}
 		self.state.as_trie_backend()
 	}
 
@@ -266,10 +374,62 @@ pub struct DatabaseSettings {
 	pub state_cache_child_ratio: Option<(usize, usize)>,
 	/// Pruning mode.
 	pub pruning: PruningMode,
+	/// Block body pruning mode.
+	pub blocks_pruning: BlocksPruning,
+	/// Pruning mode of the data written through offchain indexing.
+	pub offchain_index_pruning: OffchainIndexPruning,
 	/// Where to find the database.
 	pub source: DatabaseSettingsSrc,
 }
 
+/// Block body pruning mode.
+///
+/// Headers are always kept, independently of this setting and of the state pruning mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlocksPruning {
+	/// Keep the bodies of all blocks.
+	KeepAll,
+	/// Keep the bodies of the last `blocks` finalized blocks only.
+	///
+	/// The bodies of the blocks on other forks are removed once these forks fall behind the
+	/// finalized block.
+	KeepFinalized {
+		/// Number of finalized blocks to keep the body of.
+		blocks: u32,
+		/// Also remove the justifications of pruned blocks.
+		justifications: bool,
+	},
+}
+
+impl Default for BlocksPruning {
+	fn default() -> Self {
+		BlocksPruning::KeepAll
+	}
+}
+
+/// Pruning mode of the data written to the offchain storage through offchain indexing.
+///
+/// Whatever the mode, the data written by blocks that are removed from the best chain is
+/// discarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OffchainIndexPruning {
+	/// Keep the data written by all finalized blocks.
+	KeepAll,
+	/// Keep the data written by the last `blocks` finalized blocks only.
+	///
+	/// Values that have been written again by a more recent block are kept.
+	KeepFinalized {
+		/// Number of finalized blocks to keep the data of.
+		blocks: u32,
+	},
+}
+
+impl Default for OffchainIndexPruning {
+	fn default() -> Self {
+		OffchainIndexPruning::KeepAll
+	}
+}
+
 /// Where to find the database..
 #[derive(Debug, Clone)]
 pub enum DatabaseSettingsSrc {
@@ -314,6 +474,13 @@ impl DatabaseSettingsSrc {
 			_ => false,
 		}
 	}
+	/// Check if database supports iterating over the content of a column.
+	pub fn supports_iteration(&self) -> bool {
+		match self {
+			DatabaseSettingsSrc::ParityDb { .. } | DatabaseSettingsSrc::SubDb { .. } => false,
+			DatabaseSettingsSrc::RocksDb { .. } | DatabaseSettingsSrc::Custom(_) => true,
+		}
+	}
 }
 
 impl std::fmt::Display for DatabaseSettingsSrc {
@@ -405,6 +572,16 @@ impl<Block: BlockT> BlockchainDb<Block> {
 			meta.finalized_hash = hash;
 		}
 	}
+
+	/// Number of the last block whose body has been pruned, or zero if none has been.
+	fn pruned_blocks(&self) -> ClientResult<NumberFor<Block>> {
+		match self.db.get(columns::META, meta_keys::PRUNED_BLOCKS) {
+			Some(number) => Decode::decode(&mut &number[..]).map_err(|err| sp_blockchain::Error::Backend(
+				format!("Error decoding pruned blocks number: {}", err)
+			)),
+			None => Ok(Zero::zero()),
+		}
+	}
 }
 
 impl<Block: BlockT> sc_client_api::blockchain::HeaderBackend<Block> for BlockchainDb<Block> {
@@ -543,18 +720,18 @@ pub struct BlockImportOperation<Block: BlockT> {
 }
 
 impl<Block: BlockT> BlockImportOperation<Block> {
-	fn apply_offchain(&mut self, transaction: &mut Transaction<DbHash>) {
-		for ((prefix, key), value_operation) in self.offchain_storage_updates.drain() {
+	fn offchain_index_changes(&mut self) -> offchain_index::IndexChanges {
+		self.offchain_storage_updates.drain().map(|((prefix, key), value_operation)| {
 			let key: Vec<u8> = prefix
 				.into_iter()
 				.chain(sp_core::sp_std::iter::once(b'/'))
 				.chain(key.into_iter())
 				.collect();
 			match value_operation {
-				OffchainOverlayedChange::SetValue(val) => transaction.set_from_vec(columns::OFFCHAIN, &key, val),
-				OffchainOverlayedChange::Remove => transaction.remove(columns::OFFCHAIN, &key),
+				OffchainOverlayedChange::SetValue(val) => (key, Some(val)),
+				OffchainOverlayedChange::Remove => (key, None),
 			}
-		}
+		}).collect()
 	}
 
 	fn apply_aux(&mut self, transaction: &mut Transaction<DbHash>) {
@@ -782,6 +959,12 @@ pub struct Backend<Block: BlockT> {
 	shared_cache: SharedCache<Block>,
 	import_lock: Arc<RwLock<()>>,
 	is_archive: bool,
+	archive_prefixes: Vec<Vec<u8>>,
+	blocks_pruning: BlocksPruning,
+	blocks_pruned: AtomicBool,
+	#[cfg(any(feature = "with-kvdb-rocksdb", test))]
+	version_path: Option<PathBuf>,
+	offchain_index_pruning: OffchainIndexPruning,
 	io_stats: FrozenForDuration<(kvdb::IoStats, StateUsageInfo)>,
 	state_usage: Arc<StateUsageStats>,
 }
@@ -795,27 +978,84 @@ impl<Block: BlockT> Backend<Block> {
 		Self::from_database(db as Arc<_>, canonicalization_delay, &config)
 	}
 
+	/// Open the database for inspection or maintenance.
+	///
+	/// Unlike [`Backend::new`], the bodies of finalized blocks that fell out of the blocks
+	/// pruning window aren't pruned when opening the database.
+	pub fn new_unpruned(config: DatabaseSettings, canonicalization_delay: u64) -> ClientResult<Self> {
+		let db = crate::utils::open_database::<Block>(&config, DatabaseType::Full)?;
+		Self::from_database_unpruned(db as Arc<_>, canonicalization_delay, &config)
+	}
+
 	/// Create new memory-backed client backend for tests.
 	#[cfg(any(test, feature = "test-helpers"))]
 	pub fn new_test(keep_blocks: u32, canonicalization_delay: u64) -> Self {
+		Self::new_test_with_blocks_pruning(keep_blocks, BlocksPruning::KeepAll, canonicalization_delay)
+	}
+
+	/// Create new memory-backed client backend for tests, with the given block body pruning mode.
+	#[cfg(any(test, feature = "test-helpers"))]
+	pub fn new_test_with_blocks_pruning(
+		keep_blocks: u32,
+		blocks_pruning: BlocksPruning,
+		canonicalization_delay: u64,
+	) -> Self {
 		let db = kvdb_memorydb::create(crate::utils::NUM_COLUMNS);
 		let db = sp_database::as_database(db);
 		let db_setting = DatabaseSettings {
 			state_cache_size: 16777216,
 			state_cache_child_ratio: Some((50, 100)),
 			pruning: PruningMode::keep_blocks(keep_blocks),
+			blocks_pruning,
+			offchain_index_pruning: OffchainIndexPruning::KeepAll,
 			source: DatabaseSettingsSrc::Custom(db),
 		};
 
 		Self::new(db_setting, canonicalization_delay).expect("failed to create test-db")
 	}
 
+	/// Create new memory-backed client backend for tests, keeping the history of the storage
+	/// under `archive_prefixes`.
+	#[cfg(any(test, feature = "test-helpers"))]
+	pub fn new_test_with_archive_prefixes(keep_blocks: u32, archive_prefixes: Vec<Vec<u8>>) -> Self {
+		let db = kvdb_memorydb::create(crate::utils::NUM_COLUMNS);
+		let db = sp_database::as_database(db);
+		let db_setting = DatabaseSettings {
+			state_cache_size: 16777216,
+			state_cache_child_ratio: Some((50, 100)),
+			pruning: PruningMode::keep_blocks(keep_blocks).with_archive_prefixes(archive_prefixes),
+			blocks_pruning: BlocksPruning::KeepAll,
+			offchain_index_pruning: OffchainIndexPruning::KeepAll,
+			source: DatabaseSettingsSrc::Custom(db),
+		};
+
+		Self::new(db_setting, 0).expect("failed to create test-db")
+	}
+
 	fn from_database(
 		db: Arc<dyn Database<DbHash>>,
 		canonicalization_delay: u64,
 		config: &DatabaseSettings,
+	) -> ClientResult<Self> {
+		let backend = Self::from_database_unpruned(db, canonicalization_delay, config)?;
+		backend.prune_blocks_up_to_finalized()?;
+
+		Ok(backend)
+	}
+
+	/// Same as [`Backend::from_database`], without pruning the bodies of finalized blocks.
+	pub(crate) fn from_database_unpruned(
+		db: Arc<dyn Database<DbHash>>,
+		canonicalization_delay: u64,
+		config: &DatabaseSettings,
 	) -> ClientResult<Self> {
 		let is_archive_pruning = config.pruning.is_archive();
+		let archive_prefixes = config.pruning.archive_prefixes().to_vec();
+		if !archive_prefixes.is_empty() && config.source.supports_ref_counting() {
+			return Err(sp_blockchain::Error::Backend(
+				"Archiving storage prefixes is not supported by this database".into()
+			));
+		}
 		let blockchain = BlockchainDb::new(db.clone())?;
 		let meta = blockchain.meta.clone();
 		let map_e = |e: sc_state_db::Error<io::Error>| sp_blockchain::Error::from(
@@ -860,11 +1100,168 @@ impl<Block: BlockT> Backend<Block> {
 			),
 			import_lock: Default::default(),
 			is_archive: is_archive_pruning,
+			archive_prefixes,
+			blocks_pruning: config.blocks_pruning,
+			blocks_pruned: AtomicBool::new(false),
+			#[cfg(any(feature = "with-kvdb-rocksdb", test))]
+			version_path: match &config.source {
+				DatabaseSettingsSrc::RocksDb { path, .. } => Some(path.clone()),
+				_ => None,
+			},
+			offchain_index_pruning: config.offchain_index_pruning,
 			io_stats: FrozenForDuration::new(std::time::Duration::from_secs(1)),
 			state_usage: Arc::new(StateUsageStats::new()),
 		})
 	}
 
+	/// Remove the bodies of finalized blocks that are outside of the blocks pruning window.
+	///
+	/// This brings databases that have been created with a different setting, or before blocks
+	/// pruning existed, in line with the current setting.
+	fn prune_blocks_up_to_finalized(&self) -> ClientResult<()> {
+		let keep = match self.blocks_pruning {
+			BlocksPruning::KeepAll => return Ok(()),
+			BlocksPruning::KeepFinalized { blocks, .. } => blocks,
+		};
+		let finalized: u64 = self.blockchain.meta.read().finalized_number.saturated_into();
+		let target = finalized.saturating_sub(u64::from(keep));
+		let mut next: u64 = self.blockchain.pruned_blocks()?.saturated_into::<u64>() + 1;
+		if next <= target {
+			debug!(target: "db", "Pruning bodies of blocks #{}..#{}", next, target);
+		}
+		while next <= target {
+			let end = std::cmp::min(target, next + PRUNE_BLOCKS_BATCH - 1);
+			let mut transaction = Transaction::new();
+			for number in next..=end {
+				self.prune_block(&mut transaction, number.saturated_into())?;
+			}
+			self.storage.db.commit(transaction)?;
+			next = end + 1;
+		}
+		Ok(())
+	}
+
+	/// Remove the best block `removed_number` and make its parent the best block, committing
+	/// `transaction` along with these changes.
+	///
+	/// `state_commit` reverts the changes of the block in the state-db journal, as returned by
+	/// [`sc_state_db::StateDb::revert_one`]. `removed_hash` is `None` if the header of the removed
+	/// block has been lost, in which case only its state changes are reverted.
+	pub(crate) fn revert_best_block(
+		&self,
+		mut transaction: Transaction<DbHash>,
+		state_commit: Option<sc_state_db::CommitSet<Vec<u8>>>,
+		removed_number: NumberFor<Block>,
+		removed_hash: Option<Block::Hash>,
+		parent_hash: Block::Hash,
+		update_finalized: bool,
+	) -> ClientResult<()> {
+		if let Some(commit) = state_commit {
+			apply_state_commit(&mut transaction, commit);
+		}
+
+		let parent_number = removed_number.saturating_sub(One::one());
+		let key = utils::number_and_hash_to_lookup_key(parent_number, &parent_hash)?;
+		let changes_trie_cache_ops = match removed_hash {
+			Some(removed_hash) => {
+				transaction.remove(columns::KEY_LOOKUP, removed_hash.as_ref());
+				let mut index_update = offchain_index::IndexUpdate::new(
+					&*self.storage.db,
+					self.offchain_index_pruning,
+				);
+				index_update.discard(removed_number.saturated_into(), &removed_hash)?;
+				index_update.into_transaction(&mut transaction);
+				Some(self.changes_tries_storage.revert(
+					&mut transaction,
+					&cache::ComplexBlockId::new(removed_hash, removed_number),
+				)?)
+			},
+			None => None,
+		};
+		if update_finalized {
+			transaction.set_from_vec(columns::META, meta_keys::FINALIZED_BLOCK, key.clone());
+		}
+		transaction.set_from_vec(columns::META, meta_keys::BEST_BLOCK, key);
+		children::remove_children(&mut transaction, columns::META, meta_keys::CHILDREN_PREFIX, parent_hash);
+		self.storage.db.commit(transaction)?;
+		self.changes_tries_storage.post_commit(changes_trie_cache_ops);
+		self.blockchain.update_meta(parent_hash, parent_number, true, update_finalized);
+
+		Ok(())
+	}
+
+	/// Remove the body, and the justification if configured, of the canonical block `number`.
+	fn prune_block(&self, transaction: &mut Transaction<DbHash>, number: NumberFor<Block>) -> ClientResult<()> {
+		if self.blocks_pruning == BlocksPruning::KeepAll {
+			return Ok(());
+		}
+		let id = BlockId::<Block>::Number(number);
+		if let Some(lookup_key) = utils::block_id_to_lookup_key(&*self.storage.db, columns::KEY_LOOKUP, id)? {
+			trace!(target: "db", "Pruning body of block #{}", number);
+			self.prune_body(transaction, &lookup_key)?;
+		}
+		transaction.set_from_vec(columns::META, meta_keys::PRUNED_BLOCKS, number.encode());
+		Ok(())
+	}
+
+	/// Remove the bodies, and the justifications if configured, of the blocks on the branches
+	/// ending with the `displaced` leaves, which can't be finalized anymore.
+	fn prune_displaced_branches(
+		&self,
+		transaction: &mut Transaction<DbHash>,
+		f_header: &Block::Header,
+		displaced: &FinalizationDisplaced<Block::Hash, NumberFor<Block>>,
+	) -> ClientResult<()> {
+		if self.blocks_pruning == BlocksPruning::KeepAll {
+			return Ok(());
+		}
+		for leaf in displaced.leaves() {
+			let ancestor = sp_blockchain::lowest_common_ancestor(
+				&self.blockchain,
+				*leaf,
+				*f_header.parent_hash(),
+			)?;
+			let mut block = self.blockchain.header_metadata(*leaf)?;
+			while block.hash != ancestor.hash {
+				trace!(target: "db", "Pruning body of displaced block #{} ({})", block.number, block.hash);
+				let lookup_key = utils::number_and_hash_to_lookup_key(block.number, block.hash)?;
+				self.prune_body(transaction, &lookup_key)?;
+				block = self.blockchain.header_metadata(block.parent)?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Remove the body, and the justification if configured, of the block at `lookup_key`.
+	fn prune_body(&self, transaction: &mut Transaction<DbHash>, lookup_key: &[u8]) -> ClientResult<()> {
+		let prune_justifications = match self.blocks_pruning {
+			BlocksPruning::KeepAll => return Ok(()),
+			BlocksPruning::KeepFinalized { justifications, .. } => justifications,
+		};
+		self.note_blocks_pruned()?;
+		transaction.remove(columns::BODY, lookup_key);
+		if prune_justifications {
+			transaction.remove(columns::JUSTIFICATION, lookup_key);
+		}
+		Ok(())
+	}
+
+	/// Record in the database version that block bodies may be missing, before the first one
+	/// is removed.
+	fn note_blocks_pruned(&self) -> ClientResult<()> {
+		if self.blocks_pruned.load(AtomicOrdering::Relaxed) {
+			return Ok(());
+		}
+		#[cfg(any(feature = "with-kvdb-rocksdb", test))]
+		{
+			if let Some(path) = &self.version_path {
+				upgrade::mark_blocks_pruned(path)?;
+			}
+		}
+		self.blocks_pruned.store(true, AtomicOrdering::Relaxed);
+		Ok(())
+	}
+
 	/// Handle setting head within a transaction. `route_to` should be the last
 	/// block that existed in the database. `best_to` should be the best block
 	/// to be set.
@@ -875,6 +1272,7 @@ impl<Block: BlockT> Backend<Block> {
 	fn set_head_with_transaction(
 		&self,
 		transaction: &mut Transaction<DbHash>,
+		index_update: &mut offchain_index::IndexUpdate<Block::Hash>,
 		route_to: Block::Hash,
 		best_to: (NumberFor<Block>, Block::Hash),
 	) -> ClientResult<(Vec<Block::Hash>, Vec<Block::Hash>)> {
@@ -904,6 +1302,7 @@ impl<Block: BlockT> Backend<Block> {
 				}
 
 				retracted.push(r.hash.clone());
+				index_update.retract(r.number.saturated_into(), &r.hash)?;
 				utils::remove_number_to_key_mapping(
 					transaction,
 					columns::KEY_LOOKUP,
@@ -914,6 +1313,7 @@ impl<Block: BlockT> Backend<Block> {
 			// canonicalize: set the number lookup to map to this block's hash.
 			for e in tree_route.enacted() {
 				enacted.push(e.hash.clone());
+				index_update.enact(e.number.saturated_into(), &e.hash)?;
 				utils::insert_number_to_key_mapping(
 					transaction,
 					columns::KEY_LOOKUP,
@@ -952,6 +1352,7 @@ impl<Block: BlockT> Backend<Block> {
 	fn finalize_block_with_transaction(
 		&self,
 		transaction: &mut Transaction<DbHash>,
+		index_update: &mut offchain_index::IndexUpdate<Block::Hash>,
 		hash: &Block::Hash,
 		header: &Block::Header,
 		last_finalized: Option<Block::Hash>,
@@ -965,6 +1366,7 @@ impl<Block: BlockT> Backend<Block> {
 
 		self.note_finalized(
 			transaction,
+			index_update,
 			false,
 			header,
 			*hash,
@@ -1021,10 +1423,11 @@ impl<Block: BlockT> Backend<Block> {
 		mut operation: BlockImportOperation<Block>,
 	) -> ClientResult<()> {
 		let mut transaction = Transaction::new();
+		let mut index_update = offchain_index::IndexUpdate::new(&*self.storage.db, self.offchain_index_pruning);
 		let mut finalization_displaced_leaves = None;
 
 		operation.apply_aux(&mut transaction);
-		operation.apply_offchain(&mut transaction);
+		let index_changes = operation.offchain_index_changes();
 
 		let mut meta_updates = Vec::with_capacity(operation.finalized_blocks.len());
 		let mut last_finalized_hash = self.blockchain.meta.read().finalized_hash;
@@ -1036,6 +1439,7 @@ impl<Block: BlockT> Backend<Block> {
 
 			meta_updates.push(self.finalize_block_with_transaction(
 				&mut transaction,
+				&mut index_update,
 				&block_hash,
 				&block_header,
 				Some(last_finalized_hash),
@@ -1055,10 +1459,11 @@ impl<Block: BlockT> Backend<Block> {
 			let lookup_key = utils::number_and_hash_to_lookup_key(number, hash)?;
 
 			let (enacted, retracted) = if pending_block.leaf_state.is_best() {
-				self.set_head_with_transaction(&mut transaction, parent_hash, (number, hash))?
+				self.set_head_with_transaction(&mut transaction, &mut index_update, parent_hash, (number, hash))?
 			} else {
 				(Default::default(), Default::default())
 			};
+			index_update.import(number.saturated_into(), hash, index_changes, pending_block.leaf_state.is_best())?;
 
 			utils::insert_hash_to_key_mapping(
 				&mut transaction,
@@ -1183,6 +1588,7 @@ impl<Block: BlockT> Backend<Block> {
 				self.ensure_sequential_finalization(header, Some(last_finalized_hash))?;
 				self.note_finalized(
 					&mut transaction,
+					&mut index_update,
 					true,
 					header,
 					hash,
@@ -1223,6 +1629,7 @@ impl<Block: BlockT> Backend<Block> {
 
 			Some((number, hash, enacted, retracted, displaced_leaf, is_best, cache))
 		} else {
+			index_update.set(index_changes);
 			None
 		};
 
@@ -1233,6 +1640,7 @@ impl<Block: BlockT> Backend<Block> {
 
 				let (enacted, retracted) = self.set_head_with_transaction(
 					&mut transaction,
+					&mut index_update,
 					hash.clone(),
 					(number.clone(), hash.clone())
 				)?;
@@ -1245,6 +1653,7 @@ impl<Block: BlockT> Backend<Block> {
 			None
 		};
 
+		index_update.into_transaction(&mut transaction);
 		self.storage.db.commit(transaction)?;
 
 		if let Some((
@@ -1289,6 +1698,7 @@ impl<Block: BlockT> Backend<Block> {
 	fn note_finalized(
 		&self,
 		transaction: &mut Transaction<DbHash>,
+		index_update: &mut offchain_index::IndexUpdate<Block::Hash>,
 		is_inserted: bool,
 		f_header: &Block::Header,
 		f_hash: Block::Hash,
@@ -1318,7 +1728,15 @@ impl<Block: BlockT> Backend<Block> {
 			}
 		}
 
+		if let BlocksPruning::KeepFinalized { blocks, .. } = self.blocks_pruning {
+			if f_num > blocks.into() {
+				self.prune_block(transaction, f_num - blocks.into())?;
+			}
+		}
+		index_update.finalize(f_num.saturated_into(), &f_hash)?;
+
 		let new_displaced = self.blockchain.leaves.write().finalize_height(f_num);
+		self.prune_displaced_branches(transaction, f_header, &new_displaced)?;
 		match displaced {
 			x @ &mut None => *x = Some(new_displaced),
 			&mut Some(ref mut displaced) => displaced.merge(new_displaced),
@@ -1431,6 +1849,7 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 		justification: Option<Justification>,
 	) -> ClientResult<()> {
 		let mut transaction = Transaction::new();
+		let mut index_update = offchain_index::IndexUpdate::new(&*self.storage.db, self.offchain_index_pruning);
 		let hash = self.blockchain.expect_block_hash_from_id(&block)?;
 		let header = self.blockchain.expect_header(block)?;
 		let mut displaced = None;
@@ -1438,6 +1857,7 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 		let mut changes_trie_cache_ops = None;
 		let (hash, number, is_best, is_finalized) = self.finalize_block_with_transaction(
 			&mut transaction,
+			&mut index_update,
 			&hash,
 			&header,
 			None,
@@ -1445,6 +1865,7 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 			&mut changes_trie_cache_ops,
 			&mut displaced,
 		)?;
+		index_update.into_transaction(&mut transaction);
 		self.storage.db.commit(transaction)?;
 		self.blockchain.update_meta(hash, number, is_best, is_finalized);
 		self.changes_tries_storage.post_commit(changes_trie_cache_ops);
@@ -1519,7 +1940,6 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 				if best_number.is_zero() {
 					return Ok(c.saturated_into::<NumberFor<Block>>())
 				}
-				let mut transaction = Transaction::new();
 				let removed_number = best_number;
 				let removed = self.blockchain.header(BlockId::Number(best_number))?.ok_or_else(
 					|| sp_blockchain::Error::UnknownBlock(
@@ -1536,40 +1956,24 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 					return Ok(c.saturated_into::<NumberFor<Block>>())
 				}
 
-				match self.storage.state_db.revert_one() {
-					Some(commit) => {
-						apply_state_commit(&mut transaction, commit);
-
-						best_number = prev_number;
-						best_hash = prev_hash;
-
-						let update_finalized = best_number < finalized;
-
-						let key = utils::number_and_hash_to_lookup_key(best_number.clone(), &best_hash)?;
-						let changes_trie_cache_ops = self.changes_tries_storage.revert(
-							&mut transaction,
-							&cache::ComplexBlockId::new(
-								removed.hash(),
-								removed_number,
-							),
-						)?;
-						if update_finalized {
-							transaction.set_from_vec(
-								columns::META,
-								meta_keys::FINALIZED_BLOCK,
-								key.clone()
-							);
-							reverted_finalized.insert(removed_hash);
-						}
-						transaction.set_from_vec(columns::META, meta_keys::BEST_BLOCK, key);
-						transaction.remove(columns::KEY_LOOKUP, removed.hash().as_ref());
-						children::remove_children(&mut transaction, columns::META, meta_keys::CHILDREN_PREFIX, best_hash);
-						self.storage.db.commit(transaction)?;
-						self.changes_tries_storage.post_commit(Some(changes_trie_cache_ops));
-						self.blockchain.update_meta(best_hash, best_number, true, update_finalized);
-					}
-					None => return Ok(c.saturated_into::<NumberFor<Block>>())
+				let state_commit = match self.storage.state_db.revert_one() {
+					Some(commit) => commit,
+					None => return Ok(c.saturated_into::<NumberFor<Block>>()),
+				};
+				let update_finalized = prev_number < finalized;
+				self.revert_best_block(
+					Transaction::new(),
+					Some(state_commit),
+					removed_number,
+					Some(removed_hash),
+					prev_hash,
+					update_finalized,
+				)?;
+				if update_finalized {
+					reverted_finalized.insert(removed_hash);
 				}
+				best_number = prev_number;
+				best_hash = prev_hash;
 			}
 
 			Ok(n)
@@ -1632,6 +2036,24 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 		match self.blockchain.header_metadata(hash) {
 			Ok(ref hdr) => {
 				if !self.have_state_at(&hash, hdr.number) {
+					// Only the storage under the archived prefixes can still be read at pruned
+					// canonical blocks.
+					if !self.archive_prefixes.is_empty() && self.blockchain.hash(hdr.number)? == Some(hash) {
+						let db_state = DbState::<Block>::new(self.storage.clone(), hdr.state_root);
+						let state = RefTrackingState::new_pruned(
+							db_state,
+							self.storage.clone(),
+							hash,
+							self.archive_prefixes.clone(),
+						);
+						let caching_state = CachingState::new(state, self.shared_cache.clone(), None);
+						return Ok(SyncingCachingState::new(
+							caching_state,
+							self.state_usage.clone(),
+							self.blockchain.meta.clone(),
+							self.import_lock.clone(),
+						));
+					}
 					return Err(
 						sp_blockchain::Error::UnknownBlock(
 							format!("State already discarded for {:?}", block)
@@ -1810,6 +2232,8 @@ pub(crate) mod tests {
 			state_cache_size: 16777216,
 			state_cache_child_ratio: Some((50, 100)),
 			pruning: PruningMode::keep_blocks(1),
+			blocks_pruning: BlocksPruning::KeepAll,
+			offchain_index_pruning: OffchainIndexPruning::KeepAll,
 			source: DatabaseSettingsSrc::Custom(backing),
 		}, 0).unwrap();
 		assert_eq!(backend.blockchain().info().best_number, 9);
@@ -1906,6 +2330,65 @@ pub(crate) mod tests {
 		}
 	}
 
+	#[test]
+	fn keeps_history_of_archived_prefixes() {
+		let backend = Backend::<Block>::new(DatabaseSettings {
+			state_cache_size: 16777216,
+			state_cache_child_ratio: Some((50, 100)),
+			pruning: PruningMode::keep_blocks(1).with_archive_prefixes(vec![vec![1]]),
+			blocks_pruning: BlocksPruning::KeepAll,
+			offchain_index_pruning: OffchainIndexPruning::KeepAll,
+			source: DatabaseSettingsSrc::Custom(
+				sp_database::as_database(kvdb_memorydb::create(crate::utils::NUM_COLUMNS)),
+			),
+		}, 0).unwrap();
+
+		let mut parent_hash = Default::default();
+		for number in 0..4u8 {
+			let mut op = backend.begin_operation().unwrap();
+			let parent = if number == 0 {
+				BlockId::Hash(Default::default())
+			} else {
+				BlockId::Hash(parent_hash)
+			};
+			backend.begin_state_operation(&mut op, parent).unwrap();
+			// Values are big enough for leaves not to be inlined in the root node.
+			let storage = vec![
+				(vec![1, 3, 5], Some(vec![number; 32])),
+				(vec![5, 5, 5], Some(vec![number; 32])),
+			];
+			let (root, overlay) = op.old_state.storage_root(
+				storage.iter().map(|(k, v)| (&k[..], v.as_ref().map(|v| &v[..])))
+			);
+			let header = Header {
+				number: number as u64,
+				parent_hash,
+				state_root: root.into(),
+				digest: Default::default(),
+				extrinsics_root: Default::default(),
+			};
+			parent_hash = header.hash();
+			if number == 0 {
+				op.reset_storage(Storage {
+					top: storage.into_iter().map(|(k, v)| (k, v.unwrap())).collect(),
+					children_default: Default::default(),
+				}).unwrap();
+			} else {
+				op.update_db_storage(overlay).unwrap();
+				op.update_storage(storage, Vec::new()).unwrap();
+			}
+			op.set_block_data(header, Some(vec![]), None, NewBlockState::Final).unwrap();
+			backend.commit_operation(op).unwrap();
+		}
+
+		assert!(!backend.have_state_at(&backend.blockchain.hash(0).unwrap().unwrap(), 0));
+		let state = backend.state_at(BlockId::Number(0)).unwrap();
+		assert_eq!(state.storage(&[1, 3, 5]).unwrap(), Some(vec![0; 32]));
+		assert!(state.storage(&[5, 5, 5]).unwrap_err().contains("State already discarded"));
+		assert!(state.keys(&[]).is_empty());
+		assert_eq!(state.keys(&[1]), vec![vec![1, 3, 5]]);
+	}
+
 	#[test]
 	fn delete_only_when_negative_rc() {
 		let _ = ::env_logger::try_init();
@@ -2329,4 +2812,147 @@ pub(crate) mod tests {
 			backend.commit_operation(op).unwrap_err();
 		}
 	}
+
+	#[test]
+	fn prunes_bodies_of_old_finalized_blocks() {
+		let blocks_pruning = BlocksPruning::KeepFinalized { blocks: 2, justifications: true };
+		let backend = Backend::<Block>::new_test_with_blocks_pruning(10, blocks_pruning, 10);
+
+		let mut parent = insert_header(&backend, 0, Default::default(), None, Default::default());
+		for number in 1..6 {
+			parent = insert_header(&backend, number, parent, None, Default::default());
+			backend.finalize_block(BlockId::Number(number), Some(vec![number as u8])).unwrap();
+		}
+
+		let blockchain = backend.blockchain();
+		assert!(blockchain.body(BlockId::Number(0)).unwrap().is_some());
+		for number in 1..4 {
+			assert!(blockchain.header(BlockId::Number(number)).unwrap().is_some());
+			assert_eq!(blockchain.body(BlockId::Number(number)).unwrap(), None);
+			assert_eq!(blockchain.justification(BlockId::Number(number)).unwrap(), None);
+		}
+		for number in 4..6 {
+			assert!(blockchain.body(BlockId::Number(number)).unwrap().is_some());
+			assert_eq!(blockchain.justification(BlockId::Number(number)).unwrap(), Some(vec![number as u8]));
+		}
+	}
+
+	#[test]
+	fn prunes_bodies_of_displaced_forks() {
+		let blocks_pruning = BlocksPruning::KeepFinalized { blocks: 10, justifications: false };
+		let backend = Backend::<Block>::new_test_with_blocks_pruning(10, blocks_pruning, 10);
+		let body = |hash| backend.blockchain().body(BlockId::Hash(hash)).unwrap();
+
+		let genesis = insert_header(&backend, 0, Default::default(), None, Default::default());
+		let a1 = insert_indexed_block(&backend, 1, genesis, b"a1", NewBlockState::Best);
+		let b1 = insert_indexed_block(&backend, 1, genesis, b"b1", NewBlockState::Normal);
+		let b2 = insert_indexed_block(&backend, 2, b1, b"b2", NewBlockState::Normal);
+		let a2 = insert_indexed_block(&backend, 2, a1, b"a2", NewBlockState::Best);
+		let a3 = insert_indexed_block(&backend, 3, a2, b"a3", NewBlockState::Best);
+
+		// `b2` is still a leaf at the finalized height.
+		backend.finalize_block(BlockId::Hash(a2), None).unwrap();
+		assert!(body(b1).is_some());
+		assert!(body(b2).is_some());
+
+		backend.finalize_block(BlockId::Hash(a3), None).unwrap();
+		assert_eq!(body(b1), None);
+		assert_eq!(body(b2), None);
+		for hash in &[genesis, a1, a2, a3] {
+			assert!(body(*hash).is_some());
+		}
+	}
+
+	#[test]
+	fn existing_database_catches_up_with_blocks_pruning() {
+		let backing = {
+			let backend = Backend::<Block>::new_test(10, 10);
+			let mut parent = insert_header(&backend, 0, Default::default(), None, Default::default());
+			for number in 1..6 {
+				parent = insert_header(&backend, number, parent, None, Default::default());
+				backend.finalize_block(BlockId::Number(number), Some(vec![number as u8])).unwrap();
+			}
+			assert!(backend.blockchain().body(BlockId::Number(1)).unwrap().is_some());
+			backend.storage.db.clone()
+		};
+
+		let settings = || DatabaseSettings {
+			state_cache_size: 16777216,
+			state_cache_child_ratio: Some((50, 100)),
+			pruning: PruningMode::keep_blocks(10),
+			blocks_pruning: BlocksPruning::KeepFinalized { blocks: 1, justifications: false },
+			offchain_index_pruning: OffchainIndexPruning::KeepAll,
+			source: DatabaseSettingsSrc::Custom(backing.clone()),
+		};
+
+		// Opening the database for inspection doesn't prune anything.
+		let unpruned = Backend::<Block>::new_unpruned(settings(), 10).unwrap();
+		assert!(unpruned.blockchain().body(BlockId::Number(1)).unwrap().is_some());
+		drop(unpruned);
+
+		let backend = Backend::<Block>::new(settings(), 10).unwrap();
+
+		let blockchain = backend.blockchain();
+		for number in 1..5 {
+			assert_eq!(blockchain.body(BlockId::Number(number)).unwrap(), None);
+			assert_eq!(blockchain.justification(BlockId::Number(number)).unwrap(), Some(vec![number as u8]));
+		}
+		assert!(blockchain.body(BlockId::Number(5)).unwrap().is_some());
+		assert_eq!(blockchain.pruned_blocks().unwrap(), 4);
+	}
+
+	fn insert_indexed_block(
+		backend: &Backend<Block>,
+		number: u64,
+		parent_hash: H256,
+		value: &[u8],
+		state: NewBlockState,
+	) -> H256 {
+		let header = Header {
+			number,
+			parent_hash,
+			state_root: BlakeTwo256::trie_root(Vec::new()),
+			digest: Default::default(),
+			extrinsics_root: BlakeTwo256::hash(value),
+		};
+		let hash = header.hash();
+
+		let mut op = backend.begin_operation().unwrap();
+		backend.begin_state_operation(&mut op, BlockId::Hash(parent_hash)).unwrap();
+		op.set_block_data(header, Some(Vec::new()), None, state).unwrap();
+		let mut changes = OffchainOverlayedChanges::enabled();
+		changes.set(sp_core::offchain::STORAGE_PREFIX, b"key", value);
+		op.update_offchain_storage(changes).unwrap();
+		backend.commit_operation(op).unwrap();
+
+		hash
+	}
+
+	#[test]
+	fn offchain_index_follows_best_chain() {
+		use sp_core::offchain::OffchainStorage;
+
+		let backend = Backend::<Block>::new_test(10, 10);
+		let indexed = || backend.offchain_storage().unwrap().get(b"storage/", b"key");
+		let set_head = |hash| {
+			let mut op = backend.begin_operation().unwrap();
+			op.mark_head(BlockId::Hash(hash)).unwrap();
+			backend.commit_operation(op).unwrap();
+		};
+
+		let genesis = insert_header(&backend, 0, Default::default(), None, Default::default());
+		let a1 = insert_indexed_block(&backend, 1, genesis, b"a1", NewBlockState::Best);
+		let b1 = insert_indexed_block(&backend, 1, genesis, b"b1", NewBlockState::Normal);
+		assert_eq!(indexed(), Some(b"a1".to_vec()));
+
+		set_head(b1);
+		assert_eq!(indexed(), Some(b"b1".to_vec()));
+
+		let b2 = insert_indexed_block(&backend, 2, b1, b"b2", NewBlockState::Best);
+		set_head(a1);
+		assert_eq!(indexed(), Some(b"a1".to_vec()));
+
+		set_head(b2);
+		assert_eq!(indexed(), Some(b"b2".to_vec()));
+	}
 }
@@ -0,0 +1,509 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Moving a database to another backend.
+//!
+//! Every column is copied entry by entry, so the source backend must support iterating over
+//! its content, which ParityDb and SubDb don't. Migrating is therefore one-way: a RocksDb
+//! database can be moved to any backend, but a ParityDb or SubDb database can't be moved
+//! anywhere.
+//!
+//! Backends that count references to trie nodes (ParityDb) store them under their hash only,
+//! while the others prefix the hash with the node position in the trie. Nodes and state-db
+//! journals are converted when moving to a ref-counting backend: every node is written once
+//! for each reference the state-db journals will release when pruning, plus once if it is
+//! still part of a state that isn't being pruned. The opposite conversion would require
+//! walking every trie and isn't supported.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use hash_db::EMPTY_PREFIX;
+use log::{info, warn};
+use sp_blockchain::{Error, HeaderBackend, Result as ClientResult};
+use sp_database::{ColumnId, Database, Transaction};
+use sp_runtime::generic::BlockId;
+use sp_runtime::traits::{Block as BlockT, Hash, HashFor, Header as HeaderT, NumberFor, SaturatedConversion};
+use crate::{Backend, DatabaseSettings, DbHash, DB_HASH_LEN, columns};
+use crate::utils::{self, DatabaseType, Meta};
+
+/// Number of bytes written to the target database in a single transaction.
+const MIGRATION_BATCH_BYTES: usize = 64 * 1024 * 1024;
+
+/// Columns to migrate, along with their name for progress reports.
+const COLUMNS: &[(ColumnId, &str)] = &[
+	(columns::META, "meta"),
+	(columns::STATE, "state"),
+	(columns::STATE_META, "state meta"),
+	(columns::KEY_LOOKUP, "key lookup"),
+	(columns::HEADER, "headers"),
+	(columns::BODY, "bodies"),
+	(columns::JUSTIFICATION, "justifications"),
+	(columns::CHANGES_TRIE, "changes tries"),
+	(columns::AUX, "aux"),
+	(columns::OFFCHAIN, "offchain"),
+	(columns::CACHE, "cache"),
+];
+
+/// Progress of a migration, reported after each batch of entries written to the target.
+#[derive(Debug, Clone)]
+pub struct MigrationProgress {
+	/// Name of the column being copied.
+	pub column: &'static str,
+	/// Number of entries of the column copied so far.
+	pub entries: u64,
+	/// Number of bytes of the column copied so far.
+	pub bytes: u64,
+}
+
+/// Outcome of [`migrate_database`].
+#[derive(Debug)]
+pub struct MigrationSummary<Block: BlockT> {
+	/// Total number of entries copied.
+	pub entries: u64,
+	/// Total number of bytes copied.
+	pub bytes: u64,
+	/// Best block of the migrated database.
+	pub best: (NumberFor<Block>, Block::Hash),
+	/// Finalized block of the migrated database.
+	pub finalized: (NumberFor<Block>, Block::Hash),
+}
+
+/// References to the trie nodes of a database that prefixes node keys, as they must be counted
+/// by a ref-counting database once the prefixes are stripped.
+#[derive(Default)]
+struct NodeReferences {
+	/// Prefixed keys of the nodes deleted by the state-db journals.
+	deleted_keys: HashSet<Vec<u8>>,
+	/// Number of state-db journal records deleting each node, by node hash. Each record
+	/// releases a single reference when its block is pruned, whatever the number of prefixed
+	/// keys of the node it deletes.
+	deletions: HashMap<Vec<u8>, u32>,
+	/// Hashes of the nodes with deletions that are also stored under a key that isn't deleted.
+	live: HashSet<Vec<u8>>,
+}
+
+impl NodeReferences {
+	/// Count the deletions of the state-db journals of `source`.
+	fn from_journals<Block: BlockT>(source: &dyn Database<DbHash>) -> ClientResult<Self> {
+		let mut records = Vec::new();
+		let mut result = Ok(());
+		source.iter(columns::STATE_META, &mut |key, value| {
+			match sc_state_db::journal_deleted_keys::<Block::Hash>(key, value) {
+				Some(Ok(deleted)) => records.push(deleted),
+				Some(Err(e)) => result = Err(Error::Backend(format!("Invalid state-db journal: {:?}", e))),
+				None => (),
+			}
+		})?;
+		result?;
+
+		let mut references = NodeReferences::default();
+		for deleted in records {
+			// Nodes that aren't stored yet are inserted by blocks that aren't canonicalized,
+			// which add the reference released by the deletion.
+			let hashes = deleted.into_iter()
+				.filter(|key| source.get(columns::STATE, key).is_some())
+				.map(|key| {
+					let hash = strip_prefix(&key).to_vec();
+					references.deleted_keys.insert(key);
+					hash
+				})
+				.collect::<HashSet<_>>();
+			for hash in hashes {
+				*references.deletions.entry(hash).or_default() += 1;
+			}
+		}
+		Ok(references)
+	}
+
+	/// Note that the node stored under the prefixed `key` has been found.
+	fn note_key(&mut self, key: &[u8]) {
+		let hash = strip_prefix(key);
+		if self.deletions.contains_key(hash) && !self.deleted_keys.contains(key) {
+			self.live.insert(hash.to_vec());
+		}
+	}
+
+	/// Number of references to the nodes with more than one reference, once all the prefixed
+	/// keys have been noted.
+	fn shared(&self) -> impl Iterator<Item = (&[u8], u32)> + '_ {
+		self.deletions.iter()
+			.map(move |(hash, deletions)| (&hash[..], deletions + self.live.contains(hash) as u32))
+			.filter(|(_, references)| *references > 1)
+	}
+}
+
+/// Copy the full node database at `from` into the empty database at `to`.
+///
+/// `progress` is called after every batch written to the target. Once all columns are
+/// copied, the best and finalized blocks of the target are checked to be the same as in the
+/// source, and their headers and state to be readable. If the migration fails and the target
+/// didn't exist beforehand, it is removed.
+pub fn migrate_database<Block: BlockT>(
+	from: &DatabaseSettings,
+	to: &DatabaseSettings,
+	progress: impl FnMut(&MigrationProgress),
+) -> ClientResult<MigrationSummary<Block>> {
+	if from.source.path().is_some() && from.source.path() == to.source.path() {
+		return Err(Error::Backend("Can't migrate a database onto itself".into()));
+	}
+	if !from.source.supports_iteration() {
+		return Err(Error::Backend(format!(
+			"Migrating from a {} database is not supported, as it can't be iterated over",
+			from.source,
+		)));
+	}
+
+	let created_path = to.source.path().filter(|path| !path.exists());
+	let result = copy_database(from, to, progress);
+	if let (Err(_), Some(path)) = (&result, created_path) {
+		if let Err(err) = std::fs::remove_dir_all(path) {
+			warn!(target: "db", "Failed to remove the partially migrated database at {}: {}", path.display(), err);
+		}
+	}
+	result
+}
+
+fn copy_database<Block: BlockT>(
+	from: &DatabaseSettings,
+	to: &DatabaseSettings,
+	mut progress: impl FnMut(&MigrationProgress),
+) -> ClientResult<MigrationSummary<Block>> {
+	let strip_prefixes = match (from.source.supports_ref_counting(), to.source.supports_ref_counting()) {
+		(true, false) => return Err(Error::Backend(
+			"Migrating from a ref-counting database to one that isn't is not supported".into()
+		)),
+		(false, true) => true,
+		_ => false,
+	};
+
+	let source = utils::open_database::<Block>(from, DatabaseType::Full)?;
+	let source_meta = utils::read_meta::<Block>(&*source, columns::HEADER)?;
+	if source_meta.genesis_hash == Default::default() {
+		return Err(Error::Backend("Source database is empty".into()));
+	}
+	let target = utils::open_database::<Block>(to, DatabaseType::Full)?;
+	if utils::read_meta::<Block>(&*target, columns::HEADER)?.genesis_hash != Default::default() {
+		return Err(Error::Backend("Target database is not empty".into()));
+	}
+
+	let mut references = if strip_prefixes {
+		Some(NodeReferences::from_journals::<Block>(&*source)?)
+	} else {
+		None
+	};
+
+	let mut total_entries = 0;
+	let mut total_bytes = 0;
+	for (column, name) in COLUMNS {
+		info!(target: "db", "Migrating {} column", name);
+		let mut status = MigrationProgress { column: *name, entries: 0, bytes: 0 };
+		let mut transaction = Transaction::new();
+		let mut batch_bytes = 0;
+		// Nodes written in the current batch, which can't be read from the target yet.
+		let mut batch_nodes = HashSet::new();
+		let mut result = Ok(());
+
+		source.iter(*column, &mut |key, value| {
+			if result.is_err() {
+				return;
+			}
+			if let (columns::STATE, Some(references)) = (*column, references.as_mut()) {
+				// Nodes stored under several prefixed keys are written once, and get their
+				// extra references once the whole column has been copied.
+				references.note_key(key);
+				let hash = strip_prefix(key);
+				if batch_nodes.contains(hash) || target.get(columns::STATE, hash).is_some() {
+					return;
+				}
+				batch_nodes.insert(hash.to_vec());
+			}
+			let (key, value) = match convert_entry::<Block>(*column, key, value, strip_prefixes) {
+				Ok(entry) => entry,
+				Err(e) => {
+					result = Err(e);
+					return;
+				},
+			};
+			batch_bytes += key.len() + value.len();
+			status.entries += 1;
+			status.bytes += (key.len() + value.len()) as u64;
+			transaction.set_from_vec(*column, &key, value);
+
+			if batch_bytes >= MIGRATION_BATCH_BYTES {
+				batch_bytes = 0;
+				batch_nodes.clear();
+				result = target.commit(std::mem::take(&mut transaction)).map_err(Into::into);
+				progress(&status);
+			}
+		})?;
+		result?;
+		target.commit(transaction)?;
+		if let (columns::STATE, Some(references)) = (*column, &references) {
+			add_node_references(&*target, references)?;
+		}
+		progress(&status);
+
+		total_entries += status.entries;
+		total_bytes += status.bytes;
+	}
+
+	let info = verify_target::<Block>(target, to, &source_meta)?;
+
+	Ok(MigrationSummary {
+		entries: total_entries,
+		bytes: total_bytes,
+		best: (info.best_number, info.best_hash),
+		finalized: (info.finalized_number, info.finalized_hash),
+	})
+}
+
+/// Write the nodes with more than one reference again, once for each extra reference.
+fn add_node_references(target: &dyn Database<DbHash>, references: &NodeReferences) -> ClientResult<()> {
+	let mut transaction = Transaction::new();
+	for (hash, count) in references.shared() {
+		let value = target.get(columns::STATE, hash).ok_or_else(|| Error::Backend(format!(
+			"Migrated trie node {:?} not found", hash,
+		)))?;
+		for _ in 1..count {
+			transaction.set(columns::STATE, hash, &value);
+		}
+	}
+	target.commit(transaction)?;
+	Ok(())
+}
+
+/// Convert an entry of `column` for the target database.
+fn convert_entry<Block: BlockT>(
+	column: ColumnId,
+	key: &[u8],
+	value: &[u8],
+	strip_prefixes: bool,
+) -> ClientResult<(Vec<u8>, Vec<u8>)> {
+	if !strip_prefixes {
+		return Ok((key.to_vec(), value.to_vec()));
+	}
+	match column {
+		columns::STATE => Ok((strip_prefix(key).to_vec(), value.to_vec())),
+		columns::STATE_META => {
+			let map_key = |key: &[u8]| strip_prefix(key).to_vec();
+			// State-db of a ref-counting database doesn't count insertions.
+			match sc_state_db::map_journal_keys::<Block::Hash>(key, value, false, &map_key) {
+				Some(value) => value
+					.map(|value| (key.to_vec(), value))
+					.map_err(|e| Error::Backend(format!("Invalid state-db journal: {:?}", e))),
+				None => Ok((key.to_vec(), value.to_vec())),
+			}
+		},
+		_ => Ok((key.to_vec(), value.to_vec())),
+	}
+}
+
+/// Strip the trie position from a prefixed node key, keeping the node hash only.
+fn strip_prefix(key: &[u8]) -> &[u8] {
+	&key[key.len().saturating_sub(DB_HASH_LEN)..]
+}
+
+/// Open the migrated database and check that its genesis, best and finalized blocks are the
+/// same as in the source, and that the headers and states of the latter two can be read.
+fn verify_target<Block: BlockT>(
+	target: Arc<dyn Database<DbHash>>,
+	settings: &DatabaseSettings,
+	source: &Meta<NumberFor<Block>, Block::Hash>,
+) -> ClientResult<sp_blockchain::Info<Block>> {
+	// The backend is only used to read the database, so blocks are never canonicalized, nor
+	// their bodies pruned.
+	let backend = Backend::<Block>::from_database_unpruned(target, 0, settings)?;
+	let info = backend.blockchain.info();
+	let empty_root = HashFor::<Block>::trie_root(Vec::new());
+
+	if info.genesis_hash != source.genesis_hash {
+		return Err(Error::Backend(format!(
+			"Migrated genesis block {:?} differs from source {:?}", info.genesis_hash, source.genesis_hash,
+		)));
+	}
+
+	let check = |name: &str, source: (NumberFor<Block>, Block::Hash), target: (NumberFor<Block>, Block::Hash)| {
+		let fail = |problem: &str| Err(Error::Backend(format!(
+			"Migrated {} block #{} ({:?}) {}", name, target.0, target.1, problem,
+		)));
+		if source != target {
+			return fail(&format!("differs from source #{} ({:?})", source.0, source.1));
+		}
+		let header = match backend.blockchain.header(BlockId::Hash(target.1))? {
+			Some(header) => header,
+			None => return fail("has no header"),
+		};
+		let root = *header.state_root();
+		let pruned = backend.storage.state_db.is_pruned(&target.1, target.0.saturated_into::<u64>());
+		let found = root == empty_root ||
+			sp_state_machine::Storage::get(backend.storage.as_ref(), &root, EMPTY_PREFIX)
+				.map_err(Error::Backend)?
+				.is_some();
+		if !pruned && !found {
+			return fail(&format!("has no state root {:?}", root));
+		}
+		Ok(())
+	};
+
+	check("best", (source.best_number, source.best_hash), (info.best_number, info.best_hash))?;
+	check(
+		"finalized",
+		(source.finalized_number, source.finalized_hash),
+		(info.finalized_number, info.finalized_hash),
+	)?;
+	Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use sc_client_api::backend::Backend as _;
+	use sp_blockchain::HeaderBackend;
+	use sp_database::{Database, MemDb, Transaction};
+	use sp_runtime::generic::BlockId;
+	use crate::tests::{Block, insert_header};
+	use crate::{
+		Backend, BlocksPruning, DatabaseSettings, DatabaseSettingsSrc, DbHash, OffchainIndexPruning,
+		PruningMode, columns,
+	};
+	use crate::utils::{number_and_hash_to_lookup_key, read_meta};
+	use super::{migrate_database, convert_entry, verify_target, NodeReferences};
+
+	fn settings(source: DatabaseSettingsSrc) -> DatabaseSettings {
+		DatabaseSettings {
+			state_cache_size: 16777216,
+			state_cache_child_ratio: Some((50, 100)),
+			pruning: PruningMode::keep_blocks(10),
+			blocks_pruning: BlocksPruning::KeepAll,
+			offchain_index_pruning: OffchainIndexPruning::KeepAll,
+			source,
+		}
+	}
+
+	#[test]
+	fn migrates_all_blocks() {
+		let backend = Backend::<Block>::new_test(10, 10);
+		let mut parent = insert_header(&backend, 0, Default::default(), None, Default::default());
+		for number in 1..5 {
+			parent = insert_header(&backend, number, parent, None, Default::default());
+		}
+		backend.finalize_block(BlockId::Number(2), None).unwrap();
+
+		let target = Arc::new(MemDb::default());
+		let mut reports = 0;
+		let summary = migrate_database::<Block>(
+			&settings(DatabaseSettingsSrc::Custom(backend.storage.db.clone())),
+			&settings(DatabaseSettingsSrc::Custom(target.clone())),
+			|_| reports += 1,
+		).unwrap();
+
+		assert_eq!(reports, super::COLUMNS.len());
+		assert_eq!(summary.best, (4, parent));
+		assert_eq!(summary.finalized, (2, backend.blockchain.hash(2).unwrap().unwrap()));
+		assert_eq!(target.count(columns::HEADER), 5);
+
+		let migrated = Backend::<Block>::new(settings(DatabaseSettingsSrc::Custom(target)), 10).unwrap();
+		assert_eq!(migrated.blockchain.info(), backend.blockchain.info());
+	}
+
+	#[test]
+	fn refuses_non_empty_target() {
+		let backend = Backend::<Block>::new_test(10, 10);
+		insert_header(&backend, 0, Default::default(), None, Default::default());
+		let source = settings(DatabaseSettingsSrc::Custom(backend.storage.db.clone()));
+
+		assert!(migrate_database::<Block>(&source, &source, |_| ()).is_err());
+	}
+
+	#[test]
+	fn refuses_sources_without_iteration() {
+		let dir = tempfile::tempdir().unwrap();
+		let target = settings(DatabaseSettingsSrc::Custom(Arc::new(MemDb::default())));
+		for source in vec![
+			DatabaseSettingsSrc::ParityDb { path: dir.path().join("paritydb") },
+			DatabaseSettingsSrc::SubDb { path: dir.path().join("subdb") },
+		] {
+			assert!(migrate_database::<Block>(&settings(source), &target, |_| ()).is_err());
+		}
+	}
+
+	#[test]
+	fn verification_detects_missing_blocks() {
+		let backend = Backend::<Block>::new_test(10, 10);
+		let parent = insert_header(&backend, 0, Default::default(), None, Default::default());
+		let best = insert_header(&backend, 1, parent, None, Default::default());
+		let db = backend.storage.db.clone();
+		let meta = read_meta::<Block>(&*db, columns::HEADER).unwrap();
+		let settings = settings(DatabaseSettingsSrc::Custom(db.clone()));
+		assert!(verify_target::<Block>(db.clone(), &settings, &meta).is_ok());
+
+		let mut transaction = Transaction::new();
+		transaction.remove(columns::HEADER, &number_and_hash_to_lookup_key(1u64, best).unwrap());
+		db.commit(transaction).unwrap();
+		assert!(verify_target::<Block>(db, &settings, &meta).is_err());
+	}
+
+	#[test]
+	fn counts_node_references_from_journals() {
+		use sc_state_db::{ChangeSet, CommitSet, StateDb};
+		use sp_core::H256;
+
+		let db: Arc<dyn Database<DbHash>> = Arc::new(MemDb::default());
+		let state_db = StateDb::<H256, Vec<u8>>::new(
+			PruningMode::keep_blocks(10),
+			true,
+			&crate::StateMetaDb(&*db),
+		).unwrap();
+		let commit = |changes: CommitSet<Vec<u8>>| {
+			let mut transaction = Transaction::new();
+			crate::apply_state_commit(&mut transaction, changes);
+			db.commit(transaction).unwrap();
+			state_db.apply_pending();
+		};
+		let node = vec![7; 32];
+		let key = |prefix: u8| [vec![prefix], node.clone()].concat();
+
+		// Block 1 stores the node under two keys, block 2 deletes one of them.
+		commit(state_db.insert_block::<()>(&H256::repeat_byte(1), 1, &H256::zero(), ChangeSet {
+			inserted: vec![(key(1), b"node".to_vec()), (key(2), b"node".to_vec())],
+			deleted: vec![],
+		}).unwrap());
+		commit(state_db.insert_block::<()>(&H256::repeat_byte(2), 2, &H256::repeat_byte(1), ChangeSet {
+			inserted: vec![],
+			deleted: vec![key(1)],
+		}).unwrap());
+		commit(state_db.canonicalize_block::<()>(&H256::repeat_byte(1)).unwrap());
+		commit(state_db.canonicalize_block::<()>(&H256::repeat_byte(2)).unwrap());
+
+		let mut references = NodeReferences::from_journals::<Block>(&*db).unwrap();
+		db.iter(columns::STATE, &mut |key, _| references.note_key(key)).unwrap();
+
+		// One reference is released when block 2 is pruned, the other one is still live.
+		assert_eq!(references.shared().collect::<Vec<_>>(), vec![(&node[..], 2)]);
+	}
+
+	#[test]
+	fn strips_state_key_prefixes() {
+		let key = [vec![1, 2, 3], vec![7; 32]].concat();
+
+		let (converted, _) = convert_entry::<Block>(columns::STATE, &key, &[], true).unwrap();
+		assert_eq!(converted, vec![7; 32]);
+		let (kept, _) = convert_entry::<Block>(columns::STATE, &key, &[], false).unwrap();
+		assert_eq!(kept, key);
+	}
+}
@@ -19,12 +19,13 @@
 //! RocksDB-based offchain workers local storage.
 
 use std::{
-	collections::HashMap,
+	collections::{BTreeMap, HashMap},
 	sync::Arc,
 };
 
 use crate::{columns, Database, DbHash, Transaction};
 use parking_lot::Mutex;
+use sp_core::offchain::IterationError;
 use log::error;
 
 /// Offchain local storage
@@ -85,6 +86,38 @@ impl sp_core::offchain::OffchainStorage for LocalStorage {
 		self.db.get(columns::OFFCHAIN, &key)
 	}
 
+	fn iter_prefix(
+		&self,
+		prefix: &[u8],
+		key_prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: usize,
+	) -> Result<Vec<(Vec<u8>, Vec<u8>)>, IterationError> {
+		let full_prefix: Vec<u8> = prefix.iter().chain(key_prefix).cloned().collect();
+		// The database yields entries in no particular order, only the `count` smallest keys
+		// are kept.
+		let mut entries = BTreeMap::new();
+		let result = self.db.iter_with_prefix(columns::OFFCHAIN, &full_prefix, &mut |key, value| {
+			let key = &key[prefix.len()..];
+			if start_key.map_or(false, |start_key| key <= start_key) {
+				return;
+			}
+			if entries.len() >= count {
+				let largest = match entries.keys().next_back() {
+					Some(largest) if key < &largest[..] => Vec::clone(largest),
+					_ => return,
+				};
+				entries.remove(&largest);
+			}
+			entries.insert(key.to_vec(), value.to_vec());
+		});
+		if let Err(err) = result {
+			error!("Error iterating over local storage: {}", err);
+			return Err(IterationError);
+		}
+		Ok(entries.into_iter().collect())
+	}
+
 	fn compare_and_set(
 		&mut self,
 		prefix: &[u8],
@@ -153,4 +186,56 @@ mod tests {
 		assert!(storage.locks.lock().is_empty(), "Locks map should be empty!");
 	}
 
+	#[test]
+	fn should_iterate_over_prefix() {
+		let mut storage = LocalStorage::new_test();
+		storage.set(b"prefix", b"key1", b"value1");
+		storage.set(b"prefix", b"other", b"value2");
+		storage.set(b"other", b"key2", b"value3");
+
+		assert_eq!(
+			storage.iter_prefix(b"prefix", b"key", None, 10),
+			Ok(vec![(b"key1".to_vec(), b"value1".to_vec())]),
+		);
+	}
+
+	#[test]
+	fn should_iterate_over_pages() {
+		let mut storage = LocalStorage::new_test();
+		for key in &[b"key3", b"key1", b"key4", b"key2"] {
+			storage.set(b"prefix", *key, b"value");
+		}
+		let keys = |start_key: Option<&[u8]>, count| storage.iter_prefix(b"prefix", b"key", start_key, count)
+			.unwrap()
+			.into_iter()
+			.map(|(key, _)| key)
+			.collect::<Vec<_>>();
+
+		assert_eq!(keys(None, 2), vec![b"key1".to_vec(), b"key2".to_vec()]);
+		assert_eq!(keys(Some(b"key2"), 2), vec![b"key3".to_vec(), b"key4".to_vec()]);
+		assert_eq!(keys(Some(b"key4"), 2), Vec::<Vec<u8>>::new());
+		assert_eq!(keys(None, 0), Vec::<Vec<u8>>::new());
+	}
+
+	#[test]
+	fn should_fail_to_iterate_without_database_support() {
+		struct NoIteration;
+
+		impl Database<DbHash> for NoIteration {
+			fn commit(&self, _: Transaction<DbHash>) -> sp_database::error::Result<()> {
+				Ok(())
+			}
+
+			fn get(&self, _: sp_database::ColumnId, _: &[u8]) -> Option<Vec<u8>> {
+				None
+			}
+
+			fn lookup(&self, _: &DbHash) -> Option<Vec<u8>> {
+				None
+			}
+		}
+
+		let storage = LocalStorage::new(Arc::new(NoIteration));
+		assert_eq!(storage.iter_prefix(b"prefix", b"key", None, 10), Err(IterationError));
+	}
 }
@@ -0,0 +1,376 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Fork-aware handling of the offchain storage writes made through offchain indexing.
+//!
+//! The writes of a block are only visible in the offchain storage while the block is part of
+//! the best chain. They are journaled along with the values they replaced, so they can be
+//! reverted when the block is retracted and applied again when it is enacted. Journals are
+//! stored by block number, and those of blocks that can't be retracted anymore are dropped on
+//! finalization, unless they are still needed to prune the data written by their block.
+//!
+//! When the data is pruned, the block that last wrote each key is tracked as well, so that a
+//! value is only removed along with the block that wrote it.
+
+use std::collections::{HashMap, hash_map::Entry};
+use codec::{Decode, Encode};
+use sp_blockchain::{Error, Result as ClientResult};
+use sp_database::{Database, Transaction};
+use crate::{DbHash, OffchainIndexPruning, columns};
+use crate::utils::meta_keys;
+
+/// Offchain storage keys with their new value, `None` for removed keys.
+pub(crate) type IndexChanges = Vec<(Vec<u8>, Option<Vec<u8>>)>;
+
+/// Number and hash of a block.
+type Writer<H> = (u64, H);
+
+#[derive(Encode, Decode)]
+struct BlockJournal<H> {
+	hash: H,
+	/// Changes made by the block.
+	changes: IndexChanges,
+	/// Values replaced by the changes, while they are applied.
+	undo: Option<Vec<Undo<H>>>,
+}
+
+/// Value of a key before a block changed it.
+#[derive(Encode, Decode)]
+struct Undo<H> {
+	key: Vec<u8>,
+	value: Option<Vec<u8>>,
+	/// Block that wrote `value`, if it is tracked.
+	writer: Option<Writer<H>>,
+}
+
+/// Offchain index changes of a database transaction.
+///
+/// Changes only reach the database once added to a transaction with
+/// [`IndexUpdate::into_transaction`].
+pub(crate) struct IndexUpdate<'a, H> {
+	db: &'a dyn Database<DbHash>,
+	pruning: OffchainIndexPruning,
+	values: HashMap<Vec<u8>, Option<Vec<u8>>>,
+	/// Block that last wrote each key, only tracked when the data is pruned.
+	writers: HashMap<Vec<u8>, Option<Writer<H>>>,
+	journals: HashMap<u64, Vec<BlockJournal<H>>>,
+}
+
+impl<'a, H: Encode + Decode + PartialEq + Clone> IndexUpdate<'a, H> {
+	/// Create an empty update on top of `db`, for data pruned according to `pruning`.
+	pub fn new(db: &'a dyn Database<DbHash>, pruning: OffchainIndexPruning) -> Self {
+		IndexUpdate {
+			db,
+			pruning,
+			values: HashMap::new(),
+			writers: HashMap::new(),
+			journals: HashMap::new(),
+		}
+	}
+
+	/// Set offchain storage values directly, without journaling them.
+	///
+	/// These values aren't pruned along with any block.
+	pub fn set(&mut self, changes: IndexChanges) {
+		if self.tracks_writers() {
+			self.writers.extend(changes.iter().map(|(key, _)| (key.clone(), None)));
+		}
+		self.values.extend(changes);
+	}
+
+	/// Journal the changes of an imported block, applying them if it is the new best block.
+	pub fn import(&mut self, number: u64, hash: H, changes: IndexChanges, is_best: bool) -> ClientResult<()> {
+		let journals = load_journals(self.db, &mut self.journals, number)?;
+		journals.retain(|journal| journal.hash != hash);
+		if changes.is_empty() {
+			return Ok(());
+		}
+		let mut journal = BlockJournal { hash, changes, undo: None };
+		if is_best {
+			self.apply(number, &mut journal)?;
+		}
+		load_journals(self.db, &mut self.journals, number)?.push(journal);
+		Ok(())
+	}
+
+	/// Apply the changes of a block that has been added to the best chain.
+	pub fn enact(&mut self, number: u64, hash: &H) -> ClientResult<()> {
+		let journals = load_journals(self.db, &mut self.journals, number)?;
+		if let Some(index) = journals.iter().position(|journal| &journal.hash == hash) {
+			let mut journal = journals.swap_remove(index);
+			self.apply(number, &mut journal)?;
+			load_journals(self.db, &mut self.journals, number)?.push(journal);
+		}
+		Ok(())
+	}
+
+	/// Revert the changes of a block that has been removed from the best chain.
+	pub fn retract(&mut self, number: u64, hash: &H) -> ClientResult<()> {
+		let journals = load_journals(self.db, &mut self.journals, number)?;
+		let undo = journals.iter_mut()
+			.find(|journal| &journal.hash == hash)
+			.and_then(|journal| journal.undo.take());
+		let tracks_writers = self.tracks_writers();
+		for Undo { key, value, writer } in undo.into_iter().flatten() {
+			if tracks_writers {
+				self.writers.insert(key.clone(), writer);
+			}
+			self.values.insert(key, value);
+		}
+		Ok(())
+	}
+
+	/// Revert the changes of a block and forget about them.
+	pub fn discard(&mut self, number: u64, hash: &H) -> ClientResult<()> {
+		self.retract(number, hash)?;
+		load_journals(self.db, &mut self.journals, number)?.retain(|journal| &journal.hash != hash);
+		Ok(())
+	}
+
+	/// Drop the journals that aren't needed anymore once block `number` is finalized, and prune
+	/// the data that is out of the pruning window.
+	pub fn finalize(&mut self, number: u64, hash: &H) -> ClientResult<()> {
+		let journals = load_journals(self.db, &mut self.journals, number)?;
+		// Blocks of other forks can't be enacted anymore.
+		journals.retain(|journal| &journal.hash == hash);
+
+		match self.pruning {
+			OffchainIndexPruning::KeepAll => journals.clear(),
+			OffchainIndexPruning::KeepFinalized { blocks } => {
+				if let Some(pruned) = number.checked_sub(blocks.into()) {
+					let journals = std::mem::take(load_journals(self.db, &mut self.journals, pruned)?);
+					for journal in journals {
+						let writer = Some((pruned, journal.hash));
+						for (key, _) in journal.changes {
+							// Values that have been written by a more recent block are kept.
+							if self.writer(&key)? == writer {
+								self.values.insert(key.clone(), None);
+								self.writers.insert(key, None);
+							}
+						}
+					}
+				}
+			},
+		}
+		Ok(())
+	}
+
+	fn tracks_writers(&self) -> bool {
+		self.pruning != OffchainIndexPruning::KeepAll
+	}
+
+	fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+		match self.values.get(key) {
+			Some(value) => value.clone(),
+			None => self.db.get(columns::OFFCHAIN, key),
+		}
+	}
+
+	fn writer(&self, key: &[u8]) -> ClientResult<Option<Writer<H>>> {
+		if let Some(writer) = self.writers.get(key) {
+			return Ok(writer.clone());
+		}
+		match self.db.get(columns::META, &writer_key(key)) {
+			Some(encoded) => Decode::decode(&mut &encoded[..]).map(Some).map_err(|_| Error::Backend(
+				"Error decoding the writer of an offchain index key".into()
+			)),
+			None => Ok(None),
+		}
+	}
+
+	/// Apply the changes of `journal`, written by block `number`, unless they are already applied.
+	fn apply(&mut self, number: u64, journal: &mut BlockJournal<H>) -> ClientResult<()> {
+		if journal.undo.is_some() {
+			return Ok(());
+		}
+		let tracks_writers = self.tracks_writers();
+		let mut undo = Vec::with_capacity(journal.changes.len());
+		for (key, value) in &journal.changes {
+			let writer = if tracks_writers {
+				let writer = self.writer(key)?;
+				self.writers.insert(key.clone(), Some((number, journal.hash.clone())));
+				writer
+			} else {
+				None
+			};
+			undo.push(Undo { key: key.clone(), value: self.get(key), writer });
+			self.values.insert(key.clone(), value.clone());
+		}
+		journal.undo = Some(undo);
+		Ok(())
+	}
+
+	/// Add the changes to `transaction`.
+	pub fn into_transaction(self, transaction: &mut Transaction<DbHash>) {
+		for (key, value) in self.values {
+			match value {
+				Some(value) => transaction.set_from_vec(columns::OFFCHAIN, &key, value),
+				None => transaction.remove(columns::OFFCHAIN, &key),
+			}
+		}
+		for (key, writer) in self.writers {
+			let key = writer_key(&key);
+			match writer {
+				Some(writer) => transaction.set_from_vec(columns::META, &key, writer.encode()),
+				None => transaction.remove(columns::META, &key),
+			}
+		}
+		for (number, journals) in self.journals {
+			let key = journal_key(number);
+			if journals.is_empty() {
+				transaction.remove(columns::META, &key);
+			} else {
+				transaction.set_from_vec(columns::META, &key, journals.encode());
+			}
+		}
+	}
+}
+
+fn journal_key(number: u64) -> Vec<u8> {
+	let mut key = meta_keys::OFFCHAIN_INDEX_JOURNAL.to_vec();
+	key.extend_from_slice(&number.to_be_bytes());
+	key
+}
+
+fn writer_key(key: &[u8]) -> Vec<u8> {
+	let mut writer_key = meta_keys::OFFCHAIN_INDEX_WRITER.to_vec();
+	writer_key.extend_from_slice(key);
+	writer_key
+}
+
+fn load_journals<'j, H: Decode>(
+	db: &dyn Database<DbHash>,
+	journals: &'j mut HashMap<u64, Vec<BlockJournal<H>>>,
+	number: u64,
+) -> ClientResult<&'j mut Vec<BlockJournal<H>>> {
+	match journals.entry(number) {
+		Entry::Occupied(entry) => Ok(entry.into_mut()),
+		Entry::Vacant(entry) => {
+			let loaded = match db.get(columns::META, &journal_key(number)) {
+				Some(encoded) => Decode::decode(&mut &encoded[..]).map_err(|_| Error::Backend(
+					format!("Error decoding offchain index journal of block #{}", number)
+				))?,
+				None => Vec::new(),
+			};
+			Ok(entry.insert(loaded))
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use sp_database::{Database, MemDb, Transaction};
+	use crate::{DbHash, OffchainIndexPruning, columns};
+	use super::IndexUpdate;
+
+	fn commit(db: &MemDb<DbHash>, pruning: OffchainIndexPruning, f: impl FnOnce(&mut IndexUpdate<u64>)) {
+		let mut update = IndexUpdate::new(db, pruning);
+		f(&mut update);
+		let mut transaction = Transaction::new();
+		update.into_transaction(&mut transaction);
+		db.commit(transaction).unwrap();
+	}
+
+	fn set(key: &[u8], value: &[u8]) -> (Vec<u8>, Option<Vec<u8>>) {
+		(key.to_vec(), Some(value.to_vec()))
+	}
+
+	fn journal_count(db: &MemDb<DbHash>) -> usize {
+		(0..10).filter(|number| db.get(columns::META, &super::journal_key(*number)).is_some()).count()
+	}
+
+	#[test]
+	fn retracted_changes_are_reverted_and_enacted_again() {
+		let db = MemDb::default();
+		let pruning = OffchainIndexPruning::KeepAll;
+		commit(&db, pruning, |update| update.import(1, 10, vec![set(b"key", b"a1")], true).unwrap());
+		commit(&db, pruning, |update| update.import(1, 11, vec![set(b"key", b"b1")], false).unwrap());
+		assert_eq!(db.get(columns::OFFCHAIN, b"key"), Some(b"a1".to_vec()));
+
+		commit(&db, pruning, |update| {
+			update.retract(1, &10).unwrap();
+			update.enact(1, &11).unwrap();
+		});
+		assert_eq!(db.get(columns::OFFCHAIN, b"key"), Some(b"b1".to_vec()));
+
+		commit(&db, pruning, |update| update.retract(1, &11).unwrap());
+		assert_eq!(db.get(columns::OFFCHAIN, b"key"), None);
+		assert_eq!(db.count(columns::META), 1);
+	}
+
+	#[test]
+	fn finalization_drops_forks_and_prunes_old_data() {
+		let db = MemDb::default();
+		let pruning = OffchainIndexPruning::KeepFinalized { blocks: 1 };
+		commit(&db, pruning, |update| {
+			update.import(1, 10, vec![set(b"old", b"1"), set(b"kept", b"1")], true).unwrap();
+			update.import(1, 11, vec![set(b"fork", b"1")], false).unwrap();
+			update.import(2, 20, vec![set(b"kept", b"2")], true).unwrap();
+		});
+
+		commit(&db, pruning, |update| update.finalize(1, &10).unwrap());
+		assert_eq!(db.get(columns::OFFCHAIN, b"old"), Some(b"1".to_vec()));
+		assert_eq!(journal_count(&db), 2);
+
+		commit(&db, pruning, |update| update.finalize(2, &20).unwrap());
+		assert_eq!(db.get(columns::OFFCHAIN, b"old"), None);
+		assert_eq!(db.get(columns::OFFCHAIN, b"kept"), Some(b"2".to_vec()));
+		assert_eq!(journal_count(&db), 1);
+		// Only the writer of `kept` is still tracked.
+		assert_eq!(db.count(columns::META), 2);
+	}
+
+	#[test]
+	fn values_rewritten_with_the_same_bytes_are_kept() {
+		let db = MemDb::default();
+		let pruning = OffchainIndexPruning::KeepFinalized { blocks: 1 };
+		commit(&db, pruning, |update| {
+			update.import(1, 10, vec![set(b"key", b"same")], true).unwrap();
+			update.import(2, 20, vec![set(b"key", b"same")], true).unwrap();
+			update.finalize(1, &10).unwrap();
+		});
+		commit(&db, pruning, |update| update.finalize(2, &20).unwrap());
+		assert_eq!(db.get(columns::OFFCHAIN, b"key"), Some(b"same".to_vec()));
+
+		commit(&db, pruning, |update| {
+			update.import(3, 30, Vec::new(), true).unwrap();
+			update.finalize(3, &30).unwrap();
+		});
+		assert_eq!(db.get(columns::OFFCHAIN, b"key"), None);
+		assert_eq!(db.get(columns::META, &super::writer_key(b"key")), None);
+	}
+
+	#[test]
+	fn retraction_restores_the_previous_writer() {
+		let db = MemDb::default();
+		let pruning = OffchainIndexPruning::KeepFinalized { blocks: 1 };
+		commit(&db, pruning, |update| {
+			update.import(1, 10, vec![set(b"key", b"a")], true).unwrap();
+			update.import(2, 20, vec![set(b"key", b"b")], true).unwrap();
+		});
+		commit(&db, pruning, |update| update.retract(2, &20).unwrap());
+		assert_eq!(db.get(columns::OFFCHAIN, b"key"), Some(b"a".to_vec()));
+
+		commit(&db, pruning, |update| {
+			update.finalize(1, &10).unwrap();
+			update.import(2, 21, Vec::new(), true).unwrap();
+			update.finalize(2, &21).unwrap();
+		});
+		assert_eq!(db.get(columns::OFFCHAIN, b"key"), None);
+	}
+}
@@ -27,23 +27,45 @@ use crate::utils::DatabaseType;
 const VERSION_FILE_NAME: &'static str = "db_version";
 
 /// Current db version.
-const CURRENT_VERSION: u32 = 1;
+///
+/// Version 2 databases may miss the bodies and justifications of old finalized blocks, see
+/// `BlocksPruning`. A database is only moved to this version once blocks have actually been
+/// pruned, so that it can still be opened by older nodes until then.
+const CURRENT_VERSION: u32 = 2;
+
+/// Version of the databases that have the bodies of all blocks.
+const UNPRUNED_VERSION: u32 = 1;
 
 /// Upgrade database to current version.
 pub fn upgrade_db<Block: BlockT>(db_path: &Path, _db_type: DatabaseType) -> sp_blockchain::Result<()> {
 	let is_empty = db_path.read_dir().map_or(true, |mut d| d.next().is_none());
-	if !is_empty {
-		let db_version = current_version(db_path)?;
-		match db_version {
-			0 => Err(sp_blockchain::Error::Backend(format!("Unsupported database version: {}", db_version)))?,
-			1 => (),
-			_ => Err(sp_blockchain::Error::Backend(format!("Future database version: {}", db_version)))?,
-		}
+	if is_empty {
+		return update_version(db_path, UNPRUNED_VERSION);
 	}
 
-	update_version(db_path)
+	let db_version = current_version(db_path)?;
+	match db_version {
+		0 => Err(sp_blockchain::Error::Backend(format!("Unsupported database version: {}", db_version)))?,
+		// Bodies outside of the blocks pruning window are removed when the backend is opened,
+		// which moves the database to the current version.
+		UNPRUNED_VERSION => (),
+		CURRENT_VERSION => (),
+		_ => Err(sp_blockchain::Error::Backend(format!("Future database version: {}", db_version)))?,
+	}
+
+	Ok(())
 }
 
+/// Record that block bodies may be missing from the database.
+///
+/// Must be called before the first body is removed.
+pub fn mark_blocks_pruned(db_path: &Path) -> sp_blockchain::Result<()> {
+	if current_version(db_path)? == CURRENT_VERSION {
+		return Ok(());
+	}
+
+	update_version(db_path, CURRENT_VERSION)
+}
 
 /// Reads current database version from the file at given path.
 /// If the file does not exist returns 0.
@@ -66,12 +88,12 @@ fn db_err(err: std::io::Error) -> sp_blockchain::Error {
 	sp_blockchain::Error::Backend(format!("{}", err))
 }
 
-/// Writes current database version to the file.
+/// Writes the given database version to the file.
 /// Creates a new file if the version file does not exist yet.
-fn update_version(path: &Path) -> sp_blockchain::Result<()> {
+fn update_version(path: &Path, version: u32) -> sp_blockchain::Result<()> {
 	fs::create_dir_all(path).map_err(db_err)?;
 	let mut file = fs::File::create(version_file_path(path)).map_err(db_err)?;
-	file.write_all(format!("{}", CURRENT_VERSION).as_bytes()).map_err(db_err)?;
+	file.write_all(format!("{}", version).as_bytes()).map_err(db_err)?;
 	Ok(())
 }
 
@@ -85,8 +107,10 @@ fn version_file_path(path: &Path) -> PathBuf {
 #[cfg(test)]
 mod tests {
 	use sc_state_db::PruningMode;
-	use crate::{DatabaseSettings, DatabaseSettingsSrc};
-	use crate::tests::Block;
+	use sp_runtime::generic::BlockId;
+	use sc_client_api::backend::Backend as _;
+	use crate::{Backend, BlocksPruning, DatabaseSettings, DatabaseSettingsSrc};
+	use crate::tests::{Block, insert_header};
 	use super::*;
 
 	fn create_db(db_path: &Path, version: Option<u32>) {
@@ -97,13 +121,31 @@ mod tests {
 		}
 	}
 
-	fn open_database(db_path: &Path) -> sp_blockchain::Result<()> {
-		crate::utils::open_database::<Block>(&DatabaseSettings {
+	fn database_settings(db_path: &Path, blocks_pruning: BlocksPruning) -> DatabaseSettings {
+		DatabaseSettings {
 			state_cache_size: 0,
 			state_cache_child_ratio: None,
 			pruning: PruningMode::ArchiveAll,
+			blocks_pruning,
+			offchain_index_pruning: crate::OffchainIndexPruning::KeepAll,
 			source: DatabaseSettingsSrc::RocksDb { path: db_path.to_owned(), cache_size: 128 },
-		}, DatabaseType::Full).map(|_| ())
+		}
+	}
+
+	fn open_database(db_path: &Path) -> sp_blockchain::Result<()> {
+		crate::utils::open_database::<Block>(
+			&database_settings(db_path, BlocksPruning::KeepAll),
+			DatabaseType::Full,
+		).map(|_| ())
+	}
+
+	fn finalize_blocks(db_path: &Path, blocks_pruning: BlocksPruning) {
+		let backend = Backend::<Block>::new(database_settings(db_path, blocks_pruning), 0).unwrap();
+		let mut parent = insert_header(&backend, 0, Default::default(), None, Default::default());
+		for number in 1..4 {
+			parent = insert_header(&backend, number, parent, None, Default::default());
+			backend.finalize_block(BlockId::Number(number), None).unwrap();
+		}
 	}
 
 	#[test]
@@ -118,6 +160,43 @@ mod tests {
 		let db_dir = tempfile::TempDir::new().unwrap();
 		open_database(db_dir.path()).unwrap();
 		open_database(db_dir.path()).unwrap();
+		assert_eq!(current_version(db_dir.path()).unwrap(), UNPRUNED_VERSION);
+	}
+
+	#[test]
+	fn opening_version_1_keeps_it() {
+		let db_dir = tempfile::TempDir::new().unwrap();
+		create_db(db_dir.path(), Some(1));
+		open_database(db_dir.path()).unwrap();
+		assert_eq!(current_version(db_dir.path()).unwrap(), 1);
+	}
+
+	#[test]
+	fn opening_version_2_works() {
+		let db_dir = tempfile::TempDir::new().unwrap();
+		create_db(db_dir.path(), Some(2));
+		open_database(db_dir.path()).unwrap();
+		assert_eq!(current_version(db_dir.path()).unwrap(), 2);
+	}
+
+	#[test]
+	fn version_is_kept_without_blocks_pruning() {
+		let db_dir = tempfile::TempDir::new().unwrap();
+		finalize_blocks(db_dir.path(), BlocksPruning::KeepAll);
+		assert_eq!(current_version(db_dir.path()).unwrap(), UNPRUNED_VERSION);
+	}
+
+	#[test]
+	fn version_is_kept_until_blocks_are_pruned() {
+		let db_dir = tempfile::TempDir::new().unwrap();
+		finalize_blocks(db_dir.path(), BlocksPruning::KeepFinalized { blocks: 10, justifications: false });
+		assert_eq!(current_version(db_dir.path()).unwrap(), UNPRUNED_VERSION);
+	}
+
+	#[test]
+	fn pruning_blocks_upgrades_to_2() {
+		let db_dir = tempfile::TempDir::new().unwrap();
+		finalize_blocks(db_dir.path(), BlocksPruning::KeepFinalized { blocks: 1, justifications: false });
 		assert_eq!(current_version(db_dir.path()).unwrap(), CURRENT_VERSION);
 	}
 }
@@ -59,6 +59,12 @@ pub mod meta_keys {
 	pub const LEAF_PREFIX: &[u8; 4] = b"leaf";
 	/// Children prefix list key.
 	pub const CHILDREN_PREFIX: &[u8; 8] = b"children";
+	/// Number of the last block whose body has been pruned.
+	pub const PRUNED_BLOCKS: &[u8; 6] = b"pruned";
+	/// Offchain index journals prefix, followed by the block number.
+	pub const OFFCHAIN_INDEX_JOURNAL: &[u8; 6] = b"oindex";
+	/// Prefix of the blocks that last wrote offchain index keys, followed by the key.
+	pub const OFFCHAIN_INDEX_WRITER: &[u8; 7] = b"owriter";
 }
 
 /// Database metadata.
@@ -0,0 +1,94 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Instrumentation of the calls to host functions.
+//!
+//! Once enabled, the execution backends measure every call they make to a host function. The
+//! measurements are aggregated per function, for the runtime call being executed by [`record`]
+//! on the current thread. Runtime calls made from within a host function are recorded separately.
+
+use std::{
+	cell::RefCell,
+	collections::BTreeMap,
+	sync::atomic::{AtomicBool, Ordering},
+	time::{Duration, Instant},
+};
+
+/// Whether calls to host functions are measured.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+	/// Statistics of the runtime call being recorded on this thread, if any.
+	static RECORDING: RefCell<Option<HostCallStats>> = RefCell::new(None);
+}
+
+/// Number of calls and cumulative time spent in a host function.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HostFunctionStats {
+	/// Number of calls.
+	pub calls: u64,
+	/// Time spent in the function, including the runtime calls it made.
+	pub time: Duration,
+}
+
+/// Statistics of the host functions called during a runtime call, by function name.
+pub type HostCallStats = BTreeMap<&'static str, HostFunctionStats>;
+
+/// Enable or disable the instrumentation for the whole process.
+pub fn set_enabled(enabled: bool) {
+	ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether calls to host functions are measured.
+pub fn is_enabled() -> bool {
+	ENABLED.load(Ordering::Relaxed)
+}
+
+/// Call the host function `name` with `f`, measuring the call if it is part of a recording.
+pub fn measure<R>(name: &'static str, f: impl FnOnce() -> R) -> R {
+	if !is_enabled() {
+		return f();
+	}
+
+	let start = Instant::now();
+	let result = f();
+	let elapsed = start.elapsed();
+
+	RECORDING.with(|recording| {
+		if let Some(stats) = recording.borrow_mut().as_mut() {
+			let stats = stats.entry(name).or_default();
+			stats.calls += 1;
+			stats.time += elapsed;
+		}
+	});
+	result
+}
+
+/// Execute the runtime call `f`, recording the host functions it calls.
+///
+/// The statistics are `None` if the instrumentation is disabled. `f` must not panic.
+pub fn record<R>(f: impl FnOnce() -> R) -> (R, Option<HostCallStats>) {
+	if !is_enabled() {
+		return (f(), None);
+	}
+
+	let outer = RECORDING.with(|recording| recording.replace(Some(Default::default())));
+	let result = f();
+	let stats = RECORDING.with(|recording| recording.replace(outer));
+	(result, stats)
+}
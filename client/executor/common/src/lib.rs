@@ -19,6 +19,8 @@
 #![warn(missing_docs)]
 
 pub mod error;
+pub mod host_stats;
+pub mod limits;
 pub mod sandbox;
 pub mod util;
 pub mod wasm_runtime;
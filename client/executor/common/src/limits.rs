@@ -0,0 +1,133 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Limits on the resources used by runtime code, enforced the same way by every backend.
+//!
+//! Backends bound the stack in different ways: wasmi counts the values and frames it pushes,
+//! while compiled code runs on the native stack. A deep enough call could then succeed on one
+//! node and fail on another. Instead, chains can have the code instrumented before instantiation
+//! to track its own stack height and trap with `unreachable` once the limit is exceeded, well
+//! before any backend runs into its own limits.
+
+use crate::error::WasmError;
+use parity_wasm::elements::{External, MemoryType, Module};
+use serde::{Deserialize, Serialize};
+
+/// Recommended maximum stack height for new chains.
+///
+/// This is low enough for the wasmi value stack and the default native stack of compiled code
+/// to never be exhausted first.
+pub const DEFAULT_MAX_STACK_HEIGHT: u32 = 32 * 1024;
+
+/// Limits enforced on runtime code.
+///
+/// These limits are part of the runtime semantics: all nodes must use the same ones to agree on
+/// the outcome of executions. They are therefore set per chain, in the chain specification, and
+/// must be set from genesis: blocks that have been executed without limits may fail to import
+/// once they are enforced. By default, no limit is enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields, default)]
+pub struct ExecutionLimits {
+	/// Maximum stack height, as computed by `pwasm_utils::stack_height`: the sum of the locals,
+	/// arguments and maximum operand stack height of the functions in the call stack.
+	///
+	/// `None` doesn't instrument the code, leaving the stack bounded by the backend only.
+	pub max_stack_height: Option<u32>,
+	/// Maximum number of 64KB pages the linear memory can grow to, heap pages included.
+	///
+	/// This can only lower the maximum declared by the module. Memories without a maximum can't
+	/// grow past their initial size plus the heap pages anyway.
+	pub max_memory_pages: Option<u32>,
+}
+
+/// Instrument `module` to enforce `limits`.
+///
+/// The stack height is tracked in a new mutable global, which must be restored along with the
+/// other mutable globals before each call.
+pub fn instrument(module: Module, limits: &ExecutionLimits) -> Result<Module, WasmError> {
+	let mut module = match limits.max_stack_height {
+		Some(max_stack_height) => pwasm_utils::stack_height::inject_limiter(module, max_stack_height)
+			.map_err(|e| WasmError::Other(format!("cannot inject stack limiter: {:?}", e)))?,
+		None => module,
+	};
+
+	if let Some(max_pages) = limits.max_memory_pages {
+		if let Some(section) = module.import_section_mut() {
+			for entry in section.entries_mut() {
+				if let External::Memory(memory) = entry.external_mut() {
+					limit_memory(memory, max_pages)?;
+				}
+			}
+		}
+		if let Some(section) = module.memory_section_mut() {
+			for memory in section.entries_mut() {
+				limit_memory(memory, max_pages)?;
+			}
+		}
+	}
+
+	Ok(module)
+}
+
+/// Lower the maximum size of `memory` to `max_pages`.
+///
+/// Memories without a declared maximum are left untouched: backends already prevent them from
+/// growing past their initial size plus the heap pages, and setting a maximum would raise that.
+fn limit_memory(memory: &mut MemoryType, max_pages: u32) -> Result<(), WasmError> {
+	let initial = memory.limits().initial();
+	if initial > max_pages {
+		return Err(WasmError::Other(format!(
+			"module requires {} memory pages, more than the maximum of {}",
+			initial,
+			max_pages,
+		)));
+	}
+	if let Some(maximum) = memory.limits().maximum() {
+		*memory = MemoryType::new(initial, Some(maximum.min(max_pages)));
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn limited(initial: u32, maximum: Option<u32>, max_pages: u32) -> Result<Option<u32>, WasmError> {
+		let mut memory = MemoryType::new(initial, maximum);
+		limit_memory(&mut memory, max_pages)?;
+		Ok(memory.limits().maximum())
+	}
+
+	#[test]
+	fn memory_maximum_is_only_lowered() {
+		assert_eq!(limited(16, Some(4096), 2048).unwrap(), Some(2048));
+		assert_eq!(limited(16, Some(1024), 2048).unwrap(), Some(1024));
+		assert_eq!(limited(16, None, 2048).unwrap(), None);
+		assert!(limited(16, None, 8).is_err());
+	}
+
+	#[test]
+	fn limits_are_read_from_chain_spec_json() {
+		let limits: ExecutionLimits = serde_json::from_str(r#"{"maxStackHeight":32768}"#).unwrap();
+		assert_eq!(
+			limits,
+			ExecutionLimits { max_stack_height: Some(DEFAULT_MAX_STACK_HEIGHT), max_memory_pages: None },
+		);
+		assert_eq!(serde_json::from_str::<ExecutionLimits>("{}").unwrap(), Default::default());
+	}
+}
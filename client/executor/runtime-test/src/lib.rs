@@ -309,8 +309,29 @@ sp_core::wasm_export_functions! {
 		assert_ne!(test_message, message_slice);
 		message_slice.copy_from_slice(test_message);
 	}
+
+	fn test_recursion(depth: u32) -> u32 {
+		recurse(depth)
+	}
+
+	// Grows the linear memory by `pages` and returns whether it succeeded.
+	fn test_grow_memory(pages: u32) -> bool {
+		core::arch::wasm32::memory_grow(0, pages as usize) != usize::max_value()
+	}
  }
 
+#[cfg(not(feature = "std"))]
+#[inline(never)]
+fn recurse(depth: u32) -> u32 {
+	if depth == 0 {
+		return 0;
+	}
+	// The volatile write after the call prevents turning the recursion into a loop.
+	let mut result = recurse(depth - 1);
+	unsafe { core::ptr::write_volatile(&mut result, result.wrapping_add(depth)) };
+	result
+}
+
 #[cfg(not(feature = "std"))]
 fn execute_sandboxed(
 	code: &[u8],
@@ -0,0 +1,102 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2017-2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Reporting of the host functions called by runtime code.
+//!
+//! When the instrumentation is enabled, the calls to host functions made by each runtime call
+//! are exported as Prometheus counters, and as a `host_functions` span with one event per
+//! function. The span can be collected with `sc-tracing` by enabling the `host_functions`
+//! target, to get the calls made while importing each block.
+
+use prometheus_endpoint::{register, CounterVec, Opts, PrometheusError, Registry, F64, U64};
+use sc_executor_common::host_stats::{self, HostCallStats};
+
+/// Target of the tracing spans and events.
+const TARGET: &str = "host_functions";
+
+/// Prometheus metrics of the calls to host functions.
+#[derive(Clone)]
+pub(crate) struct HostFunctionMetrics {
+	calls: CounterVec<U64>,
+	time: CounterVec<F64>,
+}
+
+impl HostFunctionMetrics {
+	pub(crate) fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			calls: register(
+				CounterVec::new(
+					Opts::new(
+						"executor_host_function_calls_total",
+						"Number of calls to host functions made by runtime code.",
+					),
+					&["function"],
+				)?,
+				registry,
+			)?,
+			time: register(
+				CounterVec::new(
+					Opts::new(
+						"executor_host_function_time_seconds_total",
+						"Time spent in host functions called by runtime code.",
+					),
+					&["function"],
+				)?,
+				registry,
+			)?,
+		})
+	}
+
+	fn report(&self, stats: &HostCallStats) {
+		for (name, stats) in stats {
+			self.calls.with_label_values(&[name]).inc_by(stats.calls);
+			self.time.with_label_values(&[name]).inc_by(stats.time.as_secs_f64());
+		}
+	}
+}
+
+/// Execute the runtime call of `method` with `f`, and report the host functions it calls.
+pub(crate) fn record_host_calls<R>(
+	metrics: Option<&HostFunctionMetrics>,
+	method: &str,
+	f: impl FnOnce() -> R,
+) -> R {
+	let (result, stats) = host_stats::record(f);
+	let stats = match stats {
+		Some(stats) => stats,
+		None => return result,
+	};
+
+	if let Some(metrics) = metrics {
+		metrics.report(&stats);
+	}
+
+	let span = tracing::span!(target: TARGET, tracing::Level::INFO, "host_functions", method);
+	let _guard = span.enter();
+	for (name, stats) in &stats {
+		tracing::event!(
+			target: TARGET,
+			tracing::Level::INFO,
+			function = name,
+			calls = stats.calls,
+			time_ns = stats.time.as_nanos() as u64,
+		);
+	}
+
+	result
+}
@@ -47,6 +47,7 @@ fn call_in_wasm<E: Externalities>(
 		Some(1024),
 		HostFunctions::host_functions(),
 		8,
+		None,
 	);
 	executor.call_in_wasm(
 		&wasm_binary_unwrap()[..],
@@ -531,6 +532,7 @@ fn should_trap_when_heap_exhausted(wasm_method: WasmExecutionMethod) {
 		Some(17),  // `17` is the initial number of pages compiled into the binary.
 		HostFunctions::host_functions(),
 		8,
+		None,
 	);
 	executor.call_in_wasm(
 		&wasm_binary_unwrap()[..],
@@ -551,6 +553,8 @@ fn returns_mutable_static(wasm_method: WasmExecutionMethod) {
 		&wasm_binary_unwrap()[..],
 		HostFunctions::host_functions(),
 		true,
+		Default::default(),
+		None,
 	).expect("Creates runtime");
 
 	let instance = runtime.new_instance().unwrap();
@@ -585,6 +589,8 @@ fn restoration_of_globals(wasm_method: WasmExecutionMethod) {
 		&wasm_binary_unwrap()[..],
 		HostFunctions::host_functions(),
 		true,
+		Default::default(),
+		None,
 	).expect("Creates runtime");
 	let instance = runtime.new_instance().unwrap();
 
@@ -597,6 +603,178 @@ fn restoration_of_globals(wasm_method: WasmExecutionMethod) {
 	assert!(res.is_ok());
 }
 
+fn create_limited_runtime(
+	wasm_method: WasmExecutionMethod,
+	limits: crate::ExecutionLimits,
+) -> Result<Box<dyn sc_executor_common::wasm_runtime::WasmModule>, crate::error::WasmError> {
+	crate::wasm_runtime::create_wasm_runtime_with_code(
+		wasm_method,
+		1024,
+		&wasm_binary_unwrap()[..],
+		HostFunctions::host_functions(),
+		true,
+		limits,
+		None,
+	)
+}
+
+/// Limits of a chain that enforces the recommended stack height.
+fn stack_limits() -> crate::ExecutionLimits {
+	crate::ExecutionLimits {
+		max_stack_height: Some(sc_executor_common::limits::DEFAULT_MAX_STACK_HEIGHT),
+		..Default::default()
+	}
+}
+
+fn is_unreachable_trap(error: &crate::error::Error) -> bool {
+	format!("{:?}", error).to_lowercase().contains("unreachable")
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn deep_recursion_traps_at_stack_limit(wasm_method: WasmExecutionMethod) {
+	let runtime = create_limited_runtime(wasm_method, stack_limits()).expect("Creates runtime");
+	let instance = runtime.new_instance().unwrap();
+
+	let res = instance.call("test_recursion", &10u32.encode()).unwrap();
+	assert_eq!(u32::decode(&mut &res[..]).unwrap(), 55);
+
+	// The stack limiter traps before the backend runs out of stack.
+	let err = instance.call("test_recursion", &1_000_000u32.encode()).unwrap_err();
+	assert!(is_unreachable_trap(&err), "unexpected error: {:?}", err);
+
+	// The stack height is reset after a trap.
+	let res = instance.call("test_recursion", &10u32.encode()).unwrap();
+	assert_eq!(u32::decode(&mut &res[..]).unwrap(), 55);
+}
+
+#[cfg(feature = "wasmtime")]
+#[test]
+fn stack_limit_is_identical_across_backends() {
+	let max_depth = |wasm_method| {
+		let runtime = create_limited_runtime(wasm_method, stack_limits()).expect("Creates runtime");
+		let instance = runtime.new_instance().unwrap();
+		let succeeds = |depth: u32| instance.call("test_recursion", &depth.encode()).is_ok();
+
+		// Binary search for the deepest recursion that doesn't trap.
+		let (mut low, mut high) = (0u32, 1 << 20);
+		assert!(succeeds(low) && !succeeds(high));
+		while high - low > 1 {
+			let middle = low + (high - low) / 2;
+			if succeeds(middle) {
+				low = middle;
+			} else {
+				high = middle;
+			}
+		}
+		low
+	};
+
+	assert_eq!(
+		max_depth(WasmExecutionMethod::Interpreted),
+		max_depth(WasmExecutionMethod::Compiled),
+	);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn memory_growth_is_capped(wasm_method: WasmExecutionMethod) {
+	let limits = crate::ExecutionLimits { max_memory_pages: Some(2048), ..Default::default() };
+	let runtime = create_limited_runtime(wasm_method, limits).expect("Creates runtime");
+
+	let instance = runtime.new_instance().unwrap();
+	let res = instance.call("test_grow_memory", &2048u32.encode()).unwrap();
+	assert_eq!(res, false.encode());
+
+	// The runtime doesn't declare a maximum, which a limit can't raise.
+	let instance = runtime.new_instance().unwrap();
+	let res = instance.call("test_grow_memory", &1u32.encode()).unwrap();
+	assert_eq!(res, false.encode());
+
+	// The module requires more memory than allowed.
+	let limits = crate::ExecutionLimits { max_memory_pages: Some(1), ..Default::default() };
+	assert!(create_limited_runtime(wasm_method, limits).is_err());
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn memory_growth(wasm_method: WasmExecutionMethod) {
+	let runtime = create_limited_runtime(wasm_method, Default::default()).expect("Creates runtime");
+	let instance = runtime.new_instance().unwrap();
+
+	// The runtime doesn't declare a maximum, so its memory can't grow past the heap pages.
+	let res = instance.call("test_grow_memory", &1u32.encode()).unwrap();
+	assert_eq!(res, false.encode());
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn host_function_calls_are_measured(wasm_method: WasmExecutionMethod) {
+	let registry = prometheus_endpoint::Registry::new();
+	let executor = crate::WasmExecutor::new(
+		wasm_method,
+		Some(1024),
+		HostFunctions::host_functions(),
+		8,
+		None,
+	).with_host_function_instrumentation(Some(&registry)).unwrap();
+
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	for _ in 0..2 {
+		executor.call_in_wasm(
+			&wasm_binary_unwrap()[..],
+			None,
+			"test_blake2_256",
+			&b"Hello world!".to_vec().encode(),
+			&mut ext,
+			sp_core::traits::MissingHostFunctions::Allow,
+		).unwrap();
+	}
+
+	let calls = registry.gather().into_iter()
+		.find(|family| family.get_name() == "executor_host_function_calls_total")
+		.expect("Calls are exported")
+		.get_metric()
+		.iter()
+		.find(|metric| metric.get_label()[0].get_value() == "ext_hashing_blake2_256_version_1")
+		.map(|metric| metric.get_counter().get_value());
+	assert_eq!(calls, Some(2.0));
+}
+
+#[cfg(feature = "wasmtime")]
+#[test]
+fn compiled_runtime_is_cached_on_disk() {
+	let cache_dir = tempfile::tempdir().unwrap();
+	let create_runtime = || crate::wasm_runtime::create_wasm_runtime_with_code(
+		WasmExecutionMethod::Compiled,
+		1024,
+		&wasm_binary_unwrap()[..],
+		HostFunctions::host_functions(),
+		true,
+		Default::default(),
+		Some(cache_dir.path()),
+	).expect("Creates runtime");
+	let cached_files = || std::fs::read_dir(cache_dir.path()).unwrap()
+		.map(|entry| entry.unwrap().path())
+		.collect::<Vec<_>>();
+
+	create_runtime();
+	let files = cached_files();
+	assert_eq!(files.len(), 1);
+
+	// Loaded from the cache.
+	let instance = create_runtime().new_instance().unwrap();
+	assert_eq!(instance.call("test_empty_return", &[]).unwrap(), Vec::<u8>::new());
+
+	// A corrupted entry is replaced.
+	std::fs::write(&files[0], b"corrupted").unwrap();
+	let instance = create_runtime().new_instance().unwrap();
+	assert_eq!(instance.call("test_empty_return", &[]).unwrap(), Vec::<u8>::new());
+	assert_eq!(cached_files(), files);
+	assert_ne!(std::fs::read(&files[0]).unwrap(), b"corrupted");
+}
+
 #[test_case(WasmExecutionMethod::Interpreted)]
 fn heap_is_reset_between_calls(wasm_method: WasmExecutionMethod) {
 	let runtime = crate::wasm_runtime::create_wasm_runtime_with_code(
@@ -605,6 +783,8 @@ fn heap_is_reset_between_calls(wasm_method: WasmExecutionMethod) {
 		&wasm_binary_unwrap()[..],
 		HostFunctions::host_functions(),
 		true,
+		Default::default(),
+		None,
 	).expect("Creates runtime");
 	let instance = runtime.new_instance().unwrap();
 
@@ -629,6 +809,7 @@ fn parallel_execution(wasm_method: WasmExecutionMethod) {
 		Some(1024),
 		HostFunctions::host_functions(),
 		8,
+		None,
 	));
 	let code_hash = blake2_256(wasm_binary_unwrap()).to_vec();
 	let threads: Vec<_> = (0..8).map(|_|
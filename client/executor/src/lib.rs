@@ -33,6 +33,7 @@
 
 #[macro_use]
 mod native_executor;
+mod host_metrics;
 mod wasm_runtime;
 #[cfg(test)]
 mod integration_tests;
@@ -47,7 +48,7 @@ pub use sp_core::traits::{Externalities, CallInWasm};
 pub use sp_wasm_interface;
 pub use wasm_runtime::WasmExecutionMethod;
 
-pub use sc_executor_common::{error, sandbox};
+pub use sc_executor_common::{error, sandbox, limits::ExecutionLimits};
 
 /// Provides runtime information.
 pub trait RuntimeInfo {
@@ -80,6 +81,7 @@ mod tests {
 			Some(8),
 			sp_io::SubstrateHostFunctions::host_functions(),
 			8,
+			None,
 		);
 		let res = executor.call_in_wasm(
 			&wasm_binary_unwrap()[..],
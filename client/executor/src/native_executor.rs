@@ -18,6 +18,7 @@
 
 use crate::{
 	RuntimeInfo, error::{Error, Result},
+	host_metrics::{self, HostFunctionMetrics},
 	wasm_runtime::{RuntimeCache, WasmExecutionMethod},
 };
 use sp_version::{NativeVersion, RuntimeVersion};
@@ -26,9 +27,10 @@ use sp_core::{
 	NativeOrEncoded, traits::{CodeExecutor, Externalities, RuntimeCode, MissingHostFunctions},
 };
 use log::trace;
-use std::{result, panic::{UnwindSafe, AssertUnwindSafe}, sync::Arc};
+use std::{result, panic::{UnwindSafe, AssertUnwindSafe}, path::PathBuf, sync::Arc};
 use sp_wasm_interface::{HostFunctions, Function};
-use sc_executor_common::wasm_runtime::WasmInstance;
+use sc_executor_common::{host_stats, limits::ExecutionLimits, wasm_runtime::WasmInstance};
+use prometheus_endpoint::{PrometheusError, Registry};
 
 /// Default num of pages for the heap
 const DEFAULT_HEAP_PAGES: u64 = 1024;
@@ -87,8 +89,14 @@ pub struct WasmExecutor {
 	host_functions: Arc<Vec<&'static dyn Function>>,
 	/// WASM runtime cache.
 	cache: Arc<RuntimeCache>,
+	/// Directory of the on-disk cache of compiled runtimes.
+	cache_path: Option<PathBuf>,
 	/// The size of the instances cache.
 	max_runtime_instances: usize,
+	/// Limits enforced on the runtime code.
+	limits: ExecutionLimits,
+	/// Metrics of the calls to host functions, if instrumented.
+	host_metrics: Option<HostFunctionMetrics>,
 }
 
 impl WasmExecutor {
@@ -100,21 +108,55 @@ impl WasmExecutor {
 	///
 	/// `default_heap_pages` - Number of 64KB pages to allocate for Wasm execution.
 	/// 	Defaults to `DEFAULT_HEAP_PAGES` if `None` is provided.
+	///
+	/// `cache_path` - Directory where compiled runtimes are stored, to avoid compiling them
+	/// 	again after a restart.
 	pub fn new(
 		method: WasmExecutionMethod,
 		default_heap_pages: Option<u64>,
 		host_functions: Vec<&'static dyn Function>,
 		max_runtime_instances: usize,
+		cache_path: Option<PathBuf>,
 	) -> Self {
 		WasmExecutor {
 			method,
 			default_heap_pages: default_heap_pages.unwrap_or(DEFAULT_HEAP_PAGES),
 			host_functions: Arc::new(host_functions),
-			cache: Arc::new(RuntimeCache::new(max_runtime_instances)),
+			cache_path: cache_path.clone(),
+			cache: Arc::new(RuntimeCache::new(max_runtime_instances, cache_path)),
 			max_runtime_instances,
+			limits: ExecutionLimits::default(),
+			host_metrics: None,
 		}
 	}
 
+	/// Enforce `limits` on the runtime code instead of the default ones.
+	///
+	/// All nodes must use the same limits, otherwise they may disagree on the outcome of a call.
+	pub fn with_execution_limits(mut self, limits: ExecutionLimits) -> Self {
+		self.limits = limits;
+		self
+	}
+
+	/// Measure the number of calls and the time spent in each host function called by the
+	/// runtime code.
+	///
+	/// The measurements are exported to `registry`, if any, and as `host_functions` tracing
+	/// spans. Enabling the instrumentation slows down every executor of the process.
+	pub fn with_host_function_instrumentation(
+		mut self,
+		registry: Option<&Registry>,
+	) -> std::result::Result<Self, PrometheusError> {
+		self.host_metrics = registry.map(HostFunctionMetrics::register).transpose()?;
+		host_stats::set_enabled(true);
+		Ok(self)
+	}
+
+	/// Execute the runtime call of `method` with `f`, reporting the host functions it calls.
+	fn record_host_calls<R>(&self, method: &str, f: impl FnOnce() -> R) -> R {
+		host_metrics::record_host_calls(self.host_metrics.as_ref(), method, f)
+	}
+
 	/// Execute the given closure `f` with the latest runtime (based on `runtime_code`).
 	///
 	/// The closure `f` is expected to return `Err(_)` when there happened a `panic!` in native code
@@ -148,6 +190,7 @@ impl WasmExecutor {
 			self.default_heap_pages,
 			&*self.host_functions,
 			allow_missing_host_functions,
+			&self.limits,
 			|instance, version, ext| {
 				let instance = AssertUnwindSafe(instance);
 				let ext = AssertUnwindSafe(ext);
@@ -180,10 +223,10 @@ impl sp_core::traits::CallInWasm for WasmExecutor {
 			};
 
 			self.with_instance(&code, ext, allow_missing_host_functions, |instance, _, mut ext| {
-				with_externalities_safe(
+				self.record_host_calls(method, || with_externalities_safe(
 					&mut **ext,
 					move || instance.call(method, call_data),
-				)
+				))
 			}).map_err(|e| e.to_string())
 		} else {
 			let module = crate::wasm_runtime::create_wasm_runtime_with_code(
@@ -192,6 +235,8 @@ impl sp_core::traits::CallInWasm for WasmExecutor {
 				&wasm_code,
 				self.host_functions.to_vec(),
 				allow_missing_host_functions,
+				self.limits,
+				self.cache_path.as_deref(),
 			)
 				.map_err(|e| format!("Failed to create module: {:?}", e))?;
 
@@ -201,10 +246,10 @@ impl sp_core::traits::CallInWasm for WasmExecutor {
 			let instance = AssertUnwindSafe(instance);
 			let mut ext = AssertUnwindSafe(ext);
 
-			with_externalities_safe(
+			self.record_host_calls(method, || with_externalities_safe(
 				&mut **ext,
 				move || instance.call(method, call_data),
-			)
+			))
 			.and_then(|r| r)
 			.map_err(|e| e.to_string())
 		}
@@ -235,6 +280,18 @@ impl<D: NativeExecutionDispatch> NativeExecutor<D> {
 		fallback_method: WasmExecutionMethod,
 		default_heap_pages: Option<u64>,
 		max_runtime_instances: usize,
+	) -> Self {
+		Self::new_with_wasm_cache_path(fallback_method, default_heap_pages, max_runtime_instances, None)
+	}
+
+	/// Create new instance that stores compiled runtimes in `cache_path`.
+	///
+	/// See [`NativeExecutor::new`] for the other parameters.
+	pub fn new_with_wasm_cache_path(
+		fallback_method: WasmExecutionMethod,
+		default_heap_pages: Option<u64>,
+		max_runtime_instances: usize,
+		cache_path: Option<PathBuf>,
 	) -> Self {
 		let mut host_functions = sp_io::SubstrateHostFunctions::host_functions();
 
@@ -245,6 +302,7 @@ impl<D: NativeExecutionDispatch> NativeExecutor<D> {
 			default_heap_pages,
 			host_functions,
 			max_runtime_instances,
+			cache_path,
 		);
 
 		NativeExecutor {
@@ -253,6 +311,25 @@ impl<D: NativeExecutionDispatch> NativeExecutor<D> {
 			wasm: wasm_executor,
 		}
 	}
+
+	/// Enforce `limits` on the fallback Wasm code instead of the default ones.
+	///
+	/// See [`WasmExecutor::with_execution_limits`].
+	pub fn with_execution_limits(mut self, limits: ExecutionLimits) -> Self {
+		self.wasm = self.wasm.with_execution_limits(limits);
+		self
+	}
+
+	/// Measure the host functions called by the fallback Wasm code.
+	///
+	/// See [`WasmExecutor::with_host_function_instrumentation`]. Native calls aren't measured.
+	pub fn with_host_function_instrumentation(
+		mut self,
+		registry: Option<&Registry>,
+	) -> std::result::Result<Self, PrometheusError> {
+		self.wasm = self.wasm.with_host_function_instrumentation(registry)?;
+		Ok(self)
+	}
 }
 
 impl<D: NativeExecutionDispatch> RuntimeInfo for NativeExecutor<D> {
@@ -312,16 +389,16 @@ impl<D: NativeExecutionDispatch + 'static> CodeExecutor for NativeExecutor<D> {
 							onchain_version,
 						);
 
-						with_externalities_safe(
+						self.wasm.record_host_calls(method, || with_externalities_safe(
 							&mut **ext,
 							move || instance.call(method, data).map(NativeOrEncoded::Encoded)
-						)
+						))
 					}
 					(false, _, _) => {
-						with_externalities_safe(
+						self.wasm.record_host_calls(method, || with_externalities_safe(
 							&mut **ext,
 							move || instance.call(method, data).map(NativeOrEncoded::Encoded)
-						)
+						))
 					},
 					(true, true, Some(call)) => {
 						trace!(
@@ -19,6 +19,7 @@
 //! The primary means of accessing the runtimes is through a cache which saves the reusable
 //! components of the runtime that are expensive to initialize.
 
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use crate::error::{Error, WasmError};
 use parking_lot::Mutex;
@@ -26,7 +27,7 @@ use codec::Decode;
 use sp_core::traits::{Externalities, RuntimeCode, FetchRuntimeCode};
 use sp_version::RuntimeVersion;
 use std::panic::AssertUnwindSafe;
-use sc_executor_common::wasm_runtime::{WasmModule, WasmInstance};
+use sc_executor_common::{limits::ExecutionLimits, wasm_runtime::{WasmModule, WasmInstance}};
 
 use sp_wasm_interface::Function;
 
@@ -149,14 +150,21 @@ pub struct RuntimeCache {
 	runtimes: Mutex<[Option<Arc<VersionedRuntime>>; MAX_RUNTIMES]>,
 	/// The size of the instances cache for each runtime.
 	max_runtime_instances: usize,
+	/// Directory of the on-disk cache of compiled runtimes.
+	cache_path: Option<PathBuf>,
 }
 
 impl RuntimeCache {
 	/// Creates a new instance of a runtimes cache.
-	pub fn new(max_runtime_instances: usize) -> RuntimeCache {
+	///
+	/// `cache_path` is the directory where compiled runtimes are stored, so that they don't
+	/// need to be compiled again after a restart. It is only used by the compiled execution
+	/// method.
+	pub fn new(max_runtime_instances: usize, cache_path: Option<PathBuf>) -> RuntimeCache {
 		RuntimeCache {
 			runtimes: Default::default(),
 			max_runtime_instances,
+			cache_path,
 		}
 	}
 
@@ -177,6 +185,8 @@ impl RuntimeCache {
 	///
 	/// `allow_missing_func_imports` - Ignore missing function imports.
 	///
+	/// `limits` - Limits enforced on the runtime code.
+	///
 	/// `max_runtime_instances` - The size of the instances cache.
 	///
 	/// `f` - Function to execute.
@@ -196,6 +206,7 @@ impl RuntimeCache {
 		default_heap_pages: u64,
 		host_functions: &[&'static dyn Function],
 		allow_missing_func_imports: bool,
+		limits: &ExecutionLimits,
 		f: F,
 	) -> Result<Result<R, Error>, Error>
 		where F: FnOnce(
@@ -230,7 +241,9 @@ impl RuntimeCache {
 					heap_pages,
 					host_functions.into(),
 					allow_missing_func_imports,
+					*limits,
 					self.max_runtime_instances,
+					self.cache_path.as_deref(),
 				);
 				if let Err(ref err) = result {
 					log::warn!(target: "wasm-runtime", "Cannot create a runtime: {:?}", err);
@@ -260,13 +273,18 @@ impl RuntimeCache {
 	}
 }
 
-/// Create a wasm runtime with the given `code`.
+/// Create a wasm runtime with the given `code`, instrumented to enforce `limits`.
+///
+/// `cache_path` is the directory of the on-disk cache of compiled runtimes, if any.
+#[cfg_attr(not(feature = "wasmtime"), allow(unused_variables))]
 pub fn create_wasm_runtime_with_code(
 	wasm_method: WasmExecutionMethod,
 	heap_pages: u64,
 	code: &[u8],
 	host_functions: Vec<&'static dyn Function>,
 	allow_missing_func_imports: bool,
+	limits: ExecutionLimits,
+	cache_path: Option<&Path>,
 ) -> Result<Box<dyn WasmModule>, WasmError> {
 	match wasm_method {
 		WasmExecutionMethod::Interpreted =>
@@ -274,7 +292,8 @@ pub fn create_wasm_runtime_with_code(
 				code,
 				heap_pages,
 				host_functions,
-				allow_missing_func_imports
+				allow_missing_func_imports,
+				limits,
 			).map(|runtime| -> Box<dyn WasmModule> { Box::new(runtime) }),
 		#[cfg(feature = "wasmtime")]
 		WasmExecutionMethod::Compiled =>
@@ -282,7 +301,9 @@ pub fn create_wasm_runtime_with_code(
 				code,
 				heap_pages,
 				host_functions,
-				allow_missing_func_imports
+				allow_missing_func_imports,
+				limits,
+				cache_path,
 			).map(|runtime| -> Box<dyn WasmModule> { Box::new(runtime) }),
 	}
 }
@@ -314,7 +335,9 @@ fn create_versioned_wasm_runtime(
 	heap_pages: u64,
 	host_functions: Vec<&'static dyn Function>,
 	allow_missing_func_imports: bool,
+	limits: ExecutionLimits,
 	max_instances: usize,
+	cache_path: Option<&Path>,
 ) -> Result<VersionedRuntime, WasmError> {
 	#[cfg(not(target_os = "unknown"))]
 	let time = std::time::Instant::now();
@@ -324,6 +347,8 @@ fn create_versioned_wasm_runtime(
 		&code,
 		host_functions,
 		allow_missing_func_imports,
+		limits,
+		cache_path,
 	)?;
 
 	// Call to determine runtime version.
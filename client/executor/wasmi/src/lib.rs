@@ -32,6 +32,8 @@ use sp_runtime_interface::unpack_ptr_and_len;
 use sc_executor_common::wasm_runtime::{WasmModule, WasmInstance};
 use sc_executor_common::{
 	error::{Error, WasmError},
+	host_stats,
+	limits::{self, ExecutionLimits},
 	sandbox,
 };
 use sc_executor_common::util::{DataSegmentsSnapshot, WasmModuleInfo};
@@ -363,10 +365,11 @@ impl<'a> wasmi::ModuleImportResolver for Resolver<'a> {
 								.expect("Maximum is set, checked above; qed"),
 						)))
 					} else {
-						let memory = MemoryInstance::alloc(
-							Pages(memory_type.initial() as usize + self.heap_pages),
-							Some(Pages(memory_type.initial() as usize + self.heap_pages)),
-						)?;
+						// As with compiled code, the memory can grow up to the declared maximum, and
+						// not past the heap pages if there is none.
+						let initial = memory_type.initial() as usize + self.heap_pages;
+						let maximum = memory_type.maximum().map_or(initial, |m| m as usize);
+						let memory = MemoryInstance::alloc(Pages(initial), Some(Pages(maximum)))?;
 						*memory_ref = Some(memory.clone());
 						Ok(memory)
 					}
@@ -386,8 +389,8 @@ impl<'a> wasmi::Externals for FunctionExecutor<'a> {
 	{
 		let mut args = args.as_ref().iter().copied().map(Into::into);
 
-		if let Some(function) = self.host_functions.get(index) {
-			function.execute(self, &mut args)
+		if let Some(&function) = self.host_functions.get(index) {
+			host_stats::measure(function.name(), || function.execute(self, &mut args))
 				.map_err(|msg| Error::FunctionExecution(function.name().to_string(), msg))
 				.map_err(wasmi::Trap::from)
 				.map(|v| v.map(Into::into))
@@ -611,15 +614,19 @@ impl WasmModule for WasmiRuntime {
 	}
 }
 
-/// Create a new `WasmiRuntime` given the code. This function loads the module and
-/// stores it in the instance.
+/// Create a new `WasmiRuntime` given the code. This function loads the module, instruments it
+/// to enforce `limits` and stores it in the instance.
 pub fn create_runtime(
 	code: &[u8],
 	heap_pages: u64,
 	host_functions: Vec<&'static dyn Function>,
 	allow_missing_func_imports: bool,
+	limits: ExecutionLimits,
 ) -> Result<WasmiRuntime, WasmError> {
-	let module = Module::from_buffer(&code).map_err(|_| WasmError::InvalidModule)?;
+	let raw_module = parity_wasm::elements::deserialize_buffer(code)
+		.map_err(|_| WasmError::CantDeserializeWasm)?;
+	let module = Module::from_parity_wasm_module(limits::instrument(raw_module, &limits)?)
+		.map_err(|_| WasmError::InvalidModule)?;
 
 	// Extract the data segments from the wasm code.
 	//
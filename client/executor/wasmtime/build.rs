@@ -0,0 +1,86 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Exposes the exact version of the wasmtime dependency, as resolved in `Cargo.lock`, through
+//! the `SC_EXECUTOR_WASMTIME_VERSION` environment variable.
+//!
+//! The version can be given explicitly by setting `SC_EXECUTOR_WASMTIME_VERSION` when building,
+//! which is required if the lock file isn't in an ancestor of the build or source directories.
+
+use std::{env, fs, path::PathBuf};
+
+/// Major and minor version of wasmtime required in `Cargo.toml`.
+const WASMTIME_REQUIREMENT: &str = "0.19.";
+
+fn main() {
+	println!("cargo:rerun-if-env-changed=SC_EXECUTOR_WASMTIME_VERSION");
+	if let Some(version) = env::var("SC_EXECUTOR_WASMTIME_VERSION").ok().filter(|v| !v.is_empty()) {
+		if !version.starts_with(WASMTIME_REQUIREMENT) {
+			panic!(
+				"SC_EXECUTOR_WASMTIME_VERSION is set to {}, which doesn't match the wasmtime {}x \
+				dependency",
+				version,
+				WASMTIME_REQUIREMENT,
+			);
+		}
+		println!("cargo:rustc-env=SC_EXECUTOR_WASMTIME_VERSION={}", version);
+		return;
+	}
+
+	let lock_file = ["OUT_DIR", "CARGO_MANIFEST_DIR"].iter()
+		.filter_map(|var| env::var_os(var).map(PathBuf::from))
+		.flat_map(|dir| dir.ancestors().map(|dir| dir.join("Cargo.lock")).collect::<Vec<_>>())
+		.find(|path| path.is_file());
+
+	let version = match &lock_file {
+		Some(path) => {
+			println!("cargo:rerun-if-changed={}", path.display());
+			fs::read_to_string(path).ok().and_then(|lock| wasmtime_version(&lock))
+		},
+		None => {
+			println!("cargo:rerun-if-changed=build.rs");
+			None
+		},
+	};
+
+	// Compiled runtimes are only valid for the exact wasmtime version that produced them, so
+	// building without knowing it would risk loading incompatible modules from the cache.
+	let version = version.unwrap_or_else(|| panic!(
+		"Could not find the version of wasmtime in {}, set SC_EXECUTOR_WASMTIME_VERSION to the \
+		exact version of wasmtime used",
+		lock_file.as_ref().map_or(String::from("Cargo.lock"), |path| path.display().to_string()),
+	));
+	println!("cargo:rustc-env=SC_EXECUTOR_WASMTIME_VERSION={}", version);
+}
+
+/// Version of the `wasmtime` package listed in the lock file, if any.
+fn wasmtime_version(lock: &str) -> Option<String> {
+	let mut lines = lock.lines().map(str::trim);
+	while let Some(line) = lines.next() {
+		if line != r#"name = "wasmtime""# {
+			continue;
+		}
+		let version = lines.next()
+			.filter(|line| line.starts_with("version = \"") && line.ends_with('"'))
+			.map(|line| &line["version = \"".len()..line.len() - 1]);
+		if let Some(version) = version.filter(|v| v.starts_with(WASMTIME_REQUIREMENT)) {
+			return Some(version.into());
+		}
+	}
+	None
+}
@@ -0,0 +1,319 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! On-disk cache of compiled runtimes.
+//!
+//! Compiling a runtime takes several seconds, so the compiled module is stored on disk and
+//! loaded back the next time the same runtime is needed. Entries are keyed by the hash of the
+//! runtime code, of the host functions, of the execution limits, of the exact wasmtime version and
+//! settings, and of the host CPU features. Each entry is stored along with the hash of its content, which is checked before
+//! loading it. Invalid entries are removed and the runtime is compiled again.
+//!
+//! The wasmtime version is taken from `Cargo.lock` by the build script. At most
+//! `MAX_ENTRIES` compiled runtimes are kept, the least recently stored ones being removed first.
+
+use std::{fs, io, path::{Path, PathBuf}};
+use codec::Encode;
+use log::{debug, warn};
+use sc_executor_common::limits::ExecutionLimits;
+use sp_core::{hashing::blake2_256, hexdisplay::HexDisplay};
+use sp_wasm_interface::Function;
+use wasmtime::{Engine, Module};
+
+/// Exact version of the wasmtime dependency, as compiled modules can't be loaded by other
+/// wasmtime versions.
+const WASMTIME_VERSION: &str = env!("SC_EXECUTOR_WASMTIME_VERSION");
+
+/// Version of the format of the cache entries, and of the way modules are compiled. Must be
+/// bumped on any change to the instrumentation or to the wasmtime `Config`.
+const CACHE_VERSION: u32 = 2;
+
+/// Magic bytes at the start of cache entries.
+const MAGIC: &[u8; 4] = b"swrc";
+
+/// Length of the header of cache entries: magic, version and content hash.
+const HEADER_LEN: usize = 4 + 4 + 32;
+
+/// Maximum number of compiled runtimes kept in the cache. This leaves room for the runtimes of
+/// a few chains and runtime upgrades, while bounding the space taken by runtimes that aren't
+/// used anymore.
+const MAX_ENTRIES: usize = 8;
+
+/// Extension of the files of cache entries.
+const ENTRY_EXTENSION: &str = "bin";
+
+/// Entry of the compiled runtimes cache, for a given runtime code and set of host functions.
+pub struct CacheEntry {
+	path: PathBuf,
+}
+
+impl CacheEntry {
+	/// Entry for `code` instrumented to enforce `limits` and compiled to run with `host_functions`,
+	/// in the cache at `dir`.
+	pub fn new(
+		dir: &Path,
+		code: &[u8],
+		limits: &ExecutionLimits,
+		host_functions: &[&'static dyn Function],
+	) -> Self {
+		let mut preimage = Vec::with_capacity(code.len() + 64 * host_functions.len() + 32);
+		preimage.extend_from_slice(WASMTIME_VERSION.as_bytes());
+		preimage.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+		preimage.extend_from_slice(std::env::consts::ARCH.as_bytes());
+		for feature in cpu_features() {
+			preimage.extend_from_slice(feature.as_bytes());
+		}
+		preimage.extend_from_slice(&blake2_256(code));
+		preimage.extend_from_slice(&limits.max_stack_height.encode());
+		preimage.extend_from_slice(&limits.max_memory_pages.encode());
+		for function in host_functions {
+			preimage.extend_from_slice(function.name().as_bytes());
+			preimage.extend_from_slice(format!("{:?}", function.signature()).as_bytes());
+		}
+		let key = blake2_256(&preimage);
+
+		CacheEntry {
+			path: dir.join(format!("{}.{}", HexDisplay::from(&key), ENTRY_EXTENSION)),
+		}
+	}
+
+	/// Load the compiled module, if present and valid.
+	pub fn load(&self, engine: &Engine) -> Option<Module> {
+		let data = match fs::read(&self.path) {
+			Ok(data) => data,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+			Err(e) => {
+				warn!(target: "wasm-runtime", "Failed to read {}: {}", self.path.display(), e);
+				return None;
+			},
+		};
+
+		match decode_entry(&data).and_then(|compiled| {
+			Module::deserialize(engine, compiled).map_err(|e| e.to_string())
+		}) {
+			Ok(module) => {
+				debug!(target: "wasm-runtime", "Loaded compiled runtime from {}", self.path.display());
+				Some(module)
+			},
+			Err(e) => {
+				warn!(
+					target: "wasm-runtime",
+					"Removing invalid compiled runtime {}: {}",
+					self.path.display(),
+					e,
+				);
+				let _ = fs::remove_file(&self.path);
+				None
+			},
+		}
+	}
+
+	/// Store the compiled module, and evict the least recently stored entries beyond
+	/// `MAX_ENTRIES`. Failures are logged and otherwise ignored.
+	pub fn store(&self, module: &Module) {
+		let result = module.serialize()
+			.map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+			.and_then(|compiled| {
+				if let Some(dir) = self.path.parent() {
+					fs::create_dir_all(dir)?;
+				}
+				// Write to a temporary file first, so that a crash can't leave a partial entry
+				// behind under the final name.
+				let tmp_path = self.path.with_extension("tmp");
+				fs::write(&tmp_path, encode_entry(&compiled))?;
+				fs::rename(&tmp_path, &self.path)
+			});
+
+		match result {
+			Ok(()) => debug!(target: "wasm-runtime", "Stored compiled runtime to {}", self.path.display()),
+			Err(e) => warn!(
+				target: "wasm-runtime",
+				"Failed to store compiled runtime to {}: {}",
+				self.path.display(),
+				e,
+			),
+		}
+
+		if let Some(dir) = self.path.parent() {
+			evict(dir, MAX_ENTRIES);
+		}
+	}
+}
+
+/// Remove the least recently stored entries of the cache at `dir`, so that at most `max_entries`
+/// remain. Failures are logged and otherwise ignored.
+fn evict(dir: &Path, max_entries: usize) {
+	let entries = match fs::read_dir(dir) {
+		Ok(entries) => entries,
+		Err(e) => {
+			warn!(target: "wasm-runtime", "Failed to list compiled runtimes in {}: {}", dir.display(), e);
+			return;
+		},
+	};
+
+	let mut entries = entries
+		.filter_map(|entry| entry.ok())
+		.map(|entry| entry.path())
+		.filter(|path| path.extension().map_or(false, |ext| ext == ENTRY_EXTENSION))
+		.filter_map(|path| {
+			let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()?;
+			Some((modified, path))
+		})
+		.collect::<Vec<_>>();
+	if entries.len() <= max_entries {
+		return;
+	}
+
+	// Most recently stored first.
+	entries.sort_by(|a, b| b.0.cmp(&a.0));
+	for (_, path) in entries.drain(max_entries..) {
+		match fs::remove_file(&path) {
+			Ok(()) => debug!(target: "wasm-runtime", "Evicted compiled runtime {}", path.display()),
+			Err(e) => warn!(
+				target: "wasm-runtime",
+				"Failed to evict compiled runtime {}: {}",
+				path.display(),
+				e,
+			),
+		}
+	}
+}
+
+/// Features of the host CPU that Cranelift may use in the code it generates. Modules compiled on
+/// a host can't be run on hosts lacking one of them.
+fn cpu_features() -> Vec<&'static str> {
+	#[allow(unused_mut)]
+	let mut features = Vec::new();
+	#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+	{
+		macro_rules! detect {
+			($($feature:tt),*) => {
+				$(
+					if is_x86_feature_detected!($feature) {
+						features.push($feature);
+					}
+				)*
+			};
+		}
+		detect!("sse3", "ssse3", "sse4.1", "sse4.2", "popcnt", "avx", "avx2", "bmi1", "bmi2", "lzcnt");
+	}
+	features
+}
+
+fn encode_entry(compiled: &[u8]) -> Vec<u8> {
+	let mut data = Vec::with_capacity(HEADER_LEN + compiled.len());
+	data.extend_from_slice(MAGIC);
+	data.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+	data.extend_from_slice(&blake2_256(compiled));
+	data.extend_from_slice(compiled);
+	data
+}
+
+fn decode_entry(data: &[u8]) -> Result<&[u8], String> {
+	if data.len() < HEADER_LEN || &data[..4] != MAGIC {
+		return Err("not a compiled runtime".into());
+	}
+	if data[4..8] != CACHE_VERSION.to_le_bytes() {
+		return Err("unsupported version".into());
+	}
+	let compiled = &data[HEADER_LEN..];
+	if data[8..HEADER_LEN] != blake2_256(compiled) {
+		return Err("checksum mismatch".into());
+	}
+	Ok(compiled)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_wasm_interface::{FunctionContext, Signature, Value};
+
+	#[test]
+	fn entries_are_checked() {
+		let data = encode_entry(b"compiled");
+		assert_eq!(decode_entry(&data), Ok(&b"compiled"[..]));
+
+		let mut corrupted = data.clone();
+		*corrupted.last_mut().unwrap() ^= 1;
+		assert!(decode_entry(&corrupted).is_err());
+		assert!(decode_entry(&data[..HEADER_LEN - 1]).is_err());
+	}
+
+	struct DummyFunction;
+
+	impl Function for DummyFunction {
+		fn name(&self) -> &str {
+			"dummy"
+		}
+
+		fn signature(&self) -> Signature {
+			Signature::new_with_args(Vec::new())
+		}
+
+		fn execute(
+			&self,
+			_: &mut dyn FunctionContext,
+			_: &mut dyn Iterator<Item = Value>,
+		) -> sp_wasm_interface::Result<Option<Value>> {
+			Ok(None)
+		}
+	}
+
+	#[test]
+	fn keys_depend_on_code_limits_and_host_functions() {
+		let dir = Path::new("cache");
+		let limits = ExecutionLimits::default();
+		let other_limits = ExecutionLimits { max_memory_pages: Some(2048), ..limits };
+		let host_functions: Vec<&'static dyn Function> = vec![&DummyFunction];
+
+		let path = |code: &[u8], limits: &ExecutionLimits, host_functions: &[&'static dyn Function]| {
+			CacheEntry::new(dir, code, limits, host_functions).path
+		};
+		let entry = path(b"code", &limits, &host_functions);
+		assert_eq!(entry, path(b"code", &limits, &host_functions));
+		assert_ne!(entry, path(b"other code", &limits, &host_functions));
+		assert_ne!(entry, path(b"code", &other_limits, &host_functions));
+		assert_ne!(entry, path(b"code", &limits, &[]));
+	}
+
+	#[test]
+	fn least_recently_stored_entries_are_evicted() {
+		let dir = tempfile::tempdir().unwrap();
+		for i in 0..4 {
+			fs::write(dir.path().join(format!("{}.{}", i, ENTRY_EXTENSION)), b"compiled").unwrap();
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		}
+		fs::write(dir.path().join("other.tmp"), b"compiled").unwrap();
+
+		evict(dir.path(), 2);
+
+		let mut remaining = fs::read_dir(dir.path()).unwrap()
+			.map(|entry| entry.unwrap().file_name().into_string().unwrap())
+			.collect::<Vec<_>>();
+		remaining.sort();
+		assert_eq!(remaining, vec!["2.bin", "3.bin", "other.tmp"]);
+	}
+
+	#[test]
+	fn wasmtime_version_is_exact() {
+		let mut parts = WASMTIME_VERSION.split('.');
+		assert_eq!(parts.next(), Some("0"));
+		assert_eq!(parts.next(), Some("19"));
+		assert!(parts.next().map_or(false, |patch| !patch.is_empty()));
+	}
+}
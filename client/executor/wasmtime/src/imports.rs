@@ -17,7 +17,7 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::state_holder;
-use sc_executor_common::error::WasmError;
+use sc_executor_common::{error::WasmError, host_stats};
 use sp_wasm_interface::{Function, Value, ValueType};
 use std::any::Any;
 use wasmtime::{
@@ -105,7 +105,10 @@ fn resolve_memory_import(
 		}
 	}
 
-	let memory_ty = MemoryType::new(Limits::new(initial, requested_memory_ty.limits().max()));
+	// As with interpreted code, the memory can grow up to the declared maximum, and not past the
+	// heap pages if there is none.
+	let maximum = requested_memory_ty.limits().max().unwrap_or(initial);
+	let memory_ty = MemoryType::new(Limits::new(initial, Some(maximum)));
 	let memory = Memory::new(store, memory_ty);
 	Ok(Extern::Memory(memory))
 }
@@ -186,7 +189,7 @@ fn call_static(
 		let mut params = wasmtime_params.iter().cloned().map(into_value);
 
 		std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-			static_func.execute(&mut host_ctx, &mut params)
+			host_stats::measure(static_func.name(), || static_func.execute(&mut host_ctx, &mut params))
 		}))
 	});
 
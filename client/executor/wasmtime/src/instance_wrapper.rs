@@ -19,12 +19,14 @@
 //! Defines data and logic needed for interaction with an WebAssembly instance of a substrate
 //! runtime module.
 
+use crate::cache::CacheEntry;
 use crate::util;
 use crate::imports::Imports;
 
 use std::{slice, marker};
 use sc_executor_common::{
 	error::{Error, Result},
+	limits::{self, ExecutionLimits},
 	util::{WasmModuleInfo, DataSegmentsSnapshot},
 };
 use sp_wasm_interface::{Pointer, WordSize, Value};
@@ -41,15 +43,22 @@ pub struct ModuleWrapper {
 }
 
 impl ModuleWrapper {
-	pub fn new(engine: &Engine, code: &[u8]) -> Result<Self> {
-		let mut raw_module: elements::Module = elements::deserialize_buffer(code)
-			.map_err(|e| Error::from(format!("cannot decode module: {}", e)))?;
-		pwasm_utils::export_mutable_globals(&mut raw_module, "exported_internal_global");
-		let instrumented_code = elements::serialize(raw_module)
-			.map_err(|e| Error::from(format!("cannot encode module: {}", e)))?;
-
-		let module = Module::new(engine, &instrumented_code)
-			.map_err(|e| Error::from(format!("cannot create module: {}", e)))?;
+	pub fn new(
+		engine: &Engine,
+		code: &[u8],
+		limits: &ExecutionLimits,
+		cache: Option<&CacheEntry>,
+	) -> Result<Self> {
+		let module = match cache.and_then(|cache| cache.load(engine)) {
+			Some(module) => module,
+			None => {
+				let module = Self::compile(engine, code, limits)?;
+				if let Some(cache) = cache {
+					cache.store(&module);
+				}
+				module
+			},
+		};
 
 		let module_info = WasmModuleInfo::new(code)
 			.ok_or_else(|| Error::from("cannot deserialize module".to_string()))?;
@@ -63,6 +72,20 @@ impl ModuleWrapper {
 		})
 	}
 
+	fn compile(engine: &Engine, code: &[u8], limits: &ExecutionLimits) -> Result<Module> {
+		let raw_module: elements::Module = elements::deserialize_buffer(code)
+			.map_err(|e| Error::from(format!("cannot decode module: {}", e)))?;
+		// The stack height global added here must be exported below to be part of the globals
+		// snapshot, so that it is reset after a trap.
+		let mut raw_module = limits::instrument(raw_module, limits)?;
+		pwasm_utils::export_mutable_globals(&mut raw_module, "exported_internal_global");
+		let instrumented_code = elements::serialize(raw_module)
+			.map_err(|e| Error::from(format!("cannot encode module: {}", e)))?;
+
+		Module::new(engine, &instrumented_code)
+			.map_err(|e| Error::from(format!("cannot create module: {}", e)))
+	}
+
 	pub fn module(&self) -> &Module {
 		&self.module
 	}
@@ -16,6 +16,7 @@
 
 ///! Defines a `WasmRuntime` that uses the Wasmtime JIT to execute.
 
+mod cache;
 mod host;
 mod runtime;
 mod state_holder;
@@ -16,15 +16,18 @@
 
 //! Defines the compiled Wasm runtime that uses Wasmtime internally.
 
+use crate::cache::CacheEntry;
 use crate::host::HostState;
 use crate::imports::{Imports, resolve_imports};
 use crate::instance_wrapper::{ModuleWrapper, InstanceWrapper, GlobalsSnapshot};
 use crate::state_holder;
 
+use std::path::Path;
 use std::rc::Rc;
 use std::sync::Arc;
 use sc_executor_common::{
 	error::{Error, Result, WasmError},
+	limits::ExecutionLimits,
 	wasm_runtime::{WasmModule, WasmInstance},
 };
 use sp_allocator::FreeingBumpHeapAllocator;
@@ -119,11 +122,19 @@ impl WasmInstance for WasmtimeInstance {
 
 /// Create a new `WasmtimeRuntime` given the code. This function performs translation from Wasm to
 /// machine code, which can be computationally heavy.
+///
+/// The code is instrumented beforehand to enforce `limits`, so that it traps at the same point
+/// as with the other backends.
+///
+/// If `cache_path` is given, the compiled code is stored in this directory and loaded from it
+/// the next time the same code is used.
 pub fn create_runtime(
 	code: &[u8],
 	heap_pages: u64,
 	host_functions: Vec<&'static dyn Function>,
 	allow_missing_func_imports: bool,
+	limits: ExecutionLimits,
+	cache_path: Option<&Path>,
 ) -> std::result::Result<WasmtimeRuntime, WasmError> {
 	// Create the engine, store and finally the module from the given code.
 	let mut config = Config::new();
@@ -131,7 +142,8 @@ pub fn create_runtime(
 
 	let engine = Engine::new(&config);
 
-	let module_wrapper = ModuleWrapper::new(&engine, code)
+	let cache = cache_path.map(|path| CacheEntry::new(path, code, &limits, &host_functions));
+	let module_wrapper = ModuleWrapper::new(&engine, code, &limits, cache.as_ref())
 		.map_err(|e| WasmError::Other(format!("cannot create module: {}", e)))?;
 
 	Ok(WasmtimeRuntime {
@@ -36,7 +36,10 @@ mod report;
 use sc_finality_grandpa::GrandpaJustificationStream;
 use sp_runtime::traits::Block as BlockT;
 
-use report::{ReportAuthoritySet, ReportVoterState, ReportedRoundStates};
+use report::{
+	ReportAuthoritySet, ReportVoterState, ReportedEquivocation, ReportedPendingChanges,
+	ReportedRoundStates,
+};
 use notification::JustificationNotification;
 
 /// Returned when Grandpa RPC endpoint is not ready.
@@ -56,6 +59,16 @@ pub trait GrandpaApi<Notification> {
 	#[rpc(name = "grandpa_roundState")]
 	fn round_state(&self) -> FutureResult<ReportedRoundStates>;
 
+	/// Returns the most recent equivocations observed by the local voter,
+	/// oldest first.
+	#[rpc(name = "grandpa_equivocations")]
+	fn equivocations(&self) -> FutureResult<Vec<ReportedEquivocation>>;
+
+	/// Returns the pending changes to the current authority set, standard
+	/// changes first followed by forced changes.
+	#[rpc(name = "grandpa_pendingAuthorityChanges")]
+	fn pending_authority_changes(&self) -> FutureResult<ReportedPendingChanges>;
+
 	/// Returns the block most recently finalized by Grandpa, alongside
 	/// side its justification.
 	#[pubsub(
@@ -122,6 +135,18 @@ where
 		Box::new(future.map_err(jsonrpc_core::Error::from).compat())
 	}
 
+	fn equivocations(&self) -> FutureResult<Vec<ReportedEquivocation>> {
+		let equivocations = ReportedEquivocation::from_voter_state(&self.voter_state);
+		let future = async move { equivocations }.boxed();
+		Box::new(future.map_err(jsonrpc_core::Error::from).compat())
+	}
+
+	fn pending_authority_changes(&self) -> FutureResult<ReportedPendingChanges> {
+		let pending_changes = ReportedPendingChanges::from(&self.authority_set);
+		let future = async move { pending_changes }.boxed();
+		Box::new(future.map_err(jsonrpc_core::Error::from).compat())
+	}
+
 	fn subscribe_justifications(
 		&self,
 		_metadata: Self::Metadata,
@@ -157,7 +182,10 @@ mod tests {
 
 	use parity_scale_codec::Decode;
 	use sc_block_builder::BlockBuilder;
-	use sc_finality_grandpa::{report, AuthorityId, GrandpaJustificationSender, GrandpaJustification};
+	use sc_finality_grandpa::{
+		report, AuthorityId, EquivocationKind, GrandpaJustificationSender, GrandpaJustification,
+		ObservedEquivocation, PendingChangeSummary,
+	};
 	use sp_blockchain::HeaderBackend;
 	use sp_consensus::RecordProof;
 	use sp_core::crypto::Public;
@@ -185,12 +213,26 @@ mod tests {
 		fn get(&self) -> (u64, HashSet<AuthorityId>) {
 			(1, voters())
 		}
+
+		fn pending_changes(&self) -> Vec<PendingChangeSummary<String, u64>> {
+			vec![PendingChangeSummary {
+				next_authorities: vec![(AuthorityId::from_slice(&[3; 32]), 1)],
+				canon_hash: format!("{:?}", sp_core::H256::repeat_byte(1)),
+				canon_height: 10,
+				effective_number: 15,
+				forced: false,
+			}]
+		}
 	}
 
 	impl ReportVoterState for EmptyVoterState {
 		fn get(&self) -> Option<report::VoterState<AuthorityId>> {
 			None
 		}
+
+		fn equivocations(&self) -> Vec<ObservedEquivocation> {
+			Vec::new()
+		}
 	}
 
 	impl ReportVoterState for TestVoterState {
@@ -223,6 +265,15 @@ mod tests {
 				best_round: (2, best_round_state),
 			})
 		}
+
+		fn equivocations(&self) -> Vec<ObservedEquivocation> {
+			vec![ObservedEquivocation {
+				set_id: 1,
+				round: 2,
+				kind: EquivocationKind::Prevote,
+				offender: AuthorityId::from_slice(&[2; 32]),
+			}]
+		}
 	}
 
 	fn setup_io_handler<VoterState>(voter_state: VoterState) -> (
@@ -281,6 +332,45 @@ mod tests {
 		assert_eq!(io.handle_request_sync(request, meta), Some(response.into()));
 	}
 
+	#[test]
+	fn equivocations_rpc_handler() {
+		let (io, _) = setup_io_handler(TestVoterState);
+
+		let request = r#"{"jsonrpc":"2.0","method":"grandpa_equivocations","params":[],"id":1}"#;
+		let response = "{\"jsonrpc\":\"2.0\",\"result\":[{\
+			\"kind\":\"prevote\",\
+			\"offender\":\"5C7LYpP2ZH3tpKbvVvwiVe54AapxErdPBbvkYhe6y9ZBkqWt\",\
+			\"round\":2,\"setId\":1\
+		}],\"id\":1}";
+
+		let meta = sc_rpc::Metadata::default();
+		assert_eq!(io.handle_request_sync(request, meta), Some(response.into()));
+	}
+
+	#[test]
+	fn pending_authority_changes_rpc_handler() {
+		let (io, _) = setup_io_handler(EmptyVoterState);
+
+		let request = r#"{"jsonrpc":"2.0","method":"grandpa_pendingAuthorityChanges","params":[],"id":1}"#;
+		let response: serde_json::Value = serde_json::from_str(
+			&io.handle_request_sync(request, sc_rpc::Metadata::default()).unwrap(),
+		).unwrap();
+
+		let result = &response["result"];
+		assert_eq!(result["setId"], 1);
+
+		let pending = result["pending"].as_array().unwrap();
+		assert_eq!(pending.len(), 1);
+		assert_eq!(pending[0]["canonHeight"], 10);
+		assert_eq!(pending[0]["effectiveNumber"], 15);
+		assert_eq!(pending[0]["forced"], false);
+		assert_eq!(
+			pending[0]["canonHash"],
+			"0x0101010101010101010101010101010101010101010101010101010101010101",
+		);
+		assert_eq!(pending[0]["nextAuthorities"].as_array().unwrap().len(), 1);
+	}
+
 	fn setup_session() -> (sc_rpc::Metadata, jsonrpc_core::futures::sync::mpsc::Receiver<String>) {
 		let (tx, rx) = jsonrpc_core::futures::sync::mpsc::channel(1);
 		let meta = sc_rpc::Metadata::new(tx);
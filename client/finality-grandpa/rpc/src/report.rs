@@ -24,23 +24,29 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 
-use sc_finality_grandpa::{report, AuthorityId, SharedAuthoritySet, SharedVoterState};
+use sc_finality_grandpa::{
+	report, AuthorityId, EquivocationKind, ObservedEquivocation, PendingChangeSummary,
+	SharedAuthoritySet, SharedVoterState,
+};
+use sp_runtime::traits::UniqueSaturatedInto;
 
 use crate::error::Error;
 
 /// Utility trait to get reporting data for the current GRANDPA authority set.
 pub trait ReportAuthoritySet {
 	fn get(&self) -> (u64, HashSet<AuthorityId>);
+	fn pending_changes(&self) -> Vec<PendingChangeSummary<String, u64>>;
 }
 
 /// Utility trait to get reporting data for the current GRANDPA voter state.
 pub trait ReportVoterState {
 	fn get(&self) -> Option<report::VoterState<AuthorityId>>;
+	fn equivocations(&self) -> Vec<ObservedEquivocation>;
 }
 
 impl<H, N> ReportAuthoritySet for SharedAuthoritySet<H, N>
 where
-	N: Add<Output = N> + Ord + Clone + Debug,
+	N: Add<Output = N> + Ord + Clone + Debug + UniqueSaturatedInto<u64>,
 	H: Clone + Debug + Eq,
 {
 	fn get(&self) -> (u64, HashSet<AuthorityId>) {
@@ -52,12 +58,29 @@ where
 
 		(self.set_id(), current_voters)
 	}
+
+	fn pending_changes(&self) -> Vec<PendingChangeSummary<String, u64>> {
+		SharedAuthoritySet::pending_changes(self)
+			.into_iter()
+			.map(|change| PendingChangeSummary {
+				next_authorities: change.next_authorities,
+				canon_hash: format!("{:?}", change.canon_hash),
+				canon_height: change.canon_height.unique_saturated_into(),
+				effective_number: change.effective_number.unique_saturated_into(),
+				forced: change.forced,
+			})
+			.collect()
+	}
 }
 
 impl ReportVoterState for SharedVoterState {
 	fn get(&self) -> Option<report::VoterState<AuthorityId>> {
 		self.voter_state()
 	}
+
+	fn equivocations(&self) -> Vec<ObservedEquivocation> {
+		SharedVoterState::equivocations(self)
+	}
 }
 
 #[derive(Serialize, Deserialize)]
@@ -159,3 +182,106 @@ impl ReportedRoundStates {
 		})
 	}
 }
+
+/// The kind of vote an equivocation was made on, in a form suitable for
+/// serialization.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum VoteKind {
+	Prevote,
+	Precommit,
+}
+
+/// An equivocation observed by the local voter in a form suitable for
+/// serialization.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportedEquivocation {
+	set_id: u32,
+	round: u32,
+	kind: VoteKind,
+	offender: AuthorityId,
+}
+
+impl ReportedEquivocation {
+	fn from(equivocation: ObservedEquivocation) -> Result<Self, Error> {
+		use std::convert::TryInto;
+
+		Ok(Self {
+			set_id: equivocation.set_id.try_into()?,
+			round: equivocation.round.try_into()?,
+			kind: match equivocation.kind {
+				EquivocationKind::Prevote => VoteKind::Prevote,
+				EquivocationKind::Precommit => VoteKind::Precommit,
+			},
+			offender: equivocation.offender,
+		})
+	}
+
+	pub fn from_voter_state<VoterState>(
+		voter_state: &VoterState,
+	) -> Result<Vec<Self>, Error>
+	where
+		VoterState: ReportVoterState,
+	{
+		voter_state
+			.equivocations()
+			.into_iter()
+			.map(Self::from)
+			.collect()
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PendingChange {
+	canon_hash: String,
+	canon_height: u32,
+	effective_number: u32,
+	forced: bool,
+	next_authorities: Vec<(AuthorityId, u64)>,
+}
+
+impl PendingChange {
+	fn from(change: PendingChangeSummary<String, u64>) -> Result<Self, Error> {
+		use std::convert::TryInto;
+
+		Ok(Self {
+			canon_hash: change.canon_hash,
+			canon_height: change.canon_height.try_into()?,
+			effective_number: change.effective_number.try_into()?,
+			forced: change.forced,
+			next_authorities: change.next_authorities,
+		})
+	}
+}
+
+/// The pending changes to the current authority set in a form suitable for
+/// serialization.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportedPendingChanges {
+	set_id: u32,
+	pending: Vec<PendingChange>,
+}
+
+impl ReportedPendingChanges {
+	pub fn from<AuthoritySet>(authority_set: &AuthoritySet) -> Result<Self, Error>
+	where
+		AuthoritySet: ReportAuthoritySet,
+	{
+		use std::convert::TryFrom;
+
+		let (set_id, _) = authority_set.get();
+		let set_id =
+			u32::try_from(set_id).map_err(|_| Error::AuthoritySetIdReportedAsUnreasonablyLarge)?;
+
+		let pending = authority_set
+			.pending_changes()
+			.into_iter()
+			.map(PendingChange::from)
+			.collect::<Result<Vec<_>, Error>>()?;
+
+		Ok(Self { set_id, pending })
+	}
+}
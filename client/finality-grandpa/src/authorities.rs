@@ -81,6 +81,21 @@ where N: Add<Output=N> + Ord + Clone + Debug,
 			 qed.",
 		)
 	}
+
+	/// Get a summary of all pending authority set changes. Standard changes
+	/// are listed first, followed by forced changes.
+	pub fn pending_changes(&self) -> Vec<PendingChangeSummary<H, N>> {
+		self.inner.read().pending_changes().map(|change| PendingChangeSummary {
+			next_authorities: change.next_authorities.clone(),
+			canon_hash: change.canon_hash.clone(),
+			canon_height: change.canon_height.clone(),
+			effective_number: change.effective_number(),
+			forced: match change.delay_kind {
+				DelayKind::Finalized => false,
+				DelayKind::Best { .. } => true,
+			},
+		}).collect()
+	}
 }
 
 impl<H, N> From<AuthoritySet<H, N>> for SharedAuthoritySet<H, N> {
@@ -540,6 +555,22 @@ impl<H: Decode, N: Decode> Decode for PendingChange<H, N> {
 	}
 }
 
+/// A summary of a pending change to the authority set, suitable for reporting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingChangeSummary<H, N> {
+	/// The new authorities and weights to apply.
+	pub next_authorities: AuthorityList,
+	/// The announcing block's hash.
+	pub canon_hash: H,
+	/// The announcing block's height.
+	pub canon_height: N,
+	/// The block number the change will be applied at.
+	pub effective_number: N,
+	/// Whether this is a forced change, i.e. enacted on block depth in the
+	/// best chain rather than on finality.
+	pub forced: bool,
+}
+
 impl<H, N: Add<Output=N> + Clone> PendingChange<H, N> {
 	/// Returns the effective number this change will be applied at.
 	pub fn effective_number(&self) -> N {
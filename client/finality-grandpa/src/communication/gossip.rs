@@ -94,10 +94,13 @@ use sp_utils::mpsc::{tracing_unbounded, TracingUnboundedReceiver, TracingUnbound
 use prometheus_endpoint::{CounterVec, Opts, PrometheusError, register, Registry, U64};
 use rand::seq::SliceRandom;
 
-use crate::{environment, CatchUp, CompactCommit, SignedMessage};
+use crate::{
+	environment, CatchUp, CompactCommit, EquivocationKind, ObservedEquivocation, SharedVoterState,
+	SignedMessage,
+};
 use super::{cost, benefit, Round, SetId};
 
-use std::collections::{HashMap, VecDeque, HashSet};
+use std::collections::{HashMap, VecDeque, HashSet, hash_map::Entry};
 use std::time::{Duration, Instant};
 
 const REBROADCAST_AFTER: Duration = Duration::from_secs(60 * 5);
@@ -680,6 +683,11 @@ struct Inner<Block: BlockT> {
 	next_rebroadcast: Instant,
 	pending_catch_up: PendingCatchUp,
 	catch_up_config: CatchUpConfig,
+	/// Target of the prevotes and precommits seen in the rounds we accept votes for, by round,
+	/// set, kind of vote and voter.
+	seen_votes: HashMap<(u64, u64, EquivocationKind, AuthorityId), (Block::Hash, NumberFor<Block>)>,
+	/// Where to record the equivocations spotted in these votes.
+	shared_voter_state: Option<SharedVoterState>,
 }
 
 type MaybeMessage<Block> = Option<(Vec<PeerId>, NeighborPacket<NumberFor<Block>>)>;
@@ -712,6 +720,8 @@ impl<Block: BlockT> Inner<Block> {
 			pending_catch_up: PendingCatchUp::None,
 			catch_up_config,
 			config,
+			seen_votes: HashMap::new(),
+			shared_voter_state: None,
 		}
 	}
 
@@ -737,6 +747,7 @@ impl<Block: BlockT> Inner<Block> {
 			self.live_topics.push(round, set_id);
 			self.peers.reshuffle();
 		}
+		self.prune_seen_votes();
 		self.multicast_neighbor_packet()
 	}
 
@@ -772,6 +783,7 @@ impl<Block: BlockT> Inner<Block> {
 			self.live_topics.push(Round(1), set_id);
 			self.authorities = authorities;
 		}
+		self.prune_seen_votes();
 		self.multicast_neighbor_packet()
 	}
 
@@ -815,7 +827,42 @@ impl<Block: BlockT> Inner<Block> {
 		cost::PAST_REJECTION
 	}
 
-	fn validate_round_message(&self, who: &PeerId, full: &VoteMessage<Block>)
+	/// Forget the votes of the rounds we don't accept votes for anymore.
+	fn prune_seen_votes(&mut self) {
+		let local_view = self.local_view.as_ref();
+		self.seen_votes.retain(|&(round, set_id, _, _), _| local_view
+			.map(|v| v.as_view().consider_vote(Round(round), SetId(set_id)) == Consider::Accept)
+			.unwrap_or(false)
+		);
+	}
+
+	/// Note a valid vote. Returns the equivocation it makes if the voter has already cast a
+	/// different vote of the same kind in the same round.
+	fn note_vote(&mut self, full: &VoteMessage<Block>) -> Option<ObservedEquivocation> {
+		let (kind, target) = match &full.message.message {
+			finality_grandpa::Message::Prevote(prevote) =>
+				(EquivocationKind::Prevote, (prevote.target_hash, prevote.target_number)),
+			finality_grandpa::Message::Precommit(precommit) =>
+				(EquivocationKind::Precommit, (precommit.target_hash, precommit.target_number)),
+			finality_grandpa::Message::PrimaryPropose(_) => return None,
+		};
+
+		match self.seen_votes.entry((full.round.0, full.set_id.0, kind, full.message.id.clone())) {
+			Entry::Vacant(entry) => {
+				entry.insert(target);
+				None
+			},
+			Entry::Occupied(entry) if *entry.get() == target => None,
+			Entry::Occupied(_) => Some(ObservedEquivocation {
+				set_id: full.set_id.0,
+				round: full.round.0,
+				kind,
+				offender: full.message.id.clone(),
+			}),
+		}
+	}
+
+	fn validate_round_message(&mut self, who: &PeerId, full: &VoteMessage<Block>)
 		-> Action<Block::Hash>
 	{
 		match self.consider_vote(full.round, full.set_id) {
@@ -845,6 +892,15 @@ impl<Block: BlockT> Inner<Block> {
 			return Action::Discard(cost::BAD_SIGNATURE);
 		}
 
+		// the equivocating vote is still kept, so that voters can report it.
+		if let Some(equivocation) = self.note_vote(full) {
+			debug!(target: "afg", "Detected {:?} equivocation by {:?} in round {} of set {}",
+				equivocation.kind, equivocation.offender, equivocation.round, equivocation.set_id);
+			if let Some(shared_voter_state) = &self.shared_voter_state {
+				shared_voter_state.note_equivocation(equivocation);
+			}
+		}
+
 		let topic = super::round_topic::<Block>(full.round.0, full.set_id.0);
 		Action::Keep(topic, benefit::ROUND_MESSAGE)
 	}
@@ -1353,6 +1409,11 @@ impl<Block: BlockT> GossipValidator<Block> {
 		}
 	}
 
+	/// Record the equivocations spotted in the votes being validated in `shared_voter_state`.
+	pub(super) fn note_equivocations_in(&self, shared_voter_state: SharedVoterState) {
+		self.inner.write().shared_voter_state = Some(shared_voter_state);
+	}
+
 	/// Note that we've processed a catch up message.
 	pub(super) fn note_catch_up_message_processed(&self)	{
 		self.inner.write().note_catch_up_message_processed();
@@ -1633,6 +1694,7 @@ mod tests {
 			name: None,
 			is_authority: true,
 			observer_enabled: true,
+			gossip_limits: Default::default(),
 		}
 	}
 
@@ -1844,7 +1906,7 @@ mod tests {
 		val.note_set(SetId(set_id), vec![auth.clone()], |_, _| {});
 		val.note_round(Round(1), |_, _| {});
 
-		let inner = val.inner.read();
+		let mut inner = val.inner.write();
 		let unknown_voter = inner.validate_round_message(&peer, &VoteMessage {
 			round: Round(1),
 			set_id: SetId(set_id),
@@ -2634,4 +2696,67 @@ mod tests {
 
 		assert_eq!(val.inner().read().authorities, a2);
 	}
+
+	#[test]
+	fn records_equivocating_votes() {
+		use sp_keyring::Ed25519Keyring;
+
+		let (val, _) = GossipValidator::<Block>::new(config(), voter_set_state(), None);
+		let shared_voter_state = crate::SharedVoterState::empty();
+		val.note_equivocations_in(shared_voter_state.clone());
+
+		let set_id = 1;
+		let keyring = Ed25519Keyring::Alice;
+		let auth: AuthorityId = keyring.public().into();
+		let peer = PeerId::random();
+
+		val.note_set(SetId(set_id), vec![auth.clone()], |_, _| {});
+		val.note_round(Round(1), |_, _| {});
+
+		let prevote = |target_hash: H256| {
+			let message = finality_grandpa::Message::Prevote(finality_grandpa::Prevote {
+				target_hash,
+				target_number: 10,
+			});
+			let payload = sp_finality_grandpa::localized_payload(1, set_id, &message);
+			VoteMessage::<Block> {
+				round: Round(1),
+				set_id: SetId(set_id),
+				message: SignedMessage::<Block> {
+					message,
+					signature: keyring.sign(&payload[..]).into(),
+					id: auth.clone(),
+				},
+			}
+		};
+
+		let mut inner = val.inner.write();
+		let topic = crate::communication::round_topic::<Block>(1, set_id);
+
+		// voting twice for the same block is not an equivocation.
+		for vote in &[prevote(H256::repeat_byte(1)), prevote(H256::repeat_byte(1))] {
+			assert_eq!(
+				inner.validate_round_message(&peer, vote),
+				Action::Keep(topic, benefit::ROUND_MESSAGE),
+			);
+		}
+		assert!(shared_voter_state.equivocations().is_empty());
+
+		// the conflicting vote is still kept, but recorded as an equivocation.
+		assert_eq!(
+			inner.validate_round_message(&peer, &prevote(H256::repeat_byte(2))),
+			Action::Keep(topic, benefit::ROUND_MESSAGE),
+		);
+		assert_eq!(shared_voter_state.equivocations(), vec![crate::ObservedEquivocation {
+			set_id,
+			round: 1,
+			kind: crate::EquivocationKind::Prevote,
+			offender: auth.clone(),
+		}]);
+
+		// votes of rounds we don't accept votes for anymore are forgotten.
+		drop(inner);
+		val.note_set(SetId(set_id + 1), vec![auth], |_, _| {});
+		assert!(val.inner.read().seen_votes.is_empty());
+	}
 }
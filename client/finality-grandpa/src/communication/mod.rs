@@ -212,6 +212,7 @@ impl<B: BlockT, N: Network<B>> NetworkBridge<B, N> {
 		set_state: crate::environment::SharedVoterSetState<B>,
 		prometheus_registry: Option<&Registry>,
 	) -> Self {
+		let gossip_limits = config.gossip_limits.clone();
 		let (validator, report_stream) = GossipValidator::new(
 			config,
 			set_state.clone(),
@@ -223,7 +224,8 @@ impl<B: BlockT, N: Network<B>> NetworkBridge<B, N> {
 			service.clone(),
 			GRANDPA_ENGINE_ID,
 			GRANDPA_PROTOCOL_NAME,
-			validator.clone()
+			validator.clone(),
+			gossip_limits,
 		)));
 
 		{
@@ -449,6 +451,12 @@ impl<B: BlockT, N: Network<B>> NetworkBridge<B, N> {
 	) {
 		Network::set_sync_fork_request(&self.service, peers, hash, number)
 	}
+
+	/// Record the equivocations the gossip validator spots in incoming votes in the given
+	/// shared voter state.
+	pub(crate) fn note_equivocations_in(&self, shared_voter_state: crate::SharedVoterState) {
+		self.validator.note_equivocations_in(shared_voter_state)
+	}
 }
 
 impl<B: BlockT, N: Network<B>> Future for NetworkBridge<B, N> {
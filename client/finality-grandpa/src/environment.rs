@@ -42,8 +42,8 @@ use sp_runtime::traits::{
 use sc_telemetry::{telemetry, CONSENSUS_INFO};
 
 use crate::{
-	CommandOrError, Commit, Config, Error, Precommit, Prevote,
-	PrimaryPropose, SignedMessage, NewAuthoritySet, VoterCommand,
+	CommandOrError, Commit, Config, Error, EquivocationKind, ObservedEquivocation, Precommit,
+	Prevote, PrimaryPropose, SharedVoterState, SignedMessage, NewAuthoritySet, VoterCommand,
 };
 
 use sp_consensus::SelectChain;
@@ -405,6 +405,7 @@ pub(crate) struct Environment<Backend, Block: BlockT, C, N: NetworkT<Block>, SC,
 	pub(crate) voting_rule: VR,
 	pub(crate) metrics: Option<Metrics>,
 	pub(crate) justification_sender: Option<GrandpaJustificationSender<Block>>,
+	pub(crate) shared_voter_state: SharedVoterState,
 	pub(crate) _phantom: PhantomData<Backend>,
 }
 
@@ -1037,10 +1038,16 @@ where
 
 	fn prevote_equivocation(
 		&self,
-		_round: RoundNumber,
+		round: RoundNumber,
 		equivocation: finality_grandpa::Equivocation<Self::Id, Prevote<Block>, Self::Signature>,
 	) {
 		warn!(target: "afg", "Detected prevote equivocation in the finality worker: {:?}", equivocation);
+		self.shared_voter_state.note_equivocation(ObservedEquivocation {
+			set_id: self.set_id,
+			round,
+			kind: EquivocationKind::Prevote,
+			offender: equivocation.identity.clone(),
+		});
 		if let Err(err) = self.report_equivocation(equivocation.into()) {
 			warn!(target: "afg", "Error reporting prevote equivocation: {:?}", err);
 		}
@@ -1048,10 +1055,16 @@ where
 
 	fn precommit_equivocation(
 		&self,
-		_round: RoundNumber,
+		round: RoundNumber,
 		equivocation: finality_grandpa::Equivocation<Self::Id, Precommit<Block>, Self::Signature>,
 	) {
 		warn!(target: "afg", "Detected precommit equivocation in the finality worker: {:?}", equivocation);
+		self.shared_voter_state.note_equivocation(ObservedEquivocation {
+			set_id: self.set_id,
+			round,
+			kind: EquivocationKind::Precommit,
+			offender: equivocation.identity.clone(),
+		});
 		if let Err(err) = self.report_equivocation(equivocation.into()) {
 			warn!(target: "afg", "Error reporting precommit equivocation: {:?}", err);
 		}
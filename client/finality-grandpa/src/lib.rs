@@ -88,6 +88,7 @@ use finality_grandpa::{voter, voter_set::VoterSet};
 pub use finality_grandpa::BlockNumberOps;
 
 use std::{fmt, io};
+use std::collections::VecDeque;
 use std::sync::Arc;
 use std::time::Duration;
 use std::pin::Pin;
@@ -124,7 +125,7 @@ mod observer;
 mod until_imported;
 mod voting_rule;
 
-pub use authorities::SharedAuthoritySet;
+pub use authorities::{PendingChangeSummary, SharedAuthoritySet};
 pub use finality_proof::{FinalityProofProvider, StorageAndProofProvider};
 pub use notification::{GrandpaJustificationSender, GrandpaJustificationStream};
 pub use import::GrandpaBlockImport;
@@ -143,6 +144,7 @@ use sp_finality_grandpa::{AuthorityList, AuthoritySignature, SetId};
 
 // Re-export these two because it's just so damn convenient.
 pub use sp_finality_grandpa::{AuthorityId, AuthorityPair, GrandpaApi, ScheduledChange};
+pub use sc_network_gossip::GossipLimits;
 use std::marker::PhantomData;
 
 #[cfg(test)]
@@ -216,9 +218,36 @@ type CommunicationOutH<Block, H> = finality_grandpa::voter::CommunicationOut<
 	AuthorityId,
 >;
 
+/// Maximum number of equivocations observed by the voter that are kept around
+/// for querying. Older entries are discarded first.
+const MAX_OBSERVED_EQUIVOCATIONS: usize = 64;
+
+/// The kind of vote an observed equivocation was made on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EquivocationKind {
+	/// Two conflicting prevotes in the same round.
+	Prevote,
+	/// Two conflicting precommits in the same round.
+	Precommit,
+}
+
+/// An equivocation observed by the local voter or by the gossip validator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObservedEquivocation {
+	/// The authority set id the equivocation happened in.
+	pub set_id: u64,
+	/// The round the equivocation happened in.
+	pub round: u64,
+	/// The kind of vote that was equivocated.
+	pub kind: EquivocationKind,
+	/// The equivocating authority.
+	pub offender: AuthorityId,
+}
+
 /// Shared voter state for querying.
 pub struct SharedVoterState {
 	inner: Arc<RwLock<Option<Box<dyn voter::VoterState<AuthorityId> + Sync + Send>>>>,
+	equivocations: Arc<RwLock<VecDeque<ObservedEquivocation>>>,
 }
 
 impl SharedVoterState {
@@ -226,6 +255,7 @@ impl SharedVoterState {
 	pub fn empty() -> Self {
 		Self {
 			inner: Arc::new(RwLock::new(None)),
+			equivocations: Arc::new(RwLock::new(VecDeque::new())),
 		}
 	}
 
@@ -245,11 +275,37 @@ impl SharedVoterState {
 	pub fn voter_state(&self) -> Option<voter::report::VoterState<AuthorityId>> {
 		self.inner.read().as_ref().map(|vs| vs.get())
 	}
+
+	/// Record an equivocation observed by the voter or by the gossip validator. Equivocations
+	/// that have already been recorded are ignored.
+	pub(crate) fn note_equivocation(&self, equivocation: ObservedEquivocation) {
+		let mut equivocations = self.equivocations.write();
+		if equivocations.contains(&equivocation) {
+			return;
+		}
+		if equivocations.len() >= MAX_OBSERVED_EQUIVOCATIONS {
+			equivocations.pop_front();
+		}
+		equivocations.push_back(equivocation);
+	}
+
+	/// Forget the recorded equivocations, which only concern the previous authority set.
+	fn clear_equivocations(&self) {
+		self.equivocations.write().clear();
+	}
+
+	/// Get the most recent equivocations observed in the current authority set, oldest first.
+	pub fn equivocations(&self) -> Vec<ObservedEquivocation> {
+		self.equivocations.read().iter().cloned().collect()
+	}
 }
 
 impl Clone for SharedVoterState {
 	fn clone(&self) -> Self {
-		SharedVoterState { inner: self.inner.clone() }
+		SharedVoterState {
+			inner: self.inner.clone(),
+			equivocations: self.equivocations.clone(),
+		}
 	}
 }
 
@@ -273,6 +329,8 @@ pub struct Config {
 	pub name: Option<String>,
 	/// The keystore that manages the keys of this node.
 	pub keystore: Option<BareCryptoStorePtr>,
+	/// Limits applied to the GRANDPA gossip messages received from other peers.
+	pub gossip_limits: GossipLimits,
 }
 
 impl Config {
@@ -744,6 +802,7 @@ pub fn run_grandpa_voter<Block: BlockT, BE: 'static, C, N, SC, VR>(
 		persistent_data.set_state.clone(),
 		prometheus_registry.as_ref(),
 	);
+	network.note_equivocations_in(shared_voter_state.clone());
 
 	register_finality_tracker_inherent_data_provider(client.clone(), &inherent_data_providers)?;
 
@@ -874,6 +933,7 @@ where
 			voter_set_state: persistent_data.set_state,
 			metrics: metrics.as_ref().map(|m| m.environment.clone()),
 			justification_sender: Some(justification_sender),
+			shared_voter_state: shared_voter_state.clone(),
 			_phantom: PhantomData,
 		});
 
@@ -992,6 +1052,8 @@ where
 					Ok(Some(set_state))
 				})?;
 
+				self.env.shared_voter_state.clear_equivocations();
+
 				let voters = Arc::new(VoterSet::new(new.authorities.into_iter())
 					.expect("new authorities come from pending change; \
 							 pending change comes from `AuthoritySet`; \
@@ -1013,6 +1075,7 @@ where
 					voting_rule: self.env.voting_rule.clone(),
 					metrics: self.env.metrics.clone(),
 					justification_sender: self.env.justification_sender.clone(),
+					shared_voter_state: self.env.shared_voter_state.clone(),
 					_phantom: PhantomData,
 				});
 
@@ -355,6 +355,7 @@ fn run_to_completion_with<F>(
 				name: Some(format!("peer#{}", peer_id)),
 				is_authority: true,
 				observer_enabled: true,
+				gossip_limits: Default::default(),
 			},
 			link: link,
 			network: net_service,
@@ -487,6 +488,7 @@ fn finalize_3_voters_1_full_observer() {
 				name: Some(format!("peer#{}", peer_id)),
 				is_authority: true,
 				observer_enabled: true,
+				gossip_limits: Default::default(),
 			},
 			link: link,
 			network: net_service,
@@ -651,6 +653,7 @@ fn transition_3_voters_twice_1_full_observer() {
 				name: Some(format!("peer#{}", peer_id)),
 				is_authority: true,
 				observer_enabled: true,
+				gossip_limits: Default::default(),
 			},
 			link: link,
 			network: net_service,
@@ -1076,6 +1079,7 @@ fn voter_persists_its_votes() {
 								name: Some(format!("peer#{}", 0)),
 								is_authority: true,
 								observer_enabled: true,
+								gossip_limits: Default::default(),
 							},
 							link,
 							network: this.net.lock().peers[0].network_service().clone(),
@@ -1134,6 +1138,7 @@ fn voter_persists_its_votes() {
 			name: Some(format!("peer#{}", 1)),
 			is_authority: true,
 			observer_enabled: true,
+			gossip_limits: Default::default(),
 		};
 
 		let set_state = {
@@ -1294,6 +1299,7 @@ fn finalize_3_voters_1_light_observer() {
 					name: Some("observer".to_string()),
 					is_authority: false,
 					observer_enabled: true,
+					gossip_limits: Default::default(),
 				},
 				link,
 				net.lock().peers[3].network_service().clone(),
@@ -1422,6 +1428,7 @@ fn voter_catches_up_to_latest_round_when_behind() {
 				name: Some(format!("peer#{}", peer_id)),
 				is_authority: true,
 				observer_enabled: true,
+				gossip_limits: Default::default(),
 			},
 			link,
 			network: net.lock().peer(peer_id).network_service().clone(),
@@ -1546,6 +1553,7 @@ where
 		name: None,
 		is_authority: true,
 		observer_enabled: true,
+		gossip_limits: Default::default(),
 	};
 
 	let network = NetworkBridge::new(
@@ -1568,6 +1576,7 @@ where
 		voting_rule,
 		metrics: None,
 		justification_sender: None,
+		shared_voter_state: SharedVoterState::empty(),
 		_phantom: PhantomData,
 	}
 }
@@ -15,7 +15,9 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{Network, Validator};
-use crate::state_machine::{ConsensusGossip, TopicNotification, PERIODIC_MAINTENANCE_INTERVAL};
+use crate::state_machine::{
+	ConsensusGossip, GossipLimits, TopicNotification, PERIODIC_MAINTENANCE_INTERVAL,
+};
 
 use sc_network::{Event, ReputationChange};
 
@@ -71,6 +73,7 @@ impl<B: BlockT> GossipEngine<B> {
 		engine_id: ConsensusEngineId,
 		protocol_name: impl Into<Cow<'static, [u8]>>,
 		validator: Arc<dyn Validator<B>>,
+		limits: GossipLimits,
 	) -> Self where B: 'static {
 		// We grab the event stream before registering the notifications protocol, otherwise we
 		// might miss events.
@@ -78,7 +81,7 @@ impl<B: BlockT> GossipEngine<B> {
 		network.register_notifications_protocol(engine_id, protocol_name.into());
 
 		GossipEngine {
-			state_machine: ConsensusGossip::new(validator, engine_id),
+			state_machine: ConsensusGossip::new(validator, engine_id, limits),
 			network: Box::new(network),
 			periodic_maintenance_interval: futures_timer::Delay::new(PERIODIC_MAINTENANCE_INTERVAL),
 			engine_id,
@@ -364,6 +367,7 @@ mod tests {
 			[1, 2, 3, 4],
 			"my_protocol".as_bytes(),
 			Arc::new(AllowAll{}),
+			Default::default(),
 		);
 
 		// Drop network event stream sender side.
@@ -392,6 +396,7 @@ mod tests {
 			engine_id.clone(),
 			"my_protocol".as_bytes(),
 			Arc::new(AllowAll{}),
+			Default::default(),
 		);
 
 		let mut event_sender = network.inner.lock()
@@ -527,6 +532,7 @@ mod tests {
 				engine_id.clone(),
 				"my_protocol".as_bytes(),
 				Arc::new(TestValidator{}),
+				Default::default(),
 			);
 
 			// Create channels.
@@ -27,7 +27,7 @@ use lru::LruCache;
 use libp2p::PeerId;
 use sp_runtime::traits::{Block as BlockT, Hash, HashFor};
 use sp_runtime::ConsensusEngineId;
-use sc_network::ObservedRole;
+use sc_network::{ObservedRole, ReputationChange};
 use wasm_timer::Instant;
 
 // FIXME: Add additional spam/DoS attack protection: https://github.com/paritytech/substrate/issues/1115
@@ -43,10 +43,75 @@ mod rep {
 	pub const GOSSIP_SUCCESS: Rep = Rep::new(1 << 4, "Successfull gossip");
 	/// Reputation change when a peer sends us a gossip message that we already knew about.
 	pub const DUPLICATE_GOSSIP: Rep = Rep::new(-(1 << 2), "Duplicate gossip");
+	/// Reputation change when a peer sends us a gossip message larger than the configured limit.
+	pub const MESSAGE_TOO_LARGE: Rep = Rep::new(-(1 << 12), "Gossip message too large");
+	/// Reputation change when a peer sends us more gossip messages than allowed by the rate limit.
+	pub const RATE_LIMIT_EXCEEDED: Rep = Rep::new(-(1 << 8), "Gossip rate limit exceeded");
+}
+
+/// Limits enforced by a gossip engine, in order to bound the memory and processing time that
+/// remote peers can make us spend.
+#[derive(Debug, Clone)]
+pub struct GossipLimits {
+	/// Maximum number of messages received from peers that are kept per topic. Further messages
+	/// on a full topic are still forwarded to the upper layers but aren't stored for propagation.
+	/// Messages registered locally don't count towards this limit.
+	pub max_messages_per_topic: usize,
+	/// Maximum total size in bytes of the messages received from peers that are kept per topic.
+	/// Works like `max_messages_per_topic`, and bounds the memory used by a topic together with
+	/// `max_message_size`.
+	pub max_bytes_per_topic: usize,
+	/// Maximum size in bytes of an incoming message. Larger messages are discarded without being
+	/// validated.
+	pub max_message_size: usize,
+	/// Maximum number of messages that a single peer can send us per `rate_limit_interval`.
+	/// Messages beyond that limit are discarded without being validated.
+	pub max_messages_per_peer: u32,
+	/// Duration of the interval over which `max_messages_per_peer` applies.
+	pub rate_limit_interval: time::Duration,
+}
+
+impl Default for GossipLimits {
+	fn default() -> Self {
+		GossipLimits {
+			max_messages_per_topic: 1024,
+			max_bytes_per_topic: 4 * 1024 * 1024,
+			max_message_size: 1024 * 1024,
+			max_messages_per_peer: 2048,
+			rate_limit_interval: time::Duration::from_secs(10),
+		}
+	}
 }
 
 struct PeerConsensus<H> {
 	known_messages: HashSet<H>,
+	/// Start of the current rate limiting interval.
+	interval_start: Instant,
+	/// Number of messages received from this peer since `interval_start`.
+	received_in_interval: u32,
+}
+
+impl<H> PeerConsensus<H> {
+	fn new() -> Self {
+		PeerConsensus {
+			known_messages: HashSet::new(),
+			interval_start: Instant::now(),
+			received_in_interval: 0,
+		}
+	}
+
+	/// Accounts for a message received from this peer. Returns false if the peer has exceeded
+	/// the rate limit.
+	fn note_received(&mut self, limits: &GossipLimits) -> bool {
+		let now = Instant::now();
+		if now.duration_since(self.interval_start) >= limits.rate_limit_interval {
+			self.interval_start = now;
+			self.received_in_interval = 0;
+		}
+
+		self.received_in_interval = self.received_in_interval.saturating_add(1);
+		self.received_in_interval <= limits.max_messages_per_peer
+	}
 }
 
 /// Topic stream message with sender.
@@ -96,6 +161,11 @@ impl<'g, 'p, B: BlockT> ValidatorContext<B> for NetworkContext<'g, 'p, B> {
 	fn send_topic(&mut self, who: &PeerId, topic: B::Hash, force: bool) {
 		self.gossip.send_topic(self.network, who, topic, force);
 	}
+
+	/// Adjust the reputation of a peer.
+	fn report_peer(&mut self, who: &PeerId, reputation: ReputationChange) {
+		self.network.report_peer(who.clone(), reputation);
+	}
 }
 
 fn propagate<'a, B: BlockT, I>(
@@ -147,21 +217,30 @@ fn propagate<'a, B: BlockT, I>(
 pub struct ConsensusGossip<B: BlockT> {
 	peers: HashMap<PeerId, PeerConsensus<B::Hash>>,
 	messages: Vec<MessageEntry<B>>,
+	/// Number and total size of the entries of `messages` received from peers, per topic.
+	received_per_topic: HashMap<B::Hash, (usize, usize)>,
 	known_messages: LruCache<B::Hash, ()>,
 	engine_id: ConsensusEngineId,
 	validator: Arc<dyn Validator<B>>,
+	limits: GossipLimits,
 	next_broadcast: Instant,
 }
 
 impl<B: BlockT> ConsensusGossip<B> {
-	/// Create a new instance using the given validator.
-	pub fn new(validator: Arc<dyn Validator<B>>, engine_id: ConsensusEngineId) -> Self {
+	/// Create a new instance using the given validator and limits.
+	pub fn new(
+		validator: Arc<dyn Validator<B>>,
+		engine_id: ConsensusEngineId,
+		limits: GossipLimits,
+	) -> Self {
 		ConsensusGossip {
 			peers: HashMap::new(),
 			messages: Default::default(),
+			received_per_topic: HashMap::new(),
 			known_messages: LruCache::new(KNOWN_MESSAGES_CACHE_SIZE),
 			engine_id,
 			validator,
+			limits,
 			next_broadcast: Instant::now() + REBROADCAST_INTERVAL,
 		}
 	}
@@ -174,9 +253,7 @@ impl<B: BlockT> ConsensusGossip<B> {
 		}
 
 		trace!(target:"gossip", "Registering {:?} {}", role, who);
-		self.peers.insert(who.clone(), PeerConsensus {
-			known_messages: HashSet::new(),
-		});
+		self.peers.insert(who.clone(), PeerConsensus::new());
 
 		let validator = self.validator.clone();
 		let mut context = NetworkContext { gossip: self, network };
@@ -190,14 +267,32 @@ impl<B: BlockT> ConsensusGossip<B> {
 		message: Vec<u8>,
 		sender: Option<PeerId>,
 	) {
-		if self.known_messages.put(message_hash.clone(), ()).is_none() {
-			self.messages.push(MessageEntry {
-				message_hash,
-				topic,
-				message,
-				sender,
-			});
+		if self.known_messages.contains(&message_hash) {
+			return;
+		}
+
+		// Check the room left in the topic before marking the message as known, so that copies
+		// received later on from other peers aren't treated as duplicates of a message we never
+		// stored.
+		if sender.is_some() {
+			let received = self.received_per_topic.entry(topic).or_insert((0, 0));
+			if received.0 >= self.limits.max_messages_per_topic ||
+				received.1.saturating_add(message.len()) > self.limits.max_bytes_per_topic
+			{
+				trace!(target: "gossip", "Not storing message, topic {:?} is full", topic);
+				return;
+			}
+			received.0 += 1;
+			received.1 += message.len();
 		}
+
+		self.known_messages.put(message_hash.clone(), ());
+		self.messages.push(MessageEntry {
+			message_hash,
+			topic,
+			message,
+			sender,
+		});
 	}
 
 	/// Registers a message without propagating it to any peers. The message
@@ -259,6 +354,14 @@ impl<B: BlockT> ConsensusGossip<B> {
 		let mut message_expired = self.validator.message_expired();
 		self.messages.retain(|entry| !message_expired(entry.topic, &entry.message));
 
+		let received_per_topic = &mut self.received_per_topic;
+		received_per_topic.clear();
+		for entry in self.messages.iter().filter(|entry| entry.sender.is_some()) {
+			let received = received_per_topic.entry(entry.topic).or_insert((0, 0));
+			received.0 += 1;
+			received.1 += entry.message.len();
+		}
+
 		trace!(target: "gossip", "Cleaned up {} stale messages, {} left ({} known)",
 			before - self.messages.len(),
 			self.messages.len(),
@@ -293,6 +396,20 @@ impl<B: BlockT> ConsensusGossip<B> {
 		}
 
 		for message in messages {
+			if message.len() > self.limits.max_message_size {
+				trace!(target: "gossip", "Discarded message of {} bytes from {}", message.len(), who);
+				network.report_peer(who.clone(), rep::MESSAGE_TOO_LARGE);
+				continue;
+			}
+
+			if let Some(peer) = self.peers.get_mut(&who) {
+				if !peer.note_received(&self.limits) {
+					trace!(target: "gossip", "Discarded message from rate limited peer {}", who);
+					network.report_peer(who.clone(), rep::RATE_LIMIT_EXCEEDED);
+					continue;
+				}
+			}
+
 			let message_hash = HashFor::<B>::hash(&message[..]);
 
 			if self.known_messages.contains(&message_hash) {
@@ -520,7 +637,7 @@ mod tests {
 
 		let prev_hash = H256::random();
 		let best_hash = H256::random();
-		let mut consensus = ConsensusGossip::<Block>::new(Arc::new(AllowAll), [0, 0, 0, 0]);
+		let mut consensus = ConsensusGossip::<Block>::new(Arc::new(AllowAll), [0, 0, 0, 0], Default::default());
 		let m1_hash = H256::random();
 		let m2_hash = H256::random();
 		let m1 = vec![1, 2, 3];
@@ -547,7 +664,7 @@ mod tests {
 
 	#[test]
 	fn message_stream_include_those_sent_before_asking() {
-		let mut consensus = ConsensusGossip::<Block>::new(Arc::new(AllowAll), [0, 0, 0, 0]);
+		let mut consensus = ConsensusGossip::<Block>::new(Arc::new(AllowAll), [0, 0, 0, 0], Default::default());
 
 		// Register message.
 		let message = vec![4, 5, 6];
@@ -562,7 +679,7 @@ mod tests {
 
 	#[test]
 	fn can_keep_multiple_messages_per_topic() {
-		let mut consensus = ConsensusGossip::<Block>::new(Arc::new(AllowAll), [0, 0, 0, 0]);
+		let mut consensus = ConsensusGossip::<Block>::new(Arc::new(AllowAll), [0, 0, 0, 0], Default::default());
 
 		let topic = [1; 32].into();
 		let msg_a = vec![1, 2, 3];
@@ -576,7 +693,7 @@ mod tests {
 
 	#[test]
 	fn peer_is_removed_on_disconnect() {
-		let mut consensus = ConsensusGossip::<Block>::new(Arc::new(AllowAll), [0, 0, 0, 0]);
+		let mut consensus = ConsensusGossip::<Block>::new(Arc::new(AllowAll), [0, 0, 0, 0], Default::default());
 
 		let mut network = NoOpNetwork::default();
 
@@ -593,6 +710,7 @@ mod tests {
 		let to_forward = ConsensusGossip::<Block>::new(
 			Arc::new(DiscardAll),
 			[0, 0, 0, 0],
+			Default::default(),
 		).on_incoming(
 			&mut NoOpNetwork::default(),
 			PeerId::random(),
@@ -613,6 +731,7 @@ mod tests {
 		let to_forward = ConsensusGossip::<Block>::new(
 			Arc::new(AllowAll),
 			[0, 0, 0, 0],
+			Default::default(),
 		).on_incoming(
 			&mut network,
 			// Unregistered peer.
@@ -626,4 +745,129 @@ mod tests {
 			to_forward,
 		);
 	}
+
+	#[test]
+	fn on_incoming_discards_oversized_messages() {
+		let mut network = NoOpNetwork::default();
+		let remote = PeerId::random();
+		let limits = GossipLimits { max_message_size: 3, ..Default::default() };
+		let mut consensus = ConsensusGossip::<Block>::new(Arc::new(AllowAll), [0, 0, 0, 0], limits);
+		consensus.new_peer(&mut network, remote.clone(), ObservedRole::Full);
+
+		let to_forward = consensus.on_incoming(
+			&mut network,
+			remote.clone(),
+			vec![vec![1, 2, 3, 4], vec![1, 2, 3]],
+		);
+
+		assert_eq!(to_forward.len(), 1);
+		assert_eq!(to_forward[0].1.message, vec![1, 2, 3]);
+		assert_eq!(
+			network.inner.lock().unwrap().peer_reports,
+			vec![(remote.clone(), rep::MESSAGE_TOO_LARGE), (remote, rep::GOSSIP_SUCCESS)],
+		);
+	}
+
+	#[test]
+	fn on_incoming_rate_limits_peers() {
+		let mut network = NoOpNetwork::default();
+		let remote = PeerId::random();
+		let other = PeerId::random();
+		let limits = GossipLimits { max_messages_per_peer: 2, ..Default::default() };
+		let mut consensus = ConsensusGossip::<Block>::new(Arc::new(AllowAll), [0, 0, 0, 0], limits);
+		consensus.new_peer(&mut network, remote.clone(), ObservedRole::Full);
+		consensus.new_peer(&mut network, other.clone(), ObservedRole::Full);
+
+		let to_forward = consensus.on_incoming(
+			&mut network,
+			remote.clone(),
+			vec![vec![1], vec![2], vec![3]],
+		);
+		assert_eq!(to_forward.len(), 2);
+		assert_eq!(
+			network.inner.lock().unwrap().peer_reports.last(),
+			Some(&(remote, rep::RATE_LIMIT_EXCEEDED)),
+		);
+
+		// Other peers are not affected.
+		let to_forward = consensus.on_incoming(&mut network, other, vec![vec![3]]);
+		assert_eq!(to_forward.len(), 1);
+	}
+
+	#[test]
+	fn caps_messages_stored_per_topic() {
+		let mut network = NoOpNetwork::default();
+		let remote = PeerId::random();
+		let limits = GossipLimits { max_messages_per_topic: 2, ..Default::default() };
+		let mut consensus = ConsensusGossip::<Block>::new(Arc::new(AllowAll), [0, 0, 0, 0], limits);
+		consensus.new_peer(&mut network, remote.clone(), ObservedRole::Full);
+
+		let to_forward = consensus.on_incoming(
+			&mut network,
+			remote,
+			vec![vec![1], vec![2], vec![3]],
+		);
+
+		// All messages are forwarded to the upper layers, but only two are kept.
+		assert_eq!(to_forward.len(), 3);
+		assert_eq!(consensus.messages_for(H256::default()).count(), 2);
+
+		// Local messages are always stored.
+		consensus.register_message(H256::default(), vec![4]);
+		assert_eq!(consensus.messages_for(H256::default()).count(), 3);
+
+		// Room is made again once messages are garbage collected.
+		consensus.messages.retain(|entry| entry.sender.is_none());
+		consensus.collect_garbage();
+		assert_eq!(consensus.received_per_topic.get(&H256::default()), None);
+	}
+
+	#[test]
+	fn caps_bytes_stored_per_topic() {
+		let mut network = NoOpNetwork::default();
+		let remote = PeerId::random();
+		let limits = GossipLimits { max_bytes_per_topic: 5, ..Default::default() };
+		let mut consensus = ConsensusGossip::<Block>::new(Arc::new(AllowAll), [0, 0, 0, 0], limits);
+		consensus.new_peer(&mut network, remote.clone(), ObservedRole::Full);
+
+		let to_forward = consensus.on_incoming(
+			&mut network,
+			remote,
+			vec![vec![1, 1], vec![2, 2], vec![3, 3], vec![4]],
+		);
+
+		// The third message would exceed the budget of the topic, the fourth one still fits.
+		assert_eq!(to_forward.len(), 4);
+		assert_eq!(
+			consensus.messages_for(H256::default()).map(|n| n.message).collect::<Vec<_>>(),
+			vec![vec![1, 1], vec![2, 2], vec![4]],
+		);
+		assert_eq!(consensus.received_per_topic.get(&H256::default()), Some(&(3, 5)));
+	}
+
+	#[test]
+	fn messages_dropped_on_full_topic_are_not_known() {
+		let mut network = NoOpNetwork::default();
+		let remote = PeerId::random();
+		let other = PeerId::random();
+		let limits = GossipLimits { max_messages_per_topic: 1, ..Default::default() };
+		let mut consensus = ConsensusGossip::<Block>::new(Arc::new(AllowAll), [0, 0, 0, 0], limits);
+		consensus.new_peer(&mut network, remote.clone(), ObservedRole::Full);
+		consensus.new_peer(&mut network, other.clone(), ObservedRole::Full);
+
+		consensus.on_incoming(&mut network, remote.clone(), vec![vec![1], vec![2]]);
+		assert!(!consensus.known_messages.contains(&HashFor::<Block>::hash(&[2])));
+
+		// Another peer sending us the dropped message isn't punished for it.
+		let to_forward = consensus.on_incoming(&mut network, other.clone(), vec![vec![2]]);
+		assert_eq!(to_forward.len(), 1);
+		assert_eq!(
+			network.inner.lock().unwrap().peer_reports,
+			vec![
+				(remote.clone(), rep::GOSSIP_SUCCESS),
+				(remote, rep::GOSSIP_SUCCESS),
+				(other, rep::GOSSIP_SUCCESS),
+			],
+		);
+	}
 }
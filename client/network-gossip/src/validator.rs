@@ -16,7 +16,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use sc_network::{ObservedRole, PeerId};
+use sc_network::{ObservedRole, PeerId, ReputationChange};
 use sp_runtime::traits::Block as BlockT;
 
 /// Validates consensus messages.
@@ -58,6 +58,8 @@ pub trait ValidatorContext<B: BlockT> {
 	fn send_message(&mut self, who: &PeerId, message: Vec<u8>);
 	/// Send all messages with given topic to a peer.
 	fn send_topic(&mut self, who: &PeerId, topic: B::Hash, force: bool);
+	/// Adjust the reputation of a peer, e.g. because it sent an invalid message.
+	fn report_peer(&mut self, who: &PeerId, reputation: ReputationChange);
 }
 
 /// The reason for sending out the message.
@@ -15,14 +15,15 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{
-	config::{ProtocolId, Role}, block_requests, light_client_handler, finality_requests,
-	peer_info, discovery::{DiscoveryBehaviour, DiscoveryConfig, DiscoveryOut},
+	config::{ProtocolId, Role}, block_request_handler, light_client_handler, finality_requests,
+	peer_info, request_responses, discovery::{DiscoveryBehaviour, DiscoveryConfig, DiscoveryOut},
 	protocol::{message::{self, Roles}, CustomMessageOutcome, NotificationsSink, Protocol},
 	ObservedRole, DhtEvent, ExHashT,
 };
 
 use bytes::Bytes;
 use codec::Encode as _;
+use futures::{channel::oneshot, prelude::*};
 use libp2p::NetworkBehaviour;
 use libp2p::core::{Multiaddr, PeerId, PublicKey};
 use libp2p::identify::IdentifyInfo;
@@ -33,7 +34,7 @@ use sp_consensus::{BlockOrigin, import_queue::{IncomingBlock, Origin}};
 use sp_runtime::{traits::{Block as BlockT, NumberFor}, ConsensusEngineId, Justification};
 use std::{
 	borrow::Cow,
-	collections::{HashSet, VecDeque},
+	collections::{HashMap, HashSet, VecDeque},
 	iter,
 	task::{Context, Poll},
 	time::Duration,
@@ -50,8 +51,8 @@ pub struct Behaviour<B: BlockT, H: ExHashT> {
 	peer_info: peer_info::PeerInfoBehaviour,
 	/// Discovers nodes of the network.
 	discovery: DiscoveryBehaviour,
-	/// Block request handling.
-	block_requests: block_requests::BlockRequests<B>,
+	/// Generic request-response protocols.
+	request_responses: request_responses::RequestResponsesBehaviour,
 	/// Finality proof request handling.
 	finality_proof_requests: finality_requests::FinalityProofRequests<B>,
 	/// Light client request handling.
@@ -64,6 +65,21 @@ pub struct Behaviour<B: BlockT, H: ExHashT> {
 	/// Role of our local node, as originally passed from the configuration.
 	#[behaviour(ignore)]
 	role: Role,
+
+	/// Name of the request-response protocol used to send block requests.
+	#[behaviour(ignore)]
+	block_request_protocol_name: String,
+
+	/// Block requests emitted on behalf of the sync state machine and waiting for a response.
+	/// There is at most one request per peer.
+	#[behaviour(ignore)]
+	pending_block_requests: HashMap<PeerId, PendingBlockRequest<B>>,
+}
+
+/// Block request sent through the request-response protocol, awaiting its response.
+struct PendingBlockRequest<B: BlockT> {
+	request: message::BlockRequest<B>,
+	response: oneshot::Receiver<Result<Vec<u8>, request_responses::RequestFailure>>,
 }
 
 /// Event generated by `Behaviour`.
@@ -83,6 +99,10 @@ pub enum BehaviourOut<B: BlockT> {
 		protocol: Vec<u8>,
 		/// Time it took to build the response.
 		build_time: Duration,
+		/// Size in bytes of the request.
+		request_size: usize,
+		/// Size in bytes of the response.
+		response_size: usize,
 	},
 	/// Started a new request with the given node.
 	RequestStarted {
@@ -98,6 +118,10 @@ pub enum BehaviourOut<B: BlockT> {
 		protocol: Vec<u8>,
 		/// How long before the response came or the request got cancelled.
 		request_duration: Duration,
+		/// Size in bytes of the request.
+		request_size: usize,
+		/// Size in bytes of the response, or 0 if no response came.
+		response_size: usize,
 	},
 
 	/// Opened a substream with the given node with the given notifications protocol.
@@ -157,21 +181,27 @@ impl<B: BlockT, H: ExHashT> Behaviour<B, H> {
 		role: Role,
 		user_agent: String,
 		local_public_key: PublicKey,
-		block_requests: block_requests::BlockRequests<B>,
 		finality_proof_requests: finality_requests::FinalityProofRequests<B>,
 		light_client_handler: light_client_handler::LightClientHandler<B>,
 		disco_config: DiscoveryConfig,
-	) -> Self {
-		Behaviour {
+		block_request_protocol_config: request_responses::ProtocolConfig,
+		request_response_protocols: Vec<request_responses::ProtocolConfig>,
+	) -> Result<Self, request_responses::RegisterError> {
+		let block_request_protocol_name = block_request_protocol_config.name.to_string();
+		Ok(Behaviour {
 			substrate,
 			peer_info: peer_info::PeerInfoBehaviour::new(user_agent, local_public_key),
 			discovery: disco_config.finish(),
-			block_requests,
+			request_responses: request_responses::RequestResponsesBehaviour::new(
+				iter::once(block_request_protocol_config).chain(request_response_protocols)
+			)?,
 			finality_proof_requests,
 			light_client_handler,
 			events: VecDeque::new(),
 			role,
-		}
+			block_request_protocol_name,
+			pending_block_requests: HashMap::new(),
+		})
 	}
 
 	/// Returns the list of nodes that we know exist in the network.
@@ -208,6 +238,36 @@ impl<B: BlockT, H: ExHashT> Behaviour<B, H> {
 		self.peer_info.node(peer_id)
 	}
 
+	/// Returns the remote address of each of our connections, along with the peer it is
+	/// established with.
+	pub fn connected_addresses(&self) -> impl Iterator<Item = (&PeerId, &Multiaddr)> {
+		self.peer_info.connected_addresses()
+	}
+
+	/// Initiates sending a request on a request-response protocol.
+	///
+	/// The outcome is reported on `pending_response`.
+	pub fn send_request(
+		&mut self,
+		target: &PeerId,
+		protocol: &str,
+		request: Vec<u8>,
+		pending_response: oneshot::Sender<Result<Vec<u8>, request_responses::RequestFailure>>,
+	) {
+		self.request_responses.send_request(target, protocol, request, pending_response)
+	}
+
+	/// Registers a new request-response protocol.
+	///
+	/// You are very strongly encouraged to call this method very early on. Any connection open
+	/// will retain the protocols that were registered then, and not any new one.
+	pub fn register_request_response_protocol(
+		&mut self,
+		config: request_responses::ProtocolConfig,
+	) -> Result<(), request_responses::RegisterError> {
+		self.request_responses.register_protocol(config)
+	}
+
 	/// Registers a new notifications protocol.
 	///
 	/// Please call `event_stream` before registering a protocol, otherwise you may miss events
@@ -296,27 +356,27 @@ Behaviour<B, H> {
 			CustomMessageOutcome::FinalityProofImport(origin, hash, nb, proof) =>
 				self.events.push_back(BehaviourOut::FinalityProofImport(origin, hash, nb, proof)),
 			CustomMessageOutcome::BlockRequest { target, request } => {
-				match self.block_requests.send_request(&target, request) {
-					block_requests::SendRequestOutcome::Ok => {
-						self.events.push_back(BehaviourOut::RequestStarted {
-							peer: target,
-							protocol: self.block_requests.protocol_name().to_vec(),
-						});
-					},
-					block_requests::SendRequestOutcome::Replaced { request_duration, .. } => {
-						self.events.push_back(BehaviourOut::RequestFinished {
-							peer: target.clone(),
-							protocol: self.block_requests.protocol_name().to_vec(),
-							request_duration,
-						});
-						self.events.push_back(BehaviourOut::RequestStarted {
-							peer: target,
-							protocol: self.block_requests.protocol_name().to_vec(),
-						});
+				let buf = match block_request_handler::encode_block_request(&request) {
+					Ok(buf) => buf,
+					Err(err) => {
+						log::warn!(
+							target: "sync",
+							"Failed to encode block request {:?}: {:?}",
+							request,
+							err
+						);
+						return;
 					}
-					block_requests::SendRequestOutcome::NotConnected |
-					block_requests::SendRequestOutcome::EncodeError(_) => {},
-				}
+				};
+
+				// Any previous request to the same peer is replaced. Dropping its receiver
+				// marks it as obsolete.
+				let (tx, rx) = oneshot::channel();
+				self.request_responses.send_request(&target, &self.block_request_protocol_name, buf, tx);
+				self.pending_block_requests.insert(target, PendingBlockRequest {
+					request,
+					response: rx,
+				});
 			},
 			CustomMessageOutcome::FinalityProofRequest { target, block_hash, request } => {
 				self.finality_proof_requests.send_request(&target, block_hash, request);
@@ -358,35 +418,37 @@ Behaviour<B, H> {
 	}
 }
 
-impl<B: BlockT, H: ExHashT> NetworkBehaviourEventProcess<block_requests::Event<B>> for Behaviour<B, H> {
-	fn inject_event(&mut self, event: block_requests::Event<B>) {
+impl<B: BlockT, H: ExHashT> NetworkBehaviourEventProcess<request_responses::Event> for Behaviour<B, H> {
+	fn inject_event(&mut self, event: request_responses::Event) {
 		match event {
-			block_requests::Event::AnsweredRequest { peer, total_handling_time } => {
+			request_responses::Event::InboundRequest {
+				peer, protocol, result: Ok(build_time), request_size, response_size,
+			} => {
 				self.events.push_back(BehaviourOut::AnsweredRequest {
 					peer,
-					protocol: self.block_requests.protocol_name().to_vec(),
-					build_time: total_handling_time,
+					protocol: protocol.as_bytes().to_vec(),
+					build_time,
+					request_size,
+					response_size,
 				});
-			},
-			block_requests::Event::Response { peer, original_request: _, response, request_duration } => {
-				self.events.push_back(BehaviourOut::RequestFinished {
-					peer: peer.clone(),
-					protocol: self.block_requests.protocol_name().to_vec(),
-					request_duration,
+			}
+			request_responses::Event::InboundRequest { result: Err(_), .. } => {}
+			request_responses::Event::RequestStarted { peer, protocol } => {
+				self.events.push_back(BehaviourOut::RequestStarted {
+					peer,
+					protocol: protocol.as_bytes().to_vec(),
 				});
-				let ev = self.substrate.on_block_response(peer, response);
-				self.inject_event(ev);
 			}
-			block_requests::Event::RequestCancelled { peer, request_duration, .. } |
-			block_requests::Event::RequestTimeout { peer, request_duration, .. } => {
-				// There doesn't exist any mechanism to report cancellations or timeouts yet, so
-				// we process them by disconnecting the node.
+			request_responses::Event::RequestFinished {
+				peer, protocol, duration, request_size, response_size, ..
+			} => {
 				self.events.push_back(BehaviourOut::RequestFinished {
-					peer: peer.clone(),
-					protocol: self.block_requests.protocol_name().to_vec(),
-					request_duration,
+					peer,
+					protocol: protocol.as_bytes().to_vec(),
+					request_duration: duration,
+					request_size,
+					response_size,
 				});
-				self.substrate.on_block_request_failed(&peer);
 			}
 		}
 	}
@@ -477,7 +539,51 @@ impl<B: BlockT, H: ExHashT> NetworkBehaviourEventProcess<DiscoveryOut>
 }
 
 impl<B: BlockT, H: ExHashT> Behaviour<B, H> {
-	fn poll<TEv>(&mut self, _: &mut Context, _: &mut impl PollParameters) -> Poll<NetworkBehaviourAction<TEv, BehaviourOut<B>>> {
+	fn poll<TEv>(&mut self, cx: &mut Context, _: &mut impl PollParameters) -> Poll<NetworkBehaviourAction<TEv, BehaviourOut<B>>> {
+		let finished = self.pending_block_requests.iter_mut()
+			.filter_map(|(peer, pending)| match pending.response.poll_unpin(cx) {
+				Poll::Ready(result) => Some((peer.clone(), result)),
+				Poll::Pending => None,
+			})
+			.collect::<Vec<_>>();
+
+		for (peer, result) in finished {
+			let request = match self.pending_block_requests.remove(&peer) {
+				Some(pending) => pending.request,
+				None => continue,
+			};
+
+			match result {
+				Ok(Ok(response)) => match block_request_handler::decode_block_response(&request, &response) {
+					Ok(Some((response, pruned_body))) => {
+						if let Some(number) = pruned_body {
+							self.substrate.on_block_body_pruned(&peer, number);
+						}
+						let ev = self.substrate.on_block_response(peer, response);
+						self.inject_event(ev);
+					}
+					Ok(None) => {
+						debug!(target: "sync", "Block request {} refused by peer {}", request.id, peer);
+						self.substrate.on_block_request_refused(&peer, request.id);
+					}
+					Err(err) => {
+						debug!(target: "sync", "Failed to decode block response from peer {}: {}", peer, err);
+						self.substrate.on_block_request_failed(&peer);
+					}
+				},
+				Ok(Err(request_responses::RequestFailure::NotConnected)) => {}
+				Ok(Err(err)) => {
+					// There doesn't exist any mechanism to report cancellations or timeouts yet,
+					// so we process them by disconnecting the node.
+					debug!(target: "sync", "Block request to peer {} failed: {}", peer, err);
+					self.substrate.on_block_request_failed(&peer);
+				}
+				Err(oneshot::Canceled) => {
+					self.substrate.on_block_request_failed(&peer);
+				}
+			}
+		}
+
 		if let Some(event) = self.events.pop_front() {
 			return Poll::Ready(NetworkBehaviourAction::GenerateEvent(event))
 		}
@@ -0,0 +1,874 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Helper for handling (i.e. answering) block requests from a remote peer via the
+//! [`crate::request_responses::RequestResponsesBehaviour`].
+//!
+//! Requests and responses are encoded as protocol buffers (cf. `api.v1.proto`).
+//!
+//! Each peer is given an allowance of requests and of response bytes per time window, and may
+//! only have a limited number of requests waiting to be answered. Peers exceeding these limits,
+//! sending malformed requests or repeating the same request over and over see their requests
+//! refused with a response that has the `refused` flag set, and their reputation lowered.
+
+use codec::{Encode, Decode};
+use crate::{
+	chain::Client,
+	config::ProtocolId,
+	protocol::message::{self, BlockAttributes},
+	request_responses::{IncomingRequest, ProtocolConfig},
+	schema,
+};
+use futures::{channel::{mpsc, oneshot}, prelude::*};
+use libp2p::PeerId;
+use lru::LruCache;
+use prometheus_endpoint::{
+	register, exponential_buckets, CounterVec, Histogram, HistogramOpts, Opts, PrometheusError,
+	Registry, U64,
+};
+use prost::Message;
+use sc_peerset::PeersetHandle;
+use sp_runtime::{generic::BlockId, traits::{Block as BlockT, Header, NumberFor, One, Zero}};
+use std::{cmp::min, collections::HashMap, io, sync::Arc, time::Duration};
+use wasm_timer::Instant;
+
+/// Maximum number of incoming block requests queued before new ones are refused.
+const MAX_QUEUED_REQUESTS: usize = 20;
+/// Maximum number of peers whose allowance is tracked at the same time.
+const MAX_TRACKED_PEERS: usize = 1024;
+/// Maximum number of recent requests remembered in order to detect repeated requests.
+const MAX_SEEN_REQUESTS: usize = 1024;
+/// Number of times a peer can send the exact same request before getting refused.
+const MAX_SAME_REQUESTS: usize = 2;
+/// Duration after which a repeated request is counted again from scratch.
+const SEEN_REQUEST_EXPIRY: Duration = Duration::from_secs(30);
+
+/// Default maximum number of requests a single peer can send per window.
+pub const DEFAULT_MAX_REQUESTS_PER_WINDOW: u32 = 1024;
+/// Default maximum number of response bytes sent to a single peer per window.
+pub const DEFAULT_MAX_BYTES_PER_WINDOW: u64 = 1024 * 1024 * 1024;
+
+mod rep {
+	use sc_peerset::ReputationChange as Rep;
+	/// Reputation change when a peer sends us a block request that we failed to decode.
+	pub const BAD_REQUEST: Rep = Rep::new(-(1 << 12), "Bad block request");
+	/// Reputation change when a peer exceeds its allowance of block requests.
+	pub const EXCESSIVE_REQUESTS: Rep = Rep::new(-(1 << 10), "Excessive block requests");
+	/// Reputation change when a peer keeps sending us the same block request.
+	pub const SAME_REQUEST: Rep = Rep::new(-(1 << 8), "Same block request multiple times");
+}
+
+// Type alias for convenience.
+pub type Error = Box<dyn std::error::Error + 'static>;
+
+/// Configuration options for the [`BlockRequestHandler`].
+#[derive(Debug, Clone)]
+pub struct Config {
+	max_block_data_response: u32,
+	max_response_bytes: usize,
+	max_in_flight_per_peer: usize,
+	max_requests_per_window: u32,
+	max_bytes_per_window: u64,
+	window: Duration,
+}
+
+impl Default for Config {
+	/// Create a fresh configuration with the following options:
+	///
+	/// - max. block data in response = 128
+	/// - max. response size = 8 MiB
+	/// - max. requests waiting to be answered per peer = 4
+	/// - max. requests per peer per window = 1024
+	/// - max. response bytes per peer per window = 1 GiB
+	/// - window = 10s
+	fn default() -> Self {
+		Config {
+			max_block_data_response: 128,
+			max_response_bytes: 8 * 1024 * 1024,
+			max_in_flight_per_peer: 4,
+			max_requests_per_window: DEFAULT_MAX_REQUESTS_PER_WINDOW,
+			max_bytes_per_window: DEFAULT_MAX_BYTES_PER_WINDOW,
+			window: Duration::from_secs(10),
+		}
+	}
+}
+
+impl Config {
+	/// Limit the max. number of block data in a response.
+	pub fn set_max_block_data_response(&mut self, v: u32) -> &mut Self {
+		self.max_block_data_response = v;
+		self
+	}
+
+	/// Set the maximum total bytes of block data (headers, bodies and justifications) that are
+	/// sent in a response. Note that at least one block is always sent regardless of the limit.
+	pub fn set_max_response_bytes(&mut self, v: usize) -> &mut Self {
+		self.max_response_bytes = v;
+		self
+	}
+
+	/// Limit the number of requests of a single peer that can wait to be answered.
+	pub fn set_max_in_flight_per_peer(&mut self, v: usize) -> &mut Self {
+		self.max_in_flight_per_peer = v;
+		self
+	}
+
+	/// Limit the number of requests a single peer can send per window.
+	pub fn set_max_requests_per_window(&mut self, v: u32) -> &mut Self {
+		self.max_requests_per_window = v;
+		self
+	}
+
+	/// Limit the number of response bytes sent to a single peer per window.
+	pub fn set_max_bytes_per_window(&mut self, v: u64) -> &mut Self {
+		self.max_bytes_per_window = v;
+		self
+	}
+
+	/// Set the duration of the window the per-peer allowances apply to.
+	pub fn set_window(&mut self, v: Duration) -> &mut Self {
+		self.window = v;
+		self
+	}
+}
+
+/// Returns the name of the block request protocol on the wire (e.g. `/foo/sync/2`).
+pub fn protocol_name(protocol_id: &ProtocolId) -> String {
+	format!("/{}/sync/2", String::from_utf8_lossy(protocol_id.as_bytes()))
+}
+
+/// Generates a [`ProtocolConfig`] for the block request protocol, refusing incoming requests.
+pub fn generate_protocol_config(protocol_id: &ProtocolId) -> ProtocolConfig {
+	ProtocolConfig {
+		name: protocol_name(protocol_id).into(),
+		max_request_size: 1024 * 1024,
+		max_response_size: 16 * 1024 * 1024,
+		request_timeout: Duration::from_secs(40),
+		inbound_queue: None,
+	}
+}
+
+/// Requests and response bytes served to a peer during the current window.
+struct PeerAllowance {
+	window_start: Instant,
+	requests: u32,
+	bytes: u64,
+}
+
+/// Number of times a request has been received since it was first seen.
+struct SeenRequest {
+	first_seen: Instant,
+	times: usize,
+}
+
+/// Identifies a request in order to detect peers repeating the same request.
+#[derive(Hash, PartialEq, Eq)]
+struct SeenRequestKey {
+	peer: PeerId,
+	/// Encoded starting block, and whether it is a hash (`true`) or a number (`false`).
+	from_block: Option<(bool, Vec<u8>)>,
+	max_blocks: u32,
+	direction: i32,
+	fields: u32,
+}
+
+/// Outcome of handling a single request, used as label of the metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+	Served,
+	BadRequest,
+	TooManyInFlight,
+	RateLimited,
+	SameRequest,
+	Failed,
+}
+
+impl Outcome {
+	fn as_str(&self) -> &'static str {
+		match self {
+			Outcome::Served => "served",
+			Outcome::BadRequest => "bad-request",
+			Outcome::TooManyInFlight => "too-many-in-flight",
+			Outcome::RateLimited => "rate-limited",
+			Outcome::SameRequest => "same-request",
+			Outcome::Failed => "failed",
+		}
+	}
+}
+
+/// Returns the kind of data asked for by a request, used as label of the metrics.
+fn request_kind(attributes: BlockAttributes) -> &'static str {
+	if attributes.contains(BlockAttributes::BODY) {
+		"body"
+	} else if attributes.contains(BlockAttributes::JUSTIFICATION) {
+		"justification"
+	} else {
+		"header"
+	}
+}
+
+struct Metrics {
+	requests: CounterVec<U64>,
+	response_bytes: Histogram,
+}
+
+impl Metrics {
+	fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Metrics {
+			requests: register(CounterVec::new(
+				Opts::new(
+					"sub_libp2p_block_requests_handled_total",
+					"Number of incoming block requests, by kind of data requested and outcome"
+				),
+				&["kind", "outcome"]
+			)?, registry)?,
+			response_bytes: register(Histogram::with_opts(
+				HistogramOpts {
+					common_opts: Opts::new(
+						"sub_libp2p_block_requests_response_bytes",
+						"Size in bytes of the responses sent to block requests"
+					),
+					buckets: exponential_buckets(64.0, 4.0, 12)
+						.expect("parameters are always valid values; qed"),
+				}
+			)?, registry)?,
+		})
+	}
+}
+
+/// Handler for incoming block requests from a remote peer.
+pub struct BlockRequestHandler<B: BlockT> {
+	client: Arc<dyn Client<B>>,
+	request_receiver: mpsc::Receiver<IncomingRequest>,
+	config: Config,
+	peerset: PeersetHandle,
+	/// Allowance of the peers that recently sent us requests.
+	peers: LruCache<PeerId, PeerAllowance>,
+	/// Number of times each recent request has been received.
+	seen_requests: LruCache<SeenRequestKey, SeenRequest>,
+	metrics: Option<Metrics>,
+}
+
+impl<B: BlockT> BlockRequestHandler<B> {
+	/// Create a new [`BlockRequestHandler`], along with the configuration of the protocol it
+	/// answers, to be registered on the network.
+	pub fn new(
+		config: Config,
+		protocol_id: &ProtocolId,
+		client: Arc<dyn Client<B>>,
+		peerset: PeersetHandle,
+		registry: Option<&Registry>,
+	) -> Result<(Self, ProtocolConfig), PrometheusError> {
+		let (tx, request_receiver) = mpsc::channel(MAX_QUEUED_REQUESTS);
+
+		let mut protocol_config = generate_protocol_config(protocol_id);
+		protocol_config.inbound_queue = Some(tx);
+
+		let metrics = registry.map(Metrics::register).transpose()?;
+
+		let handler = BlockRequestHandler {
+			client,
+			request_receiver,
+			config,
+			peerset,
+			peers: LruCache::new(MAX_TRACKED_PEERS),
+			seen_requests: LruCache::new(MAX_SEEN_REQUESTS),
+			metrics,
+		};
+
+		Ok((handler, protocol_config))
+	}
+
+	/// Run [`BlockRequestHandler`].
+	pub async fn run(mut self) {
+		while let Some(request) = self.request_receiver.next().await {
+			// Gather the requests that are already queued, in order to know how many requests
+			// of each peer are waiting to be answered.
+			let mut requests = vec![request];
+			while let Ok(Some(request)) = self.request_receiver.try_next() {
+				requests.push(request);
+			}
+
+			let mut in_flight = HashMap::<PeerId, usize>::new();
+			for request in requests {
+				let count = in_flight.entry(request.peer.clone()).or_default();
+				*count += 1;
+				if *count > self.config.max_in_flight_per_peer {
+					log::debug!(
+						target: "sync",
+						"Refusing block request from {}: too many requests in flight",
+						request.peer
+					);
+					self.peerset.report_peer(request.peer.clone(), rep::EXCESSIVE_REQUESTS);
+					self.report_outcome("unknown", Outcome::TooManyInFlight);
+					refuse(request.pending_response);
+					continue;
+				}
+
+				self.on_request(request);
+			}
+		}
+	}
+
+	fn report_outcome(&self, kind: &str, outcome: Outcome) {
+		if let Some(metrics) = &self.metrics {
+			metrics.requests.with_label_values(&[kind, outcome.as_str()]).inc();
+		}
+	}
+
+	/// Checks the limits of the peer, then answers the request. Requests that aren't served are
+	/// answered with a refusal.
+	fn on_request(&mut self, IncomingRequest { peer, payload, pending_response }: IncomingRequest) {
+		let request = match schema::v1::BlockRequest::decode(&payload[..]) {
+			Ok(request) => request,
+			Err(err) => {
+				log::debug!(target: "sync", "Failed to decode block request from {}: {}", peer, err);
+				self.peerset.report_peer(peer, rep::BAD_REQUEST);
+				self.report_outcome("unknown", Outcome::BadRequest);
+				refuse(pending_response);
+				return;
+			}
+		};
+
+		let attributes = match BlockAttributes::from_be_u32(request.fields) {
+			Ok(attributes) => attributes,
+			Err(err) => {
+				log::debug!(target: "sync", "Invalid block request from {}: {}", peer, err);
+				self.peerset.report_peer(peer, rep::BAD_REQUEST);
+				self.report_outcome("unknown", Outcome::BadRequest);
+				refuse(pending_response);
+				return;
+			}
+		};
+		let kind = request_kind(attributes);
+
+		let now = Instant::now();
+		let window = self.config.window;
+		if self.peers.get_mut(&peer).is_none() {
+			self.peers.put(peer.clone(), PeerAllowance { window_start: now, requests: 0, bytes: 0 });
+		}
+		let allowance = self.peers.get_mut(&peer).expect("Inserted above if missing; qed");
+		if now.duration_since(allowance.window_start) >= window {
+			*allowance = PeerAllowance { window_start: now, requests: 0, bytes: 0 };
+		}
+		if allowance.requests >= self.config.max_requests_per_window
+			|| allowance.bytes >= self.config.max_bytes_per_window
+		{
+			log::debug!(
+				target: "sync",
+				"Refusing block request from {}: {} requests and {} bytes served in the current window",
+				peer, allowance.requests, allowance.bytes
+			);
+			self.peerset.report_peer(peer, rep::EXCESSIVE_REQUESTS);
+			self.report_outcome(kind, Outcome::RateLimited);
+			refuse(pending_response);
+			return;
+		}
+		allowance.requests += 1;
+		let max_response_bytes = min(
+			self.config.max_response_bytes as u64,
+			self.config.max_bytes_per_window - allowance.bytes,
+		) as usize;
+
+		let key = SeenRequestKey {
+			peer: peer.clone(),
+			from_block: request.from_block.as_ref().map(|from_block| match from_block {
+				schema::v1::block_request::FromBlock::Hash(h) => (true, h.clone()),
+				schema::v1::block_request::FromBlock::Number(n) => (false, n.clone()),
+			}),
+			max_blocks: request.max_blocks,
+			direction: request.direction,
+			fields: request.fields,
+		};
+		match self.seen_requests.get_mut(&key) {
+			Some(seen) if now.duration_since(seen.first_seen) < SEEN_REQUEST_EXPIRY => {
+				seen.times += 1;
+				if seen.times > MAX_SAME_REQUESTS {
+					log::debug!(
+						target: "sync",
+						"Refusing block request from {}: same request sent {} times",
+						peer, seen.times
+					);
+					self.peerset.report_peer(peer, rep::SAME_REQUEST);
+					self.report_outcome(kind, Outcome::SameRequest);
+					refuse(pending_response);
+					return;
+				}
+			}
+			_ => {
+				self.seen_requests.put(key, SeenRequest { first_seen: now, times: 1 });
+			}
+		}
+
+		match self.handle_request(&peer, &request, attributes, max_response_bytes) {
+			Ok(response) => {
+				if let Some(allowance) = self.peers.get_mut(&peer) {
+					allowance.bytes += response.len() as u64;
+				}
+				if let Some(metrics) = &self.metrics {
+					metrics.response_bytes.observe(response.len() as f64);
+				}
+				self.report_outcome(kind, Outcome::Served);
+				let _ = pending_response.send(response);
+			}
+			Err(e) => {
+				log::debug!(
+					target: "sync",
+					"Error handling block request from peer {}: {}", peer, e
+				);
+				self.report_outcome(kind, Outcome::Failed);
+				refuse(pending_response);
+			}
+		}
+	}
+
+	/// Builds the encoded response to a block request, of at most `max_response_bytes` of block
+	/// data unless a single block exceeds it.
+	fn handle_request(
+		&self,
+		peer: &PeerId,
+		request: &schema::v1::BlockRequest,
+		attributes: BlockAttributes,
+		max_response_bytes: usize,
+	) -> Result<Vec<u8>, Error> {
+		log::trace!(
+			target: "sync",
+			"Block request from peer {}: from block {:?} to block {:?}, max blocks {:?}",
+			peer,
+			request.from_block,
+			request.to_block,
+			request.max_blocks);
+
+		let from_block_id =
+			match request.from_block {
+				Some(schema::v1::block_request::FromBlock::Hash(ref h)) => {
+					let h = Decode::decode(&mut h.as_ref())?;
+					BlockId::<B>::Hash(h)
+				}
+				Some(schema::v1::block_request::FromBlock::Number(ref n)) => {
+					let n = Decode::decode(&mut n.as_ref())?;
+					BlockId::<B>::Number(n)
+				}
+				None => {
+					let msg = "missing `BlockRequest::from_block` field";
+					return Err(io::Error::new(io::ErrorKind::Other, msg).into())
+				}
+			};
+
+		let max_blocks =
+			if request.max_blocks == 0 {
+				self.config.max_block_data_response
+			} else {
+				min(request.max_blocks, self.config.max_block_data_response)
+			};
+
+		let direction =
+			if request.direction == schema::v1::Direction::Ascending as i32 {
+				schema::v1::Direction::Ascending
+			} else if request.direction == schema::v1::Direction::Descending as i32 {
+				schema::v1::Direction::Descending
+			} else {
+				let msg = format!("invalid `BlockRequest::direction` value: {}", request.direction);
+				return Err(io::Error::new(io::ErrorKind::Other, msg).into())
+			};
+
+		let get_header = attributes.contains(BlockAttributes::HEADER);
+		let get_body = attributes.contains(BlockAttributes::BODY);
+		let get_justification = attributes.contains(BlockAttributes::JUSTIFICATION);
+
+		let mut blocks = Vec::new();
+		let mut pruned_body = Vec::new();
+		let mut block_id = from_block_id;
+		let mut total_size = 0;
+		while let Some(header) = self.client.header(block_id).unwrap_or(None) {
+			if blocks.len() >= max_blocks as usize {
+				break
+			}
+
+			let number = *header.number();
+			let hash = header.hash();
+			let parent_hash = *header.parent_hash();
+			let justification = if get_justification {
+				self.client.justification(&BlockId::Hash(hash))?
+			} else {
+				None
+			};
+			let is_empty_justification = justification.as_ref().map(|j| j.is_empty()).unwrap_or(false);
+
+			let body = if get_body {
+				match self.client.block_body(&BlockId::Hash(hash))? {
+					Some(mut extrinsics) => extrinsics.iter_mut()
+						.map(|extrinsic| extrinsic.encode())
+						.collect(),
+					None => {
+						log::trace!(target: "sync", "Body of block #{} has been pruned.", number);
+						pruned_body = number.encode();
+						break;
+					}
+				}
+			} else {
+				Vec::new()
+			};
+
+			let block_data = schema::v1::BlockData {
+				hash: hash.encode(),
+				header: if get_header {
+					header.encode()
+				} else {
+					Vec::new()
+				},
+				body,
+				receipt: Vec::new(),
+				message_queue: Vec::new(),
+				justification: justification.unwrap_or_default(),
+				is_empty_justification,
+			};
+
+			let block_size = block_data.encoded_len();
+			if !blocks.is_empty() && total_size + block_size > max_response_bytes {
+				break
+			}
+			total_size += block_size;
+			blocks.push(block_data);
+
+			match direction {
+				schema::v1::Direction::Ascending => {
+					block_id = BlockId::Number(number + One::one())
+				}
+				schema::v1::Direction::Descending => {
+					if number.is_zero() {
+						break
+					}
+					block_id = BlockId::Hash(parent_hash)
+				}
+			}
+		}
+
+		let response = schema::v1::BlockResponse { blocks, pruned_body, refused: false };
+		log::trace!(
+			target: "sync",
+			"Sending block response to peer {} with {} blocks",
+			peer, response.blocks.len()
+		);
+
+		let mut data = Vec::with_capacity(response.encoded_len());
+		response.encode(&mut data)?;
+		Ok(data)
+	}
+}
+
+/// Refuses a request, rather than letting it time out.
+///
+/// The response has the `refused` flag set, so that the requester can tell it apart from a valid
+/// response without any block and retry later instead of penalizing us.
+fn refuse(pending_response: oneshot::Sender<Vec<u8>>) {
+	let response = schema::v1::BlockResponse { blocks: Vec::new(), pruned_body: Vec::new(), refused: true };
+	let mut data = Vec::with_capacity(response.encoded_len());
+	if response.encode(&mut data).is_ok() {
+		let _ = pending_response.send(data);
+	}
+}
+
+/// Build protobuf block request message.
+pub(crate) fn build_protobuf_block_request<Hash: Encode, Number: Encode>(
+	attributes: BlockAttributes,
+	from_block: message::FromBlock<Hash, Number>,
+	to_block: Option<Hash>,
+	direction: message::Direction,
+	max_blocks: Option<u32>,
+) -> schema::v1::BlockRequest {
+	schema::v1::BlockRequest {
+		fields: attributes.to_be_u32(),
+		from_block: match from_block {
+			message::FromBlock::Hash(h) =>
+				Some(schema::v1::block_request::FromBlock::Hash(h.encode())),
+			message::FromBlock::Number(n) =>
+				Some(schema::v1::block_request::FromBlock::Number(n.encode())),
+		},
+		to_block: to_block.map(|h| h.encode()).unwrap_or_default(),
+		direction: match direction {
+			message::Direction::Ascending => schema::v1::Direction::Ascending as i32,
+			message::Direction::Descending => schema::v1::Direction::Descending as i32,
+		},
+		max_blocks: max_blocks.unwrap_or(0),
+	}
+}
+
+/// Encodes a block request into the format used on the wire.
+pub(crate) fn encode_block_request<B: BlockT>(request: &message::BlockRequest<B>) -> Result<Vec<u8>, prost::EncodeError> {
+	let protobuf_rq = build_protobuf_block_request(
+		request.fields,
+		request.from.clone(),
+		request.to.clone(),
+		request.direction,
+		request.max,
+	);
+
+	let mut buf = Vec::with_capacity(protobuf_rq.encoded_len());
+	protobuf_rq.encode(&mut buf)?;
+	Ok(buf)
+}
+
+/// Decodes the response to `original_request` received from the wire.
+///
+/// Also returns the number of the block the response stops at if the peer has pruned its body.
+/// Returns `None` if the peer refused to answer the request.
+pub(crate) fn decode_block_response<B: BlockT>(
+	original_request: &message::BlockRequest<B>,
+	response: &[u8],
+) -> Result<Option<(message::BlockResponse<B>, Option<NumberFor<B>>)>, Error> {
+	let response = schema::v1::BlockResponse::decode(response)?;
+	if response.refused {
+		return Ok(None);
+	}
+	let pruned_body = if !response.pruned_body.is_empty() {
+		Some(Decode::decode(&mut response.pruned_body.as_ref())?)
+	} else {
+		None
+	};
+
+	let blocks = response.blocks.into_iter().map(|block_data| {
+		Ok(message::BlockData::<B> {
+			hash: Decode::decode(&mut block_data.hash.as_ref())?,
+			header: if !block_data.header.is_empty() {
+				Some(Decode::decode(&mut block_data.header.as_ref())?)
+			} else {
+				None
+			},
+			body: if original_request.fields.contains(message::BlockAttributes::BODY) {
+				Some(block_data.body.iter().map(|body| {
+					Decode::decode(&mut body.as_ref())
+				}).collect::<Result<Vec<_>, _>>()?)
+			} else {
+				None
+			},
+			receipt: if !block_data.message_queue.is_empty() {
+				Some(block_data.receipt)
+			} else {
+				None
+			},
+			message_queue: if !block_data.message_queue.is_empty() {
+				Some(block_data.message_queue)
+			} else {
+				None
+			},
+			justification: if !block_data.justification.is_empty() {
+				Some(block_data.justification)
+			} else if block_data.is_empty_justification {
+				Some(Vec::new())
+			} else {
+				None
+			},
+		})
+	}).collect::<Result<Vec<_>, codec::Error>>()?;
+
+	Ok(Some((message::BlockResponse::<B> { id: original_request.id, blocks }, pruned_body)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::channel::oneshot;
+	use substrate_test_runtime_client::{
+		runtime::{Block, Hash},
+		TestClientBuilder, TestClientBuilderExt as _,
+	};
+
+	fn handler(config: Config) -> BlockRequestHandler<Block> {
+		let client = Arc::new(TestClientBuilder::with_default_backend().build_with_longest_chain().0);
+		handler_with_client(config, client)
+	}
+
+	fn handler_with_client(config: Config, client: Arc<dyn Client<Block>>) -> BlockRequestHandler<Block> {
+		let (_, peerset) = sc_peerset::Peerset::from_config(sc_peerset::PeersetConfig {
+			in_peers: 0,
+			out_peers: 0,
+			bootnodes: Vec::new(),
+			reserved_only: false,
+			priority_groups: Vec::new(),
+		});
+		BlockRequestHandler::new(config, &ProtocolId::from(&b"test"[..]), client, peerset, None)
+			.unwrap()
+			.0
+	}
+
+	fn encoded_request(max_blocks: u32) -> Vec<u8> {
+		let request = build_protobuf_block_request::<Hash, u64>(
+			BlockAttributes::HEADER,
+			message::FromBlock::Number(0),
+			None,
+			message::Direction::Ascending,
+			Some(max_blocks),
+		);
+		let mut buf = Vec::new();
+		request.encode(&mut buf).unwrap();
+		buf
+	}
+
+	fn send(handler: &mut BlockRequestHandler<Block>, peer: &PeerId, payload: Vec<u8>) -> Option<Vec<u8>> {
+		let (tx, mut rx) = oneshot::channel();
+		handler.on_request(IncomingRequest { peer: peer.clone(), payload, pending_response: tx });
+		rx.try_recv().ok().flatten()
+	}
+
+	/// Refused requests are answered with a response that has the `refused` flag set.
+	fn refused(response: Option<Vec<u8>>) -> bool {
+		response.map_or(false, |response| {
+			schema::v1::BlockResponse::decode(&response[..]).map_or(false, |response| response.refused)
+		})
+	}
+
+	fn served(response: Option<Vec<u8>>) -> bool {
+		response.map_or(false, |response| {
+			schema::v1::BlockResponse::decode(&response[..]).map_or(false, |response| !response.refused)
+		})
+	}
+
+	#[test]
+	fn requests_over_allowance_are_refused() {
+		let mut config = Config::default();
+		config.set_max_requests_per_window(2);
+		let mut handler = handler(config);
+		let peer = PeerId::random();
+
+		assert!(served(send(&mut handler, &peer, encoded_request(1))));
+		assert!(served(send(&mut handler, &peer, encoded_request(2))));
+		assert!(refused(send(&mut handler, &peer, encoded_request(3))));
+
+		// Other peers have their own allowance.
+		assert!(served(send(&mut handler, &PeerId::random(), encoded_request(3))));
+	}
+
+	#[test]
+	fn responses_are_limited_by_the_remaining_byte_allowance() {
+		use sc_block_builder::BlockBuilderProvider;
+		use substrate_test_runtime_client::{prelude::*, sp_consensus::BlockOrigin};
+
+		let mut client = Arc::new(TestClientBuilder::new().build());
+		let block = client.new_block(Default::default()).unwrap().build().unwrap().block;
+		client.import(BlockOrigin::Own, block).unwrap();
+
+		// Size of a response with the genesis block only.
+		let genesis_response = {
+			let mut handler = handler_with_client(Config::default(), client.clone());
+			send(&mut handler, &PeerId::random(), encoded_request(1)).unwrap()
+		};
+		let mut config = Config::default();
+		config.set_max_bytes_per_window(genesis_response.len() as u64);
+		let mut handler = handler_with_client(config, client);
+		let peer = PeerId::random();
+
+		// The second block would exceed the allowance.
+		assert_eq!(send(&mut handler, &peer, encoded_request(2)), Some(genesis_response));
+		assert!(refused(send(&mut handler, &peer, encoded_request(3))));
+	}
+
+	#[test]
+	fn repeated_requests_are_refused() {
+		let mut handler = handler(Config::default());
+		let peer = PeerId::random();
+
+		for _ in 0..MAX_SAME_REQUESTS {
+			assert!(served(send(&mut handler, &peer, encoded_request(1))));
+		}
+		assert!(refused(send(&mut handler, &peer, encoded_request(1))));
+	}
+
+	#[test]
+	fn repeated_requests_are_served_again_after_expiry() {
+		let mut handler = handler(Config::default());
+		let peer = PeerId::random();
+
+		for _ in 0..MAX_SAME_REQUESTS {
+			assert!(served(send(&mut handler, &peer, encoded_request(1))));
+		}
+		for (_, seen) in handler.seen_requests.iter_mut() {
+			seen.first_seen -= SEEN_REQUEST_EXPIRY;
+		}
+		assert!(served(send(&mut handler, &peer, encoded_request(1))));
+	}
+
+	#[test]
+	fn refused_responses_are_told_apart_from_empty_ones() {
+		let request = message::BlockRequest::<Block> {
+			id: 0,
+			fields: BlockAttributes::HEADER,
+			from: message::FromBlock::Number(0),
+			to: None,
+			direction: message::Direction::Ascending,
+			max: Some(1),
+		};
+
+		let (tx, mut rx) = oneshot::channel();
+		refuse(tx);
+		let response = rx.try_recv().unwrap().unwrap();
+		assert!(decode_block_response(&request, &response).unwrap().is_none());
+
+		let (response, pruned_body) = decode_block_response(&request, &[]).unwrap().unwrap();
+		assert!(response.blocks.is_empty());
+		assert!(pruned_body.is_none());
+	}
+
+	#[test]
+	fn malformed_requests_are_refused() {
+		let mut handler = handler(Config::default());
+		assert!(refused(send(&mut handler, &PeerId::random(), vec![0xff; 8])));
+	}
+
+	#[test]
+	fn responses_stop_at_pruned_bodies() {
+		use sc_block_builder::BlockBuilderProvider;
+		use substrate_test_runtime_client::{
+			prelude::*, sc_client_db::BlocksPruning, sp_consensus::BlockOrigin,
+		};
+
+		let backend = Arc::new(Backend::new_test_with_blocks_pruning(
+			u32::max_value(),
+			BlocksPruning::KeepFinalized { blocks: 1, justifications: false },
+			u64::max_value(),
+		));
+		let mut client = Arc::new(TestClientBuilder::with_backend(backend).build());
+		for _ in 0..3 {
+			let block = client.new_block(Default::default()).unwrap().build().unwrap().block;
+			client.import(BlockOrigin::Own, block).unwrap();
+		}
+		client.finalize_block(BlockId::Number(3), None).unwrap();
+		let mut handler = handler_with_client(Config::default(), client);
+
+		let request = |from, direction| message::generic::BlockRequest {
+			id: 0,
+			fields: BlockAttributes::HEADER | BlockAttributes::BODY,
+			from: message::FromBlock::Number(from),
+			to: None,
+			direction,
+			max: Some(3),
+		};
+
+		let descending = request(3, message::Direction::Descending);
+		let response = send(&mut handler, &PeerId::random(), encode_block_request(&descending).unwrap()).unwrap();
+		let (response, pruned_body) = decode_block_response::<Block>(&descending, &response).unwrap();
+		assert_eq!(response.blocks.len(), 1);
+		assert_eq!(pruned_body, Some(2));
+
+		let ascending = request(1, message::Direction::Ascending);
+		let response = send(&mut handler, &PeerId::random(), encode_block_request(&ascending).unwrap()).unwrap();
+		let (response, pruned_body) = decode_block_response::<Block>(&ascending, &response).unwrap();
+		assert!(response.blocks.is_empty());
+		assert_eq!(pruned_body, Some(1));
+	}
+}
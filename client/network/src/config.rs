@@ -23,6 +23,10 @@
 
 pub use crate::chain::{Client, FinalityProofProvider};
 pub use crate::on_demand_layer::{AlwaysBadChecker, OnDemand};
+pub use crate::request_responses::{
+	IncomingRequest,
+	ProtocolConfig as RequestResponseConfig,
+};
 pub use libp2p::{identity, core::PublicKey, wasm_ext::ExtTransport, build_multiaddr};
 
 // Note: this re-export shouldn't be part of the public API of the crate and will be removed in
@@ -99,6 +103,9 @@ pub struct Params<B: BlockT, H: ExHashT> {
 	pub import_queue: Box<dyn ImportQueue<B>>,
 
 	/// Type to check incoming block announcements.
+	///
+	/// Chains that attach a statement to their announcements can build one with
+	/// [`sp_consensus::block_validation::StatementValidatorBuilder`].
 	pub block_announce_validator: Box<dyn BlockAnnounceValidator<B> + Send>,
 
 	/// Registry for recording prometheus metrics to.
@@ -407,6 +414,8 @@ pub struct NetworkConfiguration {
 	/// List of notifications protocols that the node supports. Must also include a
 	/// `ConsensusEngineId` for backwards-compatibility.
 	pub notifications_protocols: Vec<(ConsensusEngineId, Cow<'static, [u8]>)>,
+	/// List of request-response protocols that the node supports.
+	pub request_response_protocols: Vec<RequestResponseConfig>,
 	/// Maximum allowed number of incoming connections.
 	pub in_peers: u32,
 	/// Number of outgoing connections we're trying to maintain.
@@ -423,6 +432,12 @@ pub struct NetworkConfiguration {
 	pub transport: TransportConfig,
 	/// Maximum number of peers to ask the same blocks in parallel.
 	pub max_parallel_downloads: u32,
+	/// Maximum number of block requests a single peer can send us every 10 seconds.
+	pub max_block_requests_per_peer: u32,
+	/// Maximum number of bytes of block data sent to a single peer every 10 seconds.
+	pub max_block_response_bytes_per_peer: u64,
+	/// Maximum number of light client requests a single peer processes for us at the same time.
+	pub max_light_requests_per_peer: u32,
 	/// Should we insert non-global addresses into the DHT?
 	pub allow_non_globals_in_dht: bool,
 }
@@ -442,6 +457,7 @@ impl NetworkConfiguration {
 			boot_nodes: Vec::new(),
 			node_key,
 			notifications_protocols: Vec::new(),
+			request_response_protocols: Vec::new(),
 			in_peers: 25,
 			out_peers: 75,
 			reserved_nodes: Vec::new(),
@@ -453,8 +469,12 @@ impl NetworkConfiguration {
 				allow_private_ipv4: true,
 				wasm_external_transport: None,
 				use_yamux_flow_control: false,
+				websocket_tls: None,
 			},
 			max_parallel_downloads: 5,
+			max_block_requests_per_peer: crate::block_request_handler::DEFAULT_MAX_REQUESTS_PER_WINDOW,
+			max_block_response_bytes_per_peer: crate::block_request_handler::DEFAULT_MAX_BYTES_PER_WINDOW,
+			max_light_requests_per_peer: crate::light_client_handler::DEFAULT_MAX_REQUESTS_PER_PEER as u32,
 			allow_non_globals_in_dht: false,
 		}
 	}
@@ -524,6 +544,11 @@ pub enum TransportConfig {
 		wasm_external_transport: Option<wasm_ext::ExtTransport>,
 		/// Use flow control for yamux streams if set to true.
 		use_yamux_flow_control: bool,
+
+		/// TLS settings of the WebSocket transport. Listening on `/wss` addresses requires a
+		/// server certificate, while `/wss` addresses can always be dialed. Ignored when
+		/// compiling for WASM.
+		websocket_tls: Option<WebSocketTlsConfig>,
 	},
 
 	/// Only allow connections within the same process.
@@ -531,6 +556,35 @@ pub enum TransportConfig {
 	MemoryOnly,
 }
 
+/// Settings used to secure the WebSocket transport with TLS.
+///
+/// Files can be either in PEM or in DER format.
+#[derive(Clone, Debug, Default)]
+pub struct WebSocketTlsConfig {
+	/// Certificate presented to the nodes that connect to us. Required in order to listen on
+	/// `/wss` addresses, but not to dial them.
+	pub server: Option<WebSocketTlsServerConfig>,
+	/// Settings used when dialing `/wss` addresses.
+	pub client: WebSocketTlsClientConfig,
+}
+
+/// Certificate presented by the WebSocket transport to the nodes that connect to us.
+#[derive(Clone, Debug)]
+pub struct WebSocketTlsServerConfig {
+	/// Certificate chain, starting with the certificate of the local node.
+	pub certificate_chain: PathBuf,
+	/// Private key of the first certificate of the chain, in PKCS#8 or PKCS#1 (RSA only) format.
+	pub private_key: PathBuf,
+}
+
+/// Settings used by the WebSocket transport when dialing `/wss` addresses.
+#[derive(Clone, Debug, Default)]
+pub struct WebSocketTlsClientConfig {
+	/// Certificates to trust in addition to the usual root certificate authorities. Useful when
+	/// remotes use self-signed certificates.
+	pub trusted_certificates: Vec<PathBuf>,
+}
+
 /// The policy for connections to non-reserved peers.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum NonReservedPeerMode {
@@ -245,7 +245,7 @@
 //!
 
 mod behaviour;
-mod block_requests;
+mod block_request_handler;
 mod chain;
 mod peer_info;
 mod discovery;
@@ -253,6 +253,7 @@ mod finality_requests;
 mod light_client_handler;
 mod on_demand_layer;
 mod protocol;
+mod request_responses;
 mod schema;
 mod service;
 mod transport;
@@ -263,15 +264,16 @@ pub mod error;
 pub mod gossip;
 pub mod network_state;
 
-pub use service::{NetworkService, NetworkWorker};
+pub use service::{NetworkService, NetworkWorker, bans::MAX_BAN_DURATION};
 pub use protocol::PeerInfo;
 pub use protocol::event::{Event, DhtEvent, ObservedRole};
 pub use protocol::sync::SyncState;
+pub use request_responses::RequestFailure;
 pub use libp2p::{Multiaddr, PeerId};
 #[doc(inline)]
 pub use libp2p::multiaddr;
 
-pub use sc_peerset::ReputationChange;
+pub use sc_peerset::{ConnectionDirection, PeerState, ReputationChange, SlotType};
 use sp_runtime::traits::{Block as BlockT, NumberFor};
 
 /// The maximum allowed number of established connections per peer.
@@ -27,7 +27,7 @@
 use bytes::Bytes;
 use codec::{self, Encode, Decode};
 use crate::{
-	block_requests::build_protobuf_block_request,
+	block_request_handler::build_protobuf_block_request,
 	chain::Client,
 	config::ProtocolId,
 	protocol::message::{BlockAttributes, Direction, FromBlock},
@@ -54,7 +54,12 @@ use libp2p::{
 		SubstreamProtocol,
 	}
 };
+use lru::LruCache;
 use nohash_hasher::IntMap;
+use prometheus_endpoint::{
+	register, exponential_buckets, CounterVec, Gauge, HistogramOpts, HistogramVec, Opts,
+	PrometheusError, Registry, U64,
+};
 use prost::Message;
 use sc_client_api::{
 	StorageProof,
@@ -88,6 +93,12 @@ use wasm_timer::Instant;
 /// Reputation change for a peer when a request timed out.
 pub(crate) const TIMEOUT_REPUTATION_CHANGE: i32 = -(1 << 8);
 
+/// Maximum number of peers we keep request statistics of.
+const MAX_SCORED_PEERS: usize = 1024;
+
+/// Default maximum number of requests a single peer processes for us at the same time.
+pub const DEFAULT_MAX_REQUESTS_PER_PEER: usize = 1;
+
 /// Configuration options for `LightClientHandler` behaviour.
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -96,6 +107,7 @@ pub struct Config {
 	max_pending_requests: usize,
 	inactivity_timeout: Duration,
 	request_timeout: Duration,
+	max_requests_per_peer: usize,
 	light_protocol: Bytes,
 	block_protocol: Bytes,
 }
@@ -108,6 +120,7 @@ impl Config {
 	/// - max. pending requests = 128
 	/// - inactivity timeout = 15s
 	/// - request timeout = 15s
+	/// - max. requests in flight per peer = 1
 	pub fn new(id: &ProtocolId) -> Self {
 		let mut c = Config {
 			max_request_size: 1 * 1024 * 1024,
@@ -115,6 +128,7 @@ impl Config {
 			max_pending_requests: 128,
 			inactivity_timeout: Duration::from_secs(15),
 			request_timeout: Duration::from_secs(15),
+			max_requests_per_peer: DEFAULT_MAX_REQUESTS_PER_PEER,
 			light_protocol: Bytes::new(),
 			block_protocol: Bytes::new(),
 		};
@@ -152,6 +166,12 @@ impl Config {
 		self
 	}
 
+	/// Limit the max. number of requests a single peer processes for us at the same time.
+	pub fn set_max_requests_per_peer(&mut self, v: usize) -> &mut Self {
+		self.max_requests_per_peer = std::cmp::max(v, 1);
+		self
+	}
+
 	/// Set protocol to use for upgrade negotiation.
 	pub fn set_protocol(&mut self, id: &ProtocolId) -> &mut Self {
 		let mut vl = Vec::new();
@@ -252,6 +272,25 @@ struct RequestWrapper<B: Block, P> {
 	peer: P,
 	/// The connection to use for sending the request.
 	connection: Option<ConnectionId>,
+	/// Peers this request has previously been sent to.
+	tried_peers: Vec<PeerId>,
+}
+
+impl<B: Block> RequestWrapper<B, PeerId> {
+	/// Turns a request sent to a peer back into a pending request, which will preferably be
+	/// sent to another peer.
+	fn into_pending(self) -> RequestWrapper<B, ()> {
+		let mut tried_peers = self.tried_peers;
+		tried_peers.push(self.peer);
+		RequestWrapper {
+			timestamp: self.timestamp,
+			retries: self.retries,
+			request: self.request,
+			peer: (), // need to find another peer
+			connection: None,
+			tried_peers,
+		}
+	}
 }
 
 /// Information we have about some peer.
@@ -274,13 +313,106 @@ impl<B: Block> Default for PeerInfo<B> {
 
 type RequestId = u64;
 
-/// A peer is either idle or busy processing a request from us.
+/// A peer is either idle or busy processing requests from us.
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum PeerStatus {
 	/// The peer is available.
 	Idle,
-	/// We wait for the peer to return us a response for the given request ID.
-	BusyWith(RequestId),
+	/// We wait for the peer to return us responses for the given request IDs, which have been
+	/// sent at the given instants.
+	BusyWith(SmallVec<[(RequestId, Instant); 1]>),
+}
+
+impl PeerStatus {
+	/// Number of requests the peer is processing.
+	fn in_flight(&self) -> usize {
+		match self {
+			PeerStatus::Idle => 0,
+			PeerStatus::BusyWith(requests) => requests.len(),
+		}
+	}
+
+	/// Records that the given request has been sent to the peer.
+	fn start(&mut self, id: RequestId, now: Instant) {
+		match self {
+			PeerStatus::Idle => *self = PeerStatus::BusyWith(iter::once((id, now)).collect()),
+			PeerStatus::BusyWith(requests) => requests.push((id, now)),
+		}
+	}
+
+	/// Records that the peer is done with the given request. Returns when the request has been
+	/// sent, or `None` if the peer wasn't processing this request.
+	fn finish(&mut self, id: RequestId) -> Option<Instant> {
+		let requests = match self {
+			PeerStatus::Idle => return None,
+			PeerStatus::BusyWith(requests) => requests,
+		};
+		let position = requests.iter().position(|(r, _)| *r == id)?;
+		let (_, sent_at) = requests.remove(position);
+		if requests.is_empty() {
+			*self = PeerStatus::Idle;
+		}
+		Some(sent_at)
+	}
+}
+
+/// Statistics about the requests we have sent to a peer, used to select the peers to send
+/// requests to.
+#[derive(Debug, Clone, Default)]
+struct PeerScore {
+	/// Moving average of the time the peer took to answer our requests.
+	latency: Option<Duration>,
+	/// Number of requests the peer failed to answer, halved whenever it answers one correctly.
+	failures: u32,
+}
+
+impl PeerScore {
+	fn on_success(&mut self, latency: Duration) {
+		self.latency = Some(match self.latency {
+			Some(average) => average * 3 / 4 + latency / 4,
+			None => latency,
+		});
+		self.failures /= 2;
+	}
+
+	fn on_failure(&mut self) {
+		self.failures = self.failures.saturating_add(1);
+	}
+}
+
+struct Metrics {
+	requests: CounterVec<U64>,
+	request_duration: HistogramVec,
+	pending_requests: Gauge<U64>,
+}
+
+impl Metrics {
+	fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Metrics {
+			requests: register(CounterVec::new(
+				Opts::new(
+					"sub_libp2p_light_requests_total",
+					"Number of light client requests sent to peers, by kind and outcome"
+				),
+				&["kind", "outcome"]
+			)?, registry)?,
+			request_duration: register(HistogramVec::new(
+				HistogramOpts {
+					common_opts: Opts::new(
+						"sub_libp2p_light_request_duration_seconds",
+						"Time peers took to correctly answer our light client requests"
+					),
+					buckets: exponential_buckets(0.001, 2.0, 16)
+						.expect("parameters are always valid values; qed"),
+				},
+				&["kind"]
+			)?, registry)?,
+			pending_requests: register(Gauge::new(
+				"sub_libp2p_light_requests_pending",
+				"Number of light client requests waiting for a peer to be sent to"
+			)?, registry)?,
+		})
+	}
 }
 
 /// The light client handler behaviour.
@@ -303,6 +435,9 @@ pub struct LightClientHandler<B: Block> {
 	next_request_id: RequestId,
 	/// Handle to use for reporting misbehaviour of peers.
 	peerset: sc_peerset::PeersetHandle,
+	/// Statistics about the peers we have recently sent requests to. Survives disconnections.
+	scores: LruCache<PeerId, PeerScore>,
+	metrics: Option<Metrics>,
 }
 
 impl<B> LightClientHandler<B>
@@ -315,8 +450,9 @@ where
 		chain: Arc<dyn Client<B>>,
 		checker: Arc<dyn light::FetchChecker<B>>,
 		peerset: sc_peerset::PeersetHandle,
-	) -> Self {
-		LightClientHandler {
+		registry: Option<&Registry>,
+	) -> Result<Self, PrometheusError> {
+		Ok(LightClientHandler {
 			config: cfg,
 			chain,
 			checker,
@@ -326,7 +462,9 @@ where
 			outstanding: IntMap::default(),
 			next_request_id: 1,
 			peerset,
-		}
+			scores: LruCache::new(MAX_SCORED_PEERS),
+			metrics: registry.map(Metrics::register).transpose()?,
+		})
 	}
 
 	/// We rely on external information about peers best blocks as we lack the
@@ -349,6 +487,7 @@ where
 			request: req,
 			peer: (), // we do not know the peer yet
 			connection: None,
+			tried_peers: Vec::new(),
 		};
 		self.pending_requests.push_back(rw);
 		Ok(())
@@ -362,25 +501,25 @@ where
 
 	/// Remove the given peer.
 	///
-	/// If we have a request to this peer in flight, we move it back to
+	/// If we have requests to this peer in flight, we move them back to
 	/// the pending requests queue.
 	fn remove_peer(&mut self, peer: &PeerId) {
-		if let Some(id) = self.outstanding.iter().find(|(_, rw)| &rw.peer == peer).map(|(k, _)| *k) {
+		let ids = self.outstanding.iter()
+			.filter(|(_, rw)| &rw.peer == peer)
+			.map(|(k, _)| *k)
+			.collect::<Vec<_>>();
+		for id in ids {
 			let rw = self.outstanding.remove(&id).expect("key belongs to entry in this map");
-			let rw = RequestWrapper {
-				timestamp: rw.timestamp,
-				retries: rw.retries,
-				request: rw.request,
-				peer: (), // need to find another peer
-				connection: None,
-			};
-			self.pending_requests.push_back(rw);
+			self.pending_requests.push_back(rw.into_pending());
 		}
 		self.peers.remove(peer);
 	}
 
 	/// Prepares a request by selecting a suitable peer and connection to send it to.
 	///
+	/// Peers the request hasn't been sent to yet are preferred, then peers known to have the
+	/// required block, then the peers with the fewest recent failures and the lowest latency.
+	///
 	/// If there is currently no suitable peer for the request, the given request
 	/// is returned as `Err`.
 	fn prepare_request(&self, req: RequestWrapper<B, ()>)
@@ -388,18 +527,19 @@ where
 	{
 		let number = required_block(&req.request);
 
-		let mut peer = None;
-		for (peer_id, peer_info) in self.peers.iter() {
-			if peer_info.status == PeerStatus::Idle {
-				match peer_info.best_block {
-					Some(n) => if n >= number {
-						peer = Some((peer_id, peer_info));
-						break
-					},
-					None => peer = Some((peer_id, peer_info))
-				}
-			}
-		}
+		let peer = self.peers.iter()
+			.filter(|(_, peer_info)| peer_info.status.in_flight() < self.config.max_requests_per_peer)
+			.filter(|(_, peer_info)| peer_info.best_block.map_or(true, |n| n >= number))
+			.min_by_key(|(peer_id, peer_info)| {
+				let score = self.scores.peek(*peer_id).cloned().unwrap_or_default();
+				(
+					req.tried_peers.contains(peer_id),
+					peer_info.best_block.is_none(),
+					score.failures,
+					score.latency.unwrap_or_default(),
+					peer_info.status.in_flight(),
+				)
+			});
 
 		if let Some((peer_id, peer_info)) = peer {
 			let connection = peer_info.connections.iter().next().map(|(id, _)| *id);
@@ -409,6 +549,7 @@ where
 				request: req.request,
 				peer: peer_id.clone(),
 				connection,
+				tried_peers: req.tried_peers,
 			};
 			Ok((peer_id.clone(), rw))
 		} else {
@@ -416,6 +557,35 @@ where
 		}
 	}
 
+	/// Records a failure of the given peer to answer a request.
+	fn on_peer_failure(&mut self, peer: &PeerId) {
+		if let Some(score) = self.scores.get_mut(peer) {
+			score.on_failure();
+		} else {
+			let mut score = PeerScore::default();
+			score.on_failure();
+			self.scores.put(peer.clone(), score);
+		}
+	}
+
+	/// Records that the given peer correctly answered a request in the given time.
+	fn on_peer_success(&mut self, peer: &PeerId, latency: Duration) {
+		if let Some(score) = self.scores.get_mut(peer) {
+			score.on_success(latency);
+		} else {
+			let mut score = PeerScore::default();
+			score.on_success(latency);
+			self.scores.put(peer.clone(), score);
+		}
+	}
+
+	/// Updates the metrics with the outcome of an attempt at sending a request.
+	fn report_outcome(&self, request: &Request<B>, outcome: &str) {
+		if let Some(metrics) = &self.metrics {
+			metrics.requests.with_label_values(&[request_kind(request), outcome]).inc();
+		}
+	}
+
 	/// Process a local request's response from remote.
 	///
 	/// If successful, this will give us the actual, checked data we should be
@@ -801,19 +971,16 @@ where
 
 		// Add any outstanding requests on the closed connection back to the
 		// pending requests.
-		if let Some(id) = self.outstanding.iter()
-			.find(|(_, rw)| &rw.peer == peer && rw.connection == Some(*conn)) // (*)
+		let ids = self.outstanding.iter()
+			.filter(|(_, rw)| &rw.peer == peer && rw.connection == Some(*conn)) // (*)
 			.map(|(id, _)| *id)
-		{
+			.collect::<Vec<_>>();
+		for id in ids {
 			let rw = self.outstanding.remove(&id).expect("by (*)");
-			let rw = RequestWrapper {
-				timestamp: rw.timestamp,
-				retries: rw.retries,
-				request: rw.request,
-				peer: (), // need to find another peer
-				connection: None,
-			};
-			self.pending_requests.push_back(rw);
+			if let Some(info) = self.peers.get_mut(peer) {
+				info.status.finish(id);
+			}
+			self.pending_requests.push_back(rw.into_pending());
 		}
 	}
 
@@ -874,48 +1041,51 @@ where
 					}
 
 					if let Some(info) = self.peers.get_mut(&peer) {
-						if info.status != PeerStatus::BusyWith(id) {
+						// Make peer available again.
+						let sent_at = match info.status.finish(id) {
+							Some(sent_at) => sent_at,
 							// If we get here, something is wrong with our internal handling of peer
-							// status information. At any time, a single peer processes at most one
-							// request from us and its status should contain the request ID we are
+							// status information. The status of a peer contains the IDs of all the
+							// requests it processes for us, including the request ID we are
 							// expecting a response for. If a peer would send us a response with a
 							// random ID, we should not have an entry for it with this peer ID in
 							// our `outstanding` map, so a malicious peer should not be able to get
 							// us here. It is our own fault and must be fixed!
-							panic!("unexpected peer status {:?} for {}", info.status, peer);
-						}
-
-						info.status = PeerStatus::Idle; // Make peer available again.
+							None => panic!("unexpected peer status {:?} for {}", info.status, peer),
+						};
 
 						match self.on_response(&peer, &request.request, response) {
-							Ok(reply) => send_reply(Ok(reply), request.request),
+							Ok(reply) => {
+								let latency = sent_at.elapsed();
+								self.on_peer_success(&peer, latency);
+								self.report_outcome(&request.request, "success");
+								if let Some(metrics) = &self.metrics {
+									metrics.request_duration
+										.with_label_values(&[request_kind(&request.request)])
+										.observe(latency.as_secs_f64());
+								}
+								send_reply(Ok(reply), request.request)
+							}
 							Err(Error::UnexpectedResponse) => {
 								log::debug!("unexpected response {} from peer {}", id, peer);
+								self.on_peer_failure(&peer);
+								self.report_outcome(&request.request, "unexpected");
 								self.remove_peer(&peer);
 								self.peerset.report_peer(peer, ReputationChange::new_fatal("unexpected response from peer"));
-								let rw = RequestWrapper {
-									timestamp: request.timestamp,
-									retries: request.retries,
-									request: request.request,
-									peer: (),
-									connection: None,
-								};
-								self.pending_requests.push_back(rw);
+								self.pending_requests.push_back(request.into_pending());
 							}
 							Err(other) => {
 								log::debug!("error handling response {} from peer {}: {}", id, peer, other);
+								self.on_peer_failure(&peer);
+								self.report_outcome(&request.request, "invalid");
 								self.remove_peer(&peer);
 								self.peerset.report_peer(peer, ReputationChange::new_fatal("invalid response from peer"));
 								if request.retries > 0 {
-									let rw = RequestWrapper {
-										timestamp: request.timestamp,
-										retries: request.retries - 1,
-										request: request.request,
-										peer: (),
-										connection: None,
-									};
+									let mut rw = request.into_pending();
+									rw.retries -= 1;
 									self.pending_requests.push_back(rw)
 								} else {
+									self.report_outcome(&request.request, "failed");
 									send_reply(Err(ClientError::RemoteFetchFailed), request.request)
 								}
 							}
@@ -946,6 +1116,7 @@ where
 		while let Some(mut request) = self.pending_requests.pop_front() {
 			if now > request.timestamp + self.config.request_timeout {
 				if request.retries == 0 {
+					self.report_outcome(&request.request, "failed");
 					send_reply(Err(ClientError::RemoteFetchFailed), request.request);
 					continue
 				}
@@ -982,7 +1153,7 @@ where
 
 					let request_id = self.next_request_id();
 					if let Some(p) = self.peers.get_mut(&peer) {
-						p.status = PeerStatus::BusyWith(request_id);
+						p.status.start(request_id, Instant::now());
 					}
 					self.outstanding.insert(request_id, request);
 
@@ -1015,24 +1186,27 @@ where
 		}
 		for id in expired {
 			if let Some(rw) = self.outstanding.remove(&id) {
+				self.on_peer_failure(&rw.peer);
+				self.report_outcome(&rw.request, "timeout");
 				self.remove_peer(&rw.peer);
 				self.peerset.report_peer(rw.peer.clone(),
 					ReputationChange::new(TIMEOUT_REPUTATION_CHANGE, "light request timeout"));
 				if rw.retries == 0 {
+					self.report_outcome(&rw.request, "failed");
 					send_reply(Err(ClientError::RemoteFetchFailed), rw.request);
 					continue
 				}
-				let rw = RequestWrapper {
-					timestamp: Instant::now(),
-					retries: rw.retries - 1,
-					request: rw.request,
-					peer: (),
-					connection: None,
-				};
+				let mut rw = rw.into_pending();
+				rw.timestamp = Instant::now();
+				rw.retries -= 1;
 				self.pending_requests.push_back(rw)
 			}
 		}
 
+		if let Some(metrics) = &self.metrics {
+			metrics.pending_requests.set(self.pending_requests.len() as u64);
+		}
+
 		Poll::Pending
 	}
 }
@@ -1048,6 +1222,18 @@ fn required_block<B: Block>(request: &Request<B>) -> NumberFor<B> {
 	}
 }
 
+/// Returns the kind of a request, used as label of the metrics.
+fn request_kind<B: Block>(request: &Request<B>) -> &'static str {
+	match request {
+		Request::Body { .. } => "body",
+		Request::Header { .. } => "header",
+		Request::Read { .. } => "read",
+		Request::ReadChild { .. } => "read_child",
+		Request::Call { .. } => "call",
+		Request::Changes { .. } => "changes",
+	}
+}
+
 fn retries<B: Block>(request: &Request<B>) -> usize {
 	let rc = match request {
 		Request::Body { request, .. } => request.retry_count,
@@ -1359,7 +1545,7 @@ mod tests {
 			.map(|(peer, muxer), _| (peer, StreamMuxerBox::new(muxer)))
 			.map_err(|e| io::Error::new(io::ErrorKind::Other, e))
 			.boxed();
-		Swarm::new(transport, LightClientHandler::new(cf, client, checker, ps), local_peer)
+		Swarm::new(transport, LightClientHandler::new(cf, client, checker, ps, None).unwrap(), local_peer)
 	}
 
 	struct DummyFetchChecker<B> {
@@ -1503,7 +1689,7 @@ mod tests {
 	{
 		let client = Arc::new(substrate_test_runtime_client::new());
 		let checker = Arc::new(DummyFetchChecker { ok, _mark: std::marker::PhantomData });
-		LightClientHandler::new(cf, client, checker, ps)
+		LightClientHandler::new(cf, client, checker, ps, None).unwrap()
 	}
 
 	fn empty_dialer() -> ConnectedPoint {
@@ -1789,6 +1975,117 @@ mod tests {
 		assert_matches!(chan.1.try_recv(), Ok(Some(Err(ClientError::RemoteFetchFailed))))
 	}
 
+	fn pending_call_request(tried_peers: Vec<PeerId>) -> RequestWrapper<Block, ()> {
+		let request = light::RemoteCallRequest {
+			block: Default::default(),
+			header: dummy_header(),
+			method: "test".into(),
+			call_data: vec![],
+			retry_count: Some(1),
+		};
+		RequestWrapper {
+			timestamp: Instant::now(),
+			retries: 1,
+			request: Request::Call { request, sender: oneshot::channel().0 },
+			peer: (),
+			connection: None,
+			tried_peers,
+		}
+	}
+
+	#[test]
+	fn prefers_peers_with_fewer_failures_and_lower_latency() {
+		let peer0 = PeerId::random();
+		let peer1 = PeerId::random();
+		let peer2 = PeerId::random();
+		let pset = peerset();
+		let mut behaviour = make_behaviour(true, pset.1, make_config());
+
+		for (i, peer) in [&peer0, &peer1, &peer2].iter().enumerate() {
+			behaviour.inject_connection_established(peer, &ConnectionId::new(i), &empty_dialer());
+			behaviour.inject_connected(peer);
+		}
+
+		behaviour.on_peer_failure(&peer0);
+		behaviour.on_peer_success(&peer1, Duration::from_millis(500));
+		behaviour.on_peer_success(&peer2, Duration::from_millis(100));
+		let (peer, _) = behaviour.prepare_request(pending_call_request(Vec::new())).unwrap();
+		assert_eq!(peer, peer2);
+
+		behaviour.on_peer_failure(&peer2);
+		let (peer, _) = behaviour.prepare_request(pending_call_request(Vec::new())).unwrap();
+		assert_eq!(peer, peer1);
+	}
+
+	#[test]
+	fn retries_requests_on_another_peer() {
+		let peer0 = PeerId::random();
+		let peer1 = PeerId::random();
+		let pset = peerset();
+		let mut behaviour = make_behaviour(true, pset.1, make_config());
+
+		behaviour.inject_connection_established(&peer0, &ConnectionId::new(0), &empty_dialer());
+		behaviour.inject_connected(&peer0);
+		behaviour.inject_connection_established(&peer1, &ConnectionId::new(1), &empty_dialer());
+		behaviour.inject_connected(&peer1);
+		behaviour.on_peer_success(&peer0, Duration::from_millis(100));
+		behaviour.on_peer_success(&peer1, Duration::from_millis(500));
+
+		let (peer, _) = behaviour.prepare_request(pending_call_request(vec![peer0.clone()])).unwrap();
+		assert_eq!(peer, peer1);
+
+		// If there is no other peer, we fall back to the peers that have already been tried.
+		behaviour.inject_connection_closed(&peer1, &ConnectionId::new(1), &empty_dialer());
+		behaviour.inject_disconnected(&peer1);
+		let (peer, _) = behaviour.prepare_request(pending_call_request(vec![peer0.clone()])).unwrap();
+		assert_eq!(peer, peer0);
+	}
+
+	#[test]
+	fn sends_parallel_requests_to_a_peer_if_configured() {
+		let peer = PeerId::random();
+		let pset = peerset();
+		let mut config = make_config();
+		config.set_max_requests_per_peer(2);
+		let mut behaviour = make_behaviour(true, pset.1, config);
+
+		let conn = ConnectionId::new(1);
+		behaviour.inject_connection_established(&peer, &conn, &empty_dialer());
+		behaviour.inject_connected(&peer);
+
+		for _ in 0 .. 3 {
+			let request = light::RemoteCallRequest {
+				block: Default::default(),
+				header: dummy_header(),
+				method: "test".into(),
+				call_data: vec![],
+				retry_count: Some(1),
+			};
+			behaviour.request(Request::Call { request, sender: oneshot::channel().0 }).unwrap();
+		}
+
+		assert_matches!(poll(&mut behaviour), Poll::Ready(NetworkBehaviourAction::NotifyHandler { .. }));
+		assert_matches!(poll(&mut behaviour), Poll::Ready(NetworkBehaviourAction::NotifyHandler { .. }));
+		assert_matches!(poll(&mut behaviour), Poll::Pending);
+		assert_eq!(1, behaviour.pending_requests.len());
+		assert_eq!(2, behaviour.outstanding.len());
+		assert_eq!(2, behaviour.peers[&peer].status.in_flight());
+
+		// Answering a request makes room for the last one, and is accounted in the peer's score.
+		let request_id = *behaviour.outstanding.keys().next().unwrap();
+		let response = {
+			let r = schema::v1::light::RemoteCallResponse { proof: empty_proof() };
+			schema::v1::light::Response {
+				response: Some(schema::v1::light::response::Response::RemoteCallResponse(r)),
+			}
+		};
+		behaviour.inject_event(peer.clone(), conn, Event::Response(request_id, Response::Light(response)));
+		assert!(behaviour.scores.peek(&peer).unwrap().latency.is_some());
+		assert_matches!(poll(&mut behaviour), Poll::Ready(NetworkBehaviourAction::NotifyHandler { .. }));
+		assert_eq!(0, behaviour.pending_requests.len());
+		assert_eq!(2, behaviour.outstanding.len());
+	}
+
 	fn issue_request(request: Request<Block>) {
 		let peer = PeerId::random();
 		let pset = peerset();
@@ -47,6 +47,10 @@ pub struct NetworkState {
 	pub total_bytes_inbound: u64,
 	/// The total number of bytes sent.
 	pub total_bytes_outbound: u64,
+	/// Size of the notifications sent and received, per notifications protocol.
+	pub notifications_bytes: HashMap<String, BytesCount>,
+	/// Size of the requests and responses sent and received, per request-response protocol.
+	pub request_responses_bytes: HashMap<String, BytesCount>,
 	/// State of the peerset manager.
 	pub peerset: serde_json::Value,
 }
@@ -69,6 +73,19 @@ pub struct Peer {
 	pub open: bool,
 	/// List of addresses known for this node.
 	pub known_addresses: HashSet<Multiaddr>,
+	/// Size of the notifications, requests and responses exchanged with this node since we
+	/// connected to it.
+	pub bytes: BytesCount,
+}
+
+/// Part of the `NetworkState` struct. Unstable.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BytesCount {
+	/// Number of bytes received.
+	pub inbound: u64,
+	/// Number of bytes sent.
+	pub outbound: u64,
 }
 
 /// Part of the `NetworkState` struct. Unstable.
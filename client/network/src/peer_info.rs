@@ -105,6 +105,15 @@ impl PeerInfoBehaviour {
 		self.nodes_info.get(peer_id).map(Node)
 	}
 
+	/// Returns the remote address of each of our connections, along with the peer it is
+	/// established with.
+	pub fn connected_addresses(&self) -> impl Iterator<Item = (&PeerId, &Multiaddr)> {
+		self.nodes_info.iter()
+			.filter(|(_, info)| info.info_expire.is_none())
+			.flat_map(|(peer_id, info)| info.endpoints.iter()
+				.map(move |endpoint| (peer_id, endpoint.get_remote_address())))
+	}
+
 	/// Inserts a ping time in the cache. Has no effect if we don't have any entry for that node,
 	/// which shouldn't happen.
 	fn handle_ping_report(&mut self, peer_id: &PeerId, ping_time: Duration) {
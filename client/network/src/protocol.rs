@@ -136,6 +136,8 @@ mod rep {
 	pub const BAD_ROLE: Rep = Rep::new_fatal("Unsupported role");
 	/// Peer response data does not have requested bits.
 	pub const BAD_RESPONSE: Rep = Rep::new(-(1 << 12), "Incomplete response");
+	/// Peer sent us a block announcement that failed the validation.
+	pub const BAD_BLOCK_ANNOUNCEMENT: Rep = Rep::new(-(1 << 12), "Bad block announcement");
 }
 
 struct Metrics {
@@ -517,6 +519,12 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 		self.behaviour.peerset_debug_info()
 	}
 
+	/// Returns the reputation, connection direction and slot type of all the peers known to the
+	/// peerset manager.
+	pub fn peerset_peers_state(&mut self) -> Vec<sc_peerset::PeerState> {
+		self.behaviour.peerset_peers_state()
+	}
+
 	/// Returns the number of peers we're connected to.
 	pub fn num_connected_peers(&self) -> usize {
 		self.context_data.peers.values().count()
@@ -619,11 +627,8 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 				self.update_peer_info(&who);
 				return outcome
 			},
-			GenericMessage::BlockAnnounce(announce) => {
-				let outcome = self.on_block_announce(who.clone(), announce);
-				self.update_peer_info(&who);
-				return outcome;
-			},
+			GenericMessage::BlockAnnounce(announce) =>
+				self.push_block_announce_validation(who, announce),
 			GenericMessage::Transactions(m) =>
 				self.on_transactions(who, m),
 			GenericMessage::RemoteCallRequest(request) => self.on_remote_call_request(who, request),
@@ -815,6 +820,35 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 		self.peerset_handle.report_peer(who, reputation)
 	}
 
+	/// Must be called before [`Protocol::on_block_response`] when the peer indicates that the
+	/// response stops at block `number` because it has pruned its body.
+	pub fn on_block_body_pruned(&mut self, peer: &PeerId, number: NumberFor<B>) {
+		self.sync.on_block_body_pruned(peer, number);
+	}
+
+	/// Must be called instead of [`Protocol::on_block_response`] when the peer refused to answer
+	/// the block request with the given id, for example because we exceeded its allowance.
+	///
+	/// The peer isn't penalized, and the blocks are requested again later.
+	pub fn on_block_request_refused(&mut self, peer: &PeerId, request_id: message::RequestId) {
+		if let Some(p) = self.context_data.peers.get_mut(peer) {
+			if p.obsolete_requests.remove(&request_id).is_some() {
+				return;
+			}
+			match p.block_request.take() {
+				Some((_, request)) if request.id == request_id => {}
+				other => {
+					p.block_request = other;
+					return;
+				}
+			}
+		} else {
+			return;
+		}
+
+		self.sync.on_block_request_refused(peer);
+	}
+
 	/// Must be called in response to a [`CustomMessageOutcome::BlockRequest`] being emitted.
 	/// Must contain the same `PeerId` and request that have been emitted.
 	pub fn on_block_response(
@@ -1308,13 +1342,25 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 		}
 	}
 
-	fn on_block_announce(
+	/// Push a block announce validation.
+	///
+	/// It is required that [`ChainSync::poll_block_announce_validation`] is
+	/// called later to check for finished validations. The result of the validation
+	/// needs to be passed to [`Protocol::process_block_announce_validation_result`]
+	/// to finish the processing.
+	///
+	/// # Note
+	///
+	/// This will internally create a future, but this future will not be registered
+	/// in the task before being polled once. So, it is required to call
+	/// [`ChainSync::poll_block_announce_validation`] to ensure that the future is
+	/// registered properly and will wake up the task when being ready.
+	fn push_block_announce_validation(
 		&mut self,
 		who: PeerId,
 		announce: BlockAnnounce<B::Header>,
-	) -> CustomMessageOutcome<B> {
+	) {
 		let hash = announce.header.hash();
-		let number = *announce.header.number();
 
 		if let Some(ref mut peer) = self.context_data.peers.get_mut(&who) {
 			peer.known_blocks.insert(hash.clone());
@@ -1325,22 +1371,56 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 			message::BlockState::Normal => false,
 		};
 
-		match self.sync.on_block_announce(&who, hash, &announce, is_their_best) {
-			sync::OnBlockAnnounce::Nothing => {
+		self.sync.push_block_announce_validation(who, hash, announce, is_their_best)
+	}
+
+	/// Processes the block announce validations that have finished.
+	fn poll_block_announce_validations(&mut self, cx: &mut std::task::Context) {
+		while let Poll::Ready(result) = self.sync.poll_block_announce_validation(cx) {
+			match self.process_block_announce_validation_result(result) {
+				CustomMessageOutcome::None => {},
+				outcome => self.pending_messages.push_back(outcome),
+			}
+		}
+	}
+
+	/// Process the result of the block announce validation.
+	fn process_block_announce_validation_result(
+		&mut self,
+		validation_result: sync::PollBlockAnnounceValidation<B::Header>,
+	) -> CustomMessageOutcome<B> {
+		let (header, is_best, who) = match validation_result {
+			sync::PollBlockAnnounceValidation::Nothing { is_best, who, header } => {
+				self.update_peer_info(&who);
+
 				// `on_block_announce` returns `OnBlockAnnounce::ImportHeader`
 				// when we have all data required to import the block
 				// in the BlockAnnounce message. This is only when:
 				// 1) we're on light client;
 				// AND
 				// 2) parent block is already imported and not pruned.
-				if is_their_best {
-					return CustomMessageOutcome::PeerNewBest(who, number);
+				if is_best {
+					return CustomMessageOutcome::PeerNewBest(who, *header.number())
 				} else {
-					return CustomMessageOutcome::None;
+					return CustomMessageOutcome::None
 				}
 			}
-			sync::OnBlockAnnounce::ImportHeader => () // We proceed with the import.
-		}
+			sync::PollBlockAnnounceValidation::ImportHeader { header, is_best, who } => {
+				self.update_peer_info(&who);
+				(header, is_best, who)
+			}
+			sync::PollBlockAnnounceValidation::Failure { who, disconnect } => {
+				if disconnect {
+					self.behaviour.disconnect_peer(&who);
+				}
+
+				self.peerset_handle.report_peer(who, rep::BAD_BLOCK_ANNOUNCEMENT);
+				return CustomMessageOutcome::None
+			}
+		};
+
+		let hash = header.hash();
+		let number = *header.number();
 
 		// to import header from announced block let's construct response to request that normally would have
 		// been sent over network (but it is not in our case)
@@ -1352,7 +1432,7 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 				blocks: vec![
 					message::generic::BlockData {
 						hash: hash,
-						header: Some(announce.header),
+						header: Some(header),
 						body: None,
 						receipt: None,
 						message_queue: None,
@@ -1362,7 +1442,7 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 			},
 		);
 
-		if is_their_best {
+		if is_best {
 			self.pending_messages.push_back(CustomMessageOutcome::PeerNewBest(who, number));
 		}
 
@@ -2008,6 +2088,9 @@ impl<B: BlockT, H: ExHashT> NetworkBehaviour for Protocol<B, H> {
 			};
 			self.pending_messages.push_back(event);
 		}
+		// Check if there is any block announcement validation finished.
+		self.poll_block_announce_validations(cx);
+
 		if let Poll::Ready(Some((tx_hash, result))) = self.pending_transactions.poll_next_unpin(cx) {
 			if let Some(peers) = self.pending_transactions_peers.remove(&tx_hash) {
 				peers.into_iter().for_each(|p| self.on_handle_transaction_import(p, result));
@@ -2090,9 +2173,8 @@ impl<B: BlockT, H: ExHashT> NetworkBehaviour for Protocol<B, H> {
 					}
 					Some(Fallback::BlockAnnounce) => {
 						if let Ok(announce) = message::BlockAnnounce::decode(&mut message.as_ref()) {
-							let outcome = self.on_block_announce(peer_id.clone(), announce);
-							self.update_peer_info(&peer_id);
-							outcome
+							self.push_block_announce_validation(peer_id, announce);
+							CustomMessageOutcome::None
 						} else {
 							warn!(target: "sub-libp2p", "Failed to decode block announce");
 							CustomMessageOutcome::None
@@ -2105,7 +2187,14 @@ impl<B: BlockT, H: ExHashT> NetworkBehaviour for Protocol<B, H> {
 				}
 		};
 
+		// Make sure that the block announce validations pushed while handling the event are
+		// polled once, in order to be registered in the task.
+		self.poll_block_announce_validations(cx);
+
 		if let CustomMessageOutcome::None = outcome {
+			if let Some(message) = self.pending_messages.pop_front() {
+				return Poll::Ready(NetworkBehaviourAction::GenerateEvent(message));
+			}
 			Poll::Pending
 		} else {
 			Poll::Ready(NetworkBehaviourAction::GenerateEvent(outcome))
@@ -606,6 +606,12 @@ impl GenericProto {
 		self.peerset.debug_info()
 	}
 
+	/// Returns the reputation, connection direction and slot type of all the peers known to the
+	/// peerset manager.
+	pub fn peerset_peers_state(&mut self) -> Vec<sc_peerset::PeerState> {
+		self.peerset.peers_state()
+	}
+
 	/// Function that is called when the peerset wants us to connect to a peer.
 	fn peerset_report_connect(&mut self, peer_id: PeerId) {
 		let mut occ_entry = match self.peers.entry(peer_id) {
@@ -28,7 +28,7 @@
 //!
 
 use codec::Encode;
-use blocks::BlockCollection;
+use blocks::{BlockCollection, PeerRequestSizes};
 use sp_blockchain::{Error as ClientError, Info as BlockchainInfo, HeaderMetadata};
 use sp_consensus::{BlockOrigin, BlockStatus,
 	block_validation::{BlockAnnounceValidator, Validation},
@@ -41,6 +41,7 @@ use crate::{
 };
 use either::Either;
 use extra_requests::ExtraRequests;
+use futures::{stream::FuturesUnordered, task::Poll, Future, FutureExt, StreamExt};
 use libp2p::PeerId;
 use log::{debug, trace, warn, info, error};
 use sp_runtime::{
@@ -49,13 +50,21 @@ use sp_runtime::{
 	traits::{Block as BlockT, Header, NumberFor, Zero, One, CheckedSub, SaturatedConversion, Hash, HashFor}
 };
 use sp_arithmetic::traits::Saturating;
-use std::{fmt, ops::Range, collections::{HashMap, HashSet, VecDeque}, sync::Arc};
+use std::{
+	fmt, ops::Range, collections::{HashMap, HashSet, VecDeque}, sync::Arc, pin::Pin, task::Context,
+};
 
 mod blocks;
 mod extra_requests;
 
 /// Maximum blocks to request in a single packet.
-const MAX_BLOCKS_TO_REQUEST: usize = 128;
+const MAX_BLOCKS_TO_REQUEST: u32 = 128;
+
+/// Maximum number of block announcements being validated at the same time.
+const MAX_CONCURRENT_BLOCK_ANNOUNCE_VALIDATIONS: usize = 256;
+
+/// Maximum number of block announcements of a single peer being validated at the same time.
+const MAX_CONCURRENT_BLOCK_ANNOUNCE_VALIDATIONS_PER_PEER: usize = 4;
 
 /// Maximum blocks to store in the import queue.
 const MAX_IMPORTING_BLOCKS: usize = 2048;
@@ -192,6 +201,14 @@ pub struct ChainSync<B: BlockT> {
 	max_parallel_downloads: u32,
 	/// Total number of downloaded blocks.
 	downloaded_blocks: usize,
+	/// Number of blocks to request from each peer.
+	request_sizes: PeerRequestSizes,
+	/// All block announcements that are currently being validated.
+	block_announce_validation: FuturesUnordered<
+		Pin<Box<dyn Future<Output = PreValidateBlockAnnounce<B::Header>> + Send>>
+	>,
+	/// Number of block announcements of each peer that are currently being validated.
+	block_announce_validation_per_peer_stats: HashMap<PeerId, usize>,
 }
 
 /// All the data we have about a Peer that we are trying to sync with
@@ -209,7 +226,10 @@ pub struct PeerSync<B: BlockT> {
 	pub state: PeerSyncState<B>,
 	/// A queue of blocks that this peer has announced to us, should only
 	/// contain `ANNOUNCE_HISTORY_SIZE` entries.
-	pub recently_announced: VecDeque<B::Hash>
+	pub recently_announced: VecDeque<B::Hash>,
+	/// The highest block whose body this peer told us it has pruned. The bodies
+	/// of this block and of its ancestors aren't requested from it.
+	pub pruned_body: Option<NumberFor<B>>,
 }
 
 /// The sync status of a peer we are trying to sync with
@@ -306,13 +326,61 @@ pub enum OnBlockData<B: BlockT> {
 	Request(PeerId, BlockRequest<B>)
 }
 
-/// Result of [`ChainSync::on_block_announce`].
+/// Result of a block announcement validation, before it is handled by the sync state machine.
+enum PreValidateBlockAnnounce<H> {
+	/// The announcement failed the validation. The peer reputation should be decreased.
+	Failure {
+		/// Who sent the announcement.
+		who: PeerId,
+		/// Should the peer be disconnected?
+		disconnect: bool,
+	},
+	/// The announcement passed the validation and should be processed.
+	Process {
+		/// Is this the new best block of the peer?
+		is_new_best: bool,
+		/// Who sent the announcement.
+		who: PeerId,
+		/// The announcement.
+		announce: BlockAnnounce<H>,
+	},
+	/// The validator errored, the announcement is ignored.
+	Error {
+		/// Who sent the announcement.
+		who: PeerId,
+	},
+}
+
+/// Result of [`ChainSync::poll_block_announce_validation`].
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum OnBlockAnnounce {
+pub enum PollBlockAnnounceValidation<H> {
+	/// The announcement failed the validation.
+	///
+	/// The peer reputation should be decreased.
+	Failure {
+		/// Who sent the announcement.
+		who: PeerId,
+		/// Should the peer be disconnected?
+		disconnect: bool,
+	},
 	/// The announcement does not require further handling.
-	Nothing,
+	Nothing {
+		/// Who sent the announcement.
+		who: PeerId,
+		/// Was this the best block of the peer?
+		is_best: bool,
+		/// The header of the announcement.
+		header: H,
+	},
 	/// The announcement header should be imported.
-	ImportHeader,
+	ImportHeader {
+		/// Who sent the announcement.
+		who: PeerId,
+		/// Was this the best block of the peer?
+		is_best: bool,
+		/// The header of the announcement.
+		header: H,
+	},
 }
 
 /// Result of [`ChainSync::on_block_justification`].
@@ -377,6 +445,9 @@ impl<B: BlockT> ChainSync<B> {
 			block_announce_validator,
 			max_parallel_downloads,
 			downloaded_blocks: 0,
+			request_sizes: PeerRequestSizes::new(MAX_BLOCKS_TO_REQUEST),
+			block_announce_validation: Default::default(),
+			block_announce_validation_per_peer_stats: Default::default(),
 		}
 	}
 
@@ -457,7 +528,8 @@ impl<B: BlockT> ChainSync<B> {
 						best_hash,
 						best_number,
 						state: PeerSyncState::Available,
-						recently_announced: Default::default()
+						recently_announced: Default::default(),
+						pruned_body: None,
 					});
 					return Ok(None)
 				}
@@ -471,6 +543,7 @@ impl<B: BlockT> ChainSync<B> {
 						best_number,
 						state: PeerSyncState::Available,
 						recently_announced: Default::default(),
+						pruned_body: None,
 					});
 					self.pending_requests.add(&who);
 					return Ok(None)
@@ -494,7 +567,8 @@ impl<B: BlockT> ChainSync<B> {
 						start: self.best_queued_number,
 						state: AncestorSearchState::ExponentialBackoff(One::one()),
 					},
-					recently_announced: Default::default()
+					recently_announced: Default::default(),
+					pruned_body: None,
 				});
 
 				Ok(Some(ancestry_request::<B>(common_best)))
@@ -507,6 +581,7 @@ impl<B: BlockT> ChainSync<B> {
 					best_number,
 					state: PeerSyncState::Available,
 					recently_announced: Default::default(),
+					pruned_body: None,
 				});
 				self.pending_requests.add(&who);
 				Ok(None)
@@ -650,6 +725,7 @@ impl<B: BlockT> ChainSync<B> {
 		}
 		let major_sync = self.status().state == SyncState::Downloading;
 		let blocks = &mut self.blocks;
+		let request_sizes = &mut self.request_sizes;
 		let attrs = &self.required_block_attributes;
 		let fork_targets = &mut self.fork_targets;
 		let last_finalized = self.client.info().finalized_number;
@@ -667,12 +743,14 @@ impl<B: BlockT> ChainSync<B> {
 				id,
 				peer,
 				blocks,
+				request_sizes.size(id),
 				attrs,
 				max_parallel,
 				last_finalized,
 				best_queued,
 			) {
 				peer.state = PeerSyncState::DownloadingNew(range.start);
+				request_sizes.on_request(id, (range.end - range.start).saturated_into::<u32>());
 				trace!(
 					target: "sync",
 					"New block request for {}, (best:{}, common:{}) {:?}",
@@ -704,6 +782,38 @@ impl<B: BlockT> ChainSync<B> {
 		Either::Right(iter)
 	}
 
+	/// Handle the peer indicating that it has pruned the body of block `number`.
+	///
+	/// The bodies of this block and of its ancestors aren't requested from this peer anymore.
+	pub fn on_block_body_pruned(&mut self, who: &PeerId, number: NumberFor<B>) {
+		if let Some(peer) = self.peers.get_mut(who) {
+			debug!(target: "sync", "Peer {} has pruned the body of block #{}", who, number);
+			peer.pruned_body = Some(peer.pruned_body.map_or(number, |pruned| std::cmp::max(pruned, number)));
+		}
+	}
+
+	/// Handle the peer refusing to answer the block request that we made, for example because
+	/// we exceeded its allowance.
+	///
+	/// The peer becomes available again without penalty. The blocks that were requested from it
+	/// can be requested from other peers, and from this peer once new requests are scheduled.
+	pub fn on_block_request_refused(&mut self, who: &PeerId) {
+		if let Some(peer) = self.peers.get_mut(who) {
+			match peer.state {
+				PeerSyncState::DownloadingNew(_) => {
+					self.blocks.clear_peer_download(who);
+					peer.state = PeerSyncState::Available;
+				}
+				PeerSyncState::DownloadingStale(_) | PeerSyncState::AncestorSearch { .. } => {
+					peer.state = PeerSyncState::Available;
+				}
+				PeerSyncState::Available
+				| PeerSyncState::DownloadingJustification(..)
+				| PeerSyncState::DownloadingFinalityProof(..) => {}
+			}
+		}
+	}
+
 	/// Handle a response from the remote to a block request that we made.
 	///
 	/// `request` must be the original request that triggered `response`.
@@ -730,6 +840,7 @@ impl<B: BlockT> ChainSync<B> {
 					match &mut peer.state {
 						PeerSyncState::DownloadingNew(start_block) => {
 							self.blocks.clear_peer_download(who);
+							self.request_sizes.on_response(who, blocks.len() as u32);
 							let start_block = *start_block;
 							peer.state = PeerSyncState::Available;
 							validate_blocks::<B>(&blocks, who)?;
@@ -749,9 +860,21 @@ impl<B: BlockT> ChainSync<B> {
 									}
 								}).collect()
 						}
-						PeerSyncState::DownloadingStale(_) => {
+						PeerSyncState::DownloadingStale(hash) => {
+							let hash = hash.clone();
 							peer.state = PeerSyncState::Available;
 							if blocks.is_empty() {
+								let pruned = match (self.fork_targets.get_mut(&hash), peer.pruned_body) {
+									(Some(target), Some(pruned)) if target.number <= pruned => {
+										target.peers.remove(who);
+										true
+									}
+									_ => false,
+								};
+								if pruned {
+									debug!(target: "sync", "Peer {} has pruned the body of fork target {}", who, hash);
+									return Ok(OnBlockData::Import(BlockOrigin::NetworkInitialSync, Vec::new()));
+								}
 								debug!(target: "sync", "Empty block response from {}", who);
 								return Err(BadPeer(who.clone(), rep::NO_BLOCK));
 							}
@@ -1156,59 +1279,149 @@ impl<B: BlockT> ChainSync<B> {
 		self.pending_requests.set_all();
 	}
 
-	/// Call when a node announces a new block.
+	/// Push a block announce validation.
 	///
-	/// If `OnBlockAnnounce::ImportHeader` is returned, then the caller MUST try to import passed
-	/// header (call `on_block_data`). The network request isn't sent
-	/// in this case. Both hash and header is passed as an optimization
-	/// to avoid rehashing the header.
-	pub fn on_block_announce(&mut self, who: &PeerId, hash: B::Hash, announce: &BlockAnnounce<B::Header>, is_best: bool)
-		-> OnBlockAnnounce
-	{
+	/// It is required that [`ChainSync::poll_block_announce_validation`] is called
+	/// to check for finished block announce validations.
+	pub fn push_block_announce_validation(
+		&mut self,
+		who: PeerId,
+		hash: B::Hash,
+		announce: BlockAnnounce<B::Header>,
+		is_best: bool,
+	) {
 		let header = &announce.header;
 		let number = *header.number();
-		debug!(target: "sync", "Received block announcement {:?} with number {:?} from {}", hash, number, who);
+		debug!(target: "sync", "Pre-validating received block announcement {:?} with number {:?} from {}", hash, number, who);
 		if number.is_zero() {
 			warn!(target: "sync", "💔 Ignored genesis block (#0) announcement from {}: {}", who, hash);
-			return OnBlockAnnounce::Nothing
+			return
+		}
+
+		if self.block_announce_validation.len() >= MAX_CONCURRENT_BLOCK_ANNOUNCE_VALIDATIONS {
+			debug!(target: "sync", "Ignoring block announcement from {}, too many validations in progress", who);
+			return
 		}
+
+		let validations = self.block_announce_validation_per_peer_stats.entry(who.clone()).or_default();
+		if *validations >= MAX_CONCURRENT_BLOCK_ANNOUNCE_VALIDATIONS_PER_PEER {
+			debug!(
+				target: "sync",
+				"Ignoring block announcement from {}, too many validations of this peer in progress",
+				who,
+			);
+			return
+		}
+		*validations += 1;
+
+		// Let external validator check the block announcement.
+		let assoc_data = announce.data.as_ref().map_or(&[][..], |v| v.as_slice());
+		let future = self.block_announce_validator.validate(&header, assoc_data);
+
+		self.block_announce_validation.push(async move {
+			match future.await {
+				Ok(Validation::Success { is_new_best }) => PreValidateBlockAnnounce::Process {
+					is_new_best: is_new_best || is_best,
+					announce,
+					who,
+				},
+				Ok(Validation::Failure { disconnect }) => {
+					debug!(
+						target: "sync",
+						"Block announcement validation of block {} from {} failed",
+						hash,
+						who,
+					);
+					PreValidateBlockAnnounce::Failure { who, disconnect }
+				}
+				Err(e) => {
+					error!(target: "sync", "💔 Block announcement validation errored: {}", e);
+					PreValidateBlockAnnounce::Error { who }
+				}
+			}
+		}.boxed());
+	}
+
+	/// Poll the block announce validation futures.
+	///
+	/// Returns the result of the first validation that finished, to be handled by the caller.
+	pub fn poll_block_announce_validation(
+		&mut self,
+		cx: &mut Context,
+	) -> Poll<PollBlockAnnounceValidation<B::Header>> {
+		while let Poll::Ready(Some(result)) = self.block_announce_validation.poll_next_unpin(cx) {
+			self.peer_block_announce_validation_finished(&result);
+
+			match result {
+				PreValidateBlockAnnounce::Failure { who, disconnect } =>
+					return Poll::Ready(PollBlockAnnounceValidation::Failure { who, disconnect }),
+				PreValidateBlockAnnounce::Process { is_new_best, who, announce } =>
+					return Poll::Ready(self.on_block_announce(who, is_new_best, announce)),
+				PreValidateBlockAnnounce::Error { .. } => {},
+			}
+		}
+
+		Poll::Pending
+	}
+
+	/// Updates the number of block announcements being validated for the peer of the given
+	/// validation result.
+	fn peer_block_announce_validation_finished(
+		&mut self,
+		result: &PreValidateBlockAnnounce<B::Header>,
+	) {
+		let who = match result {
+			PreValidateBlockAnnounce::Failure { who, .. } |
+			PreValidateBlockAnnounce::Process { who, .. } |
+			PreValidateBlockAnnounce::Error { who } => who,
+		};
+
+		if let Some(validations) = self.block_announce_validation_per_peer_stats.get_mut(who) {
+			*validations = validations.saturating_sub(1);
+			if *validations == 0 {
+				self.block_announce_validation_per_peer_stats.remove(who);
+			}
+		}
+	}
+
+	/// Call when a node announced a new block and the announcement passed the validation.
+	///
+	/// If `PollBlockAnnounceValidation::ImportHeader` is returned, then the caller MUST try to
+	/// import the header (call `on_block_data`). The network request isn't sent in this case.
+	fn on_block_announce(
+		&mut self,
+		who: PeerId,
+		is_best: bool,
+		announce: BlockAnnounce<B::Header>,
+	) -> PollBlockAnnounceValidation<B::Header> {
+		let header = announce.header;
+		let hash = header.hash();
+		let number = *header.number();
+		debug!(target: "sync", "Received block announcement {:?} with number {:?} from {}", hash, number, who);
 		let parent_status = self.block_status(header.parent_hash()).ok().unwrap_or(BlockStatus::Unknown);
 		let known_parent = parent_status != BlockStatus::Unknown;
 		let ancient_parent = parent_status == BlockStatus::InChainPruned;
 
 		let known = self.is_known(&hash);
-		let peer = if let Some(peer) = self.peers.get_mut(who) {
+		let peer = if let Some(peer) = self.peers.get_mut(&who) {
 			peer
 		} else {
-			error!(target: "sync", "💔 Called on_block_announce with a bad peer ID");
-			return OnBlockAnnounce::Nothing
+			// The peer may have disconnected while its announcement was being validated.
+			debug!(target: "sync", "Ignoring block announcement of disconnected peer {}", who);
+			return PollBlockAnnounceValidation::Nothing { is_best: false, who, header }
 		};
 		while peer.recently_announced.len() >= ANNOUNCE_HISTORY_SIZE {
 			peer.recently_announced.pop_front();
 		}
 		peer.recently_announced.push_back(hash.clone());
 
-		// Let external validator check the block announcement.
-		let assoc_data = announce.data.as_ref().map_or(&[][..], |v| v.as_slice());
-		let is_best = match self.block_announce_validator.validate(&header, assoc_data) {
-			Ok(Validation::Success { is_new_best }) => is_new_best || is_best,
-			Ok(Validation::Failure) => {
-				debug!(target: "sync", "Block announcement validation of block {} from {} failed", hash, who);
-				return OnBlockAnnounce::Nothing
-			}
-			Err(e) => {
-				error!(target: "sync", "💔 Block announcement validation errored: {}", e);
-				return OnBlockAnnounce::Nothing
-			}
-		};
-
 		if is_best {
 			// update their best block
 			peer.best_number = number;
 			peer.best_hash = hash;
 		}
 		if let PeerSyncState::AncestorSearch {..} = peer.state {
-			return OnBlockAnnounce::Nothing
+			return PollBlockAnnounceValidation::Nothing { is_best, who, header }
 		}
 		// If the announced block is the best they have and is not ahead of us, our common number
 		// is either one further ahead or it's the one they just announced, if we know about it.
@@ -1221,7 +1434,7 @@ impl<B: BlockT> ChainSync<B> {
 				peer.common_number = number - One::one();
 			}
 		}
-		self.pending_requests.add(who);
+		self.pending_requests.add(&who);
 
 		// known block case
 		if known || self.is_already_downloading(&hash) {
@@ -1229,18 +1442,18 @@ impl<B: BlockT> ChainSync<B> {
 			if let Some(target) = self.fork_targets.get_mut(&hash) {
 				target.peers.insert(who.clone());
 			}
-			return OnBlockAnnounce::Nothing
+			return PollBlockAnnounceValidation::Nothing { is_best, who, header }
 		}
 
 		if ancient_parent {
 			trace!(target: "sync", "Ignored ancient block announced from {}: {} {:?}", who, hash, header);
-			return OnBlockAnnounce::Nothing
+			return PollBlockAnnounceValidation::Nothing { is_best, who, header }
 		}
 
 		let requires_additional_data = !self.role.is_light() || !known_parent;
 		if !requires_additional_data {
 			trace!(target: "sync", "Importing new header announced from {}: {} {:?}", who, hash, header);
-			return OnBlockAnnounce::ImportHeader
+			return PollBlockAnnounceValidation::ImportHeader { is_best, who, header }
 		}
 
 		if number <= self.best_queued_number {
@@ -1258,12 +1471,13 @@ impl<B: BlockT> ChainSync<B> {
 				.peers.insert(who.clone());
 		}
 
-		OnBlockAnnounce::Nothing
+		PollBlockAnnounceValidation::Nothing { is_best, who, header }
 	}
 
 	/// Call when a peer has disconnected.
 	pub fn peer_disconnected(&mut self, who: &PeerId) {
 		self.blocks.clear_peer_download(who);
+		self.request_sizes.remove(who);
 		self.peers.remove(who);
 		self.extra_justifications.peer_disconnected(who);
 		self.extra_finality_proofs.peer_disconnected(who);
@@ -1406,6 +1620,7 @@ fn peer_block_request<B: BlockT>(
 	id: &PeerId,
 	peer: &PeerSync<B>,
 	blocks: &mut BlockCollection<B>,
+	count: u32,
 	attrs: &message::BlockAttributes,
 	max_parallel_downloads: u32,
 	finalized: NumberFor<B>,
@@ -1424,12 +1639,20 @@ fn peer_block_request<B: BlockT>(
 	}
 	if let Some(range) = blocks.needed_blocks(
 		id.clone(),
-		MAX_BLOCKS_TO_REQUEST,
+		count as usize,
 		peer.best_number,
 		peer.common_number,
 		max_parallel_downloads,
 		MAX_DOWNLOAD_AHEAD,
 	) {
+		if let Some(pruned) = peer.pruned_body.filter(|_| attrs.contains(message::BlockAttributes::BODY)) {
+			if range.start <= pruned {
+				trace!(target: "sync", "Peer {} has pruned the bodies of #{}..#{}", id, range.start, pruned);
+				blocks.clear_peer_download(id);
+				return None;
+			}
+		}
+
 		// The end is not part of the range.
 		let last = range.end.saturating_sub(One::one());
 
@@ -1555,7 +1778,7 @@ mod test {
 	use super::*;
 	use super::message::FromBlock;
 	use substrate_test_runtime_client::{
-		runtime::Block,
+		runtime::{Block, Hash},
 		DefaultTestClientBuilderExt, TestClientBuilder, TestClientBuilderExt,
 	};
 	use sp_blockchain::HeaderBackend;
@@ -1639,4 +1862,56 @@ mod test {
 			})
 		);
 	}
+
+	#[test]
+	fn does_not_request_pruned_bodies() {
+		let client = Arc::new(TestClientBuilder::new().build());
+		let info = client.info();
+		let mut sync = ChainSync::new(
+			Roles::FULL,
+			client.clone(),
+			&info,
+			None,
+			Box::new(DefaultBlockAnnounceValidator),
+			1,
+		);
+
+		// the peer has pruned the bodies of the blocks we need
+		let pruning_peer = PeerId::random();
+		sync.new_peer(pruning_peer.clone(), Hash::random(), 100).unwrap();
+		sync.on_block_body_pruned(&pruning_peer, 50);
+		assert_eq!(sync.block_requests().count(), 0);
+
+		// they are requested from another peer instead
+		let archive_peer = PeerId::random();
+		sync.new_peer(archive_peer.clone(), Hash::random(), 100).unwrap();
+		let requested = sync.block_requests().map(|(who, _)| who.clone()).collect::<Vec<_>>();
+		assert_eq!(requested, vec![archive_peer]);
+	}
+	#[test]
+	fn refused_block_requests_are_requested_again() {
+		let client = Arc::new(TestClientBuilder::new().build());
+		let info = client.info();
+		let mut sync = ChainSync::new(
+			Roles::FULL,
+			client.clone(),
+			&info,
+			None,
+			Box::new(DefaultBlockAnnounceValidator),
+			1,
+		);
+
+		let busy_peer = PeerId::random();
+		sync.new_peer(busy_peer.clone(), Hash::random(), 100).unwrap();
+		let refused = sync.block_requests().map(|(_, request)| request).collect::<Vec<_>>();
+		assert_eq!(refused.len(), 1);
+
+		// the peer becomes available again, and the range it was downloading is freed
+		sync.on_block_request_refused(&busy_peer);
+		assert!(sync.peers.get(&busy_peer).unwrap().state.is_available());
+
+		sync.new_peer(PeerId::random(), Hash::random(), 100).unwrap();
+		let requested = sync.block_requests().map(|(_, request)| request).collect::<Vec<_>>();
+		assert!(requested.iter().any(|request| request.from == refused[0].from));
+	}
 }
@@ -18,12 +18,24 @@
 
 use std::cmp;
 use std::ops::Range;
+use std::time::Duration;
 use std::collections::{HashMap, BTreeMap};
 use log::trace;
 use libp2p::PeerId;
 use sp_runtime::traits::{Block as BlockT, NumberFor, One};
+use wasm_timer::Instant;
 use crate::protocol::message;
 
+/// Maximum number of peers downloading the first pending range once the buffer of blocks
+/// downloaded ahead of it is full.
+const MAX_PARALLEL_HEAD_DOWNLOADS: u32 = 2;
+
+/// Smallest number of blocks requested from a single peer.
+const MIN_BLOCKS_PER_REQUEST: u32 = 8;
+
+/// Time that we would like a single block response to take.
+const TARGET_RESPONSE_TIME: Duration = Duration::from_secs(2);
+
 /// Block data with origin.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BlockData<B: BlockT> {
@@ -145,7 +157,7 @@ impl<B: BlockT> BlockCollection<B> {
 
 		if self.blocks.iter().next().map_or(false, |(n, _)| range.start > *n + max_ahead.into()) {
 			trace!(target: "sync", "Too far ahead for peer {} ({})", who, range.start);
-			return None;
+			return self.head_range(who, peer_best, common);
 		}
 
 		self.peer_requests.insert(who, range.start);
@@ -160,6 +172,32 @@ impl<B: BlockT> BlockCollection<B> {
 		Some(range)
 	}
 
+	/// Ask `who` for the first pending range if it is still being downloaded.
+	///
+	/// Everything after that range is buffered until it arrives, so a single slow peer would
+	/// otherwise stall the download for everyone.
+	fn head_range(
+		&mut self,
+		who: PeerId,
+		peer_best: NumberFor<B>,
+		common: NumberFor<B>,
+	) -> Option<Range<NumberFor<B>>> {
+		let (start, len) = match self.blocks.iter_mut().next() {
+			Some((start, &mut BlockRangeState::Downloading { len, ref mut downloading }))
+				if *downloading < MAX_PARALLEL_HEAD_DOWNLOADS
+					&& *start > common
+					&& *start + len <= peer_best + One::one() =>
+			{
+				*downloading += 1;
+				(*start, len)
+			},
+			_ => return None,
+		};
+		trace!(target: "sync", "Requesting stalled range starting at {} from {}", start, who);
+		self.peer_requests.insert(who, start);
+		Some(start .. start + len)
+	}
+
 	/// Get a valid chain of blocks ordered in descending order and ready for importing into blockchain.
 	pub fn drain(&mut self, from: NumberFor<B>) -> Vec<BlockData<B>> {
 		let mut drained = Vec::new();
@@ -168,6 +206,10 @@ impl<B: BlockT> BlockCollection<B> {
 		let mut prev = from;
 		for (start, range_data) in &mut self.blocks {
 			match range_data {
+				BlockRangeState::Complete(blocks) if *start + (blocks.len() as u32).into() <= from => {
+					// Late answer for blocks that have already been drained.
+					ranges.push(*start);
+				},
 				BlockRangeState::Complete(blocks) if *start <= prev => {
 					prev = *start + (blocks.len() as u32).into();
 					// Remove all elements from `blocks` and add them to `drained`
@@ -206,9 +248,88 @@ impl<B: BlockT> BlockCollection<B> {
 	}
 }
 
+/// Number of blocks to request from each peer, adapted to the throughput observed for it.
+pub struct PeerRequestSizes {
+	/// Upper bound of any request size.
+	max: u32,
+	peers: HashMap<PeerId, PeerRequestSize>,
+}
+
+struct PeerRequestSize {
+	/// Number of blocks to ask for in the next request.
+	size: u32,
+	/// When the request in flight was sent, and how many blocks it asked for.
+	pending: Option<(Instant, u32)>,
+}
+
+impl PeerRequestSizes {
+	/// Create a new instance. Peers start with requests of `max` blocks.
+	pub fn new(max: u32) -> Self {
+		PeerRequestSizes {
+			max,
+			peers: HashMap::new(),
+		}
+	}
+
+	/// Number of blocks to request from `who`.
+	pub fn size(&self, who: &PeerId) -> u32 {
+		self.peers.get(who).map_or(self.max, |p| p.size)
+	}
+
+	/// Note that a request for `count` blocks has been sent to `who`.
+	pub fn on_request(&mut self, who: &PeerId, count: u32) {
+		let max = self.max;
+		let peer = self.peers.entry(who.clone())
+			.or_insert_with(|| PeerRequestSize { size: max, pending: None });
+		peer.pending = Some((Instant::now(), count));
+	}
+
+	/// Note that `who` answered its pending request with `received` blocks.
+	pub fn on_response(&mut self, who: &PeerId, received: u32) {
+		let max = self.max;
+		if let Some(peer) = self.peers.get_mut(who) {
+			if let Some((sent, requested)) = peer.pending.take() {
+				let size = next_request_size(peer.size, max, requested, received, sent.elapsed());
+				if size != peer.size {
+					trace!(target: "sync", "Request size for {}: {} -> {}", who, peer.size, size);
+				}
+				peer.size = size;
+			}
+		}
+	}
+
+	/// Forget everything about `who`.
+	pub fn remove(&mut self, who: &PeerId) {
+		self.peers.remove(who);
+	}
+}
+
+/// Compute the size of the next request to a peer that answered `received` out of `requested`
+/// blocks in `elapsed`.
+///
+/// The size moves towards the number of blocks that the peer can deliver within
+/// `TARGET_RESPONSE_TIME`, but at most doubles or halves at each step.
+fn next_request_size(current: u32, max: u32, requested: u32, received: u32, elapsed: Duration) -> u32 {
+	if received >= requested && requested < current {
+		// The request has been cropped, e.g. to the best block of the peer. Its timing doesn't
+		// tell anything about larger requests.
+		return current;
+	}
+	let elapsed_ms = cmp::max(elapsed.as_millis(), 1);
+	let ideal = u128::from(received) * TARGET_RESPONSE_TIME.as_millis() / elapsed_ms;
+	let ideal = cmp::min(ideal, u128::from(u32::max_value())) as u32;
+	let mut size = cmp::max(cmp::min(ideal, current.saturating_mul(2)), current / 2);
+	if received < requested {
+		// The peer doesn't serve that many blocks at once.
+		size = cmp::min(size, received);
+	}
+	cmp::min(cmp::max(size, MIN_BLOCKS_PER_REQUEST), max)
+}
+
 #[cfg(test)]
 mod test {
-	use super::{BlockCollection, BlockData, BlockRangeState};
+	use super::{BlockCollection, BlockData, BlockRangeState, next_request_size, MIN_BLOCKS_PER_REQUEST};
+	use std::time::Duration;
 	use crate::{protocol::message, PeerId};
 	use sp_runtime::testing::{Block as RawBlock, ExtrinsicWrapper};
 	use sp_core::H256;
@@ -304,4 +425,58 @@ mod test {
 		assert_eq!(bc.needed_blocks(peer0.clone(), 128, 10000, 600, 1, 200), None); // too far ahead
 		assert_eq!(bc.needed_blocks(peer0.clone(), 128, 10000, 600, 1, 200000), Some(100 + 128 .. 100 + 128 + 128));
 	}
+
+	#[test]
+	fn stalled_head_range_is_requested_from_another_peer() {
+		let mut bc: BlockCollection<Block> = BlockCollection::new();
+		let peer0 = PeerId::random();
+		let peer1 = PeerId::random();
+		let peer2 = PeerId::random();
+
+		assert_eq!(bc.needed_blocks(peer0.clone(), 40, 150, 0, 1, 40), Some(1 .. 41));
+		assert_eq!(bc.needed_blocks(peer1.clone(), 40, 150, 0, 1, 40), Some(41 .. 81));
+		bc.clear_peer_download(&peer1);
+		bc.insert(41, generate_blocks(40), peer1.clone());
+
+		// Everything after the first range is buffered, so the range is also asked from a second peer.
+		assert_eq!(bc.needed_blocks(peer1.clone(), 40, 150, 0, 1, 40), Some(1 .. 41));
+		// But not from a third one.
+		assert_eq!(bc.needed_blocks(peer2.clone(), 40, 150, 0, 1, 40), None);
+
+		// The first answer completes the range.
+		bc.clear_peer_download(&peer0);
+		let blocks = generate_blocks(40);
+		bc.insert(1, blocks.clone(), peer0.clone());
+		let drained = bc.drain(1);
+		assert_eq!(drained.len(), 80);
+		assert_eq!(drained[..40], blocks.iter()
+			.map(|b| BlockData { block: b.clone(), origin: Some(peer0.clone()) }).collect::<Vec<_>>()[..]);
+
+		// The late answer is dropped without holding back the following blocks.
+		assert_eq!(bc.needed_blocks(peer0.clone(), 40, 150, 80, 1, 40), Some(81 .. 121));
+		bc.clear_peer_download(&peer0);
+		bc.insert(81, generate_blocks(40), peer0.clone());
+		bc.clear_peer_download(&peer1);
+		bc.insert(1, generate_blocks(40), peer1.clone());
+		assert_eq!(bc.drain(81).len(), 40);
+		assert!(is_empty(&bc));
+	}
+
+	#[test]
+	fn request_size_adapts_to_throughput() {
+		let secs = Duration::from_secs;
+
+		// Fast peers get larger requests, up to the maximum.
+		assert_eq!(next_request_size(32, 128, 32, 32, Duration::from_millis(100)), 64);
+		assert_eq!(next_request_size(128, 128, 128, 128, Duration::from_millis(100)), 128);
+		// Slow peers get smaller requests, down to the minimum.
+		assert_eq!(next_request_size(128, 128, 128, 128, secs(8)), 64);
+		assert_eq!(next_request_size(128, 128, 128, 128, secs(3)), 85);
+		assert_eq!(next_request_size(MIN_BLOCKS_PER_REQUEST, 128, 8, 8, secs(60)), MIN_BLOCKS_PER_REQUEST);
+		// Truncated responses cap the size at what the peer is willing to send.
+		assert_eq!(next_request_size(128, 128, 128, 64, Duration::from_millis(100)), 64);
+		assert_eq!(next_request_size(128, 128, 128, 0, secs(1)), MIN_BLOCKS_PER_REQUEST);
+		// Requests cropped to the peer's best block don't change anything.
+		assert_eq!(next_request_size(128, 128, 3, 3, Duration::from_millis(100)), 128);
+	}
 }
@@ -0,0 +1,745 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Collection of generic request-response protocols.
+//!
+//! Each protocol is identified by a name and has its own size limits and timeout. Requests and
+//! responses are opaque bytes: encoding and decoding them is the responsibility of the user.
+//!
+//! Every request is sent on a separate substream, which gets closed after the response has been
+//! sent back. On the wire, both the request and the response are prefixed with their length,
+//! encoded as an unsigned varint.
+//!
+//! Incoming requests are forwarded to the channel passed in the [`ProtocolConfig`]. The user is
+//! expected to answer them by sending the response on the [`IncomingRequest::pending_response`]
+//! channel before the timeout of the protocol expires. If no channel is configured, the protocol
+//! is outbound-only and incoming requests are refused during the protocol negotiation.
+
+use bytes::Bytes;
+use futures::{
+	channel::{mpsc, oneshot},
+	future::{self, BoxFuture, Either},
+	prelude::*,
+	stream::FuturesUnordered,
+};
+use futures_timer::Delay;
+use libp2p::{
+	core::{
+		ConnectedPoint,
+		Multiaddr,
+		PeerId,
+		connection::ConnectionId,
+		upgrade::{InboundUpgrade, OutboundUpgrade, ReadOneError, UpgradeInfo},
+		upgrade::{read_one, write_one},
+	},
+	swarm::{
+		NegotiatedSubstream,
+		NetworkBehaviour,
+		NetworkBehaviourAction,
+		NotifyHandler,
+		OneShotHandler,
+		OneShotHandlerConfig,
+		PollParameters,
+		SubstreamProtocol,
+	},
+};
+use std::{
+	borrow::Cow,
+	collections::{HashMap, VecDeque},
+	pin::Pin,
+	task::{Context, Poll},
+	time::Duration,
+};
+use wasm_timer::Instant;
+
+/// Configuration for a single request-response protocol.
+#[derive(Debug, Clone)]
+pub struct ProtocolConfig {
+	/// Name of the protocol on the wire. Should be something like `/foo/bar/1`.
+	pub name: Cow<'static, str>,
+
+	/// Maximum allowed size, in bytes, of a request.
+	///
+	/// Any request larger than this value will be rejected, both when sent and when received.
+	pub max_request_size: u64,
+
+	/// Maximum allowed size, in bytes, of a response.
+	///
+	/// Any response larger than this value will be rejected, both when sent and when received.
+	pub max_response_size: u64,
+
+	/// Duration after which an emitted request is considered as failed, and after which an
+	/// incoming request that hasn't been answered is dropped.
+	pub request_timeout: Duration,
+
+	/// Channel on which incoming requests are sent.
+	///
+	/// If `None`, the local node doesn't support this protocol for incoming requests and only
+	/// emits outgoing requests.
+	///
+	/// When an incoming request arrives and the channel is full, the request is refused. The
+	/// capacity of the channel is therefore the maximum number of requests that can be queued
+	/// for this protocol.
+	pub inbound_queue: Option<mpsc::Sender<IncomingRequest>>,
+}
+
+/// A single request received by a peer on a request-response protocol.
+#[derive(Debug)]
+pub struct IncomingRequest {
+	/// Who sent the request.
+	pub peer: PeerId,
+
+	/// Request sent by the remote. Will always be smaller than
+	/// [`ProtocolConfig::max_request_size`].
+	pub payload: Vec<u8>,
+
+	/// Channel to send back the response to.
+	///
+	/// Dropping this sender, or failing to answer before [`ProtocolConfig::request_timeout`],
+	/// closes the substream without answering.
+	pub pending_response: oneshot::Sender<Vec<u8>>,
+}
+
+/// Error in a request sent to a remote.
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::Display)]
+pub enum RequestFailure {
+	/// We are not connected to the requested peer.
+	#[display(fmt = "Not connected to the requested peer")]
+	NotConnected,
+	/// The given protocol hasn't been registered.
+	#[display(fmt = "Request-response protocol not registered")]
+	UnknownProtocol,
+	/// The request is larger than the maximum request size of the protocol.
+	#[display(fmt = "Request exceeds the maximum size of the protocol")]
+	RequestTooLarge,
+	/// The connection with the remote has been closed before the response arrived.
+	#[display(fmt = "Connection closed before the response arrived")]
+	ConnectionClosed,
+	/// The remote didn't answer the request in time.
+	#[display(fmt = "Request timeout")]
+	Timeout,
+	/// The request has been dropped locally before it finished.
+	#[display(fmt = "Request obsolete")]
+	Obsolete,
+}
+
+impl std::error::Error for RequestFailure {}
+
+/// Error when answering a request received from a remote.
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::Display)]
+pub enum ResponseFailure {
+	/// The queue of incoming requests of the protocol was full or closed.
+	#[display(fmt = "Inbound queue of the protocol is full or closed")]
+	Busy,
+	/// The user dropped the [`IncomingRequest::pending_response`] channel without answering.
+	#[display(fmt = "Request dropped without an answer")]
+	Omitted,
+	/// The user didn't answer the request in time.
+	#[display(fmt = "Response timeout")]
+	Timeout,
+	/// Error while writing the response on the substream.
+	#[display(fmt = "Failed to send the response: {}", _0)]
+	Network(String),
+}
+
+impl std::error::Error for ResponseFailure {}
+
+/// Error when registering a protocol.
+#[derive(Debug, derive_more::Display)]
+pub enum RegisterError {
+	/// A protocol has been specified multiple times.
+	#[display(fmt = "Request-response protocol registered twice: {}", _0)]
+	DuplicateProtocol(Cow<'static, str>),
+}
+
+impl std::error::Error for RegisterError {}
+
+/// Event generated by the [`RequestResponsesBehaviour`].
+#[derive(Debug)]
+pub enum Event {
+	/// A remote sent a request and either we have successfully answered it or an error happened.
+	InboundRequest {
+		/// Peer which has emitted the request.
+		peer: PeerId,
+		/// Name of the protocol in question.
+		protocol: Cow<'static, str>,
+		/// If `Ok`, time elapsed between when we received the request and when we sent back the
+		/// response.
+		result: Result<Duration, ResponseFailure>,
+		/// Size in bytes of the request.
+		request_size: usize,
+		/// Size in bytes of the response, or 0 if no response has been sent.
+		response_size: usize,
+	},
+
+	/// A request has been sent to a remote.
+	RequestStarted {
+		/// Peer that we sent the request to.
+		peer: PeerId,
+		/// Name of the protocol in question.
+		protocol: Cow<'static, str>,
+	},
+
+	/// A request initiated using [`RequestResponsesBehaviour::send_request`] has finished,
+	/// successfully or not.
+	///
+	/// The outcome has also been reported on the `pending_response` channel.
+	RequestFinished {
+		/// Peer that we sent the request to.
+		peer: PeerId,
+		/// Name of the protocol in question.
+		protocol: Cow<'static, str>,
+		/// Time elapsed between the start of the request and the end.
+		duration: Duration,
+		/// Result of the request.
+		result: Result<(), RequestFailure>,
+		/// Size in bytes of the request.
+		request_size: usize,
+		/// Size in bytes of the response, or 0 if no response has been received.
+		response_size: usize,
+	},
+}
+
+/// Implementation of `NetworkBehaviour` that provides support for request-response protocols.
+pub struct RequestResponsesBehaviour {
+	/// Registered protocols, indexed by their name on the wire.
+	protocols: HashMap<Bytes, ProtocolDetails>,
+	/// List of all active connections of each peer.
+	peers: HashMap<PeerId, Vec<ConnectionId>>,
+	/// Requests we have sent and that are waiting for a response.
+	pending_requests: HashMap<u64, PendingRequest>,
+	/// Identifier to assign to the next outgoing request.
+	next_request_id: u64,
+	/// Futures answering incoming requests. Each resolves once the response has been sent back
+	/// or the request has been abandoned.
+	pending_responses: FuturesUnordered<BoxFuture<'static, AnsweredRequest>>,
+	/// Events to return as soon as possible from `poll`.
+	pending_events: VecDeque<NetworkBehaviourAction<OutboundProtocol, Event>>,
+}
+
+/// Outcome of answering an incoming request.
+struct AnsweredRequest {
+	peer: PeerId,
+	protocol: Cow<'static, str>,
+	result: Result<Duration, ResponseFailure>,
+	request_size: usize,
+	response_size: usize,
+}
+
+/// Local information about a registered protocol.
+struct ProtocolDetails {
+	name: Cow<'static, str>,
+	max_request_size: u64,
+	max_response_size: u64,
+	request_timeout: Duration,
+	inbound_queue: Option<mpsc::Sender<IncomingRequest>>,
+}
+
+/// Request emitted by the local node and waiting for a response.
+struct PendingRequest {
+	peer: PeerId,
+	connection: ConnectionId,
+	protocol: Cow<'static, str>,
+	/// `Instant` when the request has been emitted. Used for diagnostic purposes.
+	emitted: Instant,
+	/// Size in bytes of the request. Used for diagnostic purposes.
+	request_size: usize,
+	timeout: Delay,
+	pending_response: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+}
+
+impl RequestResponsesBehaviour {
+	/// Creates a new behaviour. Must be passed a list of supported protocols. Returns an error if
+	/// the same protocol is passed twice.
+	pub fn new(list: impl IntoIterator<Item = ProtocolConfig>) -> Result<Self, RegisterError> {
+		let mut behaviour = RequestResponsesBehaviour {
+			protocols: HashMap::new(),
+			peers: HashMap::new(),
+			pending_requests: HashMap::new(),
+			next_request_id: 0,
+			pending_responses: FuturesUnordered::new(),
+			pending_events: VecDeque::new(),
+		};
+
+		for protocol in list {
+			behaviour.register_protocol(protocol)?;
+		}
+
+		Ok(behaviour)
+	}
+
+	/// Registers a new request-response protocol.
+	///
+	/// Connections that are already open retain the list of protocols that were registered when
+	/// they were opened. You are therefore strongly encouraged to register protocols early on.
+	pub fn register_protocol(&mut self, config: ProtocolConfig) -> Result<(), RegisterError> {
+		let wire_name = Bytes::from(config.name.as_bytes().to_vec());
+		if self.protocols.contains_key(&wire_name) {
+			return Err(RegisterError::DuplicateProtocol(config.name));
+		}
+
+		self.protocols.insert(wire_name, ProtocolDetails {
+			name: config.name,
+			max_request_size: config.max_request_size,
+			max_response_size: config.max_response_size,
+			request_timeout: config.request_timeout,
+			inbound_queue: config.inbound_queue,
+		});
+		Ok(())
+	}
+
+	/// Initiates sending a request.
+	///
+	/// The outcome of the request is reported on `pending_response`. An error is sent back
+	/// immediately if we aren't connected to `target` or if `protocol` isn't registered.
+	pub fn send_request(
+		&mut self,
+		target: &PeerId,
+		protocol: &str,
+		request: Vec<u8>,
+		pending_response: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+	) {
+		let details = match self.protocols.get(protocol.as_bytes()) {
+			Some(details) => details,
+			None => {
+				log::debug!(target: "sub-libp2p", "Request on unknown protocol {}", protocol);
+				let _ = pending_response.send(Err(RequestFailure::UnknownProtocol));
+				return;
+			}
+		};
+
+		if request.len() as u64 > details.max_request_size {
+			log::debug!(
+				target: "sub-libp2p",
+				"Refusing to send request of {} bytes on {}: limit is {} bytes",
+				request.len(), protocol, details.max_request_size
+			);
+			let _ = pending_response.send(Err(RequestFailure::RequestTooLarge));
+			return;
+		}
+
+		let connection = match self.peers.get(target).and_then(|c| c.first()) {
+			Some(connection) => *connection,
+			None => {
+				let _ = pending_response.send(Err(RequestFailure::NotConnected));
+				return;
+			}
+		};
+
+		let request_id = self.next_request_id;
+		self.next_request_id = self.next_request_id.wrapping_add(1);
+
+		self.pending_requests.insert(request_id, PendingRequest {
+			peer: target.clone(),
+			connection,
+			protocol: details.name.clone(),
+			emitted: Instant::now(),
+			request_size: request.len(),
+			timeout: Delay::new(details.request_timeout),
+			pending_response,
+		});
+
+		log::trace!(target: "sub-libp2p", "Enqueueing request #{} to {} on {}", request_id, target, protocol);
+		self.pending_events.push_back(NetworkBehaviourAction::NotifyHandler {
+			peer_id: target.clone(),
+			handler: NotifyHandler::One(connection),
+			event: OutboundProtocol {
+				protocol: Bytes::from(protocol.as_bytes().to_vec()),
+				request,
+				max_response_size: details.max_response_size,
+				request_id,
+			},
+		});
+		self.pending_events.push_back(NetworkBehaviourAction::GenerateEvent(Event::RequestStarted {
+			peer: target.clone(),
+			protocol: details.name.clone(),
+		}));
+	}
+
+	/// Removes a pending request and reports its outcome.
+	fn finish_request(&mut self, request_id: u64, result: Result<Vec<u8>, RequestFailure>) {
+		let request = match self.pending_requests.remove(&request_id) {
+			Some(request) => request,
+			None => return,
+		};
+
+		let event_result = result.as_ref().map(|_| ()).map_err(|err| err.clone());
+		let response_size = result.as_ref().map_or(0, |response| response.len());
+		let _ = request.pending_response.send(result);
+		self.pending_events.push_back(NetworkBehaviourAction::GenerateEvent(Event::RequestFinished {
+			peer: request.peer,
+			protocol: request.protocol,
+			duration: request.emitted.elapsed(),
+			result: event_result,
+			request_size: request.request_size,
+			response_size,
+		}));
+	}
+
+	/// Handles a request that has been received from a remote on the given protocol.
+	fn on_request(
+		&mut self,
+		peer: PeerId,
+		protocol: Bytes,
+		payload: Vec<u8>,
+		mut stream: NegotiatedSubstream,
+		handling_start: Instant,
+	) {
+		let details = match self.protocols.get_mut(&protocol) {
+			Some(details) => details,
+			None => {
+				log::error!(target: "sub-libp2p", "State inconsistency: request on unknown protocol");
+				return;
+			}
+		};
+
+		let name = details.name.clone();
+		let request_size = payload.len();
+		let (tx, rx) = oneshot::channel();
+		let sent = details.inbound_queue.as_mut()
+			.map(|queue| queue.try_send(IncomingRequest {
+				peer: peer.clone(),
+				payload,
+				pending_response: tx,
+			}).is_ok())
+			.unwrap_or(false);
+
+		if !sent {
+			log::debug!(
+				target: "sub-libp2p",
+				"Dropping request from {} on {}: inbound queue is full or closed",
+				peer, name
+			);
+			self.pending_events.push_back(NetworkBehaviourAction::GenerateEvent(Event::InboundRequest {
+				peer,
+				protocol: name,
+				result: Err(ResponseFailure::Busy),
+				request_size,
+				response_size: 0,
+			}));
+			return;
+		}
+
+		let timeout = Delay::new(details.request_timeout);
+		self.pending_responses.push(async move {
+			let mut response_size = 0;
+			let result = match future::select(rx, timeout).await {
+				Either::Left((Ok(response), _)) => {
+					response_size = response.len();
+					match write_one(&mut stream, response).await {
+						Ok(()) => Ok(handling_start.elapsed()),
+						Err(err) => Err(ResponseFailure::Network(err.to_string())),
+					}
+				},
+				Either::Left((Err(_), _)) => Err(ResponseFailure::Omitted),
+				Either::Right(_) => Err(ResponseFailure::Timeout),
+			};
+			AnsweredRequest { peer, protocol: name, result, request_size, response_size }
+		}.boxed());
+	}
+}
+
+impl NetworkBehaviour for RequestResponsesBehaviour {
+	type ProtocolsHandler = OneShotHandler<InboundProtocol, OutboundProtocol, NodeEvent<NegotiatedSubstream>>;
+	type OutEvent = Event;
+
+	fn new_handler(&mut self) -> Self::ProtocolsHandler {
+		let protocols = self.protocols.iter()
+			.filter(|(_, details)| details.inbound_queue.is_some())
+			.map(|(name, details)| (name.clone(), details.max_request_size))
+			.collect();
+		let max_timeout = self.protocols.values()
+			.map(|details| details.request_timeout)
+			.max()
+			.unwrap_or_else(|| Duration::from_secs(10));
+
+		let mut cfg = OneShotHandlerConfig::default();
+		cfg.keep_alive_timeout = max_timeout;
+		cfg.outbound_substream_timeout = max_timeout;
+		OneShotHandler::new(SubstreamProtocol::new(InboundProtocol { protocols }), cfg)
+	}
+
+	fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+		Vec::new()
+	}
+
+	fn inject_connected(&mut self, _peer: &PeerId) {
+	}
+
+	fn inject_disconnected(&mut self, _peer: &PeerId) {
+	}
+
+	fn inject_connection_established(&mut self, peer_id: &PeerId, id: &ConnectionId, _: &ConnectedPoint) {
+		self.peers.entry(peer_id.clone()).or_default().push(*id);
+	}
+
+	fn inject_connection_closed(&mut self, peer_id: &PeerId, id: &ConnectionId, _: &ConnectedPoint) {
+		if let Some(connections) = self.peers.get_mut(peer_id) {
+			connections.retain(|c| c != id);
+			if connections.is_empty() {
+				self.peers.remove(peer_id);
+			}
+		} else {
+			log::error!(
+				target: "sub-libp2p",
+				"State inconsistency: peer_id not found in list of connections"
+			);
+		}
+
+		let closed = self.pending_requests.iter()
+			.filter(|(_, request)| request.connection == *id)
+			.map(|(request_id, _)| *request_id)
+			.collect::<Vec<_>>();
+		for request_id in closed {
+			self.finish_request(request_id, Err(RequestFailure::ConnectionClosed));
+		}
+	}
+
+	fn inject_event(
+		&mut self,
+		peer: PeerId,
+		_connection: ConnectionId,
+		event: NodeEvent<NegotiatedSubstream>,
+	) {
+		match event {
+			NodeEvent::Request { protocol, payload, stream, handling_start } =>
+				self.on_request(peer, protocol, payload, stream, handling_start),
+			NodeEvent::Response { request_id, response } => {
+				if !self.pending_requests.contains_key(&request_id) {
+					// The request might have timed out in the meantime.
+					log::trace!(
+						target: "sub-libp2p",
+						"Response from {} discarded because it concerns an obsolete request",
+						peer
+					);
+					return;
+				}
+				self.finish_request(request_id, Ok(response));
+			}
+		}
+	}
+
+	fn poll(&mut self, cx: &mut Context, _: &mut impl PollParameters)
+		-> Poll<NetworkBehaviourAction<OutboundProtocol, Event>>
+	{
+		if let Some(ev) = self.pending_events.pop_front() {
+			return Poll::Ready(ev);
+		}
+
+		// Check the request timeouts, and whether the user is still interested in the response.
+		let mut timed_out = Vec::new();
+		let mut obsolete = Vec::new();
+		for (request_id, request) in &mut self.pending_requests {
+			if request.pending_response.is_canceled() {
+				obsolete.push(*request_id);
+			} else if let Poll::Ready(()) = Pin::new(&mut request.timeout).poll(cx) {
+				timed_out.push(*request_id);
+			}
+		}
+		for request_id in timed_out {
+			log::debug!(target: "sub-libp2p", "Request #{} timed out", request_id);
+			self.finish_request(request_id, Err(RequestFailure::Timeout));
+		}
+		for request_id in obsolete {
+			self.finish_request(request_id, Err(RequestFailure::Obsolete));
+		}
+
+		if let Poll::Ready(Some(answered)) = self.pending_responses.poll_next_unpin(cx) {
+			if let Err(err) = &answered.result {
+				log::debug!(
+					target: "sub-libp2p",
+					"Failed to answer request from {} on {}: {}",
+					answered.peer, answered.protocol, err
+				);
+			}
+			self.pending_events.push_back(NetworkBehaviourAction::GenerateEvent(Event::InboundRequest {
+				peer: answered.peer,
+				protocol: answered.protocol,
+				result: answered.result,
+				request_size: answered.request_size,
+				response_size: answered.response_size,
+			}));
+		}
+
+		if let Some(ev) = self.pending_events.pop_front() {
+			return Poll::Ready(ev);
+		}
+
+		Poll::Pending
+	}
+}
+
+/// Output type of inbound and outbound substream upgrades.
+#[derive(Debug)]
+pub enum NodeEvent<T> {
+	/// Incoming request from remote, along with the substream to use for the response and when
+	/// we started handling this request.
+	Request {
+		protocol: Bytes,
+		payload: Vec<u8>,
+		stream: T,
+		handling_start: Instant,
+	},
+	/// Incoming response from remote to one of our requests.
+	Response {
+		request_id: u64,
+		response: Vec<u8>,
+	},
+}
+
+/// Substream upgrade protocol for incoming requests.
+///
+/// Advertises all the protocols that accept incoming requests, and reads the request using the
+/// size limit of the protocol that has been negotiated.
+#[derive(Debug, Clone)]
+pub struct InboundProtocol {
+	/// Names of the supported protocols, and their max. request length in bytes.
+	protocols: Vec<(Bytes, u64)>,
+}
+
+impl UpgradeInfo for InboundProtocol {
+	type Info = Bytes;
+	type InfoIter = std::vec::IntoIter<Self::Info>;
+
+	fn protocol_info(&self) -> Self::InfoIter {
+		self.protocols.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>().into_iter()
+	}
+}
+
+impl<T> InboundUpgrade<T> for InboundProtocol
+where
+	T: AsyncRead + AsyncWrite + Unpin + Send + 'static
+{
+	type Output = NodeEvent<T>;
+	type Error = ReadOneError;
+	type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+	fn upgrade_inbound(self, mut s: T, protocol: Self::Info) -> Self::Future {
+		// This `Instant` will be passed around until the processing of this request is done.
+		let handling_start = Instant::now();
+
+		let max_request_size = self.protocols.iter()
+			.find(|(name, _)| *name == protocol)
+			.map(|(_, max)| *max)
+			.unwrap_or(0);
+
+		async move {
+			let payload = read_one(&mut s, max_request_size as usize).await?;
+			Ok(NodeEvent::Request { protocol, payload, stream: s, handling_start })
+		}.boxed()
+	}
+}
+
+/// Substream upgrade protocol for outgoing requests.
+///
+/// Sends a request to remote and awaits the response.
+#[derive(Debug, Clone)]
+pub struct OutboundProtocol {
+	/// The protocol to use for upgrade negotiation.
+	protocol: Bytes,
+	/// The serialized request.
+	request: Vec<u8>,
+	/// The max. response length in bytes.
+	max_response_size: u64,
+	/// Identifier of the request. Passed back through the API when the response comes back.
+	request_id: u64,
+}
+
+impl UpgradeInfo for OutboundProtocol {
+	type Info = Bytes;
+	type InfoIter = std::iter::Once<Self::Info>;
+
+	fn protocol_info(&self) -> Self::InfoIter {
+		std::iter::once(self.protocol.clone())
+	}
+}
+
+impl<T> OutboundUpgrade<T> for OutboundProtocol
+where
+	T: AsyncRead + AsyncWrite + Unpin + Send + 'static
+{
+	type Output = NodeEvent<T>;
+	type Error = ReadOneError;
+	type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+	fn upgrade_outbound(self, mut s: T, _: Self::Info) -> Self::Future {
+		async move {
+			write_one(&mut s, &self.request).await?;
+			let response = read_one(&mut s, self.max_response_size as usize).await?;
+			Ok(NodeEvent::Response { request_id: self.request_id, response })
+		}.boxed()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use libp2p::swarm::ProtocolsHandler;
+
+	fn config(name: &'static str, inbound_queue: Option<mpsc::Sender<IncomingRequest>>) -> ProtocolConfig {
+		ProtocolConfig {
+			name: From::from(name),
+			max_request_size: 1024,
+			max_response_size: 1024,
+			request_timeout: Duration::from_secs(10),
+			inbound_queue,
+		}
+	}
+
+	#[test]
+	fn registering_same_protocol_twice_fails() {
+		let result = RequestResponsesBehaviour::new(vec![config("/foo/1", None), config("/foo/1", None)]);
+		match result {
+			Err(RegisterError::DuplicateProtocol(name)) => assert_eq!(name, "/foo/1"),
+			_ => panic!("duplicate protocol should be rejected"),
+		}
+	}
+
+	#[test]
+	fn only_inbound_protocols_are_advertised() {
+		let (tx, _rx) = mpsc::channel(1);
+		let mut behaviour = RequestResponsesBehaviour::new(vec![
+			config("/in/1", Some(tx)),
+			config("/out/1", None),
+		]).unwrap();
+
+		let handler = behaviour.new_handler();
+		let names = handler.listen_protocol().upgrade().protocol_info().collect::<Vec<_>>();
+		assert_eq!(names, vec![Bytes::from_static(b"/in/1")]);
+	}
+
+	#[test]
+	fn request_fails_when_not_connected_or_unknown() {
+		let mut behaviour = RequestResponsesBehaviour::new(vec![config("/foo/1", None)]).unwrap();
+		let peer = PeerId::random();
+
+		let (tx, mut rx) = oneshot::channel();
+		behaviour.send_request(&peer, "/foo/1", vec![1, 2, 3], tx);
+		assert_eq!(rx.try_recv().unwrap(), Some(Err(RequestFailure::NotConnected)));
+
+		let (tx, mut rx) = oneshot::channel();
+		behaviour.send_request(&peer, "/bar/1", vec![1, 2, 3], tx);
+		assert_eq!(rx.try_recv().unwrap(), Some(Err(RequestFailure::UnknownProtocol)));
+
+		let (tx, mut rx) = oneshot::channel();
+		behaviour.send_request(&peer, "/foo/1", vec![0; 2048], tx);
+		assert_eq!(rx.try_recv().unwrap(), Some(Err(RequestFailure::RequestTooLarge)));
+
+		assert!(behaviour.pending_events.is_empty());
+	}
+}
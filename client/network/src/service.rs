@@ -30,7 +30,7 @@
 use crate::{
 	ExHashT, NetworkStateInfo,
 	behaviour::{Behaviour, BehaviourOut},
-	config::{parse_str_addr, NonReservedPeerMode, Params, Role, TransportConfig},
+	config::{parse_str_addr, NonReservedPeerMode, Params, RequestResponseConfig, Role, TransportConfig},
 	DhtEvent,
 	discovery::DiscoveryConfig,
 	error::Error,
@@ -38,17 +38,19 @@ use crate::{
 		NetworkState, NotConnectedPeer as NetworkStateNotConnectedPeer, Peer as NetworkStatePeer,
 	},
 	on_demand_layer::AlwaysBadChecker,
-	light_client_handler, block_requests, finality_requests,
+	light_client_handler, block_request_handler, finality_requests,
 	protocol::{self, event::Event, NotifsHandlerError, LegacyConnectionKillError, NotificationsSink, Ready, sync::SyncState, PeerInfo, Protocol},
-	transport, ReputationChange,
+	request_responses::RequestFailure,
+	transport, utils::interval, ReputationChange,
 };
-use futures::prelude::*;
+use futures::{channel::oneshot, future::BoxFuture, prelude::*};
+use ip_network::IpNetwork;
 use libp2p::{PeerId, multiaddr, Multiaddr};
 use libp2p::core::{ConnectedPoint, Executor, connection::{ConnectionError, PendingConnectionError}, either::EitherError};
 use libp2p::kad::record;
 use libp2p::ping::handler::PingFailure;
 use libp2p::swarm::{NetworkBehaviour, SwarmBuilder, SwarmEvent, protocols_handler::NodeHandlerWrapperError};
-use log::{error, info, trace, warn};
+use log::{debug, error, info, trace, warn};
 use parking_lot::Mutex;
 use prometheus_endpoint::{
 	register, Counter, CounterVec, Gauge, GaugeVec, Histogram, HistogramOpts, HistogramVec, Opts,
@@ -75,12 +77,18 @@ use std::{
 		Arc,
 	},
 	task::Poll,
+	time::Duration,
 };
 
+pub(crate) mod bans;
 mod out_events;
+mod traffic;
 #[cfg(test)]
 mod tests;
 
+/// Interval at which we check whether some of the manual bans have expired.
+const BANS_EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
 /// Substrate network service. Handles network IO and manages connectivity.
 pub struct NetworkService<B: BlockT + 'static, H: ExHashT> {
 	/// Number of peers we're connected to.
@@ -106,6 +114,9 @@ pub struct NetworkService<B: BlockT + 'static, H: ExHashT> {
 	/// Field extracted from the [`Metrics`] struct and necessary to report the
 	/// notifications-related metrics.
 	notifications_sizes_metric: Option<HistogramVec>,
+	/// Bytes exchanged per protocol and per peer. Updated by both the `NetworkService` and the
+	/// [`NetworkWorker`].
+	traffic: Arc<traffic::Traffic>,
 	/// Marker to pin the `H` generic. Serves no purpose except to not break backwards
 	/// compatibility.
 	_marker: PhantomData<H>,
@@ -138,9 +149,10 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 
 		let (to_worker, from_service) = tracing_unbounded("mpsc_network_worker");
 
-		if let Some(path) = params.network_config.net_config_path {
-			fs::create_dir_all(&path)?;
+		if let Some(path) = &params.network_config.net_config_path {
+			fs::create_dir_all(path)?;
 		}
+		let bans = bans::BanList::load(params.network_config.net_config_path.as_deref());
 
 		// List of multiaddresses that we know in the network.
 		let mut known_addresses = Vec::new();
@@ -264,6 +276,20 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 			boot_node_ids.clone(),
 		)?;
 
+		let (block_request_handler, block_request_protocol_config) = {
+			let mut config = block_request_handler::Config::default();
+			config
+				.set_max_requests_per_window(params.network_config.max_block_requests_per_peer)
+				.set_max_bytes_per_window(params.network_config.max_block_response_bytes_per_peer);
+			block_request_handler::BlockRequestHandler::new(
+				config,
+				&params.protocol_id,
+				params.chain.clone(),
+				peerset_handle.clone(),
+				params.metrics_registry.as_ref(),
+			)?
+		};
+
 		// Build the swarm.
 		let (mut swarm, bandwidth): (Swarm<B, H>, _) = {
 			let user_agent = format!(
@@ -271,22 +297,20 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 				params.network_config.client_version,
 				params.network_config.node_name
 			);
-			let block_requests = {
-				let config = block_requests::Config::new(&params.protocol_id);
-				block_requests::BlockRequests::new(config, params.chain.clone())
-			};
 			let finality_proof_requests = {
 				let config = finality_requests::Config::new(&params.protocol_id);
 				finality_requests::FinalityProofRequests::new(config, params.finality_proof_provider.clone())
 			};
 			let light_client_handler = {
-				let config = light_client_handler::Config::new(&params.protocol_id);
+				let mut config = light_client_handler::Config::new(&params.protocol_id);
+				config.set_max_requests_per_peer(params.network_config.max_light_requests_per_peer as usize);
 				light_client_handler::LightClientHandler::new(
 					config,
 					params.chain,
 					checker,
 					peerset_handle.clone(),
-				)
+					params.metrics_registry.as_ref(),
+				)?
 			};
 
 			let discovery_config = {
@@ -315,22 +339,35 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 				params.role,
 				user_agent,
 				local_public,
-				block_requests,
 				finality_proof_requests,
 				light_client_handler,
-				discovery_config
-			);
+				discovery_config,
+				block_request_protocol_config,
+				params.network_config.request_response_protocols,
+			).map_err(|err| Error::DuplicateRequestResponseProtocol {
+				protocol: match err {
+					crate::request_responses::RegisterError::DuplicateProtocol(protocol) => protocol,
+				},
+			})?;
 
 			for (engine_id, protocol_name) in &params.network_config.notifications_protocols {
 				behaviour.register_notifications_protocol(*engine_id, protocol_name.clone());
 			}
 			let (transport, bandwidth) = {
-				let (config_mem, config_wasm, flowctrl) = match params.network_config.transport {
-					TransportConfig::MemoryOnly => (true, None, false),
-					TransportConfig::Normal { wasm_external_transport, use_yamux_flow_control, .. } =>
-						(false, wasm_external_transport, use_yamux_flow_control)
+				let (config_mem, config_wasm, flowctrl, ws_tls) = match params.network_config.transport {
+					TransportConfig::MemoryOnly => (true, None, false, None),
+					TransportConfig::Normal {
+						wasm_external_transport, use_yamux_flow_control, websocket_tls, ..
+					} => (false, wasm_external_transport, use_yamux_flow_control, websocket_tls)
 				};
-				transport::build_transport(local_identity, config_mem, config_wasm, flowctrl)
+				transport::build_transport(
+					local_identity,
+					config_mem,
+					config_wasm,
+					flowctrl,
+					ws_tls.as_ref(),
+					bans.banned_ranges(),
+				)?
 			};
 			let mut builder = SwarmBuilder::new(transport, behaviour, local_peer_id.clone())
 				.peer_connection_limit(crate::MAX_CONNECTIONS_PER_PEER)
@@ -348,6 +385,11 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 			(builder.build(), bandwidth)
 		};
 
+		// Restore the bans that were active before the node was restarted.
+		for peer_id in bans.banned_peers() {
+			Swarm::<B, H>::ban_peer_id(&mut swarm, peer_id.clone());
+		}
+
 		// Initialize the metrics.
 		let metrics = match &params.metrics_registry {
 			Some(registry) => {
@@ -377,6 +419,8 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 			params.network_config.notifications_protocols.iter().cloned().collect()
 		});
 
+		let traffic = Arc::new(traffic::Traffic::new(params.metrics_registry.as_ref())?);
+
 		let service = Arc::new(NetworkService {
 			bandwidth,
 			external_addresses: external_addresses.clone(),
@@ -389,6 +433,7 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 			protocol_name_by_engine,
 			notifications_sizes_metric:
 				metrics.as_ref().map(|metrics| metrics.notifications_sizes.clone()),
+			traffic,
 			_marker: PhantomData,
 		});
 
@@ -405,6 +450,10 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 			peers_notifications_sinks,
 			metrics,
 			boot_node_ids,
+			block_request_handler: block_request_handler.run().boxed().fuse(),
+			bans,
+			ip_banned_peers: HashMap::new(),
+			bans_expiry_check: Box::pin(interval(BANS_EXPIRY_CHECK_INTERVAL)),
 		})
 	}
 
@@ -463,6 +512,107 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 		self.network_service.add_known_address(peer_id, addr);
 	}
 
+	/// Returns the reputation, connection direction and slot type of all the peers known to the
+	/// peerset manager.
+	pub fn peers_state(&mut self) -> Vec<sc_peerset::PeerState> {
+		self.network_service.user_protocol_mut().peerset_peers_state()
+	}
+
+	/// Bans a peer for the given duration and records the ban.
+	fn ban_peer(&mut self, peer_id: PeerId, duration: Duration) {
+		debug!(target: "sub-libp2p", "Manually banning {:?} for {:?}", peer_id, duration);
+		self.bans.ban_peer(peer_id.clone(), duration);
+		Swarm::<B, H>::ban_peer_id(&mut self.network_service, peer_id);
+	}
+
+	/// Lifts the manual ban of a peer, unless its address is in a banned IP range.
+	fn unban_peer(&mut self, peer_id: PeerId) {
+		if self.bans.unban_peer(&peer_id) && !self.ip_banned_peers.contains_key(&peer_id) {
+			debug!(target: "sub-libp2p", "Lifting the ban of {:?}", peer_id);
+			Swarm::<B, H>::unban_peer_id(&mut self.network_service, peer_id);
+		}
+	}
+
+	/// Bans an IP range for the given duration, and bans the peers that we are connected to
+	/// through an address of that range.
+	fn ban_ip_range(&mut self, range: IpNetwork, duration: Duration) {
+		debug!(target: "sub-libp2p", "Manually banning IP range {} for {:?}", range, duration);
+		self.bans.ban_ip_range(range, duration);
+
+		// Every connection is checked, including the ones of peers that haven't opened the
+		// substream of the legacy protocol, and the secondary connections of peers.
+		let connected = self.network_service.connected_addresses()
+			.map(|(peer_id, address)| (peer_id.clone(), address.clone()))
+			.collect::<Vec<_>>();
+		for (peer_id, address) in connected {
+			if !self.ip_banned_peers.contains_key(&peer_id) {
+				self.check_ip_range_ban(peer_id, &address);
+			}
+		}
+	}
+
+	/// Lifts the ban of an IP range.
+	fn unban_ip_range(&mut self, range: IpNetwork) {
+		if self.bans.unban_ip_range(&range) {
+			debug!(target: "sub-libp2p", "Lifting the ban of IP range {}", range);
+			self.lift_ip_range_bans(&range);
+		}
+	}
+
+	/// Bans the given peer until it is disconnected if its address belongs to a banned IP range.
+	///
+	/// The transport refuses new connections from banned ranges, but connections established
+	/// before the range was banned, or through a DNS address, need to be closed.
+	fn check_ip_range_ban(&mut self, peer_id: PeerId, address: &Multiaddr) {
+		if let Some(range) = self.bans.banned_range_of(address) {
+			debug!(
+				target: "sub-libp2p",
+				"Banning {:?} because its address {} is in the banned range {}",
+				peer_id, address, range,
+			);
+			self.ip_banned_peers.insert(peer_id.clone(), range);
+			Swarm::<B, H>::ban_peer_id(&mut self.network_service, peer_id);
+		}
+	}
+
+	/// Unbans the peers that were banned because of the given IP range, unless they are also
+	/// banned individually.
+	fn lift_ip_range_bans(&mut self, range: &IpNetwork) {
+		let peers = self.ip_banned_peers.iter()
+			.filter(|(_, r)| *r == range)
+			.map(|(peer_id, _)| peer_id.clone())
+			.collect::<Vec<_>>();
+		for peer_id in peers {
+			self.ip_banned_peers.remove(&peer_id);
+			if !self.bans.is_peer_banned(&peer_id) {
+				Swarm::<B, H>::unban_peer_id(&mut self.network_service, peer_id);
+			}
+		}
+	}
+
+	/// Lifts the ban of a peer that was banned because of its IP address, now that it is
+	/// disconnected.
+	fn on_ip_banned_peer_disconnected(&mut self, peer_id: &PeerId) {
+		if self.ip_banned_peers.remove(peer_id).is_some() && !self.bans.is_peer_banned(peer_id) {
+			Swarm::<B, H>::unban_peer_id(&mut self.network_service, peer_id.clone());
+		}
+	}
+
+	/// Lifts the manual bans that have expired.
+	fn remove_expired_bans(&mut self) {
+		let (peers, ranges) = self.bans.remove_expired();
+		for peer_id in peers {
+			if !self.ip_banned_peers.contains_key(&peer_id) {
+				debug!(target: "sub-libp2p", "Ban of {:?} has expired", peer_id);
+				Swarm::<B, H>::unban_peer_id(&mut self.network_service, peer_id);
+			}
+		}
+		for range in ranges {
+			debug!(target: "sub-libp2p", "Ban of IP range {} has expired", range);
+			self.lift_ip_range_bans(&range);
+		}
+	}
+
 	/// Return a `NetworkService` that can be shared through the code base and can be used to
 	/// manipulate the worker.
 	pub fn service(&self) -> &Arc<NetworkService<B, H>> {
@@ -498,6 +648,7 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 	/// **Note**: Use this only for debugging. This API is unstable. There are warnings literally
 	/// everywhere about this. Please don't use this function to retrieve actual information.
 	pub fn network_state(&mut self) -> NetworkState {
+		let traffic = &self.service.traffic;
 		let swarm = &mut self.network_service;
 		let open = swarm.user_protocol().open_peers().cloned().collect::<Vec<_>>();
 
@@ -523,6 +674,7 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 					enabled: swarm.user_protocol().is_enabled(&peer_id),
 					open: swarm.user_protocol().is_open(&peer_id),
 					known_addresses,
+					bytes: traffic.peer(peer_id),
 				}))
 			}).collect()
 		};
@@ -549,6 +701,8 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 			external_addresses: Swarm::<B, H>::external_addresses(&swarm).cloned().collect(),
 			total_bytes_inbound: self.service.bandwidth.total_inbound(),
 			total_bytes_outbound: self.service.bandwidth.total_outbound(),
+			notifications_bytes: traffic.notifications(),
+			request_responses_bytes: traffic.request_responses(),
 			connected_peers,
 			not_connected_peers,
 			peerset: swarm.user_protocol_mut().peerset_debug_info(),
@@ -606,7 +760,7 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkService<B, H> {
 		// `peers_notifications_sinks` mutex as soon as possible.
 		let sink = {
 			let peers_notifications_sinks = self.peers_notifications_sinks.lock();
-			if let Some(sink) = peers_notifications_sinks.get(&(target, engine_id)) {
+			if let Some(sink) = peers_notifications_sinks.get(&(target.clone(), engine_id)) {
 				sink.clone()
 			} else {
 				// Notification silently discarded, as documented.
@@ -641,6 +795,13 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkService<B, H> {
 				.with_label_values(&["out", &maybe_utf8_bytes_to_string(&engine_id)])
 				.observe(message_len as f64);
 		}
+		self.traffic.record(
+			traffic::ProtocolKind::Notifications,
+			&maybe_utf8_bytes_to_string(&engine_id),
+			&target,
+			0,
+			message_len,
+		);
 	}
 
 	/// Obtains a [`NotificationSender`] for a connected peer, if it exists.
@@ -720,7 +881,7 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkService<B, H> {
 		// `peers_notifications_sinks` mutex as soon as possible.
 		let sink = {
 			let peers_notifications_sinks = self.peers_notifications_sinks.lock();
-			if let Some(sink) = peers_notifications_sinks.get(&(target, engine_id)) {
+			if let Some(sink) = peers_notifications_sinks.get(&(target.clone(), engine_id)) {
 				sink.clone()
 			} else {
 				return Err(NotificationSenderError::Closed);
@@ -740,6 +901,8 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkService<B, H> {
 			notification_size_metric: self.notifications_sizes_metric.as_ref().map(|histogram| {
 				histogram.with_label_values(&["out", &maybe_utf8_bytes_to_string(&engine_id)])
 			}),
+			target,
+			traffic: self.traffic.clone(),
 		})
 	}
 
@@ -759,6 +922,57 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkService<B, H> {
 		rx
 	}
 
+	/// Sends a single targeted request to a specific peer. On success, returns the response of
+	/// the peer.
+	///
+	/// Request-response protocols are a way to complement notifications protocols, but
+	/// notifications should remain the default ways of communicating information. For example, a
+	/// peer can announce something through a notification, after which the recipient can obtain
+	/// more information by performing a request.
+	/// As such, this function is meant to be called only with peers we are already connected to.
+	/// Calling this method with a `target` we are not connected to will *not* attempt to connect
+	/// to said peer.
+	///
+	/// No limit or throttling of concurrent outbound requests per peer and protocol are enforced.
+	/// Such restrictions, if desired, need to be enforced at the call site(s).
+	///
+	/// The protocol must have been registered through
+	/// [`NetworkConfiguration::request_response_protocols`](crate::config::NetworkConfiguration::request_response_protocols)
+	/// or [`NetworkService::register_request_response_protocol`].
+	pub async fn request(
+		&self,
+		target: PeerId,
+		protocol: impl Into<Cow<'static, str>>,
+		request: Vec<u8>,
+	) -> Result<Vec<u8>, RequestFailure> {
+		let (tx, rx) = oneshot::channel();
+		let _ = self.to_worker.unbounded_send(ServiceToWorkerMsg::Request {
+			target,
+			protocol: protocol.into(),
+			request,
+			pending_response: tx,
+		});
+
+		match rx.await {
+			Ok(v) => v,
+			// The channel can only be closed if the network worker no longer exists.
+			Err(_) => Err(RequestFailure::Obsolete),
+		}
+	}
+
+	/// Registers a new request-response protocol.
+	///
+	/// **Important**: This method is a work-around, and you are instead strongly encouraged to
+	/// pass the protocol in the `NetworkConfiguration::request_response_protocols` list instead.
+	/// If you have no other choice but to use this method, you are very strongly encouraged to
+	/// call it very early on. Any connection open will retain the protocols that were registered
+	/// then, and not any new one.
+	///
+	/// Registering a protocol whose name is already in use is ignored with a warning.
+	pub fn register_request_response_protocol(&self, config: RequestResponseConfig) {
+		let _ = self.to_worker.unbounded_send(ServiceToWorkerMsg::RegisterRequestResponseProtocol(config));
+	}
+
 	/// Registers a new notifications protocol.
 	///
 	/// After a protocol has been registered, you can call `write_notifications`.
@@ -822,6 +1036,39 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkService<B, H> {
 		let _ = self.to_worker.unbounded_send(ServiceToWorkerMsg::DisconnectPeer(who));
 	}
 
+	/// Bans a node for the given duration, disconnecting from it if we are connected.
+	///
+	/// The ban is stored in the network configuration directory, if any, and survives restarts.
+	pub fn ban_peer(&self, who: PeerId, duration: Duration) {
+		let _ = self.to_worker.unbounded_send(ServiceToWorkerMsg::BanPeer(who, duration));
+	}
+
+	/// Lifts a ban previously set with [`NetworkService::ban_peer`].
+	pub fn unban_peer(&self, who: PeerId) {
+		let _ = self.to_worker.unbounded_send(ServiceToWorkerMsg::UnbanPeer(who));
+	}
+
+	/// Bans all the nodes whose IP address belongs to the given range for the given duration.
+	/// The string should encode an IP range, for example `192.0.2.0/24`.
+	///
+	/// The ban is stored in the network configuration directory, if any, and survives restarts.
+	///
+	/// Returns an `Err` if the given string is not a valid IP range.
+	pub fn ban_ip_range(&self, range: &str, duration: Duration) -> Result<(), String> {
+		let range = range.parse::<IpNetwork>().map_err(|e| format!("{:?}", e))?;
+		let _ = self.to_worker.unbounded_send(ServiceToWorkerMsg::BanIpRange(range, duration));
+		Ok(())
+	}
+
+	/// Lifts a ban previously set with [`NetworkService::ban_ip_range`].
+	///
+	/// Returns an `Err` if the given string is not a valid IP range.
+	pub fn unban_ip_range(&self, range: &str) -> Result<(), String> {
+		let range = range.parse::<IpNetwork>().map_err(|e| format!("{:?}", e))?;
+		let _ = self.to_worker.unbounded_send(ServiceToWorkerMsg::UnbanIpRange(range));
+		Ok(())
+	}
+
 	/// Request a justification for the given block from the network.
 	///
 	/// On success, the justification will be passed to the import queue that was part at
@@ -1017,6 +1264,12 @@ pub struct NotificationSender {
 	/// Field extracted from the [`Metrics`] struct and necessary to report the
 	/// notifications-related metrics.
 	notification_size_metric: Option<Histogram>,
+
+	/// Peer the notifications are sent to.
+	target: PeerId,
+
+	/// Byte counters of the [`NetworkService`].
+	traffic: Arc<traffic::Traffic>,
 }
 
 impl NotificationSender {
@@ -1029,6 +1282,8 @@ impl NotificationSender {
 			},
 			engine_id: self.engine_id,
 			notification_size_metric: self.notification_size_metric.clone(),
+			target: &self.target,
+			traffic: &self.traffic,
 		})
 	}
 }
@@ -1044,6 +1299,12 @@ pub struct NotificationSenderReady<'a> {
 	/// Field extracted from the [`Metrics`] struct and necessary to report the
 	/// notifications-related metrics.
 	notification_size_metric: Option<Histogram>,
+
+	/// Peer the notification is sent to.
+	target: &'a PeerId,
+
+	/// Byte counters of the [`NetworkService`].
+	traffic: &'a traffic::Traffic,
 }
 
 impl<'a> NotificationSenderReady<'a> {
@@ -1054,6 +1315,13 @@ impl<'a> NotificationSenderReady<'a> {
 		if let Some(notification_size_metric) = &self.notification_size_metric {
 			notification_size_metric.observe(notification.len() as f64);
 		}
+		self.traffic.record(
+			traffic::ProtocolKind::Notifications,
+			&maybe_utf8_bytes_to_string(&self.engine_id),
+			self.target,
+			0,
+			notification.len(),
+		);
 
 		// For backwards-compatibility reason, we have to duplicate the message and pass it
 		// in the situation where the remote still uses the legacy substream.
@@ -1101,7 +1369,18 @@ enum ServiceToWorkerMsg<B: BlockT, H: ExHashT> {
 		engine_id: ConsensusEngineId,
 		protocol_name: Cow<'static, [u8]>,
 	},
+	Request {
+		target: PeerId,
+		protocol: Cow<'static, str>,
+		request: Vec<u8>,
+		pending_response: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+	},
+	RegisterRequestResponseProtocol(RequestResponseConfig),
 	DisconnectPeer(PeerId),
+	BanPeer(PeerId, Duration),
+	UnbanPeer(PeerId),
+	BanIpRange(IpNetwork, Duration),
+	UnbanIpRange(IpNetwork),
 	UpdateChain,
 	OwnBlockImported(B::Hash, NumberFor<B>),
 }
@@ -1136,6 +1415,17 @@ pub struct NetworkWorker<B: BlockT + 'static, H: ExHashT> {
 	/// For each peer and protocol combination, an object that allows sending notifications to
 	/// that peer. Shared with the [`NetworkService`].
 	peers_notifications_sinks: Arc<Mutex<HashMap<(PeerId, ConsensusEngineId), NotificationsSink>>>,
+	/// Answers the block requests received through the request-response protocol.
+	block_request_handler: future::Fuse<BoxFuture<'static, ()>>,
+	/// Peers and IP ranges that have been manually banned.
+	bans: bans::BanList,
+	/// Peers that are being disconnected because their address belongs to a banned IP range,
+	/// with the range in question. Entries are removed once the peer is disconnected, as the
+	/// transport refuses new connections from banned ranges, which bounds the size of this map
+	/// by the number of connected peers.
+	ip_banned_peers: HashMap<PeerId, IpNetwork>,
+	/// Stream that fires periodically in order to lift the expired bans.
+	bans_expiry_check: Pin<Box<dyn Stream<Item = ()> + Send>>,
 }
 
 struct Metrics {
@@ -1392,6 +1682,16 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 			protocol: &mut this.network_service,
 		});
 
+		// Answer the incoming block requests.
+		if let Poll::Ready(()) = this.block_request_handler.poll_unpin(cx) {
+			log::error!(target: "sync", "Block request handler has unexpectedly shut down");
+		}
+
+		// Lift the manual bans that have expired.
+		while let Poll::Ready(Some(())) = this.bans_expiry_check.poll_next_unpin(cx) {
+			this.remove_expired_bans();
+		}
+
 		// Check for new incoming light client requests.
 		if let Some(light_client_rqs) = this.light_client_rqs.as_mut() {
 			while let Poll::Ready(Some(rq)) = light_client_rqs.poll_next_unpin(cx) {
@@ -1451,8 +1751,23 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 					this.network_service
 						.register_notifications_protocol(engine_id, protocol_name);
 				},
+				ServiceToWorkerMsg::Request { target, protocol, request, pending_response } =>
+					this.network_service.send_request(&target, &protocol, request, pending_response),
+				ServiceToWorkerMsg::RegisterRequestResponseProtocol(config) => {
+					if let Err(err) = this.network_service.register_request_response_protocol(config) {
+						warn!(target: "sub-libp2p", "Failed to register request-response protocol: {}", err);
+					}
+				},
 				ServiceToWorkerMsg::DisconnectPeer(who) =>
 					this.network_service.user_protocol_mut().disconnect_peer(&who),
+				ServiceToWorkerMsg::BanPeer(who, duration) =>
+					this.ban_peer(who, duration),
+				ServiceToWorkerMsg::UnbanPeer(who) =>
+					this.unban_peer(who),
+				ServiceToWorkerMsg::BanIpRange(range, duration) =>
+					this.ban_ip_range(range, duration),
+				ServiceToWorkerMsg::UnbanIpRange(range) =>
+					this.unban_ip_range(range),
 				ServiceToWorkerMsg::UpdateChain =>
 					this.network_service.user_protocol_mut().update_chain(),
 				ServiceToWorkerMsg::OwnBlockImported(hash, number) =>
@@ -1495,12 +1810,21 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 					}
 					this.import_queue.import_finality_proof(origin, hash, nb, proof);
 				},
-				Poll::Ready(SwarmEvent::Behaviour(BehaviourOut::AnsweredRequest { protocol, build_time, .. })) => {
+				Poll::Ready(SwarmEvent::Behaviour(BehaviourOut::AnsweredRequest {
+					peer, protocol, build_time, request_size, response_size,
+				})) => {
 					if let Some(metrics) = this.metrics.as_ref() {
 						metrics.requests_in_total
 							.with_label_values(&[&maybe_utf8_bytes_to_string(&protocol)])
 							.observe(build_time.as_secs_f64());
 					}
+					this.service.traffic.record(
+						traffic::ProtocolKind::RequestResponse,
+						&maybe_utf8_bytes_to_string(&protocol),
+						&peer,
+						request_size,
+						response_size,
+					);
 				},
 				Poll::Ready(SwarmEvent::Behaviour(BehaviourOut::RequestStarted { protocol, .. })) => {
 					if let Some(metrics) = this.metrics.as_ref() {
@@ -1509,12 +1833,21 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 							.inc();
 					}
 				},
-				Poll::Ready(SwarmEvent::Behaviour(BehaviourOut::RequestFinished { protocol, request_duration, .. })) => {
+				Poll::Ready(SwarmEvent::Behaviour(BehaviourOut::RequestFinished {
+					peer, protocol, request_duration, request_size, response_size,
+				})) => {
 					if let Some(metrics) = this.metrics.as_ref() {
 						metrics.requests_out_finished
 							.with_label_values(&[&maybe_utf8_bytes_to_string(&protocol)])
 							.observe(request_duration.as_secs_f64());
 					}
+					this.service.traffic.record(
+						traffic::ProtocolKind::RequestResponse,
+						&maybe_utf8_bytes_to_string(&protocol),
+						&peer,
+						response_size,
+						request_size,
+					);
 				},
 				Poll::Ready(SwarmEvent::Behaviour(BehaviourOut::RandomKademliaStarted(protocol))) => {
 					if let Some(metrics) = this.metrics.as_ref() {
@@ -1592,6 +1925,15 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 								.observe(message.len() as f64);
 						}
 					}
+					for (engine_id, message) in &messages {
+						this.service.traffic.record(
+							traffic::ProtocolKind::Notifications,
+							&maybe_utf8_bytes_to_string(engine_id),
+							&remote,
+							message.len(),
+							0,
+						);
+					}
 					this.event_streams.send(Event::NotificationsReceived {
 						remote,
 						messages,
@@ -1613,6 +1955,10 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 				},
 				Poll::Ready(SwarmEvent::ConnectionEstablished { peer_id, endpoint, num_established }) => {
 					trace!(target: "sub-libp2p", "Libp2p => Connected({:?})", peer_id);
+					this.check_ip_range_ban(peer_id.clone(), endpoint.get_remote_address());
+					if num_established.get() == 1 {
+						this.service.traffic.on_peer_connected(&peer_id);
+					}
 
 					if let Some(metrics) = this.metrics.as_ref() {
 						let direction = match endpoint {
@@ -1628,6 +1974,10 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 				},
 				Poll::Ready(SwarmEvent::ConnectionClosed { peer_id, cause, endpoint, num_established }) => {
 					trace!(target: "sub-libp2p", "Libp2p => Disconnected({:?}, {:?})", peer_id, cause);
+					if num_established == 0 {
+						this.service.traffic.on_peer_disconnected(&peer_id);
+						this.on_ip_banned_peer_disconnected(&peer_id);
+					}
 					if let Some(metrics) = this.metrics.as_ref() {
 						let direction = match endpoint {
 							ConnectedPoint::Dialer { .. } => "out",
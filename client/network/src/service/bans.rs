@@ -0,0 +1,311 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! List of peers and IP ranges that have been manually banned by the node operator.
+//!
+//! Contrary to the bans decided by the peerset manager, which are based on reputation and are
+//! short-lived, these bans have an explicit expiration date and are persisted on disk (if a
+//! directory is provided) so that they survive restarts.
+
+use ip_network::IpNetwork;
+use libp2p::{PeerId, Multiaddr, multiaddr::Protocol};
+use log::warn;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::{collections::{BTreeMap, HashMap}, fs, io, net::IpAddr, path::{Path, PathBuf}, sync::Arc};
+use std::time::Duration;
+use wasm_timer::{SystemTime, UNIX_EPOCH};
+
+/// Name of the file, within the network configuration directory, where the bans are stored.
+const BANS_FILE: &str = "banned_peers.json";
+
+/// Longest possible ban. Longer bans are shortened to this duration, which guarantees that their
+/// expiration date can be computed without overflowing.
+pub const MAX_BAN_DURATION: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+/// Format of the file containing the bans. Expiration dates are in seconds since the UNIX epoch.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BansFile {
+	peers: BTreeMap<String, u64>,
+	ip_ranges: BTreeMap<String, u64>,
+}
+
+/// List of manual bans, with their expiration date.
+pub struct BanList {
+	/// File where the list is persisted, if any.
+	path: Option<PathBuf>,
+	/// Banned peers, with the time when the ban expires.
+	peers: HashMap<PeerId, SystemTime>,
+	/// Banned IP ranges, with the time when the ban expires.
+	ip_ranges: BannedRanges,
+}
+
+/// Banned IP ranges, with the time when the ban expires.
+///
+/// Cloning this object gives access to the same list, which lets the transport refuse the
+/// connections to and from banned addresses.
+#[derive(Clone, Default)]
+pub struct BannedRanges(Arc<RwLock<HashMap<IpNetwork, SystemTime>>>);
+
+impl BannedRanges {
+	/// Returns the banned IP range that contains the IP address of the given multiaddress, if
+	/// any.
+	pub fn banned_range_of(&self, addr: &Multiaddr) -> Option<IpNetwork> {
+		let ip = addr.iter().find_map(|protocol| match protocol {
+			Protocol::Ip4(ip) => Some(IpAddr::V4(ip)),
+			Protocol::Ip6(ip) => Some(IpAddr::V6(ip)),
+			_ => None,
+		})?;
+
+		self.0.read().keys().find(|range| range.contains(ip)).cloned()
+	}
+}
+
+impl BanList {
+	/// Loads the list of bans stored in the given directory. Entries that have already expired
+	/// or that can't be parsed are discarded.
+	///
+	/// If `dir` is `None`, the list only lives in memory.
+	pub fn load(dir: Option<&Path>) -> Self {
+		let path = dir.map(|dir| dir.join(BANS_FILE));
+		let mut list = BanList {
+			path,
+			peers: HashMap::new(),
+			ip_ranges: BannedRanges::default(),
+		};
+
+		let file = match list.path.as_ref().map(|path| read_file(path)) {
+			Some(Ok(file)) => file,
+			Some(Err(err)) => {
+				warn!(target: "sub-libp2p", "Failed to load the list of banned peers: {}", err);
+				return list
+			}
+			None => return list,
+		};
+
+		let now = SystemTime::now();
+		let latest = expiry(MAX_BAN_DURATION).duration_since(UNIX_EPOCH)
+			.map(|d| d.as_secs())
+			.unwrap_or(0);
+		let mut ip_ranges = list.ip_ranges.0.write();
+		for (peer_id, expires) in file.peers {
+			let expires = UNIX_EPOCH + Duration::from_secs(expires.min(latest));
+			match peer_id.parse::<PeerId>() {
+				Ok(peer_id) if expires > now => { list.peers.insert(peer_id, expires); },
+				Ok(_) => {},
+				Err(_) => warn!(target: "sub-libp2p", "Ignoring invalid banned peer {:?}", peer_id),
+			}
+		}
+		for (range, expires) in file.ip_ranges {
+			let expires = UNIX_EPOCH + Duration::from_secs(expires.min(latest));
+			match range.parse::<IpNetwork>() {
+				Ok(range) if expires > now => { ip_ranges.insert(range, expires); },
+				Ok(_) => {},
+				Err(_) => warn!(target: "sub-libp2p", "Ignoring invalid banned IP range {:?}", range),
+			}
+		}
+		drop(ip_ranges);
+
+		list
+	}
+
+	/// Returns a handle to the banned IP ranges, which stays up to date with this list.
+	pub fn banned_ranges(&self) -> BannedRanges {
+		self.ip_ranges.clone()
+	}
+
+	/// Returns the list of peers that are currently banned.
+	pub fn banned_peers(&self) -> impl Iterator<Item = &PeerId> {
+		self.peers.keys()
+	}
+
+	/// Returns true if the given peer is banned.
+	pub fn is_peer_banned(&self, peer_id: &PeerId) -> bool {
+		self.peers.contains_key(peer_id)
+	}
+
+	/// Returns the banned IP range that contains the IP address of the given multiaddress, if
+	/// any.
+	pub fn banned_range_of(&self, addr: &Multiaddr) -> Option<IpNetwork> {
+		self.ip_ranges.banned_range_of(addr)
+	}
+
+	/// Bans a peer for the given duration, replacing any previous ban of the same peer.
+	///
+	/// The duration is capped to [`MAX_BAN_DURATION`].
+	pub fn ban_peer(&mut self, peer_id: PeerId, duration: Duration) {
+		self.peers.insert(peer_id, expiry(duration));
+		self.persist();
+	}
+
+	/// Lifts the ban of a peer. Returns false if the peer wasn't banned.
+	pub fn unban_peer(&mut self, peer_id: &PeerId) -> bool {
+		let was_banned = self.peers.remove(peer_id).is_some();
+		if was_banned {
+			self.persist();
+		}
+		was_banned
+	}
+
+	/// Bans an IP range for the given duration, replacing any previous ban of the same range.
+	///
+	/// The duration is capped to [`MAX_BAN_DURATION`].
+	pub fn ban_ip_range(&mut self, range: IpNetwork, duration: Duration) {
+		self.ip_ranges.0.write().insert(range, expiry(duration));
+		self.persist();
+	}
+
+	/// Lifts the ban of an IP range. Returns false if the range wasn't banned.
+	pub fn unban_ip_range(&mut self, range: &IpNetwork) -> bool {
+		let was_banned = self.ip_ranges.0.write().remove(range).is_some();
+		if was_banned {
+			self.persist();
+		}
+		was_banned
+	}
+
+	/// Removes the bans that have expired, and returns the peers and IP ranges that are no
+	/// longer banned.
+	pub fn remove_expired(&mut self) -> (Vec<PeerId>, Vec<IpNetwork>) {
+		let now = SystemTime::now();
+
+		let peers = self.peers.iter()
+			.filter(|(_, expires)| **expires <= now)
+			.map(|(peer_id, _)| peer_id.clone())
+			.collect::<Vec<_>>();
+		for peer_id in &peers {
+			self.peers.remove(peer_id);
+		}
+
+		let mut ip_ranges = self.ip_ranges.0.write();
+		let ranges = ip_ranges.iter()
+			.filter(|(_, expires)| **expires <= now)
+			.map(|(range, _)| range.clone())
+			.collect::<Vec<_>>();
+		for range in &ranges {
+			ip_ranges.remove(range);
+		}
+		drop(ip_ranges);
+
+		if !peers.is_empty() || !ranges.is_empty() {
+			self.persist();
+		}
+
+		(peers, ranges)
+	}
+
+	/// Writes the list to disk, if a path has been provided.
+	fn persist(&self) {
+		let path = match &self.path {
+			Some(path) => path,
+			None => return,
+		};
+
+		let as_secs = |expires: &SystemTime| expires.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_secs())
+			.unwrap_or(0);
+		let file = BansFile {
+			peers: self.peers.iter()
+				.map(|(peer_id, expires)| (peer_id.to_base58(), as_secs(expires)))
+				.collect(),
+			ip_ranges: self.ip_ranges.0.read().iter()
+				.map(|(range, expires)| (range.to_string(), as_secs(expires)))
+				.collect(),
+		};
+
+		if let Err(err) = write_file(path, &file) {
+			warn!(target: "sub-libp2p", "Failed to persist the list of banned peers: {}", err);
+		}
+	}
+}
+
+/// Returns the expiration date of a ban of the given duration starting now.
+fn expiry(duration: Duration) -> SystemTime {
+	SystemTime::now() + duration.min(MAX_BAN_DURATION)
+}
+
+/// Reads the bans file at the given path. A missing file is treated as an empty list.
+fn read_file(path: &Path) -> io::Result<BansFile> {
+	match fs::read(path) {
+		Ok(bytes) => serde_json::from_slice(&bytes)
+			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+		Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(BansFile::default()),
+		Err(err) => Err(err),
+	}
+}
+
+/// Writes the bans file at the given path.
+fn write_file(path: &Path, file: &BansFile) -> io::Result<()> {
+	let bytes = serde_json::to_vec_pretty(file)
+		.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+	fs::write(path, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::BanList;
+	use ip_network::IpNetwork;
+	use libp2p::PeerId;
+	use std::time::Duration;
+
+	#[test]
+	fn bans_survive_reload() {
+		let dir = tempfile::tempdir().unwrap();
+		let peer_id = PeerId::random();
+		let range = "10.0.0.0/8".parse::<IpNetwork>().unwrap();
+
+		{
+			let mut list = BanList::load(Some(dir.path()));
+			list.ban_peer(peer_id.clone(), Duration::from_secs(3600));
+			list.ban_ip_range(range.clone(), Duration::from_secs(3600));
+		}
+
+		let list = BanList::load(Some(dir.path()));
+		assert!(list.is_peer_banned(&peer_id));
+		assert_eq!(list.banned_range_of(&"/ip4/10.1.2.3/tcp/30333".parse().unwrap()), Some(range));
+		assert_eq!(list.banned_range_of(&"/ip4/192.168.0.1/tcp/30333".parse().unwrap()), None);
+	}
+
+	#[test]
+	fn expired_bans_are_removed() {
+		let dir = tempfile::tempdir().unwrap();
+		let peer_id = PeerId::random();
+
+		let mut list = BanList::load(Some(dir.path()));
+		list.ban_peer(peer_id.clone(), Duration::from_secs(0));
+		assert_eq!(list.remove_expired(), (vec![peer_id.clone()], Vec::new()));
+		assert!(!list.is_peer_banned(&peer_id));
+		assert!(!BanList::load(Some(dir.path())).is_peer_banned(&peer_id));
+	}
+
+	#[test]
+	fn very_long_bans_are_capped() {
+		let dir = tempfile::tempdir().unwrap();
+		let peer_id = PeerId::random();
+		let range = "10.0.0.0/8".parse::<IpNetwork>().unwrap();
+
+		{
+			let mut list = BanList::load(Some(dir.path()));
+			list.ban_peer(peer_id.clone(), Duration::from_secs(u64::max_value()));
+			list.ban_ip_range(range.clone(), Duration::new(u64::max_value(), 999_999_999));
+		}
+
+		let list = BanList::load(Some(dir.path()));
+		assert!(list.is_peer_banned(&peer_id));
+		assert_eq!(list.banned_range_of(&"/ip4/10.1.2.3/tcp/30333".parse().unwrap()), Some(range));
+	}
+}
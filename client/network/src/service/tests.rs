@@ -514,3 +514,181 @@ fn ensure_public_addresses_consistent_with_transport_not_memory() {
 		.. config::NetworkConfiguration::new("test-node", "test-client", Default::default(), None)
 	});
 }
+
+#[test]
+fn request_response_round_trip() {
+	const PROTOCOL_NAME: &str = "/test/request-response/1";
+
+	fn protocol_config(inbound_queue: Option<futures::channel::mpsc::Sender<config::IncomingRequest>>)
+		-> config::RequestResponseConfig
+	{
+		config::RequestResponseConfig {
+			name: From::from(PROTOCOL_NAME),
+			max_request_size: 1024,
+			max_response_size: 1024,
+			request_timeout: Duration::from_secs(20),
+			inbound_queue,
+		}
+	}
+
+	let listen_addr = config::build_multiaddr![Memory(rand::random::<u64>())];
+	let (tx, mut rx) = futures::channel::mpsc::channel(16);
+
+	let (node1, _) = build_test_full_node(config::NetworkConfiguration {
+		notifications_protocols: vec![(ENGINE_ID, From::from(&b"/foo"[..]))],
+		request_response_protocols: vec![protocol_config(Some(tx))],
+		listen_addresses: vec![listen_addr.clone()],
+		transport: config::TransportConfig::MemoryOnly,
+		.. config::NetworkConfiguration::new_local()
+	});
+
+	let (node2, mut events_stream2) = build_test_full_node(config::NetworkConfiguration {
+		notifications_protocols: vec![(ENGINE_ID, From::from(&b"/foo"[..]))],
+		request_response_protocols: vec![protocol_config(None)],
+		listen_addresses: vec![],
+		reserved_nodes: vec![config::MultiaddrWithPeerId {
+			multiaddr: listen_addr,
+			peer_id: node1.local_peer_id().clone(),
+		}],
+		transport: config::TransportConfig::MemoryOnly,
+		.. config::NetworkConfiguration::new_local()
+	});
+
+	// Answer every request by echoing its payload in reverse.
+	async_std::task::spawn(async move {
+		while let Some(rq) = rx.next().await {
+			let mut response = rq.payload;
+			response.reverse();
+			let _ = rq.pending_response.send(response);
+		}
+	});
+
+	async_std::task::block_on(async move {
+		// Wait for the nodes to be connected.
+		loop {
+			match events_stream2.next().await.unwrap() {
+				Event::NotificationStreamOpened { .. } => break,
+				_ => {}
+			};
+		}
+
+		let response = node2.request(node1.local_peer_id().clone(), PROTOCOL_NAME, vec![1, 2, 3])
+			.await
+			.unwrap();
+		assert_eq!(response, vec![3, 2, 1]);
+
+		// Requests larger than the limit of the protocol are refused locally.
+		let result = node2.request(node1.local_peer_id().clone(), PROTOCOL_NAME, vec![0; 2048]).await;
+		assert_eq!(result, Err(crate::RequestFailure::RequestTooLarge));
+
+		// Requests on unknown protocols are refused locally.
+		let result = node2.request(node1.local_peer_id().clone(), "/test/unknown/1", vec![1]).await;
+		assert_eq!(result, Err(crate::RequestFailure::UnknownProtocol));
+	});
+}
+
+#[test]
+#[should_panic(expected = "registered multiple times")]
+fn duplicate_request_response_protocol_is_refused() {
+	let protocol_config = config::RequestResponseConfig {
+		name: From::from("/test/request-response/1"),
+		max_request_size: 1024,
+		max_response_size: 1024,
+		request_timeout: Duration::from_secs(20),
+		inbound_queue: None,
+	};
+
+	let _ = build_test_full_node(config::NetworkConfiguration {
+		request_response_protocols: vec![protocol_config.clone(), protocol_config],
+		transport: config::TransportConfig::MemoryOnly,
+		listen_addresses: vec![],
+		.. config::NetworkConfiguration::new_local()
+	});
+}
+
+#[test]
+fn connect_over_websocket_tls() {
+	// The certificate of the listening node is only valid for `localhost` and is signed by a
+	// test certificate authority that the dialing node trusts. The dialing node doesn't have a
+	// certificate of its own.
+	let res = |file: &str| std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("res").join(file);
+	let transport = |websocket_tls| config::TransportConfig::Normal {
+		enable_mdns: false,
+		allow_private_ipv4: true,
+		wasm_external_transport: None,
+		use_yamux_flow_control: true,
+		websocket_tls: Some(websocket_tls),
+	};
+	let listener_tls = config::WebSocketTlsConfig {
+		server: Some(config::WebSocketTlsServerConfig {
+			certificate_chain: res("ws-tls-node.pem"),
+			private_key: res("ws-tls-node.key"),
+		}),
+		client: Default::default(),
+	};
+	let dialer_tls = config::WebSocketTlsConfig {
+		server: None,
+		client: config::WebSocketTlsClientConfig {
+			trusted_certificates: vec![res("ws-tls-ca.pem")],
+		},
+	};
+
+	let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+	let listen_addr: crate::Multiaddr = format!("/ip4/127.0.0.1/tcp/{}/wss", port).parse().unwrap();
+	let dial_addr: crate::Multiaddr = format!("/dns4/localhost/tcp/{}/wss", port).parse().unwrap();
+
+	let (node1, mut events_stream1) = build_test_full_node(config::NetworkConfiguration {
+		notifications_protocols: vec![(ENGINE_ID, From::from(&b"/foo"[..]))],
+		listen_addresses: vec![listen_addr],
+		transport: transport(listener_tls),
+		.. config::NetworkConfiguration::new_local()
+	});
+
+	let (node2, _events_stream2) = build_test_full_node(config::NetworkConfiguration {
+		notifications_protocols: vec![(ENGINE_ID, From::from(&b"/foo"[..]))],
+		listen_addresses: vec![],
+		reserved_nodes: vec![config::MultiaddrWithPeerId {
+			multiaddr: dial_addr,
+			peer_id: node1.local_peer_id().clone(),
+		}],
+		transport: transport(dialer_tls),
+		.. config::NetworkConfiguration::new_local()
+	});
+
+	async_std::task::block_on(async move {
+		loop {
+			match events_stream1.next().await.unwrap() {
+				Event::NotificationStreamOpened { remote, .. } => {
+					assert_eq!(&remote, node2.local_peer_id());
+					break;
+				}
+				_ => {}
+			}
+		}
+	});
+}
+
+#[test]
+#[should_panic(expected = "no private key found")]
+fn websocket_tls_requires_a_private_key() {
+	let res = |file: &str| std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("res").join(file);
+
+	let _ = build_test_full_node(config::NetworkConfiguration {
+		transport: config::TransportConfig::Normal {
+			enable_mdns: false,
+			allow_private_ipv4: true,
+			wasm_external_transport: None,
+			use_yamux_flow_control: true,
+			websocket_tls: Some(config::WebSocketTlsConfig {
+				server: Some(config::WebSocketTlsServerConfig {
+					certificate_chain: res("ws-tls-node.pem"),
+					// Not a private key.
+					private_key: res("ws-tls-ca.pem"),
+				}),
+				client: Default::default(),
+			}),
+		},
+		listen_addresses: vec![],
+		.. config::NetworkConfiguration::new_local()
+	});
+}
@@ -0,0 +1,244 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Accounting of the bytes exchanged over each protocol and with each peer.
+//!
+//! Only the payloads of notifications, requests and responses are accounted for. The overhead of
+//! the encryption, multiplexing and framing layers only shows up in the total bandwidth reported
+//! by the transport.
+
+use crate::network_state::BytesCount;
+
+use libp2p::PeerId;
+use parking_lot::Mutex;
+use prometheus_endpoint::{register, CounterVec, Opts, PrometheusError, Registry, U64};
+use std::collections::HashMap;
+
+/// Family of protocols the traffic belongs to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProtocolKind {
+	/// Notifications protocol, identified by its engine ID.
+	Notifications,
+	/// Request-response protocol, identified by its name.
+	RequestResponse,
+}
+
+impl ProtocolKind {
+	fn as_str(&self) -> &'static str {
+		match self {
+			ProtocolKind::Notifications => "notifications",
+			ProtocolKind::RequestResponse => "request-response",
+		}
+	}
+}
+
+/// Byte counters, shared between the `NetworkService` and the `NetworkWorker`.
+pub struct Traffic {
+	inner: Mutex<Inner>,
+	metrics: Option<Metrics>,
+}
+
+#[derive(Default)]
+struct Inner {
+	notifications: HashMap<String, BytesCount>,
+	request_responses: HashMap<String, BytesCount>,
+	/// Only contains the peers we are connected to.
+	peers: HashMap<PeerId, BytesCount>,
+}
+
+struct Metrics {
+	peer_bytes_total: CounterVec<U64>,
+	protocol_bytes_total: CounterVec<U64>,
+}
+
+impl Traffic {
+	/// Creates the counters, and registers the Prometheus metrics if a registry is passed.
+	pub fn new(registry: Option<&Registry>) -> Result<Self, PrometheusError> {
+		let metrics = match registry {
+			Some(registry) => Some(Metrics {
+				peer_bytes_total: register(CounterVec::new(
+					Opts::new(
+						"sub_libp2p_peer_bytes_total",
+						"Total size of the notifications, requests and responses exchanged with \
+						each connected peer"
+					),
+					&["direction", "peer"]
+				)?, registry)?,
+				protocol_bytes_total: register(CounterVec::new(
+					Opts::new(
+						"sub_libp2p_protocol_bytes_total",
+						"Total size of the notifications, requests and responses exchanged over \
+						each protocol"
+					),
+					&["direction", "kind", "protocol"]
+				)?, registry)?,
+			}),
+			None => None,
+		};
+
+		Ok(Traffic {
+			inner: Mutex::new(Inner::default()),
+			metrics,
+		})
+	}
+
+	/// Records bytes received from and sent to `peer` over the given protocol.
+	pub fn record(
+		&self,
+		kind: ProtocolKind,
+		protocol: &str,
+		peer: &PeerId,
+		inbound: usize,
+		outbound: usize,
+	) {
+		let (inbound, outbound) = (inbound as u64, outbound as u64);
+
+		let mut inner = self.inner.lock();
+		let per_protocol = match kind {
+			ProtocolKind::Notifications => &mut inner.notifications,
+			ProtocolKind::RequestResponse => &mut inner.request_responses,
+		};
+		if let Some(counts) = per_protocol.get_mut(protocol) {
+			counts.add(inbound, outbound);
+		} else {
+			per_protocol.entry(protocol.to_owned()).or_default().add(inbound, outbound);
+		}
+		// Only the peers we are connected to are labelled, as their labels are removed on
+		// disconnection. The lock is held while updating the metrics, so that the labels of a
+		// peer can't be added back after it disconnected.
+		let peer = match inner.peers.get_mut(peer) {
+			Some(counts) => {
+				counts.add(inbound, outbound);
+				Some(peer.to_base58())
+			},
+			None => None,
+		};
+
+		if let Some(metrics) = &self.metrics {
+			if inbound != 0 {
+				metrics.protocol_bytes_total
+					.with_label_values(&["in", kind.as_str(), protocol])
+					.inc_by(inbound);
+				if let Some(peer) = &peer {
+					metrics.peer_bytes_total.with_label_values(&["in", peer]).inc_by(inbound);
+				}
+			}
+			if outbound != 0 {
+				metrics.protocol_bytes_total
+					.with_label_values(&["out", kind.as_str(), protocol])
+					.inc_by(outbound);
+				if let Some(peer) = &peer {
+					metrics.peer_bytes_total.with_label_values(&["out", peer]).inc_by(outbound);
+				}
+			}
+		}
+	}
+
+	/// Must be called when we connect to a peer, in order to start accounting its traffic.
+	pub fn on_peer_connected(&self, peer: &PeerId) {
+		self.inner.lock().peers.entry(peer.clone()).or_default();
+	}
+
+	/// Must be called when we are no longer connected to a peer. Its counters are discarded.
+	pub fn on_peer_disconnected(&self, peer: &PeerId) {
+		let mut inner = self.inner.lock();
+		inner.peers.remove(peer);
+
+		if let Some(metrics) = &self.metrics {
+			let peer = peer.to_base58();
+			let _ = metrics.peer_bytes_total.remove_label_values(&["in", &peer]);
+			let _ = metrics.peer_bytes_total.remove_label_values(&["out", &peer]);
+		}
+	}
+
+	/// Returns the bytes exchanged with the given peer since we connected to it.
+	pub fn peer(&self, peer: &PeerId) -> BytesCount {
+		self.inner.lock().peers.get(peer).cloned().unwrap_or_default()
+	}
+
+	/// Returns the bytes exchanged over each notifications protocol.
+	pub fn notifications(&self) -> HashMap<String, BytesCount> {
+		self.inner.lock().notifications.clone()
+	}
+
+	/// Returns the bytes exchanged over each request-response protocol.
+	pub fn request_responses(&self) -> HashMap<String, BytesCount> {
+		self.inner.lock().request_responses.clone()
+	}
+}
+
+impl BytesCount {
+	fn add(&mut self, inbound: u64, outbound: u64) {
+		self.inbound = self.inbound.saturating_add(inbound);
+		self.outbound = self.outbound.saturating_add(outbound);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ProtocolKind, Traffic};
+	use crate::network_state::BytesCount;
+	use libp2p::PeerId;
+
+	#[test]
+	fn counts_per_protocol_and_per_connected_peer() {
+		let traffic = Traffic::new(None).unwrap();
+		let connected = PeerId::random();
+		let disconnected = PeerId::random();
+		traffic.on_peer_connected(&connected);
+
+		traffic.record(ProtocolKind::Notifications, "FRNK", &connected, 10, 0);
+		traffic.record(ProtocolKind::Notifications, "FRNK", &disconnected, 0, 5);
+		traffic.record(ProtocolKind::RequestResponse, "/dot/sync/2", &connected, 100, 20);
+
+		assert_eq!(traffic.notifications()["FRNK"], BytesCount { inbound: 10, outbound: 5 });
+		assert_eq!(
+			traffic.request_responses()["/dot/sync/2"],
+			BytesCount { inbound: 100, outbound: 20 },
+		);
+		assert_eq!(traffic.peer(&connected), BytesCount { inbound: 110, outbound: 20 });
+		assert_eq!(traffic.peer(&disconnected), BytesCount::default());
+
+		traffic.on_peer_disconnected(&connected);
+		assert_eq!(traffic.peer(&connected), BytesCount::default());
+	}
+}
+
+	#[test]
+	fn only_labels_connected_peers() {
+		let registry = prometheus_endpoint::Registry::new();
+		let traffic = Traffic::new(Some(&registry)).unwrap();
+		let connected = PeerId::random();
+		let disconnected = PeerId::random();
+		traffic.on_peer_connected(&connected);
+
+		traffic.record(ProtocolKind::Notifications, "FRNK", &connected, 10, 0);
+		traffic.record(ProtocolKind::Notifications, "FRNK", &disconnected, 0, 5);
+
+		let labelled_peers = || registry.gather().iter()
+			.filter(|family| family.get_name() == "sub_libp2p_peer_bytes_total")
+			.flat_map(|family| family.get_metric().to_vec())
+			.flat_map(|metric| metric.get_label().to_vec())
+			.filter(|label| label.get_name() == "peer")
+			.map(|label| label.get_value().to_owned())
+			.collect::<Vec<_>>();
+		assert_eq!(labelled_peers(), vec![connected.to_base58()]);
+
+		traffic.on_peer_disconnected(&connected);
+		traffic.record(ProtocolKind::Notifications, "FRNK", &connected, 10, 0);
+		assert!(labelled_peers().is_empty());
+	}
+}
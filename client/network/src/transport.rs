@@ -16,6 +16,8 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+use crate::{config::WebSocketTlsConfig, service::bans::BannedRanges};
+
 use futures::prelude::*;
 use libp2p::{
 	InboundUpgradeExt, OutboundUpgradeExt, PeerId, Transport,
@@ -26,7 +28,13 @@ use libp2p::{
 	mplex, identity, bandwidth, wasm_ext, noise
 };
 #[cfg(not(target_os = "unknown"))]
-use libp2p::{tcp, dns, websocket};
+use futures::stream::BoxStream;
+#[cfg(not(target_os = "unknown"))]
+use libp2p::{Multiaddr, core::transport::{ListenerEvent, TransportError}, tcp, dns, websocket};
+#[cfg(not(target_os = "unknown"))]
+use log::debug;
+#[cfg(not(target_os = "unknown"))]
+use std::{fmt, fs, path::Path};
 use std::{io, sync::Arc, time::Duration};
 
 pub use self::bandwidth::BandwidthSinks;
@@ -36,14 +44,22 @@ pub use self::bandwidth::BandwidthSinks;
 /// If `memory_only` is true, then only communication within the same process are allowed. Only
 /// addresses with the format `/memory/...` are allowed.
 ///
+/// If `websocket_tls` is `Some`, the WebSocket transport uses the given TLS settings. Listening on
+/// `/wss` addresses requires a server certificate. Returns an error if the certificates
+/// can't be loaded.
+///
+/// TCP connections to and from the IP ranges in `banned_ranges` are refused.
+///
 /// Returns a `BandwidthSinks` object that allows querying the average bandwidth produced by all
 /// the connections spawned with this transport.
 pub fn build_transport(
 	keypair: identity::Keypair,
 	memory_only: bool,
 	wasm_external_transport: Option<wasm_ext::ExtTransport>,
-	use_yamux_flow_control: bool
-) -> (Boxed<(PeerId, StreamMuxerBox), io::Error>, Arc<BandwidthSinks>) {
+	use_yamux_flow_control: bool,
+	websocket_tls: Option<&WebSocketTlsConfig>,
+	banned_ranges: BannedRanges,
+) -> io::Result<(Boxed<(PeerId, StreamMuxerBox), io::Error>, Arc<BandwidthSinks>)> {
 	// Legacy noise configurations for backward compatibility.
 	let mut noise_legacy = noise::LegacyConfig::default();
 	noise_legacy.send_legacy_handshake = true;
@@ -92,17 +108,24 @@ pub fn build_transport(
 	};
 	#[cfg(not(target_os = "unknown"))]
 	let transport = transport.or_transport(if !memory_only {
-		let desktop_trans = tcp::TcpConfig::new();
-		let desktop_trans = websocket::WsConfig::new(desktop_trans.clone())
-			.or_transport(desktop_trans);
-		OptionalTransport::some(if let Ok(dns) = dns::DnsConfig::new(desktop_trans.clone()) {
+		// DNS addresses are resolved below the WebSocket layer, as the latter needs the domain
+		// name in order to verify the certificate of the remote when dialing `/wss` addresses.
+		let tcp_trans = IpBanFilter { inner: tcp::TcpConfig::new(), banned_ranges };
+		let tcp_trans = if let Ok(dns) = dns::DnsConfig::new(tcp_trans.clone()) {
 			dns.boxed()
 		} else {
-			desktop_trans.map_err(dns::DnsErr::Underlying).boxed()
-		})
+			tcp_trans.map_err(dns::DnsErr::Underlying).boxed()
+		};
+		let mut ws_trans = websocket::WsConfig::new(tcp_trans.clone());
+		if let Some(config) = websocket_tls {
+			ws_trans.set_tls_config(websocket_tls_config(config)?);
+		}
+		OptionalTransport::some(ws_trans.or_transport(tcp_trans))
 	} else {
 		OptionalTransport::none()
 	});
+	#[cfg(target_os = "unknown")]
+	let _ = (websocket_tls, banned_ranges);
 
 	let transport = transport.or_transport(if memory_only {
 		OptionalTransport::some(libp2p::core::transport::MemoryTransport::default())
@@ -151,6 +174,122 @@ pub fn build_transport(
 			.map_err(|err| io::Error::new(io::ErrorKind::Other, err))
 			.boxed();
 
-	(transport, bandwidth)
+	Ok((transport, bandwidth))
+}
+
+/// Transport wrapper that refuses to dial or accept connections from banned IP ranges.
+#[cfg(not(target_os = "unknown"))]
+#[derive(Clone)]
+struct IpBanFilter<T> {
+	inner: T,
+	banned_ranges: BannedRanges,
+}
+
+#[cfg(not(target_os = "unknown"))]
+impl<T> Transport for IpBanFilter<T>
+where
+	T: Transport,
+	T::Listener: Send + 'static,
+	T::ListenerUpgrade: Send + 'static,
+	T::Error: Send + 'static,
+{
+	type Output = T::Output;
+	type Error = T::Error;
+	type Listener = BoxStream<'static, Result<ListenerEvent<Self::ListenerUpgrade, T::Error>, T::Error>>;
+	type ListenerUpgrade = T::ListenerUpgrade;
+	type Dial = T::Dial;
+
+	fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<T::Error>> {
+		let banned_ranges = self.banned_ranges;
+		let listener = self.inner.listen_on(addr)?.try_filter(move |event| {
+			let banned = match event {
+				ListenerEvent::Upgrade { remote_addr, .. } => banned_ranges.banned_range_of(remote_addr)
+					.map(|range| (remote_addr.clone(), range)),
+				_ => None,
+			};
+			if let Some((remote_addr, range)) = &banned {
+				debug!(target: "sub-libp2p", "Refusing connection from {} in banned range {}", remote_addr, range);
+			}
+			future::ready(banned.is_none())
+		});
+		Ok(listener.boxed())
+	}
+
+	fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<T::Error>> {
+		if let Some(range) = self.banned_ranges.banned_range_of(&addr) {
+			debug!(target: "sub-libp2p", "Refusing to dial {} in banned range {}", addr, range);
+			return Err(TransportError::MultiaddrNotSupported(addr));
+		}
+		self.inner.dial(addr)
+	}
+}
+
+/// Loads the certificates and the private key referred to by the given configuration.
+#[cfg(not(target_os = "unknown"))]
+fn websocket_tls_config(config: &WebSocketTlsConfig) -> io::Result<websocket::tls::Config> {
+	use websocket::tls::{Certificate, PrivateKey};
+
+	let mut builder = websocket::tls::Config::builder();
+	if let Some(server) = &config.server {
+		let certificates = read_pem_or_der(&server.certificate_chain, |label| label == "CERTIFICATE")?;
+		if certificates.is_empty() {
+			return Err(invalid_data(&server.certificate_chain, &"no certificate found"));
+		}
+		let private_key = read_pem_or_der(&server.private_key, |label| label.ends_with("PRIVATE KEY"))?
+			.into_iter()
+			.next()
+			.ok_or_else(|| invalid_data(&server.private_key, &"no private key found"))?;
+
+		builder
+			.server(PrivateKey::new(private_key), certificates.into_iter().map(Certificate::new))
+			.map_err(|err| invalid_data(&server.private_key, &err))?;
+	}
+	for path in &config.client.trusted_certificates {
+		for certificate in read_pem_or_der(path, |label| label == "CERTIFICATE")? {
+			builder.add_trust(&Certificate::new(certificate))
+				.map_err(|err| invalid_data(path, &err))?;
+		}
+	}
+
+	Ok(builder.finish())
+}
+
+/// Reads the given file. If it is PEM-encoded, returns the decoded content of the blocks whose
+/// label is accepted by `accept_label`. Otherwise, returns the content of the file as is, which
+/// is assumed to be DER-encoded.
+#[cfg(not(target_os = "unknown"))]
+fn read_pem_or_der(path: &Path, accept_label: impl Fn(&str) -> bool) -> io::Result<Vec<Vec<u8>>> {
+	const BEGIN: &str = "-----BEGIN ";
+	const END: &str = "-----END ";
+	const DASHES: &str = "-----";
+
+	let content = fs::read(path)?;
+	let text = match std::str::from_utf8(&content) {
+		Ok(text) if text.trim_start().starts_with(BEGIN) => text,
+		_ => return Ok(vec![content]),
+	};
+
+	let mut blocks = Vec::new();
+	let mut current: Option<(&str, String)> = None;
+	for line in text.lines().map(str::trim) {
+		if line.starts_with(BEGIN) && line.ends_with(DASHES) {
+			current = Some((&line[BEGIN.len()..line.len() - DASHES.len()], String::new()));
+		} else if line.starts_with(END) {
+			if let Some((label, encoded)) = current.take() {
+				if accept_label(label) {
+					blocks.push(base64::decode(&encoded).map_err(|err| invalid_data(path, &err))?);
+				}
+			}
+		} else if let Some((_, encoded)) = current.as_mut() {
+			encoded.push_str(line);
+		}
+	}
+
+	Ok(blocks)
+}
+
+#[cfg(not(target_os = "unknown"))]
+fn invalid_data(path: &Path, err: &dyn fmt::Display) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err))
 }
 
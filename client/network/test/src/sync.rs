@@ -20,7 +20,7 @@ use sp_consensus::BlockOrigin;
 use std::time::Duration;
 use futures::executor::block_on;
 use super::*;
-use sp_consensus::block_validation::Validation;
+use sp_consensus::block_validation::{Validation, ValidationFuture};
 use substrate_test_runtime::Header;
 
 fn test_ancestor_search_when_common_is(n: usize) {
@@ -716,12 +716,27 @@ fn can_sync_to_peers_with_wrong_common_block() {
 struct NewBestBlockAnnounceValidator;
 
 impl BlockAnnounceValidator<Block> for NewBestBlockAnnounceValidator {
-	fn validate(
-		&mut self,
-		_: &Header,
-		_: &[u8],
-	) -> Result<Validation, Box<dyn std::error::Error + Send>> {
-		Ok(Validation::Success { is_new_best: true })
+	fn validate(&mut self, _: &Header, _: &[u8]) -> ValidationFuture {
+		async { Ok(Validation::Success { is_new_best: true }) }.boxed()
+	}
+}
+
+/// Returns `is_new_best = true` for each validated announcement, but only after the validation
+/// future has been polled once.
+struct DeferredBlockAnnounceValidator;
+
+impl BlockAnnounceValidator<Block> for DeferredBlockAnnounceValidator {
+	fn validate(&mut self, _: &Header, _: &[u8]) -> ValidationFuture {
+		let mut polled = false;
+		futures::future::poll_fn(move |cx| {
+			if polled {
+				Poll::Ready(Ok(Validation::Success { is_new_best: true }))
+			} else {
+				polled = true;
+				cx.waker().wake_by_ref();
+				Poll::Pending
+			}
+		}).boxed()
 	}
 }
 
@@ -750,3 +765,22 @@ fn sync_blocks_when_block_announce_validator_says_it_is_new_best() {
 	// that flags all blocks as `is_new_best` and thus, it should have synced the blocks.
 	assert!(!net.peer(1).has_block(&block_hash));
 }
+
+#[test]
+fn wait_until_deferred_block_announce_validation_is_ready() {
+	let _ = env_logger::try_init();
+	let mut net = TestNet::with_fork_choice(ForkChoiceStrategy::Custom(false));
+	net.add_full_peer_with_config(Default::default());
+	net.add_full_peer_with_config(FullPeerConfig {
+		block_announce_validator: Some(Box::new(DeferredBlockAnnounceValidator)),
+		..Default::default()
+	});
+
+	net.block_until_connected();
+
+	let block_hash = net.peer(0).push_blocks(1, true);
+
+	while !net.peer(1).has_block(&block_hash) {
+		net.block_until_idle();
+	}
+}
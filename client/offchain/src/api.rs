@@ -19,6 +19,8 @@ use std::{
 	sync::Arc,
 	convert::TryFrom,
 	thread::sleep,
+	path::PathBuf,
+	time::Duration,
 };
 
 use sp_core::offchain::OffchainStorage;
@@ -43,6 +45,32 @@ mod http_dummy;
 
 mod timestamp;
 
+/// Configuration of the HTTP client available to offchain workers.
+#[derive(Debug, Clone, Default)]
+pub struct HttpConfig {
+	/// URI of an HTTP proxy that all requests are tunneled through, with the `CONNECT` method.
+	///
+	/// The URI can't contain credentials.
+	pub proxy: Option<String>,
+	/// PEM files of the certificate authorities to trust, in addition to the system ones.
+	pub ca_certificates: Vec<PathBuf>,
+	/// Maximum number of requests a single offchain worker run can start.
+	pub max_requests: Option<u32>,
+	/// Maximum size of a response body, in bytes. Reading a larger body fails.
+	pub max_response_size: Option<u64>,
+	/// Maximum time to establish a connection.
+	pub connect_timeout: Option<Duration>,
+	/// Maximum time to wait for the response to a request, body included, once it is sent.
+	pub response_timeout: Option<Duration>,
+}
+
+impl HttpConfig {
+	/// Checks that the proxy URI is valid and that the certificates can be loaded.
+	pub fn check(&self) -> std::io::Result<()> {
+		http::check_config(self)
+	}
+}
+
 /// Asynchronous offchain API.
 ///
 /// NOTE this is done to prevent recursive calls into the runtime (which are not supported currently).
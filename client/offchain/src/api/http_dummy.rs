@@ -17,7 +17,8 @@
 //! Contains the same API as the `http` module, except that everything returns an error.
 
 use sp_core::offchain::{HttpRequestId, Timestamp, HttpRequestStatus, HttpError};
-use std::{future::Future, pin::Pin, task::Context, task::Poll};
+use std::{future::Future, io, pin::Pin, task::Context, task::Poll};
+use super::HttpConfig;
 
 /// Wrapper struct (wrapping nothing in case of http_dummy) used for keeping the hyper_rustls client running.
 #[derive(Clone)]
@@ -27,6 +28,15 @@ impl SharedClient {
 	pub fn new() -> Self {
 		Self
 	}
+
+	pub fn with_config(_: &HttpConfig) -> io::Result<Self> {
+		Ok(Self)
+	}
+}
+
+/// Checks the proxy URI and the certificate files of `config`, without building a client.
+pub fn check_config(_: &HttpConfig) -> io::Result<()> {
+	Ok(())
 }
 
 /// Creates a pair of [`HttpApi`] and [`HttpWorker`].
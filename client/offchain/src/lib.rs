@@ -48,6 +48,8 @@ use futures::{prelude::*, future::ready};
 mod api;
 use api::SharedClient;
 
+pub use api::HttpConfig;
+
 pub use sp_offchain::{OffchainWorkerApi, STORAGE_PREFIX};
 
 /// An offchain workers manager.
@@ -62,7 +64,21 @@ pub struct OffchainWorkers<Client, Storage, Block: traits::Block> {
 impl<Client, Storage, Block: traits::Block> OffchainWorkers<Client, Storage, Block> {
 	/// Creates new `OffchainWorkers`.
 	pub fn new(client: Arc<Client>, db: Storage) -> Self {
-		let shared_client = SharedClient::new();
+		Self::with_shared_client(client, db, SharedClient::new())
+	}
+
+	/// Creates new `OffchainWorkers`, whose HTTP client is configured with `http_config`.
+	///
+	/// Fails if the proxy URI is invalid or if a certificate can't be loaded.
+	pub fn with_http_config(
+		client: Arc<Client>,
+		db: Storage,
+		http_config: &HttpConfig,
+	) -> std::io::Result<Self> {
+		Ok(Self::with_shared_client(client, db, SharedClient::with_config(http_config)?))
+	}
+
+	fn with_shared_client(client: Arc<Client>, db: Storage, shared_client: SharedClient) -> Self {
 		Self {
 			client,
 			db,
@@ -141,6 +141,39 @@ pub enum Message {
 	Reject(IncomingIndex),
 }
 
+/// Direction of the connection with a peer, from the point of view of the peerset.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConnectionDirection {
+	/// The remote connected to us.
+	In,
+	/// We connected to the remote.
+	Out,
+}
+
+/// Kind of slot occupied by a peer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SlotType {
+	/// The peer is a reserved node and doesn't occupy a slot.
+	Reserved,
+	/// The peer belongs to a priority group and doesn't occupy a slot.
+	Priority,
+	/// The peer occupies a regular in or out slot when connected.
+	Regular,
+}
+
+/// State of a peer known to the peerset, for inspection purposes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerState {
+	/// Identity of the peer.
+	pub peer_id: PeerId,
+	/// Current reputation of the peer.
+	pub reputation: i32,
+	/// Direction of the connection, or `None` if we aren't connected to the peer.
+	pub direction: Option<ConnectionDirection>,
+	/// Kind of slot the peer occupies.
+	pub slot_type: SlotType,
+}
+
 /// Opaque identifier for an incoming connection. Allocated by the network.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct IncomingIndex(pub u64);
@@ -624,6 +657,37 @@ impl Peerset {
 		})
 	}
 
+	/// Returns the state of all the peers that we know about.
+	pub fn peers_state(&mut self) -> Vec<PeerState> {
+		self.update_time();
+
+		self.data.peers().cloned().collect::<Vec<_>>().into_iter().map(|peer_id| {
+			let (reputation, direction) = match self.data.peer(&peer_id) {
+				peersstate::Peer::Connected(entry) => {
+					let direction = if entry.is_ingoing() {
+						ConnectionDirection::In
+					} else {
+						ConnectionDirection::Out
+					};
+					(entry.reputation(), Some(direction))
+				}
+				peersstate::Peer::NotConnected(entry) => (entry.reputation(), None),
+				peersstate::Peer::Unknown(_) =>
+					unreachable!("We iterate over the known peers; QED")
+			};
+
+			let slot_type = if self.priority_groups.get(RESERVED_NODES).map_or(false, |g| g.contains(&peer_id)) {
+				SlotType::Reserved
+			} else if self.priority_groups.values().any(|g| g.contains(&peer_id)) {
+				SlotType::Priority
+			} else {
+				SlotType::Regular
+			};
+
+			PeerState { peer_id, reputation, direction, slot_type }
+		}).collect()
+	}
+
 	/// Returns the number of peers that we have discovered.
 	pub fn num_discovered_peers(&self) -> usize {
 		self.data.peers().len()
@@ -674,7 +738,10 @@ impl Stream for Peerset {
 mod tests {
 	use libp2p::PeerId;
 	use futures::prelude::*;
-	use super::{PeersetConfig, Peerset, Message, IncomingIndex, ReputationChange, BANNED_THRESHOLD};
+	use super::{
+		PeersetConfig, Peerset, Message, IncomingIndex, ReputationChange, BANNED_THRESHOLD,
+		ConnectionDirection, SlotType,
+	};
 	use std::{pin::Pin, task::Poll, thread, time::Duration};
 
 	fn assert_messages(mut peerset: Peerset, messages: Vec<Message>) -> Peerset {
@@ -831,4 +898,41 @@ mod tests {
 
 		futures::executor::block_on(fut);
 	}
+
+	#[test]
+	fn test_peerset_peers_state() {
+		let reserved = PeerId::random();
+		let incoming = PeerId::random();
+		let (mut peerset, handle) = Peerset::from_config(PeersetConfig {
+			in_peers: 25,
+			out_peers: 25,
+			bootnodes: vec![],
+			reserved_only: false,
+			priority_groups: vec![("reserved".to_owned(), vec![reserved.clone()].into_iter().collect())],
+		});
+
+		peerset.incoming(incoming.clone(), IncomingIndex(1));
+		handle.report_peer(incoming.clone(), ReputationChange::new(-1000, ""));
+
+		let fut = futures::future::poll_fn(move |cx| {
+			// Process the pending messages and actions.
+			while let Poll::Ready(_) = Stream::poll_next(Pin::new(&mut peerset), cx) {}
+
+			let states = peerset.peers_state();
+			assert_eq!(states.len(), 2);
+
+			let reserved_state = states.iter().find(|s| s.peer_id == reserved).unwrap();
+			assert_eq!(reserved_state.slot_type, SlotType::Reserved);
+			assert_eq!(reserved_state.direction, Some(ConnectionDirection::Out));
+
+			let incoming_state = states.iter().find(|s| s.peer_id == incoming).unwrap();
+			assert_eq!(incoming_state.slot_type, SlotType::Regular);
+			assert_eq!(incoming_state.direction, Some(ConnectionDirection::In));
+			assert!(incoming_state.reputation < 0);
+
+			Poll::Ready(())
+		});
+
+		futures::executor::block_on(fut);
+	}
 }
@@ -311,6 +311,12 @@ impl<'a> ConnectedPeer<'a> {
 		}
 	}
 
+	/// Returns `true` if the peer is connected through an ingoing connection.
+	pub fn is_ingoing(&self) -> bool {
+		self.state.nodes.get(&*self.peer_id)
+			.map_or(false, |p| p.connection_state == ConnectionState::In)
+	}
+
 	/// Returns the reputation value of the node.
 	pub fn reputation(&self) -> i32 {
 		self.state.nodes.get(&*self.peer_id).map_or(0, |p| p.reputation)
@@ -33,6 +33,10 @@ pub enum Error {
 	/// Client error.
 	#[display(fmt="Client error: {}", _0)]
 	Client(Box<dyn std::error::Error + Send>),
+	/// The body of the requested block has been pruned.
+	#[display(fmt="Body of block {} has been pruned", _0)]
+	#[from(ignore)]
+	BlockBodyPruned(String),
 	/// Other error type.
 	Other(String),
 }
@@ -57,6 +61,11 @@ impl From<Error> for rpc::Error {
 				message,
 				data: None,
 			},
+			Error::BlockBodyPruned(hash) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 2),
+				message: format!("Body of block {} has been pruned", hash),
+				data: None,
+			},
 			e => errors::internal(e),
 		}
 	}
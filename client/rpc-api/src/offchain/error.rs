@@ -31,6 +31,19 @@ pub enum Error {
 	UnavailableStorageKind,
 	/// Call to an unsafe RPC was denied.
 	UnsafeRpcCalled(crate::policy::UnsafeRpcError),
+	/// The offchain storage can't be iterated over.
+	#[display(fmt="The offchain storage can't be iterated over.")]
+	#[from(ignore)]
+	StorageIteration,
+	/// Provided count exceeds maximum value.
+	#[display(fmt = "count exceeds maximum value. value: {}, max: {}", value, max)]
+	#[from(ignore)]
+	InvalidCount {
+		/// Provided value
+		value: u32,
+		/// Maximum allowed value
+		max: u32,
+	},
 }
 
 impl std::error::Error for Error {
@@ -54,6 +67,16 @@ impl From<Error> for rpc::Error {
 				data: None,
 			},
 			Error::UnsafeRpcCalled(e) => e.into(),
+			Error::StorageIteration => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 2),
+				message: "The offchain storage can't be iterated over".into(),
+				data: None,
+			},
+			Error::InvalidCount { .. } => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 3),
+				message: format!("{}", e),
+				data: None,
+			},
 		}
 	}
 }
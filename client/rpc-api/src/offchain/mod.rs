@@ -36,4 +36,24 @@ pub trait OffchainApi {
 	/// Get offchain local storage under given key and prefix.
 	#[rpc(name = "offchain_localStorageGet")]
 	fn get_local_storage(&self, kind: StorageKind, key: Bytes) -> Result<Option<Bytes>>;
+
+	/// Get the entries written through offchain indexing whose key starts with given prefix.
+	///
+	/// Up to `count` entries will be returned, sorted by key.
+	/// If `start_key` is passed, return the next entries in lexicographic order.
+	///
+	/// The offchain storage must support iteration, which is the case with RocksDB but not
+	/// with ParityDb.
+	#[rpc(name = "offchain_getIndexed")]
+	fn get_indexed(&self, prefix: Bytes, count: u32, start_key: Option<Bytes>) -> Result<Vec<(Bytes, Bytes)>>;
+
+	/// Get the keys written through offchain indexing that start with given prefix.
+	///
+	/// Up to `count` keys will be returned, sorted.
+	/// If `start_key` is passed, return the next keys in lexicographic order.
+	///
+	/// The offchain storage must support iteration, which is the case with RocksDB but not
+	/// with ParityDb.
+	#[rpc(name = "offchain_getIndexedKeys")]
+	fn get_indexed_keys(&self, prefix: Bytes, count: u32, start_key: Option<Bytes>) -> Result<Vec<Bytes>>;
 }
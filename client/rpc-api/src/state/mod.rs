@@ -127,6 +127,9 @@ pub trait StateApi<Hash> {
 	fn unsubscribe_runtime_version(&self, metadata: Option<Self::Metadata>, id: SubscriptionId) -> RpcResult<bool>;
 
 	/// New storage subscription
+	///
+	/// On full nodes, changes are reported for every imported block. Light nodes follow the
+	/// finalized chain only and report changes for every finalized block.
 	#[pubsub(subscription = "state_storage", subscribe, name = "state_subscribeStorage")]
 	fn subscribe_storage(
 		&self, metadata: Self::Metadata, subscriber: Subscriber<StorageChangeSet<Hash>>, keys: Option<Vec<StorageKey>>
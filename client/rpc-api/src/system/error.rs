@@ -32,6 +32,12 @@ pub enum Error {
 	NotHealthy(Health),
 	/// Peer argument is malformatted.
 	MalformattedPeerArg(String),
+	/// IP range argument is malformatted.
+	MalformattedIpRangeArg(String),
+	/// Ban duration argument is too long.
+	#[display(fmt = "Ban duration of {} seconds exceeds the maximum of {} seconds", _0, _1)]
+	#[from(ignore)]
+	BanDurationTooLong(u64, u64),
 }
 
 impl std::error::Error for Error {}
@@ -51,7 +57,17 @@ impl From<Error> for rpc::Error {
 				code :rpc::ErrorCode::ServerError(BASE_ERROR + 2),
 				message: e.clone(),
 				data: None,
-			}
+			},
+			Error::MalformattedIpRangeArg(ref e) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 3),
+				message: e.clone(),
+				data: None,
+			},
+			Error::BanDurationTooLong(..) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 4),
+				message: format!("{}", e),
+				data: None,
+			},
 		}
 	}
 }
@@ -88,6 +88,42 @@ pub enum NodeRole {
 	Sentry,
 }
 
+/// Reputation and connection state of a peer known to the peer set manager
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerReputation {
+	/// Peer ID
+	pub peer_id: String,
+	/// Current reputation of the peer
+	pub reputation: i32,
+	/// Direction of the connection, or `None` if the peer isn't connected
+	pub direction: Option<PeerDirection>,
+	/// Kind of slot the peer occupies
+	pub slot_type: PeerSlotType,
+}
+
+/// Direction of the connection with a peer
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PeerDirection {
+	/// The peer connected to us
+	Inbound,
+	/// We connected to the peer
+	Outbound,
+}
+
+/// Kind of slot occupied by a peer
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PeerSlotType {
+	/// The peer is a reserved node
+	Reserved,
+	/// The peer belongs to a priority group, such as sentries and validators
+	Priority,
+	/// The peer occupies a regular slot
+	Regular,
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -117,4 +153,17 @@ mod tests {
 			r#"{"peerId":"2","roles":"a","protocolVersion":2,"bestHash":5,"bestNumber":6}"#,
 		);
 	}
+
+	#[test]
+	fn should_serialize_peer_reputation() {
+		assert_eq!(
+			::serde_json::to_string(&PeerReputation {
+				peer_id: "2".into(),
+				reputation: -100,
+				direction: Some(PeerDirection::Inbound),
+				slot_type: PeerSlotType::Regular,
+			}).unwrap(),
+			r#"{"peerId":"2","reputation":-100,"direction":"inbound","slotType":"regular"}"#,
+		);
+	}
 }
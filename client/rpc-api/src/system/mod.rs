@@ -27,7 +27,9 @@ use futures::{future::BoxFuture, compat::Compat};
 
 use self::error::Result as SystemResult;
 
-pub use self::helpers::{SystemInfo, Health, PeerInfo, NodeRole};
+pub use self::helpers::{
+	SystemInfo, Health, PeerInfo, NodeRole, PeerReputation, PeerDirection, PeerSlotType,
+};
 pub use self::gen_client::Client as SystemClient;
 
 /// Substrate system RPC API
@@ -100,6 +102,38 @@ pub trait SystemApi<Hash, Number> {
 	fn system_remove_reserved_peer(&self, peer_id: String)
 		-> Compat<BoxFuture<'static, Result<(), jsonrpc_core::Error>>>;
 
+	/// Returns the peers known to the peer set manager, with their reputation, the direction of
+	/// the connection and the kind of slot they occupy.
+	#[rpc(name = "system_peersReputation", returns = "Vec<PeerReputation>")]
+	fn system_peers_reputation(&self)
+		-> Compat<BoxFuture<'static, jsonrpc_core::Result<Vec<PeerReputation>>>>;
+
+	/// Bans a peer for the given number of seconds. The string should encode only the PeerId
+	/// e.g. `QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV`.
+	///
+	/// The ban is persisted and survives restarts of the node.
+	#[rpc(name = "system_banPeer", returns = "()")]
+	fn system_ban_peer(&self, peer_id: String, duration_secs: u64)
+		-> Compat<BoxFuture<'static, Result<(), jsonrpc_core::Error>>>;
+
+	/// Lifts the ban of a peer. The string should encode only the PeerId.
+	#[rpc(name = "system_unbanPeer", returns = "()")]
+	fn system_unban_peer(&self, peer_id: String)
+		-> Compat<BoxFuture<'static, Result<(), jsonrpc_core::Error>>>;
+
+	/// Bans all the peers whose IP address belongs to the given range for the given number of
+	/// seconds. The string should encode an IP range, e.g. `192.0.2.0/24`.
+	///
+	/// The ban is persisted and survives restarts of the node.
+	#[rpc(name = "system_banIpRange", returns = "()")]
+	fn system_ban_ip_range(&self, range: String, duration_secs: u64)
+		-> Compat<BoxFuture<'static, Result<(), jsonrpc_core::Error>>>;
+
+	/// Lifts the ban of an IP range. The string should encode an IP range, e.g. `192.0.2.0/24`.
+	#[rpc(name = "system_unbanIpRange", returns = "()")]
+	fn system_unban_ip_range(&self, range: String)
+		-> Compat<BoxFuture<'static, Result<(), jsonrpc_core::Error>>>;
+
 	/// Returns the roles the node is running as.
 	#[rpc(name = "system_nodeRoles", returns = "Vec<NodeRole>")]
 	fn system_node_roles(&self) -> Receiver<Vec<NodeRole>>;
@@ -23,7 +23,7 @@ use jsonrpc_pubsub::manager::SubscriptionManager;
 use sc_client_api::{BlockchainEvents, BlockBackend};
 use sp_runtime::{generic::{BlockId, SignedBlock}, traits::{Block as BlockT}};
 
-use super::{ChainBackend, client_err, error::FutureResult};
+use super::{ChainBackend, client_err, error::{Error, FutureResult}};
 use std::marker::PhantomData;
 use sp_blockchain::HeaderBackend;
 
@@ -70,9 +70,14 @@ impl<Block, Client> ChainBackend<Client, Block> for FullChain<Block, Client> whe
 	fn block(&self, hash: Option<Block::Hash>)
 		-> FutureResult<Option<SignedBlock<Block>>>
 	{
-		Box::new(result(self.client
-			.block(&BlockId::Hash(self.unwrap_or_best(hash)))
-			.map_err(client_err)
-		))
+		let hash = self.unwrap_or_best(hash);
+		let block = self.client.block(&BlockId::Hash(hash)).map_err(client_err);
+		Box::new(result(block.and_then(|block| match block {
+			// Full nodes import every body along with its header, so a missing body has
+			// been pruned.
+			None if self.client.header(BlockId::Hash(hash)).map_err(client_err)?.is_some() =>
+				Err(Error::BlockBodyPruned(format!("{:?}", hash))),
+			block => Ok(block),
+		})))
 	}
 }
@@ -272,3 +272,38 @@ fn should_notify_about_finalized_block() {
 	// no more notifications on this channel
 	assert_eq!(executor::block_on(next.into_future().compat()).unwrap().0, None);
 }
+
+#[test]
+fn should_return_error_for_pruned_block_body() {
+	use substrate_test_runtime_client::sc_client_db::BlocksPruning;
+
+	let backend = Arc::new(substrate_test_runtime_client::Backend::new_test_with_blocks_pruning(
+		u32::max_value(),
+		BlocksPruning::KeepFinalized { blocks: 1, justifications: false },
+		u64::max_value(),
+	));
+	let mut client = Arc::new(TestClientBuilder::with_backend(backend).build());
+	let api = new_full(client.clone(), SubscriptionManager::new(Arc::new(TaskExecutor)));
+
+	let mut hashes = Vec::new();
+	for _ in 0..3 {
+		let block = client.new_block(Default::default()).unwrap().build().unwrap().block;
+		hashes.push(block.hash());
+		client.import(BlockOrigin::Own, block).unwrap();
+	}
+	client.finalize_block(BlockId::number(3), None).unwrap();
+
+	assert_matches!(
+		api.block(Some(hashes[0]).into()).wait(),
+		Err(Error::BlockBodyPruned(_))
+	);
+	assert_matches!(
+		api.block(Some(hashes[2]).into()).wait(),
+		Ok(Some(ref x)) if x.block.header.number == 3
+	);
+	// Unknown blocks are still reported as missing.
+	assert_matches!(
+		api.block(Some(H256::from_low_u64_be(5)).into()).wait(),
+		Ok(None)
+	);
+}
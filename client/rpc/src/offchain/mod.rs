@@ -32,6 +32,9 @@ use sp_core::{
 use parking_lot::RwLock;
 use std::sync::Arc;
 
+/// Maximum number of entries or keys returned by the offchain indexing calls.
+const INDEXED_MAX_COUNT: u32 = 1000;
+
 /// Offchain API
 #[derive(Debug)]
 pub struct Offchain<T: OffchainStorage> {
@@ -73,4 +76,37 @@ impl<T: OffchainStorage + 'static> OffchainApi for Offchain<T> {
 		};
 		Ok(self.storage.read().get(prefix, &*key).map(Into::into))
 	}
+
+	/// Get the entries written through offchain indexing whose key starts with given prefix.
+	fn get_indexed(&self, prefix: Bytes, count: u32, start_key: Option<Bytes>) -> Result<Vec<(Bytes, Bytes)>> {
+		self.deny_unsafe.check_if_safe()?;
+
+		Ok(self.indexed(&prefix, count, start_key)?
+			.into_iter()
+			.map(|(key, value)| (key.into(), value.into()))
+			.collect())
+	}
+
+	/// Get the keys written through offchain indexing that start with given prefix.
+	fn get_indexed_keys(&self, prefix: Bytes, count: u32, start_key: Option<Bytes>) -> Result<Vec<Bytes>> {
+		self.deny_unsafe.check_if_safe()?;
+
+		Ok(self.indexed(&prefix, count, start_key)?.into_iter().map(|(key, _)| key.into()).collect())
+	}
+}
+
+impl<T: OffchainStorage> Offchain<T> {
+	/// Up to `count` indexed entries whose key starts with `prefix`, sorted by key and starting
+	/// after `start_key`.
+	fn indexed(&self, prefix: &[u8], count: u32, start_key: Option<Bytes>) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+		if count > INDEXED_MAX_COUNT {
+			return Err(Error::InvalidCount {
+				value: count,
+				max: INDEXED_MAX_COUNT,
+			});
+		}
+		self.storage.read()
+			.iter_prefix(sp_offchain::INDEXING_PREFIX, prefix, start_key.as_deref(), count as usize)
+			.map_err(|_| Error::StorageIteration)
+	}
 }
@@ -52,4 +52,52 @@ fn offchain_calls_considered_unsafe() {
 		offchain.get_local_storage(StorageKind::PERSISTENT, key),
 		Err(Error::UnsafeRpcCalled(_))
 	);
+	assert_matches!(
+		offchain.get_indexed(Bytes(b"offchain".to_vec()), 10, None),
+		Err(Error::UnsafeRpcCalled(_))
+	);
+}
+
+#[test]
+fn indexed_storage_should_work() {
+	let mut storage = InMemOffchainStorage::default();
+	storage.set(sp_offchain::INDEXING_PREFIX, b"session_2", b"b");
+	storage.set(sp_offchain::INDEXING_PREFIX, b"session_1", b"a");
+	storage.set(sp_offchain::INDEXING_PREFIX, b"other", b"c");
+	storage.set(sp_offchain::STORAGE_PREFIX, b"session_3", b"d");
+	let offchain = Offchain::new(storage, DenyUnsafe::No);
+
+	assert_eq!(
+		offchain.get_indexed(Bytes(b"session_".to_vec()), 10, None).unwrap(),
+		vec![
+			(Bytes(b"session_1".to_vec()), Bytes(b"a".to_vec())),
+			(Bytes(b"session_2".to_vec()), Bytes(b"b".to_vec())),
+		],
+	);
+	assert_eq!(
+		offchain.get_indexed_keys(Bytes(Vec::new()), 10, None).unwrap(),
+		vec![Bytes(b"other".to_vec()), Bytes(b"session_1".to_vec()), Bytes(b"session_2".to_vec())],
+	);
+}
+
+#[test]
+fn indexed_storage_should_be_paged() {
+	let mut storage = InMemOffchainStorage::default();
+	storage.set(sp_offchain::INDEXING_PREFIX, b"session_3", b"c");
+	storage.set(sp_offchain::INDEXING_PREFIX, b"session_1", b"a");
+	storage.set(sp_offchain::INDEXING_PREFIX, b"session_2", b"b");
+	let offchain = Offchain::new(storage, DenyUnsafe::No);
+
+	assert_eq!(
+		offchain.get_indexed_keys(Bytes(b"session_".to_vec()), 2, None).unwrap(),
+		vec![Bytes(b"session_1".to_vec()), Bytes(b"session_2".to_vec())],
+	);
+	assert_eq!(
+		offchain.get_indexed(Bytes(b"session_".to_vec()), 2, Some(Bytes(b"session_2".to_vec()))).unwrap(),
+		vec![(Bytes(b"session_3".to_vec()), Bytes(b"c".to_vec()))],
+	);
+	assert_matches!(
+		offchain.get_indexed_keys(Bytes(Vec::new()), INDEXED_MAX_COUNT + 1, None),
+		Err(Error::InvalidCount { .. })
+	);
 }
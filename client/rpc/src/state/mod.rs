@@ -41,7 +41,7 @@ use self::error::{Error, FutureResult};
 pub use sc_rpc_api::state::*;
 pub use sc_rpc_api::child_state::*;
 use sc_client_api::{ExecutorProvider, StorageProvider, BlockchainEvents, Backend, ProofProvider};
-use sp_blockchain::{HeaderMetadata, HeaderBackend};
+use sp_blockchain::{HeaderMetadata, HeaderBackend, ProvideCache};
 
 const STORAGE_KEYS_PAGED_MAX_COUNT: u32 = 1000;
 
@@ -201,7 +201,7 @@ pub fn new_light<BE, Block: BlockT, Client, F: Fetcher<Block>>(
 		Client: ExecutorProvider<Block> + StorageProvider<Block, BE>
 			+ HeaderMetadata<Block, Error = sp_blockchain::Error>
 			+ ProvideRuntimeApi<Block> + HeaderBackend<Block> + BlockchainEvents<Block>
-			+ Send + Sync + 'static,
+			+ ProvideCache<Block> + Send + Sync + 'static,
 		F: Send + Sync + 'static,
 {
 	let child_backend = Box::new(self::state_light::LightState::new(
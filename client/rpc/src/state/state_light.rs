@@ -22,14 +22,14 @@ use std::{
 };
 use codec::Decode;
 use futures::{
-	future::{ready, Either},
+	future::{ready, try_join_all, Either},
 	channel::oneshot::{channel, Sender},
 	FutureExt, TryFutureExt,
 	StreamExt as _, TryStreamExt as _,
 };
 use hash_db::Hasher;
 use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId, manager::SubscriptionManager};
-use log::warn;
+use log::{debug, warn};
 use parking_lot::Mutex;
 use rpc::{
 	Result as RpcResult,
@@ -39,20 +39,23 @@ use rpc::{
 };
 
 use sc_rpc_api::state::ReadProof;
-use sp_blockchain::{Error as ClientError, HeaderBackend};
+use sp_blockchain::{Error as ClientError, HeaderBackend, ProvideCache, well_known_cache_keys};
 use sc_client_api::{
 	BlockchainEvents,
 	light::{
-		RemoteCallRequest, RemoteReadRequest, RemoteReadChildRequest,
+		RemoteCallRequest, RemoteReadRequest, RemoteReadChildRequest, RemoteChangesRequest,
 		RemoteBlockchain, Fetcher, future_header,
 	},
 };
 use sp_core::{
-	Bytes, OpaqueMetadata,
+	Bytes, OpaqueMetadata, ChangesTrieConfiguration, ChangesTrieConfigurationRange,
 	storage::{StorageKey, PrefixedStorageKey, StorageData, StorageChangeSet},
 };
 use sp_version::RuntimeVersion;
-use sp_runtime::{generic::BlockId, traits::{Block as BlockT, HashFor}};
+use sp_runtime::{
+	generic::{BlockId, DigestItem},
+	traits::{Block as BlockT, Header as HeaderT, HashFor, NumberFor, One},
+};
 
 use super::{StateBackend, ChildStateBackend, error::{FutureResult, Error}, client_err};
 
@@ -93,6 +96,47 @@ struct StorageSubscriptions<Block: BlockT> {
 	keys_by_subscription: HashMap<SubscriptionId, HashSet<StorageKey>>,
 	/// Map of key => set of subscriptions that watch this key.
 	subscriptions_by_key: HashMap<StorageKey, HashSet<SubscriptionId>>,
+	/// Values of the subscribed keys that have been read at the last block. Used to avoid
+	/// issuing requests when the state hasn't changed and to only re-read changed keys.
+	last_read: Option<LastRead<Block>>,
+}
+
+/// Values of the subscribed keys that have been read at some block.
+#[derive(Clone)]
+struct LastRead<Block: BlockT> {
+	/// Hash of the block the values have been read at.
+	hash: Block::Hash,
+	/// Number of the block the values have been read at.
+	number: NumberFor<Block>,
+	/// State root of the block the values have been read at.
+	state_root: Block::Hash,
+	/// The values that have been read.
+	values: StorageMap,
+}
+
+impl<Block: BlockT> LastRead<Block> {
+	/// Returns the values of the given keys, if all of them have been read.
+	fn values_of(&self, keys: &[Vec<u8>]) -> Option<StorageMap> {
+		keys.iter()
+			.map(|key| {
+				let key = StorageKey(key.clone());
+				self.values.get(&key).map(|value| (key, value.clone()))
+			})
+			.collect()
+	}
+}
+
+impl<Block: BlockT> StorageSubscriptions<Block> {
+	/// Returns the values of the given keys if they have been read at a block with the given
+	/// state root.
+	fn cached_values(&self, state_root: &Block::Hash, keys: &[Vec<u8>]) -> Option<StorageMap> {
+		let last_read = self.last_read.as_ref()?;
+		if last_read.state_root != *state_root {
+			return None;
+		}
+
+		last_read.values_of(keys)
+	}
 }
 
 impl<Block: BlockT> SharedRequests<Block::Hash, StorageMap> for Arc<Mutex<StorageSubscriptions<Block>>> {
@@ -155,6 +199,7 @@ impl<Block: BlockT, F: Fetcher<Block> + 'static, Client> LightState<Block, F, Cl
 				active_requests: HashMap::new(),
 				keys_by_subscription: HashMap::new(),
 				subscriptions_by_key: HashMap::new(),
+				last_read: None,
 			})),
 			remote_blockchain,
 			fetcher,
@@ -170,7 +215,8 @@ impl<Block: BlockT, F: Fetcher<Block> + 'static, Client> LightState<Block, F, Cl
 impl<Block, F, Client> StateBackend<Block, Client> for LightState<Block, F, Client>
 	where
 		Block: BlockT,
-		Client: BlockchainEvents<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+		Client: BlockchainEvents<Block> + HeaderBackend<Block> + ProvideCache<Block>
+			+ Send + Sync + 'static,
 		F: Fetcher<Block> + 'static
 {
 	fn call(
@@ -312,16 +358,19 @@ impl<Block, F, Client> StateBackend<Block, Client> for LightState<Block, F, Clie
 		let keys = keys.iter().cloned().collect::<HashSet<_>>();
 		let keys_to_check = keys.iter().map(|k| k.0.clone()).collect::<HashSet<_>>();
 		let subscription_id = self.subscriptions.add(subscriber, move |sink| {
+			let client = self.client.clone();
 			let fetcher = self.fetcher.clone();
 			let remote_blockchain = self.remote_blockchain.clone();
 			let storage_subscriptions = self.storage_subscriptions.clone();
-			let initial_block = self.block_or_best(None);
+			// light nodes can only prove the state of blocks they have the header of, so
+			// subscriptions follow the finalized chain rather than every imported block
+			let initial_block = self.client.info().finalized_hash;
 			let initial_keys = keys_to_check.iter().cloned().collect::<Vec<_>>();
 
 			let changes_stream = subscription_stream::<Block, _, _, _, _, _, _, _, _>(
 				storage_subscriptions.clone(),
 				self.client
-					.import_notification_stream()
+					.finality_notification_stream()
 					.map(|notification| Ok::<_, ()>(notification.hash))
 					.compat(),
 				display_error(storage(
@@ -330,45 +379,19 @@ impl<Block, F, Client> StateBackend<Block, Client> for LightState<Block, F, Clie
 					initial_block,
 					initial_keys,
 				).map(move |r| r.map(|r| (initial_block, r)))),
-				move |block| {
-					// there'll be single request per block for all active subscriptions
-					// with all subscribed keys
-					let keys = storage_subscriptions
-						.lock()
-						.subscriptions_by_key
-						.keys()
-						.map(|k| k.0.clone())
-						.collect();
-
-					storage(
-						&*remote_blockchain,
-						fetcher.clone(),
-						block,
-						keys,
-					)
-				},
-				move |block, old_value, new_value| {
-					// let's only select keys which are valid for this subscription
-					let new_value = new_value
-						.iter()
-						.filter(|(k, _)| keys_to_check.contains(&k.0))
-						.map(|(k, v)| (k.clone(), v.clone()))
-						.collect::<HashMap<_, _>>();
-					let value_differs = old_value
-						.as_ref()
-						.map(|old_value| **old_value != new_value)
-						.unwrap_or(true);
-					match value_differs {
-						true => Some(StorageChangeSet {
-							block,
-							changes: new_value
-								.iter()
-								.map(|(k, v)| (k.clone(), v.clone()))
-								.collect(),
-						}),
-						false => None,
-					}
-				}
+				move |block| subscribed_storage(
+					&*client,
+					remote_blockchain.clone(),
+					fetcher.clone(),
+					storage_subscriptions.clone(),
+					block,
+				),
+				move |block, old_value, new_value| storage_change_set::<Block>(
+					&keys_to_check,
+					block,
+					old_value,
+					new_value,
+				),
 			);
 
 			sink
@@ -617,6 +640,177 @@ fn storage<Block: BlockT, F: Fetcher<Block>>(
 		})
 }
 
+/// Get values of all subscribed keys at given block.
+///
+/// There'll be single request per block for all active subscriptions with all subscribed keys.
+/// If the keys have been read at some ancestor of the block, only the keys that (according to
+/// the changes tries) have been changed since then are read again. Otherwise all keys are read
+/// using a single batched read proof.
+fn subscribed_storage<Block, F, Client>(
+	client: &Client,
+	remote_blockchain: Arc<dyn RemoteBlockchain<Block>>,
+	fetcher: Arc<F>,
+	storage_subscriptions: Arc<Mutex<StorageSubscriptions<Block>>>,
+	block: Block::Hash,
+) -> impl std::future::Future<Output = Result<StorageMap, Error>> where
+	Block: BlockT,
+	F: Fetcher<Block> + 'static,
+	Client: HeaderBackend<Block> + ProvideCache<Block>,
+{
+	let header = client.header(BlockId::Hash(block))
+		.ok()
+		.and_then(|header| header);
+	let (keys, cached_values, last_read) = {
+		let subscriptions = storage_subscriptions.lock();
+		let keys = subscriptions
+			.subscriptions_by_key
+			.keys()
+			.map(|k| k.0.clone())
+			.collect::<Vec<_>>();
+		let cached_values = header.as_ref().and_then(|header|
+			subscriptions.cached_values(header.state_root(), &keys)
+		);
+		(keys, cached_values, subscriptions.last_read.clone())
+	};
+	let changes = header.as_ref()
+		.and_then(|header| last_read.as_ref().map(|last_read| (header, last_read)))
+		.and_then(|(header, last_read)| Some((
+			last_read.values_of(&keys)?,
+			changes_request(client, last_read, header)?,
+		)));
+
+	let values = match (cached_values, changes) {
+		// if the state root hasn't changed since the last request, neither have the values
+		(Some(values), _) => ready(Ok(values)).boxed(),
+		(None, Some((values, request))) => changed_storage(
+			remote_blockchain,
+			fetcher,
+			block,
+			values,
+			request,
+		).boxed(),
+		(None, None) => storage(&*remote_blockchain, fetcher, block, keys).boxed(),
+	};
+
+	values.map(move |result| {
+		if let (Ok(values), Some(header)) = (&result, header) {
+			storage_subscriptions.lock().last_read = Some(LastRead {
+				hash: block,
+				number: *header.number(),
+				state_root: *header.state_root(),
+				values: values.clone(),
+			});
+		}
+		result
+	})
+}
+
+/// Compose the changes tries request for all blocks after the `last_read` block, up to (and
+/// including) the given header.
+///
+/// Returns None if the header isn't a descendant of the last read block, if changes tries are
+/// disabled or their configuration has been changed within the range, or if some of the headers
+/// or changes tries roots in the range are unknown.
+fn changes_request<Block, Client>(
+	client: &Client,
+	last_read: &LastRead<Block>,
+	header: &Block::Header,
+) -> Option<RemoteChangesRequest<Block::Header>> where
+	Block: BlockT,
+	Client: HeaderBackend<Block> + ProvideCache<Block>,
+{
+	let number = *header.number();
+	let hash = header.hash();
+	if number <= last_read.number {
+		return None;
+	}
+
+	let (zero, end, config) = client.cache()?
+		.get_at(&well_known_cache_keys::CHANGES_TRIE_CONFIG, &BlockId::Hash(hash))
+		.ok()??;
+	if zero.0 > last_read.number {
+		return None;
+	}
+	let config = Option::<ChangesTrieConfiguration>::decode(&mut &config[..]).ok()?;
+	if config.is_none() {
+		return None;
+	}
+
+	// collect changes tries roots of all blocks in the range, starting from the last one
+	let mut roots = Vec::new();
+	let mut current = header.clone();
+	loop {
+		roots.push(*current.digest().log(DigestItem::as_changes_trie_root)?);
+		if *current.parent_hash() == last_read.hash {
+			break;
+		}
+		if *current.number() <= last_read.number + One::one() {
+			return None;
+		}
+		current = client.header(BlockId::Hash(*current.parent_hash())).ok()??;
+	}
+	roots.reverse();
+
+	let first_block = (*current.number(), current.hash());
+	Some(RemoteChangesRequest {
+		changes_trie_configs: vec![ChangesTrieConfigurationRange { zero, end, config }],
+		first_block,
+		last_block: (number, hash),
+		max_block: (number, hash),
+		tries_roots: (first_block.0, first_block.1, roots),
+		storage_key: None,
+		key: Vec::new(),
+		retry_count: Default::default(),
+	})
+}
+
+/// Get values of given keys at given block, reusing the `values` read at the parent of the
+/// `request.first_block` for keys that haven't been changed since then.
+///
+/// Falls back to reading all keys if the changes can't be fetched.
+fn changed_storage<Block, F>(
+	remote_blockchain: Arc<dyn RemoteBlockchain<Block>>,
+	fetcher: Arc<F>,
+	block: Block::Hash,
+	mut values: StorageMap,
+	request: RemoteChangesRequest<Block::Header>,
+) -> impl std::future::Future<Output = Result<StorageMap, Error>> where
+	Block: BlockT,
+	F: Fetcher<Block> + 'static,
+{
+	let keys = values.keys().map(|key| key.0.clone()).collect::<Vec<_>>();
+	let changes = keys.iter()
+		.map(|key| fetcher.remote_changes(RemoteChangesRequest {
+			key: key.clone(),
+			..request.clone()
+		}))
+		.collect::<Vec<_>>();
+
+	try_join_all(changes).then(move |result| match result {
+		Ok(changes) => {
+			let changed_keys = keys.into_iter()
+				.zip(changes)
+				.filter(|(_, changes)| !changes.is_empty())
+				.map(|(key, _)| key)
+				.collect::<Vec<_>>();
+			if changed_keys.is_empty() {
+				return Either::Left(ready(Ok(values)));
+			}
+
+			Either::Right(storage(&*remote_blockchain, fetcher, block, changed_keys)
+				.map(move |result| result.map(|changed_values| {
+					values.extend(changed_values);
+					values
+				}))
+				.left_future())
+		},
+		Err(error) => {
+			debug!("Failed to fetch changes of subscribed keys: {:?}. Reading all keys", error);
+			Either::Right(storage(&*remote_blockchain, fetcher, block, keys).right_future())
+		},
+	})
+}
+
 /// Returns subscription stream that issues request on every imported block and
 /// if value has changed from previous block, emits (stream) item.
 fn subscription_stream<
@@ -677,6 +871,33 @@ fn subscription_stream<
 		.map_err(|_| ())
 }
 
+/// Returns the change set of the subscription keys if their values at given block differ from
+/// the values that have been sent with the previous notification.
+fn storage_change_set<Block: BlockT>(
+	keys_to_check: &HashSet<Vec<u8>>,
+	block: Block::Hash,
+	old_value: Option<&StorageMap>,
+	new_value: &StorageMap,
+) -> Option<StorageChangeSet<Block::Hash>> {
+	// let's only select keys which are valid for this subscription
+	let select = |values: &StorageMap| values
+		.iter()
+		.filter(|(k, _)| keys_to_check.contains(&k.0))
+		.map(|(k, v)| (k.clone(), v.clone()))
+		.collect::<HashMap<_, _>>();
+	let new_value = select(new_value);
+	let value_differs = old_value
+		.map(|old_value| select(old_value) != new_value)
+		.unwrap_or(true);
+	match value_differs {
+		true => Some(StorageChangeSet {
+			block,
+			changes: new_value.into_iter().collect(),
+		}),
+		false => None,
+	}
+}
+
 /// Request some data from remote node, probably reusing response from already
 /// (in-progress) existing request.
 fn maybe_share_remote_request<Block: BlockT, Requests, V, IssueRequest, IssueRequestFuture>(
@@ -742,11 +963,193 @@ fn ignore_error<F, T>(future: F) -> impl std::future::Future<Output=Result<Optio
 
 #[cfg(test)]
 mod tests {
+	use codec::Encode;
 	use rpc::futures::stream::futures_ordered;
-	use substrate_test_runtime_client::runtime::Block;
+	use sc_client_api::light::{LocalOrRemote, RemoteHeaderRequest, RemoteBodyRequest};
+	use sp_runtime::generic::Digest;
+	use substrate_test_runtime_client::runtime::{Block, Header, Extrinsic};
 	use sp_core::H256;
 	use super::*;
 
+	/// Light client that knows the headers of some blocks.
+	struct TestClient {
+		headers: HashMap<H256, Header>,
+		changes_trie_config: Option<ChangesTrieConfiguration>,
+	}
+
+	impl HeaderBackend<Block> for TestClient {
+		fn header(&self, id: BlockId<Block>) -> sp_blockchain::Result<Option<Header>> {
+			match id {
+				BlockId::Hash(hash) => Ok(self.headers.get(&hash).cloned()),
+				BlockId::Number(_) => unimplemented!(),
+			}
+		}
+
+		fn info(&self) -> sp_blockchain::Info<Block> {
+			unimplemented!()
+		}
+
+		fn status(&self, _: BlockId<Block>) -> sp_blockchain::Result<sp_blockchain::BlockStatus> {
+			unimplemented!()
+		}
+
+		fn number(&self, _: H256) -> sp_blockchain::Result<Option<u64>> {
+			unimplemented!()
+		}
+
+		fn hash(&self, _: u64) -> sp_blockchain::Result<Option<H256>> {
+			unimplemented!()
+		}
+	}
+
+	impl ProvideCache<Block> for TestClient {
+		fn cache(&self) -> Option<Arc<dyn sp_blockchain::Cache<Block>>> {
+			Some(Arc::new(TestCache(self.changes_trie_config.clone())))
+		}
+	}
+
+	impl RemoteBlockchain<Block> for TestClient {
+		fn header(
+			&self,
+			id: BlockId<Block>,
+		) -> sp_blockchain::Result<LocalOrRemote<Header, RemoteHeaderRequest<Header>>> {
+			Ok(HeaderBackend::header(self, id)?
+				.map(LocalOrRemote::Local)
+				.unwrap_or(LocalOrRemote::Unknown))
+		}
+	}
+
+	/// Cache with the changes tries configuration that is active since genesis.
+	struct TestCache(Option<ChangesTrieConfiguration>);
+
+	impl sp_blockchain::Cache<Block> for TestCache {
+		fn initialize(&self, _: &well_known_cache_keys::Id, _: Vec<u8>) -> sp_blockchain::Result<()> {
+			unimplemented!()
+		}
+
+		fn get_at(
+			&self,
+			key: &well_known_cache_keys::Id,
+			_: &BlockId<Block>,
+		) -> sp_blockchain::Result<Option<((u64, H256), Option<(u64, H256)>, Vec<u8>)>> {
+			assert_eq!(*key, well_known_cache_keys::CHANGES_TRIE_CONFIG);
+			Ok(Some(((0, Default::default()), None, self.0.encode())))
+		}
+	}
+
+	/// Fetcher that serves storage values and changes, remembering all read requests.
+	#[derive(Default)]
+	struct TestFetcher {
+		/// Storage values at every block.
+		storage: HashMap<H256, HashMap<Vec<u8>, Option<Vec<u8>>>>,
+		/// Numbers of blocks where the keys have been changed.
+		changes: HashMap<Vec<u8>, Vec<u64>>,
+		/// Blocks and (sorted) keys of all read requests.
+		reads: Mutex<Vec<(H256, Vec<Vec<u8>>)>>,
+	}
+
+	impl Fetcher<Block> for TestFetcher {
+		type RemoteHeaderResult = futures::future::Ready<Result<Header, ClientError>>;
+		type RemoteReadResult = futures::future::Ready<Result<
+			HashMap<Vec<u8>, Option<Vec<u8>>>,
+			ClientError,
+		>>;
+		type RemoteCallResult = futures::future::Ready<Result<Vec<u8>, ClientError>>;
+		type RemoteChangesResult = futures::future::Ready<Result<Vec<(u64, u32)>, ClientError>>;
+		type RemoteBodyResult = futures::future::Ready<Result<Vec<Extrinsic>, ClientError>>;
+
+		fn remote_header(&self, _: RemoteHeaderRequest<Header>) -> Self::RemoteHeaderResult {
+			unimplemented!()
+		}
+
+		fn remote_read(&self, request: RemoteReadRequest<Header>) -> Self::RemoteReadResult {
+			let mut keys = request.keys.clone();
+			keys.sort();
+			self.reads.lock().push((request.block, keys));
+
+			let storage = &self.storage[&request.block];
+			ready(Ok(request.keys
+				.into_iter()
+				.map(|key| {
+					let value = storage.get(&key).cloned().flatten();
+					(key, value)
+				})
+				.collect()))
+		}
+
+		fn remote_read_child(&self, _: RemoteReadChildRequest<Header>) -> Self::RemoteReadResult {
+			unimplemented!()
+		}
+
+		fn remote_call(&self, _: RemoteCallRequest<Header>) -> Self::RemoteCallResult {
+			unimplemented!()
+		}
+
+		fn remote_changes(&self, request: RemoteChangesRequest<Header>) -> Self::RemoteChangesResult {
+			ready(Ok(self.changes
+				.get(&request.key)
+				.cloned()
+				.unwrap_or_default()
+				.into_iter()
+				.filter(|block| *block >= request.first_block.0 && *block <= request.last_block.0)
+				.map(|block| (block, 0))
+				.collect()))
+		}
+
+		fn remote_body(&self, _: RemoteBodyRequest<Header>) -> Self::RemoteBodyResult {
+			unimplemented!()
+		}
+	}
+
+	fn header(number: u64, parent_hash: H256, state_root: u8) -> Header {
+		Header::new(
+			number,
+			Default::default(),
+			H256::from([state_root; 32]),
+			parent_hash,
+			Digest { logs: vec![DigestItem::ChangesTrieRoot(H256::from([number as u8; 32]))] },
+		)
+	}
+
+	/// Prepares the light client that knows blocks #1..#4 (#1 and #2 share the state root) and
+	/// storage subscriptions to keys [1] and [2] that have been read at block #1.
+	fn prepare_storage_subscriptions(
+		changes_trie_config: Option<ChangesTrieConfiguration>,
+	) -> (Arc<TestClient>, Vec<H256>, Arc<Mutex<StorageSubscriptions<Block>>>) {
+		let mut headers = vec![header(1, Default::default(), 1)];
+		headers.push(header(2, headers[0].hash(), 1));
+		headers.push(header(3, headers[1].hash(), 3));
+		headers.push(header(4, headers[2].hash(), 4));
+		let hashes = headers.iter().map(|header| header.hash()).collect::<Vec<_>>();
+		let client = Arc::new(TestClient {
+			headers: headers.into_iter().map(|header| (header.hash(), header)).collect(),
+			changes_trie_config,
+		});
+
+		let subscription = SubscriptionId::Number(1);
+		let storage_subscriptions = Arc::new(Mutex::new(StorageSubscriptions {
+			active_requests: HashMap::new(),
+			keys_by_subscription: vec![
+				(subscription.clone(), vec![StorageKey(vec![1]), StorageKey(vec![2])].into_iter().collect()),
+			].into_iter().collect(),
+			subscriptions_by_key: vec![
+				(StorageKey(vec![1]), vec![subscription.clone()].into_iter().collect()),
+				(StorageKey(vec![2]), vec![subscription].into_iter().collect()),
+			].into_iter().collect(),
+			last_read: Some(LastRead {
+				hash: hashes[0],
+				number: 1,
+				state_root: H256::from([1; 32]),
+				values: vec![
+					(StorageKey(vec![1]), Some(StorageData(vec![10]))),
+					(StorageKey(vec![2]), Some(StorageData(vec![20]))),
+				].into_iter().collect(),
+			}),
+		}));
+
+		(client, hashes, storage_subscriptions)
+	}
+
 	#[test]
 	fn subscription_stream_works() {
 		let stream = subscription_stream::<Block, _, _, _, _, _, _, _, _>(
@@ -793,6 +1196,161 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn storage_subscriptions_reuse_values_read_at_same_state_root() {
+		let mut subscriptions = StorageSubscriptions::<Block> {
+			active_requests: HashMap::new(),
+			keys_by_subscription: HashMap::new(),
+			subscriptions_by_key: HashMap::new(),
+			last_read: None,
+		};
+		let state_root = H256::from([1; 32]);
+		assert_eq!(subscriptions.cached_values(&state_root, &[vec![1]]), None);
+
+		subscriptions.last_read = Some(LastRead {
+			hash: H256::from([3; 32]),
+			number: 1,
+			state_root,
+			values: vec![
+				(StorageKey(vec![1]), Some(StorageData(vec![10]))),
+				(StorageKey(vec![2]), None),
+			].into_iter().collect(),
+		});
+
+		assert_eq!(
+			subscriptions.cached_values(&state_root, &[vec![1], vec![2]]),
+			Some(vec![
+				(StorageKey(vec![1]), Some(StorageData(vec![10]))),
+				(StorageKey(vec![2]), None),
+			].into_iter().collect()),
+		);
+		// keys that haven't been read and other states require a new request
+		assert_eq!(subscriptions.cached_values(&state_root, &[vec![1], vec![3]]), None);
+		assert_eq!(subscriptions.cached_values(&H256::from([2; 32]), &[vec![1]]), None);
+	}
+
+	#[test]
+	fn storage_subscription_only_reads_changed_keys_at_finalized_blocks() {
+		let (client, hashes, storage_subscriptions) = prepare_storage_subscriptions(
+			Some(ChangesTrieConfiguration::new(4, 2)),
+		);
+		let fetcher = Arc::new(TestFetcher {
+			storage: vec![
+				(hashes[2], vec![(vec![1], Some(vec![11])), (vec![2], Some(vec![20]))].into_iter().collect()),
+			].into_iter().collect(),
+			changes: vec![(vec![1], vec![3])].into_iter().collect(),
+			..Default::default()
+		});
+		let remote_blockchain: Arc<dyn RemoteBlockchain<Block>> = client.clone();
+		let initial_values = storage_subscriptions.lock().last_read.as_ref().unwrap().values.clone();
+		let keys_to_check = vec![vec![1], vec![2]].into_iter().collect::<HashSet<_>>();
+
+		let issue_request_subscriptions = storage_subscriptions.clone();
+		let issue_request_fetcher = fetcher.clone();
+		let stream = subscription_stream::<Block, _, _, _, _, _, _, _, _>(
+			storage_subscriptions.clone(),
+			futures_ordered(hashes[1..].iter().map(|hash| result(Ok(*hash))).collect::<Vec<_>>()),
+			ready(Ok((hashes[0], initial_values))),
+			move |block| subscribed_storage(
+				&*client,
+				remote_blockchain.clone(),
+				issue_request_fetcher.clone(),
+				issue_request_subscriptions.clone(),
+				block,
+			),
+			move |block, old_value, new_value| storage_change_set::<Block>(
+				&keys_to_check,
+				block,
+				old_value,
+				new_value,
+			),
+		);
+
+		// the state at #2 is the same as at #1 and nothing has changed at #4, so there are no
+		// notifications for these blocks
+		let notifications = stream.collect().wait().unwrap()
+			.into_iter()
+			.map(|mut change_set| {
+				change_set.changes.sort();
+				change_set
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(
+			notifications,
+			vec![
+				StorageChangeSet {
+					block: hashes[0],
+					changes: vec![
+						(StorageKey(vec![1]), Some(StorageData(vec![10]))),
+						(StorageKey(vec![2]), Some(StorageData(vec![20]))),
+					],
+				},
+				StorageChangeSet {
+					block: hashes[2],
+					changes: vec![
+						(StorageKey(vec![1]), Some(StorageData(vec![11]))),
+						(StorageKey(vec![2]), Some(StorageData(vec![20]))),
+					],
+				},
+			],
+		);
+		// only the changed key has been read
+		assert_eq!(*fetcher.reads.lock(), vec![(hashes[2], vec![vec![1]])]);
+		assert_eq!(storage_subscriptions.lock().last_read.as_ref().map(|last_read| last_read.hash), Some(hashes[3]));
+	}
+
+	#[test]
+	fn subscribed_storage_reads_all_keys_without_changes_tries() {
+		let (client, hashes, storage_subscriptions) = prepare_storage_subscriptions(None);
+		let fetcher = Arc::new(TestFetcher {
+			storage: vec![
+				(hashes[2], vec![(vec![1], Some(vec![11])), (vec![2], Some(vec![20]))].into_iter().collect()),
+			].into_iter().collect(),
+			..Default::default()
+		});
+
+		let values = futures::executor::block_on(subscribed_storage(
+			&*client,
+			client.clone(),
+			fetcher.clone(),
+			storage_subscriptions,
+			hashes[2],
+		)).unwrap();
+		assert_eq!(
+			values,
+			vec![
+				(StorageKey(vec![1]), Some(StorageData(vec![11]))),
+				(StorageKey(vec![2]), Some(StorageData(vec![20]))),
+			].into_iter().collect(),
+		);
+		assert_eq!(*fetcher.reads.lock(), vec![(hashes[2], vec![vec![1], vec![2]])]);
+	}
+
+	#[test]
+	fn storage_change_set_ignores_keys_of_other_subscriptions() {
+		let keys_to_check = vec![vec![1]].into_iter().collect::<HashSet<_>>();
+		let old_value: StorageMap = vec![
+			(StorageKey(vec![1]), Some(StorageData(vec![10]))),
+			(StorageKey(vec![2]), Some(StorageData(vec![20]))),
+		].into_iter().collect();
+		let mut new_value = old_value.clone();
+		new_value.insert(StorageKey(vec![2]), Some(StorageData(vec![21])));
+
+		assert_eq!(
+			storage_change_set::<Block>(&keys_to_check, Default::default(), Some(&old_value), &new_value),
+			None,
+		);
+
+		new_value.insert(StorageKey(vec![1]), None);
+		assert_eq!(
+			storage_change_set::<Block>(&keys_to_check, Default::default(), Some(&old_value), &new_value),
+			Some(StorageChangeSet {
+				block: Default::default(),
+				changes: vec![(StorageKey(vec![1]), None)],
+			}),
+		);
+	}
+
 	#[test]
 	fn maybe_share_remote_request_shares_request() {
 		type UnreachableFuture = futures::future::Ready<Result<u32, Error>>;
@@ -89,6 +89,42 @@ fn should_return_storage() {
 	);
 }
 
+#[test]
+fn should_return_archived_storage_at_pruned_blocks() {
+	const KEY: &[u8] = b"archived:key";
+	const OTHER_KEY: &[u8] = b"other:key";
+
+	let backend = Arc::new(substrate_test_runtime_client::Backend::new_test_with_archive_prefixes(
+		1,
+		vec![b"archived".to_vec()],
+	));
+	let mut client = Arc::new(TestClientBuilder::with_backend(backend).build());
+	let mut hashes = Vec::new();
+	for value in 0..4u8 {
+		let mut builder = client.new_block(Default::default()).unwrap();
+		// Values are big enough for leaves not to be inlined in their parent node.
+		builder.push_storage_change(KEY.to_vec(), Some(vec![value; 32])).unwrap();
+		builder.push_storage_change(OTHER_KEY.to_vec(), Some(vec![value; 32])).unwrap();
+		let block = builder.build().unwrap().block;
+		hashes.push(block.header.hash());
+		client.import_as_final(BlockOrigin::Own, block).unwrap();
+	}
+	let (api, _child) = new_full(client, SubscriptionManager::new(Arc::new(TaskExecutor)));
+
+	assert_eq!(
+		api.storage(StorageKey(KEY.to_vec()), Some(hashes[0]).into()).wait().unwrap(),
+		Some(StorageData(vec![0; 32])),
+	);
+	assert_matches!(
+		api.storage(StorageKey(OTHER_KEY.to_vec()), Some(hashes[0]).into()).wait(),
+		Err(Error::Client(ref e)) if e.to_string().contains("State already discarded")
+	);
+	assert_eq!(
+		api.storage(StorageKey(OTHER_KEY.to_vec()), Some(hashes[3]).into()).wait().unwrap(),
+		Some(StorageData(vec![3; 32])),
+	);
+}
+
 #[test]
 fn should_return_child_storage() {
 	let child_info = ChildInfo::new_default(STORAGE_KEY);
@@ -23,14 +23,18 @@ mod tests;
 
 use futures::{future::BoxFuture, FutureExt, TryFutureExt};
 use futures::{channel::oneshot, compat::Compat};
+use sc_network::MAX_BAN_DURATION;
 use sc_rpc_api::{DenyUnsafe, Receiver};
 use sp_utils::mpsc::TracingUnboundedSender;
 use sp_runtime::traits::{self, Header as HeaderT};
+use std::time::Duration;
 
 use self::error::Result;
 
 pub use sc_rpc_api::system::*;
-pub use self::helpers::{SystemInfo, Health, PeerInfo, NodeRole};
+pub use self::helpers::{
+	SystemInfo, Health, PeerInfo, NodeRole, PeerReputation, PeerDirection, PeerSlotType,
+};
 pub use self::gen_client::Client as SystemClient;
 
 macro_rules! bail_if_unsafe {
@@ -41,6 +45,17 @@ macro_rules! bail_if_unsafe {
 	};
 }
 
+macro_rules! bail_if_ban_too_long {
+	($duration_secs: expr) => {
+		// Longer bans would be silently shortened by the network.
+		let max_secs = MAX_BAN_DURATION.as_secs();
+		if $duration_secs > max_secs {
+			let err = error::Error::BanDurationTooLong($duration_secs, max_secs);
+			return async move { Err(err.into()) }.boxed().compat();
+		}
+	};
+}
+
 /// System API implementation
 pub struct System<B: traits::Block> {
 	info: SystemInfo,
@@ -65,6 +80,16 @@ pub enum Request<B: traits::Block> {
 	NetworkAddReservedPeer(String, oneshot::Sender<Result<()>>),
 	/// Must return any potential parse error.
 	NetworkRemoveReservedPeer(String, oneshot::Sender<Result<()>>),
+	/// Must return the reputation and connection state of the peers known to the peer set.
+	PeersReputation(oneshot::Sender<Vec<PeerReputation>>),
+	/// Must return any potential parse error.
+	NetworkBanPeer(String, Duration, oneshot::Sender<Result<()>>),
+	/// Must return any potential parse error.
+	NetworkUnbanPeer(String, oneshot::Sender<Result<()>>),
+	/// Must return any potential parse error.
+	NetworkBanIpRange(String, Duration, oneshot::Sender<Result<()>>),
+	/// Must return any potential parse error.
+	NetworkUnbanIpRange(String, oneshot::Sender<Result<()>>),
 	/// Must return the node role.
 	NodeRoles(oneshot::Sender<Vec<NodeRole>>)
 }
@@ -184,6 +209,93 @@ impl<B: traits::Block> SystemApi<B::Hash, <B::Header as HeaderT>::Number> for Sy
 		}.boxed().compat()
 	}
 
+	fn system_peers_reputation(&self)
+		-> Compat<BoxFuture<'static, rpc::Result<Vec<PeerReputation>>>>
+	{
+		bail_if_unsafe!(self.deny_unsafe);
+
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::PeersReputation(tx));
+
+		async move {
+			rx.await.map_err(|_| rpc::Error::internal_error())
+		}.boxed().compat()
+	}
+
+	fn system_ban_peer(&self, peer: String, duration_secs: u64)
+		-> Compat<BoxFuture<'static, std::result::Result<(), rpc::Error>>>
+	{
+		bail_if_unsafe!(self.deny_unsafe);
+		bail_if_ban_too_long!(duration_secs);
+
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::NetworkBanPeer(
+			peer,
+			Duration::from_secs(duration_secs),
+			tx,
+		));
+		async move {
+			match rx.await {
+				Ok(Ok(())) => Ok(()),
+				Ok(Err(e)) => Err(rpc::Error::from(e)),
+				Err(_) => Err(rpc::Error::internal_error()),
+			}
+		}.boxed().compat()
+	}
+
+	fn system_unban_peer(&self, peer: String)
+		-> Compat<BoxFuture<'static, std::result::Result<(), rpc::Error>>>
+	{
+		bail_if_unsafe!(self.deny_unsafe);
+
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::NetworkUnbanPeer(peer, tx));
+		async move {
+			match rx.await {
+				Ok(Ok(())) => Ok(()),
+				Ok(Err(e)) => Err(rpc::Error::from(e)),
+				Err(_) => Err(rpc::Error::internal_error()),
+			}
+		}.boxed().compat()
+	}
+
+	fn system_ban_ip_range(&self, range: String, duration_secs: u64)
+		-> Compat<BoxFuture<'static, std::result::Result<(), rpc::Error>>>
+	{
+		bail_if_unsafe!(self.deny_unsafe);
+		bail_if_ban_too_long!(duration_secs);
+
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::NetworkBanIpRange(
+			range,
+			Duration::from_secs(duration_secs),
+			tx,
+		));
+		async move {
+			match rx.await {
+				Ok(Ok(())) => Ok(()),
+				Ok(Err(e)) => Err(rpc::Error::from(e)),
+				Err(_) => Err(rpc::Error::internal_error()),
+			}
+		}.boxed().compat()
+	}
+
+	fn system_unban_ip_range(&self, range: String)
+		-> Compat<BoxFuture<'static, std::result::Result<(), rpc::Error>>>
+	{
+		bail_if_unsafe!(self.deny_unsafe);
+
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::NetworkUnbanIpRange(range, tx));
+		async move {
+			match rx.await {
+				Ok(Ok(())) => Ok(()),
+				Ok(Err(e)) => Err(rpc::Error::from(e)),
+				Err(_) => Err(rpc::Error::internal_error()),
+			}
+		}.boxed().compat()
+	}
+
 	fn system_node_roles(&self) -> Receiver<Vec<NodeRole>> {
 		let (tx, rx) = oneshot::channel();
 		let _ = self.send_back.unbounded_send(Request::NodeRoles(tx));
@@ -89,6 +89,8 @@ fn api<T: Into<Option<Status>>>(sync: T) -> System<Block> {
 						not_connected_peers: Default::default(),
 						total_bytes_inbound: 0,
 						total_bytes_outbound: 0,
+						notifications_bytes: Default::default(),
+						request_responses_bytes: Default::default(),
 						peerset: serde_json::Value::Null,
 					}).unwrap());
 				},
@@ -104,6 +106,26 @@ fn api<T: Into<Option<Status>>>(sync: T) -> System<Block> {
 						Err(s) => sender.send(Err(error::Error::MalformattedPeerArg(s.to_string()))),
 					};
 				}
+				Request::PeersReputation(sender) => {
+					let _ = sender.send(vec![PeerReputation {
+						peer_id: status.peer_id.to_base58(),
+						reputation: -100,
+						direction: Some(PeerDirection::Inbound),
+						slot_type: PeerSlotType::Regular,
+					}]);
+				}
+				Request::NetworkBanPeer(peer, _, sender) | Request::NetworkUnbanPeer(peer, sender) => {
+					let _ = match peer.parse::<PeerId>() {
+						Ok(_) => sender.send(Ok(())),
+						Err(s) => sender.send(Err(error::Error::MalformattedPeerArg(s.to_string()))),
+					};
+				}
+				Request::NetworkBanIpRange(range, _, sender) | Request::NetworkUnbanIpRange(range, sender) => {
+					let _ = match range.parse::<std::net::IpAddr>() {
+						Err(_) if range.contains('/') => sender.send(Ok(())),
+						_ => sender.send(Err(error::Error::MalformattedIpRangeArg(range))),
+					};
+				}
 				Request::NodeRoles(sender) => {
 					let _ = sender.send(vec![NodeRole::Authority]);
 				}
@@ -284,6 +306,8 @@ fn system_network_state() {
 			not_connected_peers: Default::default(),
 			total_bytes_inbound: 0,
 			total_bytes_outbound: 0,
+			notifications_bytes: Default::default(),
+			request_responses_bytes: Default::default(),
 			peerset: serde_json::Value::Null,
 		}
 	);
@@ -320,3 +344,56 @@ fn system_network_remove_reserved() {
 	assert_eq!(runtime.block_on(good_fut), Ok(()));
 	assert!(runtime.block_on(bad_fut).is_err());
 }
+
+#[test]
+fn system_peers_reputation() {
+	let peer_id = PeerId::random();
+	let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+
+	let peers = runtime.block_on(api(Status {
+		peer_id: peer_id.clone(),
+		peers: 1,
+		is_syncing: false,
+		is_dev: true,
+	}).system_peers_reputation()).unwrap();
+	assert_eq!(peers, vec![PeerReputation {
+		peer_id: peer_id.to_base58(),
+		reputation: -100,
+		direction: Some(PeerDirection::Inbound),
+		slot_type: PeerSlotType::Regular,
+	}]);
+}
+
+#[test]
+fn system_network_ban_peer() {
+	let good_peer_id = "QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV";
+	let bad_peer_id = "/ip4/198.51.100.19/tcp/30333/p2p/QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV";
+	let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+
+	let good_fut = api(None).system_ban_peer(good_peer_id.into(), 60);
+	let bad_fut = api(None).system_ban_peer(bad_peer_id.into(), 60);
+	assert_eq!(runtime.block_on(good_fut), Ok(()));
+	assert!(runtime.block_on(bad_fut).is_err());
+
+	let too_long_fut = api(None).system_ban_peer(good_peer_id.into(), u64::max_value());
+	assert!(runtime.block_on(too_long_fut).is_err());
+
+	let good_fut = api(None).system_unban_peer(good_peer_id.into());
+	assert_eq!(runtime.block_on(good_fut), Ok(()));
+}
+
+#[test]
+fn system_network_ban_ip_range() {
+	let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+
+	let good_fut = api(None).system_ban_ip_range("192.0.2.0/24".into(), 60);
+	let bad_fut = api(None).system_ban_ip_range("192.0.2.1".into(), 60);
+	assert_eq!(runtime.block_on(good_fut), Ok(()));
+	assert!(runtime.block_on(bad_fut).is_err());
+
+	let too_long_fut = api(None).system_ban_ip_range("192.0.2.0/24".into(), u64::max_value());
+	assert!(runtime.block_on(too_long_fut).is_err());
+
+	let good_fut = api(None).system_unban_ip_range("192.0.2.0/24".into());
+	assert_eq!(runtime.block_on(good_fut), Ok(()));
+}
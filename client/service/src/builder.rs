@@ -45,7 +45,7 @@ use sp_runtime::traits::{
 	Block as BlockT, SaturatedConversion, HashFor, Zero, BlockIdTo,
 };
 use sp_api::{ProvideRuntimeApi, CallApiAt};
-use sc_executor::{NativeExecutor, NativeExecutionDispatch, RuntimeInfo};
+use sc_executor::{ExecutionLimits, NativeExecutor, NativeExecutionDispatch, RuntimeInfo};
 use std::{collections::HashMap, sync::Arc};
 use wasm_timer::SystemTime;
 use sc_telemetry::{telemetry, SUBSTRATE_INFO};
@@ -60,7 +60,11 @@ use sc_client_api::{
 	proof_provider::ProofProvider,
 	execution_extensions::ExecutionExtensions
 };
-use sp_blockchain::{HeaderMetadata, HeaderBackend};
+use sp_blockchain::{HeaderMetadata, HeaderBackend, ProvideCache};
+
+/// Number of blocks the state of non-finalized blocks is kept around before being
+/// canonicalized in the database.
+pub(crate) const CANONICALIZATION_DELAY: u64 = 4096;
 
 /// A utility trait for building an RPC extension given a `DenyUnsafe` instance.
 /// This is useful since at service definition time we don't know whether the
@@ -216,11 +220,23 @@ pub fn new_full_parts<TBl, TRtApi, TExecDisp>(
 		TaskManager::new(config.task_executor.clone(), registry)?
 	};
 
-	let executor = NativeExecutor::<TExecDisp>::new(
+	let executor = NativeExecutor::<TExecDisp>::new_with_wasm_cache_path(
 		config.wasm_method,
 		config.default_heap_pages,
 		config.max_runtime_instances,
+		config.wasm_runtime_cache_path.clone(),
+	).with_execution_limits(
+		get_extension::<ExecutionLimits>(config.chain_spec.extensions())
+			.cloned()
+			.unwrap_or_default()
 	);
+	let executor = if config.instrument_host_functions {
+		executor.with_host_function_instrumentation(
+			config.prometheus_config.as_ref().map(|config| &config.registry),
+		)?
+	} else {
+		executor
+	};
 
 	let chain_spec = &config.chain_spec;
 	let fork_blocks = get_extension::<ForkBlocks<TBl>>(chain_spec.extensions())
@@ -237,6 +253,8 @@ pub fn new_full_parts<TBl, TRtApi, TExecDisp>(
 			state_cache_child_ratio:
 			config.state_cache_child_ratio.map(|v| (v, 100)),
 			pruning: config.pruning.clone(),
+			blocks_pruning: config.blocks_pruning,
+			offchain_index_pruning: config.offchain_index_pruning,
 			source: config.database.clone(),
 		};
 
@@ -257,6 +275,7 @@ pub fn new_full_parts<TBl, TRtApi, TExecDisp>(
 			ClientConfig {
 				offchain_worker_enabled : config.offchain_worker.enabled ,
 				offchain_indexing_api: config.offchain_worker.indexing_enabled,
+				native_wasm_check: config.native_wasm_check.clone(),
 			},
 		)?
 	};
@@ -285,11 +304,23 @@ pub fn new_light_parts<TBl, TRtApi, TExecDisp>(
 		KeystoreConfig::InMemory => Keystore::new_in_memory(),
 	};
 
-	let executor = NativeExecutor::<TExecDisp>::new(
+	let executor = NativeExecutor::<TExecDisp>::new_with_wasm_cache_path(
 		config.wasm_method,
 		config.default_heap_pages,
 		config.max_runtime_instances,
+		config.wasm_runtime_cache_path.clone(),
+	).with_execution_limits(
+		get_extension::<ExecutionLimits>(config.chain_spec.extensions())
+			.cloned()
+			.unwrap_or_default()
 	);
+	let executor = if config.instrument_host_functions {
+		executor.with_host_function_instrumentation(
+			config.prometheus_config.as_ref().map(|config| &config.registry),
+		)?
+	} else {
+		executor
+	};
 
 	let db_storage = {
 		let db_settings = sc_client_db::DatabaseSettings {
@@ -297,6 +328,8 @@ pub fn new_light_parts<TBl, TRtApi, TExecDisp>(
 			state_cache_child_ratio:
 				config.state_cache_child_ratio.map(|v| (v, 100)),
 			pruning: config.pruning.clone(),
+			blocks_pruning: config.blocks_pruning,
+			offchain_index_pruning: config.offchain_index_pruning,
 			source: config.database.clone(),
 		};
 		sc_client_db::light::LightStorage::new(db_settings)?
@@ -348,8 +381,6 @@ pub fn new_client<E, Block, RA>(
 		Block: BlockT,
 		E: CodeExecutor + RuntimeInfo,
 {
-	const CANONICALIZATION_DELAY: u64 = 4096;
-
 	let backend = Arc::new(Backend::new(settings, CANONICALIZATION_DELAY)?);
 	let executor = crate::client::LocalCallExecutor::new(backend.clone(), executor, spawn_handle, config.clone());
 	Ok((
@@ -399,13 +430,15 @@ pub struct SpawnTasksParams<'a, TBl: BlockT, TCl, TExPool, TRpc, Backend> {
 }
 
 /// Build a shared offchain workers instance.
+///
+/// Fails if the HTTP client configuration is invalid.
 pub fn build_offchain_workers<TBl, TBackend, TCl>(
 	config: &Configuration,
 	backend: Arc<TBackend>,
 	spawn_handle: SpawnTaskHandle,
 	client: Arc<TCl>,
 	network: Arc<NetworkService<TBl, <TBl as BlockT>::Hash>>,
-) -> Option<Arc<sc_offchain::OffchainWorkers<TCl, TBackend::OffchainStorage, TBl>>>
+) -> Result<Option<Arc<sc_offchain::OffchainWorkers<TCl, TBackend::OffchainStorage, TBl>>>, Error>
 	where
 		TBl: BlockT, TBackend: sc_client_api::Backend<TBl>,
 		<TBackend as sc_client_api::Backend<TBl>>::OffchainStorage: 'static,
@@ -414,7 +447,12 @@ pub fn build_offchain_workers<TBl, TBackend, TCl>(
 {
 	let offchain_workers = match backend.offchain_storage() {
 		Some(db) => {
-			Some(Arc::new(sc_offchain::OffchainWorkers::new(client.clone(), db)))
+			let offchain_workers = sc_offchain::OffchainWorkers::with_http_config(
+				client.clone(),
+				db,
+				&config.offchain_worker.http,
+			).map_err(|err| Error::Other(format!("Invalid offchain worker HTTP configuration: {}", err)))?;
+			Some(Arc::new(offchain_workers))
 		},
 		None => {
 			warn!("Offchain workers disabled, due to lack of offchain storage support in backend.");
@@ -436,7 +474,7 @@ pub fn build_offchain_workers<TBl, TBackend, TCl>(
 		);
 	}
 
-	offchain_workers
+	Ok(offchain_workers)
 }
 
 /// Spawn the tasks that are required to run a node.
@@ -448,7 +486,7 @@ pub fn spawn_tasks<TBl, TBackend, TExPool, TRpc, TCl>(
 		BlockBackend<TBl> + BlockIdTo<TBl, Error=sp_blockchain::Error> + ProofProvider<TBl> +
 		HeaderBackend<TBl> + BlockchainEvents<TBl> + ExecutorProvider<TBl> + UsageProvider<TBl> +
 		StorageProvider<TBl, TBackend> + CallApiAt<TBl, Error=sp_blockchain::Error> +
-		Send + 'static,
+		ProvideCache<TBl> + Send + 'static,
 		<TCl as ProvideRuntimeApi<TBl>>::Api:
 			sp_api::Metadata<TBl> +
 			sc_offchain::OffchainWorkerApi<TBl> +
@@ -716,7 +754,7 @@ fn gen_handler<TBl, TBackend, TExPool, TRpc, TCl>(
 		TCl: ProvideRuntimeApi<TBl> + BlockchainEvents<TBl> + HeaderBackend<TBl> +
 		HeaderMetadata<TBl, Error=sp_blockchain::Error> + ExecutorProvider<TBl> +
 		CallApiAt<TBl, Error=sp_blockchain::Error> + ProofProvider<TBl> +
-		StorageProvider<TBl, TBackend> + BlockBackend<TBl> + Send + Sync + 'static,
+		StorageProvider<TBl, TBackend> + BlockBackend<TBl> + ProvideCache<TBl> + Send + Sync + 'static,
 		TExPool: MaintainedTransactionPool<Block=TBl, Hash = <TBl as BlockT>::Hash> + 'static,
 		TBackend: sc_client_api::backend::Backend<TBl> + 'static,
 		TRpc: sc_rpc::RpcExtension<sc_rpc::Metadata>,
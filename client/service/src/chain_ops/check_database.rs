@@ -0,0 +1,59 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::Error;
+use crate::config::Configuration;
+use crate::builder::CANONICALIZATION_DELAY;
+use log::{info, warn};
+use sp_runtime::traits::Block as BlockT;
+use sc_client_db::{Backend, DatabaseSettings, IntegrityReport};
+
+/// Opens the database of the node without starting a client and checks its integrity.
+///
+/// If `repair` is set, the leaves set is fixed and the blocks above the last consistent block are
+/// reverted. Otherwise the database is left untouched: in particular, the bodies of finalized
+/// blocks aren't pruned when opening it.
+pub fn check_database<B: BlockT>(
+	config: &Configuration,
+	repair: bool,
+) -> Result<IntegrityReport<B>, Error> {
+	let settings = DatabaseSettings {
+		state_cache_size: config.state_cache_size,
+		state_cache_child_ratio: config.state_cache_child_ratio.map(|v| (v, 100)),
+		pruning: config.pruning.clone(),
+		blocks_pruning: config.blocks_pruning,
+		offchain_index_pruning: config.offchain_index_pruning,
+		source: config.database.clone(),
+	};
+	let backend = Backend::<B>::new_unpruned(settings, CANONICALIZATION_DELAY)?;
+	let report = backend.check_integrity(repair)?;
+
+	for problem in &report.problems {
+		warn!("{}", problem);
+	}
+	info!(
+		"Checked {} blocks and {} state roots, found {} problems.",
+		report.checked_blocks,
+		report.checked_states,
+		report.problems.len(),
+	);
+	if let Some((number, hash)) = &report.new_best {
+		info!("Best block moved back to #{} ({})", number, hash);
+	}
+	Ok(report)
+}
@@ -0,0 +1,61 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::Error;
+use crate::config::{Configuration, DatabaseConfig};
+use log::info;
+use sp_runtime::traits::Block as BlockT;
+use sc_client_db::{DatabaseSettings, MigrationSummary};
+
+/// Copies the database of the node to a new database using the `target` backend.
+///
+/// The node must not be running. The original database is left untouched.
+pub fn migrate_database<B: BlockT>(
+	config: &Configuration,
+	target: DatabaseConfig,
+) -> Result<MigrationSummary<B>, Error> {
+	let settings = |source: DatabaseConfig| DatabaseSettings {
+		state_cache_size: config.state_cache_size,
+		state_cache_child_ratio: config.state_cache_child_ratio.map(|v| (v, 100)),
+		pruning: config.pruning.clone(),
+		blocks_pruning: config.blocks_pruning,
+		offchain_index_pruning: config.offchain_index_pruning,
+		source,
+	};
+	let summary = sc_client_db::migrate_database::<B>(
+		&settings(config.database.clone()),
+		&settings(target),
+		|progress| info!(
+			"Migrating {} column: {} entries, {} MiB",
+			progress.column,
+			progress.entries,
+			progress.bytes / (1024 * 1024),
+		),
+	)?;
+
+	info!(
+		"Migrated {} entries ({} MiB). Best block #{} ({}), finalized #{} ({})",
+		summary.entries,
+		summary.bytes / (1024 * 1024),
+		summary.best.0,
+		summary.best.1,
+		summary.finalized.0,
+		summary.finalized.1,
+	);
+	Ok(summary)
+}
@@ -17,13 +17,17 @@
 //! Chain utilities.
 
 mod check_block;
+mod check_database;
 mod export_blocks;
 mod export_raw_state;
 mod import_blocks;
+mod migrate_database;
 mod revert_chain;
 
 pub use check_block::*;
+pub use check_database::*;
 pub use export_blocks::*;
 pub use export_raw_state::*;
 pub use import_blocks::*;
+pub use migrate_database::*;
 pub use revert_chain::*;
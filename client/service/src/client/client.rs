@@ -21,7 +21,7 @@
 use std::{
 	marker::PhantomData,
 	collections::{HashSet, BTreeMap, HashMap},
-	sync::Arc, panic::UnwindSafe, result,
+	sync::Arc, panic::UnwindSafe, result, cell::RefCell,
 };
 use log::{info, trace, warn};
 use parking_lot::{Mutex, RwLock};
@@ -29,6 +29,7 @@ use codec::{Encode, Decode};
 use hash_db::Prefix;
 use sp_core::{
 	convert_hash,
+	offchain::storage::OffchainOverlayedChanges,
 	storage::{well_known_keys, ChildInfo, PrefixedStorageKey, StorageData, StorageKey},
 	ChangesTrieConfiguration, ExecutionContext, NativeOrEncoded,
 };
@@ -45,6 +46,7 @@ use sp_state_machine::{
 	DBValue, Backend as StateBackend, ChangesTrieAnchorBlockId,
 	prove_read, prove_child_read, ChangesTrieRootsStorage, ChangesTrieStorage,
 	ChangesTrieConfigurationRange, key_changes, key_changes_proof,
+	BackendTrustLevel, DefaultHandler, ExecutionManager, OverlayedChanges,
 };
 use sc_executor::RuntimeVersion;
 use sp_consensus::{
@@ -61,7 +63,7 @@ use sp_blockchain::{
 use sp_trie::StorageProof;
 use sp_api::{
 	CallApiAt, ConstructRuntimeApi, Core as CoreApi, ApiExt, ApiRef, ProvideRuntimeApi,
-	CallApiAtParams,
+	CallApiAtParams, InitializeBlock,
 };
 use sc_block_builder::{BlockBuilderApi, BlockBuilderProvider};
 use sc_client_api::{
@@ -86,6 +88,7 @@ use sp_blockchain::Error;
 use prometheus_endpoint::Registry;
 use super::{
 	genesis, block_rules::{BlockRules, LookupResult as BlockLookupResult},
+	native_wasm_check::{NativeWasmCheck, NativeWasmCheckConfig, Execution},
 };
 use sc_light::{call_executor::prove_execution, fetcher::ChangesProof};
 use rand::Rng;
@@ -112,6 +115,7 @@ pub struct Client<B, E, Block, RA> where Block: BlockT {
 	block_rules: BlockRules<Block>,
 	execution_extensions: ExecutionExtensions<Block>,
 	config: ClientConfig,
+	native_wasm_check: Option<NativeWasmCheck>,
 	_phantom: PhantomData<RA>,
 }
 
@@ -179,6 +183,8 @@ pub struct ClientConfig {
 	pub offchain_worker_enabled: bool,
 	/// If true, allows access from the runtime to write into offchain worker db.
 	pub offchain_indexing_api: bool,
+	/// Compare the native and wasm runtimes on a fraction of the imported blocks.
+	pub native_wasm_check: Option<NativeWasmCheckConfig>,
 }
 
 /// Create a client with the explicitly provided backend.
@@ -310,6 +316,11 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			backend.commit_operation(op)?;
 		}
 
+		let native_wasm_check = config.native_wasm_check.clone()
+			.map(|check| NativeWasmCheck::new(check, prometheus_registry.as_ref()))
+			.transpose()
+			.map_err(|e| Error::Msg(format!("Failed to register metrics: {}", e)))?;
+
 		Ok(Client {
 			backend,
 			executor,
@@ -320,6 +331,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			block_rules: BlockRules::new(fork_blocks, bad_blocks),
 			execution_extensions,
 			config,
+			native_wasm_check,
 			_phantom: Default::default(),
 		})
 	}
@@ -883,6 +895,17 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 				} else {
 					**storage_changes = Some(gen_storage_changes);
 				}
+
+				if let Some(check) = &self.native_wasm_check {
+					if check.is_sampled(import_block.header.hash().as_ref()) {
+						self.check_native_wasm(
+							check,
+							&at,
+							Block::new(import_block.header.clone(), body.clone()),
+							import_block.origin,
+						);
+					}
+				}
 			},
 			// No block body, no storage changes
 			(true, None, None) => {},
@@ -895,6 +918,67 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		Ok(None)
 	}
 
+	/// Execute `block` with both the native and the wasm runtime, and compare the outcomes.
+	///
+	/// Nothing is checked if the native runtime can't be used for this block. The check never
+	/// fails the import.
+	fn check_native_wasm(
+		&self,
+		check: &NativeWasmCheck,
+		at: &BlockId<Block>,
+		block: Block,
+		origin: BlockOrigin,
+	) {
+		let native_version = match self.executor.native_runtime_version() {
+			Some(version) => &version.runtime_version,
+			None => return,
+		};
+		match self.runtime_version_at(at) {
+			Ok(version) if version.can_call_with(native_version) => {},
+			Ok(_) => return,
+			Err(e) => {
+				warn!(
+					target: "native-wasm-check",
+					"Skipping the check of block #{}: failed to get the runtime version: {:?}",
+					block.header().number(), e,
+				);
+				return;
+			},
+		}
+
+		let encoded_block = block.encode();
+		let execute = |manager: ExecutionManager<DefaultHandler<(), E::Error>>| {
+			let changes = RefCell::new(OverlayedChanges::default());
+			let offchain_changes = RefCell::new(OffchainOverlayedChanges::default());
+			let context = if origin == BlockOrigin::NetworkInitialSync {
+				ExecutionContext::Syncing
+			} else {
+				ExecutionContext::Importing
+			};
+			let (_, extensions) = self.execution_extensions
+				.manager_and_extensions::<Error, ()>(at, context);
+			let result = self.executor.contextual_call::<_, fn(_,_) -> _, (), _>(
+				|| Ok(()),
+				at,
+				"Core_execute_block",
+				&encoded_block,
+				&changes,
+				&offchain_changes,
+				None,
+				InitializeBlock::Skip,
+				manager,
+				None::<fn() -> result::Result<(), String>>,
+				&None,
+				Some(extensions),
+			);
+			Execution::new(result.map(drop).map_err(|e| e.to_string()), &changes.borrow())
+		};
+
+		let native = execute(ExecutionManager::NativeWhenPossible);
+		let wasm = execute(ExecutionManager::AlwaysWasm(BackendTrustLevel::Trusted));
+		check.compare(block.header().number(), block.header().hash(), native, wasm);
+	}
+
 	fn apply_finality_with_block_hash(
 		&self,
 		operation: &mut ClientImportOperation<Block, B>,
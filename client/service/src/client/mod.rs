@@ -49,10 +49,12 @@ pub mod light;
 mod call_executor;
 mod client;
 mod block_rules;
+mod native_wasm_check;
 
 pub use self::{
 	call_executor::LocalCallExecutor,
 	client::{Client, ClientConfig},
+	native_wasm_check::NativeWasmCheckConfig,
 };
 
 #[cfg(feature="test-helpers")]
@@ -0,0 +1,280 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Sampled comparison of the native and wasm runtimes.
+//!
+//! The native runtime is used instead of the wasm one when their versions match, in which case
+//! they are assumed to behave identically. When the check is enabled, a fraction of the imported
+//! blocks is executed again with both runtimes, and their storage changes are compared. Any
+//! mismatch is logged, and written to disk along with the diff of the storage changes.
+//!
+//! Reports are JSON files named after the block. For each differing key, the value written by
+//! each runtime is given in hex, `null` if deleted, or `"unchanged"`.
+
+use std::{collections::BTreeMap, fmt, fs, io, path::PathBuf};
+use log::{debug, warn};
+use prometheus_endpoint::{register, Counter, PrometheusError, Registry, U64};
+use sp_core::hexdisplay::HexDisplay;
+use sp_state_machine::OverlayedChanges;
+
+/// Configuration of the comparison of the native and wasm runtimes.
+#[derive(Debug, Clone)]
+pub struct NativeWasmCheckConfig {
+	/// Fraction of the imported blocks to execute with both runtimes, between 0 and 1.
+	pub fraction: f64,
+	/// Directory where mismatches are written.
+	pub path: PathBuf,
+}
+
+/// Child trie, if any, and key of a storage value.
+type ChangeKey = (Option<Vec<u8>>, Vec<u8>);
+
+/// Storage changes made by the execution of a block. `None` for deleted values.
+type Changes = BTreeMap<ChangeKey, Option<Vec<u8>>>;
+
+/// Changes that differ between two executions.
+type Diff<'a> = BTreeMap<&'a ChangeKey, (Option<&'a Option<Vec<u8>>>, Option<&'a Option<Vec<u8>>>)>;
+
+/// Outcome of the execution of a block with one of the runtimes.
+pub(crate) struct Execution {
+	result: Result<(), String>,
+	changes: Changes,
+}
+
+impl Execution {
+	pub(crate) fn new(result: Result<(), String>, overlay: &OverlayedChanges) -> Self {
+		let mut changes = Changes::new();
+		for (key, value) in overlay.changes() {
+			changes.insert((None, key.clone()), value.value().cloned());
+		}
+		for (child_changes, child_info) in overlay.children() {
+			for (key, value) in child_changes {
+				let child = Some(child_info.storage_key().to_vec());
+				changes.insert((child, key.clone()), value.value().cloned());
+			}
+		}
+		Execution { result, changes }
+	}
+}
+
+struct Metrics {
+	checks: Counter<U64>,
+	mismatches: Counter<U64>,
+}
+
+impl Metrics {
+	fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			checks: register(
+				Counter::new(
+					"native_wasm_checks_total",
+					"Number of blocks executed with both the native and the wasm runtime.",
+				)?,
+				registry,
+			)?,
+			mismatches: register(
+				Counter::new(
+					"native_wasm_mismatches_total",
+					"Number of blocks for which the native and the wasm runtime disagree.",
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
+
+/// Comparison of the native and wasm runtimes on sampled blocks.
+pub(crate) struct NativeWasmCheck {
+	config: NativeWasmCheckConfig,
+	metrics: Option<Metrics>,
+}
+
+impl NativeWasmCheck {
+	pub(crate) fn new(
+		config: NativeWasmCheckConfig,
+		registry: Option<&Registry>,
+	) -> Result<Self, PrometheusError> {
+		Ok(NativeWasmCheck {
+			config,
+			metrics: registry.map(Metrics::register).transpose()?,
+		})
+	}
+
+	/// Returns `true` if the block with the given hash is to be checked.
+	///
+	/// Blocks are sampled on their hash, so that every node checks the same blocks.
+	pub(crate) fn is_sampled(&self, hash: &[u8]) -> bool {
+		let mut bytes = [0u8; 8];
+		let len = hash.len().min(8);
+		bytes[..len].copy_from_slice(&hash[..len]);
+		(u64::from_le_bytes(bytes) as f64) < self.config.fraction * u64::max_value() as f64
+	}
+
+	/// Compare the executions of a block, and report any mismatch.
+	pub(crate) fn compare(
+		&self,
+		number: impl fmt::Display,
+		hash: impl fmt::Debug,
+		native: Execution,
+		wasm: Execution,
+	) {
+		if let Some(metrics) = &self.metrics {
+			metrics.checks.inc();
+		}
+
+		let same_result = native.result.is_ok() == wasm.result.is_ok();
+		let diff = diff(&native.changes, &wasm.changes);
+		if same_result && diff.is_empty() {
+			debug!(target: "native-wasm-check", "Native and wasm runtimes agree on block #{}", number);
+			return;
+		}
+
+		if let Some(metrics) = &self.metrics {
+			metrics.mismatches.inc();
+		}
+		let report = serde_json::json!({
+			"number": number.to_string(),
+			"hash": format!("{:?}", hash),
+			"native_result": result_json(&native.result),
+			"wasm_result": result_json(&wasm.result),
+			"diff": diff.iter().map(|((child, key), (native, wasm))| serde_json::json!({
+				"child": child.as_ref().map(|child| hex(child)),
+				"key": hex(key),
+				"native": change_json(*native),
+				"wasm": change_json(*wasm),
+			})).collect::<Vec<_>>(),
+		});
+		match self.write_report(&format!("{}-{:?}.json", number, hash), &report) {
+			Ok(path) => warn!(
+				target: "native-wasm-check",
+				"Native and wasm runtimes disagree on block #{} ({:?}): {} storage differences. \
+				Report written to {}",
+				number, hash, diff.len(), path.display(),
+			),
+			Err(e) => warn!(
+				target: "native-wasm-check",
+				"Native and wasm runtimes disagree on block #{} ({:?}): {} storage differences. \
+				Failed to write the report: {}",
+				number, hash, diff.len(), e,
+			),
+		}
+	}
+
+	fn write_report(&self, name: &str, report: &serde_json::Value) -> io::Result<PathBuf> {
+		fs::create_dir_all(&self.config.path)?;
+		let path = self.config.path.join(name);
+		let report = serde_json::to_vec_pretty(report)
+			.map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+		fs::write(&path, report)?;
+		Ok(path)
+	}
+}
+
+/// Changes that differ between `native` and `wasm`. `None` if the key is left unchanged.
+fn diff<'a>(native: &'a Changes, wasm: &'a Changes) -> Diff<'a> {
+	native.keys()
+		.chain(wasm.keys())
+		.filter_map(|key| {
+			let (native, wasm) = (native.get(key), wasm.get(key));
+			if native != wasm {
+				Some((key, (native, wasm)))
+			} else {
+				None
+			}
+		})
+		.collect()
+}
+
+fn change_json(change: Option<&Option<Vec<u8>>>) -> serde_json::Value {
+	match change {
+		None => "unchanged".into(),
+		Some(None) => serde_json::Value::Null,
+		Some(Some(value)) => hex(value).into(),
+	}
+}
+
+fn result_json(result: &Result<(), String>) -> serde_json::Value {
+	match result {
+		Ok(()) => serde_json::Value::Null,
+		Err(e) => serde_json::Value::String(e.clone()),
+	}
+}
+
+fn hex(data: &[u8]) -> String {
+	format!("0x{}", HexDisplay::from(&data))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn execution(result: Result<(), String>, changes: &[(&str, Option<&str>)]) -> Execution {
+		Execution {
+			result,
+			changes: changes.iter()
+				.map(|(key, value)| ((None, key.as_bytes().to_vec()), value.map(|v| v.as_bytes().to_vec())))
+				.collect(),
+		}
+	}
+
+	#[test]
+	fn diff_contains_differing_changes() {
+		let native = execution(Ok(()), &[("a", Some("1")), ("b", Some("2")), ("c", None)]);
+		let wasm = execution(Ok(()), &[("a", Some("1")), ("b", Some("3")), ("d", None)]);
+
+		let diff = diff(&native.changes, &wasm.changes);
+		let keys = diff.keys().map(|(_, key)| key.clone()).collect::<Vec<_>>();
+		assert_eq!(keys, vec![b"b".to_vec(), b"c".to_vec(), b"d".to_vec()]);
+		assert_eq!(diff[&(None, b"c".to_vec())], (Some(&None), None));
+	}
+
+	#[test]
+	fn mismatches_are_written_to_disk() {
+		let dir = tempfile::tempdir().unwrap();
+		let check = NativeWasmCheck::new(
+			NativeWasmCheckConfig { fraction: 1.0, path: dir.path().join("mismatches") },
+			None,
+		).unwrap();
+
+		let native = || execution(Ok(()), &[("a", Some("1"))]);
+		check.compare(1, 1, native(), native());
+		assert!(!dir.path().join("mismatches").exists());
+
+		check.compare(2, 2, native(), execution(Err("trap".into()), &[]));
+		let report: serde_json::Value = serde_json::from_slice(
+			&fs::read(dir.path().join("mismatches").join("2-2.json")).unwrap(),
+		).unwrap();
+		assert_eq!(report["wasm_result"], "trap");
+		assert_eq!(report["diff"][0]["key"], "0x61");
+		assert_eq!(report["diff"][0]["native"], "0x31");
+		assert_eq!(report["diff"][0]["wasm"], "unchanged");
+	}
+
+	#[test]
+	fn blocks_are_sampled_by_hash() {
+		let check = |fraction| NativeWasmCheck::new(
+			NativeWasmCheckConfig { fraction, path: PathBuf::new() },
+			None,
+		).unwrap();
+
+		assert!(!check(0.0).is_sampled(&[0; 32]));
+		assert!(check(0.5).is_sampled(&[0; 32]));
+		assert!(!check(0.5).is_sampled(&[0xff; 32]));
+		assert!(check(1.0).is_sampled(&[0x7f; 32]));
+	}
+}
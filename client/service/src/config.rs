@@ -18,10 +18,14 @@
 
 //! Service configuration.
 
-pub use sc_client_db::{Database, PruningMode, DatabaseSettingsSrc as DatabaseConfig};
+pub use sc_client_db::{
+	Database, PruningMode, BlocksPruning, OffchainIndexPruning, DatabaseSettingsSrc as DatabaseConfig,
+};
 pub use sc_network::Multiaddr;
 pub use sc_network::config::{ExtTransport, MultiaddrWithPeerId, NetworkConfiguration, Role, NodeKeyConfig};
 pub use sc_executor::WasmExecutionMethod;
+pub use sc_offchain::HttpConfig as OffchainHttpConfig;
+pub use crate::client::NativeWasmCheckConfig;
 use sc_client_api::execution_extensions::ExecutionStrategies;
 
 use std::{io, future::Future, path::{PathBuf, Path}, pin::Pin, net::SocketAddr, sync::Arc};
@@ -58,10 +62,20 @@ pub struct Configuration {
 	pub state_cache_child_ratio: Option<usize>,
 	/// Pruning settings.
 	pub pruning: PruningMode,
+	/// Block body pruning settings.
+	pub blocks_pruning: BlocksPruning,
+	/// Offchain indexing data pruning settings.
+	pub offchain_index_pruning: OffchainIndexPruning,
 	/// Chain configuration.
 	pub chain_spec: Box<dyn ChainSpec>,
 	/// Wasm execution method.
 	pub wasm_method: WasmExecutionMethod,
+	/// Directory of the on-disk cache of compiled runtimes. `None` if disabled.
+	pub wasm_runtime_cache_path: Option<PathBuf>,
+	/// Measure the calls made by the runtime to host functions.
+	pub instrument_host_functions: bool,
+	/// Compare the native and wasm runtimes on a fraction of the imported blocks.
+	pub native_wasm_check: Option<NativeWasmCheckConfig>,
 	/// Execution strategies.
 	pub execution_strategies: ExecutionStrategies,
 	/// RPC over HTTP binding address. `None` if disabled.
@@ -152,6 +166,8 @@ pub struct OffchainWorkerConfig {
 	pub enabled: bool,
 	/// allow writes from the runtime to the offchain worker database.
 	pub indexing_enabled: bool,
+	/// Configuration of the HTTP client of offchain workers.
+	pub http: OffchainHttpConfig,
 }
 
 /// Configuration of the Prometheus endpoint.
@@ -59,7 +59,8 @@ pub use self::builder::{
 	TFullCallExecutor, TLightCallExecutor, RpcExtensionBuilder, NoopRpcExtensionBuilder,
 };
 pub use config::{
-	BasePath, Configuration, DatabaseConfig, PruningMode, Role, RpcMethods, TaskExecutor, TaskType,
+	BasePath, Configuration, DatabaseConfig, PruningMode, BlocksPruning, OffchainIndexPruning, Role,
+	RpcMethods, TaskExecutor, TaskType, NativeWasmCheckConfig,
 };
 pub use sc_chain_spec::{
 	ChainSpec, GenericChainSpec, Properties, RuntimeGenesis, Extension as ChainSpecExtension,
@@ -68,7 +69,7 @@ pub use sc_chain_spec::{
 pub use sp_transaction_pool::{TransactionPool, InPoolTransaction, error::IntoPoolError};
 pub use sc_transaction_pool::txpool::Options as TransactionPoolOptions;
 pub use sc_rpc::Metadata as RpcMetadata;
-pub use sc_executor::NativeExecutionDispatch;
+pub use sc_executor::{ExecutionLimits, NativeExecutionDispatch};
 #[doc(hidden)]
 pub use std::{ops::Deref, result::Result, sync::Arc};
 #[doc(hidden)]
@@ -299,6 +300,56 @@ async fn build_network_future<
 							))),
 						};
 					}
+					sc_rpc::system::Request::PeersReputation(sender) => {
+						use sc_rpc::system::{PeerDirection, PeerReputation, PeerSlotType};
+
+						let peers = network.peers_state().into_iter().map(|peer| PeerReputation {
+							peer_id: peer.peer_id.to_base58(),
+							reputation: peer.reputation,
+							direction: peer.direction.map(|direction| match direction {
+								sc_network::ConnectionDirection::In => PeerDirection::Inbound,
+								sc_network::ConnectionDirection::Out => PeerDirection::Outbound,
+							}),
+							slot_type: match peer.slot_type {
+								sc_network::SlotType::Reserved => PeerSlotType::Reserved,
+								sc_network::SlotType::Priority => PeerSlotType::Priority,
+								sc_network::SlotType::Regular => PeerSlotType::Regular,
+							},
+						}).collect();
+						let _ = sender.send(peers);
+					}
+					sc_rpc::system::Request::NetworkBanPeer(peer_id, duration, sender) => {
+						let _ = match peer_id.parse::<PeerId>() {
+							Ok(peer_id) => {
+								network.service().ban_peer(peer_id, duration);
+								sender.send(Ok(()))
+							}
+							Err(e) => sender.send(Err(sc_rpc::system::error::Error::MalformattedPeerArg(
+								e.to_string(),
+							))),
+						};
+					}
+					sc_rpc::system::Request::NetworkUnbanPeer(peer_id, sender) => {
+						let _ = match peer_id.parse::<PeerId>() {
+							Ok(peer_id) => {
+								network.service().unban_peer(peer_id);
+								sender.send(Ok(()))
+							}
+							Err(e) => sender.send(Err(sc_rpc::system::error::Error::MalformattedPeerArg(
+								e.to_string(),
+							))),
+						};
+					}
+					sc_rpc::system::Request::NetworkBanIpRange(range, duration, sender) => {
+						let x = network.service().ban_ip_range(&range, duration)
+							.map_err(sc_rpc::system::error::Error::MalformattedIpRangeArg);
+						let _ = sender.send(x);
+					}
+					sc_rpc::system::Request::NetworkUnbanIpRange(range, sender) => {
+						let x = network.service().unban_ip_range(&range)
+							.map_err(sc_rpc::system::error::Error::MalformattedIpRangeArg);
+						let _ = sender.send(x);
+					}
 					sc_rpc::system::Request::NodeRoles(sender) => {
 						use sc_rpc::system::NodeRole;
 
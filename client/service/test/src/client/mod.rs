@@ -31,7 +31,9 @@ use substrate_test_runtime_client::{
 use sc_client_api::{
 	StorageProvider, BlockBackend, in_mem, BlockchainEvents,
 };
-use sc_client_db::{Backend, DatabaseSettings, DatabaseSettingsSrc, PruningMode};
+use sc_client_db::{
+	Backend, BlocksPruning, DatabaseSettings, DatabaseSettingsSrc, OffchainIndexPruning, PruningMode,
+};
 use sc_block_builder::BlockBuilderProvider;
 use sc_service::client::{self, Client, LocalCallExecutor, new_in_mem};
 use sp_runtime::traits::{
@@ -1276,6 +1278,8 @@ fn doesnt_import_blocks_that_revert_finality() {
 			state_cache_size: 1 << 20,
 			state_cache_child_ratio: None,
 			pruning: PruningMode::ArchiveAll,
+			blocks_pruning: BlocksPruning::KeepAll,
+			offchain_index_pruning: OffchainIndexPruning::KeepAll,
 			source: DatabaseSettingsSrc::RocksDb {
 				path: tmp.path().into(),
 				cache_size: 1024,
@@ -1477,6 +1481,8 @@ fn returns_status_for_pruned_blocks() {
 			state_cache_size: 1 << 20,
 			state_cache_child_ratio: None,
 			pruning: PruningMode::keep_blocks(1),
+			blocks_pruning: BlocksPruning::KeepAll,
+			offchain_index_pruning: OffchainIndexPruning::KeepAll,
 			source: DatabaseSettingsSrc::RocksDb {
 				path: tmp.path().into(),
 				cache_size: 1024,
@@ -1802,3 +1808,57 @@ fn cleans_up_closed_notification_sinks_on_block_import() {
 	assert_eq!(client.finality_notification_sinks().lock().len(), 0);
 }
 
+
+#[test]
+fn native_and_wasm_are_compared_on_import() {
+	use substrate_test_runtime_client::GenesisInit;
+
+	let dir = tempfile::tempdir().unwrap();
+	let registry = substrate_prometheus_endpoint::Registry::new();
+	let mut client =
+		new_in_mem::<
+			_,
+			substrate_test_runtime_client::runtime::Block,
+			_,
+			substrate_test_runtime_client::runtime::RuntimeApi
+		>(
+			substrate_test_runtime_client::new_native_executor(),
+			&substrate_test_runtime_client::GenesisParameters::default().genesis_storage(),
+			None,
+			Some(registry.clone()),
+			Box::new(TaskExecutor::new()),
+			client::ClientConfig {
+				native_wasm_check: Some(sc_service::NativeWasmCheckConfig {
+					fraction: 1.0,
+					path: dir.path().join("mismatches"),
+				}),
+				..Default::default()
+			},
+		)
+			.unwrap();
+
+	let mut builder = client.new_block(Default::default()).unwrap();
+	builder.push_transfer(Transfer {
+		from: AccountKeyring::Alice.into(),
+		to: AccountKeyring::Ferdie.into(),
+		amount: 42,
+		nonce: 0,
+	}).unwrap();
+	let block = builder.build().unwrap().block;
+
+	let (header, extrinsics) = block.deconstruct();
+	let mut import = BlockImportParams::new(BlockOrigin::NetworkBroadcast, header);
+	import.body = Some(extrinsics);
+	import.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+	client.import_block(import, Default::default()).unwrap();
+	assert_eq!(client.chain_info().best_number, 1);
+
+	let counter = |name: &str| registry.gather()
+		.into_iter()
+		.find(|family| family.get_name() == name)
+		.map(|family| family.get_metric()[0].get_counter().get_value())
+		.unwrap();
+	assert_eq!(counter("native_wasm_checks_total"), 1.0);
+	assert_eq!(counter("native_wasm_mismatches_total"), 0.0);
+	assert!(!dir.path().join("mismatches").exists());
+}
@@ -226,6 +226,7 @@ fn node_config<G: RuntimeGenesis + 'static, E: ChainSpecExtension + Clone + 'sta
 		allow_private_ipv4: true,
 		wasm_external_transport: None,
 		use_yamux_flow_control: true,
+		websocket_tls: None,
 	};
 
 	Configuration {
@@ -246,8 +247,13 @@ fn node_config<G: RuntimeGenesis + 'static, E: ChainSpecExtension + Clone + 'sta
 		state_cache_size: 16777216,
 		state_cache_child_ratio: None,
 		pruning: Default::default(),
+		blocks_pruning: Default::default(),
+		offchain_index_pruning: Default::default(),
 		chain_spec: Box::new((*spec).clone()),
 		wasm_method: sc_service::config::WasmExecutionMethod::Interpreted,
+		wasm_runtime_cache_path: None,
+		instrument_host_functions: false,
+		native_wasm_check: None,
 		execution_strategies: Default::default(),
 		rpc_http: None,
 		rpc_ipc: None,
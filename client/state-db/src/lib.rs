@@ -30,6 +30,12 @@
 //! # Pruning.
 //! See `RefWindow` for pruning algorithm details. `StateDb` prunes on each canonicalization until pruning
 //! constraints are satisfied.
+//!
+//! # Archived prefixes.
+//! Pruning constraints may list storage key prefixes whose history is kept. This relies on node
+//! keys being made of the packed nibble path of the node in the trie followed by the node hash,
+//! as done by `sp_trie::prefixed_key`. Nodes on the path to, or below, an archived prefix are never
+//! deleted, so the archived storage can still be read at pruned blocks.
 
 mod noncanonical;
 mod pruning;
@@ -38,18 +44,20 @@ mod test;
 
 use std::fmt;
 use parking_lot::RwLock;
-use codec::Codec;
+use codec::{Codec, Decode, Encode};
 use std::collections::{HashMap, hash_map::Entry};
 use noncanonical::NonCanonicalOverlay;
 use pruning::RefWindow;
 use log::trace;
 use parity_util_mem::{MallocSizeOf, malloc_size};
 use sc_client_api::{StateDbMemoryInfo, MemorySize};
+use sp_core::hexdisplay::HexDisplay;
 
 const PRUNING_MODE: &[u8] = b"mode";
 const PRUNING_MODE_ARCHIVE: &[u8] = b"archive";
 const PRUNING_MODE_ARCHIVE_CANON: &[u8] = b"archive_canonical";
 const PRUNING_MODE_CONSTRAINED: &[u8] = b"constrained";
+const PRUNING_ARCHIVE_PREFIXES: &[u8] = b"archive_prefixes";
 
 /// Database value type.
 pub type DBValue = Vec<u8>;
@@ -89,6 +97,8 @@ pub enum Error<E: fmt::Debug> {
 	InvalidParent,
 	/// Invalid pruning mode specified. Contains expected mode.
 	InvalidPruningMode(String),
+	/// Invalid archived storage prefixes specified. Contains expected prefixes.
+	InvalidArchivePrefixes(Vec<Vec<u8>>),
 }
 
 /// Pinning error type.
@@ -112,6 +122,14 @@ impl<E: fmt::Debug> fmt::Debug for Error<E> {
 			Error::InvalidBlockNumber => write!(f, "Trying to insert block with invalid number"),
 			Error::InvalidParent => write!(f, "Trying to insert block with unknown parent"),
 			Error::InvalidPruningMode(e) => write!(f, "Expected pruning mode: {}", e),
+			Error::InvalidArchivePrefixes(prefixes) => {
+				write!(f, "Expected archived storage prefixes: [")?;
+				for (i, prefix) in prefixes.iter().enumerate() {
+					let separator = if i == 0 { "" } else { ", " };
+					write!(f, "{}0x{}", separator, HexDisplay::from(prefix))?;
+				}
+				write!(f, "]")
+			},
 		}
 	}
 }
@@ -141,6 +159,8 @@ pub struct Constraints {
 	pub max_blocks: Option<u32>,
 	/// Maximum memory in the pruning overlay.
 	pub max_mem: Option<usize>,
+	/// Storage key prefixes of the main trie whose history is never pruned.
+	pub archive_prefixes: Vec<Vec<u8>>,
 }
 
 /// Pruning mode.
@@ -160,9 +180,30 @@ impl PruningMode {
 		PruningMode::Constrained(Constraints {
 			max_blocks: Some(n),
 			max_mem: None,
+			archive_prefixes: Vec::new(),
 		})
 	}
 
+	/// Keep the history of the storage under `prefixes` in a pruning mode. Archive modes
+	/// already keep everything and are returned unchanged.
+	pub fn with_archive_prefixes(self, prefixes: Vec<Vec<u8>>) -> PruningMode {
+		match self {
+			PruningMode::Constrained(constraints) => PruningMode::Constrained(Constraints {
+				archive_prefixes: prefixes,
+				..constraints
+			}),
+			mode => mode,
+		}
+	}
+
+	/// Storage key prefixes whose history is kept by this mode.
+	pub fn archive_prefixes(&self) -> &[Vec<u8>] {
+		match self {
+			PruningMode::Constrained(constraints) => &constraints.archive_prefixes,
+			PruningMode::ArchiveAll | PruningMode::ArchiveCanonical => &[],
+		}
+	}
+
 	/// Is this an archive (either ArchiveAll or ArchiveCanonical) pruning mode?
 	pub fn is_archive(&self) -> bool {
 		match *self {
@@ -187,12 +228,62 @@ impl Default for PruningMode {
 	}
 }
 
+/// Length of the hash at the end of node keys.
+const NODE_HASH_LEN: usize = 32;
+
+/// Returns true if the node stored under `key` is on the path to, or below, one of `prefixes`.
+///
+/// The node path can't be told apart from a path ending with an odd nibble 0 as it's packed in
+/// the key, so the shorter path is assumed, which may keep a few extra nodes.
+fn is_archived(key: &[u8], prefixes: &[Vec<u8>]) -> bool {
+	let path = &key[..key.len().saturating_sub(NODE_HASH_LEN)];
+	let path_nibbles = match path.last() {
+		Some(last) if last & 0x0f == 0 => path.len() * 2 - 1,
+		_ => path.len() * 2,
+	};
+	prefixes.iter().any(|prefix| {
+		let nibbles = path_nibbles.min(prefix.len() * 2);
+		let bytes = nibbles / 2;
+		path[..bytes] == prefix[..bytes] && (nibbles % 2 == 0 || path[bytes] >> 4 == prefix[bytes] >> 4)
+	})
+}
+
 fn to_meta_key<S: Codec>(suffix: &[u8], data: &S) -> Vec<u8> {
 	let mut buffer = data.encode();
 	buffer.extend(suffix);
 	buffer
 }
 
+/// Rewrite the node keys referenced by a state-db journal record.
+///
+/// `meta_key` and `value` are an entry of the meta column. Returns `None` if the entry is not
+/// a journal record, otherwise the record re-encoded with every node key passed through
+/// `map_key`, for a database opened with the given `ref_counting` setting. This allows
+/// moving a database to a backend that stores trie nodes under different keys.
+pub fn map_journal_keys<BlockHash: Hash>(
+	meta_key: &[u8],
+	value: &[u8],
+	ref_counting: bool,
+	map_key: &dyn Fn(&[u8]) -> Vec<u8>,
+) -> Option<Result<Vec<u8>, codec::Error>> {
+	noncanonical::map_journal_record::<BlockHash>(meta_key, value, map_key)
+		.or_else(|| pruning::map_journal_record::<BlockHash>(meta_key, value, ref_counting, map_key))
+}
+
+/// Node keys deleted by a state-db journal record.
+///
+/// `meta_key` and `value` are an entry of the meta column. Returns `None` if the entry is not
+/// a journal record. The nodes are removed from the database once the block of the record is
+/// pruned, or, for the blocks that aren't canonicalized yet, once it is canonicalized and then
+/// pruned.
+pub fn journal_deleted_keys<BlockHash: Hash>(
+	meta_key: &[u8],
+	value: &[u8],
+) -> Option<Result<Vec<Vec<u8>>, codec::Error>> {
+	noncanonical::journal_record_deleted_keys::<BlockHash>(meta_key, value)
+		.or_else(|| pruning::journal_record_deleted_keys::<BlockHash>(meta_key, value))
+}
+
 struct StateDbSync<BlockHash: Hash, Key: Hash> {
 	mode: PruningMode,
 	non_canonical: NonCanonicalOverlay<BlockHash, Key>,
@@ -200,7 +291,7 @@ struct StateDbSync<BlockHash: Hash, Key: Hash> {
 	pinned: HashMap<BlockHash, u32>,
 }
 
-impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<BlockHash, Key> {
+impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf + AsRef<[u8]>> StateDbSync<BlockHash, Key> {
 	fn new<D: MetaDb>(
 		mode: PruningMode,
 		ref_counting: bool,
@@ -236,10 +327,26 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 			db_mode.as_ref().map(|v| std::str::from_utf8(&v))
 		);
 		match &db_mode {
-			Some(v) if v.as_slice() == mode.id() => Ok(()),
-			Some(v) => Err(Error::InvalidPruningMode(String::from_utf8_lossy(v).into())),
-			None => Ok(()),
+			Some(v) if v.as_slice() == mode.id() => (),
+			Some(v) => return Err(Error::InvalidPruningMode(String::from_utf8_lossy(v).into())),
+			None => return Ok(()),
+		}
+
+		// The history of prefixes that weren't archived from the start is already partially
+		// pruned, and archived prefixes can't be pruned afterwards either.
+		let mut db_prefixes: Vec<Vec<u8>> = match db.get_meta(&to_meta_key(PRUNING_ARCHIVE_PREFIXES, &()))
+			.map_err(Error::Db)?
+		{
+			Some(encoded) => Decode::decode(&mut encoded.as_slice())?,
+			None => Vec::new(),
+		};
+		let mut prefixes = mode.archive_prefixes().to_vec();
+		db_prefixes.sort();
+		prefixes.sort();
+		if db_prefixes != prefixes {
+			return Err(Error::InvalidArchivePrefixes(db_prefixes));
 		}
+		Ok(())
 	}
 
 	fn insert_block<E: fmt::Debug>(
@@ -253,6 +360,12 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 		if number == 0 {
 			// Save pruning mode when writing first block.
 			meta.inserted.push((to_meta_key(PRUNING_MODE, &()), self.mode.id().into()));
+			if !self.mode.archive_prefixes().is_empty() {
+				meta.inserted.push((
+					to_meta_key(PRUNING_ARCHIVE_PREFIXES, &()),
+					self.mode.archive_prefixes().encode(),
+				));
+			}
 		}
 
 		match self.mode {
@@ -336,6 +449,9 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 				}
 				pruning.prune_one(commit);
 			}
+			if !constraints.archive_prefixes.is_empty() {
+				commit.data.deleted.retain(|key| !is_archived(key.as_ref(), &constraints.archive_prefixes));
+			}
 		}
 	}
 
@@ -440,7 +556,7 @@ pub struct StateDb<BlockHash: Hash, Key: Hash> {
 	db: RwLock<StateDbSync<BlockHash, Key>>,
 }
 
-impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDb<BlockHash, Key> {
+impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf + AsRef<[u8]>> StateDb<BlockHash, Key> {
 	/// Creates a new instance. Does not expect any metadata in the database.
 	pub fn new<D: MetaDb>(
 		mode: PruningMode,
@@ -615,6 +731,7 @@ mod tests {
 		let (db, _) = make_test_db(PruningMode::Constrained(Constraints {
 			max_blocks: Some(0),
 			max_mem: None,
+			archive_prefixes: Vec::new(),
 		}));
 		assert!(db.data_eq(&make_db(&[21, 3, 922, 94])));
 	}
@@ -624,6 +741,7 @@ mod tests {
 		let (db, sdb) = make_test_db(PruningMode::Constrained(Constraints {
 			max_blocks: Some(1),
 			max_mem: None,
+			archive_prefixes: Vec::new(),
 		}));
 		assert!(sdb.is_pruned(&H256::from_low_u64_be(0), 0));
 		assert!(sdb.is_pruned(&H256::from_low_u64_be(1), 1));
@@ -637,6 +755,7 @@ mod tests {
 		let (db, sdb) = make_test_db(PruningMode::Constrained(Constraints {
 			max_blocks: Some(2),
 			max_mem: None,
+			archive_prefixes: Vec::new(),
 		}));
 		assert!(sdb.is_pruned(&H256::from_low_u64_be(0), 0));
 		assert!(sdb.is_pruned(&H256::from_low_u64_be(1), 1));
@@ -645,6 +764,37 @@ mod tests {
 		assert!(db.data_eq(&make_db(&[1, 21, 3, 921, 922, 93, 94])));
 	}
 
+	#[test]
+	fn archived_prefixes_match_node_paths() {
+		use crate::is_archived;
+		let key = |path: &[u8]| [path, &[0xaa; 32][..]].concat();
+		let prefixes = vec![vec![0x12, 0x34]];
+
+		// Ancestors of the prefix.
+		assert!(is_archived(&key(&[]), &prefixes));
+		assert!(is_archived(&key(&[0x10]), &prefixes));
+		assert!(is_archived(&key(&[0x12]), &prefixes));
+		assert!(is_archived(&key(&[0x12, 0x30]), &prefixes));
+		// Nodes below the prefix.
+		assert!(is_archived(&key(&[0x12, 0x34]), &prefixes));
+		assert!(is_archived(&key(&[0x12, 0x34, 0x56]), &prefixes));
+		// Unrelated nodes.
+		assert!(!is_archived(&key(&[0x13]), &prefixes));
+		assert!(!is_archived(&key(&[0x12, 0x35]), &prefixes));
+		assert!(!is_archived(&key(&[0x20]), &prefixes));
+	}
+
+	#[test]
+	fn prune_keeps_archived_nodes() {
+		let (db, _) = make_test_db(PruningMode::Constrained(Constraints {
+			max_blocks: Some(0),
+			max_mem: None,
+			archive_prefixes: vec![vec![0x12]],
+		}));
+		// Test keys have no path, so all nodes are ancestors of the archived prefix.
+		assert!(db.data_eq(&make_db(&[1, 21, 3, 91, 921, 922, 93, 94])));
+	}
+
 	#[test]
 	fn detects_incompatible_mode() {
 		let mut db = make_db(&[]);
@@ -659,8 +809,37 @@ mod tests {
 			)
 			.unwrap(),
 		);
-		let new_mode = PruningMode::Constrained(Constraints { max_blocks: Some(2), max_mem: None });
+		let new_mode = PruningMode::Constrained(Constraints {
+			max_blocks: Some(2),
+			max_mem: None,
+			archive_prefixes: Vec::new(),
+		});
 		let state_db: Result<StateDb<H256, H256>, _> = StateDb::new(new_mode, false, &db);
 		assert!(state_db.is_err());
 	}
+
+	#[test]
+	fn detects_incompatible_archive_prefixes() {
+		let mode = |archive_prefixes| PruningMode::Constrained(Constraints {
+			max_blocks: Some(2),
+			max_mem: None,
+			archive_prefixes,
+		});
+		let mut db = make_db(&[]);
+		let state_db = StateDb::new(mode(vec![vec![0x12], vec![0x34]]), false, &db).unwrap();
+		db.commit(
+			&state_db
+			.insert_block::<io::Error>(
+				&H256::from_low_u64_be(0),
+				0,
+				&H256::from_low_u64_be(0),
+				make_changeset(&[], &[]),
+			)
+			.unwrap(),
+		);
+
+		assert!(StateDb::<H256, H256>::new(mode(vec![vec![0x34], vec![0x12]]), false, &db).is_ok());
+		assert!(StateDb::<H256, H256>::new(mode(vec![vec![0x12]]), false, &db).is_err());
+		assert!(StateDb::<H256, H256>::new(mode(Vec::new()), false, &db).is_err());
+	}
 }
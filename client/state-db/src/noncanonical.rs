@@ -57,6 +57,40 @@ fn to_journal_key(block: u64, index: u64) -> Vec<u8> {
 	to_meta_key(NON_CANONICAL_JOURNAL, &(block, index))
 }
 
+/// Re-encode the journal record stored at `journal_key` with every node key passed through
+/// `map_key`. Returns `None` if `journal_key` is not a key of this journal.
+pub(crate) fn map_journal_record<BlockHash: Hash>(
+	journal_key: &[u8],
+	record: &[u8],
+	map_key: &dyn Fn(&[u8]) -> Vec<u8>,
+) -> Option<Result<Vec<u8>, codec::Error>> {
+	if journal_key.len() != 16 + NON_CANONICAL_JOURNAL.len()
+		|| !journal_key.ends_with(NON_CANONICAL_JOURNAL)
+	{
+		return None;
+	}
+	Some(JournalRecord::<BlockHash, Vec<u8>>::decode(&mut &record[..]).map(|record| JournalRecord {
+		hash: record.hash,
+		parent_hash: record.parent_hash,
+		inserted: record.inserted.into_iter().map(|(k, v)| (map_key(&k), v)).collect(),
+		deleted: record.deleted.iter().map(|k| map_key(k)).collect(),
+	}.encode()))
+}
+
+/// Keys deleted by the journal record stored at `journal_key`. Returns `None` if `journal_key`
+/// is not a key of this journal.
+pub(crate) fn journal_record_deleted_keys<BlockHash: Hash>(
+	journal_key: &[u8],
+	record: &[u8],
+) -> Option<Result<Vec<Vec<u8>>, codec::Error>> {
+	if journal_key.len() != 16 + NON_CANONICAL_JOURNAL.len()
+		|| !journal_key.ends_with(NON_CANONICAL_JOURNAL)
+	{
+		return None;
+	}
+	Some(JournalRecord::<BlockHash, Vec<u8>>::decode(&mut &record[..]).map(|record| record.deleted))
+}
+
 #[cfg_attr(test, derive(PartialEq, Debug))]
 #[derive(parity_util_mem_derive::MallocSizeOf)]
 struct BlockOverlay<BlockHash: Hash, Key: Hash> {
@@ -71,6 +71,41 @@ fn to_journal_key(block: u64) -> Vec<u8> {
 	to_meta_key(PRUNING_JOURNAL, &block)
 }
 
+/// Re-encode the journal record stored at `journal_key` with every node key passed through
+/// `map_key`. Inserted keys are only kept if `count_insertions` is set. Returns `None` if
+/// `journal_key` is not a key of this journal.
+pub(crate) fn map_journal_record<BlockHash: Hash>(
+	journal_key: &[u8],
+	record: &[u8],
+	count_insertions: bool,
+	map_key: &dyn Fn(&[u8]) -> Vec<u8>,
+) -> Option<Result<Vec<u8>, codec::Error>> {
+	if journal_key.len() != 8 + PRUNING_JOURNAL.len() || !journal_key.ends_with(PRUNING_JOURNAL) {
+		return None;
+	}
+	Some(JournalRecord::<BlockHash, Vec<u8>>::decode(&mut &record[..]).map(|record| JournalRecord {
+		hash: record.hash,
+		inserted: if count_insertions {
+			record.inserted.iter().map(|k| map_key(k)).collect()
+		} else {
+			Vec::new()
+		},
+		deleted: record.deleted.iter().map(|k| map_key(k)).collect(),
+	}.encode()))
+}
+
+/// Keys deleted by the journal record stored at `journal_key`. Returns `None` if `journal_key`
+/// is not a key of this journal.
+pub(crate) fn journal_record_deleted_keys<BlockHash: Hash>(
+	journal_key: &[u8],
+	record: &[u8],
+) -> Option<Result<Vec<Vec<u8>>, codec::Error>> {
+	if journal_key.len() != 8 + PRUNING_JOURNAL.len() || !journal_key.ends_with(PRUNING_JOURNAL) {
+		return None;
+	}
+	Some(JournalRecord::<BlockHash, Vec<u8>>::decode(&mut &record[..]).map(|record| record.deleted))
+}
+
 impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 	pub fn new<D: MetaDb>(db: &D, count_insertions: bool) -> Result<RefWindow<BlockHash, Key>, Error<D::Error>> {
 		let last_pruned = db.get_meta(&to_meta_key(LAST_PRUNED, &()))
@@ -18,7 +18,7 @@
 //! # Authority discovery module.
 //!
 //! This module is used by the `client/authority-discovery` to retrieve the
-//! current set of authorities.
+//! current and the next set of authorities.
 
 // Ensure we're `no_std` when compiling for Wasm.
 #![cfg_attr(not(feature = "std"), no_std)]
@@ -34,6 +34,9 @@ decl_storage! {
 	trait Store for Module<T: Trait> as AuthorityDiscovery {
 		/// Keys of the current authority set.
 		Keys get(fn keys): Vec<AuthorityId>;
+
+		/// Keys of the next authority set.
+		NextKeys get(fn next_keys): Vec<AuthorityId>;
 	}
 	add_extra_genesis {
 		config(keys): Vec<AuthorityId>;
@@ -52,10 +55,16 @@ impl<T: Trait> Module<T> {
 		Keys::get()
 	}
 
+	/// Retrieve authority identifiers of the next authority set.
+	pub fn next_authorities() -> Vec<AuthorityId> {
+		NextKeys::get()
+	}
+
 	fn initialize_keys(keys: &[AuthorityId]) {
 		if !keys.is_empty() {
 			assert!(Keys::get().is_empty(), "Keys are already initialized!");
 			Keys::put(keys);
+			NextKeys::put(keys);
 		}
 	}
 }
@@ -75,7 +84,7 @@ impl<T: Trait> pallet_session::OneSessionHandler<T::AccountId> for Module<T> {
 		Self::initialize_keys(&keys);
 	}
 
-	fn on_new_session<'a, I: 'a>(changed: bool, validators: I, _queued_validators: I)
+	fn on_new_session<'a, I: 'a>(changed: bool, validators: I, queued_validators: I)
 	where
 		I: Iterator<Item = (&'a T::AccountId, Self::Key)>,
 	{
@@ -83,6 +92,10 @@ impl<T: Trait> pallet_session::OneSessionHandler<T::AccountId> for Module<T> {
 		if changed {
 			Keys::put(validators.map(|x| x.1).collect::<Vec<_>>());
 		}
+
+		// Remember who the authorities are for the next session. The queued set is
+		// recomputed on every session, even if the current set did not change.
+		NextKeys::put(queued_validators.map(|x| x.1).collect::<Vec<_>>());
 	}
 
 	fn on_disabled(_i: usize) {
@@ -252,4 +265,52 @@ mod tests {
 			assert_eq!(second_authorities, AuthorityDiscovery::authorities());
 		});
 	}
+
+	#[test]
+	fn next_authorities_returns_queued_authority_set() {
+		let account_id = AuthorityPair::from_seed_slice(vec![10; 32].as_ref()).unwrap().public();
+
+		let first_authorities: Vec<AuthorityId> = vec![0, 1].into_iter()
+			.map(|i| AuthorityPair::from_seed_slice(vec![i; 32].as_ref()).unwrap().public())
+			.map(AuthorityId::from)
+			.collect();
+
+		let next_authorities: Vec<AuthorityId> = vec![2, 3].into_iter()
+			.map(|i| AuthorityPair::from_seed_slice(vec![i; 32].as_ref()).unwrap().public())
+			.map(AuthorityId::from)
+			.collect();
+
+		let first_authorities_and_account_ids: Vec<(&AuthorityId, AuthorityId)> = first_authorities.clone()
+			.into_iter()
+			.map(|id| (&account_id, id))
+			.collect();
+
+		let next_authorities_and_account_ids: Vec<(&AuthorityId, AuthorityId)> = next_authorities.clone()
+			.into_iter()
+			.map(|id| (&account_id, id))
+			.collect();
+
+		let t = frame_system::GenesisConfig::default()
+			.build_storage::<Test>()
+			.unwrap();
+
+		TestExternalities::new(t).execute_with(|| {
+			use pallet_session::OneSessionHandler;
+
+			// At genesis the next authority set equals the current one.
+			AuthorityDiscovery::on_genesis_session(
+				first_authorities.iter().map(|id| (id, id.clone()))
+			);
+			assert_eq!(first_authorities, AuthorityDiscovery::next_authorities());
+
+			// The queued authority set is tracked even if the current set didn't change.
+			AuthorityDiscovery::on_new_session(
+				false,
+				first_authorities_and_account_ids.into_iter(),
+				next_authorities_and_account_ids.into_iter(),
+			);
+			assert_eq!(first_authorities, AuthorityDiscovery::authorities());
+			assert_eq!(next_authorities, AuthorityDiscovery::next_authorities());
+		});
+	}
 }
@@ -0,0 +1,708 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Consensus extension module for Sassafras consensus. Collects the VRF
+//! tickets submitted by the authorities for the next epoch and, at every
+//! epoch change, turns the winning tickets into the slot schedule of the new
+//! epoch. Slots without a ticket are assigned to a round-robin fallback
+//! author.
+//!
+//! WARNING: tickets are not generated with a ring-VRF yet, so every ticket is
+//! linked to the authority that submitted it and the slot owners of the next
+//! epoch are publicly known. This module does NOT provide slot owner anonymity.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+#![warn(unused_must_use, unsafe_code, unused_variables, unused_must_use)]
+
+use codec::{Decode, Encode};
+use frame_support::{
+	debug, decl_error, decl_module, decl_storage,
+	traits::{FindAuthor, Get, Randomness as RandomnessT},
+	weights::Weight,
+};
+use frame_system::{ensure_none, offchain::SendTransactionTypes};
+use sp_application_crypto::Public;
+use sp_runtime::{
+	generic::DigestItem,
+	traits::{Hash, IsMember, One, SaturatedConversion, Saturating},
+	transaction_validity::{
+		InvalidTransaction, TransactionPriority, TransactionSource, TransactionValidity,
+		ValidTransaction,
+	},
+	ConsensusEngineId,
+};
+use sp_std::{prelude::*, result};
+use sp_timestamp::OnTimestampSet;
+
+use sp_consensus_sassafras::{
+	digests::{NextEpochDescriptor, PreDigest},
+	inherents::{SassafrasInherentData, INHERENT_IDENTIFIER},
+	AuthorityIndex, ConsensusLog, Epoch, SassafrasConfiguration, SlotNumber, Ticket,
+	TicketEnvelope, SASSAFRAS_ENGINE_ID,
+};
+use sp_consensus_vrf::schnorrkel;
+use sp_inherents::{InherentData, InherentIdentifier, MakeFatalError, ProvideInherent};
+
+pub use sp_consensus_sassafras::{AuthorityId, RANDOMNESS_LENGTH, VRF_OUTPUT_LENGTH};
+
+#[cfg(all(feature = "std", test))]
+mod mock;
+#[cfg(all(feature = "std", test))]
+mod tests;
+
+pub trait Trait: pallet_timestamp::Trait + SendTransactionTypes<Call<Self>> {
+	/// The amount of time, in slots, that each epoch should last.
+	type EpochDuration: Get<SlotNumber>;
+
+	/// The maximum number of tickets each authority tries to generate for
+	/// every epoch.
+	type MaxAttempts: Get<u32>;
+
+	/// The expected number of winning tickets per slot of an epoch. Values
+	/// above one leave room for authorities going offline after having
+	/// submitted their tickets.
+	type RedundancyFactor: Get<u32>;
+
+	/// A configuration for base priority of the unsigned ticket submissions.
+	///
+	/// This is exposed so that it can be tuned for particular runtime, when
+	/// multiple pallets send unsigned transactions.
+	type UnsignedPriority: Get<TransactionPriority>;
+
+	/// Sassafras requires some logic to be triggered on every block to query for whether an epoch
+	/// has ended and to perform the transition to the next epoch.
+	///
+	/// Typically, the `ExternalTrigger` type should be used. An internal trigger should only be used
+	/// when no other module is responsible for changing authority set.
+	type EpochChangeTrigger: EpochChangeTrigger;
+}
+
+/// Trigger an epoch change, if any should take place.
+pub trait EpochChangeTrigger {
+	/// Trigger an epoch change, if any should take place. This should be called
+	/// during every block, after initialization is done.
+	fn trigger<T: Trait>(now: T::BlockNumber);
+}
+
+/// A type signifying to Sassafras that an external trigger
+/// for epoch changes (e.g. pallet-session) is used.
+pub struct ExternalTrigger;
+
+impl EpochChangeTrigger for ExternalTrigger {
+	fn trigger<T: Trait>(_: T::BlockNumber) { } // nothing - trigger is external.
+}
+
+/// A type signifying to Sassafras that it should perform epoch changes
+/// with an internal trigger, recycling the same authorities forever.
+pub struct SameAuthoritiesForever;
+
+impl EpochChangeTrigger for SameAuthoritiesForever {
+	fn trigger<T: Trait>(now: T::BlockNumber) {
+		if <Module<T>>::should_epoch_change(now) {
+			let authorities = <Module<T>>::authorities();
+			let next_authorities = authorities.clone();
+
+			<Module<T>>::enact_epoch_change(authorities, next_authorities);
+		}
+	}
+}
+
+/// The author index and VRF output of the block being built, if any.
+type MaybeVrfOutput = Option<(AuthorityIndex, schnorrkel::VRFOutput)>;
+
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		/// A submitted ticket has an invalid VRF proof, wasn't generated for
+		/// the next epoch or is above the ticket threshold.
+		InvalidTicket,
+	}
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Sassafras {
+		/// Current epoch index.
+		pub EpochIndex get(fn epoch_index): u64;
+
+		/// Current epoch authorities.
+		pub Authorities get(fn authorities): Vec<AuthorityId>;
+
+		/// Next epoch authorities, i.e. the authorities tickets are submitted by.
+		pub NextAuthorities get(fn next_authorities): Vec<AuthorityId>;
+
+		/// The slot at which the first epoch actually started. This is 0
+		/// until the first block of the chain.
+		pub GenesisSlot get(fn genesis_slot): u64;
+
+		/// Current slot number.
+		pub CurrentSlot get(fn current_slot): u64;
+
+		/// The epoch randomness for the *current* epoch.
+		///
+		/// # Security
+		///
+		/// This MUST NOT be used for gambling, as it can be influenced by a
+		/// malicious validator in the short term. It MAY be used in many
+		/// cryptographic protocols, however, so long as one remembers that this
+		/// (like everything else on-chain) it is public.
+		pub Randomness get(fn randomness): schnorrkel::Randomness;
+
+		/// Next epoch randomness, used to generate the tickets for the next epoch.
+		pub NextRandomness get(fn next_randomness): schnorrkel::Randomness;
+
+		/// Accumulator of the block VRF outputs of the current epoch, used to
+		/// compute the randomness of the epoch after next.
+		RandomnessAccumulator get(fn randomness_accumulator): schnorrkel::Randomness;
+
+		/// Winning tickets of the current epoch, sorted by ticket identifier.
+		pub Tickets get(fn tickets): Vec<Ticket>;
+
+		/// Winning tickets submitted so far for the next epoch, sorted by
+		/// ticket identifier. Only the `EpochDuration` lowest tickets are kept.
+		pub NextTickets get(fn next_tickets): Vec<Ticket>;
+
+		/// Temporary value (cleared at block finalization) which is `Some`
+		/// if per-block initialization has already been called for current block.
+		Initialized get(fn initialized): Option<MaybeVrfOutput>;
+	}
+	add_extra_genesis {
+		config(authorities): Vec<AuthorityId>;
+		build(|config| Module::<T>::initialize_authorities(&config.authorities))
+	}
+}
+
+decl_module! {
+	/// The Sassafras Pallet
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		/// The number of **slots** that an epoch takes. We couple sessions to
+		/// epochs, i.e. we start a new session once the new epoch begins.
+		const EpochDuration: u64 = T::EpochDuration::get();
+
+		/// The maximum number of tickets each authority tries to generate for
+		/// every epoch.
+		const MaxAttempts: u32 = T::MaxAttempts::get();
+
+		/// The expected number of winning tickets per slot of an epoch.
+		const RedundancyFactor: u32 = T::RedundancyFactor::get();
+
+		/// Initialization
+		fn on_initialize(now: T::BlockNumber) -> Weight {
+			Self::do_initialize(now);
+
+			0
+		}
+
+		/// Block finalization
+		fn on_finalize() {
+			// at the end of the block the epoch change, if any, has already been
+			// enacted, so the block VRF output can be checked against the epoch
+			// it was generated for.
+			if let Some(Some((authority_index, vrf_output))) = Initialized::take() {
+				if let Some(randomness) = Self::block_randomness(authority_index, &vrf_output) {
+					Self::deposit_randomness(&randomness);
+				}
+			}
+		}
+
+		/// Submit tickets for the next epoch. This extrinsic must be called
+		/// unsigned, the tickets are authenticated by their VRF proof (validated
+		/// in `ValidateUnsigned`).
+		#[weight = weight::weight_for_submit_tickets::<T>(tickets.len())]
+		fn submit_tickets(origin, tickets: Vec<TicketEnvelope>) {
+			ensure_none(origin)?;
+
+			let tickets = tickets.iter()
+				.map(|ticket| Self::check_ticket(ticket).ok_or(Error::<T>::InvalidTicket))
+				.collect::<Result<Vec<_>, _>>()?;
+
+			Self::append_tickets(tickets);
+		}
+	}
+}
+
+mod weight {
+	use frame_support::{
+		traits::Get,
+		weights::{constants::WEIGHT_PER_MICROS, Weight},
+	};
+
+	pub fn weight_for_submit_tickets<T: super::Trait>(tickets: usize) -> Weight {
+		// verifying the VRF proof of each ticket
+		(tickets as Weight).saturating_mul(60 * WEIGHT_PER_MICROS)
+			// epoch index, next authorities, next randomness and next tickets
+			.saturating_add(T::DbWeight::get().reads(4))
+			.saturating_add(T::DbWeight::get().writes(1))
+	}
+}
+
+impl<T: Trait> RandomnessT<<T as frame_system::Trait>::Hash> for Module<T> {
+	/// The randomness of the current epoch, fixed for the whole epoch. Every
+	/// block producer contributes a VRF output to the randomness of future
+	/// epochs, but since slot owners are known in advance the randomness
+	/// can be biased by authorities choosing not to produce their blocks.
+	fn random(subject: &[u8]) -> T::Hash {
+		let mut subject = subject.to_vec();
+		subject.reserve(VRF_OUTPUT_LENGTH);
+		subject.extend_from_slice(&Self::randomness()[..]);
+
+		<T as frame_system::Trait>::Hashing::hash(&subject[..])
+	}
+}
+
+impl<T: Trait> FindAuthor<u32> for Module<T> {
+	fn find_author<'a, I>(digests: I) -> Option<u32> where
+		I: 'a + IntoIterator<Item=(ConsensusEngineId, &'a [u8])>
+	{
+		for (id, mut data) in digests.into_iter() {
+			if id == SASSAFRAS_ENGINE_ID {
+				let pre_digest: PreDigest = PreDigest::decode(&mut data).ok()?;
+				return Some(pre_digest.authority_index)
+			}
+		}
+
+		return None;
+	}
+}
+
+impl<T: Trait> IsMember<AuthorityId> for Module<T> {
+	fn is_member(authority_id: &AuthorityId) -> bool {
+		<Module<T>>::authorities()
+			.iter()
+			.any(|id| id == authority_id)
+	}
+}
+
+impl<T: Trait> pallet_session::ShouldEndSession<T::BlockNumber> for Module<T> {
+	fn should_end_session(now: T::BlockNumber) -> bool {
+		// session module might call `should_end_session` from its own
+		// `on_initialize` handler, before ours, let's ensure that we have
+		// synced with the digest before checking if session should be ended.
+		Self::do_initialize(now);
+
+		Self::should_epoch_change(now)
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Determine the Sassafras slot duration based on the Timestamp module configuration.
+	pub fn slot_duration() -> T::Moment {
+		// we double the minimum block-period so each author can always propose within
+		// the majority of their slot.
+		<T as pallet_timestamp::Trait>::MinimumPeriod::get().saturating_mul(2.into())
+	}
+
+	/// The Sassafras configuration, as exposed to the client.
+	pub fn configuration() -> SassafrasConfiguration {
+		SassafrasConfiguration {
+			slot_duration: Self::slot_duration().saturated_into(),
+			epoch_duration: T::EpochDuration::get(),
+			max_attempts: T::MaxAttempts::get(),
+			redundancy_factor: T::RedundancyFactor::get(),
+		}
+	}
+
+	/// Information about the current epoch.
+	pub fn current_epoch() -> Epoch {
+		Epoch {
+			epoch_index: EpochIndex::get(),
+			start_slot: Self::current_epoch_start(),
+			duration: T::EpochDuration::get(),
+			authorities: Self::authorities(),
+			randomness: Self::randomness(),
+		}
+	}
+
+	/// Information about the next epoch.
+	pub fn next_epoch() -> Epoch {
+		Epoch {
+			epoch_index: EpochIndex::get().saturating_add(1),
+			start_slot: Self::current_epoch_start().saturating_add(T::EpochDuration::get()),
+			duration: T::EpochDuration::get(),
+			authorities: Self::next_authorities(),
+			randomness: Self::next_randomness(),
+		}
+	}
+
+	/// Determine whether an epoch change should take place at this block.
+	/// Assumes that initialization has already taken place.
+	pub fn should_epoch_change(now: T::BlockNumber) -> bool {
+		// The epoch has technically ended during the passage of time
+		// between this block and the last, but we have to "end" the epoch now,
+		// since there is no earlier possible block we could have done it.
+		//
+		// The exception is for block 1: the genesis has slot 0, so we treat
+		// epoch 0 as having started at the slot of block 1.
+		now != One::one() && {
+			let diff = CurrentSlot::get().saturating_sub(Self::current_epoch_start());
+			diff >= T::EpochDuration::get()
+		}
+	}
+
+	/// Returns the ticket assigned to the given slot. Only slots of the current
+	/// and of the next epoch can have a ticket assigned.
+	pub fn slot_ticket(slot_number: SlotNumber) -> Option<Ticket> {
+		let epoch_duration = T::EpochDuration::get();
+		let current_epoch_start = Self::current_epoch_start();
+		let next_epoch_start = current_epoch_start.saturating_add(epoch_duration);
+
+		// before the first block, the genesis slot isn't known yet.
+		if GenesisSlot::get() == 0 || slot_number < current_epoch_start {
+			return None;
+		}
+
+		let (tickets, slot_index) = if slot_number < next_epoch_start {
+			(Tickets::get(), slot_number - current_epoch_start)
+		} else {
+			(NextTickets::get(), slot_number - next_epoch_start)
+		};
+
+		sp_consensus_sassafras::ticket_index(slot_index, tickets.len(), epoch_duration)
+			.and_then(|index| tickets.get(index).cloned())
+	}
+
+	/// DANGEROUS: Enact an epoch change. Should be done on every block where `should_epoch_change` has returned `true`,
+	/// and the caller is the only caller of this function.
+	///
+	/// Typically, this is not handled directly by the user, but by higher-level validator-set manager logic like
+	/// `pallet-session`.
+	pub fn enact_epoch_change(
+		authorities: Vec<AuthorityId>,
+		next_authorities: Vec<AuthorityId>,
+	) {
+		// PRECONDITION: caller has done initialization and is guaranteed
+		// by the session module to be called before this.
+		debug_assert!(Self::initialized().is_some());
+
+		// Update epoch index. Epochs without any block are skipped, the index
+		// of the new epoch is the one of the current slot.
+		let next_epoch_index = EpochIndex::get()
+			.checked_add(1)
+			.expect("epoch indices will never reach 2^64 before the death of the universe; qed");
+		let slot_epoch_index = CurrentSlot::get()
+			.saturating_sub(GenesisSlot::get())
+			.checked_div(T::EpochDuration::get())
+			.unwrap_or_default();
+		let epoch_index = next_epoch_index.max(slot_epoch_index);
+
+		EpochIndex::put(epoch_index);
+		Authorities::put(authorities);
+		NextAuthorities::put(&next_authorities);
+
+		// the tickets submitted during the last epoch become the slot schedule
+		// of the new one, unless that epoch has been skipped.
+		let next_tickets = NextTickets::take();
+		if epoch_index == next_epoch_index {
+			Tickets::put(next_tickets);
+		} else {
+			Tickets::kill();
+		}
+
+		// Update epoch randomness.
+		let next_epoch_index = epoch_index
+			.checked_add(1)
+			.expect("epoch indices will never reach 2^64 before the death of the universe; qed");
+
+		let randomness = NextRandomness::get();
+		let next_randomness = compute_randomness(
+			randomness,
+			next_epoch_index,
+			RandomnessAccumulator::take(),
+		);
+
+		Randomness::put(randomness);
+		NextRandomness::put(next_randomness);
+
+		// After we update the current epoch, we signal the *next* epoch change
+		// so that nodes can track changes.
+		Self::deposit_consensus(ConsensusLog::NextEpochData(NextEpochDescriptor {
+			authorities: next_authorities,
+			randomness: next_randomness,
+		}));
+	}
+
+	// finds the start slot of the current epoch. only guaranteed to
+	// give correct results after `do_initialize` of the first block
+	// in the chain (as its result is based off of `GenesisSlot`).
+	pub fn current_epoch_start() -> SlotNumber {
+		(EpochIndex::get() * T::EpochDuration::get()) + GenesisSlot::get()
+	}
+
+	/// Submits an extrinsic with tickets for the next epoch. This method will
+	/// create an unsigned extrinsic with a call to `submit_tickets` and will
+	/// push the transaction to the pool. Only useful in an offchain context.
+	pub fn submit_tickets_unsigned(tickets: Vec<TicketEnvelope>) -> bool {
+		use frame_system::offchain::SubmitTransaction;
+
+		let call = Call::submit_tickets(tickets);
+
+		match SubmitTransaction::<T, Call<T>>::submit_unsigned_transaction(call.into()) {
+			Ok(()) => true,
+			Err(e) => {
+				debug::error!("Error submitting Sassafras tickets: {:?}", e);
+				false
+			},
+		}
+	}
+
+	fn deposit_consensus<U: Encode>(new: U) {
+		let log: DigestItem<T::Hash> = DigestItem::Consensus(SASSAFRAS_ENGINE_ID, new.encode());
+		<frame_system::Module<T>>::deposit_log(log.into())
+	}
+
+	fn deposit_randomness(randomness: &schnorrkel::Randomness) {
+		RandomnessAccumulator::mutate(|accumulator| {
+			let mut s = Vec::with_capacity(2 * RANDOMNESS_LENGTH);
+			s.extend_from_slice(&accumulator[..]);
+			s.extend_from_slice(&randomness[..]);
+
+			*accumulator = sp_io::hashing::blake2_256(&s);
+		});
+	}
+
+	// the randomness contributed by the VRF output of the current block, it
+	// must be called after any epoch change has been enacted.
+	fn block_randomness(
+		authority_index: AuthorityIndex,
+		vrf_output: &schnorrkel::VRFOutput,
+	) -> Option<schnorrkel::Randomness> {
+		let author = Authorities::get().get(authority_index as usize)?.clone();
+		let pubkey = schnorrkel::PublicKey::from_bytes(author.as_slice()).ok()?;
+
+		let transcript = sp_consensus_sassafras::make_slot_transcript(
+			&Self::randomness(),
+			CurrentSlot::get(),
+			EpochIndex::get(),
+		);
+
+		vrf_output.0.attach_input_hash(&pubkey, transcript)
+			.ok()
+			.map(|inout| inout.make_bytes(&sp_consensus_sassafras::SASSAFRAS_VRF_INOUT_CONTEXT))
+	}
+
+	fn do_initialize(now: T::BlockNumber) {
+		// since do_initialize can be called twice (if session module is present)
+		// => let's ensure that we only modify the storage once per block
+		let initialized = Self::initialized().is_some();
+		if initialized {
+			return;
+		}
+
+		let maybe_pre_digest: Option<PreDigest> = <frame_system::Module<T>>::digest()
+			.logs
+			.iter()
+			.filter_map(|s| s.as_pre_runtime())
+			.filter_map(|(id, mut data)| if id == SASSAFRAS_ENGINE_ID {
+				PreDigest::decode(&mut data).ok()
+			} else {
+				None
+			})
+			.next();
+
+		let maybe_vrf_output = maybe_pre_digest.map(|digest| {
+			// on the first non-zero block (i.e. block #1)
+			// this is where the first epoch (epoch #0) actually starts.
+			// we need to adjust internal storage accordingly.
+			if GenesisSlot::get() == 0 {
+				GenesisSlot::put(digest.slot_number);
+				debug_assert_ne!(GenesisSlot::get(), 0);
+
+				// deposit a log because this is the first block in epoch #0
+				// we use the same values as genesis because we haven't collected any
+				// randomness yet.
+				let next = NextEpochDescriptor {
+					authorities: Self::authorities(),
+					randomness: Self::randomness(),
+				};
+
+				Self::deposit_consensus(ConsensusLog::NextEpochData(next))
+			}
+
+			CurrentSlot::put(digest.slot_number);
+
+			(digest.authority_index, digest.vrf_output)
+		});
+
+		Initialized::put(maybe_vrf_output);
+
+		// enact epoch change, if necessary.
+		T::EpochChangeTrigger::trigger::<T>(now)
+	}
+
+	/// Verifies a ticket submitted for the next epoch, returning the ticket
+	/// to store if it is valid and below the ticket threshold.
+	fn check_ticket(envelope: &TicketEnvelope) -> Option<Ticket> {
+		let max_attempts = T::MaxAttempts::get();
+		if envelope.attempt >= max_attempts {
+			return None;
+		}
+
+		let authorities = Self::next_authorities();
+		let authority = authorities.get(envelope.authority_index as usize)?;
+
+		let id = sp_consensus_sassafras::verify_ticket(
+			authority,
+			&Self::next_randomness(),
+			EpochIndex::get().saturating_add(1),
+			envelope,
+		)?;
+
+		let threshold = sp_consensus_sassafras::compute_ticket_threshold(
+			T::RedundancyFactor::get(),
+			T::EpochDuration::get(),
+			max_attempts,
+			authorities.len(),
+		);
+
+		if id < threshold {
+			Some(Ticket {
+				id,
+				authority_index: envelope.authority_index,
+				attempt: envelope.attempt,
+			})
+		} else {
+			None
+		}
+	}
+
+	// merges the given tickets into the next epoch tickets, only keeping the
+	// lowest ticket identifiers that fit in an epoch.
+	fn append_tickets(tickets: Vec<Ticket>) {
+		NextTickets::mutate(|next_tickets| {
+			next_tickets.extend(tickets);
+			next_tickets.sort_unstable_by_key(|ticket| ticket.id);
+			next_tickets.dedup_by_key(|ticket| ticket.id);
+			next_tickets.truncate(T::EpochDuration::get().saturated_into());
+		});
+	}
+
+	fn initialize_authorities(authorities: &[AuthorityId]) {
+		if !authorities.is_empty() {
+			assert!(Authorities::get().is_empty(), "Authorities are already initialized!");
+			Authorities::put(authorities);
+			NextAuthorities::put(authorities);
+		}
+	}
+}
+
+impl<T: Trait> OnTimestampSet<T::Moment> for Module<T> {
+	fn on_timestamp_set(_moment: T::Moment) { }
+}
+
+impl<T: Trait> sp_runtime::BoundToRuntimeAppPublic for Module<T> {
+	type Public = AuthorityId;
+}
+
+impl<T: Trait> pallet_session::OneSessionHandler<T::AccountId> for Module<T> {
+	type Key = AuthorityId;
+
+	fn on_genesis_session<'a, I: 'a>(validators: I)
+		where I: Iterator<Item=(&'a T::AccountId, AuthorityId)>
+	{
+		let authorities = validators.map(|(_, k)| k).collect::<Vec<_>>();
+		Self::initialize_authorities(&authorities);
+	}
+
+	fn on_new_session<'a, I: 'a>(_changed: bool, validators: I, queued_validators: I)
+		where I: Iterator<Item=(&'a T::AccountId, AuthorityId)>
+	{
+		let authorities = validators.map(|(_account, k)| k).collect::<Vec<_>>();
+		let next_authorities = queued_validators.map(|(_account, k)| k).collect::<Vec<_>>();
+
+		Self::enact_epoch_change(authorities, next_authorities)
+	}
+
+	fn on_disabled(i: usize) {
+		Self::deposit_consensus(ConsensusLog::OnDisabled(i as u32))
+	}
+}
+
+/// A `ValidateUnsigned` implementation that only accepts `submit_tickets`
+/// calls with valid tickets for the next epoch. Tickets are authenticated by
+/// their VRF proof, so they are accepted from any source and gossiped.
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+	type Call = Call<T>;
+	fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+		if let Call::submit_tickets(tickets) = call {
+			if tickets.is_empty() {
+				return InvalidTransaction::Call.into();
+			}
+
+			let mut ids = Vec::with_capacity(tickets.len());
+			for ticket in tickets {
+				match Self::check_ticket(ticket) {
+					Some(ticket) => ids.push(ticket.id),
+					None => return InvalidTransaction::BadProof.into(),
+				}
+			}
+
+			// tickets are only valid until the end of the current epoch.
+			let epoch_end = Self::current_epoch_start().saturating_add(T::EpochDuration::get());
+			let longevity = epoch_end.saturating_sub(CurrentSlot::get()).max(1);
+
+			ValidTransaction::with_tag_prefix("SassafrasTickets")
+				.priority(T::UnsignedPriority::get())
+				.and_provides((EpochIndex::get().saturating_add(1), ids))
+				.longevity(longevity)
+				.propagate(true)
+				.build()
+		} else {
+			InvalidTransaction::Call.into()
+		}
+	}
+}
+
+// compute randomness for a new epoch from the randomness of the previous
+// epoch and the accumulated VRF outputs of the blocks in the prior epoch.
+fn compute_randomness(
+	last_epoch_randomness: schnorrkel::Randomness,
+	epoch_index: u64,
+	accumulator: schnorrkel::Randomness,
+) -> schnorrkel::Randomness {
+	let mut s = Vec::with_capacity(8 + 2 * RANDOMNESS_LENGTH);
+	s.extend_from_slice(&last_epoch_randomness);
+	s.extend_from_slice(&epoch_index.to_le_bytes());
+	s.extend_from_slice(&accumulator);
+
+	sp_io::hashing::blake2_256(&s)
+}
+
+impl<T: Trait> ProvideInherent for Module<T> {
+	type Call = pallet_timestamp::Call<T>;
+	type Error = MakeFatalError<sp_inherents::Error>;
+	const INHERENT_IDENTIFIER: InherentIdentifier = INHERENT_IDENTIFIER;
+
+	fn create_inherent(_: &InherentData) -> Option<Self::Call> {
+		None
+	}
+
+	fn check_inherent(call: &Self::Call, data: &InherentData) -> result::Result<(), Self::Error> {
+		let timestamp = match call {
+			pallet_timestamp::Call::set(ref timestamp) => timestamp.clone(),
+			_ => return Ok(()),
+		};
+
+		let timestamp_based_slot = (timestamp / Self::slot_duration()).saturated_into::<u64>();
+		let seal_slot = data.sassafras_inherent_data()?;
+
+		if timestamp_based_slot == seal_slot {
+			Ok(())
+		} else {
+			Err(sp_inherents::Error::from("timestamp set in block doesn't match slot in seal").into())
+		}
+	}
+}
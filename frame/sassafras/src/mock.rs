@@ -0,0 +1,207 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test utilities
+
+use codec::Encode;
+use crate as sassafras;
+use super::{Trait, Module, GenesisConfig};
+use sp_runtime::{
+	Perbill,
+	testing::{Digest, DigestItem, Header, TestXt},
+	traits::IdentityLookup,
+};
+use frame_system::InitKind;
+use frame_support::{
+	impl_outer_dispatch, impl_outer_origin, parameter_types,
+	traits::{OnInitialize, OnFinalize},
+	weights::Weight,
+};
+use sp_io;
+use sp_core::{H256, U256, crypto::{IsWrappedBy, Pair}};
+use sp_consensus_sassafras::{
+	AuthorityIndex, AuthorityPair, SlotNumber, TicketEnvelope,
+	digests::PreDigest,
+};
+use sp_consensus_vrf::schnorrkel::{VRFOutput, VRFProof};
+
+impl_outer_origin!{
+	pub enum Origin for Test where system = frame_system {}
+}
+
+impl_outer_dispatch! {
+	pub enum Call for Test where origin: Origin {
+		sassafras::Sassafras,
+	}
+}
+
+// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Test;
+
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+	pub const MaximumBlockWeight: Weight = 1024;
+	pub const MaximumBlockLength: u32 = 2 * 1024;
+	pub const AvailableBlockRatio: Perbill = Perbill::one();
+	pub const MinimumPeriod: u64 = 1;
+	pub const EpochDuration: u64 = 6;
+	pub const MaxAttempts: u32 = 4;
+	pub const RedundancyFactor: u32 = 1;
+	pub const UnsignedPriority: u64 = 1 << 20;
+}
+
+impl frame_system::Trait for Test {
+	type BaseCallFilter = ();
+	type Origin = Origin;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Call = Call;
+	type Hash = H256;
+	type Version = ();
+	type Hashing = sp_runtime::traits::BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type Event = ();
+	type BlockHashCount = BlockHashCount;
+	type MaximumBlockWeight = MaximumBlockWeight;
+	type DbWeight = ();
+	type BlockExecutionWeight = ();
+	type ExtrinsicBaseWeight = ();
+	type MaximumExtrinsicWeight = MaximumBlockWeight;
+	type AvailableBlockRatio = AvailableBlockRatio;
+	type MaximumBlockLength = MaximumBlockLength;
+	type ModuleToIndex = ();
+	type AccountData = ();
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type SystemWeightInfo = ();
+}
+
+impl<C> frame_system::offchain::SendTransactionTypes<C> for Test
+where
+	Call: From<C>,
+{
+	type OverarchingCall = Call;
+	type Extrinsic = TestXt<Call, ()>;
+}
+
+impl pallet_timestamp::Trait for Test {
+	type Moment = u64;
+	type OnTimestampSet = Sassafras;
+	type MinimumPeriod = MinimumPeriod;
+	type WeightInfo = ();
+}
+
+impl Trait for Test {
+	type EpochDuration = EpochDuration;
+	type MaxAttempts = MaxAttempts;
+	type RedundancyFactor = RedundancyFactor;
+	type UnsignedPriority = UnsignedPriority;
+	type EpochChangeTrigger = super::SameAuthoritiesForever;
+}
+
+pub type System = frame_system::Module<Test>;
+pub type Sassafras = Module<Test>;
+
+pub fn new_test_ext(authorities_len: usize) -> (Vec<AuthorityPair>, sp_io::TestExternalities) {
+	let pairs = (0..authorities_len).map(|i| {
+		AuthorityPair::from_seed(&U256::from(i).into())
+	}).collect::<Vec<_>>();
+
+	let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	GenesisConfig {
+		authorities: pairs.iter().map(|p| p.public()).collect(),
+	}.assimilate_storage::<Test>(&mut t).unwrap();
+
+	(pairs, t.into())
+}
+
+/// Signs the VRF of the block authored by `pair` at the given slot.
+pub fn make_pre_digest(
+	pair: &AuthorityPair,
+	authority_index: AuthorityIndex,
+	slot_number: SlotNumber,
+	ticket_attempt: Option<u32>,
+) -> Digest {
+	let transcript = sp_consensus_sassafras::make_slot_transcript(
+		&Sassafras::randomness(),
+		slot_number,
+		Sassafras::epoch_index(),
+	);
+	let (inout, proof, _) = sp_core::sr25519::Pair::from_ref(pair).as_ref().vrf_sign(transcript);
+
+	let pre_digest = PreDigest {
+		authority_index,
+		slot_number,
+		ticket_attempt,
+		vrf_output: VRFOutput(inout.to_output()),
+		vrf_proof: VRFProof(proof),
+	};
+	let log = DigestItem::PreRuntime(sp_consensus_sassafras::SASSAFRAS_ENGINE_ID, pre_digest.encode());
+	Digest { logs: vec![log] }
+}
+
+/// Generates the ticket of `pair` for the next epoch at the given attempt.
+pub fn make_ticket(
+	pair: &AuthorityPair,
+	authority_index: AuthorityIndex,
+	attempt: u32,
+) -> TicketEnvelope {
+	let transcript = sp_consensus_sassafras::make_ticket_transcript(
+		&Sassafras::next_randomness(),
+		attempt,
+		Sassafras::epoch_index() + 1,
+	);
+	let (inout, proof, _) = sp_core::sr25519::Pair::from_ref(pair).as_ref().vrf_sign(transcript);
+
+	TicketEnvelope {
+		authority_index,
+		attempt,
+		vrf_output: VRFOutput(inout.to_output()),
+		vrf_proof: VRFProof(proof),
+	}
+}
+
+/// Imports block `n` at slot `s`, authored by the first authority.
+pub fn go_to_block(pairs: &[AuthorityPair], n: u64, s: u64) {
+	Sassafras::on_finalize(System::block_number());
+
+	let parent_hash = if System::block_number() > 1 {
+		let hdr = System::finalize();
+		hdr.hash()
+	} else {
+		System::parent_hash()
+	};
+
+	let pre_digest = make_pre_digest(&pairs[0], 0, s, None);
+
+	System::initialize(&n, &parent_hash, &Default::default(), &pre_digest, InitKind::Full);
+	System::set_block_number(n);
+
+	Sassafras::on_initialize(n);
+}
+
+/// Slots will grow accordingly to blocks
+pub fn progress_to_block(pairs: &[AuthorityPair], n: u64) {
+	let mut slot = Sassafras::current_slot() + 1;
+	for i in System::block_number()+1..=n {
+		go_to_block(pairs, i, slot);
+		slot += 1;
+	}
+}
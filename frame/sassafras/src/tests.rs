@@ -0,0 +1,245 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Consensus extension module tests for Sassafras consensus.
+
+use super::{Call, *};
+use frame_support::{assert_noop, assert_ok, traits::OnFinalize, unsigned::ValidateUnsigned};
+use mock::*;
+use pallet_session::ShouldEndSession;
+use sp_consensus_sassafras::AuthorityPair;
+
+// generates the tickets of all the given authorities for the next epoch,
+// split into winning and losing tickets.
+fn make_tickets(pairs: &[AuthorityPair]) -> (Vec<TicketEnvelope>, Vec<TicketEnvelope>) {
+	let mut tickets = Vec::new();
+	for (authority_index, pair) in pairs.iter().enumerate() {
+		for attempt in 0..MaxAttempts::get() {
+			tickets.push(make_ticket(pair, authority_index as u32, attempt));
+		}
+	}
+
+	tickets.into_iter().partition(|ticket| Sassafras::check_ticket(ticket).is_some())
+}
+
+#[test]
+fn initial_values() {
+	let (_, mut ext) = new_test_ext(4);
+
+	ext.execute_with(|| {
+		assert_eq!(Sassafras::authorities().len(), 4);
+		assert_eq!(Sassafras::next_authorities(), Sassafras::authorities());
+		assert!(Sassafras::tickets().is_empty());
+		assert!(Sassafras::next_tickets().is_empty());
+	})
+}
+
+#[test]
+fn first_block_epoch_zero_start() {
+	let (pairs, mut ext) = new_test_ext(4);
+
+	ext.execute_with(|| {
+		let genesis_slot = 100;
+
+		assert_eq!(Sassafras::genesis_slot(), 0);
+		go_to_block(&pairs, 1, genesis_slot);
+
+		assert!(!Sassafras::should_end_session(1));
+		assert_eq!(Sassafras::genesis_slot(), genesis_slot);
+		assert_eq!(Sassafras::current_slot(), genesis_slot);
+		assert_eq!(Sassafras::epoch_index(), 0);
+		assert_eq!(Sassafras::current_epoch().start_slot, genesis_slot);
+		assert_eq!(Sassafras::next_epoch().start_slot, genesis_slot + EpochDuration::get());
+
+		Sassafras::on_finalize(1);
+		let header = System::finalize();
+
+		// the VRF output of the block has been accumulated.
+		assert_ne!(Sassafras::randomness_accumulator(), [0; 32]);
+
+		let consensus_log = sp_consensus_sassafras::ConsensusLog::NextEpochData(
+			sp_consensus_sassafras::digests::NextEpochDescriptor {
+				authorities: Sassafras::authorities(),
+				randomness: Sassafras::randomness(),
+			}
+		);
+		let consensus_digest = DigestItem::Consensus(SASSAFRAS_ENGINE_ID, consensus_log.encode());
+
+		assert_eq!(header.digest.logs.len(), 2);
+		assert_eq!(header.digest.logs[1], consensus_digest)
+	})
+}
+
+#[test]
+fn submitted_tickets_are_sorted_and_truncated() {
+	let (pairs, mut ext) = new_test_ext(3);
+
+	ext.execute_with(|| {
+		go_to_block(&pairs, 1, 100);
+
+		let (winners, _) = make_tickets(&pairs);
+		assert!(!winners.is_empty());
+
+		// submit the winning tickets in two batches, including duplicates.
+		let (first, second) = winners.split_at(winners.len() / 2);
+		assert_ok!(Sassafras::submit_tickets(Origin::none(), first.to_vec()));
+		assert_ok!(Sassafras::submit_tickets(Origin::none(), second.to_vec()));
+		assert_ok!(Sassafras::submit_tickets(Origin::none(), first.to_vec()));
+
+		let next_tickets = Sassafras::next_tickets();
+		assert_eq!(next_tickets.len(), winners.len().min(EpochDuration::get() as usize));
+
+		let mut expected = winners.iter()
+			.filter_map(|ticket| Sassafras::check_ticket(ticket))
+			.collect::<Vec<_>>();
+		expected.sort_by_key(|ticket| ticket.id);
+		expected.truncate(EpochDuration::get() as usize);
+
+		assert_eq!(next_tickets, expected);
+	})
+}
+
+#[test]
+fn invalid_tickets_are_rejected() {
+	let (pairs, mut ext) = new_test_ext(3);
+
+	ext.execute_with(|| {
+		go_to_block(&pairs, 1, 100);
+
+		let (winners, losers) = make_tickets(&pairs);
+		assert!(!winners.is_empty());
+		assert!(!losers.is_empty());
+
+		// ticket above the threshold.
+		assert_noop!(
+			Sassafras::submit_tickets(Origin::none(), vec![losers[0].clone()]),
+			Error::<Test>::InvalidTicket,
+		);
+
+		// ticket claimed by another authority.
+		let mut ticket = winners[0].clone();
+		ticket.authority_index = (ticket.authority_index + 1) % 3;
+		assert_noop!(
+			Sassafras::submit_tickets(Origin::none(), vec![ticket]),
+			Error::<Test>::InvalidTicket,
+		);
+
+		// ticket generated for too many attempts.
+		let ticket = make_ticket(&pairs[0], 0, MaxAttempts::get());
+		assert_noop!(
+			Sassafras::submit_tickets(Origin::none(), vec![ticket.clone()]),
+			Error::<Test>::InvalidTicket,
+		);
+
+		// the transaction pool rejects invalid and empty submissions.
+		assert!(
+			<Sassafras as ValidateUnsigned>::validate_unsigned(
+				TransactionSource::External,
+				&Call::submit_tickets(vec![ticket]),
+			).is_err()
+		);
+		assert!(
+			<Sassafras as ValidateUnsigned>::validate_unsigned(
+				TransactionSource::External,
+				&Call::submit_tickets(vec![]),
+			).is_err()
+		);
+		let valid = <Sassafras as ValidateUnsigned>::validate_unsigned(
+			TransactionSource::External,
+			&Call::submit_tickets(winners),
+		).expect("winning tickets are valid");
+
+		// tickets are only valid until the end of the current epoch.
+		assert_eq!(valid.priority, UnsignedPriority::get());
+		assert_eq!(valid.longevity, EpochDuration::get());
+	})
+}
+
+#[test]
+fn tickets_become_the_next_epoch_schedule() {
+	let (pairs, mut ext) = new_test_ext(3);
+
+	ext.execute_with(|| {
+		let genesis_slot = 100;
+		go_to_block(&pairs, 1, genesis_slot);
+
+		let (winners, _) = make_tickets(&pairs);
+		assert_ok!(Sassafras::submit_tickets(Origin::none(), winners));
+
+		let next_tickets = Sassafras::next_tickets();
+		let next_epoch_start = genesis_slot + EpochDuration::get();
+
+		// no tickets for the current epoch.
+		assert_eq!(Sassafras::slot_ticket(genesis_slot + 1), None);
+		// before the epoch change, the next epoch slots are already assigned.
+		assert_eq!(Sassafras::slot_ticket(next_epoch_start), next_tickets.first().cloned());
+
+		let next_randomness = Sassafras::next_randomness();
+
+		progress_to_block(&pairs, 7);
+		assert_eq!(Sassafras::current_slot(), next_epoch_start);
+		assert_eq!(Sassafras::epoch_index(), 1);
+		assert_eq!(Sassafras::randomness(), next_randomness);
+		assert_ne!(Sassafras::next_randomness(), next_randomness);
+		assert_eq!(Sassafras::tickets(), next_tickets);
+		assert!(Sassafras::next_tickets().is_empty());
+
+		for slot_index in 0..EpochDuration::get() {
+			let expected = sp_consensus_sassafras::ticket_index(
+				slot_index,
+				next_tickets.len(),
+				EpochDuration::get(),
+			).map(|index| next_tickets[index]);
+
+			assert_eq!(Sassafras::slot_ticket(next_epoch_start + slot_index), expected);
+		}
+
+		// slots beyond the next epoch are never assigned.
+		assert_eq!(Sassafras::slot_ticket(next_epoch_start + 2 * EpochDuration::get()), None);
+	})
+}
+
+#[test]
+fn epochs_without_blocks_are_skipped() {
+	let (pairs, mut ext) = new_test_ext(3);
+
+	ext.execute_with(|| {
+		let genesis_slot = 100;
+		go_to_block(&pairs, 1, genesis_slot);
+
+		let (winners, _) = make_tickets(&pairs);
+		assert_ok!(Sassafras::submit_tickets(Origin::none(), winners));
+		assert!(!Sassafras::next_tickets().is_empty());
+
+		let next_randomness = Sassafras::next_randomness();
+
+		// the next block is authored in epoch 2, no block was authored in epoch 1.
+		let epoch_start = genesis_slot + 2 * EpochDuration::get();
+		go_to_block(&pairs, 2, epoch_start + 1);
+
+		assert_eq!(Sassafras::epoch_index(), 2);
+		assert_eq!(Sassafras::current_epoch().start_slot, epoch_start);
+		assert_eq!(Sassafras::next_epoch().epoch_index, 3);
+		assert_eq!(Sassafras::next_epoch().start_slot, epoch_start + EpochDuration::get());
+		assert_eq!(Sassafras::randomness(), next_randomness);
+
+		// the tickets submitted for the skipped epoch are discarded.
+		assert!(Sassafras::tickets().is_empty());
+		assert!(Sassafras::next_tickets().is_empty());
+		assert_eq!(Sassafras::slot_ticket(epoch_start + 1), None);
+	})
+}
@@ -45,9 +45,13 @@ sp_api::decl_runtime_apis! {
 	/// The authority discovery api.
 	///
 	/// This api is used by the `client/authority-discovery` module to retrieve identifiers
-	/// of the current authority set.
+	/// of the current and of the next authority set.
+	#[api_version(2)]
 	pub trait AuthorityDiscoveryApi {
 		/// Retrieve authority identifiers of the current authority set.
 		fn authorities() -> Vec<AuthorityId>;
+
+		/// Retrieve authority identifiers of the authority set of the next session.
+		fn next_authorities() -> Vec<AuthorityId>;
 	}
 }
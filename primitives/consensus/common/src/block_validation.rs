@@ -17,8 +17,10 @@
 //! Block announcement validation.
 
 use crate::BlockStatus;
+use codec::Decode;
+use futures::FutureExt as _;
 use sp_runtime::{generic::BlockId, traits::Block};
-use std::{error::Error, sync::Arc};
+use std::{error::Error, future::Future, pin::Pin, sync::Arc};
 
 /// A type which provides access to chain information.
 pub trait Chain<B: Block> {
@@ -41,13 +43,29 @@ pub enum Validation {
 		is_new_best: bool,
 	},
 	/// Invalid block announcement.
-	Failure,
+	Failure {
+		/// Should we disconnect from this peer?
+		///
+		/// This should be used if the peer for example sent junk to spam us.
+		disconnect: bool,
+	},
 }
 
+/// Future returned by `BlockAnnounceValidator::validate`.
+pub type ValidationFuture = Pin<Box<dyn Future<Output = Result<Validation, Box<dyn Error + Send>>> + Send>>;
+
 /// Type which checks incoming block announcements.
 pub trait BlockAnnounceValidator<B: Block> {
 	/// Validate the announced header and its associated data.
-	fn validate(&mut self, header: &B::Header, data: &[u8]) -> Result<Validation, Box<dyn Error + Send>>;
+	///
+	/// The validation is asynchronous, which lets the validator fetch any additional data it
+	/// needs before accepting the announcement. Other announcements keep being processed in the
+	/// meantime.
+	///
+	/// # Note
+	///
+	/// Returning [`Validation::Failure`] will lead to a decrease of the peer's reputation.
+	fn validate(&mut self, header: &B::Header, data: &[u8]) -> ValidationFuture;
 }
 
 /// Default implementation of `BlockAnnounceValidator`.
@@ -55,7 +73,141 @@ pub trait BlockAnnounceValidator<B: Block> {
 pub struct DefaultBlockAnnounceValidator;
 
 impl<B: Block> BlockAnnounceValidator<B> for DefaultBlockAnnounceValidator {
-	fn validate(&mut self, _h: &B::Header, _d: &[u8]) -> Result<Validation, Box<dyn Error + Send>> {
-		Ok(Validation::Success { is_new_best: false })
+	fn validate(&mut self, _h: &B::Header, _d: &[u8]) -> ValidationFuture {
+		async { Ok(Validation::Success { is_new_best: false }) }.boxed()
+	}
+}
+
+/// Builder of a [`BlockAnnounceValidator`] for chains that attach a SCALE-encoded statement to
+/// their block announcements, e.g. a statement signed by the author of the block.
+pub struct StatementValidatorBuilder<B: Block, S> {
+	check: Box<dyn FnMut(&B::Header, S) -> ValidationFuture + Send>,
+	allow_missing_statement: bool,
+	disconnect_on_invalid_encoding: bool,
+}
+
+impl<B: Block, S: Decode + 'static> StatementValidatorBuilder<B, S> {
+	/// Creates a new builder.
+	///
+	/// `check` is called with the announced header and the decoded statement, and is responsible
+	/// for verifying the statement, e.g. its signature and that it refers to the header.
+	pub fn new<F, Fut>(mut check: F) -> Self
+	where
+		F: FnMut(&B::Header, S) -> Fut + Send + 'static,
+		Fut: Future<Output = Result<Validation, Box<dyn Error + Send>>> + Send + 'static,
+	{
+		StatementValidatorBuilder {
+			check: Box::new(move |header, statement| check(header, statement).boxed()),
+			allow_missing_statement: false,
+			disconnect_on_invalid_encoding: true,
+		}
+	}
+
+	/// Whether announcements without any statement are accepted. Defaults to `false`.
+	pub fn allow_missing_statement(mut self, allow: bool) -> Self {
+		self.allow_missing_statement = allow;
+		self
+	}
+
+	/// Whether to disconnect from peers sending statements that fail to decode. Defaults to
+	/// `true`.
+	pub fn disconnect_on_invalid_encoding(mut self, disconnect: bool) -> Self {
+		self.disconnect_on_invalid_encoding = disconnect;
+		self
+	}
+
+	/// Builds the validator.
+	pub fn build(self) -> StatementValidator<B, S> {
+		StatementValidator { inner: self }
+	}
+}
+
+/// [`BlockAnnounceValidator`] built with a [`StatementValidatorBuilder`].
+pub struct StatementValidator<B: Block, S> {
+	inner: StatementValidatorBuilder<B, S>,
+}
+
+impl<B: Block, S: Decode + 'static> BlockAnnounceValidator<B> for StatementValidator<B, S> {
+	fn validate(&mut self, header: &B::Header, data: &[u8]) -> ValidationFuture {
+		if data.is_empty() {
+			let validation = if self.inner.allow_missing_statement {
+				Validation::Success { is_new_best: false }
+			} else {
+				Validation::Failure { disconnect: false }
+			};
+			return async move { Ok(validation) }.boxed()
+		}
+
+		match S::decode(&mut &data[..]) {
+			Ok(statement) => (self.inner.check)(header, statement),
+			Err(_) => {
+				let disconnect = self.inner.disconnect_on_invalid_encoding;
+				async move { Ok(Validation::Failure { disconnect }) }.boxed()
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+	use futures::executor::block_on;
+	use sp_test_primitives::{Block, Header};
+
+	fn header(number: u64) -> Header {
+		Header {
+			parent_hash: Default::default(),
+			number,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		}
+	}
+
+	fn validator(allow_missing_statement: bool) -> StatementValidator<Block, u64> {
+		// The statement must be the number of the announced block.
+		StatementValidatorBuilder::<Block, u64>::new(|header: &Header, statement: u64| {
+			let valid = header.number == statement;
+			async move {
+				Ok(if valid {
+					Validation::Success { is_new_best: true }
+				} else {
+					Validation::Failure { disconnect: false }
+				})
+			}
+		})
+			.allow_missing_statement(allow_missing_statement)
+			.build()
+	}
+
+	#[test]
+	fn statement_validator_checks_decoded_statement() {
+		let mut validator = validator(false);
+
+		assert_eq!(
+			block_on(validator.validate(&header(5), &5u64.encode())).unwrap(),
+			Validation::Success { is_new_best: true },
+		);
+		assert_eq!(
+			block_on(validator.validate(&header(5), &6u64.encode())).unwrap(),
+			Validation::Failure { disconnect: false },
+		);
+		assert_eq!(
+			block_on(validator.validate(&header(5), &[1, 2])).unwrap(),
+			Validation::Failure { disconnect: true },
+		);
+	}
+
+	#[test]
+	fn statement_validator_handles_missing_statement() {
+		assert_eq!(
+			block_on(validator(false).validate(&header(5), &[])).unwrap(),
+			Validation::Failure { disconnect: false },
+		);
+		assert_eq!(
+			block_on(validator(true).validate(&header(5), &[])).unwrap(),
+			Validation::Success { is_new_best: false },
+		);
 	}
 }
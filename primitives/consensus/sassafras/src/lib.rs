@@ -0,0 +1,368 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2020 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Primitives for Sassafras.
+//!
+//! Sassafras is an experimental constant-time block production engine.
+//! Authorities submit VRF tickets on-chain one epoch ahead, the runtime sorts
+//! the winning tickets into a slot schedule and each slot is claimed by the
+//! owner of the ticket assigned to it. Slots without a ticket fall back to a
+//! round-robin author.
+//!
+//! NOTE: the tickets are meant to be generated with a ring-VRF, so that the
+//! ticket owner stays anonymous until the slot is claimed. Ring-VRFs are not
+//! available yet, tickets are currently plain schnorrkel VRF outputs which
+//! are linked to the submitting authority, i.e. slot owners are NOT anonymous.
+#![deny(warnings)]
+#![forbid(unsafe_code, missing_docs, unused_variables, unused_imports)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub mod digests;
+pub mod inherents;
+
+pub use merlin::Transcript;
+pub use sp_consensus_vrf::schnorrkel::{
+	Randomness, VRFOutput, VRFProof, RANDOMNESS_LENGTH, VRF_OUTPUT_LENGTH, VRF_PROOF_LENGTH,
+};
+
+use codec::{Decode, Encode};
+#[cfg(feature = "std")]
+use sp_core::vrf::{VRFTranscriptData, VRFTranscriptValue};
+use sp_application_crypto::Public;
+use sp_runtime::{ConsensusEngineId, RuntimeDebug};
+use sp_std::vec::Vec;
+
+/// Key type for Sassafras module.
+pub const KEY_TYPE: sp_core::crypto::KeyTypeId = sp_application_crypto::key_types::SASSAFRAS;
+
+mod app {
+	use sp_application_crypto::{app_crypto, key_types::SASSAFRAS, sr25519};
+	app_crypto!(sr25519, SASSAFRAS);
+}
+
+/// The prefix used by Sassafras to compute ticket identifiers from VRF outputs.
+pub const SASSAFRAS_TICKET_VRF_PREFIX: &[u8] = b"substrate-sassafras-ticket-vrf";
+
+/// Sassafras VRFInOut context, used to derive randomness from block VRF outputs.
+pub static SASSAFRAS_VRF_INOUT_CONTEXT: &[u8] = b"SassafrasVRFInOutContext";
+
+/// A Sassafras authority keypair.
+#[cfg(feature = "std")]
+pub type AuthorityPair = app::Pair;
+
+/// A Sassafras authority signature.
+pub type AuthoritySignature = app::Signature;
+
+/// A Sassafras authority identifier.
+pub type AuthorityId = app::Public;
+
+/// The `ConsensusEngineId` of Sassafras.
+pub const SASSAFRAS_ENGINE_ID: ConsensusEngineId = *b"SASS";
+
+/// The index of an authority.
+pub type AuthorityIndex = u32;
+
+/// A slot number.
+pub use sp_consensus_slots::SlotNumber;
+
+/// The identifier of a ticket, i.e. the first 128 bits of its VRF output.
+/// Tickets are sorted by their identifier, lowest first.
+pub type TicketId = u128;
+
+/// A ticket as stored by the runtime, after its VRF proof has been verified.
+#[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct Ticket {
+	/// The ticket identifier.
+	pub id: TicketId,
+	/// The index of the authority owning the ticket in the epoch authorities.
+	pub authority_index: AuthorityIndex,
+	/// The attempt that generated the ticket.
+	pub attempt: u32,
+}
+
+/// A ticket as submitted on-chain by an authority.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct TicketEnvelope {
+	/// The index of the authority submitting the ticket in the authorities of
+	/// the epoch the ticket is for.
+	pub authority_index: AuthorityIndex,
+	/// The attempt that generated the ticket.
+	pub attempt: u32,
+	/// The VRF output.
+	pub vrf_output: VRFOutput,
+	/// The VRF proof.
+	pub vrf_proof: VRFProof,
+}
+
+/// Information about an epoch, as exposed by the runtime.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct Epoch {
+	/// The epoch index.
+	pub epoch_index: u64,
+	/// The starting slot of the epoch.
+	pub start_slot: SlotNumber,
+	/// The duration of this epoch in slots.
+	pub duration: SlotNumber,
+	/// The authorities of the epoch.
+	pub authorities: Vec<AuthorityId>,
+	/// The randomness of the epoch.
+	pub randomness: Randomness,
+}
+
+impl Epoch {
+	/// The first slot after this epoch.
+	pub fn end_slot(&self) -> SlotNumber {
+		self.start_slot.saturating_add(self.duration)
+	}
+
+	/// The round-robin author of the given slot, used for slots without a
+	/// ticket assigned.
+	pub fn fallback_author(&self, slot_number: SlotNumber) -> Option<AuthorityIndex> {
+		if self.authorities.is_empty() {
+			return None;
+		}
+
+		Some((slot_number % self.authorities.len() as u64) as AuthorityIndex)
+	}
+}
+
+/// Configuration data used by the Sassafras consensus engine.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct SassafrasConfiguration {
+	/// The slot duration in milliseconds for Sassafras.
+	pub slot_duration: u64,
+
+	/// The duration of epochs in slots.
+	pub epoch_duration: SlotNumber,
+
+	/// The maximum number of tickets each authority tries to generate per epoch.
+	pub max_attempts: u32,
+
+	/// The expected number of winning tickets per slot. The ticket threshold is
+	/// set so that, on average, `redundancy_factor * epoch_duration` tickets are
+	/// generated per epoch.
+	pub redundancy_factor: u32,
+}
+
+#[cfg(feature = "std")]
+impl sp_consensus::SlotData for SassafrasConfiguration {
+	fn slot_duration(&self) -> u64 {
+		self.slot_duration
+	}
+
+	const SLOT_KEY: &'static [u8] = b"sassafras_configuration";
+}
+
+/// An consensus log item for Sassafras.
+#[derive(Decode, Encode, Clone, PartialEq, Eq)]
+pub enum ConsensusLog {
+	/// The epoch has changed. This provides information about the _next_
+	/// epoch.
+	#[codec(index = "1")]
+	NextEpochData(digests::NextEpochDescriptor),
+	/// Disable the authority with given index.
+	#[codec(index = "2")]
+	OnDisabled(AuthorityIndex),
+}
+
+/// Make a VRF transcript for the ticket generated at `attempt` for the epoch
+/// with the given randomness and index.
+pub fn make_ticket_transcript(
+	randomness: &Randomness,
+	attempt: u32,
+	epoch: u64,
+) -> Transcript {
+	let mut transcript = Transcript::new(&SASSAFRAS_ENGINE_ID);
+	transcript.append_message(b"type", b"ticket");
+	transcript.append_u64(b"attempt", attempt as u64);
+	transcript.append_u64(b"epoch", epoch);
+	transcript.append_message(b"chain randomness", &randomness[..]);
+	transcript
+}
+
+/// Make a VRF transcript data container for a ticket.
+#[cfg(feature = "std")]
+pub fn make_ticket_transcript_data(
+	randomness: &Randomness,
+	attempt: u32,
+	epoch: u64,
+) -> VRFTranscriptData {
+	VRFTranscriptData {
+		label: &SASSAFRAS_ENGINE_ID,
+		items: vec![
+			("type", VRFTranscriptValue::Bytes(b"ticket")),
+			("attempt", VRFTranscriptValue::U64(attempt as u64)),
+			("epoch", VRFTranscriptValue::U64(epoch)),
+			("chain randomness", VRFTranscriptValue::Bytes(&randomness[..])),
+		]
+	}
+}
+
+/// Make a VRF transcript for the block authored at the given slot. The block
+/// VRF outputs are used to build the randomness of future epochs.
+pub fn make_slot_transcript(
+	randomness: &Randomness,
+	slot_number: u64,
+	epoch: u64,
+) -> Transcript {
+	let mut transcript = Transcript::new(&SASSAFRAS_ENGINE_ID);
+	transcript.append_message(b"type", b"slot");
+	transcript.append_u64(b"slot number", slot_number);
+	transcript.append_u64(b"epoch", epoch);
+	transcript.append_message(b"chain randomness", &randomness[..]);
+	transcript
+}
+
+/// Make a VRF transcript data container for the block authored at the given slot.
+#[cfg(feature = "std")]
+pub fn make_slot_transcript_data(
+	randomness: &Randomness,
+	slot_number: u64,
+	epoch: u64,
+) -> VRFTranscriptData {
+	VRFTranscriptData {
+		label: &SASSAFRAS_ENGINE_ID,
+		items: vec![
+			("type", VRFTranscriptValue::Bytes(b"slot")),
+			("slot number", VRFTranscriptValue::U64(slot_number)),
+			("epoch", VRFTranscriptValue::U64(epoch)),
+			("chain randomness", VRFTranscriptValue::Bytes(&randomness[..])),
+		]
+	}
+}
+
+/// Computes the threshold under which a ticket identifier is a winning
+/// ticket. The threshold is chosen so that the expected number of winning
+/// tickets is `redundancy_factor * epoch_duration`, with each of the
+/// `authorities_len` authorities making `max_attempts` attempts.
+pub fn compute_ticket_threshold(
+	redundancy_factor: u32,
+	epoch_duration: SlotNumber,
+	max_attempts: u32,
+	authorities_len: usize,
+) -> TicketId {
+	let expected = (redundancy_factor as u128).saturating_mul(epoch_duration as u128);
+	let generated = (max_attempts as u128).saturating_mul(authorities_len as u128);
+
+	if generated == 0 {
+		return 0;
+	}
+
+	if expected >= generated {
+		TicketId::max_value()
+	} else {
+		(TicketId::max_value() / generated).saturating_mul(expected)
+	}
+}
+
+/// Verifies the VRF proof of the given ticket against the authority that
+/// submitted it and returns the ticket identifier, or `None` if the proof is
+/// invalid.
+pub fn verify_ticket(
+	authority: &AuthorityId,
+	randomness: &Randomness,
+	epoch: u64,
+	ticket: &TicketEnvelope,
+) -> Option<TicketId> {
+	let public = sp_consensus_vrf::schnorrkel::PublicKey::from_bytes(authority.as_slice()).ok()?;
+	let transcript = make_ticket_transcript(randomness, ticket.attempt, epoch);
+
+	let (inout, _) = public.vrf_verify(transcript, &ticket.vrf_output, &ticket.vrf_proof).ok()?;
+
+	Some(TicketId::from_le_bytes(inout.make_bytes::<[u8; 16]>(SASSAFRAS_TICKET_VRF_PREFIX)))
+}
+
+/// Returns the position, in the list of sorted winning tickets, of the ticket
+/// assigned to the slot at `slot_index` within the epoch.
+///
+/// Tickets are assigned outside-in: the lowest ticket gets the first slot, the
+/// second lowest the last slot, the third lowest the second slot and so on.
+/// If there are fewer tickets than slots, the slots left in the middle of the
+/// epoch don't have a ticket assigned.
+pub fn ticket_index(
+	slot_index: SlotNumber,
+	tickets_len: usize,
+	epoch_duration: SlotNumber,
+) -> Option<usize> {
+	if slot_index >= epoch_duration {
+		return None;
+	}
+
+	let tickets_len = (tickets_len as u64).min(epoch_duration);
+	let front = (tickets_len + 1) / 2;
+	let back = tickets_len / 2;
+
+	if slot_index < front {
+		Some((2 * slot_index) as usize)
+	} else if slot_index >= epoch_duration - back {
+		Some((2 * (epoch_duration - 1 - slot_index) + 1) as usize)
+	} else {
+		None
+	}
+}
+
+sp_api::decl_runtime_apis! {
+	/// API necessary for block authorship with Sassafras.
+	pub trait SassafrasApi {
+		/// Return the configuration for Sassafras.
+		fn configuration() -> SassafrasConfiguration;
+
+		/// Returns information about the current epoch.
+		fn current_epoch() -> Epoch;
+
+		/// Returns information about the next epoch.
+		fn next_epoch() -> Epoch;
+
+		/// Returns the ticket assigned to the given slot, if any. Only slots of
+		/// the current and of the next epoch can have a ticket assigned, for
+		/// any other slot (and for slots without a ticket) the slot is claimed
+		/// by the round-robin fallback author.
+		fn slot_ticket(slot_number: SlotNumber) -> Option<Ticket>;
+
+		/// Submits an unsigned extrinsic with tickets for the next epoch.
+		/// Returns `false` if the extrinsic couldn't be created, e.g. if the
+		/// runtime doesn't accept tickets. Only useful in an offchain context.
+		fn submit_tickets_unsigned_extrinsic(tickets: Vec<TicketEnvelope>) -> bool;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn tickets_are_assigned_outside_in() {
+		let schedule = |tickets_len| (0..6)
+			.map(|slot_index| ticket_index(slot_index, tickets_len, 6))
+			.collect::<Vec<_>>();
+
+		assert_eq!(schedule(0), vec![None; 6]);
+		assert_eq!(schedule(3), vec![Some(0), Some(2), None, None, None, Some(1)]);
+		assert_eq!(schedule(6), vec![Some(0), Some(2), Some(4), Some(5), Some(3), Some(1)]);
+		// extra tickets are never assigned.
+		assert_eq!(schedule(10), schedule(6));
+		// slots outside of the epoch don't have a ticket.
+		assert_eq!(ticket_index(6, 6, 6), None);
+	}
+
+	#[test]
+	fn ticket_threshold_is_capped() {
+		assert_eq!(compute_ticket_threshold(1, 6, 0, 3), 0);
+		assert_eq!(compute_ticket_threshold(1, 6, 2, 3), TicketId::max_value());
+		assert_eq!(compute_ticket_threshold(1, 6, 4, 3), TicketId::max_value() / 12 * 6);
+	}
+}
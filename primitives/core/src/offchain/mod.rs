@@ -43,6 +43,23 @@ pub trait OffchainStorage: Clone + Send + Sync {
 	/// Retrieve a value from storage under given key and prefix.
 	fn get(&self, prefix: &[u8], key: &[u8]) -> Option<Vec<u8>>;
 
+	/// Retrieve up to `count` values under given prefix whose key starts with `key_prefix`.
+	///
+	/// Returned keys don't include `prefix`. Entries are sorted by key, starting after
+	/// `start_key` if it is given.
+	///
+	/// Not all storages can be iterated over. The default implementation returns an error.
+	fn iter_prefix(
+		&self,
+		prefix: &[u8],
+		key_prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: usize,
+	) -> Result<Vec<(Vec<u8>, Vec<u8>)>, IterationError> {
+		let _ = (prefix, key_prefix, start_key, count);
+		Err(IterationError)
+	}
+
 	/// Replace the value in storage if given old_value matches the current one.
 	///
 	/// Returns `true` if the value has been set and false otherwise.
@@ -55,6 +72,10 @@ pub trait OffchainStorage: Clone + Send + Sync {
 	) -> bool;
 }
 
+/// The offchain storage could not be iterated over.
+#[derive(Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub struct IterationError;
+
 /// A type of supported crypto.
 #[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, RuntimeDebug, PassByEnum)]
 #[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
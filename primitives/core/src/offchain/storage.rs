@@ -18,7 +18,7 @@
 //! In-memory implementation of offchain workers database.
 
 use std::collections::hash_map::{HashMap, Entry};
-use crate::offchain::OffchainStorage;
+use crate::offchain::{IterationError, OffchainStorage};
 use std::iter::Iterator;
 
 /// In-memory storage for offchain workers.
@@ -61,6 +61,24 @@ impl OffchainStorage for InMemOffchainStorage {
 		self.storage.get(&key).cloned()
 	}
 
+	fn iter_prefix(
+		&self,
+		prefix: &[u8],
+		key_prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: usize,
+	) -> Result<Vec<(Vec<u8>, Vec<u8>)>, IterationError> {
+		let full_prefix: Vec<u8> = prefix.iter().chain(key_prefix).cloned().collect();
+		let mut entries: Vec<_> = self.storage.iter()
+			.filter(|(key, _)| key.starts_with(&full_prefix))
+			.map(|(key, value)| (key[prefix.len()..].to_vec(), value.clone()))
+			.filter(|(key, _)| start_key.map_or(true, |start_key| &key[..] > start_key))
+			.collect();
+		entries.sort();
+		entries.truncate(count);
+		Ok(entries)
+	}
+
 	fn compare_and_set(
 		&mut self,
 		prefix: &[u8],
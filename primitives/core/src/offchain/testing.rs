@@ -35,6 +35,7 @@ use crate::offchain::{
 	OpaqueNetworkState,
 	TransactionPool,
 	OffchainStorage,
+	IterationError,
 };
 
 use parking_lot::RwLock;
@@ -101,6 +102,16 @@ impl OffchainStorage for TestPersistentOffchainDB {
 		self.persistent.read().get(prefix, key)
 	}
 
+	fn iter_prefix(
+		&self,
+		prefix: &[u8],
+		key_prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: usize,
+	) -> Result<Vec<(Vec<u8>, Vec<u8>)>, IterationError> {
+		self.persistent.read().iter_prefix(prefix, key_prefix, start_key, count)
+	}
+
 	fn compare_and_set(
 		&mut self,
 		prefix: &[u8],
@@ -57,4 +57,23 @@ impl<D: KeyValueDB, H: Clone> Database<H> for DbAdapter<D> {
 	fn lookup(&self, _hash: &H) -> Option<Vec<u8>> {
 		unimplemented!();
 	}
+
+	fn iter(&self, col: ColumnId, f: &mut dyn FnMut(&[u8], &[u8])) -> error::Result<()> {
+		for (key, value) in self.0.iter(col) {
+			f(&key, &value);
+		}
+		Ok(())
+	}
+
+	fn iter_with_prefix(
+		&self,
+		col: ColumnId,
+		prefix: &[u8],
+		f: &mut dyn FnMut(&[u8], &[u8]),
+	) -> error::Result<()> {
+		for (key, value) in self.0.iter_with_prefix(col, prefix) {
+			f(&key, &value);
+		}
+		Ok(())
+	}
 }
@@ -166,6 +166,37 @@ pub trait Database<H: Clone>: Send + Sync {
 		t.release(hash.clone());
 		self.commit(t)
 	}
+
+	/// Call `f` for every key and value stored in `col`, in no particular order.
+	///
+	/// This is meant for maintenance tasks, such as moving the data to another database, and
+	/// is not supported by all backends. The default implementation returns an error.
+	fn iter(&self, col: ColumnId, f: &mut dyn FnMut(&[u8], &[u8])) -> error::Result<()> {
+		let _ = (col, f);
+		Err(error::DatabaseError(Box::new(std::io::Error::new(
+			std::io::ErrorKind::Other,
+			"Iterating over a column is not supported by this database",
+		))))
+	}
+
+	/// Call `f` for every key starting with `prefix` stored in `col` and its value, in no
+	/// particular order.
+	///
+	/// Unlike `Database::iter`, only the entries under `prefix` are visited where the backend
+	/// supports it. This is not supported by all backends. The default implementation returns
+	/// an error.
+	fn iter_with_prefix(
+		&self,
+		col: ColumnId,
+		prefix: &[u8],
+		f: &mut dyn FnMut(&[u8], &[u8]),
+	) -> error::Result<()> {
+		let _ = (col, prefix, f);
+		Err(error::DatabaseError(Box::new(std::io::Error::new(
+			std::io::ErrorKind::Other,
+			"Iterating over a column is not supported by this database",
+		))))
+	}
 }
 
 impl<H> std::fmt::Debug for dyn Database<H> {
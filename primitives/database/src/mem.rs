@@ -52,6 +52,29 @@ impl<H> Database<H> for MemDb<H>
 		let s = self.0.read();
 		s.1.get(hash).cloned()
 	}
+
+	fn iter(&self, col: ColumnId, f: &mut dyn FnMut(&[u8], &[u8])) -> error::Result<()> {
+		let s = self.0.read();
+		for (key, value) in s.0.get(&col).into_iter().flatten() {
+			f(key, value);
+		}
+		Ok(())
+	}
+
+	fn iter_with_prefix(
+		&self,
+		col: ColumnId,
+		prefix: &[u8],
+		f: &mut dyn FnMut(&[u8], &[u8]),
+	) -> error::Result<()> {
+		let s = self.0.read();
+		for (key, value) in s.0.get(&col).into_iter().flatten() {
+			if key.starts_with(prefix) {
+				f(key, value);
+			}
+		}
+		Ok(())
+	}
 }
 
 impl<H> MemDb<H>
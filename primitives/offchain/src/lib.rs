@@ -23,6 +23,9 @@
 /// Re-export of parent module scope storage prefix.
 pub use sp_core::offchain::STORAGE_PREFIX as STORAGE_PREFIX;
 
+/// Prefix of the keys written through offchain indexing, in the persistent offchain storage.
+pub const INDEXING_PREFIX: &[u8] = b"storage/";
+
 sp_api::decl_runtime_apis! {
 	/// The offchain worker api.
 	#[api_version(2)]
@@ -490,6 +490,38 @@ impl pallet_babe::Trait for Runtime {
 	type HandleEquivocation = ();
 }
 
+/// A static Sassafras epoch with the genesis authorities, the test runtime
+/// doesn't rotate epochs nor collect tickets, see `sassafras_slot_ticket`.
+fn sassafras_epoch(epoch_index: u64) -> sp_consensus_sassafras::Epoch {
+	sp_consensus_sassafras::Epoch {
+		epoch_index,
+		start_slot: epoch_index * EpochDuration::get(),
+		duration: EpochDuration::get(),
+		authorities: system::authorities().into_iter().map(|a| {
+			let authority: sr25519::Public = a.into();
+			sp_consensus_sassafras::AuthorityId::from(authority)
+		}).collect(),
+		randomness: [0; 32],
+	}
+}
+
+/// A static Sassafras slot schedule, where every odd slot is assigned a ticket
+/// owned by another authority than the fallback author of the slot.
+fn sassafras_slot_ticket(
+	slot_number: sp_consensus_sassafras::SlotNumber,
+) -> Option<sp_consensus_sassafras::Ticket> {
+	let authorities_len = system::authorities().len() as u64;
+	if slot_number % 2 == 0 || authorities_len < 2 {
+		return None;
+	}
+
+	Some(sp_consensus_sassafras::Ticket {
+		id: slot_number.into(),
+		authority_index: ((slot_number + 1) % authorities_len) as u32,
+		attempt: 0,
+	})
+}
+
 /// Adds one to the given input and returns the final result.
 #[inline(never)]
 fn benchmark_add_one(i: u64) -> u64 {
@@ -700,6 +732,37 @@ cfg_if! {
 				}
 			}
 
+			impl sp_consensus_sassafras::SassafrasApi<Block> for Runtime {
+				fn configuration() -> sp_consensus_sassafras::SassafrasConfiguration {
+					sp_consensus_sassafras::SassafrasConfiguration {
+						slot_duration: 1000,
+						epoch_duration: EpochDuration::get(),
+						max_attempts: 0,
+						redundancy_factor: 1,
+					}
+				}
+
+				fn current_epoch() -> sp_consensus_sassafras::Epoch {
+					sassafras_epoch(0)
+				}
+
+				fn next_epoch() -> sp_consensus_sassafras::Epoch {
+					sassafras_epoch(1)
+				}
+
+				fn slot_ticket(
+					slot_number: sp_consensus_sassafras::SlotNumber,
+				) -> Option<sp_consensus_sassafras::Ticket> {
+					sassafras_slot_ticket(slot_number)
+				}
+
+				fn submit_tickets_unsigned_extrinsic(
+					_tickets: Vec<sp_consensus_sassafras::TicketEnvelope>,
+				) -> bool {
+					false
+				}
+			}
+
 			impl sp_consensus_babe::BabeApi<Block> for Runtime {
 				fn configuration() -> sp_consensus_babe::BabeGenesisConfiguration {
 					sp_consensus_babe::BabeGenesisConfiguration {
@@ -942,6 +1005,37 @@ cfg_if! {
 				}
 			}
 
+			impl sp_consensus_sassafras::SassafrasApi<Block> for Runtime {
+				fn configuration() -> sp_consensus_sassafras::SassafrasConfiguration {
+					sp_consensus_sassafras::SassafrasConfiguration {
+						slot_duration: 1000,
+						epoch_duration: EpochDuration::get(),
+						max_attempts: 0,
+						redundancy_factor: 1,
+					}
+				}
+
+				fn current_epoch() -> sp_consensus_sassafras::Epoch {
+					sassafras_epoch(0)
+				}
+
+				fn next_epoch() -> sp_consensus_sassafras::Epoch {
+					sassafras_epoch(1)
+				}
+
+				fn slot_ticket(
+					slot_number: sp_consensus_sassafras::SlotNumber,
+				) -> Option<sp_consensus_sassafras::Ticket> {
+					sassafras_slot_ticket(slot_number)
+				}
+
+				fn submit_tickets_unsigned_extrinsic(
+					_tickets: Vec<sp_consensus_sassafras::TicketEnvelope>,
+				) -> bool {
+					false
+				}
+			}
+
 			impl sp_consensus_babe::BabeApi<Block> for Runtime {
 				fn configuration() -> sp_consensus_babe::BabeGenesisConfiguration {
 					sp_consensus_babe::BabeGenesisConfiguration {
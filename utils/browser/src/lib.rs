@@ -58,6 +58,7 @@ where
 		allow_private_ipv4: true,
 		enable_mdns: false,
 		use_yamux_flow_control: true,
+		websocket_tls: None,
 	};
 
 	let config = Configuration {
@@ -87,6 +88,8 @@ where
 		offchain_worker: Default::default(),
 		prometheus_config: Default::default(),
 		pruning: Default::default(),
+		blocks_pruning: Default::default(),
+		offchain_index_pruning: Default::default(),
 		rpc_cors: Default::default(),
 		rpc_http: Default::default(),
 		rpc_ipc: Default::default(),
@@ -99,6 +102,9 @@ where
 		tracing_targets: Default::default(),
 		transaction_pool: Default::default(),
 		wasm_method: Default::default(),
+		wasm_runtime_cache_path: None,
+		instrument_host_functions: false,
+		native_wasm_check: None,
 		max_runtime_instances: 8,
 		announce_block: true,
 		base_path: None,